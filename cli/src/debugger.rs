@@ -102,6 +102,10 @@ impl DebugCli {
                             capstone::arch::riscv::ArchExtraMode::RiscVC,
                         ))
                         .build(),
+                    // Capstone doesn't support the Xtensa instruction set.
+                    InstructionSet::Xtensa => Err(capstone::Error::CustomError(
+                        "Xtensa is not supported by Capstone",
+                    )),
                 }
                 .map_err(|err| anyhow!("Error creating capstone: {:?}", err))?;
 
@@ -146,6 +150,10 @@ impl DebugCli {
                         .read_core_reg(pc_desc)?;
                     println!("Core halted at address {:#0width$x}", pc, width = pc_desc.format_hex_width());
 
+                    if let Some(security_state) = cli_data.core.security_state()? {
+                        println!("Security state: {:?}", security_state);
+                    }
+
                     // determine if the target is handling an interupt
 
                     if cli_data.core.architecture() == probe_rs::Architecture::Arm {
@@ -180,38 +188,26 @@ impl DebugCli {
                                             println!("Return address (LR): {:#010x}", return_address);
 
                                             // Get reason for hard fault
-                                            let hfsr = cli_data.core.read_word_32(0xE000_ED2C)?;
-
-                                            if hfsr & (1 << 30) == (1 << 30) {
-                                                println!("-> configurable priority exception has been escalated to hard fault!");
-
-
-                                                // read cfsr 
-                                                let cfsr = cli_data.core.read_word_32(0xE000_ED28)?;
-
-                                                let ufsr = (cfsr >> 16) & 0xffff;
-                                                let bfsr = (cfsr >> 8) & 0xff;
-                                                let mmfsr = cfsr & 0xff;
-
-
-                                                if ufsr != 0 {
-                                                    println!("\tUsage Fault     - UFSR: {:#06x}", ufsr);
+                                            if let Some(status) = cli_data.core.fault_status()? {
+                                                if status.escalated {
+                                                    println!("-> configurable priority exception has been escalated to hard fault!");
                                                 }
 
-                                                if bfsr != 0 {
-                                                    println!("\tBus Fault       - BFSR: {:#04x}", bfsr);
+                                                for fault in &status.faults {
+                                                    println!("\t{}", fault);
+                                                }
 
-                                                    if bfsr & (1 << 7) == (1 << 7) {
-                                                        // Read address from BFAR
-                                                        let bfar = cli_data.core.read_word_32(0xE000_ED38)?;
-                                                        println!("\t Location       - BFAR: {:#010x}", bfar);
-                                                    }
+                                                if let Some(address) = status.memory_fault_address {
+                                                    println!("\t Location       - MMFAR: {:#010x}", address);
                                                 }
 
-                                                if mmfsr != 0 {
-                                                    println!("\tMemManage Fault - BFSR: {:04x}", bfsr);
+                                                if let Some(address) = status.bus_fault_address {
+                                                    println!("\t Location       - BFAR: {:#010x}", address);
                                                 }
 
+                                                if let Some(address) = status.secure_fault_address {
+                                                    println!("\t Location       - SFAR: {:#010x}", address);
+                                                }
                                             }
                                         }
                                     }
@@ -543,6 +539,16 @@ impl DebugCli {
 
                             println!();
                         }
+
+                        if let Some(registers) = register_file.fpu_double_registers() {
+                            for register in registers {
+                                let value: u64 = cli_data.core.read_core_reg(register)?;
+
+                                println!("{:10}: {:#018x}", register.name(), value);
+                                println!("{:>10}: {:#}", "[as f64]", f64::from_bits(value));
+                                println!();
+                            }
+                        }
                     } else {
                         println!("Core has no floating point registers");
                     }
@@ -0,0 +1,152 @@
+use std::error::Error;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use probe_rs::{
+    config::TargetSelector,
+    flashing::{download_to_probes, DownloadOptions, Format, ProgressEvent},
+    DebugProbeSelector, Probe,
+};
+use probe_rs_cli_util::{
+    common_options::ProbeOptions,
+    indicatif::{MultiProgress, ProgressBar, ProgressStyle},
+};
+
+/// Flash `path` to the targets of all `selectors`, or of all connected probes if none are given.
+pub(crate) fn download_parallel(
+    common: &ProbeOptions,
+    selectors: &[DebugProbeSelector],
+    path: &Path,
+    format: Format,
+    chip_erase: bool,
+) -> Result<()> {
+    let target = match common.get_target_selector()? {
+        TargetSelector::Specified(target) => target,
+        _ => {
+            return Err(anyhow!(
+                "Flashing multiple probes requires a chip, use '--chip'"
+            ))
+        }
+    };
+
+    let selectors: Vec<DebugProbeSelector> = if selectors.is_empty() {
+        Probe::list_all().into_iter().map(Into::into).collect()
+    } else {
+        selectors.to_vec()
+    };
+
+    if selectors.is_empty() {
+        return Err(anyhow!("No connected probes were found."));
+    }
+
+    let mut probes = Vec::new();
+    for selector in &selectors {
+        let mut probe = Probe::open(selector.clone())
+            .with_context(|| format!("Failed to open the probe {}", selector))?;
+        common.configure_probe(&mut probe)?;
+        probes.push(probe);
+    }
+
+    let mut loader = target.flash_loader();
+    loader
+        .load_file(path, format, &target)
+        .with_context(|| format!("Failed to load {}", path.display()))?;
+
+    // One progress bar per device, which shows the running operation.
+    let multi_progress = MultiProgress::new();
+    let style = ProgressStyle::default_bar()
+        .template("{prefix:30} {msg:12} [{wide_bar}] {bytes:>8}/{total_bytes:>8}")
+        .expect("Error in progress bar creation. This is a bug, please report it.")
+        .progress_chars("##-");
+
+    let bars: Vec<ProgressBar> = selectors
+        .iter()
+        .map(|selector| {
+            let bar = multi_progress.add(ProgressBar::new(0));
+            bar.set_style(style.clone());
+            bar.set_prefix(selector.to_string());
+            bar.set_message("Attaching");
+            bar.enable_steady_tick(Duration::from_millis(100));
+            bar
+        })
+        .collect();
+
+    let progress_bars = bars.clone();
+    let dry_run = common.dry_run;
+
+    let summary = download_to_probes(
+        probes,
+        target,
+        common.permissions(),
+        loader,
+        move || {
+            let mut options = DownloadOptions::new();
+            options.dry_run = dry_run;
+            options.do_chip_erase = chip_erase;
+            options
+        },
+        move |index, event| {
+            let bar = &progress_bars[index];
+
+            match event {
+                ProgressEvent::Progress(report) => {
+                    bar.set_message(format!("{:?}", report.operation));
+                    bar.set_length(report.bytes_total);
+                    bar.set_position(report.bytes_done);
+                }
+                ProgressEvent::FinishedProgramming => bar.set_message("Programmed"),
+                ProgressEvent::FailedProgramming => bar.set_message("Failed"),
+                _ => {}
+            }
+        },
+    );
+
+    for (bar, device) in bars.iter().zip(&summary.devices) {
+        match &device.result {
+            Ok(()) => bar.finish_with_message("Done"),
+            Err(_) => bar.abandon_with_message("Failed"),
+        }
+    }
+
+    println!();
+    for device in &summary.devices {
+        let selector = device
+            .selector
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+
+        match &device.result {
+            Ok(()) => println!(
+                "{:30} {:20} OK     {:.1}s",
+                selector,
+                device.probe,
+                device.duration.as_secs_f32()
+            ),
+            Err(error) => {
+                let mut message = error.to_string();
+                let mut source = error.source();
+                while let Some(error) = source {
+                    message.push_str(&format!(": {}", error));
+                    source = error.source();
+                }
+
+                println!("{:30} {:20} FAILED {}", selector, device.probe, message)
+            }
+        }
+    }
+
+    println!(
+        "\n{} of {} devices flashed in {:.1}s",
+        summary.succeeded(),
+        summary.devices.len(),
+        summary.duration.as_secs_f32()
+    );
+
+    if !summary.is_success() {
+        return Err(anyhow!("Flashing failed for {} devices", summary.failed()));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use std::{fs::File, io::Write, path::Path, time::Instant};
+
+use anyhow::{bail, Context, Result};
+use ihex::Record;
+use probe_rs::flashing::{read_flash, read_image};
+use probe_rs_cli_util::common_options::ProbeOptions;
+
+/// The file formats the memory can be dumped to.
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub(crate) enum DumpFileType {
+    Bin,
+    Hex,
+}
+
+/// Dump the memory in `range`, or the whole flash if no range is given, into the file at `path`.
+pub(crate) fn dump_flash(
+    common: &ProbeOptions,
+    path: &Path,
+    format: DumpFileType,
+    range: Option<(u64, u64)>,
+) -> Result<()> {
+    let mut session = common.simple_attach()?;
+
+    let instant = Instant::now();
+
+    let chunks = match range {
+        Some((address, size)) => {
+            vec![(address, read_image(&mut session, address..address + size)?)]
+        }
+        None => read_flash(&mut session)?,
+    };
+
+    let elapsed = instant.elapsed();
+
+    let mut file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+
+    match format {
+        DumpFileType::Bin => {
+            if chunks.len() > 1 {
+                bail!("The flash consists of multiple regions, which can't be stored in a binary file. Use the 'hex' format or give the range to dump.");
+            }
+
+            for (_, data) in &chunks {
+                file.write_all(data)?;
+            }
+        }
+        DumpFileType::Hex => file.write_all(hex_file(&chunks)?.as_bytes())?,
+    }
+
+    let size: usize = chunks.iter().map(|(_, data)| data.len()).sum();
+
+    println!("Read {} bytes in {:?}", size, elapsed);
+
+    Ok(())
+}
+
+/// Create an Intel HEX file with the data of the chunks.
+fn hex_file(chunks: &[(u64, Vec<u8>)]) -> Result<String> {
+    let mut records = Vec::new();
+    let mut upper_address = None;
+
+    for (address, data) in chunks {
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let record_address = address + offset as u64;
+
+            if record_address > u32::MAX as u64 {
+                bail!(
+                    "The address {:#x} can't be stored in an Intel HEX file",
+                    record_address
+                );
+            }
+
+            let upper = (record_address >> 16) as u16;
+            if upper_address != Some(upper) {
+                records.push(Record::ExtendedLinearAddress(upper));
+                upper_address = Some(upper);
+            }
+
+            // The records are aligned to 16 bytes, so they never cross a 64 KiB boundary.
+            let len = (16 - (record_address % 16) as usize).min(data.len() - offset);
+
+            records.push(Record::Data {
+                offset: record_address as u16,
+                value: data[offset..offset + len].to_vec(),
+            });
+
+            offset += len;
+        }
+    }
+
+    records.push(Record::EndOfFile);
+
+    Ok(ihex::create_object_file_representation(&records)?)
+}
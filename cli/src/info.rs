@@ -5,13 +5,13 @@ use probe_rs::{
         arm::{
             ap::{GenericAp, MemoryAp},
             armv6m::Demcr,
-            memory::Component,
+            memory::{Component, CoresightComponent},
             sequences::DefaultArmSequence,
             ApAddress, ApInformation, ArmProbeInterface, DpAddress, MemoryApInformation,
         },
         riscv::communication_interface::RiscvCommunicationInterface,
     },
-    MemoryMappedRegister, Probe, WireProtocol,
+    MemoryMappedRegister, Probe, ProbeCapabilities, WireProtocol,
 };
 
 use anyhow::Result;
@@ -20,10 +20,19 @@ use probe_rs_cli_util::common_options::ProbeOptions;
 pub(crate) fn show_info_of_device(common: &ProbeOptions) -> Result<()> {
     let mut probe = common.attach_probe()?;
 
+    let capabilities = probe.capabilities();
+    show_probe_capabilities(&capabilities);
+
     let protocols = if let Some(protocol) = common.protocol {
         vec![protocol]
     } else {
-        vec![WireProtocol::Jtag, WireProtocol::Swd]
+        // Skip protocols which the probe is known not to support.
+        [WireProtocol::Jtag, WireProtocol::Swd]
+            .into_iter()
+            .filter(|protocol| {
+                capabilities.protocols.is_empty() || capabilities.supports_protocol(*protocol)
+            })
+            .collect()
     };
 
     for protocol in protocols {
@@ -45,6 +54,27 @@ pub(crate) fn show_info_of_device(common: &ProbeOptions) -> Result<()> {
     Ok(())
 }
 
+fn show_probe_capabilities(capabilities: &ProbeCapabilities) {
+    println!("Probe capabilities:");
+
+    let protocols: Vec<_> = capabilities
+        .protocols
+        .iter()
+        .map(|protocol| protocol.to_string())
+        .collect();
+    println!("\tProtocols:      {}", protocols.join(", "));
+
+    if let Some(max_speed_khz) = capabilities.max_speed_khz {
+        println!("\tMax. speed:     {} kHz", max_speed_khz);
+    }
+
+    println!("\tSWO modes:      {:?}", capabilities.swo_modes);
+    println!("\tTarget voltage: {}", capabilities.target_voltage);
+    println!("\tTarget power:   {}", capabilities.target_power);
+    println!("\tUART:           {}", capabilities.uart);
+    println!();
+}
+
 fn try_show_info(
     mut probe: Probe,
     protocol: WireProtocol,
@@ -149,21 +179,48 @@ fn show_arm_info(interface: &mut Box<dyn ArmProbeInterface>) -> Result<()> {
                 demcr.set_dwtena(true);
                 memory.write_word_32(Demcr::ADDRESS, demcr.into())?;
 
-                let component_table = Component::try_parse(&mut memory, base_address);
-
-                component_table
-                    .iter()
-                    .for_each(|entry| println!("{:#08x?}", entry));
-
-                // let mut reader = crate::memory::romtable::RomTableReader::new(&link_ref, baseaddr as u64);
-
-                // for e in reader.entries() {
-                //     if let Ok(e) = e {
-                //         println!("ROM Table Entry: Component @ 0x{:08x}", e.component_addr());
-                //     }
-                // }
+                let component = match Component::try_parse(&mut memory, base_address) {
+                    Ok(component) => CoresightComponent::new(component, access_port),
+                    Err(e) => {
+                        println!("Failed to read the ROM table of AP {}: {}", ap_index, e);
+                        continue;
+                    }
+                };
+
+                println!("AP {}:", ap_index);
+
+                for component in component.iter() {
+                    let id = component.component.id();
+                    let peripheral_id = id.peripheral_id();
+
+                    let name = peripheral_id
+                        .determine_part()
+                        .map(|part| part.name())
+                        .unwrap_or("Unknown component");
+
+                    println!(
+                        "\t{:#010x}: {} ({:?}, designer: {}, part: {:#05x}, CIDR: {:#010x}, PIDR: {:#012x})",
+                        id.component_address(),
+                        name,
+                        id.class(),
+                        peripheral_id.designer().unwrap_or("unknown"),
+                        peripheral_id.part(),
+                        id.cidr(),
+                        peripheral_id.pidr(),
+                    );
+                }
+            }
+            ApInformation::Other { idr, .. } => {
+                let jep106 = jep106::JEP106Code::new(
+                    ((idr.DESIGNER >> 7) & 0xf) as u8,
+                    (idr.DESIGNER & 0x7f) as u8,
+                );
+
+                println!(
+                    "AP {}: Unknown type of access port (designer: {}, class: {:?}, type: {:#x}, variant: {:#x}, revision: {:#x})",
+                    ap_index, jep106, idr.CLASS, idr.TYPE as u8, idr.VARIANT, idr.REVISION
+                );
             }
-            ApInformation::Other { .. } => println!("Unknown Type of access port"),
         }
     }
 
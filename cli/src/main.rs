@@ -1,7 +1,11 @@
 mod common;
 mod debugger;
+mod download_parallel;
+mod dump_flash;
 mod gdb;
 mod info;
+mod option_bytes;
+mod profile;
 mod run;
 mod trace;
 
@@ -10,14 +14,17 @@ use debugger::CliState;
 use probe_rs::{
     architecture::arm::{component::TraceSink, swo::SwoConfig},
     debug::debug_info::DebugInfo,
-    flashing::{erase_all, BinOptions, FileDownloadError, Format},
-    MemoryInterface, Probe,
+    flashing::{erase_all, erase_range, BinOptions, ElfAddress, ElfOptions, Format},
+    DebugProbeSelector, MemoryInterface, Probe, RemoteProbeServer,
 };
 
 use probe_rs_cli_util::{
     clap,
     clap::Parser,
-    common_options::{print_chip_info, print_families, CargoOptions, FlashOptions, ProbeOptions},
+    common_options::{
+        parse_u32, parse_u64, print_chip_info, print_families, CargoOptions, FlashOptions,
+        ProbeOptions,
+    },
     flash::run_flash_download,
 };
 
@@ -25,9 +32,9 @@ use rustyline::Editor;
 
 use anyhow::{Context, Result};
 
-use std::{fs::File, path::PathBuf};
+use std::path::Path;
 use std::{io, time::Instant};
-use std::{num::ParseIntError, path::Path};
+use std::{net::TcpListener, path::PathBuf};
 
 #[derive(clap::Parser)]
 #[clap(
@@ -98,6 +105,28 @@ enum Cli {
         #[clap(value_parser = parse_u32)]
         words: u32,
     },
+    /// Dump the flash, or a memory range, of the attached target into a file
+    #[clap(name = "dump-flash")]
+    DumpFlash {
+        #[clap(flatten)]
+        common: ProbeOptions,
+
+        /// The path of the file to write.
+        #[clap(value_parser)]
+        path: PathBuf,
+
+        /// Format of the file. Possible values are case-insensitive.
+        #[clap(value_enum, ignore_case = true, default_value = "bin", long)]
+        format: dump_flash::DumpFileType,
+
+        /// The address of the memory to dump. If it is not given, all flash regions are dumped.
+        #[clap(long, value_parser = parse_u64, requires = "size")]
+        address: Option<u64>,
+
+        /// The number of bytes to dump.
+        #[clap(long, value_parser = parse_u64, requires = "address")]
+        size: Option<u64>,
+    },
     /// Download memory to attached target
     Download {
         #[clap(flatten)]
@@ -114,9 +143,33 @@ enum Cli {
         #[clap(long, value_parser = parse_u32)]
         skip_bytes: Option<u32>,
 
+        /// Flash the ELF segments to their virtual addresses (VMA) instead of their physical addresses (LMA).
+        #[clap(long)]
+        elf_virtual_addresses: bool,
+        /// Only flash the ELF sections with these names. Can be given multiple times.
+        #[clap(long = "section")]
+        sections: Vec<String>,
+        /// Don't flash the ELF sections with these names. Can be given multiple times.
+        #[clap(long = "skip-section")]
+        skipped_sections: Vec<String>,
+
         /// The path to the file to be downloaded to the flash
         path: String,
 
+        /// Additional files which are flashed together with the file, e.g. a bootloader or a file system image.
+        ///
+        /// The format is detected from the file extension ('.hex', '.uf2', '.bin', otherwise ELF).
+        /// Binary files need the address at which they are flashed, e.g. 'fs.bin@0x08080000'.
+        #[clap(long = "add", value_parser = parse_image)]
+        additional_files: Vec<(PathBuf, Format)>,
+
+        /// Overwrite data of the image before flashing, e.g. a serial number or a MAC address.
+        ///
+        /// The data is given as hex bytes in memory order, at an address or at an ELF symbol,
+        /// e.g. '0x0800f000=0011223344' or 'SERIAL_NUMBER=78563412'. Can be given multiple times.
+        #[clap(long = "patch", value_parser = parse_patch)]
+        patches: Vec<Patch>,
+
         /// Whether to erase the entire chip before downloading
         #[clap(long)]
         chip_erase: bool,
@@ -129,10 +182,43 @@ enum Cli {
         #[clap(long = "disable-double-buffering")]
         disable_double_buffering: bool,
     },
+    /// Download a file to the targets of multiple probes at the same time
+    ///
+    /// All probes are attached to the chip given with '--chip', and flashed in parallel.
+    /// A summary of all devices is printed at the end.
+    #[clap(name = "download-parallel")]
+    DownloadParallel {
+        #[clap(flatten)]
+        common: ProbeOptions,
+
+        /// The probes to use, e.g. 'VID:PID:SERIAL'. Can be given multiple times.
+        /// If no probe is given, all connected probes are used.
+        #[clap(long = "probes", value_parser)]
+        probes: Vec<DebugProbeSelector>,
+
+        /// The path to the file to be downloaded to the flash.
+        ///
+        /// The format is detected from the file extension ('.hex', '.uf2', '.bin', otherwise ELF).
+        /// Binary files need the address at which they are flashed, e.g. 'firmware.bin@0x08000000'.
+        #[clap(value_parser = parse_image)]
+        image: (PathBuf, Format),
+
+        /// Whether to erase the entire chip before downloading
+        #[clap(long)]
+        chip_erase: bool,
+    },
     /// Erase all nonvolatile memory of attached target
     Erase {
         #[clap(flatten)]
         common: ProbeOptions,
+
+        /// The start address of the memory to erase. If it is not given, all nonvolatile memory is erased.
+        #[clap(long, value_parser = parse_u64, requires = "end")]
+        start: Option<u64>,
+
+        /// The end address of the memory to erase, exclusive. It has to be on a sector boundary.
+        #[clap(long, value_parser = parse_u64, requires = "start")]
+        end: Option<u64>,
     },
     /// Flash and run an ELF program
     #[clap(name = "run")]
@@ -150,6 +236,18 @@ enum Cli {
         /// Disable double-buffering when downloading flash.  If downloading times out, try this option.
         #[clap(long = "disable-double-buffering")]
         disable_double_buffering: bool,
+
+        /// Load the ELF file into the RAM and run it from there, without touching the flash.
+        ///
+        /// The ELF file has to be linked for the RAM. On Cortex-M cores, the vector table has to be at its lowest address.
+        #[clap(long)]
+        ram: bool,
+
+        /// Show the RTT output of another core of a multi-core target, given as the core index and the ELF file of its firmware, e.g. '1=net.elf'.
+        ///
+        /// The firmware of the other core is not flashed. The RTT output is prefixed with the name of the core. Can be given multiple times.
+        #[clap(long = "rtt-core", value_parser = parse_rtt_core)]
+        rtt_cores: Vec<(usize, PathBuf)>,
     },
     /// Trace a memory location on the target
     #[clap(name = "trace")]
@@ -182,8 +280,59 @@ enum Cli {
         #[clap(subcommand)]
         source: ItmSource,
     },
+    /// Profile the running application by periodically sampling the program counter.
+    ///
+    /// The samples are written in the folded stack format, which can be turned
+    /// into a flamegraph using tools like `inferno-flamegraph`.
+    #[clap(name = "profile")]
+    Profile {
+        #[clap(flatten)]
+        shared: CoreOptions,
+
+        #[clap(flatten)]
+        common: ProbeOptions,
+
+        /// The path to the ELF file of the running application.
+        path: PathBuf,
+
+        #[clap(value_parser = parse_u64)]
+        duration_ms: u64,
+
+        /// The number of core clock cycles between two samples.
+        #[clap(long, default_value = "4096")]
+        interval: u32,
+
+        /// Write the folded stacks to this file instead of stdout.
+        #[clap(long)]
+        output_file: Option<String>,
+
+        #[clap(subcommand)]
+        source: ItmSource,
+    },
+    /// Read and program the option bytes of the target
+    #[clap(name = "option-bytes")]
+    OptionBytes {
+        #[clap(flatten)]
+        common: ProbeOptions,
+
+        #[clap(subcommand)]
+        command: option_bytes::OptionBytesCommand,
+    },
     #[clap(subcommand)]
     Chip(Chip),
+    /// Share the selected debug probe over the network
+    ///
+    /// The probe can then be used on another machine with the '--remote' option.
+    /// Multiple clients can use the probe at the same time, e.g. a GDB server and an RTT logger.
+    /// The connection is not authenticated, so only listen on trusted networks.
+    Serve {
+        #[clap(flatten)]
+        common: ProbeOptions,
+
+        /// The address to listen on.
+        #[clap(long, default_value = "127.0.0.1:1338")]
+        address: String,
+    },
 }
 
 #[derive(clap::Parser)]
@@ -225,6 +374,15 @@ pub(crate) enum ItmSource {
     },
 }
 
+impl From<ItmSource> for TraceSink {
+    fn from(source: ItmSource) -> Self {
+        match source {
+            ItmSource::TraceMemory => TraceSink::TraceMemory,
+            ItmSource::Swo { clk, baud } => TraceSink::Swo(SwoConfig::new(clk).set_baud(baud)),
+        }
+    }
+}
+
 fn main() -> Result<()> {
     // Initialize the logging backend.
     pretty_env_logger::init();
@@ -255,19 +413,45 @@ fn main() -> Result<()> {
             loc,
             words,
         } => dump_memory(&shared, &common, loc, words),
+        Cli::DumpFlash {
+            common,
+            path,
+            format,
+            address,
+            size,
+        } => dump_flash::dump_flash(&common, &path, format, address.zip(size)),
         Cli::Download {
             common,
             format,
             base_address,
             skip_bytes,
+            elf_virtual_addresses,
+            sections,
+            skipped_sections,
             path,
+            additional_files,
+            patches,
             chip_erase,
             disable_progressbars,
             disable_double_buffering,
         } => download_program_fast(
             common,
-            format.into(base_address, skip_bytes),
+            format.into(
+                base_address,
+                skip_bytes,
+                ElfOptions {
+                    address: if elf_virtual_addresses {
+                        ElfAddress::Virtual
+                    } else {
+                        ElfAddress::Physical
+                    },
+                    sections,
+                    skipped_sections,
+                },
+            ),
             &path,
+            &additional_files,
+            &patches,
             chip_erase,
             disable_progressbars,
             disable_double_buffering,
@@ -277,8 +461,32 @@ fn main() -> Result<()> {
             path,
             chip_erase,
             disable_double_buffering,
-        } => run::run(common, &path, chip_erase, disable_double_buffering),
-        Cli::Erase { common } => erase(&common),
+            ram,
+            rtt_cores,
+        } => {
+            let exit_code = run::run(
+                common,
+                &path,
+                chip_erase,
+                disable_double_buffering,
+                ram,
+                &rtt_cores,
+            )?;
+
+            // Only exit once the session was dropped, so the probe is detached from the target.
+            if let Some(exit_code) = exit_code {
+                std::process::exit(exit_code);
+            }
+
+            Ok(())
+        }
+        Cli::DownloadParallel {
+            common,
+            probes,
+            image: (path, format),
+            chip_erase,
+        } => download_parallel::download_parallel(&common, &probes, &path, format, chip_erase),
+        Cli::Erase { common, start, end } => erase(&common, start.zip(end)),
         Cli::Trace {
             shared,
             common,
@@ -290,24 +498,50 @@ fn main() -> Result<()> {
             duration_ms,
             source,
             output_file,
-        } => {
-            let sink = match source {
-                ItmSource::TraceMemory => TraceSink::TraceMemory,
-                ItmSource::Swo { clk, baud } => TraceSink::Swo(SwoConfig::new(clk).set_baud(baud)),
-            };
-            trace::itm_trace(
-                &shared,
-                &common,
-                sink,
-                std::time::Duration::from_millis(duration_ms),
-                output_file,
-            )
-        }
+        } => trace::itm_trace(
+            &shared,
+            &common,
+            source.into(),
+            std::time::Duration::from_millis(duration_ms),
+            output_file,
+        ),
+        Cli::Profile {
+            shared,
+            common,
+            path,
+            duration_ms,
+            interval,
+            output_file,
+            source,
+        } => profile::profile(
+            &shared,
+            &common,
+            source.into(),
+            &path,
+            interval,
+            std::time::Duration::from_millis(duration_ms),
+            output_file,
+        ),
+        Cli::OptionBytes { common, command } => option_bytes::option_bytes(&common, command),
         Cli::Chip(Chip::List) => print_families(io::stdout()).map_err(Into::into),
         Cli::Chip(Chip::Info { name }) => print_chip_info(name, io::stdout()),
+        Cli::Serve { common, address } => serve_probe(&common, &address),
     }
 }
 
+fn serve_probe(common: &ProbeOptions, address: &str) -> Result<()> {
+    let probe = common.attach_probe()?;
+
+    let listener =
+        TcpListener::bind(address).with_context(|| format!("Failed to listen on {}", address))?;
+
+    println!("Serving {} on {}", probe.get_name(), listener.local_addr()?);
+
+    RemoteProbeServer::new(probe).serve(&listener)?;
+
+    Ok(())
+}
+
 fn list_connected_devices() -> Result<()> {
     let links = Probe::list_all();
 
@@ -363,24 +597,33 @@ fn download_program_fast(
     common: ProbeOptions,
     format: Format,
     path: &str,
+    additional_files: &[(PathBuf, Format)],
+    patches: &[Patch],
     do_chip_erase: bool,
     disable_progressbars: bool,
     disable_double_buffering: bool,
 ) -> Result<()> {
     let mut session = common.simple_attach()?;
 
-    let mut file = match File::open(path) {
-        Ok(file) => file,
-        Err(e) => return Err(FileDownloadError::IO(e)).context("Failed to open binary file."),
-    };
-
     let mut loader = session.target().flash_loader();
 
-    match format {
-        Format::Bin(options) => loader.load_bin_data(&mut file, options),
-        Format::Elf => loader.load_elf_data(&mut file),
-        Format::Hex => loader.load_hex_data(&mut file),
-    }?;
+    loader
+        .load_file(Path::new(path), format, session.target())
+        .with_context(|| format!("Failed to load {}", path))?;
+
+    for (additional_path, format) in additional_files {
+        loader
+            .load_file(additional_path, format.clone(), session.target())
+            .with_context(|| format!("Failed to load {}", additional_path.display()))?;
+    }
+
+    for patch in patches {
+        match &patch.location {
+            PatchLocation::Address(address) => loader.patch(*address, &patch.data),
+            PatchLocation::Symbol(symbol) => loader.patch_symbol(symbol, &patch.data),
+        }
+        .with_context(|| format!("Failed to patch {}", patch.location))?;
+    }
 
     run_flash_download(
         &mut session,
@@ -391,6 +634,12 @@ fn download_program_fast(
             list_probes: false,
             disable_progressbars,
             disable_double_buffering,
+            skip_unchanged_sectors: false,
+            preserve: Vec::new(),
+            bank: None,
+            swap_banks: false,
+            verification_manifest: None,
+            resume: None,
             reset_halt: false,
             log: None,
             restore_unwritten: false,
@@ -407,10 +656,13 @@ fn download_program_fast(
     Ok(())
 }
 
-fn erase(common: &ProbeOptions) -> Result<()> {
+fn erase(common: &ProbeOptions, range: Option<(u64, u64)>) -> Result<()> {
     let mut session = common.simple_attach()?;
 
-    erase_all(&mut session)?;
+    match range {
+        Some((start, end)) => erase_range(&mut session, start, end)?,
+        None => erase_all(&mut session)?,
+    }
 
     Ok(())
 }
@@ -529,13 +781,15 @@ enum DownloadFileType {
     Elf,
     Hex,
     Bin,
+    Uf2,
 }
 
 impl DownloadFileType {
-    fn into(self, base_address: Option<u64>, skip: Option<u32>) -> Format {
+    fn into(self, base_address: Option<u64>, skip: Option<u32>, elf: ElfOptions) -> Format {
         match self {
-            DownloadFileType::Elf => Format::Elf,
+            DownloadFileType::Elf => Format::Elf(elf),
             DownloadFileType::Hex => Format::Hex,
+            DownloadFileType::Uf2 => Format::Uf2,
             DownloadFileType::Bin => Format::Bin(BinOptions {
                 base_address,
                 skip: skip.unwrap_or(0),
@@ -544,10 +798,113 @@ impl DownloadFileType {
     }
 }
 
-fn parse_u32(input: &str) -> Result<u32, ParseIntError> {
-    parse_int::parse(input)
+/// Parse an additional file for the download command, e.g. 'fs.bin@0x08080000'.
+fn parse_image(input: &str) -> Result<(PathBuf, Format), String> {
+    let (path, base_address) = match input.rsplit_once('@') {
+        Some((path, address)) => {
+            let address =
+                parse_u64(address).map_err(|e| format!("Invalid address '{}': {}", address, e))?;
+
+            (PathBuf::from(path), Some(address))
+        }
+        None => (PathBuf::from(input), None),
+    };
+
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+
+    let format = match extension.as_deref() {
+        Some("bin") if base_address.is_none() => {
+            return Err(format!(
+                "The address of the binary file is missing, e.g. '{}@0x08000000'",
+                input
+            ))
+        }
+        Some("bin") => Format::Bin(BinOptions {
+            base_address,
+            skip: 0,
+        }),
+        _ if base_address.is_some() => {
+            return Err("An address can only be given for binary files".to_string())
+        }
+        Some("hex") => Format::Hex,
+        Some("uf2") => Format::Uf2,
+        _ => Format::Elf(ElfOptions::default()),
+    };
+
+    Ok((path, format))
+}
+
+/// Where the data of a patch is written to.
+#[derive(Debug, Clone)]
+enum PatchLocation {
+    Address(u64),
+    Symbol(String),
+}
+
+impl std::fmt::Display for PatchLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchLocation::Address(address) => write!(f, "{:#010x}", address),
+            PatchLocation::Symbol(symbol) => write!(f, "'{}'", symbol),
+        }
+    }
+}
+
+/// Data which overwrites the data of the image.
+#[derive(Debug, Clone)]
+struct Patch {
+    location: PatchLocation,
+    data: Vec<u8>,
 }
 
-fn parse_u64(input: &str) -> Result<u64, ParseIntError> {
-    parse_int::parse(input)
+/// Parse a patch for the download command, e.g. '0x0800f000=0011223344' or 'SERIAL_NUMBER=78563412'.
+fn parse_patch(input: &str) -> Result<Patch, String> {
+    let (location, data) = input.split_once('=').ok_or_else(|| {
+        format!(
+            "The data of the patch is missing, e.g. '{}=0011223344'",
+            input
+        )
+    })?;
+
+    let location = if location.starts_with(|c: char| c.is_ascii_digit()) {
+        PatchLocation::Address(
+            parse_u64(location).map_err(|e| format!("Invalid address '{}': {}", location, e))?,
+        )
+    } else {
+        PatchLocation::Symbol(location.to_owned())
+    };
+
+    if data.is_empty() || data.len() % 2 != 0 || !data.is_ascii() {
+        return Err(format!(
+            "The data '{}' has to consist of hex bytes, e.g. '0011223344'",
+            data
+        ));
+    }
+
+    let data = (0..data.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&data[i..i + 2], 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid data '{}': {}", data, e))?;
+
+    Ok(Patch { location, data })
+}
+
+/// Parse the core and the ELF file for RTT on another core, e.g. '1=net.elf'.
+fn parse_rtt_core(input: &str) -> Result<(usize, PathBuf), String> {
+    let (core, path) = input.split_once('=').ok_or_else(|| {
+        format!(
+            "The ELF file of the core is missing, e.g. '{}=net.elf'",
+            input
+        )
+    })?;
+
+    let core = core
+        .parse()
+        .map_err(|e| format!("Invalid core index '{}': {}", core, e))?;
+
+    Ok((core, PathBuf::from(path)))
 }
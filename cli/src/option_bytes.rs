@@ -0,0 +1,125 @@
+use std::ops::Range;
+
+use anyhow::{anyhow, Result};
+use probe_rs::flashing::{program_option_bytes, read_option_bytes, OptionBytes, ReadoutProtection};
+use probe_rs_cli_util::common_options::{parse_range, ProbeOptions};
+
+#[derive(clap::Subcommand)]
+pub(crate) enum OptionBytesCommand {
+    /// Print the current option bytes.
+    #[clap(name = "read")]
+    Read,
+    /// Modify the option bytes and program them.
+    ///
+    /// This resets the target. Returning to readout protection level 0 erases the flash,
+    /// and requires the '--allow-erase-all' option.
+    #[clap(name = "write")]
+    Write {
+        /// Set the field to the value, e.g. 'BOR_LEV=2'. Can be given multiple times.
+        #[clap(long = "set", value_parser = parse_assignment)]
+        fields: Vec<(String, u32)>,
+
+        /// Set the readout protection level.
+        #[clap(value_enum, ignore_case = true, long)]
+        rdp: Option<RdpLevel>,
+
+        /// Write protect the flash sectors in the address range, e.g. '0x08000000..0x08004000'.
+        /// Can be given multiple times.
+        #[clap(long, value_parser = parse_range)]
+        protect: Vec<Range<u64>>,
+
+        /// Remove the write protection of the flash sectors in the address range.
+        /// Can be given multiple times.
+        #[clap(long, value_parser = parse_range)]
+        unprotect: Vec<Range<u64>>,
+    },
+}
+
+/// The readout protection levels which can be set.
+///
+/// Level 2 is not supported, because it permanently disables debugging.
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub(crate) enum RdpLevel {
+    Level0,
+    Level1,
+}
+
+impl From<RdpLevel> for ReadoutProtection {
+    fn from(level: RdpLevel) -> Self {
+        match level {
+            RdpLevel::Level0 => ReadoutProtection::Level0,
+            RdpLevel::Level1 => ReadoutProtection::Level1,
+        }
+    }
+}
+
+fn parse_assignment(input: &str) -> Result<(String, u32)> {
+    let (name, value) = input
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Expected an assignment like 'FIELD=VALUE'"))?;
+
+    Ok((name.trim().to_string(), parse_int::parse(value.trim())?))
+}
+
+fn print_option_bytes(option_bytes: &OptionBytes) -> Result<()> {
+    for (register, value) in option_bytes.registers().iter().zip(option_bytes.raw()) {
+        println!("{}: {:#010x}", register.name, value);
+    }
+
+    println!(
+        "Readout protection: {:?}",
+        option_bytes.readout_protection()
+    );
+
+    for range in option_bytes.write_protected_ranges()? {
+        println!(
+            "Write protected: {:#010x}..{:#010x}",
+            range.start, range.end
+        );
+    }
+
+    for (field, value) in option_bytes.fields() {
+        println!("  {:<16} {:#x}", field.name, value);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn option_bytes(common: &ProbeOptions, command: OptionBytesCommand) -> Result<()> {
+    let mut session = common.simple_attach()?;
+
+    let mut option_bytes = read_option_bytes(&mut session)?;
+
+    match command {
+        OptionBytesCommand::Read => print_option_bytes(&option_bytes)?,
+        OptionBytesCommand::Write {
+            fields,
+            rdp,
+            protect,
+            unprotect,
+        } => {
+            for (name, value) in fields {
+                option_bytes.set(&name, value)?;
+            }
+
+            if let Some(rdp) = rdp {
+                option_bytes.set_readout_protection(rdp.into())?;
+            }
+
+            for range in unprotect {
+                option_bytes.set_write_protection(range, false)?;
+            }
+
+            for range in protect {
+                option_bytes.set_write_protection(range, true)?;
+            }
+
+            program_option_bytes(&mut session, &option_bytes)?;
+
+            println!("Programmed option bytes:");
+            print_option_bytes(&read_option_bytes(&mut session)?)?;
+        }
+    }
+
+    Ok(())
+}
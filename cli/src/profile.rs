@@ -0,0 +1,136 @@
+//! Statistical profiling using the PC sampling of the DWT.
+
+use super::{CoreOptions, ProbeOptions};
+use anyhow::Context;
+use object::{Object, ObjectSymbol, SymbolKind};
+use probe_rs::architecture::arm::{component::TraceSink, TraceEvent};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+/// Name used for samples taken while the core was sleeping.
+const SLEEP_SYMBOL: &str = "[sleep]";
+
+/// Name used for samples which could not be resolved to a symbol.
+const UNKNOWN_SYMBOL: &str = "[unknown]";
+
+/// The function symbols of an ELF file, sorted by address.
+struct SymbolTable {
+    symbols: Vec<(u64, u64, String)>,
+}
+
+impl SymbolTable {
+    fn from_elf(data: &[u8]) -> anyhow::Result<Self> {
+        let file = object::File::parse(data)?;
+
+        let mut symbols: Vec<_> = file
+            .symbols()
+            .filter(|symbol| symbol.kind() == SymbolKind::Text)
+            .filter_map(|symbol| {
+                let name = symbol.name().ok()?;
+
+                // The lowest bit of Thumb function addresses is set.
+                Some((
+                    symbol.address() & !1,
+                    symbol.size(),
+                    rustc_demangle::demangle(name).to_string(),
+                ))
+            })
+            .collect();
+
+        symbols.sort_by_key(|(address, _, _)| *address);
+
+        Ok(Self { symbols })
+    }
+
+    /// Find the function containing `address`.
+    fn lookup(&self, address: u64) -> Option<&str> {
+        let index = match self
+            .symbols
+            .binary_search_by_key(&address, |(address, _, _)| *address)
+        {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+
+        let (start, size, name) = &self.symbols[index];
+
+        if *size == 0 || address < start + size {
+            Some(name)
+        } else {
+            None
+        }
+    }
+}
+
+/// Profile the application by sampling the PC, and write the samples in the
+/// folded stack format, which is used by flamegraph tools like `inferno`.
+///
+/// # Args
+/// * `shared_options` - Specifies information about which core to profile.
+/// * `common` - Specifies information about the probe to use for profiling.
+/// * `sink` - Specifies the destination for trace data.
+/// * `elf` - The ELF file of the running application, used to resolve the sampled addresses.
+/// * `interval` - The number of core clock cycles between samples.
+/// * `duration` - Specifies the duration to profile for.
+/// * `output_file` - An optionally specified filename to write the folded stacks into.
+pub(crate) fn profile(
+    shared_options: &CoreOptions,
+    common: &ProbeOptions,
+    sink: TraceSink,
+    elf: &Path,
+    interval: u32,
+    duration: std::time::Duration,
+    output_file: Option<String>,
+) -> anyhow::Result<()> {
+    let data = std::fs::read(elf).with_context(|| format!("Failed to read {}", elf.display()))?;
+    let symbols = SymbolTable::from_elf(&data)?;
+
+    let mut session = common.simple_attach()?;
+
+    session.setup_tracing(shared_options.core, sink)?;
+
+    // Only the PC samples are of interest, don't waste bandwidth on other packets.
+    session.set_exception_trace(false)?;
+    session.set_itm_local_timestamps(false)?;
+    session.enable_pc_sampling(interval)?;
+
+    let mut samples: HashMap<&str, u64> = HashMap::new();
+    let mut total = 0;
+
+    let start = std::time::Instant::now();
+
+    while start.elapsed() < duration {
+        session.poll_trace_events(|event| {
+            if let TraceEvent::PcSample { pc } = event {
+                let name = match pc {
+                    Some(pc) => symbols.lookup(pc.into()).unwrap_or(UNKNOWN_SYMBOL),
+                    None => SLEEP_SYMBOL,
+                };
+
+                *samples.entry(name).or_default() += 1;
+                total += 1;
+            }
+        })?;
+    }
+
+    session.disable_pc_sampling()?;
+
+    log::info!("Collected {} PC samples", total);
+
+    let mut samples: Vec<_> = samples.into_iter().collect();
+    samples.sort();
+
+    let mut output: Box<dyn Write> = match output_file {
+        Some(destination) => Box::new(std::fs::File::create(destination)?),
+        None => Box::new(std::io::stdout()),
+    };
+
+    for (name, count) in samples {
+        // Semicolons separate the frames in the folded stack format.
+        writeln!(output, "{} {}", name.replace(';', ":"), count)?;
+    }
+
+    Ok(())
+}
@@ -1,18 +1,35 @@
 use anyhow::{Context, Result};
-use probe_rs::flashing::FileDownloadError;
+use probe_rs::config::{MemoryRange, MemoryRegion, Target};
+use probe_rs::flashing::{run_from_ram, ElfOptions, FileDownloadError};
+use probe_rs::semihosting::SemihostingCommand;
+use probe_rs::{BreakpointCause, Core, CoreStatus, HaltReason, Session};
 use probe_rs_cli_util::common_options::{CargoOptions, FlashOptions, ProbeOptions};
 use probe_rs_cli_util::flash::run_flash_download;
 use probe_rs_cli_util::rtt;
 use std::fs::File;
 use std::io::Write;
-use std::path::Path;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
 
+/// How long to wait for a disconnected probe to show up again.
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait before polling RTT again, if no data was received.
+const IDLE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Flash and run the firmware, and show its RTT output.
+///
+/// Returns the exit code of the firmware if it exited using semihosting.
 pub fn run(
     common: ProbeOptions,
     path: &str,
     chip_erase: bool,
     disable_double_buffering: bool,
-) -> Result<()> {
+    ram: bool,
+    rtt_cores: &[(usize, PathBuf)],
+) -> Result<Option<i32>> {
     let mut session = common.simple_attach()?;
 
     let mut file = match File::open(path) {
@@ -21,53 +38,235 @@ pub fn run(
     };
 
     let mut loader = session.target().flash_loader();
-    loader.load_elf_data(&mut file)?;
-
-    run_flash_download(
-        &mut session,
-        Path::new(path),
-        &FlashOptions {
-            version: false,
-            list_chips: false,
-            list_probes: false,
-            disable_progressbars: false,
-            disable_double_buffering,
-            reset_halt: false,
-            log: None,
-            restore_unwritten: false,
-            flash_layout_output_path: None,
-            elf: None,
-            work_dir: None,
-            cargo_options: CargoOptions::default(),
-            probe_options: common,
-        },
-        loader,
-        chip_erase,
-    )?;
+    loader.load_elf_data(&mut file, ElfOptions::default())?;
+
+    if ram {
+        // The core must not be reset afterwards, that would start the firmware in the flash.
+        run_from_ram(&mut session, &loader, 0).context("Failed to run the image from RAM")?;
+    } else {
+        run_flash_download(
+            &mut session,
+            Path::new(path),
+            &FlashOptions {
+                version: false,
+                list_chips: false,
+                list_probes: false,
+                disable_progressbars: false,
+                disable_double_buffering,
+                skip_unchanged_sectors: false,
+                preserve: Vec::new(),
+                bank: None,
+                swap_banks: false,
+                verification_manifest: None,
+                resume: None,
+                reset_halt: false,
+                log: None,
+                restore_unwritten: false,
+                flash_layout_output_path: None,
+                elf: None,
+                work_dir: None,
+                cargo_options: CargoOptions::default(),
+                probe_options: common,
+            },
+            loader,
+            chip_erase,
+        )?;
+
+        session.core(0)?.reset()?;
+    }
 
     let rtt_config = rtt::RttConfig::default();
 
-    let memory_map = session.target().memory_map.clone();
+    let mut cores = vec![CoreRtt::new(session.target(), 0, PathBuf::from(path))?];
+    for (core_index, elf) in rtt_cores {
+        cores.push(CoreRtt::new(session.target(), *core_index, elf.clone())?);
+    }
+
+    attach_rtt(&mut cores, &mut session, &rtt_config);
+
+    if cores.iter().all(|core_rtt| core_rtt.target_rtt.is_none()) {
+        return Ok(None);
+    }
 
-    let mut core = session.core(0)?;
-    core.reset()?;
+    // The output is only tagged with the name of the core if there is more than one.
+    let tag_output = cores.len() > 1;
 
-    let mut rtta = match rtt::attach_to_rtt(&mut core, &memory_map, Path::new(path), &rtt_config) {
-        Ok(target_rtt) => Some(target_rtt),
-        Err(error) => {
-            log::error!("{:?} Continuing without RTT... ", error);
-            None
+    let mut stdout = std::io::stdout();
+    loop {
+        if let Some(exit_code) = handle_semihosting(&mut session.core(0)?, &mut stdout)? {
+            return Ok(Some(exit_code));
         }
-    };
 
-    if let Some(rtta) = &mut rtta {
-        let mut stdout = std::io::stdout();
-        loop {
-            for (_ch, data) in rtta.poll_rtt_fallible(&mut core)? {
-                stdout.write_all(data.as_bytes()).unwrap();
+        let mut disconnected = false;
+        let mut received = false;
+
+        for core_rtt in &mut cores {
+            let poll_result = match core_rtt.target_rtt.as_mut() {
+                Some(target_rtt) => {
+                    target_rtt.poll_rtt_reattach(&mut session.core(core_rtt.index)?)
+                }
+                None => continue,
+            };
+
+            match poll_result {
+                Ok(rtt::RttPollEvent::TargetRebooted) => {
+                    log::warn!(
+                        "Core {} was reset, attaching to RTT again once it is initialized...",
+                        core_rtt.name
+                    );
+                }
+                Ok(rtt::RttPollEvent::Data(data)) => {
+                    for (_ch, data) in data {
+                        received |= !data.is_empty();
+
+                        if tag_output {
+                            for line in data.split_inclusive('\n') {
+                                write!(stdout, "[{}] {}", core_rtt.name, line).unwrap();
+                            }
+                        } else {
+                            stdout.write_all(data.as_bytes()).unwrap();
+                        }
+                    }
+                }
+                Err(error) if probe_rs::is_disconnect_error(error.as_ref()) => {
+                    disconnected = true;
+                    break;
+                }
+                Err(error) => return Err(error),
             }
         }
+
+        if disconnected {
+            log::warn!("The probe was disconnected, waiting for it to come back...");
+
+            session.reconnect(RECONNECT_TIMEOUT)?;
+
+            attach_rtt(&mut cores, &mut session, &rtt_config);
+        } else if !received {
+            thread::sleep(IDLE_INTERVAL);
+        }
     }
+}
+
+/// Attach to RTT on all `cores`, the cores where this fails continue without RTT.
+fn attach_rtt(cores: &mut [CoreRtt], session: &mut Session, rtt_config: &rtt::RttConfig) {
+    for core_rtt in cores {
+        if let Err(error) = core_rtt.attach(session, rtt_config) {
+            log::error!(
+                "{:?} Continuing without RTT on core {}... ",
+                error,
+                core_rtt.name
+            );
+        }
+    }
+}
+
+/// The RTT connection to the firmware on a core.
+struct CoreRtt {
+    index: usize,
+    name: String,
+    elf: PathBuf,
+    /// The memory map without the RAM which the core can't access, so only its own control block is found.
+    memory_map: Vec<MemoryRegion>,
+    /// The RTT scan regions of the target in the RAM of the core.
+    rtt_scan_regions: Vec<Range<u64>>,
+    target_rtt: Option<rtt::RttActiveTarget>,
+}
+
+impl CoreRtt {
+    fn new(target: &Target, index: usize, elf: PathBuf) -> Result<Self> {
+        let name = target
+            .cores
+            .get(index)
+            .map(|core| core.name.clone())
+            .with_context(|| format!("The target has no core {}", index))?;
+
+        let memory_map = target
+            .memory_map
+            .iter()
+            .filter(|region| match region {
+                MemoryRegion::Ram(ram) => ram.cores.contains(&name),
+                _ => true,
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let rtt_scan_regions = target
+            .rtt_scan_regions
+            .iter()
+            .filter(|range| {
+                memory_map.iter().any(|region| match region {
+                    MemoryRegion::Ram(ram) => ram.range.intersects_range(range),
+                    _ => false,
+                })
+            })
+            .cloned()
+            .collect();
+
+        Ok(Self {
+            index,
+            name,
+            elf,
+            memory_map,
+            rtt_scan_regions,
+            target_rtt: None,
+        })
+    }
+
+    fn attach(&mut self, session: &mut Session, rtt_config: &rtt::RttConfig) -> Result<()> {
+        // The previous connection is invalid, if the core is attached again.
+        self.target_rtt = None;
+
+        let mut core = session.core(self.index)?;
+
+        self.target_rtt = Some(rtt::attach_to_rtt(
+            &mut core,
+            &self.memory_map,
+            &self.rtt_scan_regions,
+            &self.elf,
+            rtt_config,
+        )?);
+
+        Ok(())
+    }
+}
+
+/// Handle a semihosting request, if the core is halted on one.
+///
+/// Console output is written to `stdout`, and the exit code is returned once the target exits.
+fn handle_semihosting(core: &mut Core, stdout: &mut impl Write) -> Result<Option<i32>> {
+    let command = match core.status()? {
+        CoreStatus::Halted(HaltReason::Breakpoint(BreakpointCause::Semihosting(command))) => {
+            command
+        }
+        _ => return Ok(None),
+    };
+
+    let return_value = match command {
+        SemihostingCommand::ExitSuccess => return Ok(Some(0)),
+        SemihostingCommand::ExitError {
+            reason,
+            exit_status,
+        } => {
+            log::error!("The target exited with reason {:#x}", reason);
+            return Ok(Some(exit_status.map_or(1, |status| status as i32)));
+        }
+        SemihostingCommand::Unknown { operation, .. } => {
+            log::warn!("Unsupported semihosting operation {:#x}", operation);
+            // Return -1 to the target, which indicates an error for all operations.
+            u64::MAX
+        }
+        command => {
+            if let Some(output) = command.read_output(core)? {
+                stdout.write_all(&output)?;
+                stdout.flush()?;
+            }
+            0
+        }
+    };
+
+    core.finish_semihosting(return_value)?;
+    core.run()?;
 
-    Ok(())
+    Ok(None)
 }
@@ -1324,13 +1324,17 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
                     capstone::arch::riscv::ArchExtraMode::RiscVC,
                 ))
                 .build(),
+            // Capstone doesn't support the Xtensa instruction set.
+            InstructionSet::Xtensa => Err(capstone::Error::CustomError(
+                "Xtensa is not supported by Capstone",
+            )),
         }
         .map_err(|err| anyhow!("Error creating capstone: {:?}", err))?;
         let _ = cs.set_skipdata(true);
 
         // Adjust instruction offset as required for variable length instruction sets.
         let instruction_offset_as_bytes = match target_instruction_set {
-            InstructionSet::Thumb2 | InstructionSet::RV32C => {
+            InstructionSet::Thumb2 | InstructionSet::RV32C | InstructionSet::Xtensa => {
                 // Since we cannot guarantee the size of individual instructions, let's assume we will read the 120% of the requested number of 16-bit instructions.
                 (instruction_offset
                     * target_core
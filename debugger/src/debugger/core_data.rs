@@ -45,6 +45,7 @@ impl<'p> CoreHandle<'p> {
         &mut self,
         debug_adapter: &mut DebugAdapter<P>,
         target_memory_map: &[probe_rs::config::MemoryRegion],
+        rtt_scan_regions: &[std::ops::Range<u64>],
         program_binary: &std::path::Path,
         rtt_config: &rtt::RttConfig,
     ) -> Result<()> {
@@ -52,14 +53,17 @@ impl<'p> CoreHandle<'p> {
         match rtt::attach_to_rtt(
             &mut self.core,
             target_memory_map,
+            rtt_scan_regions,
             program_binary,
             rtt_config,
         ) {
             Ok(target_rtt) => {
                 for any_channel in target_rtt.active_channels.iter() {
                     if let Some(up_channel) = &any_channel.up_channel {
-                        if any_channel.data_format == DataFormat::Defmt {
-                            // For defmt, we set the channel to be blocking when full.
+                        if any_channel.data_format == DataFormat::Defmt
+                            && any_channel.mode.is_none()
+                        {
+                            // For defmt, we set the channel to be blocking when full, unless another mode was configured.
                             up_channel.set_mode(&mut self.core, ChannelMode::BlockIfFull)?;
                         }
                         debugger_rtt_channels.push(debug_rtt::DebuggerRttChannel {
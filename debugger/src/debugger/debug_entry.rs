@@ -11,7 +11,7 @@ use crate::{
 };
 use anyhow::{anyhow, Context, Result};
 use probe_rs::{
-    flashing::{download_file_with_options, DownloadOptions, FlashProgress, Format},
+    flashing::{download_file_with_options, DownloadOptions, ElfOptions, FlashProgress, Format},
     CoreStatus, Probe,
 };
 use serde::Deserialize;
@@ -778,6 +778,7 @@ impl Debugger {
                                             )
                                             .ok();
                                     }
+                                    probe_rs::flashing::ProgressEvent::Progress(_) => {}
                                 }
                             })
                         } else {
@@ -787,7 +788,7 @@ impl Debugger {
                         download_file_with_options(
                             &mut session_data.session,
                             path_to_elf,
-                            Format::Elf,
+                            Format::Elf(ElfOptions::default()),
                             download_options,
                         )
                     };
@@ -58,7 +58,7 @@ impl DebuggerRttChannel {
                     }
                 })
                 .and_then(|rtt_channel| {
-                    match rtt_channel.get_rtt_data(core, rtt_target.defmt_state.as_ref()) {
+                    match rtt_channel.get_rtt_data(core) {
                         Ok(data_result) => data_result,
                         Err(rtt_error) => {
                             debug_adapter
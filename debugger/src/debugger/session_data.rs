@@ -222,6 +222,7 @@ impl SessionData {
         let mut no_delay_required = false;
         let mut status_of_cores: Vec<CoreStatus> = vec![];
         let target_memory_map = &self.session.target().memory_map.clone();
+        let rtt_scan_regions = &self.session.target().rtt_scan_regions.clone();
         for core_config in session_config.core_configs.iter() {
             if let Ok(mut target_core) = self.attach_core(core_config.core_index) {
                 match target_core.core.status() {
@@ -245,6 +246,7 @@ impl SessionData {
                                     match target_core.attach_to_rtt(
                                         debug_adapter,
                                         target_memory_map,
+                                        rtt_scan_regions,
                                         core_config.program_binary.as_ref().unwrap(),
                                         &core_config.rtt_config,
                                     ) {
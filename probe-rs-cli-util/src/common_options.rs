@@ -33,15 +33,17 @@
 //! ```
 use crate::ArtifactError;
 
-use std::{fs::File, io::Write, path::Path, path::PathBuf};
+use std::{fs::File, io::Write, num::ParseIntError, ops::Range, path::Path, path::PathBuf};
 
 use byte_unit::Byte;
 use clap;
 use probe_rs::{
-    config::{RegistryError, TargetSelector},
-    flashing::{FileDownloadError, FlashError, FlashLoader},
-    DebugProbeError, DebugProbeSelector, FakeProbe, Permissions, Probe, Session, Target,
-    WireProtocol,
+    config::{CoreAccessOptions, RegistryError, TargetSelector},
+    flashing::{
+        DaplinkDriveError, ElfOptions, FileDownloadError, FlashBank, FlashError, FlashLoader,
+    },
+    BusPirate, DebugProbeError, DebugProbeSelector, FakeProbe, Permissions, Probe, RemoteBitbang,
+    RemoteProbe, Session, Target, WireProtocol,
 };
 
 /// Common options when flashing a target device.
@@ -66,6 +68,38 @@ pub struct FlashOptions {
         programming with timeout errors, try this option."
     )]
     pub disable_double_buffering: bool,
+    #[clap(
+        long = "skip-unchanged-sectors",
+        help = "Read the flash before erasing it, and skip the sectors which already contain the data. This makes reflashing a mostly unchanged image faster."
+    )]
+    pub skip_unchanged_sectors: bool,
+    #[clap(
+        long = "preserve",
+        value_parser = parse_range,
+        help = "Keep the current contents of the flash in the given address range, e.g. '0x08000000..0x08004000' for a bootloader. Can be given multiple times."
+    )]
+    pub preserve: Vec<Range<u64>>,
+    #[clap(
+        long = "bank",
+        value_parser = parse_bank,
+        help = "Flash the image to a bank of a dual bank flash: '1', '2' or 'inactive'. The image has to be linked for the start of the flash."
+    )]
+    pub bank: Option<FlashBank>,
+    #[clap(
+        long = "swap-banks",
+        help = "Swap the banks of a dual bank flash after flashing, so the chip boots from the other bank after the next reset."
+    )]
+    pub swap_banks: bool,
+    #[clap(
+        long = "verification-manifest",
+        help = "Read back the programmed data after flashing, and write a JSON manifest with the digests of the programmed ranges, the skipped ranges, the unique ID of the device and the timing to the given file."
+    )]
+    pub verification_manifest: Option<PathBuf>,
+    #[clap(
+        long = "resume",
+        help = "Record the programmed sectors in the given file. If flashing is interrupted, running the same command again skips the sectors which were already programmed."
+    )]
+    pub resume: Option<PathBuf>,
     #[clap(
         name = "reset-halt",
         long = "reset-halt",
@@ -141,7 +175,13 @@ pub struct ProbeOptions {
     #[structopt(name = "chip description file path", long = "chip-description-path")]
     pub chip_description_path: Option<PathBuf>,
 
-    /// Protocol used to connect to chip. Possible options: [swd, jtag]
+    /// Load a flash algorithm from a CMSIS-Pack FLM file, and use it for the flash described in the file.
+    ///
+    /// This requires '--chip'.
+    #[clap(long)]
+    pub flash_algorithm: Option<PathBuf>,
+
+    /// Protocol used to connect to chip. Possible options: [swd, jtag, cjtag]
     #[structopt(long, help_heading = "PROBE CONFIGURATION")]
     pub protocol: Option<WireProtocol>,
 
@@ -150,12 +190,48 @@ pub struct ProbeOptions {
     /// Use '--probe VID:PID' or '--probe VID:PID:Serial' if you have more than one probe with the same VID:PID.",
     #[structopt(long = "probe", help_heading = "PROBE CONFIGURATION")]
     pub probe_selector: Option<DebugProbeSelector>,
+
+    /// Use a probe shared by a probe server at the given address, e.g. 'lab-pc:1338'.
+    ///
+    /// The server can be started using 'probe-rs-cli serve'.
+    #[clap(long, help_heading = "PROBE CONFIGURATION")]
+    pub remote: Option<String>,
+
+    /// Use an adapter speaking the OpenOCD remote_bitbang protocol at the given address, e.g. 'localhost:9824'.
+    ///
+    /// This is commonly used to connect to simulators.
+    #[clap(long, help_heading = "PROBE CONFIGURATION")]
+    pub remote_bitbang: Option<String>,
+
+    /// Use a Bus Pirate connected to the given serial port as a probe, e.g. '/dev/ttyUSB0'.
+    ///
+    /// This is very slow, and only meant to be used if no other probe is available.
+    #[clap(long, help_heading = "PROBE CONFIGURATION")]
+    pub bus_pirate: Option<String>,
+
+    /// Use GPIO pins of this machine as a probe, e.g. 'chip=/dev/gpiochip0,swclk=11,swdio=25'.
+    ///
+    /// Supported keys are chip, swclk/tck, swdio/tms, tdi, tdo, reset and delay_ns.
+    /// Pins which are not given use the Raspberry Pi defaults of OpenOCD.
+    #[cfg(all(feature = "gpiod", target_os = "linux"))]
+    #[clap(long, help_heading = "PROBE CONFIGURATION")]
+    pub gpio: Option<probe_rs::GpioProbeConfig>,
     #[clap(
         long,
         help = "The protocol speed in kHz.",
         help_heading = "PROBE CONFIGURATION"
     )]
     pub speed: Option<u32>,
+    /// The TARGETSEL value used to select the debug port on SWD multidrop targets, e.g. '0x01002927'.
+    ///
+    /// This overrides the value from the target description for all cores, and requires '--chip'.
+    #[clap(long, value_parser = parse_u32, help_heading = "PROBE CONFIGURATION")]
+    pub targetsel: Option<u32>,
+    /// The index of the TAP used as the debug target in a JTAG scan chain with multiple TAPs.
+    ///
+    /// The TAPs are numbered starting at the TAP closest to TDO of the probe.
+    #[clap(long, help_heading = "PROBE CONFIGURATION")]
+    pub jtag_tap: Option<usize>,
     #[structopt(
         long = "connect-under-reset",
         help = "Use this flag to assert the nreset & ntrst pins during attaching the probe to the chip."
@@ -169,6 +245,12 @@ pub struct ProbeOptions {
         even when it has read-only protection."
     )]
     pub allow_erase_all: bool,
+    #[structopt(
+        long = "allow-otp-programming",
+        help = "Use this flag to allow programming the one-time programmable memory, e.g. the OTP area of the STM32. \
+        These changes can't be reverted."
+    )]
+    pub allow_otp_programming: bool,
 }
 
 impl ProbeOptions {
@@ -192,14 +274,45 @@ impl ProbeOptions {
     /// Resolves a resultant target selector from passed [ProbeOptions].
     pub fn get_target_selector(&self) -> Result<TargetSelector, OperationError> {
         let target = if let Some(chip_name) = &self.chip {
-            let target = probe_rs::config::get_target_by_name(chip_name).map_err(|error| {
+            let mut target = probe_rs::config::get_target_by_name(chip_name).map_err(|error| {
                 OperationError::ChipNotFound {
                     source: error,
                     name: chip_name.clone(),
                 }
             })?;
 
+            if let Some(targetsel) = self.targetsel {
+                for core in &mut target.cores {
+                    if let CoreAccessOptions::Arm(options) = &mut core.core_access_options {
+                        options.psel = targetsel;
+                    }
+                }
+            }
+
+            if let Some(path) = &self.flash_algorithm {
+                let data = std::fs::read(path)?;
+
+                let name = path
+                    .file_stem()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or("flm")
+                    .to_lowercase();
+
+                let algorithm = probe_rs::flashing::load_flm(&data, &name).map_err(|error| {
+                    OperationError::FailedFlashAlgorithmLoading {
+                        source: error,
+                        path: path.clone(),
+                    }
+                })?;
+
+                target.add_flash_algorithm(algorithm);
+            }
+
             TargetSelector::Specified(target)
+        } else if self.targetsel.is_some() {
+            return Err(OperationError::TargetselWithoutChip);
+        } else if self.flash_algorithm.is_some() {
+            return Err(OperationError::FlashAlgorithmWithoutChip);
         } else {
             TargetSelector::Auto
         };
@@ -207,6 +320,60 @@ impl ProbeOptions {
         Ok(target)
     }
 
+    /// Opens the probe selected by the options.
+    fn open_probe(&self) -> Result<Probe, OperationError> {
+        // Probes which are not connected over USB are selected by their own options.
+        if let Some(address) = &self.remote {
+            let probe = RemoteProbe::connect(address.as_str())
+                .map_err(OperationError::FailedToOpenProbe)?;
+
+            return Ok(Probe::from_specific_probe(probe));
+        }
+
+        if let Some(address) = &self.remote_bitbang {
+            let probe = RemoteBitbang::connect(address.as_str())
+                .map_err(OperationError::FailedToOpenProbe)?;
+
+            return Ok(Probe::from_specific_probe(probe));
+        }
+
+        if let Some(port) = &self.bus_pirate {
+            let probe = BusPirate::open(port).map_err(OperationError::FailedToOpenProbe)?;
+
+            return Ok(Probe::from_specific_probe(probe));
+        }
+
+        #[cfg(all(feature = "gpiod", target_os = "linux"))]
+        if let Some(config) = &self.gpio {
+            let probe =
+                probe_rs::GpioProbe::new(config).map_err(OperationError::FailedToOpenProbe)?;
+
+            return Ok(Probe::from_specific_probe(probe));
+        }
+
+        // If we got a probe selector as an argument, open the probe
+        // matching the selector if possible.
+        match &self.probe_selector {
+            Some(selector) => {
+                Probe::open(selector.clone()).map_err(OperationError::FailedToOpenProbe)
+            }
+            None => {
+                // Only automatically select a probe if there is
+                // only a single probe detected.
+                let list = Probe::list_all();
+                if list.len() > 1 {
+                    return Err(OperationError::MultipleProbesFound { number: list.len() });
+                }
+
+                if let Some(info) = list.first() {
+                    Probe::open(info).map_err(OperationError::FailedToOpenProbe)
+                } else {
+                    Err(OperationError::NoProbesFound)
+                }
+            }
+        }
+    }
+
     /// Attaches to specified probe and configures it.
     pub fn attach_probe(&self) -> Result<Probe, OperationError> {
         let mut probe = {
@@ -214,29 +381,16 @@ impl ProbeOptions {
                 Probe::from_specific_probe(Box::new(FakeProbe::new()));
             }
 
-            // If we got a probe selector as an argument, open the probe
-            // matching the selector if possible.
-            match &self.probe_selector {
-                Some(selector) => {
-                    Probe::open(selector.clone()).map_err(OperationError::FailedToOpenProbe)
-                }
-                None => {
-                    // Only automatically select a probe if there is
-                    // only a single probe detected.
-                    let list = Probe::list_all();
-                    if list.len() > 1 {
-                        return Err(OperationError::MultipleProbesFound { number: list.len() });
-                    }
-
-                    if let Some(info) = list.first() {
-                        Probe::open(info).map_err(OperationError::FailedToOpenProbe)
-                    } else {
-                        Err(OperationError::NoProbesFound)
-                    }
-                }
-            }
+            self.open_probe()
         }?;
 
+        self.configure_probe(&mut probe)?;
+
+        Ok(probe)
+    }
+
+    /// Selects the protocol, the JTAG TAP and the speed of the probe, if they are given.
+    pub fn configure_probe(&self, probe: &mut Probe) -> Result<(), OperationError> {
         if let Some(protocol) = self.protocol {
             // Select protocol and speed
             probe.select_protocol(protocol).map_err(|error| {
@@ -247,6 +401,15 @@ impl ProbeOptions {
             })?;
         }
 
+        if let Some(index) = self.jtag_tap {
+            probe.select_jtag_tap(index).map_err(|error| {
+                OperationError::FailedToSelectJtagTap {
+                    source: error,
+                    index,
+                }
+            })?;
+        }
+
         if let Some(speed) = self.speed {
             let _actual_speed = probe.set_speed(speed).map_err(|error| {
                 OperationError::FailedToSelectProtocolSpeed {
@@ -256,7 +419,20 @@ impl ProbeOptions {
             })?;
         }
 
-        Ok(probe)
+        Ok(())
+    }
+
+    /// The permissions of the session, as allowed by the options.
+    pub fn permissions(&self) -> Permissions {
+        let mut permissions = Permissions::new();
+        if self.allow_erase_all {
+            permissions = permissions.allow_erase_all();
+        }
+        if self.allow_otp_programming {
+            permissions = permissions.allow_otp_programming();
+        }
+
+        permissions
     }
 
     /// Attaches to target device session. Attaches under reset if
@@ -266,10 +442,7 @@ impl ProbeOptions {
         probe: Probe,
         target: TargetSelector,
     ) -> Result<Session, OperationError> {
-        let mut permissions = Permissions::new();
-        if self.allow_erase_all {
-            permissions = permissions.allow_erase_all();
-        }
+        let permissions = self.permissions();
 
         let session = if self.connect_under_reset {
             probe.attach_under_reset(target, permissions)
@@ -305,7 +478,7 @@ impl ProbeOptions {
         let target = session.target();
 
         // Create the flash loader
-        let mut loader = FlashLoader::new(target.memory_map.to_vec(), target.source().clone());
+        let mut loader = target.flash_loader();
 
         // Add data from the ELF.
         let mut file = File::open(elf_path).map_err(|error| OperationError::FailedToOpenElf {
@@ -315,7 +488,7 @@ impl ProbeOptions {
 
         // Try and load the ELF data.
         loader
-            .load_elf_data(&mut file)
+            .load_elf_data(&mut file, ElfOptions::default())
             .map_err(OperationError::FailedToLoadElfData)?;
 
         Ok(loader)
@@ -463,6 +636,12 @@ pub enum OperationError {
         target_spec: Option<String>,
         path: PathBuf,
     },
+    #[error("Flashing '{path}' through the DAPLink drive failed.")]
+    DaplinkDriveFlashingFailed {
+        #[source]
+        source: DaplinkDriveError,
+        path: PathBuf,
+    },
     #[error("Failed to parse the chip description '{path}'.")]
     FailedChipDescriptionParsing {
         #[source]
@@ -489,6 +668,16 @@ pub enum OperationError {
         source: RegistryError,
         name: String,
     },
+    #[error("A TARGETSEL value can only be used together with a chip.")]
+    TargetselWithoutChip,
+    #[error("Failed to load the flash algorithm '{path}'.")]
+    FailedFlashAlgorithmLoading {
+        #[source]
+        source: probe_rs::flashing::FlmError,
+        path: PathBuf,
+    },
+    #[error("A flash algorithm can only be loaded together with a chip.")]
+    FlashAlgorithmWithoutChip,
     #[error("The protocol '{protocol}' could not be selected.")]
     FailedToSelectProtocol {
         #[source]
@@ -501,6 +690,12 @@ pub enum OperationError {
         source: DebugProbeError,
         speed: u32,
     },
+    #[error("The JTAG TAP {index} could not be selected.")]
+    FailedToSelectJtagTap {
+        #[source]
+        source: DebugProbeError,
+        index: usize,
+    },
     #[error("Connecting to the chip was unsuccessful.")]
     AttachingFailed {
         #[source]
@@ -513,6 +708,12 @@ pub enum OperationError {
     TargetResetFailed(#[source] probe_rs::Error),
     #[error("The target could not be reset and halted.")]
     TargetResetHaltFailed(#[source] probe_rs::Error),
+    #[error("Failed to write the verification manifest '{path}'.")]
+    FailedToWriteManifest {
+        #[source]
+        source: std::io::Error,
+        path: PathBuf,
+    },
     #[error("Failed to write to file")]
     IOError(#[source] std::io::Error),
     #[error("probe-rs API was called in the wrong order.")]
@@ -521,6 +722,54 @@ pub enum OperationError {
     CliArgument(#[from] clap::Error),
 }
 
+/// Parse a decimal, or a hexadecimal number prefixed with `0x`, in command line arguments.
+pub fn parse_u32(input: &str) -> Result<u32, ParseIntError> {
+    parse_int::parse(input)
+}
+
+/// Parse a decimal, or a hexadecimal number prefixed with `0x`, in command line arguments.
+pub fn parse_u64(input: &str) -> Result<u64, ParseIntError> {
+    parse_int::parse(input)
+}
+
+fn parse_bank(input: &str) -> Result<FlashBank, String> {
+    match input.to_ascii_lowercase().as_str() {
+        "1" => Ok(FlashBank::Bank1),
+        "2" => Ok(FlashBank::Bank2),
+        "inactive" => Ok(FlashBank::Inactive),
+        _ => Err(format!(
+            "'{}' is not a flash bank, expected '1', '2' or 'inactive'",
+            input
+        )),
+    }
+}
+
+/// Parse an address range of the form `start..end`, e.g. `0x08000000..0x08004000`.
+pub fn parse_range(input: &str) -> Result<Range<u64>, String> {
+    let (start, end) = input
+        .split_once("..")
+        .ok_or_else(|| format!("'{}' is not a range of the form 'start..end'", input))?;
+
+    let parse = |input: &str| {
+        match input
+            .strip_prefix("0x")
+            .or_else(|| input.strip_prefix("0X"))
+        {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => input.parse(),
+        }
+        .map_err(|e| format!("Invalid address '{}': {}", input, e))
+    };
+
+    let range = parse(start)?..parse(end)?;
+
+    if range.is_empty() {
+        return Err(format!("The range '{}' is empty", input));
+    }
+
+    Ok(range)
+}
+
 impl From<std::io::Error> for OperationError {
     fn from(e: std::io::Error) -> Self {
         OperationError::IOError(e)
@@ -4,15 +4,22 @@ use crate::{
     logging,
 };
 
+use std::fs::File;
 use std::time::Duration;
 use std::{path::Path, sync::Arc, time::Instant};
 
 use colored::Colorize;
 use probe_rs::{
-    flashing::{DownloadOptions, FlashLoader, FlashProgress, ProgressEvent},
+    flashing::{
+        DaplinkDrive, DownloadOptions, FlashError, FlashLoader, FlashProgress, ProgressEvent,
+        VerificationManifest,
+    },
     Session,
 };
 
+/// How long to wait for a DAPLink drive to show up again after programming.
+const DAPLINK_DRIVE_TIMEOUT: Duration = Duration::from_secs(60);
+
 /// Performs the flash download with the given loader. Ensure that the loader has the data to load already stored.
 /// This function also manages the update and display of progress bars.
 pub fn run_flash_download(
@@ -30,8 +37,34 @@ pub fn run_flash_download(
     download_option.dry_run = opt.probe_options.dry_run;
     download_option.do_chip_erase = do_chip_erase;
     download_option.disable_double_buffering = opt.disable_double_buffering;
+    download_option.skip_unchanged_sectors = opt.skip_unchanged_sectors;
+    download_option.preserved_ranges = opt.preserve.clone();
+    download_option.bank = opt.bank;
+    download_option.swap_banks = opt.swap_banks;
+    download_option.resume_file = opt.resume.clone();
+
+    // The OTP memory can't be changed again, so the changes are shown before flashing.
+    if loader.otp_data().next().is_some() {
+        let report =
+            loader
+                .otp_report(session)
+                .map_err(|source| OperationError::FlashingFailed {
+                    source,
+                    target: session.target().clone(),
+                    target_spec: opt.probe_options.chip.clone(),
+                    path: path.to_path_buf(),
+                })?;
+
+        if !report.is_empty() {
+            logging::eprintln(format!(
+                "    {} the OTP memory is changed:\n{}",
+                "Warning".yellow().bold(),
+                report
+            ));
+        }
+    }
 
-    if !opt.disable_progressbars {
+    let result = if !opt.disable_progressbars {
         // Create progress bars.
         let multi_progress = MultiProgress::new();
         let style = ProgressStyle::default_bar()
@@ -135,28 +168,91 @@ pub fn run_flash_download(
                         fp.finish()
                     };
                 }
+                // The progress bars compute the throughput and ETA themselves.
+                Progress(_) => {}
             }
         });
 
         download_option.progress = Some(&progress);
 
-        loader.commit(session, download_option).map_err(|error| {
-            OperationError::FlashingFailed {
-                source: error,
-                target: session.target().clone(),
-                target_spec: opt.probe_options.chip.clone(),
-                path: path.to_path_buf(),
-            }
-        })?;
+        commit(
+            session,
+            &loader,
+            download_option,
+            opt.verification_manifest.is_some(),
+        )
     } else {
-        loader.commit(session, download_option).map_err(|error| {
-            OperationError::FlashingFailed {
-                source: error,
-                target: session.target().clone(),
-                target_spec: opt.probe_options.chip.clone(),
-                path: path.to_path_buf(),
+        commit(
+            session,
+            &loader,
+            download_option,
+            opt.verification_manifest.is_some(),
+        )
+    };
+
+    let manifest = match result {
+        Ok(manifest) => manifest,
+        Err(error) => {
+            // Targets without a flash algorithm can still be programmed
+            // through the mass storage interface of DAPLink probes.
+            let drive = match error {
+                FlashError::NoFlashLoaderAlgorithmAttached { .. } if !opt.probe_options.dry_run => {
+                    let serial_number = opt
+                        .probe_options
+                        .probe_selector
+                        .as_ref()
+                        .and_then(|selector| selector.serial_number.as_deref());
+
+                    DaplinkDrive::find(serial_number)
+                }
+                _ => None,
+            };
+
+            match drive {
+                Some(drive) => {
+                    logging::println(format!(
+                        "    {} no flash algorithm available, using the DAPLink drive at {}",
+                        "Flashing".green().bold(),
+                        drive.path().display()
+                    ));
+
+                    drive
+                        .download(&loader, DAPLINK_DRIVE_TIMEOUT)
+                        .map_err(|source| OperationError::DaplinkDriveFlashingFailed {
+                            source,
+                            path: path.to_path_buf(),
+                        })?;
+                }
+                None => {
+                    return Err(OperationError::FlashingFailed {
+                        source: error,
+                        target: session.target().clone(),
+                        target_spec: opt.probe_options.chip.clone(),
+                        path: path.to_path_buf(),
+                    })
+                }
+            }
+
+            // The data written through the drive can't be read back.
+            None
+        }
+    };
+
+    if let (Some(manifest), Some(manifest_path)) = (manifest, &opt.verification_manifest) {
+        write_manifest(&manifest, manifest_path).map_err(|source| {
+            OperationError::FailedToWriteManifest {
+                source,
+                path: manifest_path.clone(),
             }
         })?;
+
+        if !manifest.is_verified() {
+            logging::eprintln(format!(
+                "    {} the data read back doesn't match the image, see {}",
+                "Warning".yellow().bold(),
+                manifest_path.display()
+            ));
+        }
     }
 
     // Stop timer.
@@ -169,3 +265,23 @@ pub fn run_flash_download(
 
     Ok(())
 }
+
+/// Commit the data of the loader, and create a verification manifest if `manifest` is set.
+fn commit(
+    session: &mut Session,
+    loader: &FlashLoader,
+    options: DownloadOptions<'_>,
+    manifest: bool,
+) -> Result<Option<VerificationManifest>, FlashError> {
+    if manifest {
+        loader.commit_with_manifest(session, options).map(Some)
+    } else {
+        loader.commit(session, options).map(|_| None)
+    }
+}
+
+fn write_manifest(manifest: &VerificationManifest, path: &Path) -> Result<(), std::io::Error> {
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(file, manifest)?;
+    Ok(())
+}
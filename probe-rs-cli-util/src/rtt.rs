@@ -1,44 +1,120 @@
 use crate::*;
 use anyhow::{anyhow, Result};
 use chrono::Local;
-use defmt_decoder::DecodeError;
 use num_traits::Zero;
+use once_cell::sync::Lazy;
 use probe_rs::config::MemoryRegion;
 use probe_rs::Core;
+use probe_rs_rtt::defmt::{DefmtDecoder, DefmtTable, DEFMT_CHANNEL_NAME};
 pub use probe_rs_rtt::ChannelMode;
-use probe_rs_rtt::{DownChannel, Rtt, ScanRegion, UpChannel};
+use probe_rs_rtt::{DownChannel, Rtt, ScanRegion, UpChannel, VirtualTerminals};
 use serde::Deserialize;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
-use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::{
     fmt,
     fmt::Write,
     fs,
-    io::{Read, Seek},
+    io::{Cursor, Read, Seek},
     str::FromStr,
 };
 
+/// The addresses of the RTT control blocks which were found, by ELF file, for instant re-attach.
+static RTT_LOCATIONS: Lazy<Mutex<HashMap<ElfKey, u32>>> = Lazy::new(Default::default);
+
+/// The key of an ELF file in [`RTT_LOCATIONS`].
+///
+/// A digest of the contents is part of the key, the control block can move when the firmware is
+/// rebuilt, even if the modification time of the file doesn't change.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ElfKey {
+    path: PathBuf,
+    digest: u64,
+}
+
+impl ElfKey {
+    fn new(path: &Path, elf: &[u8]) -> Self {
+        let mut hasher = DefaultHasher::new();
+        elf.hash(&mut hasher);
+
+        Self {
+            path: path.to_path_buf(),
+            digest: hasher.finish(),
+        }
+    }
+
+    fn cached_location(&self) -> Option<u32> {
+        RTT_LOCATIONS.lock().unwrap().get(self).copied()
+    }
+
+    fn cache_location(self, address: u32) {
+        RTT_LOCATIONS.lock().unwrap().insert(self, address);
+    }
+}
+
+/// Attach to the RTT control block of the firmware `elf_file`.
+///
+/// The control block is looked up at the address where it was found before, at the address of the
+/// `_SEGGER_RTT` symbol, or in the `rtt_scan_regions` of the target, in this order. Only if none of
+/// these is known, all RAM in `memory_map` is scanned.
 pub fn attach_to_rtt(
     core: &mut Core,
     memory_map: &[MemoryRegion],
+    rtt_scan_regions: &[Range<u64>],
     elf_file: &Path,
     rtt_config: &RttConfig,
 ) -> Result<crate::rtt::RttActiveTarget, anyhow::Error> {
     log::info!("Initializing RTT");
-    let rtt_header_address = if let Ok(mut file) = File::open(elf_file) {
-        if let Some(address) = RttActiveTarget::get_rtt_symbol(&mut file) {
-            ScanRegion::Exact(address as u32)
-        } else {
-            ScanRegion::Ram
+    let elf = fs::read(elf_file).ok();
+    let cache_key = elf.as_deref().map(|elf| ElfKey::new(elf_file, elf));
+
+    let rtt = match cache_key
+        .as_ref()
+        .and_then(ElfKey::cached_location)
+        .and_then(|address| Rtt::attach_region(core, memory_map, &ScanRegion::Exact(address)).ok())
+    {
+        Some(rtt) => Ok(rtt),
+        None => {
+            let rtt_header_address =
+                match elf.and_then(|elf| RttActiveTarget::get_rtt_symbol(&mut Cursor::new(elf))) {
+                    Some(address) => ScanRegion::Exact(address as u32),
+                    None => ScanRegion::from_ranges(rtt_scan_regions),
+                };
+
+            Rtt::attach_region(core, memory_map, &rtt_header_address)
         }
-    } else {
-        ScanRegion::Ram
     };
 
-    match Rtt::attach_region(core, memory_map, &rtt_header_address) {
+    match rtt {
         Ok(rtt) => {
             log::info!("RTT initialized.");
-            let app = RttActiveTarget::new(rtt, elf_file, rtt_config)?;
+            if let Some(cache_key) = cache_key {
+                cache_key.cache_location(rtt.ptr());
+            }
+
+            let mut app = RttActiveTarget::new(rtt, elf_file, rtt_config)?;
+            app.attach_options = Some(AttachOptions {
+                memory_map: memory_map.to_vec(),
+                rtt_scan_regions: rtt_scan_regions.to_vec(),
+                elf_file: elf_file.to_path_buf(),
+                rtt_config: rtt_config.clone(),
+            });
+
+            for channel in &app.active_channels {
+                if let (Some(up_channel), Some(mode)) = (&channel.up_channel, channel.mode) {
+                    log::debug!(
+                        "Setting the mode of RTT channel {} to {:?}",
+                        up_channel.number(),
+                        mode
+                    );
+                    up_channel.set_mode(core, mode)?;
+                }
+            }
+
             Ok(app)
         }
         Err(err) => Err(anyhow!("Error attempting to attach to RTT: {}", err)),
@@ -110,6 +186,10 @@ pub struct RttChannelConfig {
     #[serde(default = "default_include_location")]
     // Control the inclusion of source location information for DataFormat::Defmt.
     pub show_location: bool,
+    #[structopt(skip)]
+    #[serde(default)]
+    // The mode of the up channel, which is set on the target when attaching. If it is `None`, the mode is not changed.
+    pub mode: Option<ChannelMode>,
 }
 
 /// This is the primary interface through which RTT channel data is read and written. Every actual RTT channel has a configuration and buffer that is used for this purpose.
@@ -119,9 +199,15 @@ pub struct RttActiveChannel {
     pub down_channel: Option<DownChannel>,
     pub channel_name: String,
     pub data_format: DataFormat,
+    /// The mode which is set for the up channel when attaching, if it was configured.
+    pub mode: Option<ChannelMode>,
     /// Data that will be written to the down_channel (host to target)
     _input_data: String,
     rtt_buffer: RttBuffer,
+    /// The decoder keeps incomplete defmt frames between polls.
+    defmt_decoder: Option<DefmtDecoder>,
+    /// Splits the output of up channel 0 into the SEGGER virtual terminals.
+    virtual_terminals: Option<VirtualTerminals>,
     show_timestamps: bool,
     show_location: bool,
 }
@@ -146,11 +232,11 @@ impl RttActiveChannel {
             .unwrap_or(1024); // If no explicit config is requested, assign a default
         let defmt_enabled: bool = up_channel
             .as_ref()
-            .map(|up| up.name() == Some("defmt"))
+            .map(|up| up.name() == Some(DEFMT_CHANNEL_NAME))
             .or_else(|| {
                 down_channel
                     .as_ref()
-                    .map(|down| down.name() == Some("defmt"))
+                    .map(|down| down.name() == Some(DEFMT_CHANNEL_NAME))
             })
             .unwrap_or(false); // If no explicit config is requested, assign a default
         let (data_format, show_location) = if defmt_enabled {
@@ -163,6 +249,10 @@ impl RttActiveChannel {
         } else {
             (full_config.data_format, false)
         };
+        let virtual_terminals = up_channel
+            .as_ref()
+            .filter(|up| up.number() == 0 && data_format == DataFormat::String)
+            .map(|_| VirtualTerminals::new());
         let name = up_channel
             .as_ref()
             .and_then(|up| up.name().map(Into::into))
@@ -184,8 +274,11 @@ impl RttActiveChannel {
             down_channel,
             channel_name: name,
             data_format,
+            mode: full_config.mode,
             _input_data: String::new(),
             rtt_buffer: RttBuffer::new(buffer_size),
+            defmt_decoder: None,
+            virtual_terminals,
             show_timestamps: full_config.show_timestamps,
             show_location,
         }
@@ -230,7 +323,6 @@ impl RttActiveChannel {
     pub fn get_rtt_data(
         &mut self,
         core: &mut Core,
-        defmt_state: Option<&(defmt_decoder::Table, Option<defmt_decoder::Locations>)>,
     ) -> Result<Option<(String, String)>, anyhow::Error> {
         self
             .poll_rtt(core)
@@ -241,13 +333,26 @@ impl RttActiveChannel {
                         let mut formatted_data = String::new();
                         match self.data_format {
                             DataFormat::String => {
-                                let incoming = String::from_utf8_lossy(&self.rtt_buffer.0[..bytes_read]).to_string();
-                                for (_i, line) in incoming.split_terminator('\n').enumerate() {
-                                    if self.show_timestamps {
-                                        write!(formatted_data, "{} :", Local::now())
-                                            .map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
+                                let data = &self.rtt_buffer.0[..bytes_read];
+                                let mut parts = vec![];
+                                match self.virtual_terminals.as_mut() {
+                                    Some(terminals) => terminals.demux(data, |terminal, data| parts.push((Some(terminal), data.to_vec()))),
+                                    None => parts.push((None, data.to_vec())),
+                                }
+                                // Like the J-Link RTT Viewer, the terminal is only shown if the firmware uses more than one.
+                                let show_terminals = self.virtual_terminals.as_ref().map_or(false, |terminals| terminals.is_used());
+                                for (terminal, data) in parts {
+                                    let incoming = String::from_utf8_lossy(&data).to_string();
+                                    for (_i, line) in incoming.split_terminator('\n').enumerate() {
+                                        if self.show_timestamps {
+                                            write!(formatted_data, "{} :", Local::now())
+                                                .map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
+                                        }
+                                        if let (true, Some(terminal)) = (show_terminals, terminal) {
+                                            write!(formatted_data, "{}> ", terminal).map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
+                                        }
+                                        writeln!(formatted_data, "{}", line).map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
                                     }
-                                    writeln!(formatted_data, "{}", line).map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
                                 }
                             }
                             DataFormat::BinaryLE => {
@@ -257,46 +362,30 @@ impl RttActiveChannel {
                                 }
                             }
                             DataFormat::Defmt => {
-                                match defmt_state {
-                                    Some((table, locs)) => {
-                                        let mut stream_decoder = table.new_stream_decoder();
-                                        stream_decoder.received(&self.rtt_buffer.0[..bytes_read]);
-                                        loop {
-                                            match stream_decoder.decode() {
-                                                Ok(frame) => {
-                                                    let loc = locs.as_ref().and_then(|locs| locs.get(&frame.index()) );
-                                                    writeln!(formatted_data, "{}", frame.display(false)).map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
-                                                    if self.show_location {
-                                                        if let Some(loc) = loc {
-                                                            let relpath = if let Ok(relpath) =
-                                                                loc.file.strip_prefix(&std::env::current_dir().unwrap())
-                                                            {
-                                                                relpath
-                                                            } else {
-                                                                // not relative; use full path
-                                                                &loc.file
-                                                            };
-                                                            writeln!(formatted_data,
-                                                                "└─ {}:{}",
-                                                                relpath.display(),
-                                                                loc.line
-                                                            ).map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
-                                                        } else {
-                                                            writeln!(formatted_data, "└─ <invalid location: defmt frame-index: {}>", frame.index()).map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
-                                                        }
-                                                    }
-                                                    continue;
-                                                },
-                                                Err(DecodeError::UnexpectedEof) => break,
-                                                Err(DecodeError::Malformed) => match table.encoding().can_recover() {
-                                                    // If recovery is impossible, break out of here and propagate the error.
-                                                    false => {
-                                                        return Err(anyhow!("Unrecoverable error while decoding Defmt data and some data may have been lost: {:?}", DecodeError::Malformed));
-                                                    },
-                                                    // If recovery is possible, skip the current frame and continue with new data.
-                                                    true => continue,
-                                                },
-
+                                match self.defmt_decoder.as_mut() {
+                                    Some(decoder) => {
+                                        decoder.received(&self.rtt_buffer.0[..bytes_read]);
+                                        for record in decoder.records() {
+                                            let record = record?;
+                                            writeln!(formatted_data, "{}", record).map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
+                                            if self.show_location {
+                                                if let Some(loc) = record.location {
+                                                    let relpath = if let Ok(relpath) =
+                                                        loc.file.strip_prefix(&std::env::current_dir().unwrap())
+                                                    {
+                                                        relpath
+                                                    } else {
+                                                        // not relative; use full path
+                                                        &loc.file
+                                                    };
+                                                    writeln!(formatted_data,
+                                                        "└─ {}:{}",
+                                                        relpath.display(),
+                                                        loc.line
+                                                    ).map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
+                                                } else {
+                                                    writeln!(formatted_data, "└─ <invalid location: defmt frame-index: {}>", record.index).map_or_else(|err| log::error!("Failed to format RTT data - {:?}", err), |r|r);
+                                                }
                                             }
                                         }
                                     }
@@ -328,7 +417,32 @@ impl RttActiveChannel {
 #[derive(Debug)]
 pub struct RttActiveTarget {
     pub active_channels: Vec<RttActiveChannel>,
-    pub defmt_state: Option<(defmt_decoder::Table, Option<defmt_decoder::Locations>)>,
+    pub defmt_table: Option<Arc<DefmtTable>>,
+    /// The control block, without its channels, to detect resets of the target.
+    rtt: Rtt,
+    /// How RTT is attached again after a reset, if it was attached with [`attach_to_rtt`].
+    attach_options: Option<AttachOptions>,
+    /// The target was reset, and RTT wasn't attached again yet.
+    rebooted: bool,
+}
+
+/// The arguments of [`attach_to_rtt`], to attach again after a reset of the target.
+#[derive(Debug)]
+struct AttachOptions {
+    memory_map: Vec<MemoryRegion>,
+    rtt_scan_regions: Vec<Range<u64>>,
+    elf_file: PathBuf,
+    rtt_config: RttConfig,
+}
+
+/// The result of [`RttActiveTarget::poll_rtt_reattach`].
+#[derive(Debug)]
+pub enum RttPollEvent {
+    /// The formatted data of the channels, by channel number.
+    Data(HashMap<String, String>),
+    /// The target was reset, or the firmware initialized RTT again. RTT is attached again once the
+    /// firmware has initialized the control block, the channels may have changed then.
+    TargetRebooted,
 }
 
 impl RttActiveTarget {
@@ -372,30 +486,15 @@ impl RttActiveTarget {
         let defmt_enabled = active_channels
             .iter()
             .any(|elem| elem.data_format == DataFormat::Defmt);
-        let defmt_state = if defmt_enabled {
+        let defmt_table = if defmt_enabled {
             let elf = fs::read(elf_file).map_err(|err| {
                 anyhow!(
                     "Error reading program binary while initalizing RTT: {}",
                     err
                 )
             })?;
-            if let Some(table) = defmt_decoder::Table::parse(&elf)? {
-                let locs = {
-                    let locs = table.get_locations(&elf)?;
-
-                    if !table.is_empty() && locs.is_empty() {
-                        log::warn!("Insufficient DWARF info; compile your program with `debug = 2` to enable location info.");
-                        None
-                    } else if table.indices().all(|idx| locs.contains_key(&(idx as u64))) {
-                        Some(locs)
-                    } else {
-                        log::warn!(
-                            "Location info is incomplete; it will be omitted from the output."
-                        );
-                        None
-                    }
-                };
-                Some((table, locs))
+            if let Some(table) = DefmtTable::parse(&elf)? {
+                Some(Arc::new(table))
             } else {
                 log::warn!("No `Table` definition in DWARF info; compile your program with `debug = 2` to enable location info.");
                 None
@@ -404,9 +503,21 @@ impl RttActiveTarget {
             None
         };
 
+        if let Some(table) = &defmt_table {
+            for channel in active_channels
+                .iter_mut()
+                .filter(|channel| channel.data_format == DataFormat::Defmt)
+            {
+                channel.defmt_decoder = Some(DefmtDecoder::new(table.clone()));
+            }
+        }
+
         Ok(Self {
             active_channels,
-            defmt_state,
+            defmt_table,
+            rtt,
+            attach_options: None,
+            rebooted: false,
         })
     }
 
@@ -435,14 +546,9 @@ impl RttActiveTarget {
         note = "This function is deprecated and will be removed in a future version. Please use `poll_rtt_fallible` instead."
     )]
     pub fn poll_rtt(&mut self, core: &mut Core) -> HashMap<String, String> {
-        let defmt_state = self.defmt_state.as_ref();
         self.active_channels
             .iter_mut()
-            .filter_map(|active_channel| {
-                active_channel
-                    .get_rtt_data(core, defmt_state)
-                    .unwrap_or_default()
-            })
+            .filter_map(|active_channel| active_channel.get_rtt_data(core).unwrap_or_default())
             .collect::<HashMap<_, _>>()
     }
 
@@ -452,16 +558,50 @@ impl RttActiveTarget {
         &mut self,
         core: &mut Core,
     ) -> Result<HashMap<String, String>, anyhow::Error> {
-        let defmt_state = self.defmt_state.as_ref();
         let mut data = HashMap::new();
         for channel in self.active_channels.iter_mut() {
-            if let Some((channel, formatted_data)) = channel.get_rtt_data(core, defmt_state)? {
+            if let Some((channel, formatted_data)) = channel.get_rtt_data(core)? {
                 data.insert(channel, formatted_data);
             }
         }
         Ok(data)
     }
 
+    /// Polls the RTT target on all channels like [`RttActiveTarget::poll_rtt_fallible`], and attaches
+    /// to RTT again after the target was reset.
+    ///
+    /// [`RttPollEvent::TargetRebooted`] is returned once when the reset is detected. No data is
+    /// returned until the firmware has initialized RTT again.
+    pub fn poll_rtt_reattach(&mut self, core: &mut Core) -> Result<RttPollEvent, anyhow::Error> {
+        if !self.rebooted && self.rtt.is_stale(core)? {
+            self.rebooted = true;
+            return Ok(RttPollEvent::TargetRebooted);
+        }
+
+        if self.rebooted {
+            let options = match &self.attach_options {
+                Some(options) => options,
+                None => return Ok(RttPollEvent::Data(HashMap::new())),
+            };
+
+            match attach_to_rtt(
+                core,
+                &options.memory_map,
+                &options.rtt_scan_regions,
+                &options.elf_file,
+                &options.rtt_config,
+            ) {
+                Ok(target_rtt) => *self = target_rtt,
+                Err(error) => {
+                    log::debug!("RTT is not initialized again yet: {}", error);
+                    return Ok(RttPollEvent::Data(HashMap::new()));
+                }
+            }
+        }
+
+        self.poll_rtt_fallible(core).map(RttPollEvent::Data)
+    }
+
     // pub fn push_rtt(&mut self) {
     //     self.tabs[self.current_tab].push_rtt();
     // }
@@ -484,3 +624,100 @@ impl fmt::Debug for RttBuffer {
         self.0.fmt(f)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    use probe_rs::{Core, FakeProbe, MemoryInterface, Permissions};
+
+    use super::{attach_to_rtt, ElfKey, RttConfig, RttPollEvent};
+
+    /// Initialize a control block at 0x100 with one up channel, which contains `data`.
+    fn write_control_block(core: &mut Core, data: &[u8]) {
+        let mut control_block = b"SEGGER RTT\0\0\0\0\0\0".to_vec();
+
+        // 1 up and 0 down channels, the buffer of the up channel is at 0x200
+        for value in [1, 0, 0, 0x200, 64, data.len() as u32, 0, 0] {
+            control_block.extend_from_slice(&value.to_le_bytes());
+        }
+
+        core.write_8(0x200, data).unwrap();
+        core.write_8(0x100, &control_block).unwrap();
+    }
+
+    fn data(event: RttPollEvent) -> HashMap<String, String> {
+        match event {
+            RttPollEvent::Data(data) => data,
+            RttPollEvent::TargetRebooted => panic!("Unexpected reboot"),
+        }
+    }
+
+    #[test]
+    fn poll_rtt_reattach() {
+        let mut session = FakeProbe::with_mocked_memory(vec![0; 0x400])
+            .into_probe()
+            .attach("nrf51822_xxAC", Permissions::default())
+            .unwrap();
+        let mut core = session.core(0).unwrap();
+
+        write_control_block(&mut core, b"boot\n");
+
+        let mut target_rtt = attach_to_rtt(
+            &mut core,
+            &[],
+            &[0x100..0x200],
+            Path::new("missing.elf"),
+            &RttConfig::default(),
+        )
+        .unwrap();
+
+        let event = target_rtt.poll_rtt_reattach(&mut core).unwrap();
+        assert_eq!(data(event)["0"], "boot\n");
+
+        // The target is reset, and the control block isn't initialized yet
+        core.write_8(0x100, &[0; 16]).unwrap();
+
+        let event = target_rtt.poll_rtt_reattach(&mut core).unwrap();
+        assert!(matches!(event, RttPollEvent::TargetRebooted));
+
+        let event = target_rtt.poll_rtt_reattach(&mut core).unwrap();
+        assert!(data(event).is_empty());
+
+        // The firmware initializes the control block again
+        write_control_block(&mut core, b"boot again\n");
+
+        let event = target_rtt.poll_rtt_reattach(&mut core).unwrap();
+        assert_eq!(data(event)["0"], "boot again\n");
+
+        let event = target_rtt.poll_rtt_reattach(&mut core).unwrap();
+        assert!(data(event).is_empty());
+    }
+
+    #[test]
+    fn rtt_location_cache() {
+        let path = Path::new("firmware.elf");
+
+        let key = ElfKey::new(path, b"firmware");
+        assert_eq!(key.cached_location(), None);
+
+        key.cache_location(0x2000_0400);
+        assert_eq!(
+            ElfKey::new(path, b"firmware").cached_location(),
+            Some(0x2000_0400)
+        );
+
+        // The firmware was rebuilt
+        assert_eq!(
+            ElfKey::new(path, b"rebuilt firmware").cached_location(),
+            None
+        );
+
+        // Another file with the same contents
+        assert_eq!(
+            ElfKey::new(Path::new("other.elf"), b"firmware").cached_location(),
+            None
+        );
+    }
+}
@@ -1,6 +1,11 @@
 use super::memory::MemoryRegion;
-use crate::{serialize::hex_option, CoreType};
+use crate::{
+    serialize::{hex_option, hex_u_int},
+    CoreType,
+};
 use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
 /// A single chip variant.
 ///
 /// This describes an exact chip variant, including the cores, flash and memory size. For example,
@@ -27,6 +32,16 @@ pub struct Chip {
     /// [`ChipFamily::flash_algorithms`]: crate::ChipFamily::flash_algorithms
     #[serde(default)]
     pub flash_algorithms: Vec<String>,
+    /// SWJ sequences which are sent when attaching to the chip, e.g. to wake up
+    /// the debug port from the dormant state. Only used for ARM chips.
+    #[serde(default, skip_serializing_if = "SwjSequences::is_empty")]
+    pub swj_sequences: SwjSequences,
+    /// The memory ranges which are scanned for the RTT control block, if the
+    /// address isn't known from the ELF file. If empty, all RAM is scanned.
+    ///
+    /// This speeds up attaching to RTT on chips with a lot of RAM.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rtt_scan_regions: Vec<Range<u64>>,
 }
 
 impl Chip {
@@ -44,10 +59,45 @@ impl Chip {
             }],
             memory_map: vec![],
             flash_algorithms: vec![],
+            swj_sequences: SwjSequences::default(),
+            rtt_scan_regions: vec![],
         }
     }
 }
 
+/// A sequence of bits sent on SWDIO/TMS, with one clock cycle on SWCLK/TCK per bit.
+///
+/// This corresponds to the `DAP_SWJ_Sequence` function of the ARM debug sequences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwjSequence {
+    /// The number of bits to send, between 1 and 64.
+    pub bit_len: u8,
+    /// The bits to send, starting with the least significant bit.
+    #[serde(serialize_with = "hex_u_int")]
+    pub bits: u64,
+}
+
+/// SWJ sequences which are sent when attaching to a chip.
+///
+/// This allows bringing up chips which need e.g. a vendor specific wake up
+/// sequence, without a custom debug sequence.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwjSequences {
+    /// Sequences sent after the probe is attached, before the debug port is set up.
+    #[serde(default)]
+    pub before_attach: Vec<SwjSequence>,
+    /// Sequences sent after the debug port is set up, before the cores are attached.
+    #[serde(default)]
+    pub after_attach: Vec<SwjSequence>,
+}
+
+impl SwjSequences {
+    /// Returns `true` if no sequences are sent when attaching.
+    pub fn is_empty(&self) -> bool {
+        self.before_attach.is_empty() && self.after_attach.is_empty()
+    }
+}
+
 /// An individual core inside a chip
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Core {
@@ -70,6 +120,8 @@ pub enum CoreAccessOptions {
     Arm(ArmCoreAccessOptions),
     /// Riscv specific options
     Riscv(RiscvCoreAccessOptions),
+    /// Xtensa specific options
+    Xtensa(XtensaCoreAccessOptions),
 }
 
 /// The data required to access an ARM core
@@ -78,6 +130,11 @@ pub struct ArmCoreAccessOptions {
     /// The access port number to access the core
     pub ap: u8,
     /// The port select number to access the core
+    ///
+    /// For SWD multidrop targets (DPv2), this is the value written to the TARGETSEL
+    /// register to select the debug port of the core. It can also be given as `targetsel`.
+    /// A value of 0 means that the default debug port is used.
+    #[serde(alias = "targetsel")]
     pub psel: u32,
     /// The base address of the debug registers for the core.
     /// Required for Cortex-A, optional for Cortex-M
@@ -90,5 +147,23 @@ pub struct ArmCoreAccessOptions {
 }
 
 /// The data required to access a Risc-V core
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RiscvCoreAccessOptions {}
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RiscvCoreAccessOptions {
+    /// The index of the hart in the debug module, for debug modules with multiple harts
+    #[serde(default)]
+    pub hart_id: u32,
+}
+
+/// The data required to access an Xtensa core
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct XtensaCoreAccessOptions {
+    /// The interrupt level used for debug exceptions, as configured in the core.
+    ///
+    /// This is 6 for the Xtensa cores of the ESP32 and ESP32-S3.
+    #[serde(default = "default_xtensa_debug_level")]
+    pub debug_level: u8,
+}
+
+fn default_xtensa_debug_level() -> u8 {
+    6
+}
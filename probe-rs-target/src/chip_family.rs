@@ -42,6 +42,8 @@ pub enum CoreType {
     Armv8m,
     /// RISC-V
     Riscv,
+    /// Xtensa LX6 and LX7, as used by the ESP32 and ESP32-S3
+    Xtensa,
 }
 
 impl CoreType {
@@ -61,6 +63,8 @@ pub enum Architecture {
     Arm,
     /// A RISC-V core.
     Riscv,
+    /// An Xtensa core.
+    Xtensa,
 }
 
 impl CoreType {
@@ -68,6 +72,7 @@ impl CoreType {
     pub fn architecture(&self) -> Architecture {
         match self {
             CoreType::Riscv => Architecture::Riscv,
+            CoreType::Xtensa => Architecture::Xtensa,
             _ => Architecture::Arm,
         }
     }
@@ -86,6 +91,8 @@ pub enum InstructionSet {
     RV32,
     /// RISC-V 32-bit compressed instruction sets (RV32C) - covers all ISA variants that allow compressed 16-bit instructions.
     RV32C,
+    /// Xtensa instruction set, with 24-bit instructions and the 16-bit density option.
+    Xtensa,
 }
 
 impl InstructionSet {
@@ -100,11 +107,16 @@ impl InstructionSet {
             InstructionSet::A64 => 4,
             InstructionSet::RV32 => 4,
             InstructionSet::RV32C => 2,
+            InstructionSet::Xtensa => 2,
         }
     }
-    /// Get the maximum instruction size in bytes. All supported architectures have a maximum instruction size of 4 bytes.
+    /// Get the maximum instruction size in bytes. All supported architectures have a maximum instruction size of 4 bytes,
+    /// except Xtensa, which has a maximum instruction size of 3 bytes.
     pub fn get_maximum_instruction_size(&self) -> u8 {
-        4
+        match self {
+            InstructionSet::Xtensa => 3,
+            _ => 4,
+        }
     }
 }
 
@@ -185,6 +197,20 @@ impl ChipFamily {
                 ));
             }
 
+            // The SWJ sequences are sent using a single 64 bit value each.
+            if let Some(sequence) = variant
+                .swj_sequences
+                .before_attach
+                .iter()
+                .chain(variant.swj_sequences.after_attach.iter())
+                .find(|sequence| sequence.bit_len == 0 || sequence.bit_len > 64)
+            {
+                return Err(format!(
+                    "SWJ sequence of variant `{}` has an invalid length of {} bits, it must be between 1 and 64 bits",
+                    variant.name, sequence.bit_len
+                ));
+            }
+
             // Core specific validation logic based on type
             for core in variant.cores.iter() {
                 // The core access options must match the core type specified
@@ -223,6 +249,14 @@ impl ChipFamily {
                             ));
                         }
                     }
+                    CoreAccessOptions::Xtensa(_) => {
+                        if core.core_type != CoreType::Xtensa {
+                            return Err(format!(
+                                "Xtensa options don't match core type {:?} on core {}",
+                                core.core_type, core.name
+                            ));
+                        }
+                    }
                 }
             }
         }
@@ -39,6 +39,25 @@ pub struct RawFlashAlgorithm {
     /// Address of the `EraseAll()` entry point. Optional.
     #[serde(serialize_with = "hex_option")]
     pub pc_erase_all: Option<u64>,
+    /// Address of the `Read()` entry point. Optional.
+    ///
+    /// This is required for flash which is not memory mapped, or only visible
+    /// through a cache, so that its contents can't be read back directly.
+    #[serde(serialize_with = "hex_option")]
+    pub pc_read: Option<u64>,
+    /// Address of the `Verify()` entry point. Optional.
+    ///
+    /// If present, the flash algorithm is used to compare the flash with the written data,
+    /// which allows verifying flash that can't be read back by the debugger.
+    #[serde(serialize_with = "hex_option")]
+    pub pc_verify: Option<u64>,
+    /// The compression of the page data, which the `ProgramPage()` entry point accepts. Optional.
+    ///
+    /// If present, the data of each page is compressed before it is transferred to the target,
+    /// if that makes it smaller. `ProgramPage()` then gets the size of the compressed data as
+    /// fourth argument, or `0` if the data is not compressed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_compression: Option<PageCompression>,
     /// The offset from the start of RAM to the data section.
     #[serde(serialize_with = "hex_u_int")]
     pub data_section_offset: u64,
@@ -49,6 +68,14 @@ pub struct RawFlashAlgorithm {
     pub cores: Vec<String>,
 }
 
+/// A compression format for the page data, which is decompressed on the target by the flash algorithm.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PageCompression {
+    /// The LZ4 block format, without the frame header.
+    Lz4,
+}
+
 pub fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
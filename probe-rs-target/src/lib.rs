@@ -18,11 +18,14 @@ mod flash_properties;
 mod memory;
 pub(crate) mod serialize;
 
-pub use chip::{ArmCoreAccessOptions, Chip, Core, CoreAccessOptions, RiscvCoreAccessOptions};
+pub use chip::{
+    ArmCoreAccessOptions, Chip, Core, CoreAccessOptions, RiscvCoreAccessOptions, SwjSequence,
+    SwjSequences, XtensaCoreAccessOptions,
+};
 pub use chip_family::{
     Architecture, ChipFamily, CoreType, InstructionSet, TargetDescriptionSource,
 };
-pub use flash_algorithm::RawFlashAlgorithm;
+pub use flash_algorithm::{PageCompression, RawFlashAlgorithm};
 pub use flash_properties::FlashProperties;
 pub use memory::{
     GenericRegion, MemoryRange, MemoryRegion, NvmRegion, PageInfo, RamRegion, SectorDescription,
@@ -31,14 +31,17 @@ pub enum ApType {
     JtagComAp = 0x0,
     /// A AMBA based AHB3 AP (see E1.5).
     AmbaAhb3 = 0x1,
-    /// A AMBA based AHB2 and AHB3 AP (see E1.8).
-    AmbaAhb2Ahb3 = 0x2,
+    /// A AMBA based APB2 and APB3 AP (see E1.8).
+    AmbaApb2Apb3 = 0x2,
     /// A AMBA based AXI3 and AXI4 AP (see E1.2).
     AmbaAxi3Axi4 = 0x4,
     /// A AMBA based AHB5 AP (see E1.6).
     AmbaAhb5 = 0x5,
-    /// A AMBA based AHB4 AP (see E1.3).
-    AmbaAhb4 = 0x6,
+    /// A AMBA based APB4 and APB5 AP (see E1.9).
+    ///
+    /// This is the type of the APB-AP, which is commonly the root
+    /// of the debug infrastructure in ADIv6 based systems.
+    AmbaApb4Apb5 = 0x6,
     /// A AMBA based AXI5 AP (see E1.4).
     AmbaAxi5 = 0x7,
     /// A AMBA based protected AHB5 AP (see E1.7).
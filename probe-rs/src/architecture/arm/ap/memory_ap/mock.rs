@@ -1,7 +1,7 @@
 use anyhow::anyhow;
 
 use super::super::{ApAccess, Register};
-use super::{AddressIncrement, ApRegister, DataSize, CSW, DRW, TAR};
+use super::{AddressIncrement, ApRegister, DataSize, CSW, DRW, TAR, TAR2};
 use crate::architecture::arm::{ap::AccessPort, DpAddress};
 use crate::{
     architecture::arm::dp::{DebugPortError, DpAccess, DpRegister},
@@ -22,14 +22,24 @@ impl MockMemoryAp {
     /// writes do not clobber adjacent memory. The memory is also quite small so it can be feasibly
     /// printed out for debugging.
     pub fn with_pattern() -> Self {
+        Self::with_memory((1..=16).collect())
+    }
+
+    /// Creates a MockMemoryAp with the given memory contents, starting at address 0.
+    pub fn with_memory(memory: Vec<u8>) -> Self {
         let mut store = HashMap::new();
         store.insert(CSW::ADDRESS, 0);
         store.insert(TAR::ADDRESS, 0);
+        store.insert(TAR2::ADDRESS, 0);
         store.insert(DRW::ADDRESS, 0);
-        Self {
-            memory: (1..=16).collect(),
-            store,
-        }
+        Self { memory, store }
+    }
+
+    /// The upper 32 bits of the address, as written to the TAR2 register.
+    ///
+    /// The mocked memory is only indexed using the lower 32 bits of the address.
+    pub fn upper_address(&self) -> u32 {
+        self.store[&TAR2::ADDRESS]
     }
 }
 
@@ -117,6 +127,7 @@ impl ApAccess for MockMemoryAp {
             }
             CSW::ADDRESS => Ok(R::from(self.store[&R::ADDRESS])),
             TAR::ADDRESS => Ok(R::from(self.store[&R::ADDRESS])),
+            TAR2::ADDRESS => Ok(R::from(self.store[&R::ADDRESS])),
             _ => Err(anyhow!("MockMemoryAp: unknown register").into()),
         }
     }
@@ -196,6 +207,10 @@ impl ApAccess for MockMemoryAp {
                 self.store.insert(TAR::ADDRESS, value);
                 Ok(())
             }
+            TAR2::ADDRESS => {
+                self.store.insert(TAR2::ADDRESS, value);
+                Ok(())
+            }
             _ => Err(anyhow!("MockMemoryAp: unknown register").into()),
         }
     }
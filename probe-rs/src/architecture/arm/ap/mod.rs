@@ -145,7 +145,7 @@ pub trait ApAccess {
         R: ApRegister<PORT>;
 }
 
-impl<T: DapAccess> ApAccess for T {
+impl<T: DapAccess + ?Sized> ApAccess for T {
     fn read_ap_register<PORT, R>(&mut self, port: impl Into<PORT>) -> Result<R, DebugProbeError>
     where
         PORT: AccessPort,
@@ -0,0 +1,62 @@
+//! Debug authentication of locked devices.
+//!
+//! Some devices disable debug access in production, and only allow debugging again after a
+//! challenge-response handshake: The device sends a challenge, which usually contains a random
+//! nonce, and the debugger has to answer it with a response signed using a key known to the device.
+//!
+//! The handshake itself is device specific, and is run by the debug sequence of the target when
+//! attaching. The response has to be computed by the user, who owns the keys, using a
+//! [`DebugAuthenticator`] passed to the session with [`crate::Permissions::allow_debug_authentication`].
+
+use crate::Error;
+
+/// The protocol used for the debug authentication of a device.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthenticationProtocol {
+    /// The debug authentication of NXP devices using the debug mailbox, like the LPC55Sxx.
+    ///
+    /// The challenge is the Debug Authentication Challenge (DAC), and the response is the
+    /// Debug Authentication Response (DAR), as described in the user manual of the device.
+    NxpDebugMailbox,
+}
+
+/// A challenge sent by a locked device.
+#[derive(Debug, Clone)]
+pub struct AuthenticationChallenge {
+    /// The protocol used for the authentication, which defines the format of the challenge and the response.
+    pub protocol: AuthenticationProtocol,
+    /// The challenge, as sent by the device.
+    pub data: Vec<u8>,
+}
+
+/// Computes the response to the debug authentication challenge of a locked device.
+///
+/// This is implemented by the user, using the credentials required for the device. It is also
+/// implemented for closures, so a simple authenticator can be created like this:
+///
+/// ```
+/// use std::sync::Arc;
+/// use probe_rs::architecture::arm::AuthenticationChallenge;
+/// use probe_rs::Permissions;
+///
+/// let permissions = Permissions::new().allow_debug_authentication(Arc::new(
+///     |_challenge: &AuthenticationChallenge| -> Result<Vec<u8>, probe_rs::Error> {
+///         // Sign the challenge using the debug key
+///         Ok(vec![0; 4])
+///     },
+/// ));
+/// ```
+pub trait DebugAuthenticator: Send + Sync {
+    /// Compute the response to the given challenge.
+    fn respond(&self, challenge: &AuthenticationChallenge) -> Result<Vec<u8>, Error>;
+}
+
+impl<F> DebugAuthenticator for F
+where
+    F: Fn(&AuthenticationChallenge) -> Result<Vec<u8>, Error> + Send + Sync,
+{
+    fn respond(&self, challenge: &AuthenticationChallenge) -> Result<Vec<u8>, Error> {
+        self(challenge)
+    }
+}
@@ -3,7 +3,10 @@ use super::{
         valid_access_ports, AccessPort, ApAccess, ApClass, BaseaddrFormat, GenericAp, MemoryAp,
         BASE, BASE2, CFG, CSW, IDR,
     },
-    dp::{Abort, Ctrl, DebugPortError, DebugPortVersion, DpAccess, Select, DPIDR},
+    dp::{
+        Abort, Ctrl, DebugPortError, DebugPortVersion, DpAccess, Select, Select1, BASEPTR0,
+        BASEPTR1, DPIDR, DPIDR1,
+    },
     memory::{adi_v5_memory_interface::ADIMemoryInterface, Component},
     sequences::{ArmDebugSequence, DefaultArmSequence},
     ApAddress, DapAccess, DpAddress, PortType, RawDapAccess, SwoAccess, SwoConfig,
@@ -23,7 +26,7 @@ use std::{
 };
 
 /// An error with the DAP protocol occurred.
-#[derive(Debug, thiserror::Error, Clone, PartialEq, Eq)]
+#[derive(Debug, thiserror::Error, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum DapError {
     /// An error occurred during SWD communication.
     #[error("An error occurred in the SWD communication between probe and device.")]
@@ -137,15 +140,57 @@ impl ArmDebugState for Uninitialized {}
 
 impl ArmDebugState for Initialized {}
 
+/// Offset of the AP registers in the 4 KiB block of an APv2 access port (ADIv6).
+///
+/// The registers keep the addresses they had in ADIv5, relative to this offset.
+const APV2_REGISTER_OFFSET: u64 = 0xD00;
+
+/// Offset of the CIDR1 register of a component, which contains the component class.
+const CIDR1_OFFSET: u64 = 0xFF4;
+
+/// Offset of the DEVARCH register of a CoreSight component.
+const DEVARCH_OFFSET: u64 = 0xFBC;
+
+/// Offset of the DEVID register of a CoreSight component.
+const DEVID_OFFSET: u64 = 0xFC8;
+
+/// DEVARCH.ARCHID of a Class 0x9 ROM table.
+const ROM_TABLE_ARCHID: u32 = 0x0AF7;
+
+/// DEVARCH.ARCHID of an APv2 MEM-AP, without the revision bits.
+const MEM_AP_ARCHID: u32 = 0x0A17;
+
+/// DEVARCH.ARCHID of an APv2 JTAG-AP, without the revision bits.
+const JTAG_AP_ARCHID: u32 = 0x0A27;
+
+/// Maximum nesting depth of ROM tables in the debug address space.
+///
+/// This prevents endless loops for broken ROM tables.
+const MAX_ROM_TABLE_DEPTH: usize = 8;
+
 #[derive(Debug)]
 pub(crate) struct DpState {
-    pub _debug_port_version: DebugPortVersion,
+    pub debug_port_version: DebugPortVersion,
 
     pub current_dpbanksel: u8,
 
     pub current_apsel: u8,
     pub current_apbanksel: u8,
 
+    /// The address in the debug address space which is currently selected
+    /// by the SELECT and SELECT1 registers, with the lower 4 bits cleared.
+    ///
+    /// Only used for ADIv6, `None` if the value of the registers is unknown.
+    pub current_select_address: Option<u64>,
+
+    /// Size of the debug address space in bits (ADIv6 only).
+    pub address_size: u8,
+
+    /// Base addresses of the APs in the debug address space (ADIv6 only).
+    ///
+    /// The AP number of an [`ApAddress`] is used as an index into this list.
+    pub ap_base_addresses: Vec<u64>,
+
     /// Information about the APs of the target.
     /// APs are identified by a number, starting from zero.
     pub ap_information: Vec<ApInformation>,
@@ -154,13 +199,22 @@ pub(crate) struct DpState {
 impl DpState {
     pub fn new() -> Self {
         Self {
-            _debug_port_version: DebugPortVersion::Unsupported(0xFF),
+            debug_port_version: DebugPortVersion::Unsupported(0xFF),
             current_dpbanksel: 0,
             current_apsel: 0,
             current_apbanksel: 0,
+            current_select_address: None,
+            address_size: 32,
+            ap_base_addresses: Vec::new(),
             ap_information: Vec::new(),
         }
     }
+
+    /// Returns `true` if the DP implements ADIv6, where APs are
+    /// selected by their address in the debug address space.
+    pub fn is_adiv6(&self) -> bool {
+        self.debug_port_version == DebugPortVersion::DPv3
+    }
 }
 
 /// Information about an access port. Can be used for target discovery.
@@ -170,10 +224,15 @@ pub enum ApInformation {
     ///
     /// [ARM Debug Interface Architecture Specification]: https://developer.arm.com/documentation/ihi0031/d/
     MemoryAp(MemoryApInformation),
-    /// Information about an AP with an unknown class.
+    /// Information about an AP with an unknown class, e.g. a vendor specific AP like the CTRL-AP of Nordic devices.
+    ///
+    /// The registers of such an AP can be accessed using [`DapAccess::read_raw_ap_register`]
+    /// and [`DapAccess::write_raw_ap_register`].
     Other {
         /// Zero-based port number of the access port. This is used in the debug port to select an AP.
         address: ApAddress,
+        /// The identification register of the access port, which can be used to identify vendor specific APs.
+        idr: IDR,
     },
 }
 
@@ -241,6 +300,7 @@ impl ApInformation {
         } else {
             Ok(ApInformation::Other {
                 address: access_port.ap_address(),
+                idr,
             })
         }
     }
@@ -335,7 +395,10 @@ impl<S: ArmDebugState> SwdSequence for ArmCommunicationInterface<S> {
 }
 
 impl ArmCommunicationInterface<Uninitialized> {
-    pub(crate) fn new(probe: Box<dyn DapProbe>, use_overrun_detect: bool) -> Self {
+    /// Create a new interface for a probe with raw access to the DAP.
+    ///
+    /// This can be used by probe drivers to implement [`DebugProbe::try_get_arm_interface`].
+    pub fn new(probe: Box<dyn DapProbe>, use_overrun_detect: bool) -> Self {
         let state = Uninitialized { use_overrun_detect };
 
         Self { probe, state }
@@ -419,7 +482,7 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
 
                 Ok(Memory::new(adi_v5_memory_interface, access_port))
             }
-            ApInformation::Other { address } => Err(ProbeRsError::Other(anyhow!(format!(
+            ApInformation::Other { address, .. } => Err(ProbeRsError::Other(anyhow!(format!(
                 "AP {:x?} is not a memory AP",
                 address
             )))),
@@ -452,6 +515,22 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
             ctrl_reg.set_orun_detect(self.state.use_overrun_detect);
             self.write_dp_register(dp, ctrl_reg)?;
 
+            let dpidr: DPIDR = self.read_dp_register(dp)?;
+            let debug_port_version = DebugPortVersion::from(dpidr.version());
+
+            log::debug!("DP {:x?} is a {} debug port", dp, debug_port_version);
+
+            // note(unwrap): we have inserted the state above, it must exist.
+            self.state.dps.get_mut(&dp).unwrap().debug_port_version = debug_port_version;
+
+            if debug_port_version == DebugPortVersion::DPv3 {
+                let dpidr1: DPIDR1 = self.read_dp_register(dp)?;
+                self.state.dps.get_mut(&dp).unwrap().address_size = dpidr1.asize().max(32);
+
+                let ap_base_addresses = self.find_adiv6_access_ports(dp)?;
+                self.state.dps.get_mut(&dp).unwrap().ap_base_addresses = ap_base_addresses;
+            }
+
             /* determine the number and type of available APs */
             log::trace!("Searching valid APs");
 
@@ -480,12 +559,12 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
 
         // DP register addresses are 4 bank bits, 4 address bits. Lowest 2 address bits are
         // always 0, so this leaves only 4 possible addresses: 0x0, 0x4, 0x8, 0xC.
-        // Only address 0x4 is banked, the rest are don't care.
+        // Only address 0x4 is banked, the rest are don't care. In DPv3, address 0x0 is banked as well.
 
         let bank = dp_register_address >> 4;
         let addr = dp_register_address & 0xF;
 
-        if addr != 4 {
+        if addr != 4 && !(addr == 0 && dp_state.is_adiv6()) {
             return Ok(());
         }
 
@@ -496,8 +575,13 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
 
             log::debug!("Changing DP_BANK_SEL to {}", dp_state.current_dpbanksel);
 
-            select.set_ap_sel(dp_state.current_apsel);
-            select.set_ap_bank_sel(dp_state.current_apbanksel);
+            if dp_state.is_adiv6() {
+                let address = dp_state.current_select_address.unwrap_or(0);
+                select.set_addr((address as u32) >> 4);
+            } else {
+                select.set_ap_sel(dp_state.current_apsel);
+                select.set_ap_bank_sel(dp_state.current_apbanksel);
+            }
             select.set_dp_bank_sel(dp_state.current_dpbanksel);
 
             self.write_dp_register(dp, select)?;
@@ -516,6 +600,18 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
         // NOTE(unwrap): select_dp adds the dp to state if not present.
         let dp_state = self.state.dps.get_mut(&ap.dp).unwrap();
 
+        if dp_state.is_adiv6() {
+            let base_address = *dp_state
+                .ap_base_addresses
+                .get(ap.ap as usize)
+                .ok_or(DebugPortError::AccessPortNotFound(ap.ap))?;
+
+            return self.select_debug_address(
+                ap.dp,
+                base_address + APV2_REGISTER_OFFSET + u64::from(ap_register_address),
+            );
+        }
+
         let port = ap.ap;
         let ap_bank = ap_register_address >> 4;
 
@@ -550,6 +646,173 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
         Ok(())
     }
 
+    /// Select an address in the debug address space of an ADIv6 DP,
+    /// which is then accessed using AP accesses.
+    fn select_debug_address(&mut self, dp: DpAddress, address: u64) -> Result<(), DebugProbeError> {
+        // NOTE(unwrap): select_dp adds the dp to state if not present.
+        let dp_state = self.state.dps.get_mut(&dp).unwrap();
+
+        let address = address & !0xF;
+        let current_address = dp_state.current_select_address;
+
+        if current_address == Some(address) {
+            return Ok(());
+        }
+
+        if dp_state.address_size < 64 && address >> dp_state.address_size != 0 {
+            return Err(DebugPortError::AddressOutOfRange(address).into());
+        }
+
+        let large_address = dp_state.address_size > 32;
+
+        log::debug!("Changing selected address to {:#x}", address);
+
+        // Invalidate the cached address, in case one of the writes below fails.
+        dp_state.current_select_address = None;
+
+        if large_address && current_address.map(|current| current >> 32) != Some(address >> 32) {
+            let mut select1 = Select1(0);
+            select1.set_addr((address >> 32) as u32);
+            self.write_dp_register(dp, select1)?;
+        }
+
+        let dp_state = self.state.dps.get_mut(&dp).unwrap();
+
+        let mut select = Select(0);
+        select.set_addr((address as u32) >> 4);
+        select.set_dp_bank_sel(dp_state.current_dpbanksel);
+
+        self.write_dp_register(dp, select)?;
+
+        self.state.dps.get_mut(&dp).unwrap().current_select_address = Some(address);
+
+        Ok(())
+    }
+
+    /// Read a word from the debug address space of an ADIv6 DP.
+    fn read_debug_address(&mut self, dp: DpAddress, address: u64) -> Result<u32, DebugProbeError> {
+        self.select_debug_address(dp, address)?;
+
+        self.probe
+            .raw_read_register(PortType::AccessPort, (address & 0xC) as u8)
+    }
+
+    /// Find the APs of an ADIv6 DP, by walking the ROM tables in its debug address space,
+    /// starting at the address from the BASEPTR registers.
+    ///
+    /// Returns the base addresses of the APs, in the order in which they were found.
+    fn find_adiv6_access_ports(&mut self, dp: DpAddress) -> Result<Vec<u64>, DebugProbeError> {
+        let baseptr0: BASEPTR0 = self.read_dp_register(dp)?;
+
+        if !baseptr0.valid() {
+            log::warn!("DP {:x?} has no valid base pointer, unable to find APs", dp);
+            return Ok(Vec::new());
+        }
+
+        let mut base_address = u64::from(baseptr0.ptr()) << 12;
+
+        // NOTE(unwrap): select_dp adds the dp to state if not present.
+        if self.state.dps.get(&dp).unwrap().address_size > 32 {
+            let baseptr1: BASEPTR1 = self.read_dp_register(dp)?;
+            base_address |= u64::from(baseptr1.ptr()) << 32;
+        }
+
+        log::debug!("Searching APs starting at {:#x}", base_address);
+
+        let mut access_ports = Vec::new();
+        self.find_adiv6_access_ports_in_component(dp, base_address, 0, &mut access_ports)?;
+
+        Ok(access_ports)
+    }
+
+    /// Check if the component at `address` is an AP, or a ROM table containing further APs.
+    fn find_adiv6_access_ports_in_component(
+        &mut self,
+        dp: DpAddress,
+        address: u64,
+        depth: usize,
+        access_ports: &mut Vec<u64>,
+    ) -> Result<(), DebugProbeError> {
+        let cidr1 = self.read_debug_address(dp, address + CIDR1_OFFSET)?;
+
+        // Number of entries and size of an entry in bytes.
+        let (max_entries, entry_size) = match (cidr1 >> 4) & 0xF {
+            // Class 0x1 ROM table
+            0x1 => (960, 4),
+            // CoreSight component
+            0x9 => {
+                let devarch = self.read_debug_address(dp, address + DEVARCH_OFFSET)?;
+                let archid = devarch & 0xFFFF;
+
+                if archid & 0xFFF == MEM_AP_ARCHID || archid & 0xFFF == JTAG_AP_ARCHID {
+                    log::debug!("Found AP at {:#x}", address);
+                    access_ports.push(address);
+                    return Ok(());
+                }
+
+                if archid != ROM_TABLE_ARCHID {
+                    return Ok(());
+                }
+
+                // DEVID.FORMAT indicates if the ROM table uses 32-bit or 64-bit entries.
+                let devid = self.read_debug_address(dp, address + DEVID_OFFSET)?;
+
+                if devid & 0xF == 1 {
+                    (256, 8)
+                } else {
+                    (512, 4)
+                }
+            }
+            _ => return Ok(()),
+        };
+
+        if depth >= MAX_ROM_TABLE_DEPTH {
+            log::warn!(
+                "ROM table at {:#x} is nested too deeply, skipping it",
+                address
+            );
+            return Ok(());
+        }
+
+        for index in 0..max_entries {
+            let entry_address = address + index * entry_size;
+
+            let mut entry = u64::from(self.read_debug_address(dp, entry_address)?);
+
+            if entry_size == 8 {
+                entry |= u64::from(self.read_debug_address(dp, entry_address + 4)?) << 32;
+            }
+
+            // A zero entry marks the end of the table.
+            if entry == 0 {
+                break;
+            }
+
+            // Bit 0 indicates that the component is present, bit 1 that the entry is valid.
+            if entry & 0b11 != 0b11 {
+                continue;
+            }
+
+            // The offset is a signed value, relative to the base address of the ROM table.
+            let offset = if entry_size == 4 {
+                entry as u32 as i32 as i64 as u64
+            } else {
+                entry
+            } & !0xFFF;
+
+            let component_address = address.wrapping_add(offset);
+
+            self.find_adiv6_access_ports_in_component(
+                dp,
+                component_address,
+                depth + 1,
+                access_ports,
+            )?;
+        }
+
+        Ok(())
+    }
+
     /// Determine the type and additional information about an AP.
     pub(crate) fn ap_information(
         &mut self,
@@ -0,0 +1,97 @@
+//! Module for using the CTI.
+//!
+//! CTI = Cross Trigger Interface
+//!
+//! A CTI connects the debug events of a core, like entering the halted state, to the
+//! channels of the cross trigger matrix (CTM), which are shared by the CTIs of all cores.
+//! This allows halting and restarting several cores at the same time.
+
+use super::super::memory::romtable::CoresightComponent;
+use crate::architecture::arm::ArmProbeInterface;
+use crate::Error;
+
+const REGISTER_OFFSET_CONTROL: u32 = 0x000;
+const REGISTER_OFFSET_INTACK: u32 = 0x010;
+const REGISTER_OFFSET_APPPULSE: u32 = 0x01C;
+const REGISTER_OFFSET_INEN: u32 = 0x020;
+const REGISTER_OFFSET_OUTEN: u32 = 0x0A0;
+const REGISTER_OFFSET_TRIGOUTSTATUS: u32 = 0x134;
+const REGISTER_OFFSET_GATE: u32 = 0x140;
+const REGISTER_OFFSET_ACCESS: u32 = 0xFB0;
+
+/// CTICONTROL.GLBEN
+const CONTROL_ENABLE: u32 = 1 << 0;
+
+/// An interface to control the CTI (Cross Trigger Interface) of a core.
+pub struct Cti<'a> {
+    component: &'a CoresightComponent,
+    interface: &'a mut Box<dyn ArmProbeInterface>,
+}
+
+impl<'a> Cti<'a> {
+    /// Create a new CTI interface from a probe and a ROM table component.
+    pub fn new(
+        interface: &'a mut Box<dyn ArmProbeInterface>,
+        component: &'a CoresightComponent,
+    ) -> Self {
+        Cti {
+            interface,
+            component,
+        }
+    }
+
+    /// Unlock the CTI registers for writing.
+    pub fn unlock(&mut self) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_ACCESS, 0xC5AC_CE55)?;
+
+        Ok(())
+    }
+
+    /// Enable or disable the mapping of triggers to channels.
+    pub fn set_enabled(&mut self, enabled: bool) -> Result<(), Error> {
+        let control = if enabled { CONTROL_ENABLE } else { 0 };
+
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_CONTROL, control)
+    }
+
+    /// Connect the trigger input `trigger` to the channels set in the bitmask `channels`.
+    pub fn map_trigger_input(&mut self, trigger: u32, channels: u32) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_INEN + 4 * trigger, channels)
+    }
+
+    /// Connect the channels set in the bitmask `channels` to the trigger output `trigger`.
+    pub fn map_trigger_output(&mut self, trigger: u32, channels: u32) -> Result<(), Error> {
+        self.component.write_reg(
+            self.interface,
+            REGISTER_OFFSET_OUTEN + 4 * trigger,
+            channels,
+        )
+    }
+
+    /// Select the channels, as a bitmask, which are propagated to and from the cross trigger matrix.
+    pub fn set_channel_gate(&mut self, channels: u32) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_GATE, channels)
+    }
+
+    /// Generate a single pulse on the channels set in the bitmask `channels`.
+    pub fn pulse_channels(&mut self, channels: u32) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_APPPULSE, channels)
+    }
+
+    /// Acknowledge the trigger outputs set in the bitmask `triggers`, which deasserts them.
+    pub fn acknowledge(&mut self, triggers: u32) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_INTACK, triggers)
+    }
+
+    /// Read the status of the trigger outputs, as a bitmask.
+    pub fn trigger_output_status(&mut self) -> Result<u32, Error> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_TRIGOUTSTATUS)
+    }
+}
@@ -93,6 +93,51 @@ impl<'a> Dwt<'a> {
         ctrl.set_exctrcena(false);
         ctrl.store(self.component, self.interface)
     }
+
+    /// Enable periodic PC sampling.
+    ///
+    /// A PC sample is sent roughly every `interval` core clock cycles. The DWT only supports
+    /// multiples of 64 cycles up to 1024 cycles, and multiples of 1024 cycles up to 16384 cycles,
+    /// so the closest supported interval is used.
+    pub fn enable_pc_sampling(&mut self, interval: u32) -> Result<(), Error> {
+        let (cyctap, postpreset) = pc_sampling_config(interval);
+
+        let mut ctrl = Ctrl::load(self.component, self.interface)?;
+
+        // POSTPRESET must not be changed while the counter is running.
+        ctrl.set_pcsamplena(false);
+        ctrl.store(self.component, self.interface)?;
+
+        ctrl.set_cyctap(cyctap);
+        ctrl.set_postpreset(postpreset);
+        ctrl.set_cyccntena(true);
+        ctrl.set_pcsamplena(true);
+        ctrl.store(self.component, self.interface)
+    }
+
+    /// Disable periodic PC sampling.
+    pub fn disable_pc_sampling(&mut self) -> Result<(), Error> {
+        let mut ctrl = Ctrl::load(self.component, self.interface)?;
+        ctrl.set_pcsamplena(false);
+        ctrl.store(self.component, self.interface)
+    }
+}
+
+/// Calculate the CYCTAP and POSTPRESET values for a PC sampling interval in cycles.
+///
+/// The POSTCNT counter is decremented on every change of the selected CYCCNT tap bit,
+/// i.e. every 64 cycles for bit 6 and every 1024 cycles for bit 10, and a sample is
+/// sent when it reaches zero and is reloaded with POSTPRESET.
+fn pc_sampling_config(interval: u32) -> (bool, u32) {
+    let (cyctap, tap_cycles) = if interval <= 16 * 64 {
+        (false, 64)
+    } else {
+        (true, 1024)
+    };
+
+    let postpreset = (interval.saturating_add(tap_cycles / 2) / tap_cycles).clamp(1, 16) - 1;
+
+    (cyctap, postpreset)
 }
 
 bitfield! {
@@ -292,3 +337,17 @@ impl DebugRegister for Function {
     const ADDRESS: u32 = 0x28;
     const NAME: &'static str = "DWT/FUNCTION";
 }
+
+#[cfg(test)]
+mod test {
+    use super::pc_sampling_config;
+
+    #[test]
+    fn pc_sampling_interval() {
+        assert_eq!(pc_sampling_config(0), (false, 0));
+        assert_eq!(pc_sampling_config(64), (false, 0));
+        assert_eq!(pc_sampling_config(1000), (false, 15));
+        assert_eq!(pc_sampling_config(4096), (true, 3));
+        assert_eq!(pc_sampling_config(u32::MAX), (true, 15));
+    }
+}
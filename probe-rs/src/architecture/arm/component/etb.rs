@@ -0,0 +1,141 @@
+//! Module for using the ETB.
+//!
+//! ETB = Embedded Trace Buffer
+//!
+//! The ETB stores the formatted trace data of all trace sources in a circular buffer
+//! in on-chip RAM, which can be read out over the debug interface.
+
+use super::super::memory::romtable::CoresightComponent;
+use crate::architecture::arm::ArmProbeInterface;
+use crate::Error;
+
+const REGISTER_OFFSET_RDP: u32 = 0x004;
+const REGISTER_OFFSET_STS: u32 = 0x00C;
+const REGISTER_OFFSET_RRD: u32 = 0x010;
+const REGISTER_OFFSET_RRP: u32 = 0x014;
+const REGISTER_OFFSET_RWP: u32 = 0x018;
+const REGISTER_OFFSET_CTL: u32 = 0x020;
+const REGISTER_OFFSET_FFSR: u32 = 0x300;
+const REGISTER_OFFSET_FFCR: u32 = 0x304;
+const REGISTER_OFFSET_ACCESS: u32 = 0xFB0;
+
+/// STS.Full, set when the write pointer wrapped around.
+const STS_FULL: u32 = 1 << 0;
+/// FFSR.FtStopped, set when the formatter stopped.
+const FFSR_STOPPED: u32 = 1 << 1;
+/// FFCR.EnFTC | FFCR.EnFCont, enable continuous formatting.
+const FFCR_FORMATTING: u32 = 0b11;
+/// FFCR.StopFl | FFCR.FOnMan, stop the formatter after a manual flush.
+const FFCR_FLUSH_AND_STOP: u32 = (1 << 12) | (1 << 6);
+
+/// An interface to control the ETB (Embedded Trace Buffer) of a MCU.
+pub struct Etb<'a> {
+    component: &'a CoresightComponent,
+    interface: &'a mut Box<dyn ArmProbeInterface>,
+}
+
+impl<'a> Etb<'a> {
+    /// Create a new ETB interface from a probe and a ROM table component.
+    pub fn new(
+        interface: &'a mut Box<dyn ArmProbeInterface>,
+        component: &'a CoresightComponent,
+    ) -> Self {
+        Etb {
+            interface,
+            component,
+        }
+    }
+
+    /// Unlock the ETB registers for writing.
+    pub fn unlock(&mut self) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_ACCESS, 0xC5AC_CE55)?;
+
+        Ok(())
+    }
+
+    /// Get the size of the trace buffer in bytes.
+    pub fn size(&mut self) -> Result<u32, Error> {
+        let depth = self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_RDP)?;
+
+        Ok(depth * core::mem::size_of::<u32>() as u32)
+    }
+
+    /// Clear the trace buffer and start capturing formatted trace data.
+    pub fn enable_capture(&mut self) -> Result<(), Error> {
+        self.unlock()?;
+
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_CTL, 0)?;
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_RWP, 0)?;
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_FFCR, FFCR_FORMATTING)?;
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_CTL, 1)?;
+
+        Ok(())
+    }
+
+    /// Flush the formatter and stop capturing trace data.
+    pub fn disable_capture(&mut self) -> Result<(), Error> {
+        self.component.write_reg(
+            self.interface,
+            REGISTER_OFFSET_FFCR,
+            FFCR_FORMATTING | FFCR_FLUSH_AND_STOP,
+        )?;
+
+        // The formatter stops after the flush has been completed.
+        for _ in 0..1000 {
+            let ffsr = self
+                .component
+                .read_reg(self.interface, REGISTER_OFFSET_FFSR)?;
+
+            if ffsr & FFSR_STOPPED != 0 {
+                break;
+            }
+        }
+
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_CTL, 0)?;
+
+        Ok(())
+    }
+
+    /// Read the captured trace data, oldest data first.
+    ///
+    /// The capture has to be stopped using [`Etb::disable_capture`] before.
+    pub fn read(&mut self) -> Result<Vec<u8>, Error> {
+        let depth = self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_RDP)?;
+        let write_pointer = self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_RWP)?;
+        let status = self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_STS)?;
+
+        // If the buffer wrapped around, the oldest data is at the write pointer.
+        let (start, words) = if status & STS_FULL != 0 {
+            (write_pointer, depth)
+        } else {
+            (0, write_pointer)
+        };
+
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_RRP, start)?;
+
+        let mut data = Vec::with_capacity(words as usize * 4);
+        for _ in 0..words {
+            let word = self
+                .component
+                .read_reg(self.interface, REGISTER_OFFSET_RRD)?;
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+
+        Ok(data)
+    }
+}
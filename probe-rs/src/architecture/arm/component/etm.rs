@@ -0,0 +1,202 @@
+//! Module for using the ETM.
+//!
+//! ETM = Embedded Trace Macrocell
+//!
+//! The ETM generates a compressed trace of the executed instructions. Both the ETMv3
+//! used by the Cortex-M3 and Cortex-M4, and the ETMv4 used by the Cortex-M7, Cortex-M33
+//! and Cortex-M55 are supported. The ETM is configured to trace all instructions,
+//! without any filters, triggers or timestamps.
+
+use std::time::{Duration, Instant};
+
+use super::super::memory::romtable::CoresightComponent;
+use crate::architecture::arm::ArmProbeInterface;
+use crate::{DebugProbeError, Error};
+
+const REGISTER_OFFSET_ACCESS: u32 = 0xFB0;
+
+/// Register offset of ETMIDR for ETMv3, and of TRCIDR1 for ETMv4.
+///
+/// Both contain the major architecture version in bits 11:8.
+const REGISTER_OFFSET_IDR: u32 = 0x1E4;
+
+const ETMV3_CR: u32 = 0x000;
+const ETMV3_SR: u32 = 0x010;
+const ETMV3_TEEVR: u32 = 0x020;
+const ETMV3_TECR1: u32 = 0x024;
+const ETMV3_TRACEIDR: u32 = 0x200;
+
+/// ETMCR.PowerDown
+const ETMV3_CR_POWER_DOWN: u32 = 1 << 0;
+/// ETMCR.ETMProgramming
+const ETMV3_CR_PROGRAMMING: u32 = 1 << 10;
+/// ETMSR.ProgBit
+const ETMV3_SR_PROGRAMMING: u32 = 1 << 1;
+/// The event which is always true, used to enable tracing unconditionally.
+const ETMV3_EVENT_ALWAYS: u32 = 0x6F;
+/// TECR1.ExcludeInclude, set to exclude no address ranges, which traces everything.
+const ETMV3_TECR1_EXCLUDE: u32 = 1 << 24;
+
+const ETMV4_PRGCTLR: u32 = 0x004;
+const ETMV4_STATR: u32 = 0x00C;
+const ETMV4_CONFIGR: u32 = 0x010;
+const ETMV4_EVENTCTL0R: u32 = 0x020;
+const ETMV4_EVENTCTL1R: u32 = 0x024;
+const ETMV4_STALLCTLR: u32 = 0x02C;
+const ETMV4_TSCTLR: u32 = 0x030;
+const ETMV4_TRACEIDR: u32 = 0x040;
+const ETMV4_VICTLR: u32 = 0x080;
+const ETMV4_VIIECTLR: u32 = 0x084;
+const ETMV4_VISSCTLR: u32 = 0x088;
+const ETMV4_OSLAR: u32 = 0x300;
+
+/// TRCSTATR.IDLE
+const ETMV4_STATR_IDLE: u32 = 1 << 0;
+/// TRCVICTLR with resource 1 (always true) selected as the ViewInst event,
+/// and the start/stop logic in the started state.
+const ETMV4_VICTLR_ALWAYS: u32 = 0x01 | (1 << 9);
+
+/// The time to wait for the ETM to enter or leave the programming state.
+const PROGRAMMING_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The architecture version of an ETM.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EtmVersion {
+    /// ETMv3, used by the Cortex-M3 and Cortex-M4.
+    V3,
+    /// ETMv4, used by the Cortex-M7, Cortex-M33 and Cortex-M55.
+    V4,
+}
+
+/// An interface to control the ETM (Embedded Trace Macrocell) of a MCU.
+pub struct Etm<'a> {
+    component: &'a CoresightComponent,
+    interface: &'a mut Box<dyn ArmProbeInterface>,
+}
+
+impl<'a> Etm<'a> {
+    /// Create a new ETM interface from a probe and a ROM table component.
+    pub fn new(
+        interface: &'a mut Box<dyn ArmProbeInterface>,
+        component: &'a CoresightComponent,
+    ) -> Self {
+        Etm {
+            interface,
+            component,
+        }
+    }
+
+    /// Unlock the ETM registers for writing.
+    pub fn unlock(&mut self) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_ACCESS, 0xC5AC_CE55)?;
+
+        Ok(())
+    }
+
+    /// Read the architecture version of the ETM.
+    pub fn version(&mut self) -> Result<EtmVersion, Error> {
+        let idr = self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_IDR)?;
+
+        match (idr >> 8) & 0xf {
+            0b0100 => Ok(EtmVersion::V4),
+            // ETMv3 reports 0b0010 for the major version.
+            _ => Ok(EtmVersion::V3),
+        }
+    }
+
+    /// Enable tracing of all instructions, with the given trace ID (ATID).
+    ///
+    /// The trace ID is used to find the trace data of the ETM in the formatted trace stream.
+    pub fn enable(&mut self, trace_id: u8) -> Result<(), Error> {
+        self.unlock()?;
+
+        match self.version()? {
+            EtmVersion::V3 => self.enable_v3(trace_id),
+            EtmVersion::V4 => self.enable_v4(trace_id),
+        }
+    }
+
+    /// Stop tracing.
+    pub fn disable(&mut self) -> Result<(), Error> {
+        match self.version()? {
+            EtmVersion::V3 => {
+                let cr = self.component.read_reg(self.interface, ETMV3_CR)?;
+                self.component.write_reg(
+                    self.interface,
+                    ETMV3_CR,
+                    cr | ETMV3_CR_PROGRAMMING | ETMV3_CR_POWER_DOWN,
+                )
+            }
+            EtmVersion::V4 => self.component.write_reg(self.interface, ETMV4_PRGCTLR, 0),
+        }
+    }
+
+    fn enable_v3(&mut self, trace_id: u8) -> Result<(), Error> {
+        // Power up the ETM, and enter the programming state.
+        let mut cr = self.component.read_reg(self.interface, ETMV3_CR)?;
+        cr &= !ETMV3_CR_POWER_DOWN;
+        cr |= ETMV3_CR_PROGRAMMING;
+        self.component.write_reg(self.interface, ETMV3_CR, cr)?;
+
+        self.wait_for_status(ETMV3_SR, ETMV3_SR_PROGRAMMING, true)?;
+
+        self.component
+            .write_reg(self.interface, ETMV3_TRACEIDR, trace_id as u32)?;
+        self.component
+            .write_reg(self.interface, ETMV3_TEEVR, ETMV3_EVENT_ALWAYS)?;
+        self.component
+            .write_reg(self.interface, ETMV3_TECR1, ETMV3_TECR1_EXCLUDE)?;
+
+        // Leave the programming state, which starts the trace.
+        cr &= !ETMV3_CR_PROGRAMMING;
+        self.component.write_reg(self.interface, ETMV3_CR, cr)?;
+
+        self.wait_for_status(ETMV3_SR, ETMV3_SR_PROGRAMMING, false)
+    }
+
+    fn enable_v4(&mut self, trace_id: u8) -> Result<(), Error> {
+        // Clear the OS lock, which is set after a power-on reset.
+        self.component.write_reg(self.interface, ETMV4_OSLAR, 0)?;
+
+        self.component.write_reg(self.interface, ETMV4_PRGCTLR, 0)?;
+        self.wait_for_status(ETMV4_STATR, ETMV4_STATR_IDLE, true)?;
+
+        self.component.write_reg(self.interface, ETMV4_CONFIGR, 0)?;
+        self.component
+            .write_reg(self.interface, ETMV4_EVENTCTL0R, 0)?;
+        self.component
+            .write_reg(self.interface, ETMV4_EVENTCTL1R, 0)?;
+        self.component
+            .write_reg(self.interface, ETMV4_STALLCTLR, 0)?;
+        self.component.write_reg(self.interface, ETMV4_TSCTLR, 0)?;
+        self.component
+            .write_reg(self.interface, ETMV4_TRACEIDR, trace_id as u32)?;
+        self.component
+            .write_reg(self.interface, ETMV4_VICTLR, ETMV4_VICTLR_ALWAYS)?;
+        self.component
+            .write_reg(self.interface, ETMV4_VIIECTLR, 0)?;
+        self.component
+            .write_reg(self.interface, ETMV4_VISSCTLR, 0)?;
+
+        self.component.write_reg(self.interface, ETMV4_PRGCTLR, 1)?;
+        self.wait_for_status(ETMV4_STATR, ETMV4_STATR_IDLE, false)
+    }
+
+    /// Wait until the bit `mask` of the status register at `offset` has the value `set`.
+    fn wait_for_status(&mut self, offset: u32, mask: u32, set: bool) -> Result<(), Error> {
+        let start = Instant::now();
+
+        while start.elapsed() < PROGRAMMING_TIMEOUT {
+            let status = self.component.read_reg(self.interface, offset)?;
+
+            if (status & mask != 0) == set {
+                return Ok(());
+            }
+        }
+
+        Err(Error::Probe(DebugProbeError::Timeout))
+    }
+}
@@ -83,6 +83,30 @@ impl<'a> Itm<'a> {
 
         Ok(())
     }
+
+    /// Enable the given stimulus ports, and disable all others.
+    ///
+    /// Bit `n` of `ports` enables stimulus port `n`.
+    pub fn set_stimulus_ports(&mut self, ports: u32) -> Result<(), Error> {
+        self.component
+            .write_reg(self.interface, register::ITM_TER::ADDRESS, ports)
+    }
+
+    /// Enable or disable the generation of local timestamps.
+    pub fn set_local_timestamps(&mut self, enabled: bool) -> Result<(), Error> {
+        let mut value = self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_ITM_TCR)?;
+
+        if enabled {
+            value |= 1 << 1;
+        } else {
+            value &= !(1 << 1);
+        }
+
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_ITM_TCR, value)
+    }
 }
 
 mod register {
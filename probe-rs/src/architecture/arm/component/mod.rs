@@ -1,19 +1,29 @@
 //! Types and functions for interacting with CoreSight Components
 
+mod cti;
 mod dwt;
+mod etb;
+mod etm;
 mod itm;
+mod mtb;
 mod swo;
 mod tmc;
 mod tpiu;
 mod trace_funnel;
 
 use super::memory::romtable::{CoresightComponent, PeripheralType, RomTableError};
+use crate::architecture::arm::ap::AccessPort;
 use crate::architecture::arm::core::armv6m::Demcr;
-use crate::architecture::arm::{ArmProbeInterface, SwoConfig, SwoMode};
+use crate::architecture::arm::core::armv7m::Dhcsr;
+use crate::architecture::arm::{ApAddress, ArmProbeInterface, SwoConfig, SwoMode};
 use crate::{Core, Error, MemoryInterface, MemoryMappedRegister};
 
+pub use cti::Cti;
 pub use dwt::Dwt;
+pub use etb::Etb;
+pub use etm::{Etm, EtmVersion};
 pub use itm::Itm;
+pub use mtb::{decode_mtb_packets, executed_ranges, ExecutedRange, Mtb, MtbPacket};
 pub use swo::Swo;
 pub use tmc::TraceMemoryController;
 pub use tpiu::Tpiu;
@@ -288,6 +298,105 @@ pub(crate) fn read_trace_memory(
     Ok(itm_trace)
 }
 
+/// The trace ID (ATID) used for the ETM.
+const ETM_TRACE_ID: u8 = 0x10;
+
+/// Configures the ETM to trace all instructions into the on-chip trace buffer.
+///
+/// The ETB is used if available, otherwise the TMC is configured as a circular buffer.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn setup_etm_trace(
+    interface: &mut Box<dyn ArmProbeInterface>,
+    components: &[CoresightComponent],
+) -> Result<(), Error> {
+    // Start the capture first, so the start of the trace is not lost.
+    if let Ok(component) = find_component(components, PeripheralType::Etb) {
+        Etb::new(interface, component).enable_capture()?;
+    } else {
+        let mut tmc =
+            TraceMemoryController::new(interface, find_component(components, PeripheralType::Tmc)?);
+
+        tmc.disable_capture()?;
+        while !tmc.ready()? {}
+
+        tmc.set_mode(tmc::Mode::Circular)?;
+        tmc.enable_formatting()?;
+
+        tmc.enable_capture()?;
+    }
+
+    let mut etm = Etm::new(interface, find_component(components, PeripheralType::Etm)?);
+    etm.enable(ETM_TRACE_ID)
+}
+
+/// Stops the ETM trace, and reads the captured instruction trace from the on-chip trace buffer.
+///
+/// The returned data only contains the trace stream of the ETM, with the
+/// formatting of the trace buffer removed.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn read_etm_trace(
+    interface: &mut Box<dyn ArmProbeInterface>,
+    components: &[CoresightComponent],
+) -> Result<Vec<u8>, Error> {
+    Etm::new(interface, find_component(components, PeripheralType::Etm)?).disable()?;
+
+    let formatted = if let Ok(component) = find_component(components, PeripheralType::Etb) {
+        let mut etb = Etb::new(interface, component);
+        etb.disable_capture()?;
+        etb.read()?
+    } else {
+        let mut tmc =
+            TraceMemoryController::new(interface, find_component(components, PeripheralType::Tmc)?);
+
+        tmc.stop_on_flush(true)?;
+        tmc.manual_flush()?;
+        while !tmc.ready()? {}
+
+        tmc.disable_capture()?;
+        tmc.read_circular_buffer()?
+    };
+
+    let mut id = 0.into();
+    let mut etm_trace = Vec::new();
+
+    for frame_buffer in formatted.chunks_exact(16) {
+        let mut frame = tmc::Frame::new(frame_buffer, id);
+        for (id, data) in &mut frame {
+            if u8::from(id) == ETM_TRACE_ID {
+                etm_trace.push(data);
+            }
+        }
+        id = frame.id();
+    }
+
+    Ok(etm_trace)
+}
+
+/// Clears the MTB and starts tracing into a buffer of `size` bytes.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn setup_mtb(
+    interface: &mut Box<dyn ArmProbeInterface>,
+    components: &[CoresightComponent],
+    size: u32,
+) -> Result<(), Error> {
+    let mut mtb = Mtb::new(interface, find_component(components, PeripheralType::Mtb)?);
+    mtb.enable(size)
+}
+
+/// Reads the packets recorded by the MTB, oldest first.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn read_mtb(
+    interface: &mut Box<dyn ArmProbeInterface>,
+    components: &[CoresightComponent],
+) -> Result<Vec<MtbPacket>, Error> {
+    let mut mtb = Mtb::new(interface, find_component(components, PeripheralType::Mtb)?);
+    mtb.read()
+}
+
 /// Configures DWT trace unit `unit` to begin tracing `address`.
 ///
 ///
@@ -315,6 +424,64 @@ pub fn remove_swv_data_trace(
     dwt.disable_data_trace(unit)
 }
 
+/// Enables the ITM stimulus ports given by the bits of `ports`, and disables all others.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn set_itm_stimulus_ports(
+    interface: &mut Box<dyn ArmProbeInterface>,
+    components: &[CoresightComponent],
+    ports: u32,
+) -> Result<(), Error> {
+    let mut itm = Itm::new(interface, find_component(components, PeripheralType::Itm)?);
+    itm.set_stimulus_ports(ports)
+}
+
+/// Enables or disables the generation of local timestamps by the ITM.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn set_itm_local_timestamps(
+    interface: &mut Box<dyn ArmProbeInterface>,
+    components: &[CoresightComponent],
+    enabled: bool,
+) -> Result<(), Error> {
+    let mut itm = Itm::new(interface, find_component(components, PeripheralType::Itm)?);
+    itm.set_local_timestamps(enabled)
+}
+
+/// Enables or disables exception tracing by the DWT.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn set_exception_trace(
+    interface: &mut Box<dyn ArmProbeInterface>,
+    components: &[CoresightComponent],
+    enabled: bool,
+) -> Result<(), Error> {
+    let mut dwt = Dwt::new(interface, find_component(components, PeripheralType::Dwt)?);
+
+    if enabled {
+        dwt.enable_exception_trace()
+    } else {
+        dwt.disable_exception_trace()
+    }
+}
+
+/// Enables periodic PC sampling by the DWT, with a sample roughly every `interval` cycles,
+/// or disables it if `interval` is `None`.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn set_pc_sampling(
+    interface: &mut Box<dyn ArmProbeInterface>,
+    components: &[CoresightComponent],
+    interval: Option<u32>,
+) -> Result<(), Error> {
+    let mut dwt = Dwt::new(interface, find_component(components, PeripheralType::Dwt)?);
+
+    match interval {
+        Some(interval) => dwt.enable_pc_sampling(interval),
+        None => dwt.disable_pc_sampling(),
+    }
+}
+
 /// Sets TRCENA in DEMCR to begin trace generation.
 pub fn enable_tracing(core: &mut Core) -> Result<(), Error> {
     let mut demcr = Demcr(core.read_word_32(Demcr::ADDRESS)?);
@@ -330,3 +497,289 @@ pub fn disable_swv(core: &mut Core) -> Result<(), Error> {
     core.write_word_32(Demcr::ADDRESS, demcr.into())?;
     Ok(())
 }
+
+/// The CTM channel which is used to halt all cores.
+const CTI_CHANNEL_HALT: u32 = 1 << 0;
+/// The CTM channel which is used to restart all cores.
+const CTI_CHANNEL_RESTART: u32 = 1 << 1;
+/// The CTI trigger input which is asserted when the core is halted.
+const CTI_TRIGGER_IN_HALTED: u32 = 0;
+/// The CTI trigger output which requests the core to halt (EDBGRQ).
+const CTI_TRIGGER_OUT_HALT: u32 = 0;
+/// The CTI trigger output which requests the core to restart (DBGRESTART).
+const CTI_TRIGGER_OUT_RESTART: u32 = 1;
+
+/// Finds the CTI of each core.
+///
+/// `cores` contains the access port of each core, and the base address of its CTI if it is
+/// known. Otherwise, the first CTI in the ROM table of the access port is used.
+fn find_core_ctis<'a>(
+    components: &'a [CoresightComponent],
+    cores: &[(ApAddress, Option<u64>)],
+) -> Result<Vec<&'a CoresightComponent>, Error> {
+    cores
+        .iter()
+        .map(|(ap, cti_base)| {
+            components
+                .iter()
+                .filter(|component| component.ap.ap_address() == *ap)
+                .flat_map(|component| component.iter())
+                .find(|component| {
+                    let id = component.component.id();
+
+                    id.peripheral_id().is_of_type(PeripheralType::Cti)
+                        && cti_base.map_or(true, |base| id.component_address() == base)
+                })
+                .ok_or_else(|| {
+                    Error::architecture_specific(RomTableError::ComponentNotFound(
+                        PeripheralType::Cti,
+                    ))
+                })
+        })
+        .collect()
+}
+
+/// Connects the CTIs of the given `cores`, so that halting one core halts all others,
+/// and all cores can be halted and restarted at the same time.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn enable_cross_trigger(
+    interface: &mut Box<dyn ArmProbeInterface>,
+    components: &[CoresightComponent],
+    cores: &[(ApAddress, Option<u64>)],
+) -> Result<(), Error> {
+    for component in find_core_ctis(components, cores)? {
+        let mut cti = Cti::new(interface, component);
+
+        cti.unlock()?;
+        cti.set_enabled(false)?;
+        cti.map_trigger_input(CTI_TRIGGER_IN_HALTED, CTI_CHANNEL_HALT)?;
+        cti.map_trigger_output(CTI_TRIGGER_OUT_HALT, CTI_CHANNEL_HALT)?;
+        cti.map_trigger_output(CTI_TRIGGER_OUT_RESTART, CTI_CHANNEL_RESTART)?;
+        cti.set_channel_gate(CTI_CHANNEL_HALT | CTI_CHANNEL_RESTART)?;
+        cti.set_enabled(true)?;
+    }
+
+    Ok(())
+}
+
+/// Disconnects the CTIs of the given `cores` again.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn disable_cross_trigger(
+    interface: &mut Box<dyn ArmProbeInterface>,
+    components: &[CoresightComponent],
+    cores: &[(ApAddress, Option<u64>)],
+) -> Result<(), Error> {
+    for component in find_core_ctis(components, cores)? {
+        let mut cti = Cti::new(interface, component);
+
+        cti.map_trigger_input(CTI_TRIGGER_IN_HALTED, 0)?;
+        cti.map_trigger_output(CTI_TRIGGER_OUT_HALT, 0)?;
+        cti.map_trigger_output(CTI_TRIGGER_OUT_RESTART, 0)?;
+        cti.set_channel_gate(0)?;
+        cti.set_enabled(false)?;
+    }
+
+    Ok(())
+}
+
+/// Requests all cores connected with [`enable_cross_trigger`] to halt, or to restart if
+/// `restart` is set.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn pulse_cross_trigger(
+    interface: &mut Box<dyn ArmProbeInterface>,
+    components: &[CoresightComponent],
+    cores: &[(ApAddress, Option<u64>)],
+    restart: bool,
+) -> Result<(), Error> {
+    let ctis = find_core_ctis(components, cores)?;
+
+    // A pending halt request would stop the cores again right after the restart.
+    if restart {
+        for component in &ctis {
+            Cti::new(interface, component).acknowledge(1 << CTI_TRIGGER_OUT_HALT)?;
+        }
+    }
+
+    // The pulse is propagated to all other CTIs through the cross trigger matrix.
+    if let Some(component) = ctis.first() {
+        let channel = if restart {
+            CTI_CHANNEL_RESTART
+        } else {
+            CTI_CHANNEL_HALT
+        };
+
+        Cti::new(interface, component).pulse_channels(channel)?;
+    }
+
+    Ok(())
+}
+
+/// Acknowledges the halt and restart requests of the CTIs of the given `cores`.
+///
+/// The requests are held until they are acknowledged, so this has to be done
+/// after the cores have been halted or restarted.
+///
+/// Expects to be given a list of all ROM table `components` as the second argument.
+pub(crate) fn acknowledge_cross_trigger(
+    interface: &mut Box<dyn ArmProbeInterface>,
+    components: &[CoresightComponent],
+    cores: &[(ApAddress, Option<u64>)],
+) -> Result<(), Error> {
+    for component in find_core_ctis(components, cores)? {
+        Cti::new(interface, component)
+            .acknowledge((1 << CTI_TRIGGER_OUT_HALT) | (1 << CTI_TRIGGER_OUT_RESTART))?;
+    }
+
+    Ok(())
+}
+
+/// Prepares a halted core to be restarted through its CTI.
+///
+/// Like [`Core::run`], this steps over a possible breakpoint at the current instruction,
+/// and unmasks the interrupts, which are masked while stepping.
+pub(crate) fn prepare_cross_trigger_restart(core: &mut Core) -> Result<(), Error> {
+    core.step()?;
+
+    let mut dhcsr = Dhcsr::from(core.read_word_32(Dhcsr::ADDRESS)?);
+    if dhcsr.c_maskints() {
+        dhcsr.set_c_maskints(false);
+        dhcsr.enable_write();
+        core.write_word_32(Dhcsr::ADDRESS, dhcsr.into())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        acknowledge_cross_trigger, disable_cross_trigger, enable_cross_trigger, pulse_cross_trigger,
+    };
+    use crate::architecture::arm::{
+        ap::MemoryAp,
+        communication_interface::UninitializedArmProbe,
+        memory::{Component, CoresightComponent},
+        ApAddress, ArmProbeInterface, DpAddress,
+    };
+    use crate::{DebugProbe, FakeProbe, MemoryInterface};
+
+    const AP: ApAddress = ApAddress {
+        dp: DpAddress::Default,
+        ap: 0,
+    };
+    const CTI_BASES: [u64; 2] = [0x1000, 0x2000];
+
+    const CONTROL: u64 = 0x000;
+    const INTACK: u64 = 0x010;
+    const APPPULSE: u64 = 0x01C;
+    const INEN0: u64 = 0x020;
+    const OUTEN0: u64 = 0x0A0;
+    const OUTEN1: u64 = 0x0A4;
+    const GATE: u64 = 0x140;
+    const LAR: u64 = 0xFB0;
+
+    /// An interface with the memory of two cores, which each have a CoreSight CTI.
+    fn interface_with_ctis() -> (Box<dyn ArmProbeInterface>, Vec<CoresightComponent>) {
+        let mut memory = vec![0; 0x3000];
+
+        for base in CTI_BASES {
+            let mut write = |offset: u64, value: u32| {
+                let address = (base + offset) as usize;
+                memory[address..address + 4].copy_from_slice(&value.to_le_bytes());
+            };
+
+            // DEVTYPE, PIDR4 to PIDR7, PIDR0 to PIDR3 and CIDR0 to CIDR3 of a CoreSight CTI
+            write(0xFCC, 0x14);
+            for (i, value) in [0x04, 0, 0, 0, 0x06, 0xB9, 0x4B, 0x00]
+                .into_iter()
+                .enumerate()
+            {
+                write(0xFD0 + 4 * i as u64, value);
+            }
+            for (i, value) in [0x0D, 0x90, 0x05, 0xB1].into_iter().enumerate() {
+                write(0xFF0 + 4 * i as u64, value);
+            }
+        }
+
+        let probe: Box<dyn DebugProbe> = Box::new(FakeProbe::with_mocked_memory(memory));
+        let mut interface = probe
+            .try_get_arm_interface()
+            .map_err(|(_, err)| err)
+            .unwrap()
+            .initialize_unspecified()
+            .unwrap();
+
+        let ap = MemoryAp::new(AP);
+        let components = CTI_BASES
+            .iter()
+            .map(|&base| {
+                let mut memory = interface.memory_interface(ap).unwrap();
+                CoresightComponent::new(Component::try_parse(&mut memory, base).unwrap(), ap)
+            })
+            .collect();
+
+        (interface, components)
+    }
+
+    fn read_reg(interface: &mut Box<dyn ArmProbeInterface>, address: u64) -> u32 {
+        let mut memory = interface.memory_interface(MemoryAp::new(AP)).unwrap();
+        memory.read_word_32(address).unwrap()
+    }
+
+    fn write_reg(interface: &mut Box<dyn ArmProbeInterface>, address: u64, value: u32) {
+        let mut memory = interface.memory_interface(MemoryAp::new(AP)).unwrap();
+        memory.write_word_32(address, value).unwrap()
+    }
+
+    #[test]
+    fn cross_trigger_halt_and_restart() {
+        let (mut interface, components) = interface_with_ctis();
+        let cores = CTI_BASES.map(|base| (AP, Some(base)));
+
+        enable_cross_trigger(&mut interface, &components, &cores).unwrap();
+
+        for base in CTI_BASES {
+            assert_eq!(read_reg(&mut interface, base + LAR), 0xC5AC_CE55);
+            // The halted event is sent to channel 0, which halts the core.
+            // Channel 1 restarts the core.
+            assert_eq!(read_reg(&mut interface, base + INEN0), 0b01);
+            assert_eq!(read_reg(&mut interface, base + OUTEN0), 0b01);
+            assert_eq!(read_reg(&mut interface, base + OUTEN1), 0b10);
+            assert_eq!(read_reg(&mut interface, base + GATE), 0b11);
+            assert_eq!(read_reg(&mut interface, base + CONTROL), 1);
+        }
+
+        // Halt, like `Session::halt_all`. The pulse reaches the other CTI through the CTM.
+        pulse_cross_trigger(&mut interface, &components, &cores, false).unwrap();
+
+        assert_eq!(read_reg(&mut interface, CTI_BASES[0] + APPPULSE), 0b01);
+        assert_eq!(read_reg(&mut interface, CTI_BASES[1] + APPPULSE), 0);
+
+        acknowledge_cross_trigger(&mut interface, &components, &cores).unwrap();
+
+        for base in CTI_BASES {
+            assert_eq!(read_reg(&mut interface, base + INTACK), 0b11);
+            write_reg(&mut interface, base + INTACK, 0);
+        }
+
+        // Restart, like `Session::run_all`. A pending halt request is acknowledged first.
+        pulse_cross_trigger(&mut interface, &components, &cores, true).unwrap();
+
+        for base in CTI_BASES {
+            assert_eq!(read_reg(&mut interface, base + INTACK), 0b01);
+        }
+        assert_eq!(read_reg(&mut interface, CTI_BASES[0] + APPPULSE), 0b10);
+        assert_eq!(read_reg(&mut interface, CTI_BASES[1] + APPPULSE), 0);
+
+        disable_cross_trigger(&mut interface, &components, &cores).unwrap();
+
+        for base in CTI_BASES {
+            for offset in [INEN0, OUTEN0, OUTEN1, GATE, CONTROL] {
+                assert_eq!(read_reg(&mut interface, base + offset), 0);
+            }
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Module for using the MTB.
+//!
+//! MTB = Micro Trace Buffer
+//!
+//! The MTB is found on many Cortex-M0+ devices. It records every non-sequential change
+//! of the program counter as a packet in a circular buffer in the system SRAM, which
+//! gives a short history of the executed code, e.g. to find out how a fault was reached.
+
+use super::super::memory::romtable::CoresightComponent;
+use crate::architecture::arm::ArmProbeInterface;
+use crate::Error;
+
+const REGISTER_OFFSET_POSITION: u32 = 0x000;
+const REGISTER_OFFSET_MASTER: u32 = 0x004;
+const REGISTER_OFFSET_FLOW: u32 = 0x008;
+const REGISTER_OFFSET_BASE: u32 = 0x00C;
+
+/// MASTER.EN
+const MASTER_ENABLE: u32 = 1 << 31;
+/// MASTER.MASK, the size of the buffer is 2^(MASK + 4) bytes.
+const MASTER_MASK: u32 = 0x1f;
+/// POSITION.WRAP, set when the write pointer wrapped around.
+const POSITION_WRAP: u32 = 1 << 2;
+/// POSITION.POINTER, the offset of the next packet in the buffer.
+const POSITION_POINTER: u32 = !0b111;
+
+/// Size of a single trace packet in bytes.
+const PACKET_SIZE: usize = 8;
+
+/// A packet recorded by the MTB for a non-sequential change of the program counter.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MtbPacket {
+    /// The address of the branch instruction, or the PC at which an exception occurred.
+    pub source: u32,
+    /// The target of the branch, or the address of the exception handler.
+    pub destination: u32,
+    /// `true` if the packet was caused by an exception entry or return, or by entering debug state.
+    pub exception: bool,
+    /// `true` if this is the first packet after the trace was started.
+    pub start: bool,
+}
+
+/// A range of instructions which were executed sequentially.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ExecutedRange {
+    /// The address of the first executed instruction.
+    pub start: u32,
+    /// The address of the last executed instruction.
+    pub end: u32,
+    /// `true` if the range was entered through an exception entry or return.
+    pub exception: bool,
+}
+
+/// Decode the packets of an MTB buffer, oldest first.
+///
+/// Incomplete data at the end of `data` is ignored.
+pub fn decode_mtb_packets(data: &[u8]) -> Vec<MtbPacket> {
+    data.chunks_exact(PACKET_SIZE)
+        .map(|packet| {
+            let source = u32::from_le_bytes(packet[0..4].try_into().unwrap());
+            let destination = u32::from_le_bytes(packet[4..8].try_into().unwrap());
+
+            MtbPacket {
+                source: source & !1,
+                destination: destination & !1,
+                exception: source & 1 != 0,
+                start: destination & 1 != 0,
+            }
+        })
+        .collect()
+}
+
+/// Reconstruct the executed ranges of instructions from a list of MTB packets, oldest first.
+///
+/// Between two branches, the code from the destination of the first branch up to the
+/// source of the next branch was executed. The last range ends at `current_pc`, if given,
+/// otherwise the destination of the last branch is returned as a range with a single
+/// instruction.
+pub fn executed_ranges(packets: &[MtbPacket], current_pc: Option<u32>) -> Vec<ExecutedRange> {
+    // Only the packets since the trace was started last are consecutive.
+    let start = packets.iter().rposition(|packet| packet.start).unwrap_or(0);
+    let packets = &packets[start..];
+
+    let mut ranges: Vec<_> = packets
+        .windows(2)
+        .map(|pair| ExecutedRange {
+            start: pair[0].destination,
+            end: pair[1].source,
+            exception: pair[0].exception,
+        })
+        .collect();
+
+    if let Some(last) = packets.last() {
+        ranges.push(ExecutedRange {
+            start: last.destination,
+            end: current_pc.unwrap_or(last.destination),
+            exception: last.exception,
+        });
+    }
+
+    ranges
+}
+
+/// An interface to control the MTB (Micro Trace Buffer) of a MCU.
+pub struct Mtb<'a> {
+    component: &'a CoresightComponent,
+    interface: &'a mut Box<dyn ArmProbeInterface>,
+}
+
+impl<'a> Mtb<'a> {
+    /// Create a new MTB interface from a probe and a ROM table component.
+    pub fn new(
+        interface: &'a mut Box<dyn ArmProbeInterface>,
+        component: &'a CoresightComponent,
+    ) -> Self {
+        Mtb {
+            interface,
+            component,
+        }
+    }
+
+    /// The address of the trace buffer in the system SRAM.
+    pub fn base_address(&mut self) -> Result<u32, Error> {
+        self.component
+            .read_reg(self.interface, REGISTER_OFFSET_BASE)
+    }
+
+    /// Clear the trace buffer, and start tracing into a buffer of `size` bytes.
+    ///
+    /// `size` is rounded down to a power of two, with a minimum of 16 bytes. The
+    /// buffer starts at [`Mtb::base_address`], and the application must not use
+    /// this part of the SRAM.
+    pub fn enable(&mut self, size: u32) -> Result<(), Error> {
+        let mask = 31 - size.max(16).leading_zeros() - 4;
+
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_MASTER, 0)?;
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_POSITION, 0)?;
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_FLOW, 0)?;
+        self.component.write_reg(
+            self.interface,
+            REGISTER_OFFSET_MASTER,
+            MASTER_ENABLE | (mask & MASTER_MASK),
+        )
+    }
+
+    /// Stop tracing.
+    pub fn disable(&mut self) -> Result<(), Error> {
+        let master = self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_MASTER)?;
+
+        self.component.write_reg(
+            self.interface,
+            REGISTER_OFFSET_MASTER,
+            master & !MASTER_ENABLE,
+        )
+    }
+
+    /// Read the recorded packets, oldest first.
+    ///
+    /// Tracing is paused while the buffer is read, and resumed afterwards if it was enabled.
+    pub fn read(&mut self) -> Result<Vec<MtbPacket>, Error> {
+        let master = self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_MASTER)?;
+
+        self.component.write_reg(
+            self.interface,
+            REGISTER_OFFSET_MASTER,
+            master & !MASTER_ENABLE,
+        )?;
+
+        let position = self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_POSITION)?;
+        let base = self.base_address()?;
+
+        let size = 1usize << ((master & MASTER_MASK) + 4);
+        let pointer = (position & POSITION_POINTER) as usize % size;
+
+        let mut buffer = vec![0u8; size];
+        {
+            let mut memory = self.interface.memory_interface(self.component.ap)?;
+            memory.read_8(base as u64, &mut buffer)?;
+        }
+
+        // If the buffer wrapped around, the oldest packet is at the write pointer.
+        let data = if position & POSITION_WRAP != 0 {
+            [&buffer[pointer..], &buffer[..pointer]].concat()
+        } else {
+            buffer.truncate(pointer);
+            buffer
+        };
+
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_MASTER, master)?;
+
+        Ok(decode_mtb_packets(&data))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_mtb_packets, executed_ranges, ExecutedRange, MtbPacket};
+
+    fn packet(source: u32, destination: u32) -> MtbPacket {
+        MtbPacket {
+            source,
+            destination,
+            exception: false,
+            start: false,
+        }
+    }
+
+    #[test]
+    fn decode_packets() {
+        let data = [
+            0x01, 0x01, 0x00, 0x00, 0x01, 0x02, 0x00, 0x00, // 0x100 -> 0x200, A and S set
+            0x10, 0x02, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, // 0x210 -> 0x300
+            0xff, 0xff, // incomplete
+        ];
+
+        assert_eq!(
+            decode_mtb_packets(&data),
+            vec![
+                MtbPacket {
+                    source: 0x100,
+                    destination: 0x200,
+                    exception: true,
+                    start: true,
+                },
+                packet(0x210, 0x300),
+            ]
+        );
+    }
+
+    #[test]
+    fn ranges_between_branches() {
+        let packets = [packet(0x100, 0x200), packet(0x210, 0x300)];
+
+        assert_eq!(
+            executed_ranges(&packets, Some(0x308)),
+            vec![
+                ExecutedRange {
+                    start: 0x200,
+                    end: 0x210,
+                    exception: false
+                },
+                ExecutedRange {
+                    start: 0x300,
+                    end: 0x308,
+                    exception: false
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn ranges_start_at_last_start_packet() {
+        let mut restart = packet(0x400, 0x500);
+        restart.start = true;
+
+        let packets = [packet(0x100, 0x200), restart, packet(0x520, 0x600)];
+
+        assert_eq!(
+            executed_ranges(&packets, None),
+            vec![
+                ExecutedRange {
+                    start: 0x500,
+                    end: 0x520,
+                    exception: false
+                },
+                ExecutedRange {
+                    start: 0x600,
+                    end: 0x600,
+                    exception: false
+                }
+            ]
+        );
+    }
+}
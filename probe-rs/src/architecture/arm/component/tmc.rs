@@ -13,6 +13,8 @@ use bitfield::bitfield;
 
 const REGISTER_OFFSET_RSZ: u32 = 0x04;
 const REGISTER_OFFSET_RRD: u32 = 0x10;
+const REGISTER_OFFSET_RRP: u32 = 0x14;
+const REGISTER_OFFSET_RWP: u32 = 0x18;
 const REGISTER_OFFSET_CTL: u32 = 0x20;
 const REGISTER_OFFSET_CBUFLVL: u32 = 0x30;
 
@@ -139,6 +141,16 @@ impl<'a> TraceMemoryController<'a> {
         Ok(())
     }
 
+    /// Enable the formatting of the trace data, which is required to capture
+    /// the trace data of multiple trace sources.
+    pub fn enable_formatting(&mut self) -> Result<(), Error> {
+        let mut ffcr = FormatFlushControl::load(self.component, self.interface)?;
+        ffcr.set_enft(true);
+        ffcr.set_enti(true);
+        ffcr.store(self.component, self.interface)?;
+        Ok(())
+    }
+
     /// Generate a manual flush event.
     pub fn manual_flush(&mut self) -> Result<(), Error> {
         let mut ffcr = FormatFlushControl::load(self.component, self.interface)?;
@@ -154,6 +166,36 @@ impl<'a> TraceMemoryController<'a> {
             .read_reg(self.interface, REGISTER_OFFSET_RSZ)?;
         Ok(size_words * core::mem::size_of::<u32>() as u32)
     }
+
+    /// Read the trace data captured in [`Mode::Circular`], oldest data first.
+    ///
+    /// The capture has to be stopped using [`TraceMemoryController::disable_capture`] before.
+    pub fn read_circular_buffer(&mut self) -> Result<Vec<u8>, Error> {
+        let size = self.fifo_size()?;
+        let write_pointer = self
+            .component
+            .read_reg(self.interface, REGISTER_OFFSET_RWP)?;
+
+        // If the buffer wrapped around, the oldest data is at the write pointer.
+        let (start, length) = if self.full()? {
+            (write_pointer, size)
+        } else {
+            (0, write_pointer)
+        };
+
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_RRP, start)?;
+
+        let mut data = Vec::with_capacity(length as usize);
+        for _ in 0..length / core::mem::size_of::<u32>() as u32 {
+            let word = self
+                .component
+                .read_reg(self.interface, REGISTER_OFFSET_RRD)?;
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+
+        Ok(data)
+    }
 }
 
 bitfield! {
@@ -1,5 +1,6 @@
 //! Register types and the core interface for armv6-M
 
+use super::cortex_m::{clear_watchpoint, set_watchpoint, DwtVersion};
 use super::{ArmError, CortexMState, Dfsr, CORTEX_M_COMMON_REGS};
 
 use crate::architecture::arm::sequences::ArmDebugSequence;
@@ -10,7 +11,8 @@ use crate::error::Error;
 use crate::memory::{valid_32_address, Memory};
 use crate::{
     Architecture, CoreInformation, CoreInterface, CoreStatus, CoreType, DebugProbeError,
-    HaltReason, InstructionSet, MemoryInterface, MemoryMappedRegister, RegisterId,
+    HaltReason, InstructionSet, MemoryInterface, MemoryMappedRegister, RegisterId, VectorCatch,
+    WatchpointConfig,
 };
 use anyhow::Result;
 use bitfield::bitfield;
@@ -496,6 +498,25 @@ impl<'probe> Armv6m<'probe> {
             sequence,
         })
     }
+
+    /// Enable or disable halting on `condition` in DEMCR.
+    fn set_vector_catch(&mut self, condition: VectorCatch, enabled: bool) -> Result<(), Error> {
+        let mut demcr = Demcr(self.memory.read_word_32(Demcr::ADDRESS)?);
+
+        match condition {
+            VectorCatch::CoreReset => demcr.set_vc_corereset(enabled),
+            VectorCatch::HardFault => demcr.set_vc_harderr(enabled),
+            _ => {
+                return Err(Error::architecture_specific(
+                    ArmError::VectorCatchNotSupported(condition),
+                ))
+            }
+        }
+
+        self.memory.write_word_32(Demcr::ADDRESS, demcr.into())?;
+
+        Ok(())
+    }
 }
 
 impl<'probe> CoreInterface for Armv6m<'probe> {
@@ -728,6 +749,7 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
 
     fn status(&mut self) -> Result<crate::core::CoreStatus, Error> {
         let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        self.state.record_reset_status(dhcsr.s_reset_st());
 
         if dhcsr.s_lockup() {
             log::warn!("The core is in locked up status as a result of an unrecoverable exception");
@@ -794,7 +816,7 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
 
     fn read_core_reg(&mut self, address: RegisterId) -> Result<RegisterValue, Error> {
         if self.state.current_state.is_halted() {
-            let val = super::cortex_m::read_core_reg(&mut self.memory, address)?;
+            let val = super::cortex_m::read_core_reg(&mut self.memory, self.state, address)?;
             Ok(val.into())
         } else {
             Err(Error::architecture_specific(ArmError::CoreNotHalted))
@@ -803,7 +825,12 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
 
     fn write_core_reg(&mut self, address: RegisterId, value: RegisterValue) -> Result<(), Error> {
         if self.state.current_state.is_halted() {
-            super::cortex_m::write_core_reg(&mut self.memory, address, value.try_into()?)?;
+            super::cortex_m::write_core_reg(
+                &mut self.memory,
+                self.state,
+                address,
+                value.try_into()?,
+            )?;
             Ok(())
         } else {
             Err(Error::architecture_specific(ArmError::CoreNotHalted))
@@ -831,6 +858,29 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
     fn fpu_support(&mut self) -> Result<bool, crate::error::Error> {
         Ok(false)
     }
+
+    fn reset_detected(&mut self) -> Result<bool, Error> {
+        let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        self.state.record_reset_status(dhcsr.s_reset_st());
+
+        Ok(self.state.take_reset_detected())
+    }
+
+    fn enable_vector_catch(&mut self, condition: VectorCatch) -> Result<(), Error> {
+        self.set_vector_catch(condition, true)
+    }
+
+    fn disable_vector_catch(&mut self, condition: VectorCatch) -> Result<(), Error> {
+        self.set_vector_catch(condition, false)
+    }
+
+    fn set_watchpoint(&mut self, config: &WatchpointConfig) -> Result<usize, Error> {
+        set_watchpoint(&mut self.memory, DwtVersion::Armv6m, config)
+    }
+
+    fn clear_watchpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        clear_watchpoint(&mut self.memory, DwtVersion::Armv6m, unit_index)
+    }
 }
 
 impl<'probe> MemoryInterface for Armv6m<'probe> {
@@ -467,6 +467,9 @@ impl<'probe> CoreInterface for Armv7a<'probe> {
         self.memory
             .write_word_32(bp_control_addr, saved_bp_control)?;
 
+        // Update core status, the core was halted by the step
+        let _ = self.status()?;
+
         // try to read the program counter
         let pc_value = self.read_core_reg(register::PC.id)?;
 
@@ -743,9 +746,13 @@ impl<'probe> CoreInterface for Armv7a<'probe> {
     }
 
     fn fpu_support(&mut self) -> Result<bool, crate::error::Error> {
-        Err(crate::error::Error::Other(anyhow::anyhow!(
-            "Fpu detection not yet implemented"
-        )))
+        // The number of FP registers can only be read while the core is halted.
+        self.read_fp_reg_count()?;
+
+        match self.state.fp_reg_count {
+            Some(count) => Ok(count > 0),
+            None => Err(Error::architecture_specific(Armv7aError::NotHalted)),
+        }
     }
 
     fn on_session_stop(&mut self) -> Result<(), Error> {
@@ -803,8 +810,24 @@ impl<'probe> MemoryInterface for Armv7a<'probe> {
         Ok(())
     }
     fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), Error> {
-        for (i, word) in data.iter_mut().enumerate() {
-            *word = self.read_word_32(address + ((i as u64) * 4))?;
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let address = valid_32_address(address)?;
+        valid_32_address(address as u64 + (data.len() as u64 - 1) * 4)?;
+
+        // LDC p14, c5, [r0], #4
+        let instr = build_ldc(14, 5, 0, 4);
+
+        // Save r0
+        self.prepare_r0_for_clobber()?;
+
+        // Load r0 with the start address, it is incremented by every LDC.
+        self.set_r0(address)?;
+
+        for word in data.iter_mut() {
+            *word = self.execute_instruction_with_result(instr)?;
         }
 
         Ok(())
@@ -858,15 +881,31 @@ impl<'probe> MemoryInterface for Armv7a<'probe> {
         Ok(())
     }
     fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), Error> {
-        for (i, word) in data.iter().enumerate() {
-            self.write_word_32(address + ((i as u64) * 4), *word)?;
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let address = valid_32_address(address)?;
+        valid_32_address(address as u64 + (data.len() as u64 - 1) * 4)?;
+
+        // STC p14, c5, [r0], #4
+        let instr = build_stc(14, 5, 0, 4);
+
+        // Save r0
+        self.prepare_r0_for_clobber()?;
+
+        // Load r0 with the start address, it is incremented by every STC.
+        self.set_r0(address)?;
+
+        for word in data {
+            self.execute_instruction_with_input(instr, *word)?;
         }
 
         Ok(())
     }
     fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), Error> {
         for (i, byte) in data.iter().enumerate() {
-            self.write_word_8(address + ((i as u64) * 4), *byte)?;
+            self.write_word_8(address + (i as u64), *byte)?;
         }
 
         Ok(())
@@ -1150,6 +1189,23 @@ mod test {
         probe.expected_read(Dbgdtrtx::get_mmio_address(TEST_BASE_ADDRESS), value);
     }
 
+    fn add_write_memory_expectations(probe: &mut MockProbe, address: u64, value: u32) {
+        add_set_r0_expectation(probe, address as u32);
+
+        let mut dbgdscr = Dbgdscr(0);
+        dbgdscr.set_instrcoml_l(true);
+        dbgdscr.set_rxfull_l(true);
+
+        probe.expected_write(Dbgdtrrx::get_mmio_address(TEST_BASE_ADDRESS), value);
+        probe.expected_read(Dbgdscr::get_mmio_address(TEST_BASE_ADDRESS), dbgdscr.into());
+
+        probe.expected_write(
+            Dbgitr::get_mmio_address(TEST_BASE_ADDRESS),
+            build_stc(14, 5, 0, 4),
+        );
+        probe.expected_read(Dbgdscr::get_mmio_address(TEST_BASE_ADDRESS), dbgdscr.into());
+    }
+
     #[test]
     fn armv7a_new() {
         let mut probe = MockProbe::new();
@@ -1559,6 +1615,99 @@ mod test {
         armv7a.run().unwrap();
     }
 
+    #[test]
+    fn armv7a_step() {
+        const BP_COUNT: u32 = 4;
+        const BP_VALUE: u32 = 0x2345;
+        const PC_VALUE: u32 = 0x1234;
+        const REG_VALUE: u32 = 0xABCD;
+        let mut probe = MockProbe::new();
+        let mut state = CortexAState::new();
+
+        // Add expectations
+        add_status_expectations(&mut probe, true);
+        add_enable_itr_expectations(&mut probe);
+        add_read_reg_expectations(&mut probe, 0, 0);
+        add_read_fp_count_expectations(&mut probe);
+
+        // Save the last breakpoint
+        add_idr_expectations(&mut probe, BP_COUNT);
+        let bp_value_addr = Dbgbvr::get_mmio_address(TEST_BASE_ADDRESS) + (3 * 4);
+        let bp_control_addr = Dbgbcr::get_mmio_address(TEST_BASE_ADDRESS) + (3 * 4);
+        probe.expected_read(bp_value_addr, BP_VALUE);
+        probe.expected_read(bp_control_addr, 1);
+
+        // Set an address mismatch breakpoint on the current PC
+        add_read_pc_expectations(&mut probe, PC_VALUE);
+
+        let mut dbgbcr = Dbgbcr(0);
+        // Breakpoint type - address mismatch
+        dbgbcr.set_bt(0b0100);
+        // Match on all modes
+        dbgbcr.set_hmc(true);
+        dbgbcr.set_pmc(0b11);
+        // Match on all bytes
+        dbgbcr.set_bas(0b1111);
+        // Enable
+        dbgbcr.set_e(true);
+
+        probe.expected_write(bp_value_addr, PC_VALUE);
+        probe.expected_write(bp_control_addr, dbgbcr.into());
+
+        // Writeback r0
+        add_set_r0_expectation(&mut probe, 0);
+
+        // Write resume request
+        let mut dbgdrcr = Dbgdrcr(0);
+        dbgdrcr.set_rrq(true);
+        probe.expected_write(Dbgdrcr::get_mmio_address(TEST_BASE_ADDRESS), dbgdrcr.into());
+
+        // Wait for running
+        add_status_expectations(&mut probe, false);
+
+        // Read status
+        add_status_expectations(&mut probe, false);
+
+        // Wait for halted
+        add_status_expectations(&mut probe, true);
+
+        // Restore the breakpoint
+        probe.expected_write(bp_value_addr, BP_VALUE);
+        probe.expected_write(bp_control_addr, 1);
+
+        // Read status
+        add_status_expectations(&mut probe, true);
+
+        // Read PC
+        add_read_reg_expectations(&mut probe, 0, 0);
+        add_read_pc_expectations(&mut probe, PC_VALUE + 2);
+
+        // Read register after the step
+        add_read_reg_expectations(&mut probe, 2, REG_VALUE);
+
+        let mock_mem = Memory::new(
+            probe,
+            MemoryAp::new(ApAddress {
+                ap: 0,
+                dp: DpAddress::Default,
+            }),
+        );
+
+        let mut armv7a = Armv7a::new(
+            mock_mem,
+            &mut state,
+            TEST_BASE_ADDRESS,
+            DefaultArmSequence::create(),
+        )
+        .unwrap();
+
+        assert_eq!((PC_VALUE + 2) as u64, armv7a.step().unwrap().pc);
+        assert_eq!(
+            RegisterValue::from(REG_VALUE),
+            armv7a.read_core_reg(RegisterId(2)).unwrap()
+        );
+    }
+
     #[test]
     fn armv7a_available_breakpoint_units() {
         const BP_COUNT: u32 = 4;
@@ -1796,4 +1945,98 @@ mod test {
 
         assert_eq!(0xBA, armv7a.read_word_8(MEMORY_ADDRESS).unwrap());
     }
+
+    #[test]
+    fn armv7a_read_32() {
+        const MEMORY_VALUES: [u32; 2] = [0xBA5EBA11, 0xDEADBEEF];
+        const MEMORY_ADDRESS: u64 = 0x12345678;
+
+        let mut probe = MockProbe::new();
+        let mut state = CortexAState::new();
+
+        // Add expectations
+        add_status_expectations(&mut probe, true);
+        add_enable_itr_expectations(&mut probe);
+        add_read_reg_expectations(&mut probe, 0, 0);
+        add_read_fp_count_expectations(&mut probe);
+
+        // Read memory, r0 is only set once
+        add_read_memory_expectations(&mut probe, MEMORY_ADDRESS, MEMORY_VALUES[0]);
+
+        let mut dbgdscr = Dbgdscr(0);
+        dbgdscr.set_instrcoml_l(true);
+        dbgdscr.set_txfull_l(true);
+
+        probe.expected_write(
+            Dbgitr::get_mmio_address(TEST_BASE_ADDRESS),
+            build_ldc(14, 5, 0, 4),
+        );
+        probe.expected_read(Dbgdscr::get_mmio_address(TEST_BASE_ADDRESS), dbgdscr.into());
+        probe.expected_read(
+            Dbgdtrtx::get_mmio_address(TEST_BASE_ADDRESS),
+            MEMORY_VALUES[1],
+        );
+
+        let mock_mem = Memory::new(
+            probe,
+            MemoryAp::new(ApAddress {
+                ap: 0,
+                dp: DpAddress::Default,
+            }),
+        );
+
+        let mut armv7a = Armv7a::new(
+            mock_mem,
+            &mut state,
+            TEST_BASE_ADDRESS,
+            DefaultArmSequence::create(),
+        )
+        .unwrap();
+
+        let mut data = [0; 2];
+        armv7a.read_32(MEMORY_ADDRESS, &mut data).unwrap();
+
+        assert_eq!(MEMORY_VALUES, data);
+    }
+
+    #[test]
+    fn armv7a_write_8() {
+        const MEMORY_VALUE: u32 = 0xBA5EBA11;
+        const MEMORY_ADDRESS: u64 = 0x12345679;
+        const MEMORY_WORD_ADDRESS: u64 = 0x12345678;
+
+        let mut probe = MockProbe::new();
+        let mut state = CortexAState::new();
+
+        // Add expectations
+        add_status_expectations(&mut probe, true);
+        add_enable_itr_expectations(&mut probe);
+        add_read_reg_expectations(&mut probe, 0, 0);
+        add_read_fp_count_expectations(&mut probe);
+
+        // Both bytes are in the same word, so each one is a read-modify-write of it
+        add_read_memory_expectations(&mut probe, MEMORY_WORD_ADDRESS, MEMORY_VALUE);
+        add_write_memory_expectations(&mut probe, MEMORY_WORD_ADDRESS, 0xBA5EAA11);
+
+        add_read_memory_expectations(&mut probe, MEMORY_WORD_ADDRESS, 0xBA5EAA11);
+        add_write_memory_expectations(&mut probe, MEMORY_WORD_ADDRESS, 0xBABBAA11);
+
+        let mock_mem = Memory::new(
+            probe,
+            MemoryAp::new(ApAddress {
+                ap: 0,
+                dp: DpAddress::Default,
+            }),
+        );
+
+        let mut armv7a = Armv7a::new(
+            mock_mem,
+            &mut state,
+            TEST_BASE_ADDRESS,
+            DefaultArmSequence::create(),
+        )
+        .unwrap();
+
+        armv7a.write_8(MEMORY_ADDRESS, &[0xAA, 0xBB]).unwrap();
+    }
 }
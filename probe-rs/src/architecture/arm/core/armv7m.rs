@@ -6,9 +6,12 @@ use crate::core::{
 };
 use crate::error::Error;
 use crate::memory::{valid_32_address, Memory};
-use crate::{CoreType, DebugProbeError, InstructionSet};
+use crate::{CoreType, DebugProbeError, InstructionSet, VectorCatch, WatchpointConfig};
 
-use super::cortex_m::Mvfr0;
+use super::cortex_m::{
+    caches_present, clear_watchpoint, read_fault_status, set_watchpoint,
+    write_with_cache_maintenance, DwtVersion, FaultStatus, Mvfr0,
+};
 use super::{register, ArmError, CortexMState, Dfsr, CORTEX_M_COMMON_REGS, CORTEX_M_WITH_FP_REGS};
 use crate::{
     core::{Architecture, CoreStatus, HaltReason},
@@ -625,6 +628,7 @@ impl<'probe> Armv7m<'probe> {
 
             state.current_state = core_state;
             state.fp_present = Mvfr0(memory.read_word_32(Mvfr0::ADDRESS)?).fp_present();
+            state.caches_present = caches_present(&mut memory)?;
 
             state.initialize();
         }
@@ -635,6 +639,45 @@ impl<'probe> Armv7m<'probe> {
             sequence,
         })
     }
+
+    /// Enable or disable halting on `condition` in DEMCR.
+    fn set_vector_catch(&mut self, condition: VectorCatch, enabled: bool) -> Result<(), Error> {
+        let mut demcr = Demcr(self.memory.read_word_32(Demcr::ADDRESS)?);
+
+        match condition {
+            VectorCatch::CoreReset => demcr.set_vc_corereset(enabled),
+            VectorCatch::HardFault => demcr.set_vc_harderr(enabled),
+            VectorCatch::MemoryManagement => demcr.set_vc_mmerr(enabled),
+            VectorCatch::BusFault => demcr.set_vc_buserr(enabled),
+            VectorCatch::StateError => demcr.set_vc_staterr(enabled),
+            VectorCatch::CheckError => demcr.set_vc_chkerr(enabled),
+            VectorCatch::CoprocessorError => demcr.set_vc_nocperr(enabled),
+            VectorCatch::InterruptError => demcr.set_vc_interr(enabled),
+            _ => {
+                return Err(Error::architecture_specific(
+                    ArmError::VectorCatchNotSupported(condition),
+                ))
+            }
+        }
+
+        self.memory.write_word_32(Demcr::ADDRESS, demcr.into())?;
+
+        Ok(())
+    }
+
+    /// Write to memory using `write`, with cache maintenance if the core has caches.
+    fn write_cached(
+        &mut self,
+        address: u64,
+        len: u64,
+        write: impl FnOnce(&mut Memory<'probe>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        if self.state.caches_present && self.state.cache_maintenance {
+            write_with_cache_maintenance(&mut self.memory, address, len, write)
+        } else {
+            write(&mut self.memory)
+        }
+    }
 }
 
 impl<'probe> CoreInterface for Armv7m<'probe> {
@@ -659,6 +702,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
 
     fn status(&mut self) -> Result<CoreStatus, Error> {
         let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        self.state.record_reset_status(dhcsr.s_reset_st());
 
         if dhcsr.s_lockup() {
             log::error!(
@@ -725,8 +769,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
 
     fn read_core_reg(&mut self, address: RegisterId) -> Result<RegisterValue, Error> {
         if self.state.current_state.is_halted() {
-            let val = super::cortex_m::read_core_reg(&mut self.memory, address)?;
-            Ok(val.into())
+            super::cortex_m::read_core_reg_value(&mut self.memory, self.state, address)
         } else {
             Err(Error::architecture_specific(ArmError::CoreNotHalted))
         }
@@ -734,8 +777,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
 
     fn write_core_reg(&mut self, address: RegisterId, value: RegisterValue) -> Result<(), Error> {
         if self.state.current_state.is_halted() {
-            super::cortex_m::write_core_reg(&mut self.memory, address, value.try_into()?)?;
-            Ok(())
+            super::cortex_m::write_core_reg_value(&mut self.memory, self.state, address, value)
         } else {
             Err(Error::architecture_specific(ArmError::CoreNotHalted))
         }
@@ -767,6 +809,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         self.step()?;
 
         let mut dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        self.state.record_reset_status(dhcsr.s_reset_st());
 
         // First disable the DHCSR->C_MASKINTS.
         if dhcsr.c_maskints() {
@@ -803,6 +846,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         };
 
         let mut dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        self.state.record_reset_status(dhcsr.s_reset_st());
 
         // Follow the rules of the ... ARMv7-M Architecture reference, C1.6 Debug System Registers - DHCSR, with respect to setting maskints
         if !dhcsr.c_debugen() {
@@ -1016,6 +1060,37 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
     fn fpu_support(&mut self) -> Result<bool, crate::error::Error> {
         Ok(self.state.fp_present)
     }
+
+    fn fault_status(&mut self) -> Result<Option<FaultStatus>, Error> {
+        read_fault_status(&mut self.memory, false).map(Some)
+    }
+
+    fn reset_detected(&mut self) -> Result<bool, Error> {
+        let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        self.state.record_reset_status(dhcsr.s_reset_st());
+
+        Ok(self.state.take_reset_detected())
+    }
+
+    fn enable_vector_catch(&mut self, condition: VectorCatch) -> Result<(), Error> {
+        self.set_vector_catch(condition, true)
+    }
+
+    fn disable_vector_catch(&mut self, condition: VectorCatch) -> Result<(), Error> {
+        self.set_vector_catch(condition, false)
+    }
+
+    fn set_cache_maintenance(&mut self, enabled: bool) {
+        self.state.cache_maintenance = enabled;
+    }
+
+    fn set_watchpoint(&mut self, config: &WatchpointConfig) -> Result<usize, Error> {
+        set_watchpoint(&mut self.memory, DwtVersion::Armv7m, config)
+    }
+
+    fn clear_watchpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        clear_watchpoint(&mut self.memory, DwtVersion::Armv7m, unit_index)
+    }
 }
 
 impl<'probe> MemoryInterface for Armv7m<'probe> {
@@ -1041,22 +1116,28 @@ impl<'probe> MemoryInterface for Armv7m<'probe> {
         self.memory.read_8(address, data)
     }
     fn write_word_64(&mut self, address: u64, data: u64) -> Result<(), crate::error::Error> {
-        self.memory.write_word_64(address, data)
+        self.write_cached(address, 8, |memory| memory.write_word_64(address, data))
     }
     fn write_word_32(&mut self, address: u64, data: u32) -> Result<(), Error> {
-        self.memory.write_word_32(address, data)
+        self.write_cached(address, 4, |memory| memory.write_word_32(address, data))
     }
     fn write_word_8(&mut self, address: u64, data: u8) -> Result<(), Error> {
-        self.memory.write_word_8(address, data)
+        self.write_cached(address, 1, |memory| memory.write_word_8(address, data))
     }
     fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), crate::error::Error> {
-        self.memory.write_64(address, data)
+        self.write_cached(address, data.len() as u64 * 8, |memory| {
+            memory.write_64(address, data)
+        })
     }
     fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), Error> {
-        self.memory.write_32(address, data)
+        self.write_cached(address, data.len() as u64 * 4, |memory| {
+            memory.write_32(address, data)
+        })
     }
     fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), Error> {
-        self.memory.write_8(address, data)
+        self.write_cached(address, data.len() as u64, |memory| {
+            memory.write_8(address, data)
+        })
     }
     fn flush(&mut self) -> Result<(), Error> {
         self.memory.flush()
@@ -894,6 +894,9 @@ impl<'probe> CoreInterface for Armv8a<'probe> {
         edecr.set_ss(false);
         self.memory.write_word_32(edecr_address, edecr.into())?;
 
+        // Update core status, the core was halted by the step
+        let _ = self.status()?;
+
         // try to read the program counter
         let pc_value = self.read_core_reg(self.registers().program_counter().id)?;
 
@@ -1043,6 +1046,16 @@ impl<'probe> CoreInterface for Armv8a<'probe> {
             self.state.current_state = CoreStatus::Halted(reason);
             self.state.is_64_bit = edscr.currently_64_bit();
 
+            log::debug!(
+                "Core halted in EL{} ({})",
+                edscr.el(),
+                if self.state.is_64_bit {
+                    "AArch64"
+                } else {
+                    "AArch32"
+                }
+            );
+
             return Ok(CoreStatus::Halted(reason));
         }
         // Core is neither halted nor sleeping, so we assume it is running.
@@ -1197,7 +1210,7 @@ impl<'probe> MemoryInterface for Armv8a<'probe> {
     }
     fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), Error> {
         for (i, byte) in data.iter().enumerate() {
-            self.write_word_8(address + ((i as u64) * 4), *byte)?;
+            self.write_word_8(address + (i as u64), *byte)?;
         }
 
         Ok(())
@@ -1606,6 +1619,29 @@ mod test {
         probe.expected_read(Dbgdtrtx::get_mmio_address(TEST_BASE_ADDRESS), value);
     }
 
+    fn add_write_memory_expectations(probe: &mut MockProbe, address: u64, value: u32) {
+        add_set_r0_expectation(probe, address as u32);
+
+        let mut edscr = Edscr(0);
+        edscr.set_ite(true);
+        edscr.set_rxfull(true);
+
+        probe.expected_write(Dbgdtrrx::get_mmio_address(TEST_BASE_ADDRESS), value);
+        probe.expected_read(Edscr::get_mmio_address(TEST_BASE_ADDRESS), edscr.into());
+
+        probe.expected_write(
+            Editr::get_mmio_address(TEST_BASE_ADDRESS),
+            prep_instr_for_itr_32(build_mrc(14, 0, 1, 0, 5, 0)),
+        );
+        probe.expected_read(Edscr::get_mmio_address(TEST_BASE_ADDRESS), edscr.into());
+
+        probe.expected_write(
+            Editr::get_mmio_address(TEST_BASE_ADDRESS),
+            prep_instr_for_itr_32(build_str(1, 0, 4)),
+        );
+        probe.expected_read(Edscr::get_mmio_address(TEST_BASE_ADDRESS), edscr.into());
+    }
+
     #[test]
     fn armv8a_new() {
         let mut probe = MockProbe::new(false);
@@ -2123,6 +2159,69 @@ mod test {
         armv8a.run().unwrap();
     }
 
+    #[test]
+    fn armv8a_step() {
+        const PC_VALUE: u32 = 0x1234;
+        const REG_VALUE: u32 = 0xABCD;
+
+        let mut probe = MockProbe::new(false);
+        let mut state = CortexAState::new();
+
+        // Add expectations
+        add_status_expectations(&mut probe, true);
+
+        // Enable single stepping
+        let mut edecr = Edecr(0);
+        probe.expected_read(Edecr::get_mmio_address(TEST_BASE_ADDRESS), edecr.into());
+        edecr.set_ss(true);
+        probe.expected_write(Edecr::get_mmio_address(TEST_BASE_ADDRESS), edecr.into());
+
+        // Write resume request
+        add_resume_expectations(&mut probe);
+        add_status_expectations(&mut probe, false);
+        add_resume_cleanup_expectations(&mut probe);
+
+        // Wait for halted
+        add_status_expectations(&mut probe, true);
+
+        // Disable single stepping
+        edecr.set_ss(false);
+        probe.expected_write(Edecr::get_mmio_address(TEST_BASE_ADDRESS), edecr.into());
+
+        // Read status
+        add_status_expectations(&mut probe, true);
+
+        // Read PC
+        add_read_reg_expectations(&mut probe, 0, 0);
+        add_read_pc_expectations(&mut probe, PC_VALUE);
+
+        // Read register after the step
+        add_read_reg_expectations(&mut probe, 2, REG_VALUE);
+
+        let mock_mem = Memory::new(
+            probe,
+            MemoryAp::new(ApAddress {
+                ap: 0,
+                dp: DpAddress::Default,
+            }),
+        );
+
+        let mut armv8a = Armv8a::new(
+            mock_mem,
+            &mut state,
+            TEST_BASE_ADDRESS,
+            TEST_CTI_ADDRESS,
+            DefaultArmSequence::create(),
+        )
+        .unwrap();
+
+        assert_eq!(PC_VALUE as u64, armv8a.step().unwrap().pc);
+        assert_eq!(
+            RegisterValue::from(REG_VALUE),
+            armv8a.read_core_reg(RegisterId(2)).unwrap()
+        );
+    }
+
     #[test]
     fn armv8a_available_breakpoint_units() {
         const BP_COUNT: u32 = 4;
@@ -2439,4 +2538,45 @@ mod test {
 
         assert_eq!(0xBA, armv8a.read_word_8(MEMORY_ADDRESS).unwrap());
     }
+
+    #[test]
+    fn armv8a_write_8() {
+        const MEMORY_VALUE: u32 = 0xBA5EBA11;
+        const MEMORY_ADDRESS: u64 = 0x12345679;
+        const MEMORY_WORD_ADDRESS: u64 = 0x12345678;
+
+        let mut probe = MockProbe::new(false);
+        let mut state = CortexAState::new();
+
+        // Add expectations
+        add_status_expectations(&mut probe, true);
+
+        // Both bytes are in the same word, so each one is a read-modify-write of it
+        add_read_reg_expectations(&mut probe, 0, 0);
+        add_read_reg_expectations(&mut probe, 1, 0);
+        add_read_memory_expectations(&mut probe, MEMORY_WORD_ADDRESS, MEMORY_VALUE);
+        add_write_memory_expectations(&mut probe, MEMORY_WORD_ADDRESS, 0xBA5EAA11);
+
+        add_read_memory_expectations(&mut probe, MEMORY_WORD_ADDRESS, 0xBA5EAA11);
+        add_write_memory_expectations(&mut probe, MEMORY_WORD_ADDRESS, 0xBABBAA11);
+
+        let mock_mem = Memory::new(
+            probe,
+            MemoryAp::new(ApAddress {
+                ap: 0,
+                dp: DpAddress::Default,
+            }),
+        );
+
+        let mut armv8a = Armv8a::new(
+            mock_mem,
+            &mut state,
+            TEST_BASE_ADDRESS,
+            TEST_CTI_ADDRESS,
+            DefaultArmSequence::create(),
+        )
+        .unwrap();
+
+        armv8a.write_8(MEMORY_ADDRESS, &[0xAA, 0xBB]).unwrap();
+    }
 }
@@ -602,5 +602,6 @@ pub static AARCH64_REGISTER_FILE: RegisterFile = RegisterFile {
             size_in_bits: 128,
         },
     ]),
+    fp_double_registers: None,
     fp_status: Some(&FPSR),
 };
@@ -6,16 +6,23 @@ use crate::error::Error;
 use crate::memory::{valid_32_address, Memory};
 use crate::{
     architecture::arm::core::register, CoreStatus, DebugProbeError, HaltReason, MemoryInterface,
+    SecurityState, VectorCatch,
 };
 use crate::{Architecture, CoreInformation};
 use crate::{CoreInterface, CoreType, InstructionSet, MemoryMappedRegister};
-use crate::{RegisterId, RegisterValue};
+use crate::{RegisterId, RegisterValue, WatchpointConfig};
 use anyhow::Result;
 
 use bitfield::bitfield;
 
-use super::cortex_m::Mvfr0;
-use super::{ArmError, CortexMState, Dfsr, CORTEX_M_COMMON_REGS, CORTEX_M_WITH_FP_REGS};
+use super::cortex_m::{
+    caches_present, clear_watchpoint, read_fault_status, set_watchpoint,
+    write_with_cache_maintenance, DwtVersion, FaultStatus, Mvfr0,
+};
+use super::{
+    ArmError, CortexMState, Dfsr, CORTEX_M_COMMON_REGS, CORTEX_M_SECURE_REGS,
+    CORTEX_M_SECURE_WITH_FP_REGS, CORTEX_M_WITH_FP_REGS,
+};
 use std::sync::Arc;
 use std::{
     mem::size_of,
@@ -65,6 +72,18 @@ impl<'probe> Armv8m<'probe> {
 
             state.current_state = core_state;
             state.fp_present = Mvfr0(memory.read_word_32(Mvfr0::ADDRESS)?).fp_present();
+            state.caches_present = caches_present(&mut memory)?;
+            state.security_extension =
+                IdPfr1(memory.read_word_32(IdPfr1::ADDRESS)?).security_extension();
+
+            if state.security_extension {
+                let dhcsr = Dhcsr(memory.read_word_32(Dhcsr::ADDRESS)?);
+
+                log::debug!(
+                    "Core implements the Security Extension, secure debug is {}",
+                    if dhcsr.s_sde() { "enabled" } else { "disabled" }
+                );
+            }
 
             state.initialize();
         }
@@ -75,6 +94,79 @@ impl<'probe> Armv8m<'probe> {
             sequence,
         })
     }
+
+    /// Check if a register can be accessed in the current debug state.
+    ///
+    /// Registers of the Secure state can only be accessed if secure debug is enabled.
+    fn check_register_access(&mut self, address: RegisterId) -> Result<(), Error> {
+        if !self.state.current_state.is_halted() {
+            return Err(Error::architecture_specific(ArmError::CoreNotHalted));
+        }
+
+        let secure_register = [
+            register::MSP_S.id,
+            register::PSP_S.id,
+            register::MSPLIM_S.id,
+            register::PSPLIM_S.id,
+            register::EXTRA_S.id,
+        ]
+        .contains(&address);
+
+        if secure_register {
+            let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+            self.state.record_reset_status(dhcsr.s_reset_st());
+
+            if !dhcsr.s_sde() {
+                return Err(Error::architecture_specific(
+                    ArmError::SecureDebugNotEnabled,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Enable or disable halting on `condition` in DEMCR.
+    fn set_vector_catch(&mut self, condition: VectorCatch, enabled: bool) -> Result<(), Error> {
+        let mut demcr = Demcr(self.memory.read_word_32(Demcr::ADDRESS)?);
+
+        match condition {
+            VectorCatch::CoreReset => demcr.set_vc_corereset(enabled),
+            VectorCatch::HardFault => demcr.set_vc_harderr(enabled),
+            VectorCatch::MemoryManagement => demcr.set_vc_mmerr(enabled),
+            VectorCatch::BusFault => demcr.set_vc_buserr(enabled),
+            VectorCatch::StateError => demcr.set_vc_staterr(enabled),
+            VectorCatch::CheckError => demcr.set_vc_chkerr(enabled),
+            VectorCatch::CoprocessorError => demcr.set_vc_nocperr(enabled),
+            VectorCatch::InterruptError => demcr.set_vc_interr(enabled),
+            VectorCatch::SecureFault if self.state.security_extension => {
+                demcr.set_vc_sferr(enabled)
+            }
+            _ => {
+                return Err(Error::architecture_specific(
+                    ArmError::VectorCatchNotSupported(condition),
+                ))
+            }
+        }
+
+        self.memory.write_word_32(Demcr::ADDRESS, demcr.into())?;
+
+        Ok(())
+    }
+
+    /// Write to memory using `write`, with cache maintenance if the core has caches.
+    fn write_cached(
+        &mut self,
+        address: u64,
+        len: u64,
+        write: impl FnOnce(&mut Memory<'probe>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        if self.state.caches_present && self.state.cache_maintenance {
+            write_with_cache_maintenance(&mut self.memory, address, len, write)
+        } else {
+            write(&mut self.memory)
+        }
+    }
 }
 
 impl<'probe> CoreInterface for Armv8m<'probe> {
@@ -224,21 +316,15 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
     }
 
     fn read_core_reg(&mut self, address: RegisterId) -> Result<RegisterValue, Error> {
-        if self.state.current_state.is_halted() {
-            let value = super::cortex_m::read_core_reg(&mut self.memory, address)?;
-            Ok(value.into())
-        } else {
-            Err(Error::architecture_specific(ArmError::CoreNotHalted))
-        }
+        self.check_register_access(address)?;
+
+        super::cortex_m::read_core_reg_value(&mut self.memory, self.state, address)
     }
 
     fn write_core_reg(&mut self, address: RegisterId, value: RegisterValue) -> Result<(), Error> {
-        if self.state.current_state.is_halted() {
-            super::cortex_m::write_core_reg(&mut self.memory, address, value.try_into()?)?;
-            Ok(())
-        } else {
-            Err(Error::architecture_specific(ArmError::CoreNotHalted))
-        }
+        self.check_register_access(address)?;
+
+        super::cortex_m::write_core_reg_value(&mut self.memory, self.state, address, value)
     }
 
     fn available_breakpoint_units(&mut self) -> Result<u32, Error> {
@@ -281,10 +367,11 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
     }
 
     fn registers(&self) -> &'static RegisterFile {
-        if self.state.fp_present {
-            &CORTEX_M_WITH_FP_REGS
-        } else {
-            &CORTEX_M_COMMON_REGS
+        match (self.state.fp_present, self.state.security_extension) {
+            (true, true) => &CORTEX_M_SECURE_WITH_FP_REGS,
+            (true, false) => &CORTEX_M_WITH_FP_REGS,
+            (false, true) => &CORTEX_M_SECURE_REGS,
+            (false, false) => &CORTEX_M_COMMON_REGS,
         }
     }
 
@@ -318,6 +405,7 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
 
     fn status(&mut self) -> Result<crate::core::CoreStatus, Error> {
         let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        self.state.record_reset_status(dhcsr.s_reset_st());
 
         if dhcsr.s_lockup() {
             log::warn!("The core is in locked up status as a result of an unrecoverable exception");
@@ -404,6 +492,56 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
     fn fpu_support(&mut self) -> Result<bool, crate::error::Error> {
         Ok(self.state.fp_present)
     }
+
+    fn security_state(&mut self) -> Result<Option<SecurityState>, Error> {
+        if !self.state.security_extension {
+            return Ok(None);
+        }
+
+        // The current security state is only known in Debug state.
+        if !self.state.current_state.is_halted() {
+            return Err(Error::architecture_specific(ArmError::CoreNotHalted));
+        }
+
+        let dscsr = Dscsr(self.memory.read_word_32(Dscsr::ADDRESS)?);
+
+        if dscsr.cds() {
+            Ok(Some(SecurityState::Secure))
+        } else {
+            Ok(Some(SecurityState::NonSecure))
+        }
+    }
+
+    fn fault_status(&mut self) -> Result<Option<FaultStatus>, Error> {
+        read_fault_status(&mut self.memory, self.state.security_extension).map(Some)
+    }
+
+    fn reset_detected(&mut self) -> Result<bool, Error> {
+        let dhcsr = Dhcsr(self.memory.read_word_32(Dhcsr::ADDRESS)?);
+        self.state.record_reset_status(dhcsr.s_reset_st());
+
+        Ok(self.state.take_reset_detected())
+    }
+
+    fn enable_vector_catch(&mut self, condition: VectorCatch) -> Result<(), Error> {
+        self.set_vector_catch(condition, true)
+    }
+
+    fn disable_vector_catch(&mut self, condition: VectorCatch) -> Result<(), Error> {
+        self.set_vector_catch(condition, false)
+    }
+
+    fn set_cache_maintenance(&mut self, enabled: bool) {
+        self.state.cache_maintenance = enabled;
+    }
+
+    fn set_watchpoint(&mut self, config: &WatchpointConfig) -> Result<usize, Error> {
+        set_watchpoint(&mut self.memory, DwtVersion::Armv8m, config)
+    }
+
+    fn clear_watchpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        clear_watchpoint(&mut self.memory, DwtVersion::Armv8m, unit_index)
+    }
 }
 
 impl<'probe> MemoryInterface for Armv8m<'probe> {
@@ -429,22 +567,28 @@ impl<'probe> MemoryInterface for Armv8m<'probe> {
         self.memory.read_word_64(address)
     }
     fn write_word_64(&mut self, address: u64, data: u64) -> Result<(), crate::error::Error> {
-        self.memory.write_word_64(address, data)
+        self.write_cached(address, 8, |memory| memory.write_word_64(address, data))
     }
     fn write_word_32(&mut self, address: u64, data: u32) -> Result<(), Error> {
-        self.memory.write_word_32(address, data)
+        self.write_cached(address, 4, |memory| memory.write_word_32(address, data))
     }
     fn write_word_8(&mut self, address: u64, data: u8) -> Result<(), Error> {
-        self.memory.write_word_8(address, data)
+        self.write_cached(address, 1, |memory| memory.write_word_8(address, data))
     }
     fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), crate::error::Error> {
-        self.memory.write_64(address, data)
+        self.write_cached(address, data.len() as u64 * 8, |memory| {
+            memory.write_64(address, data)
+        })
     }
     fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), Error> {
-        self.memory.write_32(address, data)
+        self.write_cached(address, data.len() as u64 * 4, |memory| {
+            memory.write_32(address, data)
+        })
     }
     fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), Error> {
-        self.memory.write_8(address, data)
+        self.write_cached(address, data.len() as u64, |memory| {
+            memory.write_8(address, data)
+        })
     }
     fn flush(&mut self) -> Result<(), Error> {
         self.memory.flush()
@@ -730,6 +874,85 @@ impl MemoryMappedRegister for Dhcsr {
     const NAME: &'static str = "DHCSR";
 }
 
+bitfield! {
+    /// Debug Security Control and Status Register, DSCSR (see armv8-M Architecture Reference Manual D1.2.40)
+    ///
+    /// Only present if the Security Extension is implemented.
+    #[derive(Copy, Clone)]
+    pub struct Dscsr(u32);
+    impl Debug;
+    /// Current domain Secure key. Writes to `CDS` are ignored, unless this bit is written as `0` at the same time.
+    pub cdskey, set_cdskey: 17;
+    /// Current domain Secure. Indicates the current security state of the PE, and can be
+    /// written by the debugger in Debug state.
+    ///
+    /// `0`: PE is in Non-secure state.\
+    /// `1`: PE is in Secure state.
+    pub cds, set_cds: 16;
+    /// Secure banked register select. If `SBRSELEN` is set, this selects if the
+    /// Secure or Non-secure banked registers are accessed with the REGSEL values of
+    /// the current stack pointers.
+    pub sbrsel, set_sbrsel: 1;
+    /// Secure banked register select enable.
+    pub sbrselen, set_sbrselen: 0;
+}
+
+impl From<u32> for Dscsr {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Dscsr> for u32 {
+    fn from(value: Dscsr) -> Self {
+        value.0
+    }
+}
+
+impl MemoryMappedRegister for Dscsr {
+    const ADDRESS: u64 = 0xE000_EE08;
+    const NAME: &'static str = "DSCSR";
+}
+
+bitfield! {
+    /// Processor Feature Register 1, ID_PFR1 (see armv8-M Architecture Reference Manual D1.2.96)
+    #[derive(Copy, Clone)]
+    pub struct IdPfr1(u32);
+    impl Debug;
+    /// M-profile programmers' model.
+    pub mprogmod, _: 11, 8;
+    /// Security. Identifies whether the Security Extension is implemented.
+    ///
+    /// `0b0000`: Security Extension not implemented.\
+    /// `0b0001`: Security Extension implemented.\
+    /// `0b0011`: Security Extension implemented with state handling instructions.
+    pub security, _: 7, 4;
+}
+
+impl IdPfr1 {
+    /// Returns `true` if the Security Extension is implemented.
+    pub fn security_extension(&self) -> bool {
+        self.security() != 0
+    }
+}
+
+impl From<u32> for IdPfr1 {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<IdPfr1> for u32 {
+    fn from(value: IdPfr1) -> Self {
+        value.0
+    }
+}
+
+impl MemoryMappedRegister for IdPfr1 {
+    const ADDRESS: u64 = 0xE000_ED44;
+    const NAME: &'static str = "ID_PFR1";
+}
+
 bitfield! {
     /// Application Interrupt and Reset Control Register, AIRCR (see armv8-M Architecture Reference Manual D1.2.3)
     ///
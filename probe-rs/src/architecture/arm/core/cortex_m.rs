@@ -1,7 +1,12 @@
 //! Common functions and data types for Cortex-M core variants
 
-use crate::{DebugProbeError, Error, Memory, MemoryMappedRegister, RegisterId};
+use super::CortexMState;
+use crate::{
+    DebugProbeError, Error, Memory, MemoryMappedRegister, RegisterId, RegisterValue,
+    WatchpointAccess, WatchpointConfig, WatchpointSize,
+};
 
+use anyhow::anyhow;
 use bitfield::bitfield;
 use std::time::{Duration, Instant};
 
@@ -160,7 +165,567 @@ impl MemoryMappedRegister for Mvfr0 {
     const NAME: &'static str = "MVFR0";
 }
 
-pub(crate) fn read_core_reg(memory: &mut Memory, addr: RegisterId) -> Result<u32, Error> {
+/// Configurable Fault Status Register, which combines the MemManage, BusFault and UsageFault status registers.
+const CFSR: u64 = 0xE000_ED28;
+/// HardFault Status Register
+const HFSR: u64 = 0xE000_ED2C;
+/// MemManage Fault Address Register
+const MMFAR: u64 = 0xE000_ED34;
+/// BusFault Address Register
+const BFAR: u64 = 0xE000_ED38;
+/// Secure Fault Status Register, only present with the ARMv8-M Security Extension.
+const SFSR: u64 = 0xE000_EDE4;
+/// Secure Fault Address Register, only present with the ARMv8-M Security Extension.
+const SFAR: u64 = 0xE000_EDE8;
+
+/// A fault reported in the fault status registers of a Cortex-M core.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FaultKind {
+    /// MemManage: Instruction fetch from a location which doesn't permit execution.
+    InstructionAccessViolation,
+    /// MemManage: Data access to a location which doesn't permit the access.
+    DataAccessViolation,
+    /// MemManage: Access violation while unstacking on an exception return.
+    UnstackingAccessViolation,
+    /// MemManage: Access violation while stacking on an exception entry.
+    StackingAccessViolation,
+    /// MemManage: Access violation during lazy floating point state preservation.
+    LazyFpAccessViolation,
+    /// BusFault: Bus error on an instruction fetch.
+    InstructionBusError,
+    /// BusFault: Bus error on a data access, the faulting instruction is known.
+    PreciseDataBusError,
+    /// BusFault: Bus error on a data access, the faulting instruction is not known.
+    ImpreciseDataBusError,
+    /// BusFault: Bus error while unstacking on an exception return.
+    UnstackingBusError,
+    /// BusFault: Bus error while stacking on an exception entry.
+    StackingBusError,
+    /// BusFault: Bus error during lazy floating point state preservation.
+    LazyFpBusError,
+    /// UsageFault: Execution of an undefined instruction.
+    UndefinedInstruction,
+    /// UsageFault: Execution of an instruction with an invalid EPSR state, e.g. a cleared Thumb bit.
+    InvalidState,
+    /// UsageFault: Invalid EXC_RETURN value loaded into the PC.
+    InvalidPcLoad,
+    /// UsageFault: Access to a coprocessor which is not present or not enabled, e.g. the FPU.
+    NoCoprocessor,
+    /// UsageFault: Stack pointer limit violation (ARMv8-M only).
+    StackOverflow,
+    /// UsageFault: Unaligned access which is not permitted.
+    UnalignedAccess,
+    /// UsageFault: Integer division by zero, if trapping is enabled.
+    DivideByZero,
+    /// HardFault: Bus error while reading the vector table.
+    VectorTableRead,
+    /// HardFault: A debug event occurred while debugging was disabled.
+    DebugEvent,
+    /// SecureFault: Branch to a Secure address which is not a valid entry point.
+    InvalidEntryPoint,
+    /// SecureFault: Invalid integrity signature while unstacking.
+    InvalidIntegritySignature,
+    /// SecureFault: Invalid exception return.
+    InvalidExceptionReturn,
+    /// SecureFault: Attribution unit violation, e.g. a Non-secure access to Secure memory.
+    AttributionUnitViolation,
+    /// SecureFault: Invalid transition from Secure to Non-secure state.
+    InvalidTransition,
+    /// SecureFault: Error during lazy floating point state preservation.
+    LazyStatePreservationError,
+    /// SecureFault: Error during lazy floating point state activation.
+    LazyStateError,
+}
+
+impl std::fmt::Display for FaultKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            FaultKind::InstructionAccessViolation => "MemManage: instruction access violation",
+            FaultKind::DataAccessViolation => "MemManage: data access violation",
+            FaultKind::UnstackingAccessViolation => "MemManage: access violation on unstacking",
+            FaultKind::StackingAccessViolation => "MemManage: access violation on stacking",
+            FaultKind::LazyFpAccessViolation => {
+                "MemManage: access violation on lazy FP state preservation"
+            }
+            FaultKind::InstructionBusError => "BusFault: instruction bus error",
+            FaultKind::PreciseDataBusError => "BusFault: precise data bus error",
+            FaultKind::ImpreciseDataBusError => "BusFault: imprecise data bus error",
+            FaultKind::UnstackingBusError => "BusFault: bus error on unstacking",
+            FaultKind::StackingBusError => "BusFault: bus error on stacking",
+            FaultKind::LazyFpBusError => "BusFault: bus error on lazy FP state preservation",
+            FaultKind::UndefinedInstruction => "UsageFault: undefined instruction",
+            FaultKind::InvalidState => "UsageFault: invalid state (Thumb bit cleared?)",
+            FaultKind::InvalidPcLoad => "UsageFault: invalid EXC_RETURN loaded into the PC",
+            FaultKind::NoCoprocessor => "UsageFault: coprocessor not present or not enabled",
+            FaultKind::StackOverflow => "UsageFault: stack overflow",
+            FaultKind::UnalignedAccess => "UsageFault: unaligned access",
+            FaultKind::DivideByZero => "UsageFault: division by zero",
+            FaultKind::VectorTableRead => "HardFault: bus error on vector table read",
+            FaultKind::DebugEvent => "HardFault: debug event",
+            FaultKind::InvalidEntryPoint => "SecureFault: invalid entry point",
+            FaultKind::InvalidIntegritySignature => "SecureFault: invalid integrity signature",
+            FaultKind::InvalidExceptionReturn => "SecureFault: invalid exception return",
+            FaultKind::AttributionUnitViolation => "SecureFault: attribution unit violation",
+            FaultKind::InvalidTransition => "SecureFault: invalid transition",
+            FaultKind::LazyStatePreservationError => "SecureFault: lazy state preservation error",
+            FaultKind::LazyStateError => "SecureFault: lazy state error",
+        };
+
+        f.write_str(description)
+    }
+}
+
+/// The decoded fault status registers of a Cortex-M core.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaultStatus {
+    /// All faults which are reported in the fault status registers.
+    pub faults: Vec<FaultKind>,
+    /// `true` if a configurable fault was escalated to a HardFault.
+    pub escalated: bool,
+    /// The address of the access which caused a MemManage fault, if valid.
+    pub memory_fault_address: Option<u32>,
+    /// The address of the access which caused a precise BusFault, if valid.
+    pub bus_fault_address: Option<u32>,
+    /// The address of the access which caused a SecureFault, if valid.
+    pub secure_fault_address: Option<u32>,
+    /// The raw value of the HardFault Status Register (HFSR).
+    pub hfsr: u32,
+    /// The raw value of the Configurable Fault Status Register (CFSR).
+    pub cfsr: u32,
+    /// The raw value of the Secure Fault Status Register (SFSR), if present.
+    pub sfsr: Option<u32>,
+}
+
+impl FaultStatus {
+    /// Decode the values of the fault status and fault address registers.
+    ///
+    /// `secure` contains the values of SFSR and SFAR, if the core implements the Security Extension.
+    pub fn decode(hfsr: u32, cfsr: u32, mmfar: u32, bfar: u32, secure: Option<(u32, u32)>) -> Self {
+        const CFSR_FAULTS: &[(u32, FaultKind)] = &[
+            (0, FaultKind::InstructionAccessViolation),
+            (1, FaultKind::DataAccessViolation),
+            (3, FaultKind::UnstackingAccessViolation),
+            (4, FaultKind::StackingAccessViolation),
+            (5, FaultKind::LazyFpAccessViolation),
+            (8, FaultKind::InstructionBusError),
+            (9, FaultKind::PreciseDataBusError),
+            (10, FaultKind::ImpreciseDataBusError),
+            (11, FaultKind::UnstackingBusError),
+            (12, FaultKind::StackingBusError),
+            (13, FaultKind::LazyFpBusError),
+            (16, FaultKind::UndefinedInstruction),
+            (17, FaultKind::InvalidState),
+            (18, FaultKind::InvalidPcLoad),
+            (19, FaultKind::NoCoprocessor),
+            (20, FaultKind::StackOverflow),
+            (24, FaultKind::UnalignedAccess),
+            (25, FaultKind::DivideByZero),
+        ];
+        const HFSR_FAULTS: &[(u32, FaultKind)] =
+            &[(1, FaultKind::VectorTableRead), (31, FaultKind::DebugEvent)];
+        const SFSR_FAULTS: &[(u32, FaultKind)] = &[
+            (0, FaultKind::InvalidEntryPoint),
+            (1, FaultKind::InvalidIntegritySignature),
+            (2, FaultKind::InvalidExceptionReturn),
+            (3, FaultKind::AttributionUnitViolation),
+            (4, FaultKind::InvalidTransition),
+            (5, FaultKind::LazyStatePreservationError),
+            (7, FaultKind::LazyStateError),
+        ];
+
+        let set_faults = |value: u32, faults: &'static [(u32, FaultKind)]| {
+            faults
+                .iter()
+                .filter(move |(bit, _)| value & (1 << bit) != 0)
+                .map(|(_, fault)| *fault)
+        };
+
+        let sfsr = secure.map(|(sfsr, _)| sfsr).unwrap_or(0);
+
+        let faults = set_faults(hfsr, HFSR_FAULTS)
+            .chain(set_faults(cfsr, CFSR_FAULTS))
+            .chain(set_faults(sfsr, SFSR_FAULTS))
+            .collect();
+
+        FaultStatus {
+            faults,
+            escalated: hfsr & (1 << 30) != 0,
+            // MMFSR.MMARVALID
+            memory_fault_address: (cfsr & (1 << 7) != 0).then_some(mmfar),
+            // BFSR.BFARVALID
+            bus_fault_address: (cfsr & (1 << 15) != 0).then_some(bfar),
+            // SFSR.SFARVALID
+            secure_fault_address: secure
+                .and_then(|(sfsr, sfar)| (sfsr & (1 << 6) != 0).then_some(sfar)),
+            hfsr,
+            cfsr,
+            sfsr: secure.map(|(sfsr, _)| sfsr),
+        }
+    }
+
+    /// Returns `true` if any fault is reported.
+    pub fn is_faulted(&self) -> bool {
+        !self.faults.is_empty() || self.escalated
+    }
+}
+
+/// Read and decode the fault status registers of an ARMv7-M or ARMv8-M core.
+pub(crate) fn read_fault_status(
+    memory: &mut Memory,
+    security_extension: bool,
+) -> Result<FaultStatus, Error> {
+    let hfsr = memory.read_word_32(HFSR)?;
+    let cfsr = memory.read_word_32(CFSR)?;
+    let mmfar = memory.read_word_32(MMFAR)?;
+    let bfar = memory.read_word_32(BFAR)?;
+
+    let secure = if security_extension {
+        Some((memory.read_word_32(SFSR)?, memory.read_word_32(SFAR)?))
+    } else {
+        None
+    };
+
+    Ok(FaultStatus::decode(hfsr, cfsr, mmfar, bfar, secure))
+}
+
+/// Configuration and Control Register
+const CCR: u64 = 0xE000_ED14;
+/// CCR.DC, set if the data cache is enabled.
+const CCR_DC: u32 = 1 << 16;
+/// CCR.IC, set if the instruction cache is enabled.
+const CCR_IC: u32 = 1 << 17;
+/// Cache Level ID Register, which reads as zero if the core has no caches.
+const CLIDR: u64 = 0xE000_ED78;
+/// Cache Type Register
+const CTR: u64 = 0xE000_ED7C;
+/// Instruction cache invalidate all to the Point of Unification.
+const ICIALLU: u64 = 0xE000_EF50;
+/// Data cache clean and invalidate by address to the Point of Coherency.
+const DCCIMVAC: u64 = 0xE000_EF70;
+
+/// Returns `true` if the core implements an instruction or data cache, like the Cortex-M7 or Cortex-M55.
+pub(crate) fn caches_present(memory: &mut Memory) -> Result<bool, Error> {
+    Ok(memory.read_word_32(CLIDR)? != 0)
+}
+
+/// Returns `true` if the range is in the Code, SRAM or external RAM region,
+/// which are the only cacheable regions in the default memory map.
+fn is_cacheable(address: u64, len: u64) -> bool {
+    let end = address.saturating_add(len);
+
+    address < 0x4000_0000 || (address < 0xA000_0000 && end > 0x6000_0000)
+}
+
+/// Write to memory using `write`, and keep the caches of the core coherent with the written data.
+///
+/// Debugger accesses bypass the caches, so the data cache lines of the written range are cleaned
+/// and invalidated before the write, to ensure that neither dirty lines overwrite the written data
+/// later, nor stale lines hide it from the core. Afterwards, the instruction cache is invalidated,
+/// in case the written data contains code, e.g. a software breakpoint or a loaded program.
+pub(crate) fn write_with_cache_maintenance<'probe>(
+    memory: &mut Memory<'probe>,
+    address: u64,
+    len: u64,
+    write: impl FnOnce(&mut Memory<'probe>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    if len == 0 || !is_cacheable(address, len) {
+        return write(memory);
+    }
+
+    let ccr = memory.read_word_32(CCR)?;
+
+    if ccr & CCR_DC != 0 {
+        // CTR.DminLine is the log2 of the number of words in the smallest data cache line.
+        let line_size = 4 << ((memory.read_word_32(CTR)? >> 16) & 0xf);
+
+        let start = address & !(line_size - 1);
+        let end = address.saturating_add(len);
+
+        for line in (start..end).step_by(line_size as usize) {
+            memory.write_word_32(DCCIMVAC, line as u32)?;
+        }
+    }
+
+    write(memory)?;
+
+    if ccr & CCR_IC != 0 {
+        memory.write_word_32(ICIALLU, 0)?;
+    }
+
+    Ok(())
+}
+
+/// Debug Exception and Monitor Control Register
+const DEMCR: u64 = 0xE000_EDFC;
+/// DEMCR.TRCENA, which enables the DWT. This is called DWTENA on ARMv6-M.
+const DEMCR_TRCENA: u32 = 1 << 24;
+/// DWT Control Register
+const DWT_CTRL: u64 = 0xE000_1000;
+/// Comparator register of the first DWT comparator. Each comparator has a block of
+/// 16 bytes, with the comparator, mask and function registers.
+const DWT_COMP0: u64 = 0xE000_1020;
+/// Offset of the DWT_MASK register in a comparator block, which doesn't exist on ARMv8-M.
+const DWT_MASK_OFFSET: u64 = 0x4;
+/// Offset of the DWT_FUNCTION register in a comparator block.
+const DWT_FUNCTION_OFFSET: u64 = 0x8;
+/// DWT_FUNCTION.DATAVMATCH on ARMv7-M.
+const DWT_FUNCTION_DATAVMATCH: u32 = 1 << 8;
+/// DWT_FUNCTION.ACTION on ARMv8-M, set to generate a debug event.
+const DWT_FUNCTION_ACTION_DEBUG_EVENT: u32 = 0b01 << 4;
+/// DWT_FUNCTION.MATCH on ARMv8-M for a data value comparison linked to the previous comparator.
+const DWT_FUNCTION_MATCH_LINKED_VALUE: u32 = 0b1011;
+
+/// The version of the DWT, which defines the encoding of the DWT_FUNCTION registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DwtVersion {
+    /// ARMv6-M, which only supports address comparisons.
+    Armv6m,
+    /// ARMv7-M, which supports data value comparisons on comparator 1.
+    Armv7m,
+    /// ARMv8-M, which supports data value comparisons linked to an address comparator.
+    Armv8m,
+}
+
+fn dwt_register(unit: usize, offset: u64) -> u64 {
+    DWT_COMP0 + 0x10 * unit as u64 + offset
+}
+
+/// The DATAVSIZE encoding of the size of a watchpoint.
+fn datavsize(size: WatchpointSize) -> u32 {
+    match size {
+        WatchpointSize::Byte => 0b00,
+        WatchpointSize::HalfWord => 0b01,
+        WatchpointSize::Word => 0b10,
+    }
+}
+
+/// The comparator value for a data value comparison, which has to be replicated
+/// into all byte lanes for byte and halfword sizes.
+fn replicate_value(value: u32, size: WatchpointSize) -> u32 {
+    match size {
+        WatchpointSize::Byte => (value & 0xff) * 0x0101_0101,
+        WatchpointSize::HalfWord => (value & 0xffff) * 0x0001_0001,
+        WatchpointSize::Word => value,
+    }
+}
+
+/// The DWT_FUNCTION value of an ARMv6-M or ARMv7-M watchpoint. For a data value comparison,
+/// `address_unit` is the comparator which contains the linked address.
+fn armv7m_function(config: &WatchpointConfig, address_unit: Option<usize>) -> u32 {
+    let mut function = match config.access {
+        WatchpointAccess::Read => 0b0101,
+        WatchpointAccess::Write => 0b0110,
+        WatchpointAccess::ReadWrite => 0b0111,
+    };
+
+    if let Some(unit) = address_unit {
+        // Use the same comparator for both DATAVADDR0 and DATAVADDR1, in case a second
+        // linked address comparator is supported.
+        function |= DWT_FUNCTION_DATAVMATCH
+            | datavsize(config.size) << 10
+            | (unit as u32) << 12
+            | (unit as u32) << 16;
+    }
+
+    function
+}
+
+/// The DWT_FUNCTION value of an ARMv8-M data address comparison. If `debug_event` is not set,
+/// the comparator only triggers the linked data value comparison of the next comparator.
+fn armv8m_address_function(config: &WatchpointConfig, debug_event: bool) -> u32 {
+    let match_ = match config.access {
+        WatchpointAccess::ReadWrite => 0b0100,
+        WatchpointAccess::Write => 0b0101,
+        WatchpointAccess::Read => 0b0110,
+    };
+
+    let action = if debug_event {
+        DWT_FUNCTION_ACTION_DEBUG_EVENT
+    } else {
+        0
+    };
+
+    match_ | action | datavsize(config.size) << 10
+}
+
+/// The DWT_FUNCTION value of an ARMv8-M data value comparison, linked to the previous comparator.
+fn armv8m_linked_value_function(config: &WatchpointConfig) -> u32 {
+    DWT_FUNCTION_MATCH_LINKED_VALUE | DWT_FUNCTION_ACTION_DEBUG_EVENT | datavsize(config.size) << 10
+}
+
+/// Returns which of the DWT comparators, given their DWT_FUNCTION values, are unused.
+fn free_dwt_units(version: DwtVersion, functions: &[u32]) -> Vec<bool> {
+    let mut free: Vec<bool> = functions
+        .iter()
+        .map(|function| function & 0xf == 0)
+        .collect();
+
+    // On ARMv7-M, the address comparator linked to a data value comparison is disabled,
+    // but still in use.
+    if version == DwtVersion::Armv7m && functions.len() > 1 {
+        let function = functions[1];
+
+        if function & 0xf != 0 && function & DWT_FUNCTION_DATAVMATCH != 0 {
+            let linked = ((function >> 12) & 0xf) as usize;
+
+            if let Some(unit) = free.get_mut(linked) {
+                *unit = false;
+            }
+        }
+    }
+
+    free
+}
+
+/// Configure a DWT comparator for a data watchpoint, and return the index of the comparator
+/// which generates the debug event.
+pub(crate) fn set_watchpoint(
+    memory: &mut Memory,
+    version: DwtVersion,
+    config: &WatchpointConfig,
+) -> Result<usize, Error> {
+    let size = config.size.bytes();
+
+    if config.address % size != 0 || config.address > u32::MAX as u64 {
+        return Err(Error::Other(anyhow!(
+            "Watchpoint address {:#010x} is not a 32-bit address aligned to {} bytes",
+            config.address,
+            size
+        )));
+    }
+
+    let address = config.address as u32;
+
+    // The DWT is only accessible if it is enabled in DEMCR.
+    let demcr = memory.read_word_32(DEMCR)?;
+    memory.write_word_32(DEMCR, demcr | DEMCR_TRCENA)?;
+
+    let num_comparators = (memory.read_word_32(DWT_CTRL)? >> 28) as usize;
+
+    let mut functions = Vec::with_capacity(num_comparators);
+    for unit in 0..num_comparators {
+        functions.push(memory.read_word_32(dwt_register(unit, DWT_FUNCTION_OFFSET))?);
+    }
+
+    let free = free_dwt_units(version, &functions);
+
+    let no_comparator = || Error::Other(anyhow!("No available DWT comparators for the watchpoint"));
+
+    match (version, config.value) {
+        (DwtVersion::Armv8m, None) => {
+            let unit = free
+                .iter()
+                .position(|free| *free)
+                .ok_or_else(no_comparator)?;
+
+            memory.write_word_32(dwt_register(unit, 0), address)?;
+            memory.write_word_32(
+                dwt_register(unit, DWT_FUNCTION_OFFSET),
+                armv8m_address_function(config, true),
+            )?;
+
+            Ok(unit)
+        }
+        (_, None) => {
+            let unit = free
+                .iter()
+                .position(|free| *free)
+                .ok_or_else(no_comparator)?;
+
+            memory.write_word_32(dwt_register(unit, 0), address)?;
+            memory.write_word_32(dwt_register(unit, DWT_MASK_OFFSET), size.trailing_zeros())?;
+            memory.write_word_32(
+                dwt_register(unit, DWT_FUNCTION_OFFSET),
+                armv7m_function(config, None),
+            )?;
+
+            Ok(unit)
+        }
+        (DwtVersion::Armv6m, Some(_)) => Err(Error::Other(anyhow!(
+            "Data value watchpoints are not supported on ARMv6-M cores"
+        ))),
+        (DwtVersion::Armv7m, Some(value)) => {
+            // Only comparator 1 supports data value comparisons.
+            if !free.get(1).copied().unwrap_or(false) {
+                return Err(no_comparator());
+            }
+
+            let address_unit = free
+                .iter()
+                .enumerate()
+                .position(|(unit, free)| unit != 1 && *free)
+                .ok_or_else(no_comparator)?;
+
+            memory.write_word_32(dwt_register(address_unit, 0), address)?;
+            memory.write_word_32(
+                dwt_register(address_unit, DWT_MASK_OFFSET),
+                size.trailing_zeros(),
+            )?;
+            memory.write_word_32(dwt_register(address_unit, DWT_FUNCTION_OFFSET), 0)?;
+
+            memory.write_word_32(dwt_register(1, 0), replicate_value(value, config.size))?;
+            memory.write_word_32(
+                dwt_register(1, DWT_FUNCTION_OFFSET),
+                armv7m_function(config, Some(address_unit)),
+            )?;
+
+            Ok(1)
+        }
+        (DwtVersion::Armv8m, Some(value)) => {
+            // The data value comparator is linked to the address comparator before it.
+            let unit = (1..free.len())
+                .find(|unit| free[unit - 1] && free[*unit])
+                .ok_or_else(no_comparator)?;
+
+            memory.write_word_32(dwt_register(unit - 1, 0), address)?;
+            memory.write_word_32(
+                dwt_register(unit - 1, DWT_FUNCTION_OFFSET),
+                armv8m_address_function(config, false),
+            )?;
+
+            memory.write_word_32(dwt_register(unit, 0), replicate_value(value, config.size))?;
+            memory.write_word_32(
+                dwt_register(unit, DWT_FUNCTION_OFFSET),
+                armv8m_linked_value_function(config),
+            )?;
+
+            Ok(unit)
+        }
+    }
+}
+
+/// Disable the data watchpoint of DWT comparator `unit`, as returned by [`set_watchpoint`].
+pub(crate) fn clear_watchpoint(
+    memory: &mut Memory,
+    version: DwtVersion,
+    unit: usize,
+) -> Result<(), Error> {
+    let num_comparators = (memory.read_word_32(DWT_CTRL)? >> 28) as usize;
+
+    if unit >= num_comparators {
+        return Err(Error::Other(anyhow!(
+            "Watchpoint unit {} is not available, the core has {} DWT comparators",
+            unit,
+            num_comparators
+        )));
+    }
+
+    let function = memory.read_word_32(dwt_register(unit, DWT_FUNCTION_OFFSET))?;
+    memory.write_word_32(dwt_register(unit, DWT_FUNCTION_OFFSET), 0)?;
+
+    // Also release the address comparator of a linked data value comparison.
+    if version == DwtVersion::Armv8m && function & 0xf == DWT_FUNCTION_MATCH_LINKED_VALUE {
+        memory.write_word_32(dwt_register(unit - 1, DWT_FUNCTION_OFFSET), 0)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn read_core_reg(
+    memory: &mut Memory,
+    state: &mut CortexMState,
+    addr: RegisterId,
+) -> Result<u32, Error> {
     // Write the DCRSR value to select the register we want to read.
     let mut dcrsr_val = Dcrsr(0);
     dcrsr_val.set_regwnr(false); // Perform a read.
@@ -168,7 +733,7 @@ pub(crate) fn read_core_reg(memory: &mut Memory, addr: RegisterId) -> Result<u32
 
     memory.write_word_32(Dcrsr::ADDRESS, dcrsr_val.into())?;
 
-    wait_for_core_register_transfer(memory, Duration::from_millis(100))?;
+    wait_for_core_register_transfer(memory, state, Duration::from_millis(100))?;
 
     let value = memory.read_word_32(Dcrdr::ADDRESS)?;
 
@@ -177,6 +742,7 @@ pub(crate) fn read_core_reg(memory: &mut Memory, addr: RegisterId) -> Result<u32
 
 pub(crate) fn write_core_reg(
     memory: &mut Memory,
+    state: &mut CortexMState,
     addr: RegisterId,
     value: u32,
 ) -> Result<(), Error> {
@@ -189,18 +755,80 @@ pub(crate) fn write_core_reg(
 
     memory.write_word_32(Dcrsr::ADDRESS, dcrsr_val.into())?;
 
-    wait_for_core_register_transfer(memory, Duration::from_millis(100))?;
+    wait_for_core_register_transfer(memory, state, Duration::from_millis(100))?;
 
     Ok(())
 }
 
-fn wait_for_core_register_transfer(memory: &mut Memory, timeout: Duration) -> Result<(), Error> {
+/// ID of the first double precision FPU register, D0.
+///
+/// The D registers are not part of the DCRSR register selection, so the IDs
+/// are chosen outside of its range.
+const FP_DOUBLE_REGISTER_BASE: u16 = 0x100;
+
+/// ID of the first single precision FPU register, S0, as used by DCRSR.
+const FP_SINGLE_REGISTER_BASE: u16 = 0b100_0000;
+
+/// Returns the IDs of the two S registers which make up the given D register,
+/// lower half first, or `None` if `addr` is not a D register.
+fn fp_double_register_halves(addr: RegisterId) -> Option<(RegisterId, RegisterId)> {
+    let index = addr.0.checked_sub(FP_DOUBLE_REGISTER_BASE)?;
+
+    if index < 16 {
+        let low = FP_SINGLE_REGISTER_BASE + 2 * index;
+        Some((RegisterId(low), RegisterId(low + 1)))
+    } else {
+        None
+    }
+}
+
+/// Read a core register, including the double precision views of the FPU registers.
+pub(crate) fn read_core_reg_value(
+    memory: &mut Memory,
+    state: &mut CortexMState,
+    addr: RegisterId,
+) -> Result<RegisterValue, Error> {
+    match fp_double_register_halves(addr) {
+        Some((low, high)) => {
+            let low = read_core_reg(memory, state, low)?;
+            let high = read_core_reg(memory, state, high)?;
+
+            Ok(((u64::from(high) << 32) | u64::from(low)).into())
+        }
+        None => Ok(read_core_reg(memory, state, addr)?.into()),
+    }
+}
+
+/// Write a core register, including the double precision views of the FPU registers.
+pub(crate) fn write_core_reg_value(
+    memory: &mut Memory,
+    state: &mut CortexMState,
+    addr: RegisterId,
+    value: RegisterValue,
+) -> Result<(), Error> {
+    match fp_double_register_halves(addr) {
+        Some((low, high)) => {
+            let value: u64 = value.try_into()?;
+
+            write_core_reg(memory, state, low, value as u32)?;
+            write_core_reg(memory, state, high, (value >> 32) as u32)
+        }
+        None => write_core_reg(memory, state, addr, value.try_into()?),
+    }
+}
+
+fn wait_for_core_register_transfer(
+    memory: &mut Memory,
+    state: &mut CortexMState,
+    timeout: Duration,
+) -> Result<(), Error> {
     // now we have to poll the dhcsr register, until the dhcsr.s_regrdy bit is set
     // (see C1-292, cortex m0 arm)
     let start = Instant::now();
 
     while start.elapsed() < timeout {
         let dhcsr_val = Dhcsr(memory.read_word_32(Dhcsr::ADDRESS)?);
+        state.record_reset_status(dhcsr_val.s_reset_st());
 
         if dhcsr_val.s_regrdy() {
             return Ok(());
@@ -208,3 +836,119 @@ fn wait_for_core_register_transfer(memory: &mut Memory, timeout: Duration) -> Re
     }
     Err(Error::Probe(DebugProbeError::Timeout))
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        armv7m_function, armv8m_address_function, armv8m_linked_value_function,
+        fp_double_register_halves, free_dwt_units, is_cacheable, replicate_value, DwtVersion,
+        FaultKind, FaultStatus,
+    };
+    use crate::{RegisterId, WatchpointAccess, WatchpointConfig, WatchpointSize};
+
+    #[test]
+    fn cacheable_regions() {
+        assert!(is_cacheable(0x2000_0000, 4));
+        assert!(is_cacheable(0x3FFF_FFFC, 8));
+        assert!(is_cacheable(0x9000_0000, 4));
+        assert!(!is_cacheable(0x4000_0000, 4));
+        assert!(!is_cacheable(0xE000_ED14, 4));
+    }
+
+    #[test]
+    fn escalated_precise_bus_fault() {
+        let status = FaultStatus::decode(1 << 30, (1 << 15) | (1 << 9), 0, 0x2000_1000, None);
+
+        assert!(status.is_faulted());
+        assert!(status.escalated);
+        assert_eq!(status.faults, vec![FaultKind::PreciseDataBusError]);
+        assert_eq!(status.bus_fault_address, Some(0x2000_1000));
+        assert_eq!(status.memory_fault_address, None);
+    }
+
+    #[test]
+    fn usage_and_secure_faults() {
+        let status = FaultStatus::decode(
+            0,
+            (1 << 25) | (1 << 16),
+            0x1234,
+            0,
+            Some(((1 << 6) | (1 << 3), 0x3000_0000)),
+        );
+
+        assert!(!status.escalated);
+        assert_eq!(
+            status.faults,
+            vec![
+                FaultKind::UndefinedInstruction,
+                FaultKind::DivideByZero,
+                FaultKind::AttributionUnitViolation
+            ]
+        );
+        assert_eq!(status.memory_fault_address, None);
+        assert_eq!(status.secure_fault_address, Some(0x3000_0000));
+    }
+
+    #[test]
+    fn no_fault() {
+        assert!(!FaultStatus::decode(0, 0, 0, 0, None).is_faulted());
+    }
+
+    #[test]
+    fn double_register_halves() {
+        assert_eq!(
+            fp_double_register_halves(RegisterId(0x100)),
+            Some((RegisterId(64), RegisterId(65)))
+        );
+        assert_eq!(
+            fp_double_register_halves(RegisterId(0x10f)),
+            Some((RegisterId(94), RegisterId(95)))
+        );
+        assert_eq!(fp_double_register_halves(RegisterId(0x110)), None);
+        assert_eq!(fp_double_register_halves(RegisterId(64)), None);
+    }
+
+    #[test]
+    fn armv7m_watchpoint_function() {
+        let config = WatchpointConfig::new(0x2000_0000, WatchpointAccess::Write);
+        assert_eq!(armv7m_function(&config, None), 0b0110);
+
+        let config = config
+            .with_size(WatchpointSize::HalfWord)
+            .with_value(0x1234);
+        assert_eq!(
+            armv7m_function(&config, Some(2)),
+            0b0110 | (1 << 8) | (0b01 << 10) | (2 << 12) | (2 << 16)
+        );
+    }
+
+    #[test]
+    fn armv8m_watchpoint_function() {
+        let config = WatchpointConfig::new(0x2000_0000, WatchpointAccess::Read)
+            .with_size(WatchpointSize::Byte)
+            .with_value(0x42);
+
+        assert_eq!(armv8m_address_function(&config, true), 0b0110 | (0b01 << 4));
+        assert_eq!(armv8m_address_function(&config, false), 0b0110);
+        assert_eq!(armv8m_linked_value_function(&config), 0b1011 | (0b01 << 4));
+        assert_eq!(replicate_value(0x1242, WatchpointSize::Byte), 0x4242_4242);
+        assert_eq!(
+            replicate_value(0x1234, WatchpointSize::HalfWord),
+            0x1234_1234
+        );
+    }
+
+    #[test]
+    fn linked_address_comparator_is_not_free() {
+        let value_function = 0b0110 | (1 << 8) | (2 << 12);
+
+        assert_eq!(
+            free_dwt_units(DwtVersion::Armv7m, &[0, value_function, 0, 0]),
+            vec![true, false, false, true]
+        );
+        assert_eq!(
+            free_dwt_units(DwtVersion::Armv8m, &[0, value_function, 0, 0]),
+            vec![true, false, true, true]
+        );
+    }
+}
@@ -3,7 +3,7 @@ use crate::{
         BreakpointCause, MemoryMappedRegister, RegisterDataType, RegisterDescription, RegisterFile,
         RegisterId, RegisterKind, RegisterValue,
     },
-    CoreStatus, HaltReason,
+    CoreStatus, HaltReason, VectorCatch,
 };
 
 use bitfield::bitfield;
@@ -112,6 +112,90 @@ pub(crate) mod register {
         size_in_bits: 32,
     };
 
+    pub const MSP_NS: RegisterDescription = RegisterDescription {
+        name: "MSP_NS",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b11000),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    pub const PSP_NS: RegisterDescription = RegisterDescription {
+        name: "PSP_NS",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b11001),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    pub const MSP_S: RegisterDescription = RegisterDescription {
+        name: "MSP_S",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b11010),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    pub const PSP_S: RegisterDescription = RegisterDescription {
+        name: "PSP_S",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b11011),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    pub const MSPLIM_S: RegisterDescription = RegisterDescription {
+        name: "MSPLIM_S",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b11100),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    pub const PSPLIM_S: RegisterDescription = RegisterDescription {
+        name: "PSPLIM_S",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b11101),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    pub const MSPLIM_NS: RegisterDescription = RegisterDescription {
+        name: "MSPLIM_NS",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b11110),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    pub const PSPLIM_NS: RegisterDescription = RegisterDescription {
+        name: "PSPLIM_NS",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b11111),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    // Secure CONTROL bits [31:24], FAULTMASK bits [23:16],
+    // BASEPRI bits [15:8], and PRIMASK bits [7:0]
+    pub const EXTRA_S: RegisterDescription = RegisterDescription {
+        name: "EXTRA_S",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b100010),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
+    // Non-secure CONTROL bits [31:24], FAULTMASK bits [23:16],
+    // BASEPRI bits [15:8], and PRIMASK bits [7:0]
+    pub const EXTRA_NS: RegisterDescription = RegisterDescription {
+        name: "EXTRA_NS",
+        _kind: RegisterKind::General,
+        id: RegisterId(0b100011),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 32,
+    };
+
     pub const FP: RegisterDescription = RegisterDescription {
         name: "FP",
         _kind: RegisterKind::General,
@@ -313,6 +397,7 @@ static ARM32_COMMON_REGS: RegisterFile = RegisterFile {
 
     fp_status: None,
     fp_registers: None,
+    fp_double_registers: None,
 };
 
 static AARCH32_COMMON_REGS: RegisterFile = RegisterFile {
@@ -682,6 +767,34 @@ static CORTEX_M_COMMON_REGS: RegisterFile = RegisterFile {
     ..ARM32_COMMON_REGS
 };
 
+/// Registers of ARMv8-M cores with the Security Extension, which are banked
+/// between the Secure and the Non-secure state.
+const CORTEX_M_SECURITY_EXTENSION_REGS: &[RegisterDescription] = &[
+    register::EXTRA,
+    register::MSP_S,
+    register::PSP_S,
+    register::MSP_NS,
+    register::PSP_NS,
+    register::MSPLIM_S,
+    register::PSPLIM_S,
+    register::MSPLIM_NS,
+    register::PSPLIM_NS,
+    register::EXTRA_S,
+    register::EXTRA_NS,
+];
+
+static CORTEX_M_SECURE_REGS: RegisterFile = RegisterFile {
+    other: CORTEX_M_SECURITY_EXTENSION_REGS,
+
+    ..CORTEX_M_COMMON_REGS
+};
+
+static CORTEX_M_SECURE_WITH_FP_REGS: RegisterFile = RegisterFile {
+    other: CORTEX_M_SECURITY_EXTENSION_REGS,
+
+    ..CORTEX_M_WITH_FP_REGS
+};
+
 static CORTEX_M_WITH_FP_REGS: RegisterFile = RegisterFile {
     fp_status: Some(&register::FPSCR),
     fp_registers: Some(&[
@@ -910,6 +1023,122 @@ static CORTEX_M_WITH_FP_REGS: RegisterFile = RegisterFile {
             size_in_bits: 32,
         },
     ]),
+    // The D registers can't be selected through DCRSR, they are
+    // accessed as two consecutive S registers instead.
+    fp_double_registers: Some(&[
+        RegisterDescription {
+            name: "D0",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(256),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D1",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(257),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D2",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(258),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D3",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(259),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D4",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(260),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D5",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(261),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D6",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(262),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D7",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(263),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D8",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(264),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D9",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(265),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D10",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(266),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D11",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(267),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D12",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(268),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D13",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(269),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D14",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(270),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "D15",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(271),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+    ]),
 
     ..CORTEX_M_COMMON_REGS
 };
@@ -997,6 +1226,15 @@ pub struct CortexMState {
     current_state: CoreStatus,
 
     fp_present: bool,
+
+    security_extension: bool,
+
+    caches_present: bool,
+
+    cache_maintenance: bool,
+
+    /// The sticky reset status of DHCSR was set since the last call of `take_reset_detected`.
+    reset_detected: bool,
 }
 
 impl CortexMState {
@@ -1006,6 +1244,10 @@ impl CortexMState {
             hw_breakpoints_enabled: false,
             current_state: CoreStatus::Unknown,
             fp_present: false,
+            security_extension: false,
+            caches_present: false,
+            cache_maintenance: true,
+            reset_detected: false,
         }
     }
 
@@ -1016,6 +1258,19 @@ impl CortexMState {
     fn initialized(&self) -> bool {
         self.initialized
     }
+
+    /// Remember the sticky reset status of a DHCSR value.
+    ///
+    /// Reading DHCSR clears the status, so it has to be recorded by every read of DHCSR, to report
+    /// the reset in `take_reset_detected`.
+    fn record_reset_status(&mut self, s_reset_st: bool) {
+        self.reset_detected |= s_reset_st;
+    }
+
+    /// Returns `true` if a reset was recorded since the last call.
+    fn take_reset_detected(&mut self) -> bool {
+        std::mem::take(&mut self.reset_detected)
+    }
 }
 
 #[derive(Debug)]
@@ -1057,4 +1312,8 @@ impl CortexAState {
 pub enum ArmError {
     #[error("The core needs to be halted for this operation but was not.")]
     CoreNotHalted,
+    #[error("Secure debug is not enabled, so secure registers can not be accessed.")]
+    SecureDebugNotEnabled,
+    #[error("Vector catch for {0:?} is not supported by this core.")]
+    VectorCatchNotSupported(VectorCatch),
 }
@@ -21,6 +21,12 @@ pub enum DebugPortError {
         /// The version of the operated debug port.
         version: DebugPortVersion,
     },
+    /// The AP with the given number was not found in the debug address space of the debug port.
+    #[error("AP {0} was not found in the debug address space")]
+    AccessPortNotFound(u8),
+    /// The address is outside of the debug address space implemented by the debug port.
+    #[error("Address {0:#x} is outside of the debug address space")]
+    AddressOutOfRange(u64),
     /// An error with operating the debug probe occurred.
     #[error("A Debug Probe Error occurred")]
     DebugProbe(#[from] DebugProbeError),
@@ -292,6 +298,9 @@ bitfield! {
     /// Selects the active four-word register bank on the current AP. See Using the AP to access debug resources on page A1-31.
     /// After a powerup reset, the value of this field is UNKNOWN.
     pub u8, ap_bank_sel, set_ap_bank_sel: 7, 4;
+    /// Bits `[31:4]` of the address in the debug address space which is accessed with AP accesses.
+    /// This replaces the APSEL and APBANKSEL fields in DPv3.
+    pub u32, addr, set_addr: 31, 4;
     /// Debug Port address bank select.
     /// The behavior of SELECT.DPBANKSEL depends on the DP version, as follows:
     /// DPv0 In DPv0 the SELECT.DPBANKSEL field must be written as zero, otherwise accesses to DP register 0x4 are UNPREDICTABLE.
@@ -355,7 +364,8 @@ bitfield! {
     ///
     /// `0x0`: Reserved. Implementations of DPv0 do not implement DPIDR.\
     /// `0x1`: DPv1 is implemented.\
-    /// `0x2`: DPv2 is implemented.
+    /// `0x2`: DPv2 is implemented.\
+    /// `0x3`: DPv3 is implemented.
     ///
     /// All remaining values are reserved.
     pub u8, version, _: 15, 12;
@@ -450,6 +460,141 @@ impl Register for TARGETID {
     const NAME: &'static str = "TARGETID";
 }
 
+bitfield! {
+    /// DPIDR1, Debug Port Identification register 1 (see ADI v6.0 B2.2.6)
+    ///
+    /// DPIDR1 provides additional information about the Debug Port. Only present in DPv3.
+    #[derive(Clone)]
+    pub struct DPIDR1(u32);
+    impl Debug;
+    /// Specifies if the DP implements an error mode, in which the DP reports errors without setting sticky flags.
+    pub errmode, _: 7;
+    /// Physical address size in bits, which is used for the SELECT and SELECT1 registers.
+    pub u8, asize, _: 6, 0;
+}
+
+impl From<u32> for DPIDR1 {
+    fn from(raw: u32) -> Self {
+        Self(raw)
+    }
+}
+
+impl From<DPIDR1> for u32 {
+    fn from(raw: DPIDR1) -> Self {
+        raw.0
+    }
+}
+
+impl DpRegister for DPIDR1 {
+    const VERSION: DebugPortVersion = DebugPortVersion::DPv3;
+}
+
+impl Register for DPIDR1 {
+    const ADDRESS: u8 = 0x10;
+    const NAME: &'static str = "DPIDR1";
+}
+
+bitfield! {
+    /// BASEPTR0, Base Pointer register 0 (see ADI v6.0 B2.2.1)
+    ///
+    /// BASEPTR0 contains the lower bits of the address of the top-level
+    /// component in the debug address space, usually a ROM table. Only present in DPv3.
+    #[derive(Clone)]
+    pub struct BASEPTR0(u32);
+    impl Debug;
+    /// Bits `[31:12]` of the base address.
+    pub ptr, _: 31, 12;
+    /// Specifies if the base address is valid.
+    pub valid, _: 0;
+}
+
+impl From<u32> for BASEPTR0 {
+    fn from(raw: u32) -> Self {
+        Self(raw)
+    }
+}
+
+impl From<BASEPTR0> for u32 {
+    fn from(raw: BASEPTR0) -> Self {
+        raw.0
+    }
+}
+
+impl DpRegister for BASEPTR0 {
+    const VERSION: DebugPortVersion = DebugPortVersion::DPv3;
+}
+
+impl Register for BASEPTR0 {
+    const ADDRESS: u8 = 0x20;
+    const NAME: &'static str = "BASEPTR0";
+}
+
+bitfield! {
+    /// BASEPTR1, Base Pointer register 1 (see ADI v6.0 B2.2.2)
+    ///
+    /// BASEPTR1 contains bits `[63:32]` of the address of the top-level component
+    /// in the debug address space. Only present in DPv3.
+    #[derive(Clone)]
+    pub struct BASEPTR1(u32);
+    impl Debug;
+    /// Bits `[63:32]` of the base address.
+    pub ptr, _: 31, 0;
+}
+
+impl From<u32> for BASEPTR1 {
+    fn from(raw: u32) -> Self {
+        Self(raw)
+    }
+}
+
+impl From<BASEPTR1> for u32 {
+    fn from(raw: BASEPTR1) -> Self {
+        raw.0
+    }
+}
+
+impl DpRegister for BASEPTR1 {
+    const VERSION: DebugPortVersion = DebugPortVersion::DPv3;
+}
+
+impl Register for BASEPTR1 {
+    const ADDRESS: u8 = 0x30;
+    const NAME: &'static str = "BASEPTR1";
+}
+
+bitfield! {
+    /// SELECT1, AP Select register 1 (see ADI v6.0 B2.2.12)
+    ///
+    /// SELECT1 contains bits `[63:32]` of the address in the debug address space,
+    /// which is accessed with AP accesses. Only present in DPv3.
+    #[derive(Clone)]
+    pub struct Select1(u32);
+    impl Debug;
+    /// Bits `[63:32]` of the selected address.
+    pub addr, set_addr: 31, 0;
+}
+
+impl From<u32> for Select1 {
+    fn from(raw: u32) -> Self {
+        Select1(raw)
+    }
+}
+
+impl From<Select1> for u32 {
+    fn from(raw: Select1) -> Self {
+        raw.0
+    }
+}
+
+impl DpRegister for Select1 {
+    const VERSION: DebugPortVersion = DebugPortVersion::DPv3;
+}
+
+impl Register for Select1 {
+    const ADDRESS: u8 = 0x54;
+    const NAME: &'static str = "SELECT1";
+}
+
 /// The ID of a debug port. Can be used to detect and select devices in a multidrop setup.
 #[derive(Debug)]
 pub struct DebugPortId {
@@ -547,6 +692,8 @@ pub enum DebugPortVersion {
     DPv1,
     /// Version 2 (**very** rare (only known example is the RP2040))
     DPv2,
+    /// Version 3, used by the ADIv6 architecture.
+    DPv3,
     /// Some unsupported value was encountered!
     Unsupported(u8),
 }
@@ -559,6 +706,7 @@ impl From<DebugPortVersion> for u8 {
             DPv0 => 0,
             DPv1 => 1,
             DPv2 => 2,
+            DPv3 => 3,
             Unsupported(val) => val,
         }
     }
@@ -581,6 +729,7 @@ impl Display for DebugPortVersion {
             DPv0 => write!(f, "DPv0"),
             DPv1 => write!(f, "DPv1"),
             DPv2 => write!(f, "DPv2"),
+            DPv3 => write!(f, "DPv3"),
             Unsupported(version) => write!(f, "<unsupported Debugport Version {}>", version),
         }
     }
@@ -592,6 +741,7 @@ impl From<u8> for DebugPortVersion {
             0 => DebugPortVersion::DPv0,
             1 => DebugPortVersion::DPv1,
             2 => DebugPortVersion::DPv2,
+            3 => DebugPortVersion::DPv3,
             value => DebugPortVersion::Unsupported(value),
         }
     }
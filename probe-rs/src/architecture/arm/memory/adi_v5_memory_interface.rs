@@ -55,6 +55,13 @@ where
     // cached on a lower level, where the other Memory AP information is
     // stored.
     cached_csw_value: Option<CSW>,
+
+    // Cached value of the TAR2 register, which contains the upper 32 bits
+    // of the address when the large address extension is present.
+    //
+    // Most accesses don't cross a 4 GiB boundary, so this avoids writing
+    // the register for every access.
+    cached_tar2_value: Option<u32>,
 }
 
 impl<'interface, AP> ADIMemoryInterface<'interface, AP>
@@ -71,6 +78,7 @@ where
             only_32bit_data_size: ap_information.only_32bit_data_size,
             supports_hnonsec: ap_information.supports_hnonsec,
             cached_csw_value: None,
+            cached_tar2_value: None,
             has_large_address_extension: ap_information.has_large_address_extension,
             has_large_data_extension: ap_information.has_large_data_extension,
         })
@@ -138,20 +146,27 @@ where
         let address_lower = address as u32;
         let address_upper = (address >> 32) as u32;
 
-        let tar = TAR {
-            address: address_lower,
-        };
-        self.write_ap_register(access_port, tar)?;
-
         if self.has_large_address_extension {
-            let tar = TAR2 {
-                address: address_upper,
-            };
-            self.write_ap_register(access_port, tar)?;
+            if self.cached_tar2_value != Some(address_upper) {
+                // Invalidate the cached value, in case the write fails.
+                self.cached_tar2_value = None;
+
+                let tar = TAR2 {
+                    address: address_upper,
+                };
+                self.write_ap_register(access_port, tar)?;
+
+                self.cached_tar2_value = Some(address_upper);
+            }
         } else if address_upper != 0 {
             return Err(AccessPortError::OutOfBounds);
         }
 
+        let tar = TAR {
+            address: address_lower,
+        };
+        self.write_ap_register(access_port, tar)?;
+
         Ok(())
     }
 
@@ -806,6 +821,22 @@ mod tests {
             Self::new(mock, &ap_information).unwrap()
         }
 
+        /// Creates a new MemoryInterface for an AccessPort with the large address extension.
+        fn new_mock_large_address(
+            mock: &'interface mut MockMemoryAp,
+        ) -> ADIMemoryInterface<'interface, MockMemoryAp> {
+            let ap_information = MemoryApInformation {
+                address: DUMMY_AP.ap_address(),
+                only_32bit_data_size: false,
+                supports_hnonsec: false,
+                debug_base_address: 0xf000_0000,
+                has_large_address_extension: true,
+                has_large_data_extension: false,
+            };
+
+            Self::new(mock, &ap_information).unwrap()
+        }
+
         fn mock_memory(&self) -> &[u8] {
             &self.interface.memory
         }
@@ -833,6 +864,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn read_word_32_large_address() {
+        let mut mock = MockMemoryAp::with_pattern();
+        mock.memory[..8].copy_from_slice(&DATA8[..8]);
+
+        let mut mi = ADIMemoryInterface::new_mock_large_address(&mut mock);
+
+        let value = mi.read_word_32(DUMMY_AP, 0x1_0000_0004).unwrap();
+        assert_eq!(value, 0x87868584);
+
+        assert_eq!(mock.upper_address(), 1);
+    }
+
+    #[test]
+    fn large_address_without_extension_should_error() {
+        let mut mock = MockMemoryAp::with_pattern();
+        let mut mi = ADIMemoryInterface::new_mock(&mut mock);
+
+        assert!(mi.read_word_32(DUMMY_AP, 0x1_0000_0004).is_err());
+    }
+
     #[test]
     fn read_word_8() {
         let mut mock = MockMemoryAp::with_pattern();
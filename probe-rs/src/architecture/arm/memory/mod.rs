@@ -4,4 +4,7 @@ pub(crate) mod adi_v5_memory_interface;
 pub(crate) mod romtable;
 
 use super::ap::AccessPortError;
-pub use romtable::{Component, CoresightComponent, PeripheralType};
+pub use romtable::{
+    Component, ComponentClass, ComponentId, CoresightComponent, CoresightComponentIter, PartInfo,
+    PeripheralID, PeripheralType, RomTable, RomTableError,
+};
@@ -106,6 +106,13 @@ pub struct RomTable {
 }
 
 impl RomTable {
+    /// Returns the components referenced by the entries of this ROM table.
+    ///
+    /// Use [`CoresightComponent::iter`] to iterate nested ROM tables as well.
+    pub fn entries(&self) -> impl Iterator<Item = &CoresightComponent> {
+        self.entries.iter().map(|entry| &entry.component)
+    }
+
     /// Tries to parse a CoreSight component table.
     ///
     /// This does not check whether the data actually signalizes
@@ -225,7 +232,8 @@ struct RomTableEntry {
 #[derive(Debug, PartialEq)]
 pub struct ComponentId {
     component_address: u64,
-    class: RawComponent,
+    class: ComponentClass,
+    cidr: u32,
     peripheral_id: PeripheralID,
 }
 
@@ -235,6 +243,17 @@ impl ComponentId {
         self.component_address
     }
 
+    /// Retreive the class of the component.
+    pub fn class(&self) -> ComponentClass {
+        self.class
+    }
+
+    /// Retreive the raw value of the component ID registers CIDR0 to CIDR3,
+    /// with CIDR0 in the lowest byte.
+    pub fn cidr(&self) -> u32 {
+        self.cidr
+    }
+
     /// Retreive the peripheral ID of the component.
     pub fn peripheral_id(&self) -> &PeripheralID {
         &self.peripheral_id
@@ -258,11 +277,10 @@ impl<'probe: 'memory, 'memory> ComponentInformationReader<'probe, 'memory> {
         }
     }
 
-    /// Reads the component class from a component information table.
+    /// Reads the component ID registers from a component information table.
     ///
     /// This function does a direct memory access and is meant for internal use only.
-    fn component_class(&mut self) -> Result<RawComponent, RomTableError> {
-        #![allow(clippy::verbose_bit_mask)]
+    fn component_id_registers(&mut self) -> Result<[u32; 4], RomTableError> {
         let mut cidr = [0u32; 4];
 
         self.memory
@@ -271,6 +289,13 @@ impl<'probe: 'memory, 'memory> ComponentInformationReader<'probe, 'memory> {
 
         log::debug!("CIDR: {:x?}", cidr);
 
+        Ok(cidr)
+    }
+
+    /// Determines the component class from the component ID registers.
+    fn component_class(&self, cidr: &[u32; 4]) -> Result<ComponentClass, RomTableError> {
+        #![allow(clippy::verbose_bit_mask)]
+
         let preambles = [
             cidr[0] & 0xff,
             cidr[1] & 0x0f,
@@ -352,9 +377,15 @@ impl<'probe: 'memory, 'memory> ComponentInformationReader<'probe, 'memory> {
     ///
     /// This function does a direct memory access and is meant for internal use only.
     fn read_all(&mut self) -> Result<ComponentId, RomTableError> {
+        let cidr = self.component_id_registers()?;
+
         Ok(ComponentId {
             component_address: self.base_address,
-            class: self.component_class()?,
+            class: self.component_class(&cidr)?,
+            cidr: cidr
+                .iter()
+                .enumerate()
+                .fold(0, |value, (i, byte)| value | ((byte & 0xFF) << (8 * i))),
             peripheral_id: self.peripheral_id()?,
         })
     }
@@ -364,16 +395,20 @@ impl<'probe: 'memory, 'memory> ComponentInformationReader<'probe, 'memory> {
 ///
 /// This does not describe the exact component type which is determined via the `PeripheralID`.
 ///
-/// Meant for internal parsing usage only.
-///
 /// Described in table D1-2 in the ADIv5.2 spec.
-#[derive(Primitive, Debug, PartialEq)]
-enum RawComponent {
+#[derive(Primitive, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ComponentClass {
+    /// Generic verification component.
     GenericVerificationComponent = 0,
+    /// Class 0x1 ROM table.
     RomTable = 1,
+    /// CoreSight component, which can also be a Class 0x9 ROM table.
     CoreSightComponent = 9,
+    /// Peripheral Test Block.
     PeripheralTestBlock = 0xB,
+    /// Generic IP component.
     GenericIPComponent = 0xE,
+    /// CoreLink, PrimeCell, or system component with no standardized register layout.
     CoreLinkOrPrimeCellOrSystemComponent = 0xF,
 }
 
@@ -422,18 +457,18 @@ impl Component {
         }
 
         let class = match component_id.class {
-            RawComponent::GenericVerificationComponent => {
+            ComponentClass::GenericVerificationComponent => {
                 Component::GenericVerificationComponent(component_id)
             }
-            RawComponent::RomTable => {
+            ComponentClass::RomTable => {
                 let rom_table = RomTable::try_parse(memory, component_id.component_address)?;
 
                 Component::Class1RomTable(component_id, rom_table)
             }
-            RawComponent::CoreSightComponent => Component::Class9RomTable(component_id),
-            RawComponent::PeripheralTestBlock => Component::PeripheralTestBlock(component_id),
-            RawComponent::GenericIPComponent => Component::GenericIPComponent(component_id),
-            RawComponent::CoreLinkOrPrimeCellOrSystemComponent => {
+            ComponentClass::CoreSightComponent => Component::Class9RomTable(component_id),
+            ComponentClass::PeripheralTestBlock => Component::PeripheralTestBlock(component_id),
+            ComponentClass::GenericIPComponent => Component::GenericIPComponent(component_id),
+            ComponentClass::CoreLinkOrPrimeCellOrSystemComponent => {
                 Component::CoreLinkOrPrimeCellOrSystemComponent(component_id)
             }
         };
@@ -604,6 +639,8 @@ pub struct PeripheralID {
     dev_type: u8,
     /// The arch_id of the peripheral
     arch_id: u16,
+    /// The raw value of the peripheral ID registers PIDR0 to PIDR7.
+    raw: u64,
 }
 
 impl PeripheralID {
@@ -625,6 +662,9 @@ impl PeripheralID {
             SIZE: 2u32.pow((data[4] >> 4) & 0x0F) as u8,
             dev_type,
             arch_id,
+            raw: data.iter().enumerate().fold(0, |value, (i, byte)| {
+                value | (u64::from(byte & 0xFF) << (8 * i))
+            }),
         }
     }
 
@@ -645,6 +685,47 @@ impl PeripheralID {
         self.PART
     }
 
+    /// Returns the name of the component designer, if it is known.
+    pub fn designer(&self) -> Option<&'static str> {
+        self.JEP106.and_then(|jep106| jep106.get())
+    }
+
+    /// Returns the major revision of the component.
+    pub fn revision(&self) -> u8 {
+        self.REVISION
+    }
+
+    /// Returns the minor revision of the component, for errata fixes.
+    pub fn revand(&self) -> u8 {
+        self.REVAND
+    }
+
+    /// Returns the size of the component in 4KB blocks.
+    pub fn size(&self) -> u8 {
+        self.SIZE
+    }
+
+    /// Returns the value of the DEVTYPE register.
+    pub fn dev_type(&self) -> u8 {
+        self.dev_type
+    }
+
+    /// Returns the ARCHID of the DEVARCH register, or 0 if the component doesn't implement it.
+    pub fn arch_id(&self) -> u16 {
+        self.arch_id
+    }
+
+    /// Returns the raw value of the peripheral ID registers PIDR0 to PIDR7,
+    /// with PIDR0 in the lowest byte.
+    pub fn pidr(&self) -> u64 {
+        self.raw
+    }
+
+    /// Returns the type of the component, if it is known.
+    pub fn peripheral_type(&self) -> Option<PeripheralType> {
+        self.determine_part().map(|info| info.peripheral_type())
+    }
+
     /// Uses the available data to match it againts a table of known components.
     /// If the component is known, some info about it is returned.
     /// If it is not known, None is returned.
@@ -674,6 +755,7 @@ impl PeripheralID {
             ("ARM Ltd", 0x471, 0x00, 0x0000) => Some(PartInfo::new("Cortex-M0  ROM", PeripheralType::Rom)),
             ("ARM Ltd", 0x4C0, 0x00, 0x0000) => Some(PartInfo::new("Cortex-M0+ ROM", PeripheralType::Rom)),
             ("ARM Ltd", 0x4C4, 0x00, 0x0000) => Some(PartInfo::new("Cortex-M4 ROM", PeripheralType::Rom)),
+            ("ARM Ltd", 0x906, 0x14, 0x0000) => Some(PartInfo::new("CoreSight CTI", PeripheralType::Cti)),
             ("ARM Ltd", 0x907, 0x21, 0x0000) => Some(PartInfo::new("CoreSight ETB", PeripheralType::Etb)),
             ("ARM Ltd", 0x908, 0x12, 0x0000) => Some(PartInfo::new("CoreSight TraceFunnel", PeripheralType::TraceFunnel)),
             ("ARM Ltd", 0x910, 0x00, 0x0000) => Some(PartInfo::new("CoreSight ETM9", PeripheralType::Etm)),
@@ -684,6 +766,7 @@ impl PeripheralID {
             ("ARM Ltd", 0x923, 0x11, 0x0000) => Some(PartInfo::new("Cortex-M3 TPIU", PeripheralType::Tpiu)),
             ("ARM Ltd", 0x924, 0x13, 0x0000) => Some(PartInfo::new("Cortex-M3 ETM", PeripheralType::Etm)),
             ("ARM Ltd", 0x925, 0x13, 0x0000) => Some(PartInfo::new("Cortex-M4 ETM", PeripheralType::Etm)),
+            ("ARM Ltd", 0x932, 0x31, _) => Some(PartInfo::new("MTB-M0+", PeripheralType::Mtb)),
             ("ARM Ltd", 0x961, _, 0x0000) => Some(PartInfo::new("CoreSight TMC", PeripheralType::Tmc)),
             ("ARM Ltd", 0x962, 0x00, 0x0000) => Some(PartInfo::new("CoreSight STM", PeripheralType::Stm)),
             ("ARM Ltd", 0x963, 0x63, 0x0a63) => Some(PartInfo::new("CoreSight STM", PeripheralType::Stm)),
@@ -701,6 +784,7 @@ impl PeripheralID {
             ("ARM Ltd", 0xD21, 0x00, 0x1A03) => Some(PartInfo::new("Cortex-M33 BPU", PeripheralType::Bpu)),
             ("ARM Ltd", 0xD21, 0x13, 0x4A13) => Some(PartInfo::new("Cortex-M33 ETM", PeripheralType::Etm)),
             ("ARM Ltd", 0xD21, 0x11, 0x0000) => Some(PartInfo::new("Cortex-M33 TPIU", PeripheralType::Tpiu)),
+            ("ARM Ltd", 0xD21, 0x14, 0x1A14) => Some(PartInfo::new("Cortex-M33 CTI", PeripheralType::Cti)),
             _ => None,
         }
     }
@@ -773,6 +857,10 @@ pub enum PeripheralType {
     Tsgen,
     /// Trace Memory Controller
     Tmc,
+    /// Micro Trace Buffer
+    Mtb,
+    /// Cross Trigger Interface
+    Cti,
 }
 
 impl std::fmt::Display for PeripheralType {
@@ -792,6 +880,26 @@ impl std::fmt::Display for PeripheralType {
             PeripheralType::TraceFunnel => write!(f, "Trace Funnel"),
             PeripheralType::Tsgen => write!(f, "Tsgen (Time Stamp Generator)"),
             PeripheralType::Tmc => write!(f, "Tmc (Trace Memory Controller)"),
+            PeripheralType::Mtb => write!(f, "Mtb (Micro Trace Buffer)"),
+            PeripheralType::Cti => write!(f, "Cti (Cross Trigger Interface)"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{PeripheralID, PeripheralType};
+
+    #[test]
+    fn parse_peripheral_id() {
+        // PIDR0 to PIDR7 of a Cortex-M4 ETM.
+        let data = [0x25, 0xB9, 0x4B, 0x00, 0x04, 0x00, 0x00, 0x00];
+        let peripheral_id = PeripheralID::from_raw(&data, 0x13, 0);
+
+        assert_eq!(peripheral_id.part(), 0x925);
+        assert_eq!(peripheral_id.designer(), Some("ARM Ltd"));
+        assert_eq!(peripheral_id.revision(), 4);
+        assert_eq!(peripheral_id.peripheral_type(), Some(PeripheralType::Etm));
+        assert_eq!(peripheral_id.pidr(), 0x0000_0004_004B_B925);
+    }
+}
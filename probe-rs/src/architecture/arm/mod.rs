@@ -1,6 +1,7 @@
 //! All the interface bits for ARM.
 
 pub mod ap;
+pub mod authentication;
 pub(crate) mod communication_interface;
 pub mod component;
 pub(crate) mod core;
@@ -10,10 +11,15 @@ pub mod sequences;
 pub mod swo;
 mod traits;
 
+pub use authentication::{AuthenticationChallenge, AuthenticationProtocol, DebugAuthenticator};
 pub use communication_interface::{
-    ApInformation, ArmChipInfo, ArmCommunicationInterface, DapError, MemoryApInformation, Register,
+    ApInformation, ArmChipInfo, ArmCommunicationInterface, DapError, DapProbe, MemoryApInformation,
+    Register, SwdSequence, UninitializedArmProbe,
+};
+pub use swo::{
+    ExceptionAction, ItmDecoder, SwoAccess, SwoConfig, SwoMode, SwoReader, TimestampRelation,
+    TraceEvent, TraceEvents,
 };
-pub use swo::{SwoAccess, SwoConfig, SwoMode, SwoReader};
 pub use traits::*;
 
 pub use self::core::armv6m;
@@ -21,6 +27,7 @@ pub use self::core::armv7a;
 pub use self::core::armv7m;
 pub use self::core::armv8a;
 pub use self::core::armv8m;
+pub use self::core::cortex_m::{FaultKind, FaultStatus};
 pub use self::core::Dump;
 
 pub use communication_interface::ArmProbeInterface;
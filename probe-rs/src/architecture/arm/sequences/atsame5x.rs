@@ -1,4 +1,7 @@
-//! Sequences for ATSAM D5x/E5x target families
+//! Sequences for ATSAM D1x/D2x/D5x/E5x/L2x/C2x target families
+//!
+//! All of these families have a Device Service Unit (DSU) at the same address,
+//! which is used to detect the device, to detect the protection and to perform a Chip-Erase.
 
 use super::ArmDebugSequence;
 use crate::{architecture, DebugProbeError, Error, Memory, Permissions};
@@ -151,6 +154,56 @@ impl DsuStatusB {
     pub const ADDRESS: u64 = 0x4100_2102;
 }
 
+bitfield! {
+    /// Device Service Unit Device Identification Register, DSU - DID
+    #[derive(Copy, Clone)]
+    pub struct DsuDid(u32);
+    impl Debug;
+
+    /// Processor
+    /// 1 for Cortex-M0+, 6 for Cortex-M4.
+    pub u8, processor, _: 31, 28;
+    /// Product Family
+    /// For Cortex-M0+ devices 0 for SAM D, 1 for SAM L and 2 for SAM C.
+    pub u8, family, _: 27, 23;
+    /// Product Series
+    pub u8, series, _: 21, 16;
+    /// Die Number
+    pub u8, die, _: 15, 12;
+    /// Revision Number
+    pub u8, revision, _: 11, 8;
+    /// Device Selection
+    pub u8, devsel, _: 7, 0;
+}
+
+impl From<u32> for DsuDid {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<DsuDid> for u32 {
+    fn from(value: DsuDid) -> Self {
+        value.0
+    }
+}
+
+impl DsuDid {
+    /// The DSU DID register address
+    pub const ADDRESS: u64 = 0x4100_2118;
+
+    /// The name of the product family, if it is known.
+    pub fn family_name(&self) -> Option<&'static str> {
+        match (self.processor(), self.family()) {
+            (1, 0) => Some("SAM D1x/D2x/DA1"),
+            (1, 1) => Some("SAM L2x"),
+            (1, 2) => Some("SAM C2x"),
+            (6, _) => Some("SAM D5x/E5x"),
+            _ => None,
+        }
+    }
+}
+
 /// A wrapper for different types that can perform SWD Commands (SWJ_Pins SWJ_Sequence)
 struct SwdSequenceShim<'a>(&'a mut dyn architecture::arm::communication_interface::DapProbe);
 
@@ -174,15 +227,23 @@ impl<'a> architecture::arm::communication_interface::SwdSequence for SwdSequence
     }
 }
 
-/// Marker struct indicating initialization sequencing for ATSAM D5x/E5x family parts.
+/// Marker struct indicating initialization sequencing for ATSAM D1x/D2x/D5x/E5x/L2x/C2x family parts.
 pub struct AtSAME5x {}
 
 impl AtSAME5x {
-    /// Create the sequencer for the ATSAM D5x/E5x family of parts.
+    /// Create the sequencer for the ATSAM D1x/D2x/D5x/E5x/L2x/C2x family of parts.
     pub fn create() -> Arc<Self> {
         Arc::new(Self {})
     }
 
+    /// Read the Device Identification register of the DSU
+    ///
+    /// # Errors
+    /// Subject to probe communication errors
+    pub fn device_id(&self, memory: &mut Memory) -> Result<DsuDid, Error> {
+        Ok(DsuDid::from(memory.read_word_32(DsuDid::ADDRESS)?))
+    }
+
     /// Perform a Chip-Erase operation
     ///
     /// Issue a Chip-Erase command to the device provided that `permission` grants `erase-all`.
@@ -248,7 +309,8 @@ impl AtSAME5x {
     /// CPU Reset Extension is a vendor specific feature that allows the CPU core to remain
     /// in reset while the rest of the debugging subsystem can run and initialize itself.
     ///
-    /// For more details see: 12.6.2 CPU Reset Extension in the SAM D5/E5 Family Data Sheet
+    /// For more details see: 12.6.2 CPU Reset Extension in the SAM D5/E5 Family Data Sheet,
+    /// or 13.6.3 CPU Reset Extension in the SAM D21 Family Data Sheet
     ///
     /// # Errors
     /// Subject to probe communication errors
@@ -327,7 +389,7 @@ impl AtSAME5x {
 }
 
 impl ArmDebugSequence for AtSAME5x {
-    /// `reset_hardware_assert` for ATSAM D5x/E5x devices
+    /// `reset_hardware_assert` for ATSAM D1x/D2x/D5x/E5x/L2x/C2x devices
     ///
     /// Instead of keeping `nReset` asserted, the device is instead put into CPU Reset Extension
     /// which will keep the CPU Core in reset until manually released by the debugger probe.
@@ -339,7 +401,7 @@ impl ArmDebugSequence for AtSAME5x {
         self.reset_hardware_with_extension(&mut shim)
     }
 
-    /// `reset_hardware_deassert` for ATSAM D5x/E5x devices
+    /// `reset_hardware_deassert` for ATSAM D1x/D2x/D5x/E5x/L2x/C2x devices
     ///
     /// Instead of de-asserting `nReset` here (this was already done during the CPU Reset Extension process),
     /// the device is released from Reset Extension.
@@ -360,10 +422,10 @@ impl ArmDebugSequence for AtSAME5x {
         self.release_reset_extension(memory)
     }
 
-    /// `debug_device_unlock` for ATSAM D5x/E5x devices
+    /// `debug_device_unlock` for ATSAM D1x/D2x/D5x/E5x/L2x/C2x devices
     ///
     /// First check the device lock status by querying its Device Service Unit (DSU).
-    /// If the device is already unlocked then return `Ok` directly.
+    /// If the device is already unlocked, the device is identified using the DSU and `Ok` is returned.
     /// If the device is locked the following happens:
     /// * If the `erase_all` permission is missing return the appropriate error
     /// * If the Chip-Erase command is also locked then return an error since Chip-Erase Unlock can only be
@@ -382,9 +444,21 @@ impl ArmDebugSequence for AtSAME5x {
 
         if dsu_status_b.prot() {
             log::warn!("The Device is locked, unlocking..");
-            self.erase_all(&mut memory, permissions)
-        } else {
-            Ok(())
+            return self.erase_all(&mut memory, permissions);
+        }
+
+        // The device identification is not accessible while the device is locked.
+        let did = self.device_id(&mut memory)?;
+        match did.family_name() {
+            Some(family) => log::debug!(
+                "Detected {} device, DEVSEL {:#04x}, revision {}",
+                family,
+                did.devsel(),
+                did.revision()
+            ),
+            None => log::warn!("Unknown device identification {:#010x}", u32::from(did)),
         }
+
+        Ok(())
     }
 }
@@ -0,0 +1,180 @@
+//! Sequences for the NXP Kinetis devices.
+
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::ArmDebugSequence;
+use crate::{
+    architecture::arm::{ap::MemoryAp, ApAddress, ArmProbeInterface, DapAccess},
+    DebugProbeError,
+};
+
+/// The index of the MDM-AP, which controls the security and the reset of the chip.
+const MDM_AP: u8 = 1;
+
+/// The identification register value of the MDM-AP.
+const MDM_AP_IDR: u32 = 0x001C_0000;
+/// The mask for the identification register, the lowest bits differ between the families.
+const MDM_AP_IDR_MASK: u32 = 0xFFFF_FF00;
+
+/// The offset of the status register of the MDM-AP.
+const MDM_STATUS: u8 = 0x00;
+/// The offset of the control register of the MDM-AP.
+const MDM_CONTROL: u8 = 0x04;
+/// The offset of the identification register of the MDM-AP.
+const MDM_IDR: u8 = 0xFC;
+
+/// The flash controller is ready for a mass erase.
+const STATUS_FLASH_READY: u32 = 1 << 1;
+/// The chip is secured.
+const STATUS_SYSTEM_SECURITY: u32 = 1 << 2;
+/// A mass erase using the MDM-AP is allowed.
+const STATUS_MASS_ERASE_ENABLE: u32 = 1 << 5;
+
+/// Start a mass erase, stays set until the mass erase is done.
+const CONTROL_MASS_ERASE: u32 = 1 << 0;
+/// Hold the chip in reset.
+const CONTROL_SYSTEM_RESET: u32 = 1 << 3;
+
+/// The time to wait for the flash controller to become ready.
+const FLASH_READY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The time to wait for the mass erase.
+const MASS_ERASE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The sequence handle for the Kinetis devices.
+///
+/// A secured Kinetis device blocks the access to the AHB-AP.
+/// It can only be recovered by erasing the flash through the MDM-AP.
+pub struct Kinetis(());
+
+impl Kinetis {
+    /// Create a sequence handle for the Kinetis devices.
+    pub fn create() -> Arc<dyn ArmDebugSequence> {
+        Arc::new(Self(()))
+    }
+}
+
+/// Wait until `done` returns true for the value of the MDM-AP register at `address`.
+fn wait_for_mdm_register(
+    interface: &mut dyn ArmProbeInterface,
+    mdm_ap: ApAddress,
+    address: u8,
+    timeout: Duration,
+    done: impl Fn(u32) -> bool,
+) -> Result<u32, crate::Error> {
+    let start = Instant::now();
+
+    loop {
+        let value = interface.read_raw_ap_register(mdm_ap, address)?;
+
+        if done(value) {
+            return Ok(value);
+        }
+
+        if start.elapsed() > timeout {
+            return Err(crate::Error::Probe(DebugProbeError::Timeout));
+        }
+
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Mass erase the flash of a secured device using the MDM-AP, which unsecures it.
+///
+/// The chip is held in reset during the mass erase, so that the firmware can't interfere with it.
+fn mass_erase(
+    interface: &mut dyn ArmProbeInterface,
+    mdm_ap: ApAddress,
+    permissions: &crate::Permissions,
+) -> Result<(), crate::Error> {
+    let status = interface.read_raw_ap_register(mdm_ap, MDM_STATUS)?;
+
+    if status & STATUS_MASS_ERASE_ENABLE == 0 {
+        return Err(crate::Error::ArchitectureSpecific(
+            "The chip is secured, and the mass erase using the MDM-AP is disabled".into(),
+        ));
+    }
+
+    permissions.erase_all()?;
+
+    interface.write_raw_ap_register(mdm_ap, MDM_CONTROL, CONTROL_SYSTEM_RESET)?;
+
+    wait_for_mdm_register(
+        interface,
+        mdm_ap,
+        MDM_STATUS,
+        FLASH_READY_TIMEOUT,
+        |status| status & STATUS_FLASH_READY != 0,
+    )?;
+
+    interface.write_raw_ap_register(
+        mdm_ap,
+        MDM_CONTROL,
+        CONTROL_SYSTEM_RESET | CONTROL_MASS_ERASE,
+    )?;
+
+    // The mass erase bit is cleared by the chip when the erase is done.
+    wait_for_mdm_register(
+        interface,
+        mdm_ap,
+        MDM_CONTROL,
+        MASS_ERASE_TIMEOUT,
+        |control| control & CONTROL_MASS_ERASE == 0,
+    )?;
+
+    let status = interface.read_raw_ap_register(mdm_ap, MDM_STATUS)?;
+
+    interface.write_raw_ap_register(mdm_ap, MDM_CONTROL, 0)?;
+
+    if status & STATUS_SYSTEM_SECURITY != 0 {
+        return Err(crate::Error::ArchitectureSpecific(
+            "The chip is still secured after the mass erase".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+impl ArmDebugSequence for Kinetis {
+    fn debug_device_unlock(
+        &self,
+        interface: &mut Box<dyn ArmProbeInterface>,
+        default_ap: MemoryAp,
+        permissions: &crate::Permissions,
+    ) -> Result<(), crate::Error> {
+        let mdm_ap = ApAddress {
+            dp: default_ap.ap_address().dp,
+            ap: MDM_AP,
+        };
+
+        let idr = interface.read_raw_ap_register(mdm_ap, MDM_IDR)?;
+
+        if idr & MDM_AP_IDR_MASK != MDM_AP_IDR {
+            log::warn!(
+                "The AP {} is not a MDM-AP (IDR {:#010x}), not checking the security of the chip",
+                MDM_AP,
+                idr
+            );
+            return Ok(());
+        }
+
+        let status = interface.read_raw_ap_register(mdm_ap, MDM_STATUS)?;
+
+        if status & STATUS_SYSTEM_SECURITY == 0 {
+            return Ok(());
+        }
+
+        log::warn!("The chip is secured. The flash will be erased to unsecure it.");
+
+        mass_erase(&mut **interface, mdm_ap, permissions)?;
+
+        log::info!("The chip was mass erased and is unsecured now");
+
+        // The AHB-AP is only accessible after initializing the debug port again.
+        Err(DebugProbeError::ReAttachRequired.into())
+    }
+}
@@ -1,11 +1,15 @@
 //! Debug sequences to operate special requirements ARM targets.
 
 pub mod atsame5x;
+pub mod kinetis;
 mod nrf;
 pub mod nrf52;
 pub mod nrf53;
 pub mod nrf91;
 pub mod nxp;
+pub mod psoc6;
+pub mod rp2040;
+pub mod silabs;
 pub mod stm32f_series;
 pub mod stm32h7;
 
@@ -457,7 +461,7 @@ pub trait ArmDebugSequence: Send + Sync {
         // Make sure the debug port is in the correct mode based on what the probe
         // has selected via active_protocol
         match interface.active_protocol() {
-            Some(crate::WireProtocol::Jtag) => {
+            Some(crate::WireProtocol::Jtag | crate::WireProtocol::Cjtag) => {
                 // Execute SWJ-DP Switch Sequence SWD to JTAG (0xE73C).
                 interface.swj_sequence(16, 0xE73C)?;
             }
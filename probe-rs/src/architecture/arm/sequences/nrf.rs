@@ -6,7 +6,8 @@ use crate::architecture::arm::{
     communication_interface::Initialized, ApAddress, ArmCommunicationInterface, ArmProbeInterface,
     DapAccess,
 };
-use crate::Memory;
+use crate::{DebugProbeError, Memory};
+use std::time::{Duration, Instant};
 
 pub trait Nrf: Sync + Send {
     /// Returns the ahb_ap and ctrl_ap of every core
@@ -20,16 +21,23 @@ pub trait Nrf: Sync + Send {
         ctrl_ap_address: ApAddress,
     ) -> Result<bool, crate::Error>;
 
-    /// Returns true if a network core is present
+    /// Returns true if a network core is present.
+    ///
+    /// The network core has to be the second entry returned by [`Nrf::core_aps`],
+    /// after the application core, which controls its power.
     fn has_network_core(&self) -> bool;
 }
 
+const RESET: u8 = 0x00;
 const ERASEALL: u8 = 0x04;
 const ERASEALLSTATUS: u8 = 0x08;
 
 const APPLICATION_RESET_S_NETWORK_FORCEOFF_REGISTER: u32 = 0x50005614;
 const RELEASE_FORCEOFF: u32 = 0;
 
+/// The maximum time an erase all procedure is allowed to take.
+const ERASEALL_TIMEOUT: Duration = Duration::from_secs(15);
+
 /// Unlocks the core by performing an erase all procedure.
 /// The `ap_address` must be of the ctrl ap of the core.
 fn unlock_core(
@@ -40,7 +48,18 @@ fn unlock_core(
     permissions.erase_all()?;
 
     arm_interface.write_raw_ap_register(ap_address, ERASEALL, 1)?;
-    while arm_interface.read_raw_ap_register(ap_address, ERASEALLSTATUS)? != 0 {}
+
+    let start = Instant::now();
+    while arm_interface.read_raw_ap_register(ap_address, ERASEALLSTATUS)? != 0 {
+        if start.elapsed() > ERASEALL_TIMEOUT {
+            return Err(crate::Error::Probe(DebugProbeError::Timeout));
+        }
+    }
+
+    // The erased device is only unlocked after a reset.
+    arm_interface.write_raw_ap_register(ap_address, RESET, 1)?;
+    arm_interface.write_raw_ap_register(ap_address, RESET, 0)?;
+
     Ok(())
 }
 
@@ -69,6 +88,14 @@ impl<T: Nrf> ArmDebugSequence for T {
         for (core_index, (core_ahb_ap_address, core_ctrl_ap_address)) in
             self.core_aps(&mut interface).iter().copied().enumerate()
         {
+            // The network core is held in force-off until the application core releases it,
+            // and its AHB-AP reports it as locked until then. The application core is
+            // already unlocked at this point, so the release can be written through it.
+            if core_index == 1 && self.has_network_core() {
+                log::debug!("Releasing the network core from force-off");
+                set_network_core_running(&mut interface)?;
+            }
+
             log::info!("Checking if core {} is unlocked", core_index);
             if self.is_core_unlocked(
                 interface.get_arm_interface()?,
@@ -100,10 +127,6 @@ impl<T: Nrf> ArmDebugSequence for T {
             }
         }
 
-        if self.has_network_core() {
-            set_network_core_running(&mut interface)?;
-        }
-
         Ok(())
     }
 }
@@ -8,7 +8,22 @@ use crate::architecture::arm::{
 };
 use crate::Memory;
 
+/// The offset of the `APPROTECT.STATUS` register of the CTRL-AP.
+const APPROTECT_STATUS: u8 = 0x00C;
+/// Non-secure debug accesses are allowed.
+const APPROTECT_STATUS_APPROTECT: u32 = 1 << 0;
+/// Secure debug accesses are allowed.
+const APPROTECT_STATUS_SECUREAPPROTECT: u32 = 1 << 1;
+
 /// The sequence handle for the nRF9160.
+///
+/// The nRF9160 can be configured to only allow non-secure debug accesses, using the
+/// `SECUREAPPROTECT` register of the UICR. The debugger needs secure accesses to halt the core and to
+/// run the flash algorithms, so such a device is treated as locked and recovered by an erase all.
+///
+/// The erase all of the CTRL-AP, like the flash algorithms, only erases the application flash, the RAM and the UICR.
+/// The modem firmware is stored in the flash of the modem, which is not accessible through the debug port,
+/// so it is kept when recovering the device or using `--chip-erase`.
 pub struct Nrf9160(());
 
 impl Nrf9160 {
@@ -47,8 +62,18 @@ impl Nrf for Nrf9160 {
         _ahb_ap_address: ApAddress,
         ctrl_ap_address: ApAddress,
     ) -> Result<bool, crate::Error> {
-        let approtect_status = arm_interface.read_raw_ap_register(ctrl_ap_address, 0x00C)?;
-        Ok(approtect_status != 0)
+        let approtect_status =
+            arm_interface.read_raw_ap_register(ctrl_ap_address, APPROTECT_STATUS)?;
+
+        if approtect_status & APPROTECT_STATUS_SECUREAPPROTECT == 0 {
+            if approtect_status & APPROTECT_STATUS_APPROTECT != 0 {
+                log::warn!("Only non-secure debug accesses are allowed, the secure debug access has to be unlocked");
+            }
+
+            return Ok(false);
+        }
+
+        Ok(true)
     }
 
     fn has_network_core(&self) -> bool {
@@ -8,11 +8,12 @@ use std::{
 
 use crate::{
     architecture::arm::{
-        ap::{ApAccess, GenericAp, IDR},
+        ap::{AccessPort, ApAccess, GenericAp, MemoryAp, CSW, IDR},
         communication_interface::Initialized,
         core::armv7m::{Aircr, Demcr, Dhcsr},
         dp::{Abort, Ctrl, DpAccess, Select, DPIDR},
-        ApAddress, ArmCommunicationInterface, DapAccess, DpAddress,
+        ApAddress, ArmCommunicationInterface, ArmProbeInterface, AuthenticationChallenge,
+        AuthenticationProtocol, DapAccess, DpAddress,
     },
     core::MemoryMappedRegister,
     CommunicationInterface, DebugProbeError,
@@ -97,6 +98,64 @@ impl ArmDebugSequence for LPC55S69 {
         Ok(())
     }
 
+    fn debug_device_unlock(
+        &self,
+        interface: &mut Box<dyn ArmProbeInterface>,
+        default_ap: MemoryAp,
+        permissions: &crate::Permissions,
+    ) -> Result<(), crate::Error> {
+        // If debugging is disabled, the AHB-AP is either not accessible at all,
+        // or doesn't allow any transfers.
+        let csw: Result<CSW, _> = interface.read_ap_register(default_ap);
+
+        if matches!(csw, Ok(csw) if csw.DeviceEn == 1) {
+            return Ok(());
+        }
+
+        log::warn!("Debug access is disabled, starting the debug authentication");
+
+        let authenticator = permissions.debug_authenticator()?;
+
+        let mailbox = ApAddress {
+            dp: default_ap.ap_address().dp,
+            ap: 2,
+        };
+
+        let challenge = debug_mailbox_command(&mut **interface, mailbox, DM_AUTH_START, &[])?;
+
+        let challenge = AuthenticationChallenge {
+            protocol: AuthenticationProtocol::NxpDebugMailbox,
+            data: challenge
+                .iter()
+                .flat_map(|word| word.to_le_bytes())
+                .collect(),
+        };
+
+        let response = authenticator.respond(&challenge)?;
+
+        if response.len() % 4 != 0 {
+            return Err(crate::Error::ArchitectureSpecific(
+                format!(
+                    "The debug authentication response has a length of {} bytes, which is not a multiple of 4",
+                    response.len()
+                )
+                .into(),
+            ));
+        }
+
+        let response: Vec<u32> = response
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+
+        debug_mailbox_command(&mut **interface, mailbox, DM_AUTH_RESPONSE, &response)?;
+
+        log::info!("Debug authentication successful");
+
+        // The debug port has to be initialized again to access the now enabled AHB-AP.
+        Err(DebugProbeError::ReAttachRequired.into())
+    }
+
     fn reset_catch_set(
         &self,
         interface: &mut crate::Memory,
@@ -260,6 +319,87 @@ fn wait_for_stop_after_reset(memory: &mut crate::Memory) -> Result<(), crate::Er
     Ok(())
 }
 
+/// Register offsets and commands of the debug mailbox access port.
+const DM_REQUEST: u8 = 0x4;
+const DM_RETURN: u8 = 0x8;
+
+const DM_AUTH_START: u16 = 0x10;
+const DM_AUTH_RESPONSE: u16 = 0x11;
+
+/// Sent by both sides to acknowledge a transferred word, with the number of remaining words in the upper half.
+const DM_ACK_TOKEN: u32 = 0xA5A5;
+
+/// Read the RETURN register of the debug mailbox, waiting until the ROM has processed the request.
+fn read_debug_mailbox_return<A: DapAccess + ?Sized>(
+    interface: &mut A,
+    mailbox: ApAddress,
+) -> Result<u32, crate::Error> {
+    let start = Instant::now();
+
+    loop {
+        match interface.read_raw_ap_register(mailbox, DM_RETURN) {
+            Ok(value) => return Ok(value),
+            Err(e) if start.elapsed() > Duration::from_secs(1) => return Err(e.into()),
+            Err(_) => thread::sleep(Duration::from_millis(1)),
+        }
+    }
+}
+
+/// Execute a command of the debug mailbox, and return the words of the response.
+fn debug_mailbox_command<A: DapAccess + ?Sized>(
+    interface: &mut A,
+    mailbox: ApAddress,
+    command: u16,
+    parameters: &[u32],
+) -> Result<Vec<u32>, crate::Error> {
+    interface.write_raw_ap_register(
+        mailbox,
+        DM_REQUEST,
+        (parameters.len() as u32) << 16 | command as u32,
+    )?;
+
+    for (i, parameter) in parameters.iter().enumerate() {
+        let ack = read_debug_mailbox_return(interface, mailbox)?;
+
+        if ack != ((parameters.len() - i) as u32) << 16 | DM_ACK_TOKEN {
+            return Err(crate::Error::ArchitectureSpecific(
+                format!(
+                    "Unexpected acknowledge {:#010x} from the debug mailbox for command {:#x}",
+                    ack, command
+                )
+                .into(),
+            ));
+        }
+
+        interface.write_raw_ap_register(mailbox, DM_REQUEST, *parameter)?;
+    }
+
+    let header = read_debug_mailbox_return(interface, mailbox)?;
+
+    let status = header & 0xFFFF;
+    let length = (header >> 16) & 0x7FFF;
+
+    if status != 0 {
+        return Err(crate::Error::ArchitectureSpecific(
+            format!(
+                "The debug mailbox command {:#x} failed with status {:#x}",
+                command, status
+            )
+            .into(),
+        ));
+    }
+
+    let mut response = Vec::with_capacity(length as usize);
+
+    for i in 0..length {
+        interface.write_raw_ap_register(mailbox, DM_REQUEST, (length - i) << 16 | DM_ACK_TOKEN)?;
+
+        response.push(read_debug_mailbox_return(interface, mailbox)?);
+    }
+
+    Ok(response)
+}
+
 fn enable_debug_mailbox(
     interface: &mut ArmCommunicationInterface<Initialized>,
     dp: DpAddress,
@@ -0,0 +1,190 @@
+//! Sequences for the Cypress/Infineon PSoC 6 devices.
+
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{cortex_m_core_start, ArmDebugSequence};
+use crate::{
+    architecture::arm::{
+        ap::MemoryAp,
+        communication_interface::DapProbe,
+        dp::{Abort, Ctrl, Select, DPIDR},
+        ApAddress, DpAddress, Pins, PortType, Register,
+    },
+    CoreType, DebugProbeError, Memory,
+};
+
+/// The index of the AP of the CM0+ core, which is also used to access the system.
+const CM0_AP: u8 = 1;
+
+/// The address of the `TST_CTRL` register of the SRSS.
+const TST_CTRL: u32 = 0x4026_0100;
+/// Setting this bit in `TST_CTRL` makes the boot code wait for the debugger, instead of
+/// starting the application.
+const TST_CTRL_TEST_MODE: u32 = 1 << 31;
+
+/// The address of the `CM4_PWR_CTL` register of the CPUSS.
+const CM4_PWR_CTL: u64 = 0x4021_0080;
+/// The key which has to be written to the upper half of `CM4_PWR_CTL`.
+const CM4_PWR_CTL_VECTKEY: u32 = 0x05FA << 16;
+/// The mask of the `PWR_MODE` field of `CM4_PWR_CTL`.
+const CM4_PWR_MODE_MASK: u32 = 0b11;
+/// The `PWR_MODE` value of an enabled CM4 core.
+const CM4_PWR_MODE_ENABLED: u32 = 0b11;
+
+/// The offsets of the MEM-AP registers in bank 0.
+const AP_CSW: u8 = 0x00;
+const AP_TAR: u8 = 0x04;
+const AP_DRW: u8 = 0x0C;
+
+/// `CSW` value for privileged 32 bit accesses.
+const AP_CSW_WORD: u32 = 0x2300_0002;
+
+/// The time after releasing the reset, in which the test mode has to be entered.
+///
+/// The boot code only checks for the test mode for a short time, the acquisition
+/// is repeated as fast as possible until this window is over.
+const ACQUIRE_WINDOW: Duration = Duration::from_millis(100);
+
+/// The sequence handle for the PSoC 6 devices.
+///
+/// The PSoC 6 has a CM0+ and a CM4 core. The CM0+ boots first, and starts the CM4 once the application
+/// enables it. To get control of the chip before the application runs, e.g. when it disables the
+/// debug port or uses the SWD pins, the chip is acquired by entering the test mode directly after reset.
+///
+/// Programming the flash is done by the flash algorithm, which uses the IPC to call the SROM.
+pub struct Psoc6(());
+
+impl Psoc6 {
+    /// Create a sequence handle for the PSoC 6 devices.
+    pub fn create() -> Arc<dyn ArmDebugSequence> {
+        Arc::new(Self(()))
+    }
+
+    /// Connect to the debug port, and try to enter the test mode.
+    fn enter_test_mode(interface: &mut dyn DapProbe) -> Result<(), DebugProbeError> {
+        // The debug port is reset together with the chip, so the switch to SWD has to be repeated.
+        interface.swj_sequence(51, 0x0007_FFFF_FFFF_FFFF)?;
+        interface.swj_sequence(16, 0xE79E)?;
+        interface.swj_sequence(51, 0x0007_FFFF_FFFF_FFFF)?;
+        interface.swj_sequence(3, 0x00)?;
+
+        interface.raw_read_register(PortType::DebugPort, DPIDR::ADDRESS)?;
+
+        let mut abort = Abort(0);
+        abort.set_orunerrclr(true);
+        abort.set_wderrclr(true);
+        abort.set_stkerrclr(true);
+        abort.set_stkcmpclr(true);
+        interface.raw_write_register(PortType::DebugPort, Abort::ADDRESS, abort.into())?;
+
+        let mut ctrl = Ctrl(0);
+        ctrl.set_cdbgpwrupreq(true);
+        ctrl.set_csyspwrupreq(true);
+        interface.raw_write_register(PortType::DebugPort, Ctrl::ADDRESS, ctrl.into())?;
+
+        let mut select = Select(0);
+        select.set_ap_sel(CM0_AP);
+        interface.raw_write_register(PortType::DebugPort, Select::ADDRESS, select.into())?;
+
+        interface.raw_write_register(PortType::AccessPort, AP_CSW, AP_CSW_WORD)?;
+        interface.raw_write_register(PortType::AccessPort, AP_TAR, TST_CTRL)?;
+        interface.raw_write_register(PortType::AccessPort, AP_DRW, TST_CTRL_TEST_MODE)?;
+
+        // Check that the write went through.
+        interface.raw_write_register(PortType::AccessPort, AP_TAR, TST_CTRL)?;
+        let tst_ctrl = interface.raw_read_register(PortType::AccessPort, AP_DRW)?;
+
+        if tst_ctrl & TST_CTRL_TEST_MODE == 0 {
+            return Err(DebugProbeError::Other(anyhow::anyhow!(
+                "TST_CTRL is {:#010x}, the test mode is not active",
+                tst_ctrl
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Reset the chip, and acquire it by entering the test mode.
+    ///
+    /// Afterwards, the CM0+ core waits in the boot code for the debugger, and the application is not started.
+    pub fn acquire(&self, interface: &mut dyn DapProbe) -> Result<(), crate::Error> {
+        let mut pins = Pins(0);
+        pins.set_nreset(true);
+
+        interface.swj_pins(0, pins.0 as u32, 0)?;
+        thread::sleep(Duration::from_millis(1));
+        interface.swj_pins(pins.0 as u32, pins.0 as u32, 0)?;
+
+        let start = Instant::now();
+
+        loop {
+            match Self::enter_test_mode(interface) {
+                Ok(()) => {
+                    log::info!("Acquired the PSoC 6 in test mode");
+                    return Ok(());
+                }
+                Err(e) if start.elapsed() > ACQUIRE_WINDOW => {
+                    log::warn!("Failed to acquire the PSoC 6: {}", e);
+                    return Err(DebugProbeError::Timeout.into());
+                }
+                Err(e) => log::trace!("Acquire attempt failed: {}", e),
+            }
+        }
+    }
+
+    /// Make sure the CM4 core is enabled, it is held in reset until the CM0+ enables it.
+    fn enable_cm4(memory: &mut Memory) -> Result<(), crate::Error> {
+        let interface = memory.get_arm_interface()?;
+
+        let mut cm0 = interface.memory_interface(MemoryAp::new(ApAddress {
+            dp: DpAddress::Default,
+            ap: CM0_AP,
+        }))?;
+
+        let pwr_ctl = cm0.read_word_32(CM4_PWR_CTL)?;
+
+        if pwr_ctl & CM4_PWR_MODE_MASK != CM4_PWR_MODE_ENABLED {
+            log::info!("Enabling the CM4 core");
+            cm0.write_word_32(
+                CM4_PWR_CTL,
+                CM4_PWR_CTL_VECTKEY
+                    | (pwr_ctl & 0xFFFF & !CM4_PWR_MODE_MASK)
+                    | CM4_PWR_MODE_ENABLED,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ArmDebugSequence for Psoc6 {
+    /// Instead of keeping the reset asserted, the chip is reset and acquired in test mode,
+    /// which keeps the application from running.
+    fn reset_hardware_assert(&self, interface: &mut dyn DapProbe) -> Result<(), crate::Error> {
+        self.acquire(interface)
+    }
+
+    /// The reset was already released when acquiring the chip.
+    fn reset_hardware_deassert(&self, _memory: &mut Memory) -> Result<(), crate::Error> {
+        Ok(())
+    }
+
+    fn debug_core_start(
+        &self,
+        core: &mut Memory,
+        core_type: CoreType,
+        _debug_base: Option<u64>,
+        _cti_base: Option<u64>,
+    ) -> Result<(), crate::Error> {
+        // The AP of the CM4 is only accessible while the core is enabled.
+        if core_type == CoreType::Armv7em {
+            Self::enable_cm4(core)?;
+        }
+
+        cortex_m_core_start(core)
+    }
+}
@@ -0,0 +1,112 @@
+//! Sequences for the RP2040.
+
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{ArmDebugSequence, DefaultArmSequence};
+use crate::architecture::arm::{
+    communication_interface::DapProbe,
+    dp::{Ctrl, Select, DPIDR},
+    DpAddress, PortType, Register,
+};
+
+/// The multidrop target selection value of the debug port of core 0.
+const CORE0_DP: DpAddress = DpAddress::Multidrop(0x0100_2927);
+
+/// The multidrop target selection value of the rescue debug port.
+///
+/// The rescue DP has no access ports. Requesting debug power up on it
+/// resets the whole chip, and sets a flag which makes the bootrom halt
+/// in a safe state instead of booting the flash image.
+const RESCUE_DP: DpAddress = DpAddress::Multidrop(0xf100_2927);
+
+/// The time the bootrom needs to reach its safe state after a rescue reset.
+const RESCUE_DELAY: Duration = Duration::from_millis(50);
+
+/// The sequence handle for the RP2040.
+///
+/// Both cores of the RP2040 have their own debug port on the multidrop SWD bus,
+/// which are selected using the `psel` of the cores in the target description.
+/// Until core 0 launches it, core 1 sleeps in the bootrom, where it can be halted
+/// and debugged like any other sleeping core.
+pub struct Rp2040(());
+
+impl Rp2040 {
+    /// Create a sequence handle for the RP2040.
+    pub fn create() -> Arc<dyn ArmDebugSequence> {
+        Arc::new(Self(()))
+    }
+
+    /// Reset the chip using the rescue debug port.
+    ///
+    /// Afterwards, the bootrom doesn't start the image in flash, and both cores wait in the bootrom.
+    /// This can be used to recover a chip where the image in flash prevents
+    /// access to the debug ports of the cores, e.g. by disabling their clocks.
+    pub fn rescue(interface: &mut Box<dyn DapProbe>) -> Result<(), crate::Error> {
+        log::info!("Resetting the RP2040 using the rescue DP");
+
+        interface.select_dp(RESCUE_DP)?;
+        interface.raw_write_register(PortType::DebugPort, Select::ADDRESS, Select(0).into())?;
+
+        let mut ctrl = Ctrl(0);
+        ctrl.set_cdbgpwrupreq(true);
+        interface.raw_write_register(PortType::DebugPort, Ctrl::ADDRESS, ctrl.into())?;
+
+        // The chip is held in reset until the request is cleared again.
+        interface.raw_write_register(PortType::DebugPort, Ctrl::ADDRESS, Ctrl(0).into())?;
+
+        thread::sleep(RESCUE_DELAY);
+
+        Ok(())
+    }
+
+    /// Check if the debug port of core 0 responds.
+    fn core0_dp_responds(interface: &mut Box<dyn DapProbe>) -> bool {
+        let start = Instant::now();
+
+        while start.elapsed() < RESCUE_DELAY {
+            let dpidr = interface
+                .select_dp(CORE0_DP)
+                .and_then(|_| interface.raw_read_register(PortType::DebugPort, DPIDR::ADDRESS));
+
+            match dpidr {
+                Ok(dpidr) => {
+                    log::debug!("DPIDR of core 0: {:#010x}", dpidr);
+                    return true;
+                }
+                Err(e) => log::debug!("Core 0 DP did not respond: {:?}", e),
+            }
+        }
+
+        false
+    }
+}
+
+impl ArmDebugSequence for Rp2040 {
+    fn debug_port_setup(&self, interface: &mut Box<dyn DapProbe>) -> Result<(), crate::Error> {
+        DefaultArmSequence(()).debug_port_setup(interface)?;
+
+        if interface.active_protocol() != Some(crate::WireProtocol::Swd) {
+            return Ok(());
+        }
+
+        // An image in flash can make the debug ports of the cores inaccessible,
+        // which can only be recovered from using the rescue DP.
+        if !Self::core0_dp_responds(interface) {
+            log::warn!("The debug port of core 0 does not respond, trying to recover the RP2040 using the rescue DP.");
+
+            Self::rescue(interface)?;
+
+            if !Self::core0_dp_responds(interface) {
+                return Err(crate::Error::Probe(crate::DebugProbeError::Timeout));
+            }
+
+            log::warn!("The RP2040 was reset, the cores are waiting in the bootrom.");
+        }
+
+        Ok(())
+    }
+}
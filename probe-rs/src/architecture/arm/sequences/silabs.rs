@@ -0,0 +1,106 @@
+//! Sequences for the Silicon Labs EFM32/EFR32 Series 0 and 1 devices.
+
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::ArmDebugSequence;
+use crate::{
+    architecture::arm::{ap::MemoryAp, ApAddress, ArmProbeInterface, DapAccess},
+    DebugProbeError,
+};
+
+/// The identification register value of the Authentication Access Port (AAP).
+const AAP_IDR_VALUE: u32 = 0x16E6_0001;
+
+/// The offset of the command register of the AAP.
+const AAP_CMD: u8 = 0x00;
+/// The offset of the command key register of the AAP.
+const AAP_CMDKEY: u8 = 0x04;
+/// The offset of the status register of the AAP.
+const AAP_STATUS: u8 = 0x08;
+/// The offset of the identification register of the AAP.
+const AAP_IDR: u8 = 0xFC;
+
+/// Erase the flash, the RAM and the lock bits.
+const CMD_DEVICEERASE: u32 = 1 << 0;
+/// Request a system reset.
+const CMD_SYSRESETREQ: u32 = 1 << 1;
+
+/// The key which enables writes to the command register.
+const CMDKEY_WRITEEN: u32 = 0xCFAC_C118;
+
+/// A device erase is in progress.
+const STATUS_ERASEBUSY: u32 = 1 << 0;
+
+/// The time to wait for the device erase.
+const DEVICE_ERASE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The sequence handle for the EFM32/EFR32 Series 0 and 1 devices.
+///
+/// When the debug access of these devices is locked, the AHB-AP is replaced by the
+/// Authentication Access Port (AAP), which can only erase and reset the device.
+/// After a device erase the debug lock is removed, and the AHB-AP is accessible again.
+pub struct EfmSeries1(());
+
+impl EfmSeries1 {
+    /// Create a sequence handle for the EFM32/EFR32 Series 0 and 1 devices.
+    pub fn create() -> Arc<dyn ArmDebugSequence> {
+        Arc::new(Self(()))
+    }
+
+    /// Erase the device using the AAP, which removes the debug lock, and reset it.
+    fn device_erase(
+        interface: &mut dyn ArmProbeInterface,
+        aap: ApAddress,
+        permissions: &crate::Permissions,
+    ) -> Result<(), crate::Error> {
+        permissions.erase_all()?;
+
+        interface.write_raw_ap_register(aap, AAP_CMDKEY, CMDKEY_WRITEEN)?;
+        interface.write_raw_ap_register(aap, AAP_CMD, CMD_DEVICEERASE)?;
+
+        let start = Instant::now();
+        while interface.read_raw_ap_register(aap, AAP_STATUS)? & STATUS_ERASEBUSY != 0 {
+            if start.elapsed() > DEVICE_ERASE_TIMEOUT {
+                return Err(crate::Error::Probe(DebugProbeError::Timeout));
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // The debug lock is only removed after a reset.
+        interface.write_raw_ap_register(aap, AAP_CMD, CMD_SYSRESETREQ)?;
+        interface.write_raw_ap_register(aap, AAP_CMDKEY, 0)?;
+
+        Ok(())
+    }
+}
+
+impl ArmDebugSequence for EfmSeries1 {
+    fn debug_device_unlock(
+        &self,
+        interface: &mut Box<dyn ArmProbeInterface>,
+        default_ap: MemoryAp,
+        permissions: &crate::Permissions,
+    ) -> Result<(), crate::Error> {
+        let aap = default_ap.ap_address();
+
+        let idr = interface.read_raw_ap_register(aap, AAP_IDR)?;
+
+        if idr != AAP_IDR_VALUE {
+            return Ok(());
+        }
+
+        log::warn!("The debug access is locked. The device will be erased to unlock it.");
+
+        Self::device_erase(&mut **interface, aap, permissions)?;
+
+        log::info!("The device was erased and is unlocked now");
+
+        // The AHB-AP only replaces the AAP after initializing the debug port again.
+        Err(DebugProbeError::ReAttachRequired.into())
+    }
+}
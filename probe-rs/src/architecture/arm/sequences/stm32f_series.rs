@@ -1,11 +1,17 @@
 //! Sequences for STM32F-series devices
 
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use super::ArmDebugSequence;
-use crate::architecture::arm::{
-    ap::MemoryAp, component::TraceSink, memory::CoresightComponent, ApAddress, ArmProbeInterface,
-    DpAddress,
+use crate::{
+    architecture::arm::{
+        ap::MemoryAp, component::TraceSink, core::armv7m::Aircr, memory::CoresightComponent,
+        ApAddress, ArmProbeInterface, DpAddress,
+    },
+    DebugProbeError, Memory,
 };
 
 /// Marker structure for STM32F-series devices.
@@ -55,15 +61,169 @@ mod dbgmcu {
     }
 }
 
+mod flash {
+    use crate::Memory;
+    use bitfield::bitfield;
+
+    /// The base address of the flash interface.
+    const FLASH: u64 = 0x4002_3C00;
+
+    /// The first key which has to be written to OPTKEYR to unlock OPTCR.
+    const OPTKEY1: u32 = 0x0819_2A3B;
+    /// The second key which has to be written to OPTKEYR to unlock OPTCR.
+    const OPTKEY2: u32 = 0x4C5D_6E7F;
+
+    /// The offset of the option key register (OPTKEYR) in the flash interface.
+    const OPTKEYR: u64 = 0x08;
+
+    /// The `RDP` value of readout protection level 0.
+    pub const RDP_LEVEL_0: u8 = 0xAA;
+    /// The `RDP` value of readout protection level 2. All other values select level 1.
+    pub const RDP_LEVEL_2: u8 = 0xCC;
+
+    bitfield! {
+        /// The status register (SR) of the flash interface. This register is described in
+        /// "RM0090: STM32F4 reference manual" section 3.9.4
+        pub struct Status(u32);
+        impl Debug;
+
+        pub u8, bsy, _: 16;
+        pub u8, errors, _: 7, 4;
+        pub u8, operr, _: 1;
+    }
+
+    impl Status {
+        /// The offset of the status register in the flash interface.
+        const ADDRESS: u64 = 0x0C;
+
+        /// Read the status register from memory.
+        pub fn read(memory: &mut Memory<'_>) -> Result<Self, crate::Error> {
+            let contents = memory.read_word_32(FLASH + Self::ADDRESS)?;
+            Ok(Self(contents))
+        }
+    }
+
+    bitfield! {
+        /// The option control register (OPTCR) of the flash interface. This register is described in
+        /// "RM0090: STM32F4 reference manual" section 3.9.6
+        pub struct OptionControl(u32);
+        impl Debug;
+
+        pub u8, rdp, set_rdp: 15, 8;
+        pub u8, optstrt, set_optstrt: 1;
+        pub u8, optlock, _: 0;
+    }
+
+    impl OptionControl {
+        /// The offset of the option control register in the flash interface.
+        const ADDRESS: u64 = 0x14;
+
+        /// Read the option control register from memory.
+        pub fn read(memory: &mut Memory<'_>) -> Result<Self, crate::Error> {
+            let contents = memory.read_word_32(FLASH + Self::ADDRESS)?;
+            Ok(Self(contents))
+        }
+
+        /// Write the option control register to memory.
+        pub fn write(&mut self, memory: &mut Memory<'_>) -> Result<(), crate::Error> {
+            memory.write_word_32(FLASH + Self::ADDRESS, self.0)
+        }
+
+        /// Unlock the option control register, if it is locked.
+        pub fn unlock(memory: &mut Memory<'_>) -> Result<(), crate::Error> {
+            if Self::read(memory)?.optlock() {
+                memory.write_word_32(FLASH + OPTKEYR, OPTKEY1)?;
+                memory.write_word_32(FLASH + OPTKEYR, OPTKEY2)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// The time to wait for the mass erase when removing the readout protection.
+///
+/// Erasing the 2 MB of flash of the largest parts takes up to 32 seconds.
+const RDP_REGRESSION_TIMEOUT: Duration = Duration::from_secs(40);
+
+impl Stm32fSeries {
+    /// Remove readout protection level 1, which erases the whole flash.
+    ///
+    /// With the readout protection active, the flash can't be accessed while a debugger
+    /// is connected, so every access to it fails.
+    fn remove_readout_protection(
+        &self,
+        memory: &mut Memory<'_>,
+        permissions: &crate::Permissions,
+    ) -> Result<(), crate::Error> {
+        let optcr = flash::OptionControl::read(memory)?;
+
+        match optcr.rdp() {
+            flash::RDP_LEVEL_0 => return Ok(()),
+            flash::RDP_LEVEL_2 => {
+                // With level 2 the debug port is disabled, so this should be unreachable.
+                return Err(crate::Error::ArchitectureSpecific(
+                    "The chip is permanently protected (RDP level 2)".into(),
+                ));
+            }
+            _ => (),
+        }
+
+        log::warn!("The chip is read protected (RDP level 1). Removing the protection erases the whole flash.");
+        permissions.erase_all()?;
+
+        flash::OptionControl::unlock(memory)?;
+
+        let mut optcr = flash::OptionControl::read(memory)?;
+        optcr.set_rdp(flash::RDP_LEVEL_0);
+        optcr.write(memory)?;
+        optcr.set_optstrt(true);
+        optcr.write(memory)?;
+
+        let start = Instant::now();
+        loop {
+            let status = flash::Status::read(memory)?;
+
+            if !status.bsy() {
+                if status.errors() != 0 || status.operr() {
+                    return Err(crate::Error::ArchitectureSpecific(
+                        format!("Removing the readout protection failed: {:?}", status).into(),
+                    ));
+                }
+
+                break;
+            }
+
+            if start.elapsed() > RDP_REGRESSION_TIMEOUT {
+                return Err(crate::Error::Probe(DebugProbeError::Timeout));
+            }
+
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        log::info!("Removed the readout protection, resetting the chip to reload the option bytes");
+
+        // The new option bytes are only loaded on reset, which also drops the debug connection.
+        let mut aircr = Aircr(0);
+        aircr.vectkey();
+        aircr.set_sysresetreq(true);
+        memory.write_word_32(Aircr::ADDRESS, aircr.into()).ok();
+
+        Err(crate::Error::Probe(DebugProbeError::ReAttachRequired))
+    }
+}
+
 impl ArmDebugSequence for Stm32fSeries {
     fn debug_device_unlock(
         &self,
         interface: &mut Box<dyn ArmProbeInterface>,
         default_ap: MemoryAp,
-        _permissions: &crate::Permissions,
+        permissions: &crate::Permissions,
     ) -> Result<(), crate::Error> {
         let mut memory = interface.memory_interface(default_ap)?;
 
+        self.remove_readout_protection(&mut memory, permissions)?;
+
         let mut cr = dbgmcu::Control::read(&mut memory)?;
         cr.enable_standby_debug(true);
         cr.enable_sleep_debug(true);
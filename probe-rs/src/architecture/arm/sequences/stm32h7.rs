@@ -34,12 +34,20 @@ enum TraceFunnelId {
 }
 
 /// Marker struct indicating initialization sequencing for STM32H7 family parts.
-pub struct Stm32h7 {}
+pub struct Stm32h7 {
+    /// The part has a Cortex-M4 core in the D2 domain, in addition to the Cortex-M7.
+    dual_core: bool,
+}
 
 impl Stm32h7 {
     /// Create the sequencer for the H7 family of parts.
     pub fn create() -> Arc<Self> {
-        Arc::new(Self {})
+        Arc::new(Self { dual_core: false })
+    }
+
+    /// Create the sequencer for the dual core H7 parts, like the STM32H745/H747/H755/H757.
+    pub fn create_dual_core() -> Arc<Self> {
+        Arc::new(Self { dual_core: true })
     }
 
     /// Configure all debug components on the chip.
@@ -69,6 +77,14 @@ impl Stm32h7 {
         control.enable_sleep_debug(enable);
         control.enable_stop_debug(enable);
 
+        // The Cortex-M4 of the dual core parts is in the D2 domain, which is powered down
+        // when the Cortex-M4 sleeps. Its AP is only accessible while the domain is powered.
+        if self.dual_core {
+            control.enable_d2_standby_debug(enable);
+            control.enable_d2_sleep_debug(enable);
+            control.enable_d2_stop_debug(enable);
+        }
+
         control.write(memory)?;
 
         Ok(())
@@ -92,6 +108,10 @@ mod dbgmcu {
         pub u8, dbgstop_d1, enable_stop_debug: 1;
         pub u8, dbgstby_d1, enable_standby_debug: 2;
 
+        pub u8, dbgsleep_d2, enable_d2_sleep_debug: 3;
+        pub u8, dbgstop_d2, enable_d2_stop_debug: 4;
+        pub u8, dbgstby_d2, enable_d2_standby_debug: 5;
+
         pub u8, d3dbgcken, enable_d3_clock: 22;
         pub u8, d1dbgcken, enable_d1_clock: 21;
         pub u8, traceclken, enable_traceck: 20;
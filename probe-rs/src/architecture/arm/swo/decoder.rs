@@ -0,0 +1,563 @@
+//! Decoder for the ITM and DWT trace packets sent over SWO.
+//!
+//! The packet format is specified in the ARMv7-M Architecture Reference Manual,
+//! Appendix D4 "Debug ITM and DWT Packet Protocol".
+
+use std::collections::VecDeque;
+
+/// Number of zero bits which are sent before the final `1` bit of a synchronization packet.
+const SYNC_ZERO_BITS: usize = 47;
+
+/// Header of an overflow packet.
+const OVERFLOW_HEADER: u8 = 0x70;
+
+/// Header of the first global timestamp packet, containing the lower bits of the timestamp.
+const GLOBAL_TIMESTAMP_1_HEADER: u8 = 0x94;
+
+/// Header of the second global timestamp packet, containing the upper bits of the timestamp.
+const GLOBAL_TIMESTAMP_2_HEADER: u8 = 0xb4;
+
+/// Maximum number of payload bytes for packets using continuation bits,
+/// which is reached by the second global timestamp packet with a 64 bit timestamp.
+const MAX_CONTINUATION_BYTES: usize = 6;
+
+/// The action of the core which caused an exception trace packet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExceptionAction {
+    /// The exception was entered.
+    Entered,
+    /// The exception was exited.
+    Exited,
+    /// The core returned to the exception, e.g. after a preempting exception was handled.
+    Returned,
+}
+
+/// The relation of a local timestamp to the packet it belongs to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TimestampRelation {
+    /// The timestamp is synchronous to the packets before it.
+    Sync,
+    /// The timestamp was delayed relative to the packets before it.
+    TimestampDelayed,
+    /// The packets before the timestamp were delayed.
+    PacketDelayed,
+    /// Both the packets and the timestamp were delayed.
+    TimestampAndPacketDelayed,
+}
+
+/// A decoded ITM or DWT trace packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// The trace stream was (re-)synchronized.
+    Sync,
+    /// The ITM overflowed, and some packets were lost.
+    Overflow,
+    /// Data written by software to an ITM stimulus port.
+    Instrumentation {
+        /// The number of the stimulus port.
+        port: u8,
+        /// The written data, 1, 2 or 4 bytes.
+        payload: Vec<u8>,
+    },
+    /// A local timestamp, relative to the previous local timestamp.
+    LocalTimestamp {
+        /// The number of timestamp clock cycles since the previous local timestamp.
+        delta: u32,
+        /// How the timestamp relates to the packets before it.
+        relation: TimestampRelation,
+    },
+    /// A global timestamp.
+    ///
+    /// Global timestamps are split into two packets, the first one containing
+    /// the lower bits and the second one containing the upper bits.
+    /// The value contains all bits known so far.
+    GlobalTimestamp {
+        /// The value of the timestamp.
+        value: u64,
+    },
+    /// A periodic sample of the program counter.
+    PcSample {
+        /// The sampled program counter, or `None` if the core was sleeping.
+        pc: Option<u32>,
+    },
+    /// An exception was entered, exited or returned to.
+    ExceptionTrace {
+        /// The exception number.
+        exception: u16,
+        /// The action of the core.
+        action: ExceptionAction,
+    },
+    /// One or more of the DWT profiling counters wrapped around.
+    EventCounter {
+        /// The counters which wrapped, bit 0 is CPICNT, bit 5 is CYCCNT.
+        counters: u8,
+    },
+    /// The program counter of an access which matched a DWT comparator.
+    DataTracePc {
+        /// The number of the comparator.
+        comparator: u8,
+        /// The program counter of the instruction which caused the access.
+        pc: u32,
+    },
+    /// The address of an access which matched a DWT comparator.
+    DataTraceAddress {
+        /// The number of the comparator.
+        comparator: u8,
+        /// The lower 16 bits of the accessed address.
+        address: u16,
+    },
+    /// The value of an access which matched a DWT comparator.
+    DataTraceValue {
+        /// The number of the comparator.
+        comparator: u8,
+        /// `true` if the access was a write, `false` for a read.
+        write: bool,
+        /// The accessed value, 1, 2 or 4 bytes.
+        value: Vec<u8>,
+    },
+    /// A packet which could not be decoded, because it is malformed or unknown.
+    Unknown {
+        /// The header of the packet.
+        header: u8,
+        /// The payload of the packet.
+        payload: Vec<u8>,
+    },
+}
+
+/// A decoder for the ITM and DWT trace packets sent over SWO.
+///
+/// Data is added using [`ItmDecoder::push`], and decoded packets are returned
+/// by [`ItmDecoder::pull`] or the [`Iterator`] implementation. Packets can be
+/// split over multiple calls to `push`.
+///
+/// The decoder starts unsynchronized, and discards all data until a synchronization
+/// packet is found, unless [`ItmDecoder::assume_synchronized`] is used.
+#[derive(Debug, Default)]
+pub struct ItmDecoder {
+    buffer: VecDeque<u8>,
+    synchronized: bool,
+    zero_bits: usize,
+    global_timestamp: u64,
+}
+
+impl ItmDecoder {
+    /// Create a new, unsynchronized decoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Treat the incoming data as synchronized.
+    ///
+    /// This is useful if the ITM was enabled after the probe started to capture
+    /// data, because the ITM only sends synchronization packets periodically.
+    pub fn assume_synchronized(mut self) -> Self {
+        self.synchronized = true;
+        self
+    }
+
+    /// Returns `true` if the decoder is synchronized to the packet stream.
+    pub fn is_synchronized(&self) -> bool {
+        self.synchronized
+    }
+
+    /// Add raw trace data to the decoder.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend(data);
+    }
+
+    /// Decode the next complete packet, if available.
+    pub fn pull(&mut self) -> Option<TraceEvent> {
+        loop {
+            if !self.synchronized {
+                self.find_sync()?;
+                return Some(TraceEvent::Sync);
+            }
+
+            let header = *self.buffer.front()?;
+
+            if header == 0x00 {
+                // The start of a synchronization packet, which is only
+                // complete after at least 47 zero bits and a one bit.
+                self.find_sync()?;
+                return Some(TraceEvent::Sync);
+            }
+
+            let (length, event) = self.decode(header)?;
+            self.buffer.drain(..length);
+
+            if let Some(event) = event {
+                return Some(event);
+            }
+        }
+    }
+
+    /// Search for a synchronization packet, and consume all data up to its end.
+    ///
+    /// Returns `None` if no complete synchronization packet was found.
+    fn find_sync(&mut self) -> Option<()> {
+        while let Some(byte) = self.buffer.pop_front() {
+            if byte == 0 {
+                self.zero_bits += 8;
+                continue;
+            }
+
+            let found = self.zero_bits + byte.trailing_zeros() as usize >= SYNC_ZERO_BITS
+                && byte.count_ones() == 1;
+
+            self.zero_bits = byte.leading_zeros() as usize;
+
+            if found {
+                self.zero_bits = 0;
+                self.synchronized = true;
+                return Some(());
+            }
+
+            self.synchronized = false;
+        }
+
+        // Put back the zero bytes seen so far, they are needed if
+        // the synchronization packet is completed by later data.
+        for _ in 0..self.zero_bits / 8 {
+            self.buffer.push_back(0);
+        }
+        self.zero_bits = 0;
+
+        None
+    }
+
+    /// Decode the packet starting with `header`.
+    ///
+    /// Returns the length of the packet, including the header, and the decoded
+    /// event, or `None` if the packet is incomplete.
+    fn decode(&mut self, header: u8) -> Option<(usize, Option<TraceEvent>)> {
+        if header == OVERFLOW_HEADER {
+            return Some((1, Some(TraceEvent::Overflow)));
+        }
+
+        match header & 0b11 {
+            0b00 => self.decode_protocol_packet(header),
+            size => {
+                let length = match size {
+                    0b01 => 1,
+                    0b10 => 2,
+                    _ => 4,
+                };
+
+                if self.buffer.len() <= length {
+                    return None;
+                }
+
+                let payload: Vec<u8> = self.buffer.range(1..=length).copied().collect();
+                let address = header >> 3;
+
+                let event = if header & 0b100 == 0 {
+                    TraceEvent::Instrumentation {
+                        port: address,
+                        payload,
+                    }
+                } else {
+                    decode_hardware_packet(header, address, payload)
+                };
+
+                Some((1 + length, Some(event)))
+            }
+        }
+    }
+
+    /// Decode a timestamp or extension packet.
+    fn decode_protocol_packet(&mut self, header: u8) -> Option<(usize, Option<TraceEvent>)> {
+        // Local timestamp, format 2, without payload.
+        if header & 0b1000_1111 == 0 {
+            return Some((
+                1,
+                Some(TraceEvent::LocalTimestamp {
+                    delta: (header >> 4) as u32,
+                    relation: TimestampRelation::Sync,
+                }),
+            ));
+        }
+
+        let payload = self.continuation_payload(header)?;
+        let length = 1 + payload.len();
+
+        let event = if header & 0b1100_1111 == 0b1100_0000 {
+            let relation = match (header >> 4) & 0b11 {
+                0b00 => TimestampRelation::Sync,
+                0b01 => TimestampRelation::TimestampDelayed,
+                0b10 => TimestampRelation::PacketDelayed,
+                _ => TimestampRelation::TimestampAndPacketDelayed,
+            };
+
+            Some(TraceEvent::LocalTimestamp {
+                delta: continuation_value(&payload) as u32,
+                relation,
+            })
+        } else if header == GLOBAL_TIMESTAMP_1_HEADER {
+            // Bits 25:0 of the timestamp, the two upper bits of the last byte are flags.
+            let value = continuation_value(&payload) & 0x3ff_ffff;
+            self.global_timestamp = (self.global_timestamp & !0x3ff_ffff) | value;
+
+            Some(TraceEvent::GlobalTimestamp {
+                value: self.global_timestamp,
+            })
+        } else if header == GLOBAL_TIMESTAMP_2_HEADER {
+            let value = continuation_value(&payload);
+            self.global_timestamp = (self.global_timestamp & 0x3ff_ffff) | (value << 26);
+
+            Some(TraceEvent::GlobalTimestamp {
+                value: self.global_timestamp,
+            })
+        } else if header & 0b100 != 0 {
+            // Extension packets are used for the page of the stimulus ports,
+            // which is not used by the ARMv7-M ITM.
+            log::debug!("Ignoring ITM extension packet {:#04x}", header);
+            None
+        } else {
+            Some(TraceEvent::Unknown { header, payload })
+        };
+
+        Some((length, event))
+    }
+
+    /// Collect the payload of a packet using continuation bits,
+    /// or `None` if the packet is incomplete.
+    fn continuation_payload(&self, header: u8) -> Option<Vec<u8>> {
+        let mut payload = Vec::new();
+
+        if header & 0x80 == 0 {
+            return Some(payload);
+        }
+
+        for &byte in self.buffer.iter().skip(1) {
+            payload.push(byte);
+
+            if byte & 0x80 == 0 || payload.len() == MAX_CONTINUATION_BYTES {
+                return Some(payload);
+            }
+        }
+
+        None
+    }
+}
+
+impl Iterator for ItmDecoder {
+    type Item = TraceEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pull()
+    }
+}
+
+/// Combine the 7 bit values of a payload using continuation bits, least significant first.
+fn continuation_value(payload: &[u8]) -> u64 {
+    payload.iter().enumerate().fold(0, |value, (i, byte)| {
+        value | (u64::from(byte & 0x7f) << (7 * i))
+    })
+}
+
+/// Decode a hardware source packet, sent by the DWT.
+fn decode_hardware_packet(header: u8, discriminator: u8, payload: Vec<u8>) -> TraceEvent {
+    let value = payload
+        .iter()
+        .rev()
+        .fold(0u32, |value, byte| (value << 8) | u32::from(*byte));
+
+    match (discriminator, payload.len()) {
+        (0, 1) => TraceEvent::EventCounter {
+            counters: payload[0],
+        },
+        (1, 2) => {
+            let action = match (payload[1] >> 4) & 0b11 {
+                0b01 => ExceptionAction::Entered,
+                0b10 => ExceptionAction::Exited,
+                0b11 => ExceptionAction::Returned,
+                _ => return TraceEvent::Unknown { header, payload },
+            };
+
+            TraceEvent::ExceptionTrace {
+                exception: (value & 0x1ff) as u16,
+                action,
+            }
+        }
+        (2, 4) => TraceEvent::PcSample { pc: Some(value) },
+        (2, 1) if payload[0] == 0 => TraceEvent::PcSample { pc: None },
+        (8..=15, 4) if discriminator & 1 == 0 => TraceEvent::DataTracePc {
+            comparator: (discriminator >> 1) & 0b11,
+            pc: value,
+        },
+        (8..=15, 2) if discriminator & 1 != 0 => TraceEvent::DataTraceAddress {
+            comparator: (discriminator >> 1) & 0b11,
+            address: value as u16,
+        },
+        (16..=23, _) => TraceEvent::DataTraceValue {
+            comparator: (discriminator >> 1) & 0b11,
+            write: discriminator & 1 != 0,
+            value: payload,
+        },
+        _ => TraceEvent::Unknown { header, payload },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ExceptionAction, ItmDecoder, TimestampRelation, TraceEvent};
+
+    const SYNC: [u8; 6] = [0, 0, 0, 0, 0, 0x80];
+
+    fn decode(data: &[u8]) -> Vec<TraceEvent> {
+        let mut decoder = ItmDecoder::new();
+        decoder.push(data);
+        decoder.collect()
+    }
+
+    #[test]
+    fn data_before_sync_is_discarded() {
+        let mut data = vec![0x01, b'a', 0x03];
+        data.extend_from_slice(&SYNC);
+        data.extend_from_slice(&[0x01, b'b']);
+
+        assert_eq!(
+            decode(&data),
+            vec![
+                TraceEvent::Sync,
+                TraceEvent::Instrumentation {
+                    port: 0,
+                    payload: vec![b'b']
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn instrumentation_packets() {
+        let mut decoder = ItmDecoder::new().assume_synchronized();
+        decoder.push(&[0x0a, 0x34, 0x12, 0x0b, 1, 2, 3, 4]);
+
+        assert_eq!(
+            decoder.collect::<Vec<_>>(),
+            vec![
+                TraceEvent::Instrumentation {
+                    port: 1,
+                    payload: vec![0x34, 0x12]
+                },
+                TraceEvent::Instrumentation {
+                    port: 1,
+                    payload: vec![1, 2, 3, 4]
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn packets_split_over_multiple_pushes() {
+        let mut decoder = ItmDecoder::new().assume_synchronized();
+
+        decoder.push(&[0x03, 0xef, 0xbe]);
+        assert_eq!(decoder.pull(), None);
+
+        decoder.push(&[0xad, 0xde]);
+        assert_eq!(
+            decoder.pull(),
+            Some(TraceEvent::Instrumentation {
+                port: 0,
+                payload: vec![0xef, 0xbe, 0xad, 0xde]
+            })
+        );
+    }
+
+    #[test]
+    fn sync_split_over_multiple_pushes() {
+        let mut decoder = ItmDecoder::new();
+
+        decoder.push(&SYNC[..3]);
+        assert_eq!(decoder.pull(), None);
+
+        decoder.push(&SYNC[3..]);
+        assert_eq!(decoder.pull(), Some(TraceEvent::Sync));
+        assert!(decoder.is_synchronized());
+    }
+
+    #[test]
+    fn local_timestamps() {
+        assert_eq!(
+            decode(&[&SYNC[..], &[0x30, 0xd0, 0x85, 0x01]].concat()),
+            vec![
+                TraceEvent::Sync,
+                TraceEvent::LocalTimestamp {
+                    delta: 3,
+                    relation: TimestampRelation::Sync
+                },
+                TraceEvent::LocalTimestamp {
+                    delta: 0x85,
+                    relation: TimestampRelation::TimestampDelayed
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn global_timestamps() {
+        assert_eq!(
+            decode(&[&SYNC[..], &[0x94, 0x81, 0x01, 0xb4, 0x02]].concat()),
+            vec![
+                TraceEvent::Sync,
+                TraceEvent::GlobalTimestamp { value: 0x81 },
+                TraceEvent::GlobalTimestamp {
+                    value: 0x81 | (2 << 26)
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn pc_samples() {
+        assert_eq!(
+            decode(&[&SYNC[..], &[0x17, 0x00, 0x10, 0x00, 0x08, 0x15, 0x00]].concat()),
+            vec![
+                TraceEvent::Sync,
+                TraceEvent::PcSample {
+                    pc: Some(0x0800_1000)
+                },
+                TraceEvent::PcSample { pc: None }
+            ]
+        );
+    }
+
+    #[test]
+    fn exception_trace() {
+        assert_eq!(
+            decode(&[&SYNC[..], &[0x0e, 0x0f, 0x10, 0x0e, 0x0f, 0x20, 0x70]].concat()),
+            vec![
+                TraceEvent::Sync,
+                TraceEvent::ExceptionTrace {
+                    exception: 15,
+                    action: ExceptionAction::Entered
+                },
+                TraceEvent::ExceptionTrace {
+                    exception: 15,
+                    action: ExceptionAction::Exited
+                },
+                TraceEvent::Overflow
+            ]
+        );
+    }
+
+    #[test]
+    fn data_trace() {
+        assert_eq!(
+            decode(&[&SYNC[..], &[0x47, 0x00, 0x01, 0x00, 0x08, 0x8d, 0x2a]].concat()),
+            vec![
+                TraceEvent::Sync,
+                TraceEvent::DataTracePc {
+                    comparator: 0,
+                    pc: 0x0800_0100
+                },
+                TraceEvent::DataTraceValue {
+                    comparator: 0,
+                    write: true,
+                    value: vec![0x2a]
+                }
+            ]
+        );
+    }
+}
@@ -1,10 +1,14 @@
 //! SWO tracing related functions.
 
+mod decoder;
+
 use crate::architecture::arm::communication_interface::ArmProbeInterface;
-use crate::Error;
+use crate::{Error, Session};
+
+pub use decoder::{ExceptionAction, ItmDecoder, TimestampRelation, TraceEvent};
 
 /// The protocol the SWO pin should use for data transmission.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum SwoMode {
     /// UART
     Uart,
@@ -209,3 +213,33 @@ impl<'a> std::io::Read for SwoReader<'a> {
         Ok(swo.len())
     }
 }
+
+/// An iterator over the decoded ITM and DWT packets of a session,
+/// created by [`Session::trace_events`].
+pub struct TraceEvents<'a> {
+    session: &'a mut Session,
+}
+
+impl<'a> TraceEvents<'a> {
+    pub(crate) fn new(session: &'a mut Session) -> Self {
+        Self { session }
+    }
+}
+
+impl<'a> Iterator for TraceEvents<'a> {
+    type Item = Result<TraceEvent, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.session.next_trace_event() {
+                return Some(Ok(event));
+            }
+
+            match self.session.read_trace_data() {
+                Ok(data) if data.is_empty() => return None,
+                Ok(data) => self.session.push_trace_data(&data),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{DebugProbe, DebugProbeError};
 
 /// The type of port we are using.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub enum PortType {
     /// Debug Port (e.g. SWD or JTAG)
     DebugPort,
@@ -29,7 +29,7 @@ bitfield::bitfield! {
 }
 
 /// Debug port address.
-#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash, serde::Serialize, serde::Deserialize)]
 pub enum DpAddress {
     /// Access the single DP on the bus, assuming there is only one.
     /// Will cause corruption if multiple are present.
@@ -152,6 +152,11 @@ pub trait RawDapAccess {
 /// Operations on this trait perform logical register reads/writes. Implementations
 /// are responsible for bank switching and AP selection, so one method call can result
 /// in multiple transactions on the wire, if necessary.
+///
+/// This can be used to access any access port by its address, including vendor specific
+/// APs like the CTRL-AP of Nordic devices or the DM-AP of NXP devices. Typed registers can
+/// be accessed using [`ApAccess`](crate::architecture::arm::ap::ApAccess), which is implemented
+/// for every type implementing this trait, including `dyn ArmProbeInterface`.
 pub trait DapAccess {
     /// Read a Debug Port register.
     ///
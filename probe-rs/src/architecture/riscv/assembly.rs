@@ -28,6 +28,35 @@ pub fn addi(source: u8, destination: u8, immediate: u16) -> u32 {
     i_type_instruction(opcode, source, function, destination, immediate)
 }
 
+/// Assemble a `slli` instruction.
+pub fn slli(source: u8, destination: u8, shamt: u8) -> u32 {
+    let opcode = 0b001_0011;
+    let function = 0b001;
+
+    i_type_instruction(opcode, source, function, destination, shift_amount(shamt))
+}
+
+/// Assemble a `srai` instruction.
+pub fn srai(source: u8, destination: u8, shamt: u8) -> u32 {
+    let opcode = 0b001_0011;
+    let function = 0b101;
+
+    i_type_instruction(
+        opcode,
+        source,
+        function,
+        destination,
+        0b0100_0000_0000 | shift_amount(shamt),
+    )
+}
+
+/// The shift amount of an immediate shift, which is at most 63 on RV64.
+fn shift_amount(shamt: u8) -> u16 {
+    assert!(shamt <= 0x3f);
+
+    shamt as u16
+}
+
 // We need to perform the csrr instruction, which reads a CSR.
 // This is a pseudo instruction, which actually is encoded as a
 // csrrs instruction, with the rs1 register being x0,
@@ -58,6 +87,83 @@ pub fn csrrw(rd: u8, rs1: u8, csr: u16) -> u32 {
     i_type_instruction(opcode, rs1, funct3, rd, csr)
 }
 
+/// Assemble a `fmv.x.w` instruction, or a `fmv.x.d` instruction if `double` is set,
+/// which moves the floating point register `source` to the register `destination`.
+pub fn fmv_x_f(destination: u8, source: u8, double: bool) -> u32 {
+    let funct7 = if double { 0b111_0001 } else { 0b111_0000 };
+
+    fp_move_instruction(funct7, source, destination)
+}
+
+/// Assemble a `fmv.w.x` instruction, or a `fmv.d.x` instruction if `double` is set,
+/// which moves the register `source` to the floating point register `destination`.
+pub fn fmv_f_x(destination: u8, source: u8, double: bool) -> u32 {
+    let funct7 = if double { 0b111_1001 } else { 0b111_1000 };
+
+    fp_move_instruction(funct7, source, destination)
+}
+
+fn fp_move_instruction(funct7: u32, rs1: u8, rd: u8) -> u32 {
+    let opcode = 0b101_0011;
+
+    assert!(rs1 <= 0x1f);
+    assert!(rd <= 0x1f);
+
+    funct7 << 25 | (rs1 as u32) << 15 | (rd as u32) << 7 | opcode
+}
+
+/// Assemble a `vsetvli` instruction, with the vector type `vtypei`.
+pub fn vsetvli(destination: u8, source: u8, vtypei: u16) -> u32 {
+    let opcode = 0b101_0111;
+    let function = 0b111;
+
+    // The highest bit of the immediate is zero for vsetvli.
+    assert!(vtypei <= 0x7ff);
+
+    i_type_instruction(opcode, source, function, destination, vtypei)
+}
+
+/// Assemble a `vsetvl` instruction, with the application vector length in `avl`
+/// and the vector type in `vtype`.
+pub fn vsetvl(destination: u8, avl: u8, vtype: u8) -> u32 {
+    let opcode = 0b101_0111;
+    let function = 0b111;
+
+    assert!(vtype <= 0x1f);
+
+    1 << 31 | (vtype as u32) << 20 | i_type_instruction(opcode, avl, function, destination, 0)
+}
+
+/// Assemble a `vmv.x.s` instruction, which moves element 0 of the vector register `source`
+/// to the register `destination`.
+pub fn vmv_x_s(destination: u8, source: u8) -> u32 {
+    vector_instruction(0b01_0000, source, 0, 0b010, destination)
+}
+
+/// Assemble a `vslide1down.vx` instruction, which slides the elements of the vector register
+/// `source` down by one, and inserts the value of the register `value` as the last element.
+pub fn vslide1down_vx(destination: u8, source: u8, value: u8) -> u32 {
+    vector_instruction(0b00_1111, source, value, 0b110, destination)
+}
+
+/// Assemble an unmasked vector arithmetic instruction.
+fn vector_instruction(funct6: u32, vs2: u8, rs1: u8, funct3: u32, rd: u8) -> u32 {
+    let opcode = 0b101_0111;
+    let unmasked = 1;
+
+    assert!(vs2 <= 0x1f);
+    assert!(rs1 <= 0x1f);
+    assert!(rd <= 0x1f);
+
+    funct6 << 26
+        | unmasked << 25
+        | (vs2 as u32) << 20
+        | (rs1 as u32) << 15
+        | funct3 << 12
+        | (rd as u32) << 7
+        | opcode
+}
+
 /// Assemble an I-type instruction, as specified in the RISCV ISA
 ///
 /// This function panics if any of the values would have to be truncated.
@@ -77,7 +183,9 @@ fn i_type_instruction(opcode: u8, rs1: u8, funct3: u8, rd: u8, imm: u16) -> u32
 
 #[cfg(test)]
 mod test {
-    use super::{csrr, csrw, lw, sw};
+    use super::{
+        csrr, csrw, fmv_f_x, fmv_x_f, lw, slli, srai, sw, vmv_x_s, vsetvl, vsetvli, vslide1down_vx,
+    };
 
     #[test]
     fn assemble_csrr() {
@@ -126,4 +234,31 @@ mod test {
 
         assert_eq!(assembled, expected);
     }
+
+    #[test]
+    fn assemble_shifts() {
+        // Assembly output of the semihosting sequence
+        // 'slli x0, x0, 0x1f' and 'srai x0, x0, 7'
+        assert_eq!(slli(0, 0, 0x1f), 0x01f01013);
+        assert_eq!(srai(0, 0, 7), 0x40705013);
+    }
+
+    #[test]
+    fn assemble_fp_moves() {
+        // Assembly output of 'fmv.x.w s0, ft1' and 'fmv.d.x fs0, s1'
+        assert_eq!(fmv_x_f(8, 1, false), 0xe0008453);
+        assert_eq!(fmv_f_x(8, 9, true), 0xf2048453);
+    }
+
+    #[test]
+    fn assemble_vector() {
+        // Assembly output of 'vsetvli s0, zero, e32, m1, ta, ma'
+        assert_eq!(vsetvli(8, 0, 0xd0), 0x0d007457);
+        // Assembly output of 'vsetvl zero, s0, s1'
+        assert_eq!(vsetvl(0, 8, 9), 0x80947057);
+        // Assembly output of 'vmv.x.s a0, v8'
+        assert_eq!(vmv_x_s(10, 8), 0x42802557);
+        // Assembly output of 'vslide1down.vx v8, v8, a0'
+        assert_eq!(vslide1down_vx(8, 8, 10), 0x3e856457);
+    }
 }
@@ -13,12 +13,10 @@ use crate::{
     architecture::riscv::*,
     probe::{CommandResult, DeferredResultIndex},
 };
-use crate::{MemoryInterface, Probe};
+use crate::{MemoryInterface, Probe, VectorCatch};
 
 use crate::{probe::JTAGAccess, Error as ProbeRsError, RegisterId};
 
-use crate::memory::valid_32_address;
-
 use bitfield::bitfield;
 use std::{
     collections::HashMap,
@@ -64,6 +62,18 @@ pub enum RiscvError {
     /// The given trigger type is not available for the address breakpoint.
     #[error("Unexpected trigger type {0} for address breakpoint.")]
     UnexpectedTriggerType(u32),
+    /// No unused trigger of the given type is available.
+    #[error("No unused trigger of type {0} is available.")]
+    TriggerNotAvailable(u32),
+    /// The given vector catch condition is not supported by RISC-V cores.
+    #[error("Vector catch for {0:?} is not supported by RISC-V cores.")]
+    VectorCatchNotSupported(VectorCatch),
+    /// The hart with the given index does not exist.
+    #[error("Hart {0} does not exist.")]
+    HartNotAvailable(u32),
+    /// The address can not be accessed by the hart or the system bus.
+    #[error("Address {0:#010x} is out of range.")]
+    AddressOutOfRange(u64),
 }
 
 impl From<RiscvError> for ProbeRsError {
@@ -200,6 +210,21 @@ pub struct RiscvCommunicationInterfaceState {
     /// Number of harts
     num_harts: u32,
 
+    /// The hart selected by `hartsel`, which is accessed by all operations
+    selected_hart: u32,
+
+    /// Support for selecting multiple harts using the hart array mask
+    supports_hart_array: bool,
+
+    /// Support for halting harts directly after a reset, using `setresethaltreq`
+    supports_reset_halt_req: bool,
+
+    /// Width of the system bus addresses, 0 if there is no system bus access
+    sbasize: u8,
+
+    /// XLEN of each hart, determined the first time it is needed
+    xlen: HashMap<u32, RiscvBusAccess>,
+
     memory_access_info: HashMap<RiscvBusAccess, MemoryAccessMethod>,
 
     /// describes, if the given register can be read / written with an
@@ -238,6 +263,16 @@ impl RiscvCommunicationInterfaceState {
             // We assume only a singe hart exisits initially
             num_harts: 1,
 
+            selected_hart: 0,
+
+            supports_hart_array: false,
+
+            supports_reset_halt_req: false,
+
+            sbasize: 0,
+
+            xlen: HashMap::new(),
+
             memory_access_info: HashMap::new(),
 
             abstract_cmd_register_info: HashMap::new(),
@@ -322,6 +357,8 @@ impl RiscvCommunicationInterface {
 
         self.state.implicit_ebreak = status.impebreak();
 
+        self.state.supports_reset_halt_req = status.hasresethaltreq();
+
         // check if the configuration string pointer is valid, and retrieve it, if valid
         self.state.confstrptr = if status.confstrptrvalid() {
             let confstrptr_0: Confstrptr0 = self.read_dm_register()?;
@@ -384,11 +421,18 @@ impl RiscvCommunicationInterface {
 
         self.state.num_harts = num_harts;
 
-        // Select hart 0 again
-        let mut control = Dmcontrol(0);
-        control.set_hartsel(0);
-        control.set_dmactive(true);
+        // Check if the hart array mask is supported, hasel reads as 0 otherwise.
+        let mut control = self.dmcontrol();
+        control.set_hasel(true);
+        self.write_dm_register(control)?;
 
+        let control: Dmcontrol = self.read_dm_register()?;
+
+        self.state.supports_hart_array = control.hasel();
+        log::debug!("Support for hart array: {}", self.state.supports_hart_array);
+
+        // Select hart 0 again
+        let control = self.dmcontrol();
         self.write_dm_register(control)?;
 
         // determine size of the program buffer, and number of data
@@ -432,7 +476,16 @@ impl RiscvCommunicationInterface {
         // Only version 1 is supported, this means that
         // the system bus access conforms to the debug
         // specification 13.2.
-        if sbcs.sbversion() == 1 {
+        if sbcs.sbversion() == 1 && sbcs.sbasize() < 32 {
+            // A size of 0 means that there is no system bus access at all,
+            // and smaller address widths are not supported.
+            log::debug!(
+                "System bus interface with {} bit addresses is not supported.",
+                sbcs.sbasize()
+            );
+        } else if sbcs.sbversion() == 1 {
+            self.state.sbasize = sbcs.sbasize() as u8;
+
             // When possible, we use system bus access for memory access
 
             if sbcs.sbaccess8() {
@@ -474,6 +527,149 @@ impl RiscvCommunicationInterface {
         Ok(())
     }
 
+    /// The number of harts connected to the debug module.
+    pub fn num_harts(&self) -> u32 {
+        self.state.num_harts
+    }
+
+    /// Whether harts can be halted directly after a reset, using `setresethaltreq`.
+    pub fn supports_reset_halt_req(&self) -> bool {
+        self.state.supports_reset_halt_req
+    }
+
+    /// Select the hart which is accessed by all following operations.
+    pub fn select_hart(&mut self, hart: u32) -> Result<(), RiscvError> {
+        if hart >= self.state.num_harts {
+            return Err(RiscvError::HartNotAvailable(hart));
+        }
+
+        if self.state.selected_hart != hart {
+            self.state.selected_hart = hart;
+
+            let control = self.dmcontrol();
+            self.write_dm_register(control)?;
+        }
+
+        Ok(())
+    }
+
+    /// A `dmcontrol` value which keeps the debug module active, and selects the current hart.
+    pub(super) fn dmcontrol(&self) -> Dmcontrol {
+        let mut control = Dmcontrol(0);
+        control.set_dmactive(true);
+        control.set_hartsel(self.state.selected_hart);
+        control
+    }
+
+    /// Halt the given harts.
+    ///
+    /// If the debug module supports the hart array mask, all harts are halted at the same time.
+    /// Otherwise they are halted one after another.
+    pub fn halt_harts(&mut self, harts: &[u32], timeout: Duration) -> Result<(), RiscvError> {
+        self.request_harts(
+            harts,
+            timeout,
+            |control| control.set_haltreq(true),
+            |status| status.allhalted(),
+        )
+    }
+
+    /// Resume the given harts.
+    ///
+    /// If the debug module supports the hart array mask, all harts are resumed at the same time.
+    /// Otherwise they are resumed one after another.
+    pub fn resume_harts(&mut self, harts: &[u32]) -> Result<(), RiscvError> {
+        self.request_harts(
+            harts,
+            RISCV_TIMEOUT,
+            |control| control.set_resumereq(true),
+            |status| status.allresumeack(),
+        )
+    }
+
+    /// Send a halt or resume request to the given harts, and wait until it is acknowledged.
+    fn request_harts(
+        &mut self,
+        harts: &[u32],
+        timeout: Duration,
+        request: impl Fn(&mut Dmcontrol),
+        done: impl Fn(&Dmstatus) -> bool,
+    ) -> Result<(), RiscvError> {
+        let first_hart = match harts.first() {
+            Some(&hart) => hart,
+            None => return Ok(()),
+        };
+
+        let previous_hart = self.state.selected_hart;
+
+        let result = if self.state.supports_hart_array {
+            // The hart selected by hartsel is always part of the selected harts,
+            // so it has to be one of them.
+            self.select_hart(first_hart)?;
+            self.set_hart_array_mask(harts)?;
+            self.request_selected_harts(true, timeout, &request, &done)
+        } else {
+            harts.iter().try_for_each(|&hart| {
+                self.select_hart(hart)?;
+                self.request_selected_harts(false, timeout, &request, &done)
+            })
+        };
+
+        self.select_hart(previous_hart)?;
+
+        result
+    }
+
+    /// Send a request to the selected harts, and clear it when `done` returns `true`.
+    fn request_selected_harts(
+        &mut self,
+        hart_array: bool,
+        timeout: Duration,
+        request: impl Fn(&mut Dmcontrol),
+        done: impl Fn(&Dmstatus) -> bool,
+    ) -> Result<(), RiscvError> {
+        let mut control = self.dmcontrol();
+        control.set_hasel(hart_array);
+        request(&mut control);
+        self.write_dm_register(control)?;
+
+        let start = Instant::now();
+
+        let result = loop {
+            let status: Dmstatus = self.read_dm_register()?;
+
+            if done(&status) {
+                break Ok(());
+            }
+
+            if start.elapsed() > timeout {
+                break Err(RiscvError::Timeout);
+            }
+        };
+
+        // Clear the request
+        let control = self.dmcontrol();
+        self.write_dm_register(control)?;
+
+        result
+    }
+
+    /// Set the hart array mask to the given harts.
+    fn set_hart_array_mask(&mut self, harts: &[u32]) -> Result<(), RiscvError> {
+        // The mask is accessed in windows of 32 harts.
+        for window in 0..(self.state.num_harts + 31) / 32 {
+            let mask = harts
+                .iter()
+                .filter(|&&hart| hart / 32 == window)
+                .fold(0, |mask, hart| mask | 1 << (hart % 32));
+
+            self.write_dm_register(Hawindowsel(window))?;
+            self.write_dm_register(Hawindow(mask))?;
+        }
+
+        Ok(())
+    }
+
     pub(super) fn read_dm_register<R: DebugRegister>(&mut self) -> Result<R, RiscvError> {
         log::debug!("Reading DM register '{}' at {:#010x}", R::NAME, R::ADDRESS);
 
@@ -589,8 +785,48 @@ impl RiscvCommunicationInterface {
         Ok(())
     }
 
+    /// Check the status of a system bus access, and clear the error flags if it failed.
+    ///
+    /// The error flags are sticky, and no further system bus accesses are
+    /// possible until they are cleared.
+    fn check_sysbus_error(&mut self, sbcs: Sbcs) -> Result<(), RiscvError> {
+        if sbcs.sberror() == 0 && !sbcs.sbbusyerror() {
+            return Ok(());
+        }
+
+        log::debug!(
+            "System bus access failed, sberror={}, sbbusyerror={}",
+            sbcs.sberror(),
+            sbcs.sbbusyerror()
+        );
+
+        let mut clear = Sbcs(0);
+        clear.set_sberror(0b111);
+        clear.set_sbbusyerror(true);
+        self.write_dm_register(clear)?;
+
+        Err(RiscvError::SystemBusAccess)
+    }
+
+    /// Split an address into the values of `sbaddress1` and `sbaddress0`.
+    ///
+    /// `sbaddress1` is only used if the system bus has addresses wider than 32 bits.
+    fn sysbus_address(&self, address: u64) -> Result<(Option<u32>, u32), RiscvError> {
+        let high_word = (address >> 32) as u32;
+
+        if self.state.sbasize > 32 {
+            Ok((Some(high_word), address as u32))
+        } else if high_word == 0 {
+            Ok((None, address as u32))
+        } else {
+            Err(RiscvError::AddressOutOfRange(address))
+        }
+    }
+
     /// Perform a single read from a memory location, using system bus access.
-    fn perform_memory_read_sysbus<V: RiscvValue>(&mut self, address: u32) -> Result<V, RiscvError> {
+    fn perform_memory_read_sysbus<V: RiscvValue>(&mut self, address: u64) -> Result<V, RiscvError> {
+        let (address_high, address_low) = self.sysbus_address(address)?;
+
         let mut sbcs = Sbcs(0);
 
         sbcs.set_sbaccess(V::WIDTH as u32);
@@ -598,17 +834,19 @@ impl RiscvCommunicationInterface {
 
         self.write_dm_register(sbcs)?;
 
-        self.write_dm_register(Sbaddress0(address))?;
+        // The read is triggered by the write to sbaddress0, so it has to be written last.
+        if let Some(address_high) = address_high {
+            self.write_dm_register(Sbaddress1(address_high))?;
+        }
+        self.write_dm_register(Sbaddress0(address_low))?;
         let data = self.read_large_dtm_register::<V, Sbdata>()?;
 
         // Check that the read was succesful
         let sbcs = self.read_dm_register::<Sbcs>()?;
 
-        if sbcs.sberror() != 0 {
-            Err(RiscvError::SystemBusAccess)
-        } else {
-            Ok(data)
-        }
+        self.check_sysbus_error(sbcs)?;
+
+        Ok(data)
     }
 
     /// Perform multiple reads from consecutive memory locations
@@ -616,9 +854,11 @@ impl RiscvCommunicationInterface {
     /// Only reads up to a width of 32 bits are currently supported.
     fn perform_memory_read_multiple_sysbus<V: RiscvValue32>(
         &mut self,
-        address: u32,
+        address: u64,
         data: &mut [V],
     ) -> Result<(), RiscvError> {
+        let (address_high, address_low) = self.sysbus_address(address)?;
+
         let mut sbcs = Sbcs(0);
 
         sbcs.set_sbaccess(V::WIDTH as u32);
@@ -630,7 +870,10 @@ impl RiscvCommunicationInterface {
 
         self.schedule_write_dm_register(sbcs)?;
 
-        self.schedule_write_dm_register(Sbaddress0(address))?;
+        if let Some(address_high) = address_high {
+            self.schedule_write_dm_register(Sbaddress1(address_high))?;
+        }
+        self.schedule_write_dm_register(Sbaddress0(address_low))?;
 
         let data_len = data.len();
 
@@ -664,32 +907,29 @@ impl RiscvCommunicationInterface {
             _ => panic!("Internal error occurred."),
         };
 
-        let sbcs = Sbcs(sbcs);
-
-        if sbcs.sberror() != 0 {
-            Err(RiscvError::SystemBusAccess)
-        } else {
-            Ok(())
-        }
+        self.check_sysbus_error(Sbcs(sbcs))
     }
 
     /// Perform memory read from a single location using the program buffer.
     /// Only reads up to a width of 32 bits are currently supported.
     fn perform_memory_read_progbuf<V: RiscvValue32>(
         &mut self,
-        address: u32,
+        address: u64,
     ) -> Result<V, RiscvError> {
         // assemble
         //  lb s1, 0(s0)
 
+        self.check_progbuf_address(address)?;
+        let xlen = self.xlen()?;
+
         // Backup register s0
-        let s0 = self.abstract_cmd_register_read(&register::S0)?;
+        let s0 = self.abstract_cmd_register_read_xlen(&register::S0)?;
 
         let lw_command: u32 = assembly::lw(0, 8, V::WIDTH as u8, 8);
 
         self.setup_program_buffer(&[lw_command])?;
 
-        self.write_dm_register(Data0(address))?;
+        self.write_arg0_xlen(xlen, address)?;
 
         // Write s0, then execute program buffer
         let mut command = AccessRegisterCommand(0);
@@ -697,8 +937,8 @@ impl RiscvCommunicationInterface {
         command.set_transfer(true);
         command.set_write(true);
 
-        // registers are 32 bit, so we have size 2 here
-        command.set_aarsize(RiscvBusAccess::A32);
+        // the whole register has to be written
+        command.set_aarsize(xlen);
         command.set_postexec(true);
 
         // register s0, ie. 0x1008
@@ -715,22 +955,35 @@ impl RiscvCommunicationInterface {
         }
 
         // Read back s0
-        let value = self.abstract_cmd_register_read(&register::S0)?;
+        let value: u32 = self.abstract_cmd_register_read(&register::S0)?;
 
         // Restore s0 register
-        self.abstract_cmd_register_write(&register::S0, s0)?;
+        self.abstract_cmd_register_write_xlen(&register::S0, s0)?;
 
         Ok(V::from_register_value(value))
     }
 
     fn perform_memory_read_multiple_progbuf<V: RiscvValue32>(
         &mut self,
-        address: u32,
+        address: u64,
         data: &mut [V],
     ) -> Result<(), RiscvError> {
+        if self.state.supports_autoexec && data.len() > 1 {
+            match self.perform_memory_read_multiple_progbuf_autoexec(address, data) {
+                Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::Busy)) => {
+                    log::warn!("The hart is too slow to read memory using autoexec, disabling it");
+                    self.state.supports_autoexec = false;
+                }
+                other => return other,
+            }
+        }
+
+        self.check_progbuf_address(address)?;
+        let xlen = self.xlen()?;
+
         // Backup registers s0 and s1
-        let s0 = self.abstract_cmd_register_read(&register::S0)?;
-        let s1 = self.abstract_cmd_register_read(&register::S1)?;
+        let s0 = self.abstract_cmd_register_read_xlen(&register::S0)?;
+        let s1 = self.abstract_cmd_register_read_xlen(&register::S1)?;
 
         // Load a word from address in register 8 (S0), with offset 0, into register 9 (S9)
         let lw_command: u32 = assembly::lw(0, 8, V::WIDTH as u8, 9);
@@ -740,7 +993,7 @@ impl RiscvCommunicationInterface {
             assembly::addi(8, 8, V::WIDTH.byte_width() as u16),
         ])?;
 
-        self.write_dm_register(Data0(address))?;
+        self.write_arg0_xlen(xlen, address)?;
 
         // Write s0, then execute program buffer
         let mut command = AccessRegisterCommand(0);
@@ -748,8 +1001,8 @@ impl RiscvCommunicationInterface {
         command.set_transfer(true);
         command.set_write(true);
 
-        // registers are 32 bit, so we have size 2 here
-        command.set_aarsize(RiscvBusAccess::A32);
+        // the whole register has to be written
+        command.set_aarsize(xlen);
         command.set_postexec(true);
 
         // register s0, ie. 0x1008
@@ -765,7 +1018,7 @@ impl RiscvCommunicationInterface {
             command.set_transfer(true);
             command.set_write(false);
 
-            // registers are 32 bit, so we have size 2 here
+            // only the lower 32 bits of the register are needed
             command.set_aarsize(RiscvBusAccess::A32);
             command.set_postexec(true);
 
@@ -779,7 +1032,7 @@ impl RiscvCommunicationInterface {
             *word = V::from_register_value(value.0);
         }
 
-        let last_value = self.abstract_cmd_register_read(&register::S1)?;
+        let last_value: u32 = self.abstract_cmd_register_read(&register::S1)?;
 
         data[data.len() - 1] = V::from_register_value(last_value);
 
@@ -791,18 +1044,153 @@ impl RiscvCommunicationInterface {
             ));
         }
 
-        self.abstract_cmd_register_write(&register::S0, s0)?;
-        self.abstract_cmd_register_write(&register::S1, s1)?;
+        self.abstract_cmd_register_write_xlen(&register::S0, s0)?;
+        self.abstract_cmd_register_write_xlen(&register::S1, s1)?;
 
         Ok(())
     }
 
+    /// Perform multiple reads from consecutive memory locations using the program buffer,
+    /// transferring all values in a single batch.
+    ///
+    /// The program buffer loads a value into `s1`, and increments the address in `s0`.
+    /// With `abstractauto`, each read of `data0` transfers the next value from `s1` to `data0`,
+    /// and executes the program buffer again, without writing the command for every value.
+    fn perform_memory_read_multiple_progbuf_autoexec<V: RiscvValue32>(
+        &mut self,
+        address: u64,
+        data: &mut [V],
+    ) -> Result<(), RiscvError> {
+        self.check_progbuf_address(address)?;
+        let xlen = self.xlen()?;
+
+        // Backup registers s0 and s1
+        let s0 = self.abstract_cmd_register_read_xlen(&register::S0)?;
+        let s1 = self.abstract_cmd_register_read_xlen(&register::S1)?;
+
+        // Load a word from address in register 8 (S0), with offset 0, into register 9 (S9)
+        let lw_command: u32 = assembly::lw(0, 8, V::WIDTH as u8, 9);
+
+        self.setup_program_buffer(&[
+            lw_command,
+            assembly::addi(8, 8, V::WIDTH.byte_width() as u16),
+        ])?;
+
+        self.write_arg0_xlen(xlen, address)?;
+
+        // Write s0, then execute program buffer to load the first value
+        let mut command = AccessRegisterCommand(0);
+        command.set_cmd_type(0);
+        command.set_transfer(true);
+        command.set_write(true);
+        command.set_aarsize(xlen);
+        command.set_postexec(true);
+        command.set_regno((register::S0).id.0 as u32);
+
+        self.schedule_write_dm_register(command)?;
+
+        // Read s1 into data 0, then execute program buffer to load the next value
+        let mut command = AccessRegisterCommand(0);
+        command.set_cmd_type(0);
+        command.set_transfer(true);
+        command.set_write(false);
+        command.set_aarsize(RiscvBusAccess::A32);
+        command.set_postexec(true);
+        command.set_regno((register::S1).id.0 as u32);
+
+        self.schedule_write_dm_register(command)?;
+
+        let mut abstractauto = Abstractauto(0);
+        abstractauto.set_autoexecdata(1);
+        self.schedule_write_dm_register(abstractauto)?;
+
+        let data_len = data.len();
+
+        let mut read_results = Vec::with_capacity(data_len - 1);
+        for _ in 0..data_len - 2 {
+            read_results.push(self.schedule_read_dm_register::<Data0>()?);
+        }
+
+        // The second to last value is read without loading another one,
+        // the last value is still in s1.
+        self.schedule_write_dm_register(Abstractauto(0))?;
+        read_results.push(self.schedule_read_dm_register::<Data0>()?);
+
+        let abstractcs_result = self.schedule_read_dm_register::<Abstractcs>()?;
+
+        let result = self.execute()?;
+
+        let abstractcs = match result[abstractcs_result] {
+            CommandResult::U32(abstractcs) => Abstractcs(abstractcs),
+            _ => panic!("Internal error occurred."),
+        };
+
+        self.check_autoexec_result(abstractcs, s0, s1)?;
+
+        for (out_index, &idx) in read_results.iter().enumerate() {
+            data[out_index] = match result[idx] {
+                CommandResult::U32(data) => V::from_register_value(data),
+                _ => panic!("Internal error occurred."),
+            };
+        }
+
+        let last_value: u32 = self.abstract_cmd_register_read(&register::S1)?;
+        data[data_len - 1] = V::from_register_value(last_value);
+
+        self.abstract_cmd_register_write_xlen(&register::S0, s0)?;
+        self.abstract_cmd_register_write_xlen(&register::S1, s1)?;
+
+        Ok(())
+    }
+
+    /// Check `abstractcs` after a batch of commands executed using `abstractauto`.
+    ///
+    /// On an error, `cmderr` is cleared and `s0` and `s1` are restored, so that the
+    /// access can be retried without `abstractauto`.
+    fn check_autoexec_result(
+        &mut self,
+        abstractcs: Abstractcs,
+        s0: u64,
+        s1: u64,
+    ) -> Result<(), RiscvError> {
+        if abstractcs.cmderr() == 0 {
+            return Ok(());
+        }
+
+        let error = AbstractCommandErrorKind::parse(abstractcs.cmderr() as u8);
+
+        log::debug!(
+            "Executing the program buffer using autoexec failed: {:?} ({:x?})",
+            error,
+            abstractcs
+        );
+
+        // Wait for a command which is still executing, before restoring the registers
+        let start_time = Instant::now();
+        while self.read_dm_register::<Abstractcs>()?.busy() {
+            if start_time.elapsed() > RISCV_TIMEOUT {
+                return Err(RiscvError::Timeout);
+            }
+        }
+
+        let mut abstractcs_clear = Abstractcs(0);
+        abstractcs_clear.set_cmderr(0x7);
+        self.write_dm_register(abstractcs_clear)?;
+
+        self.abstract_cmd_register_write_xlen(&register::S0, s0)?;
+        self.abstract_cmd_register_write_xlen(&register::S1, s1)?;
+
+        Err(RiscvError::AbstractCommand(error))
+    }
+
     /// Memory write using system bus
     fn perform_memory_write_sysbus<V: RiscvValue>(
         &mut self,
-        address: u32,
+        address: u64,
         data: &[V],
     ) -> Result<(), RiscvError> {
+        let (address_high, address_low) = self.sysbus_address(address)?;
+
         let mut sbcs = Sbcs(0);
 
         // Set correct access width
@@ -811,7 +1199,10 @@ impl RiscvCommunicationInterface {
 
         self.schedule_write_dm_register(sbcs)?;
 
-        self.schedule_write_dm_register(Sbaddress0(address))?;
+        if let Some(address_high) = address_high {
+            self.schedule_write_dm_register(Sbaddress1(address_high))?;
+        }
+        self.schedule_write_dm_register(Sbaddress0(address_low))?;
 
         for value in data {
             self.schedule_write_large_dtm_register::<V, Sbdata>(*value)?;
@@ -828,20 +1219,14 @@ impl RiscvCommunicationInterface {
             _ => panic!("Internal error occurred."),
         };
 
-        let sbcs = Sbcs(sbcs);
-
-        if sbcs.sberror() != 0 {
-            Err(RiscvError::SystemBusAccess)
-        } else {
-            Ok(())
-        }
+        self.check_sysbus_error(Sbcs(sbcs))
     }
 
     /// Perform memory write to a single location using the program buffer.
     /// Only writes up to a width of 32 bits are currently supported.
     fn perform_memory_write_progbuf<V: RiscvValue32>(
         &mut self,
-        address: u32,
+        address: u64,
         data: V,
     ) -> Result<(), RiscvError> {
         log::debug!(
@@ -850,19 +1235,22 @@ impl RiscvCommunicationInterface {
             data
         );
 
+        self.check_progbuf_address(address)?;
+        let xlen = self.xlen()?;
+
         // Backup registers s0 and s1
-        let s0 = self.abstract_cmd_register_read(&register::S0)?;
-        let s1 = self.abstract_cmd_register_read(&register::S1)?;
+        let s0 = self.abstract_cmd_register_read_xlen(&register::S0)?;
+        let s1 = self.abstract_cmd_register_read_xlen(&register::S1)?;
 
         let sw_command = assembly::sw(0, 8, V::WIDTH as u32, 9);
 
         self.setup_program_buffer(&[sw_command])?;
 
         // write address into s0
-        self.abstract_cmd_register_write(&register::S0, address)?;
+        self.abstract_cmd_register_write_xlen(&register::S0, address)?;
 
         // write data into data 0
-        self.write_dm_register(Data0(data.into()))?;
+        self.write_arg0_xlen(xlen, Into::<u32>::into(data).into())?;
 
         // Write s1, then execute program buffer
         let mut command = AccessRegisterCommand(0);
@@ -870,8 +1258,8 @@ impl RiscvCommunicationInterface {
         command.set_transfer(true);
         command.set_write(true);
 
-        // registers are 32 bit, so we have size 2 here
-        command.set_aarsize(RiscvBusAccess::A32);
+        // the whole register has to be written
+        command.set_aarsize(xlen);
         command.set_postexec(true);
 
         // register s1, ie. 0x1009
@@ -895,8 +1283,8 @@ impl RiscvCommunicationInterface {
 
         // Restore register s0 and s1
 
-        self.abstract_cmd_register_write(&register::S0, s0)?;
-        self.abstract_cmd_register_write(&register::S1, s1)?;
+        self.abstract_cmd_register_write_xlen(&register::S0, s0)?;
+        self.abstract_cmd_register_write_xlen(&register::S1, s1)?;
 
         Ok(())
     }
@@ -905,11 +1293,24 @@ impl RiscvCommunicationInterface {
     /// Only writes up to a width of 32 bits are currently supported.
     fn perform_memory_write_multiple_progbuf<V: RiscvValue32>(
         &mut self,
-        address: u32,
+        address: u64,
         data: &[V],
     ) -> Result<(), RiscvError> {
-        let s0 = self.abstract_cmd_register_read(&register::S0)?;
-        let s1 = self.abstract_cmd_register_read(&register::S1)?;
+        if self.state.supports_autoexec && data.len() > 1 {
+            match self.perform_memory_write_multiple_progbuf_autoexec(address, data) {
+                Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::Busy)) => {
+                    log::warn!("The hart is too slow to write memory using autoexec, disabling it");
+                    self.state.supports_autoexec = false;
+                }
+                other => return other,
+            }
+        }
+
+        self.check_progbuf_address(address)?;
+        let xlen = self.xlen()?;
+
+        let s0 = self.abstract_cmd_register_read_xlen(&register::S0)?;
+        let s1 = self.abstract_cmd_register_read_xlen(&register::S1)?;
 
         // Setup program buffer for multiple writes
         // Store value from register s9 into memory,
@@ -922,7 +1323,12 @@ impl RiscvCommunicationInterface {
         ])?;
 
         // write address into s0
-        self.abstract_cmd_register_write(&register::S0, address)?;
+        self.abstract_cmd_register_write_xlen(&register::S0, address)?;
+
+        // The upper bits of the values are not changed by the loop below.
+        if xlen == RiscvBusAccess::A64 {
+            self.write_dm_register(Data1(0))?;
+        }
 
         for value in data {
             // write address into data 0
@@ -934,8 +1340,8 @@ impl RiscvCommunicationInterface {
             command.set_transfer(true);
             command.set_write(true);
 
-            // registers are 32 bit, so we have size 2 here
-            command.set_aarsize(RiscvBusAccess::A32);
+            // the whole register has to be written
+            command.set_aarsize(xlen);
             command.set_postexec(true);
 
             // register s1
@@ -964,8 +1370,82 @@ impl RiscvCommunicationInterface {
 
         // Restore register s0 and s1
 
-        self.abstract_cmd_register_write(&register::S0, s0)?;
-        self.abstract_cmd_register_write(&register::S1, s1)?;
+        self.abstract_cmd_register_write_xlen(&register::S0, s0)?;
+        self.abstract_cmd_register_write_xlen(&register::S1, s1)?;
+
+        Ok(())
+    }
+
+    /// Perform multiple memory writes to consecutive locations using the program buffer,
+    /// transferring all values in a single batch.
+    ///
+    /// With `abstractauto`, each write of `data0` transfers the value to `s1` and executes the
+    /// program buffer, which stores it and increments the address in `s0`.
+    fn perform_memory_write_multiple_progbuf_autoexec<V: RiscvValue32>(
+        &mut self,
+        address: u64,
+        data: &[V],
+    ) -> Result<(), RiscvError> {
+        self.check_progbuf_address(address)?;
+        let xlen = self.xlen()?;
+
+        let s0 = self.abstract_cmd_register_read_xlen(&register::S0)?;
+        let s1 = self.abstract_cmd_register_read_xlen(&register::S1)?;
+
+        // Store value from register s9 into memory,
+        // then increase the address for next write.
+        let sw_command = assembly::sw(0, 8, V::WIDTH as u32, 9);
+
+        self.setup_program_buffer(&[
+            sw_command,
+            assembly::addi(8, 8, V::WIDTH.byte_width() as u16),
+        ])?;
+
+        // write address into s0
+        self.abstract_cmd_register_write_xlen(&register::S0, address)?;
+
+        // The upper bits of the values are not changed below.
+        if xlen == RiscvBusAccess::A64 {
+            self.schedule_write_dm_register(Data1(0))?;
+        }
+
+        // Write the first value into s1, then execute program buffer
+        self.schedule_write_dm_register(Data0(data[0].into()))?;
+
+        let mut command = AccessRegisterCommand(0);
+        command.set_cmd_type(0);
+        command.set_transfer(true);
+        command.set_write(true);
+        command.set_aarsize(xlen);
+        command.set_postexec(true);
+        command.set_regno((register::S1).id.0 as u32);
+
+        self.schedule_write_dm_register(command)?;
+
+        // Every following write to data 0 repeats the command
+        let mut abstractauto = Abstractauto(0);
+        abstractauto.set_autoexecdata(1);
+        self.schedule_write_dm_register(abstractauto)?;
+
+        for value in &data[1..] {
+            self.schedule_write_dm_register(Data0((*value).into()))?;
+        }
+
+        self.schedule_write_dm_register(Abstractauto(0))?;
+
+        let abstractcs_result = self.schedule_read_dm_register::<Abstractcs>()?;
+
+        let result = self.execute()?;
+
+        let abstractcs = match result[abstractcs_result] {
+            CommandResult::U32(abstractcs) => Abstractcs(abstractcs),
+            _ => panic!("Internal error occurred."),
+        };
+
+        self.check_autoexec_result(abstractcs, s0, s1)?;
+
+        self.abstract_cmd_register_write_xlen(&register::S0, s0)?;
+        self.abstract_cmd_register_write_xlen(&register::S1, s1)?;
 
         Ok(())
     }
@@ -976,11 +1456,10 @@ impl RiscvCommunicationInterface {
         // resumereq    = 0
         // ackhavereset = 0
 
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.dmcontrol();
         dmcontrol.set_haltreq(false);
         dmcontrol.set_resumereq(false);
         dmcontrol.set_ackhavereset(false);
-        dmcontrol.set_dmactive(true);
         self.write_dm_register(dmcontrol)?;
 
         // read abstractcs to see its state
@@ -1057,11 +1536,93 @@ impl RiscvCommunicationInterface {
         entry.unset(rw);
     }
 
+    /// Determine the XLEN of the hart, which is the width of its registers.
+    ///
+    /// This uses the method described in the RISC-V debug specification 0.13, appendix A.3:
+    /// An abstract command reading a register with a size larger than XLEN fails.
+    /// The hart has to be halted.
+    pub(crate) fn xlen(&mut self) -> Result<RiscvBusAccess, RiscvError> {
+        if let Some(xlen) = self.state.xlen.get(&self.state.selected_hart) {
+            return Ok(*xlen);
+        }
+
+        // A 64-bit access needs at least two data registers.
+        let xlen = if self.state.data_register_count < 2 {
+            RiscvBusAccess::A32
+        } else {
+            let mut command = AccessRegisterCommand(0);
+            command.set_cmd_type(0);
+            command.set_transfer(true);
+            command.set_aarsize(RiscvBusAccess::A64);
+            command.set_regno((register::S0).id.0 as u32);
+
+            match self.execute_abstract_command(command.0) {
+                Ok(()) => RiscvBusAccess::A64,
+                Err(RiscvError::AbstractCommand(
+                    AbstractCommandErrorKind::NotSupported | AbstractCommandErrorKind::Exception,
+                )) => RiscvBusAccess::A32,
+                Err(e) => return Err(e),
+            }
+        };
+
+        log::debug!(
+            "XLEN of hart {}: {} bits",
+            self.state.selected_hart,
+            xlen.byte_width() * 8
+        );
+
+        self.state.xlen.insert(self.state.selected_hart, xlen);
+
+        Ok(xlen)
+    }
+
+    /// Check that an address can be accessed by the hart using the program buffer.
+    fn check_progbuf_address(&mut self, address: u64) -> Result<(), RiscvError> {
+        if address > u32::MAX as u64 && self.xlen()? != RiscvBusAccess::A64 {
+            return Err(RiscvError::AddressOutOfRange(address));
+        }
+
+        Ok(())
+    }
+
+    /// Write a value with the given XLEN into the data registers used for abstract commands.
+    fn write_arg0_xlen(&mut self, xlen: RiscvBusAccess, value: u64) -> Result<(), RiscvError> {
+        match xlen {
+            RiscvBusAccess::A64 => self.write_large_dtm_register::<u64, Arg0>(value),
+            _ => self.write_large_dtm_register::<u32, Arg0>(value as u32),
+        }
+    }
+
+    /// Read a core register using an abstract command, with the full XLEN of the hart.
+    pub(crate) fn abstract_cmd_register_read_xlen(
+        &mut self,
+        regno: impl Into<RegisterId>,
+    ) -> Result<u64, RiscvError> {
+        match self.xlen()? {
+            RiscvBusAccess::A64 => self.abstract_cmd_register_read::<u64>(regno),
+            _ => self.abstract_cmd_register_read::<u32>(regno).map(u64::from),
+        }
+    }
+
+    /// Write a core register using an abstract command, with the full XLEN of the hart.
+    ///
+    /// On RV32 harts, only the lower 32 bits of the value are written.
+    pub(crate) fn abstract_cmd_register_write_xlen(
+        &mut self,
+        regno: impl Into<RegisterId>,
+        value: u64,
+    ) -> Result<(), RiscvError> {
+        match self.xlen()? {
+            RiscvBusAccess::A64 => self.abstract_cmd_register_write(regno, value),
+            _ => self.abstract_cmd_register_write(regno, value as u32),
+        }
+    }
+
     // Read a core register using an abstract command
-    pub(crate) fn abstract_cmd_register_read(
+    pub(crate) fn abstract_cmd_register_read<V: RiscvValue>(
         &mut self,
         regno: impl Into<RegisterId>,
-    ) -> Result<u32, RiscvError> {
+    ) -> Result<V, RiscvError> {
         let regno = regno.into();
 
         // Check if the register was already tried via abstract cmd
@@ -1075,7 +1636,7 @@ impl RiscvCommunicationInterface {
         let mut command = AccessRegisterCommand(0);
         command.set_cmd_type(0);
         command.set_transfer(true);
-        command.set_aarsize(RiscvBusAccess::A32);
+        command.set_aarsize(V::WIDTH);
 
         command.set_regno(regno.0 as u32);
 
@@ -1092,9 +1653,7 @@ impl RiscvCommunicationInterface {
             Err(e) => return Err(e),
         }
 
-        let register_value: Data0 = self.read_dm_register()?;
-
-        Ok(register_value.into())
+        self.read_large_dtm_register::<V, Arg0>()
     }
 
     pub(crate) fn abstract_cmd_register_write<V: RiscvValue>(
@@ -1137,10 +1696,10 @@ impl RiscvCommunicationInterface {
     }
 
     /// Read the CSR progbuf register.
-    pub fn read_csr_progbuf(&mut self, address: u16) -> Result<u32, RiscvError> {
+    pub fn read_csr_progbuf(&mut self, address: u16) -> Result<u64, RiscvError> {
         log::debug!("Reading CSR {:#04x}", address);
 
-        let s0 = self.abstract_cmd_register_read(&register::S0)?;
+        let s0 = self.abstract_cmd_register_read_xlen(&register::S0)?;
 
         // Read csr value into register 8 (s0)
         let csrr_cmd = assembly::csrr(8, address);
@@ -1154,23 +1713,23 @@ impl RiscvCommunicationInterface {
         self.execute_abstract_command(postexec_cmd.0)?;
 
         // read the s0 value
-        let reg_value = self.abstract_cmd_register_read(&register::S0)?;
+        let reg_value = self.abstract_cmd_register_read_xlen(&register::S0)?;
 
         // restore original value in s0
-        self.abstract_cmd_register_write(&register::S0, s0)?;
+        self.abstract_cmd_register_write_xlen(&register::S0, s0)?;
 
         Ok(reg_value)
     }
 
     /// Write the CSR progbuf register.
-    pub fn write_csr_progbuf(&mut self, address: u16, value: u32) -> Result<(), RiscvError> {
+    pub fn write_csr_progbuf(&mut self, address: u16, value: u64) -> Result<(), RiscvError> {
         log::debug!("Writing CSR {:#04x}={}", address, value);
 
         // Backup register s0
-        let s0 = self.abstract_cmd_register_read(&register::S0)?;
+        let s0 = self.abstract_cmd_register_read_xlen(&register::S0)?;
 
         // Write value into s0
-        self.abstract_cmd_register_write(&register::S0, value)?;
+        self.abstract_cmd_register_write_xlen(&register::S0, value)?;
 
         // Built the CSRW command to write into the program buffer
         let csrw_cmd = assembly::csrw(address, 8);
@@ -1184,11 +1743,154 @@ impl RiscvCommunicationInterface {
 
         // command: transfer, regno = 0x1008
         // restore original value in s0
-        self.abstract_cmd_register_write(&register::S0, s0)?;
+        self.abstract_cmd_register_write_xlen(&register::S0, s0)?;
 
         Ok(())
     }
 
+    /// Read a floating point register using the program buffer.
+    ///
+    /// The register is moved to `s0`, so this only works if FLEN is not larger than XLEN.
+    pub fn read_fp_register_progbuf(&mut self, index: u8, double: bool) -> Result<u64, RiscvError> {
+        log::debug!("Reading FP register f{}", index);
+
+        let s0 = self.abstract_cmd_register_read_xlen(&register::S0)?;
+
+        self.setup_program_buffer(&[assembly::fmv_x_f(8, index, double)])?;
+        self.execute_program_buffer()?;
+
+        let value = self.abstract_cmd_register_read_xlen(&register::S0)?;
+
+        self.abstract_cmd_register_write_xlen(&register::S0, s0)?;
+
+        Ok(value)
+    }
+
+    /// Write a floating point register using the program buffer.
+    ///
+    /// The value is moved from `s0`, so this only works if FLEN is not larger than XLEN.
+    pub fn write_fp_register_progbuf(
+        &mut self,
+        index: u8,
+        double: bool,
+        value: u64,
+    ) -> Result<(), RiscvError> {
+        log::debug!("Writing FP register f{}={:#x}", index, value);
+
+        let s0 = self.abstract_cmd_register_read_xlen(&register::S0)?;
+
+        self.abstract_cmd_register_write_xlen(&register::S0, value)?;
+
+        self.setup_program_buffer(&[assembly::fmv_f_x(index, 8, double)])?;
+        self.execute_program_buffer()?;
+
+        self.abstract_cmd_register_write_xlen(&register::S0, s0)?;
+
+        Ok(())
+    }
+
+    /// Read the vector register `index`, which is `vlenb` bytes wide, using the program buffer.
+    ///
+    /// The register is read one element at a time: the first element is moved to `s0`, and the
+    /// register is slid down by one element, inserting the element at the end again. Once all
+    /// elements were read, the register has its original value again.
+    ///
+    /// The vector extension has to be enabled in `mstatus`.
+    pub fn read_vector_register(&mut self, index: u8, vlenb: usize) -> Result<Vec<u8>, RiscvError> {
+        log::debug!("Reading vector register v{}", index);
+
+        let mut value = Vec::with_capacity(vlenb);
+
+        self.access_vector_register(|interface| {
+            interface.setup_program_buffer(&[
+                assembly::vmv_x_s(8, index),
+                assembly::vslide1down_vx(index, index, 8),
+            ])?;
+
+            while value.len() < vlenb {
+                interface.execute_program_buffer()?;
+
+                let element = interface.abstract_cmd_register_read::<u32>(&register::S0)?;
+                value.extend_from_slice(&element.to_le_bytes());
+            }
+
+            Ok(())
+        })?;
+
+        Ok(value)
+    }
+
+    /// Write the vector register `index` using the program buffer.
+    ///
+    /// The value has to be exactly VLEN bits wide. See [`Self::read_vector_register`].
+    pub fn write_vector_register(&mut self, index: u8, value: &[u8]) -> Result<(), RiscvError> {
+        log::debug!("Writing vector register v{}", index);
+
+        self.access_vector_register(|interface| {
+            interface.setup_program_buffer(&[assembly::vslide1down_vx(index, index, 8)])?;
+
+            // Inserting all elements at the end, starting with the first one,
+            // leaves them at the right position in the register.
+            for element in value.chunks(4) {
+                let mut bytes = [0; 4];
+                bytes[..element.len()].copy_from_slice(element);
+
+                interface.abstract_cmd_register_write(&register::S0, u32::from_le_bytes(bytes))?;
+                interface.execute_program_buffer()?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Configure the vector unit to use all elements of a vector register, with 32 bit wide
+    /// elements, and run `access`. `vl`, `vtype`, `s0` and `s1` are restored afterwards.
+    fn access_vector_register(
+        &mut self,
+        access: impl FnOnce(&mut Self) -> Result<(), RiscvError>,
+    ) -> Result<(), RiscvError> {
+        // Vector length and vector type CSRs
+        const VL: u16 = 0xc20;
+        const VTYPE: u16 = 0xc21;
+
+        // The vector type `e32, m1, ta, ma`
+        const VTYPE_E32: u16 = 0b1101_0000;
+
+        let s0 = self.abstract_cmd_register_read_xlen(&register::S0)?;
+        let s1 = self.abstract_cmd_register_read_xlen(&register::S1)?;
+
+        self.setup_program_buffer(&[assembly::csrr(8, VL), assembly::csrr(9, VTYPE)])?;
+        self.execute_program_buffer()?;
+
+        let vl = self.abstract_cmd_register_read_xlen(&register::S0)?;
+        let vtype = self.abstract_cmd_register_read_xlen(&register::S1)?;
+
+        // With the source register x0, vl is set to the maximum number of elements.
+        self.setup_program_buffer(&[assembly::vsetvli(8, 0, VTYPE_E32)])?;
+        self.execute_program_buffer()?;
+
+        let result = access(self);
+
+        self.abstract_cmd_register_write_xlen(&register::S0, vl)?;
+        self.abstract_cmd_register_write_xlen(&register::S1, vtype)?;
+
+        self.setup_program_buffer(&[assembly::vsetvl(0, 8, 9)])?;
+        self.execute_program_buffer()?;
+
+        self.abstract_cmd_register_write_xlen(&register::S0, s0)?;
+        self.abstract_cmd_register_write_xlen(&register::S1, s1)?;
+
+        result
+    }
+
+    /// Execute the program buffer, without transferring a register.
+    fn execute_program_buffer(&mut self) -> Result<(), RiscvError> {
+        let mut postexec_cmd = AccessRegisterCommand(0);
+        postexec_cmd.set_postexec(true);
+
+        self.execute_abstract_command(postexec_cmd.0)
+    }
+
     fn read_large_dtm_register<V, R>(&mut self) -> Result<V, RiscvError>
     where
         V: RiscvValue,
@@ -1205,7 +1907,7 @@ impl RiscvCommunicationInterface {
         V::write_to_register::<R>(self, value)
     }
 
-    fn read_word<V: RiscvValue32>(&mut self, address: u32) -> Result<V, crate::Error> {
+    fn read_word<V: RiscvValue32>(&mut self, address: u64) -> Result<V, crate::Error> {
         let result = match self.state.memory_access_method(V::WIDTH) {
             MemoryAccessMethod::ProgramBuffer => self.perform_memory_read_progbuf(address)?,
             MemoryAccessMethod::SystemBus => self.perform_memory_read_sysbus(address)?,
@@ -1219,12 +1921,12 @@ impl RiscvCommunicationInterface {
 
     fn read_multiple<V: RiscvValue32>(
         &mut self,
-        address: u32,
+        address: u64,
         data: &mut [V],
     ) -> Result<(), crate::Error> {
-        log::debug!("read_32 from {:#08x}", address);
+        log::debug!("read_multiple from {:#08x}", address);
 
-        match self.state.memory_access_method(RiscvBusAccess::A32) {
+        match self.state.memory_access_method(V::WIDTH) {
             MemoryAccessMethod::ProgramBuffer => {
                 self.perform_memory_read_multiple_progbuf(address, data)?;
             }
@@ -1239,7 +1941,7 @@ impl RiscvCommunicationInterface {
         Ok(())
     }
 
-    fn write_word<V: RiscvValue32>(&mut self, address: u32, data: V) -> Result<(), crate::Error> {
+    fn write_word<V: RiscvValue32>(&mut self, address: u64, data: V) -> Result<(), crate::Error> {
         match self.state.memory_access_method(V::WIDTH) {
             MemoryAccessMethod::ProgramBuffer => {
                 self.perform_memory_write_progbuf(address, data)?
@@ -1255,7 +1957,7 @@ impl RiscvCommunicationInterface {
 
     fn write_multiple<V: RiscvValue32>(
         &mut self,
-        address: u32,
+        address: u64,
         data: &[V],
     ) -> Result<(), crate::Error> {
         match self.state.memory_access_method(V::WIDTH) {
@@ -1704,7 +2406,6 @@ impl MemoryInterface for RiscvCommunicationInterface {
     }
 
     fn read_word_64(&mut self, address: u64) -> Result<u64, crate::error::Error> {
-        let address = valid_32_address(address)?;
         let mut ret = self.read_word::<u32>(address)? as u64;
         ret |= (self.read_word::<u32>(address + 4)? as u64) << 32;
 
@@ -1712,43 +2413,37 @@ impl MemoryInterface for RiscvCommunicationInterface {
     }
 
     fn read_word_32(&mut self, address: u64) -> Result<u32, crate::Error> {
-        let address = valid_32_address(address)?;
         self.read_word(address)
     }
 
     fn read_word_8(&mut self, address: u64) -> Result<u8, crate::Error> {
-        let address = valid_32_address(address)?;
         log::debug!("read_word_8 from {:#08x}", address);
         self.read_word(address)
     }
 
     fn read_64(&mut self, address: u64, data: &mut [u64]) -> Result<(), crate::error::Error> {
-        let address = valid_32_address(address)?;
         log::debug!("read_64 from {:#08x}", address);
 
         for (i, d) in data.iter_mut().enumerate() {
-            *d = self.read_word_64((address + (i as u32 * 8)).into())?;
+            *d = self.read_word_64(address + (i as u64 * 8))?;
         }
 
         Ok(())
     }
 
     fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), crate::Error> {
-        let address = valid_32_address(address)?;
         log::debug!("read_32 from {:#08x}", address);
         self.read_multiple(address, data)
     }
 
     /// Read 8-bit values from target memory.
     fn read_8(&mut self, address: u64, data: &mut [u8]) -> Result<(), crate::Error> {
-        let address = valid_32_address(address)?;
         log::debug!("read_8 from {:#08x}", address);
 
         self.read_multiple(address, data)
     }
 
     fn write_word_64(&mut self, address: u64, data: u64) -> Result<(), crate::error::Error> {
-        let address = valid_32_address(address)?;
         let low_word = data as u32;
         let high_word = (data >> 32) as u32;
 
@@ -1757,35 +2452,30 @@ impl MemoryInterface for RiscvCommunicationInterface {
     }
 
     fn write_word_32(&mut self, address: u64, data: u32) -> Result<(), crate::Error> {
-        let address = valid_32_address(address)?;
         self.write_word(address, data)
     }
 
     fn write_word_8(&mut self, address: u64, data: u8) -> Result<(), crate::Error> {
-        let address = valid_32_address(address)?;
         self.write_word(address, data)
     }
 
     fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), crate::error::Error> {
-        let address = valid_32_address(address)?;
         log::debug!("write_64 to {:#08x}", address);
 
         for (i, d) in data.iter().enumerate() {
-            self.write_word_64((address + (i as u32 * 8)).into(), *d)?;
+            self.write_word_64(address + (i as u64 * 8), *d)?;
         }
 
         Ok(())
     }
 
     fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), crate::Error> {
-        let address = valid_32_address(address)?;
         log::debug!("write_32 to {:#08x}", address);
 
         self.write_multiple(address, data)
     }
 
     fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), crate::Error> {
-        let address = valid_32_address(address)?;
         log::debug!("write_8 to {:#08x}", address);
 
         self.write_multiple(address, data)
@@ -2087,6 +2777,9 @@ impl From<u32> for AccessMemoryCommand {
     }
 }
 
+data_register! { Hawindowsel, 0x14, "hawindowsel" }
+data_register! { Hawindow, 0x15, "hawindow" }
+
 data_register! { Sbaddress0, 0x39, "sbaddress0" }
 data_register! { Sbaddress1, 0x3a, "sbaddress1" }
 data_register! { Sbaddress2, 0x3b, "sbaddress2" }
@@ -2101,3 +2794,643 @@ data_register! { Confstrptr0, 0x19, "confstrptr0" }
 data_register! { Confstrptr1, 0x1a, "confstrptr1" }
 data_register! { Confstrptr2, 0x1b, "confstrptr2" }
 data_register! { Confstrptr3, 0x1c, "confstrptr3" }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use super::{
+        assembly, Abstractauto, Abstractcs, AccessRegisterCommand, Data0, Data1, DebugRegister,
+        Dmcontrol, Dmstatus, Dtm, Hartinfo, MemoryAccessMethod, Progbuf0, Progbuf3, RiscvBusAccess,
+        RiscvCommunicationInterface, RiscvCommunicationInterfaceState, RiscvError, Sbaddress0,
+        Sbcs, Sbdata0,
+    };
+    use crate::{probe::JTAGAccess, DebugProbe, DebugProbeError, WireProtocol};
+
+    /// An access to the data registers or `abstractauto`, as seen by the debug module.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Access {
+        Read(u8, u32),
+        Write(u8, u32),
+    }
+
+    /// A debug module with a single halted hart, which executes the programs used to access memory.
+    #[derive(Debug, Default)]
+    struct DebugModule {
+        /// Support for 64-bit register accesses.
+        rv64: bool,
+        /// Commands executed because of `abstractauto` fail with `cmderr == Busy`, like on a hart
+        /// which is too slow to execute the program buffer between two accesses.
+        slow: bool,
+        data0: u32,
+        data1: u32,
+        command: u32,
+        abstractauto: u32,
+        cmderr: u32,
+        progbuf: [u32; 4],
+        s0: u64,
+        s1: u64,
+        /// The read-only fields of `sbcs` describe the system bus, it isn't available if they are 0.
+        sbcs: u32,
+        sbaddress0: u32,
+        sbdata0: u32,
+        memory: HashMap<u64, u32>,
+        accesses: Vec<Access>,
+    }
+
+    impl DebugModule {
+        fn read(&mut self, address: u8) -> u32 {
+            match address {
+                Data0::ADDRESS => {
+                    let value = self.data0;
+                    self.accesses.push(Access::Read(address, value));
+                    self.autoexec();
+                    value
+                }
+                Data1::ADDRESS => {
+                    self.accesses.push(Access::Read(address, self.data1));
+                    self.data1
+                }
+                Abstractcs::ADDRESS => 4 << 24 | self.cmderr << 8 | 2,
+                Abstractauto::ADDRESS => self.abstractauto,
+                // Version 0.13
+                Dmstatus::ADDRESS => 2,
+                Dmcontrol::ADDRESS => 1,
+                Hartinfo::ADDRESS => 0,
+                Sbcs::ADDRESS => self.sbcs,
+                Sbdata0::ADDRESS => {
+                    let value = self.sbdata0;
+                    if Sbcs(self.sbcs).sbreadondata() {
+                        self.sysbus_access(false);
+                    }
+                    value
+                }
+                _ => panic!("Unexpected read of DM register {:#04x}", address),
+            }
+        }
+
+        fn write(&mut self, address: u8, value: u32) {
+            match address {
+                Data0::ADDRESS => {
+                    self.accesses.push(Access::Write(address, value));
+                    self.data0 = value;
+                    self.autoexec();
+                }
+                Data1::ADDRESS => {
+                    self.accesses.push(Access::Write(address, value));
+                    self.data1 = value;
+                }
+                Dmcontrol::ADDRESS => {}
+                Abstractcs::ADDRESS => self.cmderr &= !(value >> 8 & 0x7),
+                AccessRegisterCommand::ADDRESS => {
+                    self.command = value;
+                    self.execute();
+                }
+                Abstractauto::ADDRESS => {
+                    self.accesses.push(Access::Write(address, value));
+                    self.abstractauto = value;
+                }
+                Progbuf0::ADDRESS..=Progbuf3::ADDRESS => {
+                    self.progbuf[(address - Progbuf0::ADDRESS) as usize] = value
+                }
+                Sbcs::ADDRESS => self.write_sbcs(Sbcs(value)),
+                Sbaddress0::ADDRESS => {
+                    self.sbaddress0 = value;
+                    if Sbcs(self.sbcs).sbreadonaddr() {
+                        self.sysbus_access(false);
+                    }
+                }
+                Sbdata0::ADDRESS => {
+                    self.sbdata0 = value;
+                    self.sysbus_access(true);
+                }
+                _ => panic!("Unexpected write of DM register {:#04x}", address),
+            }
+        }
+
+        fn write_sbcs(&mut self, value: Sbcs) {
+            let mut sbcs = Sbcs(self.sbcs);
+
+            // The error flags are cleared by writing 1 to them
+            sbcs.set_sberror(sbcs.sberror() & !value.sberror());
+            if value.sbbusyerror() {
+                sbcs.set_sbbusyerror(false);
+            }
+
+            sbcs.set_sbreadonaddr(value.sbreadonaddr());
+            sbcs.set_sbaccess(value.sbaccess());
+            sbcs.set_sbautoincrement(value.sbautoincrement());
+            sbcs.set_sbreadondata(value.sbreadondata());
+
+            self.sbcs = sbcs.into();
+        }
+
+        /// Perform a system bus access at `sbaddress0`, which fails for addresses without memory.
+        fn sysbus_access(&mut self, write: bool) {
+            let mut sbcs = Sbcs(self.sbcs);
+
+            // No accesses are possible until the error is cleared
+            if sbcs.sberror() != 0 {
+                return;
+            }
+
+            assert_eq!(
+                sbcs.sbaccess(),
+                RiscvBusAccess::A32 as u32,
+                "Only 32-bit system bus accesses are supported"
+            );
+
+            let address = self.sbaddress0 as u64;
+
+            match self.memory.get_mut(&address) {
+                Some(value) if write => *value = self.sbdata0,
+                Some(value) => self.sbdata0 = *value,
+                None => {
+                    // Bad address
+                    sbcs.set_sberror(2);
+                    self.sbcs = sbcs.into();
+                    return;
+                }
+            }
+
+            if sbcs.sbautoincrement() {
+                self.sbaddress0 += 4;
+            }
+        }
+
+        fn autoexec(&mut self) {
+            if self.abstractauto & 1 == 0 {
+                return;
+            }
+
+            if self.slow {
+                if self.cmderr == 0 {
+                    self.cmderr = 1;
+                }
+            } else {
+                self.execute();
+            }
+        }
+
+        /// Execute the access register command in `command`.
+        fn execute(&mut self) {
+            if self.cmderr != 0 {
+                return;
+            }
+
+            let command = self.command;
+            assert_eq!(
+                command >> 24,
+                0,
+                "Only access register commands are supported"
+            );
+
+            let aarsize = command >> 20 & 0x7;
+            let postexec = command >> 18 & 1 == 1;
+            let transfer = command >> 17 & 1 == 1;
+            let write = command >> 16 & 1 == 1;
+            let regno = command & 0xffff;
+
+            if transfer {
+                if aarsize > RiscvBusAccess::A64 as u32
+                    || aarsize == RiscvBusAccess::A64 as u32 && !self.rv64
+                {
+                    self.cmderr = 2;
+                    return;
+                }
+
+                let register = match regno {
+                    0x1008 => &mut self.s0,
+                    0x1009 => &mut self.s1,
+                    _ => panic!("Unexpected access of register {:#06x}", regno),
+                };
+
+                if write {
+                    *register = self.data0 as u64;
+                    if aarsize == RiscvBusAccess::A64 as u32 {
+                        *register |= (self.data1 as u64) << 32;
+                    }
+                } else {
+                    self.data0 = *register as u32;
+                    if aarsize == RiscvBusAccess::A64 as u32 {
+                        self.data1 = (*register >> 32) as u32;
+                    }
+                }
+            }
+
+            if postexec {
+                for instruction in self.progbuf {
+                    if instruction == assembly::EBREAK {
+                        break;
+                    } else if instruction == assembly::lw(0, 8, RiscvBusAccess::A32 as u8, 9) {
+                        self.s1 = self.memory[&self.s0] as u64;
+                    } else if instruction == assembly::sw(0, 8, RiscvBusAccess::A32 as u32, 9) {
+                        self.memory.insert(self.s0, self.s1 as u32);
+                    } else if instruction == assembly::addi(8, 8, 4) {
+                        self.s0 += 4;
+                    } else {
+                        panic!("Unexpected instruction {:#010x}", instruction);
+                    }
+                }
+            }
+        }
+
+        /// The accesses to the data registers and `abstractauto` since the last call.
+        fn take_accesses(&mut self) -> Vec<Access> {
+            std::mem::take(&mut self.accesses)
+        }
+    }
+
+    /// A probe connected to the DTM of a [`DebugModule`].
+    #[derive(Debug)]
+    struct MockProbe {
+        dm: Arc<Mutex<DebugModule>>,
+        idle_cycles: u8,
+        /// The result of the previous DMI access, which is returned by the following one.
+        dmi_result: u32,
+    }
+
+    impl DebugProbe for MockProbe {
+        fn new_from_selector(
+            _selector: impl Into<crate::DebugProbeSelector>,
+        ) -> Result<Box<Self>, DebugProbeError>
+        where
+            Self: Sized,
+        {
+            todo!()
+        }
+
+        fn get_name(&self) -> &str {
+            "Mock RISC-V probe"
+        }
+
+        fn speed_khz(&self) -> u32 {
+            todo!()
+        }
+
+        fn set_speed(&mut self, _speed_khz: u32) -> Result<u32, DebugProbeError> {
+            todo!()
+        }
+
+        fn attach(&mut self) -> Result<(), DebugProbeError> {
+            todo!()
+        }
+
+        fn detach(&mut self) -> Result<(), DebugProbeError> {
+            todo!()
+        }
+
+        fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+            todo!()
+        }
+
+        fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+            todo!()
+        }
+
+        fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+            todo!()
+        }
+
+        fn select_protocol(&mut self, _protocol: WireProtocol) -> Result<(), DebugProbeError> {
+            todo!()
+        }
+
+        fn active_protocol(&self) -> Option<WireProtocol> {
+            Some(WireProtocol::Jtag)
+        }
+
+        fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+            self
+        }
+    }
+
+    impl JTAGAccess for MockProbe {
+        fn read_register(&mut self, address: u32, len: u32) -> Result<Vec<u8>, DebugProbeError> {
+            assert_eq!((address, len), (0x10, 32), "Only dtmcs can be read");
+
+            // Version 0.13, with 7 address bits
+            let dtmcs: u32 = 7 << 4 | 1;
+            Ok(dtmcs.to_le_bytes().to_vec())
+        }
+
+        fn set_idle_cycles(&mut self, idle_cycles: u8) {
+            self.idle_cycles = idle_cycles;
+        }
+
+        fn get_idle_cycles(&self) -> u8 {
+            self.idle_cycles
+        }
+
+        fn set_ir_len(&mut self, _len: u32) {}
+
+        fn write_register(
+            &mut self,
+            address: u32,
+            data: &[u8],
+            len: u32,
+        ) -> Result<Vec<u8>, DebugProbeError> {
+            if address == 0x10 {
+                // dmireset
+                return Ok(vec![0; 4]);
+            }
+
+            assert_eq!(
+                (address, len),
+                (0x11, 41),
+                "Unexpected JTAG register access"
+            );
+
+            let request = data
+                .iter()
+                .rev()
+                .fold(0u128, |value, &byte| value << 8 | byte as u128);
+            let response = (self.dmi_result as u128) << 2;
+
+            let op = request & 0x3;
+            let value = (request >> 2) as u32;
+            let dm_address = (request >> 34) as u8;
+
+            let mut dm = self.dm.lock().unwrap();
+            match op {
+                0 => {}
+                1 => self.dmi_result = dm.read(dm_address),
+                2 => dm.write(dm_address, value),
+                _ => panic!("Invalid DMI operation"),
+            }
+
+            Ok(response.to_le_bytes()[..6].to_vec())
+        }
+    }
+
+    fn interface(dm: &Arc<Mutex<DebugModule>>) -> RiscvCommunicationInterface {
+        let probe = MockProbe {
+            dm: dm.clone(),
+            idle_cycles: 0,
+            dmi_result: 0,
+        };
+        let dtm = Dtm::new(Box::new(probe)).map_err(|(_, e)| e).unwrap();
+
+        let xlen = if dm.lock().unwrap().rv64 {
+            RiscvBusAccess::A64
+        } else {
+            RiscvBusAccess::A32
+        };
+
+        let mut state = RiscvCommunicationInterfaceState::new();
+        state.progbuf_size = 4;
+        state.data_register_count = 2;
+        state.supports_autoexec = true;
+        state.xlen.insert(0, xlen);
+
+        RiscvCommunicationInterface { dtm, state }
+    }
+
+    fn debug_module(rv64: bool, slow: bool) -> Arc<Mutex<DebugModule>> {
+        let (s0, s1) = if rv64 {
+            (0x1111_2222_0123_4567, 0x3333_4444_89ab_cdef)
+        } else {
+            (0x0123_4567, 0x89ab_cdef)
+        };
+
+        let mut dm = DebugModule {
+            rv64,
+            slow,
+            s0,
+            s1,
+            ..Default::default()
+        };
+
+        for index in 0..8 {
+            dm.memory
+                .insert(0x2000_0000 + index * 4, 0x1000 + index as u32);
+        }
+
+        Arc::new(Mutex::new(dm))
+    }
+
+    /// `sbcs` of a version 1 system bus with `sbasize` bit addresses, which supports 32-bit accesses.
+    fn sysbus_sbcs(sbasize: u32) -> u32 {
+        1 << 29 | sbasize << 5 | 1 << 2
+    }
+
+    const DATA0: u8 = Data0::ADDRESS;
+    const DATA1: u8 = Data1::ADDRESS;
+    const ABSTRACTAUTO: u8 = Abstractauto::ADDRESS;
+
+    #[test]
+    fn read_memory_using_autoexec() {
+        let dm = debug_module(false, false);
+        let mut interface = interface(&dm);
+
+        let mut data = [0u32; 5];
+        interface
+            .perform_memory_read_multiple_progbuf(0x2000_0000, &mut data)
+            .unwrap();
+
+        assert_eq!(data, [0x1000, 0x1001, 0x1002, 0x1003, 0x1004]);
+
+        let mut dm = dm.lock().unwrap();
+        assert_eq!(
+            dm.take_accesses(),
+            vec![
+                // Backup of s0 and s1
+                Access::Read(DATA0, 0x0123_4567),
+                Access::Read(DATA0, 0x89ab_cdef),
+                // The address is written into s0, the first value is loaded into data0
+                Access::Write(DATA0, 0x2000_0000),
+                Access::Write(ABSTRACTAUTO, 1),
+                // Each read loads the next value
+                Access::Read(DATA0, 0x1000),
+                Access::Read(DATA0, 0x1001),
+                Access::Read(DATA0, 0x1002),
+                Access::Write(ABSTRACTAUTO, 0),
+                Access::Read(DATA0, 0x1003),
+                // The last value is read from s1
+                Access::Read(DATA0, 0x1004),
+                // Restore s0 and s1
+                Access::Write(DATA0, 0x0123_4567),
+                Access::Write(DATA0, 0x89ab_cdef),
+            ]
+        );
+
+        assert_eq!((dm.s0, dm.s1), (0x0123_4567, 0x89ab_cdef));
+        assert!(interface.state.supports_autoexec);
+    }
+
+    #[test]
+    fn write_memory_using_autoexec_rv64() {
+        let dm = debug_module(true, false);
+        let mut interface = interface(&dm);
+
+        interface
+            .perform_memory_write_multiple_progbuf(0x2000_0010, &[0xa0u32, 0xa1, 0xa2, 0xa3])
+            .unwrap();
+
+        let mut dm = dm.lock().unwrap();
+        assert_eq!(
+            dm.take_accesses(),
+            vec![
+                // Backup of s0 and s1, data0 is read last
+                Access::Read(DATA1, 0x1111_2222),
+                Access::Read(DATA0, 0x0123_4567),
+                Access::Read(DATA1, 0x3333_4444),
+                Access::Read(DATA0, 0x89ab_cdef),
+                // The address is written into s0, data0 is written last
+                Access::Write(DATA1, 0),
+                Access::Write(DATA0, 0x2000_0010),
+                // The upper bits of s1 are cleared once
+                Access::Write(DATA1, 0),
+                Access::Write(DATA0, 0xa0),
+                Access::Write(ABSTRACTAUTO, 1),
+                // Each write stores the value
+                Access::Write(DATA0, 0xa1),
+                Access::Write(DATA0, 0xa2),
+                Access::Write(DATA0, 0xa3),
+                Access::Write(ABSTRACTAUTO, 0),
+                // Restore s0 and s1
+                Access::Write(DATA1, 0x1111_2222),
+                Access::Write(DATA0, 0x0123_4567),
+                Access::Write(DATA1, 0x3333_4444),
+                Access::Write(DATA0, 0x89ab_cdef),
+            ]
+        );
+
+        let memory = (0..5)
+            .map(|index| dm.memory[&(0x2000_000c + index * 4)])
+            .collect::<Vec<_>>();
+        assert_eq!(memory, [0x1003, 0xa0, 0xa1, 0xa2, 0xa3]);
+        assert_eq!(
+            (dm.s0, dm.s1),
+            (0x1111_2222_0123_4567, 0x3333_4444_89ab_cdef)
+        );
+    }
+
+    #[test]
+    fn read_memory_disables_autoexec_if_busy() {
+        let dm = debug_module(false, true);
+        let mut interface = interface(&dm);
+
+        let mut data = [0u32; 4];
+        interface
+            .perform_memory_read_multiple_progbuf(0x2000_0000, &mut data)
+            .unwrap();
+
+        assert_eq!(data, [0x1000, 0x1001, 0x1002, 0x1003]);
+        assert!(!interface.state.supports_autoexec);
+
+        {
+            let mut dm = dm.lock().unwrap();
+            let autoexec_writes = dm
+                .take_accesses()
+                .into_iter()
+                .filter(|access| matches!(access, Access::Write(ABSTRACTAUTO, _)))
+                .collect::<Vec<_>>();
+
+            // Autoexec is disabled again, before the access is retried without it
+            assert_eq!(
+                autoexec_writes,
+                [
+                    Access::Write(ABSTRACTAUTO, 1),
+                    Access::Write(ABSTRACTAUTO, 0)
+                ]
+            );
+            assert_eq!((dm.abstractauto, dm.cmderr), (0, 0));
+            assert_eq!((dm.s0, dm.s1), (0x0123_4567, 0x89ab_cdef));
+        }
+
+        // Autoexec isn't used again
+        interface
+            .perform_memory_read_multiple_progbuf(0x2000_0010, &mut data)
+            .unwrap();
+
+        assert_eq!(data, [0x1004, 0x1005, 0x1006, 0x1007]);
+        assert!(!dm
+            .lock()
+            .unwrap()
+            .take_accesses()
+            .contains(&Access::Write(ABSTRACTAUTO, 1)));
+    }
+
+    #[test]
+    fn write_memory_disables_autoexec_if_busy() {
+        let dm = debug_module(false, true);
+        let mut interface = interface(&dm);
+
+        interface
+            .perform_memory_write_multiple_progbuf(0x2000_0000, &[0xa0u32, 0xa1, 0xa2])
+            .unwrap();
+
+        assert!(!interface.state.supports_autoexec);
+
+        let mut dm = dm.lock().unwrap();
+        let autoexec_writes = dm
+            .take_accesses()
+            .into_iter()
+            .filter(|access| matches!(access, Access::Write(ABSTRACTAUTO, _)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            autoexec_writes,
+            [
+                Access::Write(ABSTRACTAUTO, 1),
+                Access::Write(ABSTRACTAUTO, 0)
+            ]
+        );
+        assert_eq!((dm.abstractauto, dm.cmderr), (0, 0));
+        assert_eq!((dm.s0, dm.s1), (0x0123_4567, 0x89ab_cdef));
+
+        let memory = (0..3)
+            .map(|index| dm.memory[&(0x2000_0000 + index * 4)])
+            .collect::<Vec<_>>();
+        assert_eq!(memory, [0xa0, 0xa1, 0xa2]);
+    }
+
+    #[test]
+    fn sysbus_error_is_cleared() {
+        let dm = debug_module(false, false);
+        dm.lock().unwrap().sbcs = sysbus_sbcs(32);
+        let mut interface = interface(&dm);
+        interface.enter_debug_mode().unwrap();
+
+        assert!(matches!(
+            interface.state.memory_access_method(RiscvBusAccess::A32),
+            MemoryAccessMethod::SystemBus
+        ));
+
+        let result = interface.perform_memory_read_sysbus::<u32>(0x3000_0000);
+        assert!(matches!(result, Err(RiscvError::SystemBusAccess)));
+        assert_eq!(Sbcs(dm.lock().unwrap().sbcs).sberror(), 0);
+
+        // The failed access doesn't affect the following ones
+        assert_eq!(
+            interface
+                .perform_memory_read_sysbus::<u32>(0x2000_0004)
+                .unwrap(),
+            0x1001
+        );
+
+        let result = interface.perform_memory_write_sysbus(0x3000_0000, &[0xa0u32]);
+        assert!(matches!(result, Err(RiscvError::SystemBusAccess)));
+
+        interface
+            .perform_memory_write_sysbus(0x2000_0008, &[0xa1u32])
+            .unwrap();
+        assert_eq!(dm.lock().unwrap().memory[&0x2000_0008], 0xa1);
+    }
+
+    #[test]
+    fn narrow_sysbus_is_not_used() {
+        let dm = debug_module(false, false);
+        dm.lock().unwrap().sbcs = sysbus_sbcs(16);
+        let mut interface = interface(&dm);
+        interface.enter_debug_mode().unwrap();
+
+        assert_eq!(interface.state.sbasize, 0);
+        assert!(matches!(
+            interface.state.memory_access_method(RiscvBusAccess::A32),
+            MemoryAccessMethod::ProgramBuffer
+        ));
+    }
+}
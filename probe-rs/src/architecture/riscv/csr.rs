@@ -0,0 +1,146 @@
+//! Names of the control and status registers (CSRs), see the RISC-V privileged specification, 2.2.
+
+/// CSRs with a single name, and their address.
+const NAMED_CSRS: &[(&str, u16)] = &[
+    // Unprivileged floating-point CSRs
+    ("fflags", 0x001),
+    ("frm", 0x002),
+    ("fcsr", 0x003),
+    // Unprivileged counters and timers
+    ("cycle", 0xc00),
+    ("time", 0xc01),
+    ("instret", 0xc02),
+    ("cycleh", 0xc80),
+    ("timeh", 0xc81),
+    ("instreth", 0xc82),
+    // Supervisor trap setup and handling
+    ("sstatus", 0x100),
+    ("sie", 0x104),
+    ("stvec", 0x105),
+    ("scounteren", 0x106),
+    ("senvcfg", 0x10a),
+    ("sscratch", 0x140),
+    ("sepc", 0x141),
+    ("scause", 0x142),
+    ("stval", 0x143),
+    ("sip", 0x144),
+    // Supervisor protection and translation
+    ("satp", 0x180),
+    // Machine information
+    ("mvendorid", 0xf11),
+    ("marchid", 0xf12),
+    ("mimpid", 0xf13),
+    ("mhartid", 0xf14),
+    ("mconfigptr", 0xf15),
+    // Machine trap setup
+    ("mstatus", 0x300),
+    ("misa", 0x301),
+    ("medeleg", 0x302),
+    ("mideleg", 0x303),
+    ("mie", 0x304),
+    ("mtvec", 0x305),
+    ("mcounteren", 0x306),
+    ("mstatush", 0x310),
+    // Machine trap handling
+    ("mscratch", 0x340),
+    ("mepc", 0x341),
+    ("mcause", 0x342),
+    ("mtval", 0x343),
+    ("mip", 0x344),
+    ("mtinst", 0x34a),
+    ("mtval2", 0x34b),
+    // Machine configuration
+    ("menvcfg", 0x30a),
+    ("menvcfgh", 0x31a),
+    ("mseccfg", 0x747),
+    ("mseccfgh", 0x757),
+    // Machine counters
+    ("mcycle", 0xb00),
+    ("minstret", 0xb02),
+    ("mcycleh", 0xb80),
+    ("minstreth", 0xb82),
+    ("mcountinhibit", 0x320),
+    // Debug and trace (trigger module)
+    ("tselect", 0x7a0),
+    ("tdata1", 0x7a1),
+    ("tdata2", 0x7a2),
+    ("tdata3", 0x7a3),
+    ("tinfo", 0x7a4),
+    ("tcontrol", 0x7a5),
+    ("mcontext", 0x7a8),
+    ("scontext", 0x7aa),
+    // Debug Mode
+    ("dcsr", 0x7b0),
+    ("dpc", 0x7b1),
+    ("dscratch0", 0x7b2),
+    ("dscratch1", 0x7b3),
+];
+
+/// Numbered CSRs, named `<prefix><index><suffix>`, with the range of valid indices,
+/// and the address of the CSR with the first index.
+const NUMBERED_CSRS: &[(&str, &str, std::ops::RangeInclusive<u16>, u16)] = &[
+    ("pmpcfg", "", 0..=15, 0x3a0),
+    ("pmpaddr", "", 0..=63, 0x3b0),
+    ("hpmcounter", "", 3..=31, 0xc03),
+    ("hpmcounter", "h", 3..=31, 0xc83),
+    ("mhpmcounter", "", 3..=31, 0xb03),
+    ("mhpmcounter", "h", 3..=31, 0xb83),
+    ("mhpmevent", "", 3..=31, 0x323),
+];
+
+/// Look up the address of a CSR by its name, e.g. `mstatus` or `pmpaddr0`.
+///
+/// The address can be used with [`crate::Core::read_csr`] and [`crate::Core::write_csr`].
+/// Names are not case sensitive. Vendor specific CSRs have no name, and have to be
+/// accessed using their address.
+pub fn csr_address(name: &str) -> Option<u16> {
+    let name = name.to_ascii_lowercase();
+
+    if let Some((_, address)) = NAMED_CSRS.iter().find(|(csr, _)| *csr == name) {
+        return Some(*address);
+    }
+
+    NUMBERED_CSRS
+        .iter()
+        .find_map(|(prefix, suffix, indices, first_address)| {
+            let digits = name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            let index: u16 = digits.parse().ok()?;
+
+            // Only accept the canonical form of the index, without a sign or leading zeros.
+            if index.to_string() != digits || !indices.contains(&index) {
+                return None;
+            }
+
+            Some(first_address + index - indices.start())
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::csr_address;
+
+    #[test]
+    fn named_csrs() {
+        assert_eq!(csr_address("mstatus"), Some(0x300));
+        assert_eq!(csr_address("MCAUSE"), Some(0x342));
+        assert_eq!(csr_address("satp"), Some(0x180));
+        assert_eq!(csr_address("dpc"), Some(0x7b1));
+        assert_eq!(csr_address("mfoo"), None);
+    }
+
+    #[test]
+    fn numbered_csrs() {
+        assert_eq!(csr_address("pmpcfg0"), Some(0x3a0));
+        assert_eq!(csr_address("pmpcfg15"), Some(0x3af));
+        assert_eq!(csr_address("pmpaddr63"), Some(0x3ef));
+        assert_eq!(csr_address("hpmcounter3"), Some(0xc03));
+        assert_eq!(csr_address("hpmcounter31h"), Some(0xc9f));
+        assert_eq!(csr_address("mhpmevent31"), Some(0x33f));
+
+        assert_eq!(csr_address("pmpcfg16"), None);
+        assert_eq!(csr_address("hpmcounter2"), None);
+        assert_eq!(csr_address("pmpaddr01"), None);
+        assert_eq!(csr_address("pmpaddr+1"), None);
+        assert_eq!(csr_address("pmpaddr"), None);
+    }
+}
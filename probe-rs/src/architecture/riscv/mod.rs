@@ -3,28 +3,67 @@
 #![allow(clippy::inconsistent_digit_grouping)]
 
 use crate::core::{Architecture, BreakpointCause};
-use crate::{CoreInterface, CoreType, DebugProbeError, InstructionSet};
+use crate::semihosting::SemihostingCommand;
+use crate::{
+    CoreInterface, CoreType, DebugProbeError, InstructionSet, VectorCatch, WatchpointConfig,
+};
 use anyhow::{anyhow, Result};
 use communication_interface::{
-    AbstractCommandErrorKind, DebugRegister, RiscvCommunicationInterface, RiscvError,
+    AbstractCommandErrorKind, DebugRegister, RiscvBusAccess, RiscvCommunicationInterface,
+    RiscvError,
 };
 
 use crate::core::{CoreInformation, RegisterFile, RegisterValue};
-use crate::memory::valid_32_address;
 use crate::{CoreStatus, Error, HaltReason, MemoryInterface, RegisterId};
 
+use assembly::{slli, srai, EBREAK};
 use bitfield::bitfield;
-use register::RISCV_REGISTERS;
+use register::{
+    RISCV_DV_REGISTERS, RISCV_D_REGISTERS, RISCV_FV_REGISTERS, RISCV_F_REGISTERS, RISCV_REGISTERS,
+    RISCV_V_REGISTERS, VECTOR_REGISTERS_START,
+};
 use std::time::{Duration, Instant};
+use triggers::{
+    icount_tdata1, tdata1_from_rv64, tdata1_to_rv64, trap_tdata1, trigger_unused,
+    vector_catch_causes, watchpoint_triggers, TDATA1, TDATA2, TSELECT, TYPE_ICOUNT, TYPE_MCONTROL,
+};
 
 #[macro_use]
 mod register;
 pub(crate) mod assembly;
+mod csr;
 mod dtm;
+mod triggers;
 
 pub mod communication_interface;
 pub mod sequences;
 
+pub use csr::csr_address;
+
+/// Debug Control and Status, which is 32 bits wide independent of XLEN.
+const DCSR: u16 = 0x7b0;
+/// Debug PC, the address of the instruction the hart halted on.
+const DPC: u16 = 0x7b1;
+
+/// The argument registers `a0` and `a1`, numbered like the abstract commands access them.
+const A0: u16 = 0x100a;
+const A1: u16 = 0x100b;
+
+/// The floating point registers `f0` to `f31`, numbered like the abstract commands access them.
+const FP_REGISTERS: std::ops::Range<u16> = 0x1020..0x1040;
+/// The vector registers `v0` to `v31`, see [`VECTOR_REGISTERS_START`].
+const VECTOR_REGISTERS: std::ops::Range<u16> = VECTOR_REGISTERS_START..VECTOR_REGISTERS_START + 32;
+
+const MISA: u16 = 0x301;
+const MSTATUS: u16 = 0x300;
+/// Vector register length in bytes (VLEN / 8).
+const VLENB: u16 = 0xc22;
+
+/// Position of the floating point context status field `FS` in `mstatus`.
+const MSTATUS_FS_SHIFT: u32 = 13;
+/// Position of the vector context status field `VS` in `mstatus`.
+const MSTATUS_VS_SHIFT: u32 = 9;
+
 /// A interface to operate RISC-V cores.
 pub struct Riscv32<'probe> {
     interface: &'probe mut RiscvCommunicationInterface,
@@ -40,7 +79,38 @@ impl<'probe> Riscv32<'probe> {
         Self { interface, state }
     }
 
+    /// Read the lower 32 bits of a CSR.
+    ///
+    /// On RV64, `tdata1` is converted to the RV32 layout,
+    /// so triggers are handled the same way for both.
     fn read_csr(&mut self, address: u16) -> Result<u32, RiscvError> {
+        let value = self.read_csr_xlen(address)?;
+
+        if address == TDATA1 && self.interface.xlen()? == RiscvBusAccess::A64 {
+            Ok(tdata1_from_rv64(value))
+        } else {
+            Ok(value as u32)
+        }
+    }
+
+    /// Write a CSR, zero-extending the value on RV64.
+    ///
+    /// On RV64, `tdata1` is converted from the RV32 layout, see [`Self::read_csr`].
+    fn write_csr(&mut self, address: u16, value: u32) -> Result<(), RiscvError> {
+        let value = if address == TDATA1 && self.interface.xlen()? == RiscvBusAccess::A64 {
+            tdata1_to_rv64(value)
+        } else {
+            value.into()
+        };
+
+        self.write_csr_xlen(address, value)
+    }
+
+    /// Read a CSR or a core register with the full XLEN of the hart.
+    ///
+    /// Debug Mode always uses the XLEN of machine mode (MXLEN), even if the hart
+    /// was running with a smaller SXLEN or UXLEN before it was halted.
+    fn read_csr_xlen(&mut self, address: u16) -> Result<u64, RiscvError> {
         // We need to use the "Access Register Command",
         // which has cmdtype 0
 
@@ -49,9 +119,18 @@ impl<'probe> Riscv32<'probe> {
 
         log::debug!("Reading CSR {:#x}", address);
 
+        // dcsr is 32 bits wide, independent of XLEN
+        let result = if address == DCSR {
+            self.interface
+                .abstract_cmd_register_read::<u32>(address)
+                .map(u64::from)
+        } else {
+            self.interface.abstract_cmd_register_read_xlen(address)
+        };
+
         // always try to read register with abstract command, fallback to program buffer,
         // if not supported
-        match self.interface.abstract_cmd_register_read(address) {
+        match result {
             Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::NotSupported)) => {
                 log::debug!("Could not read core register {:#x} with abstract command, falling back to program buffer", address);
                 self.interface.read_csr_progbuf(address)
@@ -60,10 +139,20 @@ impl<'probe> Riscv32<'probe> {
         }
     }
 
-    fn write_csr(&mut self, address: u16, value: u32) -> Result<(), RiscvError> {
+    /// Write a CSR or a core register with the full XLEN of the hart.
+    fn write_csr_xlen(&mut self, address: u16, value: u64) -> Result<(), RiscvError> {
         log::debug!("Writing CSR {:#x}", address);
 
-        match self.interface.abstract_cmd_register_write(address, value) {
+        // dcsr is 32 bits wide, independent of XLEN
+        let result = if address == DCSR {
+            self.interface
+                .abstract_cmd_register_write(address, value as u32)
+        } else {
+            self.interface
+                .abstract_cmd_register_write_xlen(address, value)
+        };
+
+        match result {
             Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::NotSupported)) => {
                 log::debug!("Could not write core register {:#x} with abstract command, falling back to program buffer", address);
                 self.interface.write_csr_progbuf(address, value)
@@ -72,12 +161,231 @@ impl<'probe> Riscv32<'probe> {
         }
     }
 
+    /// Decode the semihosting request, if the hart halted on the semihosting sequence.
+    ///
+    /// The sequence is `slli x0, x0, 0x1f`, `ebreak`, `srai x0, x0, 7`, with uncompressed
+    /// instructions. The operation is passed in `a0`, and the parameter in `a1`.
+    fn semihosting_command(&mut self) -> Result<Option<SemihostingCommand>, crate::Error> {
+        let dpc = self.read_csr_xlen(DPC)?;
+
+        // The instructions around the ebreak can be unreadable, e.g. if a breakpoint
+        // was placed at the start of a memory region, which is not a semihosting call.
+        let mut sequence = [0u8; 12];
+        if dpc < 4 || self.read_8(dpc - 4, &mut sequence).is_err() {
+            return Ok(None);
+        }
+
+        let instructions: Vec<u32> = sequence
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+
+        if instructions != [slli(0, 0, 0x1f), EBREAK, srai(0, 0, 7)] {
+            return Ok(None);
+        }
+
+        let is_64_bit = self.interface.xlen()? == RiscvBusAccess::A64;
+        let operation = self.read_csr_xlen(A0)? as u32;
+        let parameter = self.read_csr_xlen(A1)?;
+
+        SemihostingCommand::decode(self, operation, parameter, is_64_bit).map(Some)
+    }
+
+    /// Detect the floating point and vector extensions of the hart using `misa`.
+    ///
+    /// The hart has to be halted. The result is cached, the extensions are not expected
+    /// to be changed by writing `misa`.
+    fn extensions(&mut self) -> Result<Extensions, crate::Error> {
+        if let Some(extensions) = self.state.extensions {
+            return Ok(extensions);
+        }
+
+        let misa = Misa(self.read_csr(MISA)?);
+        let implemented = |extension: u8| misa.extensions() & (1 << (extension - b'A')) != 0;
+
+        let flen = if implemented(b'D') {
+            Some(RiscvBusAccess::A64)
+        } else if implemented(b'F') {
+            Some(RiscvBusAccess::A32)
+        } else {
+            None
+        };
+
+        let extensions = Extensions {
+            flen,
+            vector: implemented(b'V'),
+        };
+
+        log::debug!("Detected extensions: {:?}", extensions);
+        self.state.extensions = Some(extensions);
+
+        Ok(extensions)
+    }
+
+    /// Run `access` with the context status field of `mstatus` at `shift` enabled, which is
+    /// required to access the floating point and vector registers, and restore it afterwards.
+    fn with_context_enabled<T>(
+        &mut self,
+        shift: u32,
+        access: impl FnOnce(&mut Self) -> Result<T, crate::Error>,
+    ) -> Result<T, crate::Error> {
+        let mstatus = self.read_csr_xlen(MSTATUS)?;
+
+        // Any state other than off allows accessing the registers.
+        if (mstatus >> shift) & 0b11 != 0 {
+            return access(self);
+        }
+
+        // Set the state to initial
+        self.write_csr_xlen(MSTATUS, mstatus | 1 << shift)?;
+
+        let result = access(self);
+
+        self.write_csr_xlen(MSTATUS, mstatus)?;
+
+        result
+    }
+
+    /// Read a floating point register, with the FLEN of the hart.
+    fn read_fp_register(&mut self, index: u8) -> Result<RegisterValue, crate::Error> {
+        let flen = self.flen()?;
+        let regno = FP_REGISTERS.start + index as u16;
+
+        self.with_context_enabled(MSTATUS_FS_SHIFT, |core| {
+            let result = match flen {
+                RiscvBusAccess::A64 => core.interface.abstract_cmd_register_read::<u64>(regno),
+                _ => core
+                    .interface
+                    .abstract_cmd_register_read::<u32>(regno)
+                    .map(u64::from),
+            };
+
+            // The register can only be moved through s0 if it is not wider than XLEN.
+            let value = match result {
+                Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::NotSupported))
+                    if flen <= core.interface.xlen()? =>
+                {
+                    core.interface
+                        .read_fp_register_progbuf(index, flen == RiscvBusAccess::A64)?
+                }
+                other => other?,
+            };
+
+            Ok(match flen {
+                RiscvBusAccess::A64 => RegisterValue::U64(value),
+                _ => RegisterValue::U32(value as u32),
+            })
+        })
+    }
+
+    /// Write a floating point register, with the FLEN of the hart.
+    fn write_fp_register(&mut self, index: u8, value: RegisterValue) -> Result<(), crate::Error> {
+        let flen = self.flen()?;
+        let regno = FP_REGISTERS.start + index as u16;
+
+        let value: u64 = match flen {
+            RiscvBusAccess::A64 => value.try_into()?,
+            _ => TryInto::<u32>::try_into(value)?.into(),
+        };
+
+        self.with_context_enabled(MSTATUS_FS_SHIFT, |core| {
+            let result = match flen {
+                RiscvBusAccess::A64 => core.interface.abstract_cmd_register_write(regno, value),
+                _ => core
+                    .interface
+                    .abstract_cmd_register_write(regno, value as u32),
+            };
+
+            match result {
+                Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::NotSupported))
+                    if flen <= core.interface.xlen()? =>
+                {
+                    core.interface.write_fp_register_progbuf(
+                        index,
+                        flen == RiscvBusAccess::A64,
+                        value,
+                    )?
+                }
+                other => other?,
+            }
+
+            Ok(())
+        })
+    }
+
+    fn flen(&mut self) -> Result<RiscvBusAccess, crate::Error> {
+        self.extensions()?
+            .flen
+            .ok_or_else(|| anyhow!("The hart does not have floating point registers").into())
+    }
+
+    /// Read a vector register, which has to be at most 128 bits wide.
+    fn read_vector_register(&mut self, index: u8) -> Result<RegisterValue, crate::Error> {
+        self.with_context_enabled(MSTATUS_VS_SHIFT, |core| {
+            let vlenb = core.vlenb()?;
+            let value = core.interface.read_vector_register(index, vlenb)?;
+
+            let mut bytes = [0; 16];
+            bytes[..vlenb].copy_from_slice(&value);
+
+            Ok(RegisterValue::U128(u128::from_le_bytes(bytes)))
+        })
+    }
+
+    /// Write a vector register, which has to be at most 128 bits wide.
+    fn write_vector_register(
+        &mut self,
+        index: u8,
+        value: RegisterValue,
+    ) -> Result<(), crate::Error> {
+        let value: u128 = value.try_into()?;
+
+        self.with_context_enabled(MSTATUS_VS_SHIFT, |core| {
+            let vlenb = core.vlenb()?;
+
+            if vlenb < 16 && value >> (vlenb * 8) != 0 {
+                return Err(anyhow!(
+                    "Value {:#x} is too large for a vector register with {} bits",
+                    value,
+                    vlenb * 8
+                )
+                .into());
+            }
+
+            core.interface
+                .write_vector_register(index, &value.to_le_bytes()[..vlenb])?;
+
+            Ok(())
+        })
+    }
+
+    /// The width of the vector registers in bytes.
+    ///
+    /// The vector context has to be enabled to read it.
+    fn vlenb(&mut self) -> Result<usize, crate::Error> {
+        if !self.extensions()?.vector {
+            return Err(anyhow!("The hart does not have vector registers").into());
+        }
+
+        let vlenb = self.read_csr_xlen(VLENB)? as usize;
+
+        // The value of a register has to fit into a `RegisterValue`.
+        if vlenb > 16 {
+            return Err(anyhow!(
+                "Vector registers with a VLEN of {} bits are not supported",
+                vlenb * 8
+            )
+            .into());
+        }
+
+        Ok(vlenb)
+    }
+
     // Resume the core.
     fn resume_core(&mut self) -> Result<(), crate::Error> {
         // set resume request.
-        let mut dmcontrol = Dmcontrol(0);
+        let mut dmcontrol = self.interface.dmcontrol();
         dmcontrol.set_resumereq(true);
-        dmcontrol.set_dmactive(true);
         self.interface.write_dm_register(dmcontrol)?;
 
         // check if request has been acknowleged.
@@ -87,12 +395,116 @@ impl<'probe> Riscv32<'probe> {
         };
 
         // clear resume request.
-        let mut dmcontrol = Dmcontrol(0);
-        dmcontrol.set_dmactive(true);
+        let dmcontrol = self.interface.dmcontrol();
         self.interface.write_dm_register(dmcontrol)?;
 
         Ok(())
     }
+
+    /// Find `count` consecutive unused triggers which support the type `trigger_type`,
+    /// and return the index of the first one.
+    fn find_free_triggers(&mut self, trigger_type: u32, count: usize) -> Result<usize, Error> {
+        let mut found = 0;
+
+        for index in 0..self.available_breakpoint_units()? as usize {
+            self.write_csr(TSELECT, index as u32)?;
+            let tdata1 = self.read_csr(TDATA1)?;
+
+            let usable = if !trigger_unused(tdata1) {
+                false
+            } else if tdata1 >> 28 == trigger_type {
+                true
+            } else {
+                // The type field only accepts the supported trigger types,
+                // so check if the type can be written.
+                let mut probe = Mcontrol(0);
+                probe.set_type(trigger_type);
+                probe.set_dmode(true);
+                self.write_csr(TDATA1, probe.0)?;
+                let supported = self.read_csr(TDATA1)? >> 28 == trigger_type;
+                self.write_csr(TDATA1, tdata1)?;
+
+                supported
+            };
+
+            if usable {
+                found += 1;
+
+                if found == count {
+                    return Ok(index + 1 - count);
+                }
+            } else {
+                found = 0;
+            }
+        }
+
+        Err(RiscvError::TriggerNotAvailable(trigger_type).into())
+    }
+
+    /// Find a trigger of type `trigger_type` which is in use.
+    fn find_used_trigger(&mut self, trigger_type: u32) -> Result<Option<usize>, Error> {
+        for index in 0..self.available_breakpoint_units()? as usize {
+            self.write_csr(TSELECT, index as u32)?;
+            let tdata1 = self.read_csr(TDATA1)?;
+
+            if tdata1 >> 28 == trigger_type && !trigger_unused(tdata1) {
+                return Ok(Some(index));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Check that an address fits into the registers of the hart.
+    fn valid_address(&mut self, address: u64) -> Result<u64, RiscvError> {
+        if address > u32::MAX as u64 && self.interface.xlen()? != RiscvBusAccess::A64 {
+            return Err(RiscvError::AddressOutOfRange(address));
+        }
+
+        Ok(address)
+    }
+
+    fn write_trigger(&mut self, index: usize, tdata1: u32, tdata2: u64) -> Result<(), Error> {
+        self.write_csr(TSELECT, index as u32)?;
+        self.write_csr(TDATA1, tdata1)?;
+        self.write_csr_xlen(TDATA2, tdata2)?;
+
+        Ok(())
+    }
+
+    /// Halt on the exceptions or interrupts for `condition`, using an etrigger or itrigger.
+    ///
+    /// All conditions of the same trigger type share a single trigger.
+    fn set_vector_catch(&mut self, condition: VectorCatch, enabled: bool) -> Result<(), Error> {
+        let (trigger_type, causes) =
+            vector_catch_causes(condition).ok_or(RiscvError::VectorCatchNotSupported(condition))?;
+
+        match self.find_used_trigger(trigger_type)? {
+            Some(index) => {
+                self.write_csr(TSELECT, index as u32)?;
+                let current = self.read_csr(TDATA2)?;
+
+                let causes = if enabled {
+                    current | causes
+                } else {
+                    current & !causes
+                };
+
+                if causes == 0 {
+                    self.clear_hw_breakpoint(index)?;
+                } else {
+                    self.write_csr(TDATA2, causes)?;
+                }
+            }
+            None if enabled => {
+                let index = self.find_free_triggers(trigger_type, 1)?;
+                self.write_trigger(index, trap_tdata1(trigger_type), causes.into())?;
+            }
+            None => (),
+        }
+
+        Ok(())
+    }
 }
 
 impl<'probe> CoreInterface for Riscv32<'probe> {
@@ -127,22 +539,23 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
             self.interface.read_dm_register::<Dmcontrol>()?
         );
 
-        let mut dmcontrol = Dmcontrol(0);
-
+        let mut dmcontrol = self.interface.dmcontrol();
         dmcontrol.set_haltreq(true);
-        dmcontrol.set_dmactive(true);
 
         self.interface.write_dm_register(dmcontrol)?;
 
         self.wait_for_core_halted(timeout)?;
 
         // clear the halt request
-        let mut dmcontrol = Dmcontrol(0);
-
-        dmcontrol.set_dmactive(true);
+        let dmcontrol = self.interface.dmcontrol();
 
         self.interface.write_dm_register(dmcontrol)?;
 
+        // The registers of the hart can only be detected while it is halted.
+        if let Err(error) = self.extensions() {
+            log::warn!("Failed to detect the extensions of the hart: {}", error);
+        }
+
         let pc = self.read_core_reg(register::RISCV_REGISTERS.program_counter.id)?;
 
         Ok(CoreInformation { pc: pc.try_into()? })
@@ -173,14 +586,26 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
 
     fn reset_and_halt(
         &mut self,
-        _timeout: Duration,
+        timeout: Duration,
     ) -> Result<crate::core::CoreInformation, crate::Error> {
+        // If the debug module supports it, the hart is halted directly after the reset
+        // using `resethaltreq`. Otherwise, a halt request is kept pending during the reset,
+        // which might only be handled after the hart has already executed some instructions.
+        let reset_halt_req = self.interface.supports_reset_halt_req();
+
+        if reset_halt_req {
+            log::debug!("Setting resethaltreq bit");
+            let mut dmcontrol = self.interface.dmcontrol();
+            dmcontrol.set_resethaltreq(true);
+
+            self.interface.write_dm_register(dmcontrol)?;
+        }
+
         log::debug!("Resetting core, setting hartreset bit");
 
-        let mut dmcontrol = Dmcontrol(0);
-        dmcontrol.set_dmactive(true);
+        let mut dmcontrol = self.interface.dmcontrol();
         dmcontrol.set_hartreset(true);
-        dmcontrol.set_haltreq(true);
+        dmcontrol.set_haltreq(!reset_halt_req);
 
         self.interface.write_dm_register(dmcontrol)?;
 
@@ -190,9 +615,8 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         if readback.hartreset() {
             log::debug!("Clearing hartreset bit");
             // Reset is performed by setting the bit high, and then low again
-            let mut dmcontrol = Dmcontrol(0);
-            dmcontrol.set_dmactive(true);
-            dmcontrol.set_haltreq(true);
+            let mut dmcontrol = self.interface.dmcontrol();
+            dmcontrol.set_haltreq(!reset_halt_req);
             dmcontrol.set_hartreset(false);
 
             self.interface.write_dm_register(dmcontrol)?;
@@ -201,33 +625,42 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
             //
             // TODO: Cache this
             log::debug!("Hartreset bit not supported, using ndmreset");
-            let mut dmcontrol = Dmcontrol(0);
-            dmcontrol.set_dmactive(true);
+            let mut dmcontrol = self.interface.dmcontrol();
             dmcontrol.set_ndmreset(true);
-            dmcontrol.set_haltreq(true);
+            dmcontrol.set_haltreq(!reset_halt_req);
 
             self.interface.write_dm_register(dmcontrol)?;
 
             log::debug!("Clearing ndmreset bit");
-            let mut dmcontrol = Dmcontrol(0);
-            dmcontrol.set_dmactive(true);
+            let mut dmcontrol = self.interface.dmcontrol();
             dmcontrol.set_ndmreset(false);
-            dmcontrol.set_haltreq(true);
+            dmcontrol.set_haltreq(!reset_halt_req);
 
             self.interface.write_dm_register(dmcontrol)?;
         }
 
-        // check that cores have reset
-        let readback: Dmstatus = self.interface.read_dm_register()?;
+        // check that cores have reset and halted
+        let start = Instant::now();
 
-        if !(readback.allhavereset() && readback.allhalted()) {
-            return Err(RiscvError::RequestNotAcknowledged.into());
+        loop {
+            let readback: Dmstatus = self.interface.read_dm_register()?;
+
+            if readback.allhavereset() && readback.allhalted() {
+                break;
+            }
+
+            if start.elapsed() > timeout {
+                return Err(RiscvError::RequestNotAcknowledged.into());
+            }
         }
 
         // acknowledge the reset, clear the halt request
-        let mut dmcontrol = Dmcontrol(0);
-        dmcontrol.set_dmactive(true);
+        let mut dmcontrol = self.interface.dmcontrol();
         dmcontrol.set_ackhavereset(true);
+        if reset_halt_req {
+            // Don't halt on following resets, e.g. ones triggered by the target itself
+            dmcontrol.set_clrresethaltreq(true);
+        }
 
         self.interface.write_dm_register(dmcontrol)?;
 
@@ -272,10 +705,24 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         dcsr.set_stopcount(true);
         self.write_csr(0x7b0, dcsr.0)?;
 
+        // Harts which don't support single stepping using dcsr can step using an icount trigger.
+        let icount_trigger = if Dcsr(self.read_csr(0x7b0)?).step() {
+            None
+        } else {
+            let index = self.find_free_triggers(TYPE_ICOUNT, 1)?;
+            self.write_csr(TSELECT, index as u32)?;
+            self.write_csr(TDATA1, icount_tdata1(1))?;
+            Some(index)
+        };
+
         // Now we can resume the core for the single step.
         self.resume_core()?;
         self.wait_for_core_halted(Duration::from_millis(100))?;
 
+        if let Some(index) = icount_trigger {
+            self.clear_hw_breakpoint(index)?;
+        }
+
         let pc = self.read_core_reg(RegisterId(0x7b1))?;
 
         // clear step request
@@ -299,9 +746,20 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
     }
 
     fn read_core_reg(&mut self, address: crate::RegisterId) -> Result<RegisterValue, crate::Error> {
-        self.read_csr(address.0)
-            .map(|v| v.into())
-            .map_err(|e| e.into())
+        if FP_REGISTERS.contains(&address.0) {
+            return self.read_fp_register((address.0 - FP_REGISTERS.start) as u8);
+        }
+
+        if VECTOR_REGISTERS.contains(&address.0) {
+            return self.read_vector_register((address.0 - VECTOR_REGISTERS.start) as u8);
+        }
+
+        let value = self.read_csr_xlen(address.0)?;
+
+        match self.interface.xlen()? {
+            RiscvBusAccess::A64 if address.0 != DCSR => Ok(RegisterValue::U64(value)),
+            _ => Ok(RegisterValue::U32(value as u32)),
+        }
     }
 
     fn write_core_reg(
@@ -309,8 +767,20 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         address: crate::RegisterId,
         value: RegisterValue,
     ) -> Result<(), crate::Error> {
-        let value: u32 = value.try_into()?;
-        self.write_csr(address.0, value).map_err(|e| e.into())
+        if FP_REGISTERS.contains(&address.0) {
+            return self.write_fp_register((address.0 - FP_REGISTERS.start) as u8, value);
+        }
+
+        if VECTOR_REGISTERS.contains(&address.0) {
+            return self.write_vector_register((address.0 - VECTOR_REGISTERS.start) as u8, value);
+        }
+
+        let value: u64 = match self.interface.xlen()? {
+            RiscvBusAccess::A64 => value.try_into()?,
+            _ => TryInto::<u32>::try_into(value)?.into(),
+        };
+
+        self.write_csr_xlen(address.0, value).map_err(|e| e.into())
     }
 
     fn available_breakpoint_units(&mut self) -> Result<u32, crate::Error> {
@@ -354,14 +824,11 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
                     }
                 }
                 Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::Exception)) => {
-                    // An exception means we have to read tdata1 to discover the type
+                    // An exception means we have to read tdata1 to discover the type.
+                    // The type is in the topmost bits, which are moved to the RV32 layout on RV64.
                     let tdata_val = self.read_csr(tdata1)?;
 
-                    // Read the mxl field from the misa register (see RISC-V Privileged Spec, 3.1.1)
-                    let misa_value = Misa(self.read_csr(0x301)?);
-                    let xlen = u32::pow(2, misa_value.mxl() + 4);
-
-                    let trigger_type = tdata_val >> (xlen - 4);
+                    let trigger_type = tdata_val >> 28;
 
                     if trigger_type == 0 {
                         break;
@@ -421,7 +888,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
     }
 
     fn set_hw_breakpoint(&mut self, bp_unit_index: usize, addr: u64) -> Result<(), crate::Error> {
-        let addr = valid_32_address(addr)?;
+        let addr = self.valid_address(addr)?;
 
         if !self.hw_breakpoints_enabled() {
             self.enable_breakpoints(true)?;
@@ -469,7 +936,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
         instruction_breakpoint.set_select(false);
 
         self.write_csr(tdata1, instruction_breakpoint.0)?;
-        self.write_csr(tdata2, addr)?;
+        self.write_csr_xlen(tdata2, addr)?;
 
         Ok(())
     }
@@ -487,7 +954,19 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
     }
 
     fn registers(&self) -> &'static RegisterFile {
-        &RISCV_REGISTERS
+        let extensions = match self.state.extensions {
+            Some(extensions) => extensions,
+            None => return &RISCV_REGISTERS,
+        };
+
+        match (extensions.flen, extensions.vector) {
+            (Some(RiscvBusAccess::A64), false) => &RISCV_D_REGISTERS,
+            (Some(RiscvBusAccess::A64), true) => &RISCV_DV_REGISTERS,
+            (Some(_), false) => &RISCV_F_REGISTERS,
+            (Some(_), true) => &RISCV_FV_REGISTERS,
+            (None, true) => &RISCV_V_REGISTERS,
+            (None, false) => &RISCV_REGISTERS,
+        }
     }
 
     fn hw_breakpoints_enabled(&self) -> bool {
@@ -524,8 +1003,11 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
             let dcsr = Dcsr(self.read_core_reg(RegisterId::from(0x7b0))?.try_into()?);
 
             let reason = match dcsr.cause() {
-                // An ebreak instruction was hit
-                1 => HaltReason::Breakpoint(BreakpointCause::Software),
+                // An ebreak instruction was hit, which can be a semihosting request
+                1 => match self.semihosting_command()? {
+                    Some(command) => HaltReason::Breakpoint(BreakpointCause::Semihosting(command)),
+                    None => HaltReason::Breakpoint(BreakpointCause::Software),
+                },
                 // Trigger module caused halt
                 2 => HaltReason::Breakpoint(BreakpointCause::Hardware),
                 // Debugger requested a halt
@@ -580,8 +1062,13 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
                 && trigger_any_mode_active
                 && trigger_any_action_enabled
             {
-                let breakpoint = self.read_csr(tdata2)?;
-                breakpoints.push(Some(breakpoint as u64));
+                let breakpoint = self.read_csr_xlen(tdata2)?;
+                breakpoints.push(Some(breakpoint));
+            } else if !trigger_unused(tdata_value.0) && !tdata_value.execute() {
+                // The trigger is used for something else, like a watchpoint,
+                // so it must not be used for a breakpoint.
+                let data = self.read_csr(tdata2)?;
+                breakpoints.push(Some(data as u64));
             } else {
                 breakpoints.push(None);
             }
@@ -591,9 +1078,7 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
     }
 
     fn fpu_support(&mut self) -> Result<bool, crate::error::Error> {
-        Err(crate::error::Error::Other(anyhow::anyhow!(
-            "Fpu detection not yet implemented"
-        )))
+        Ok(self.extensions()?.flen.is_some())
     }
 
     fn debug_on_sw_breakpoint(&mut self, enabled: bool) -> Result<(), crate::error::Error> {
@@ -605,6 +1090,74 @@ impl<'probe> CoreInterface for Riscv32<'probe> {
 
         self.write_csr(0x7b0, dcsr.0).map_err(|e| e.into())
     }
+
+    fn set_watchpoint(&mut self, config: &WatchpointConfig) -> Result<usize, crate::Error> {
+        let address = self.valid_address(config.address)?;
+
+        if config.address % config.size.bytes() != 0 {
+            return Err(anyhow!(
+                "Watchpoint address {:#010x} is not aligned to {} bytes",
+                address,
+                config.size.bytes()
+            )
+            .into());
+        }
+
+        let triggers = watchpoint_triggers(config, address);
+        let first = self.find_free_triggers(TYPE_MCONTROL, triggers.len())?;
+
+        for (index, (tdata1, tdata2)) in triggers.into_iter().enumerate() {
+            self.write_trigger(first + index, tdata1, tdata2)?;
+        }
+
+        Ok(first)
+    }
+
+    fn clear_watchpoint(&mut self, unit_index: usize) -> Result<(), crate::Error> {
+        self.write_csr(TSELECT, unit_index as u32)?;
+        let tdata1 = Mcontrol(self.read_csr(TDATA1)?);
+
+        self.clear_hw_breakpoint(unit_index)?;
+
+        // A watchpoint with a value uses a second trigger for the value.
+        if tdata1.type_() == TYPE_MCONTROL && tdata1.chain() {
+            self.clear_hw_breakpoint(unit_index + 1)?;
+        }
+
+        Ok(())
+    }
+
+    fn enable_vector_catch(&mut self, condition: VectorCatch) -> Result<(), crate::Error> {
+        self.set_vector_catch(condition, true)
+    }
+
+    fn disable_vector_catch(&mut self, condition: VectorCatch) -> Result<(), crate::Error> {
+        self.set_vector_catch(condition, false)
+    }
+
+    fn read_csr(&mut self, address: u16) -> Result<u64, crate::Error> {
+        Ok(self.read_csr_xlen(address)?)
+    }
+
+    fn write_csr(&mut self, address: u16, value: u64) -> Result<(), crate::Error> {
+        if value > u32::MAX as u64 && self.interface.xlen()? != RiscvBusAccess::A64 {
+            return Err(
+                anyhow!("Value {:#x} is too large for the CSR {:#x}", value, address).into(),
+            );
+        }
+
+        Ok(self.write_csr_xlen(address, value)?)
+    }
+
+    fn finish_semihosting(&mut self, return_value: u64) -> Result<(), crate::Error> {
+        self.write_csr_xlen(A0, return_value)?;
+
+        // Continue with the srai after the ebreak, which has no effect.
+        let dpc = self.read_csr_xlen(DPC)?;
+        self.write_csr_xlen(DPC, dpc + 4)?;
+
+        Ok(())
+    }
 }
 
 impl<'probe> MemoryInterface for Riscv32<'probe> {
@@ -657,16 +1210,29 @@ impl<'probe> MemoryInterface for Riscv32<'probe> {
 pub struct RiscVState {
     /// A flag to remember whether we want to use hw_breakpoints during stepping of the core.
     hw_breakpoints_enabled: bool,
+
+    /// The extensions of the hart which add registers, detected when it is first halted.
+    extensions: Option<Extensions>,
 }
 
 impl RiscVState {
     pub(crate) fn new() -> Self {
         Self {
             hw_breakpoints_enabled: false,
+            extensions: None,
         }
     }
 }
 
+/// The floating point and vector extensions of a hart.
+#[derive(Debug, Clone, Copy)]
+struct Extensions {
+    /// The width of the floating point registers, if the F or D extension is implemented.
+    flen: Option<RiscvBusAccess>,
+    /// Whether the V extension is implemented.
+    vector: bool,
+}
+
 bitfield! {
     /// `dmcontrol` register, located at
     /// address 0x10
@@ -895,11 +1461,11 @@ bitfield! {
 
 bitfield! {
     /// Isa and Extensions (see RISC-V Privileged Spec, 3.1.1)
+    ///
+    /// Only the lower 32 bits are used, the MXL field is not at a fixed position.
     pub struct Misa(u32);
     impl Debug;
 
-    /// Machine XLEN
-    mxl, _: 31, 30;
     /// Standard RISC-V extensions
     extensions, _: 25, 0;
 }
@@ -422,7 +422,741 @@ pub(super) static RISCV_REGISTERS: RegisterFile = RegisterFile {
     msp: None,
     other: &[],
     psr: None,
-    // TODO: Add FPU registers
     fp_registers: None,
+    fp_double_registers: None,
     fp_status: None,
 };
+
+/// Floating point control and status register.
+static FCSR: RegisterDescription = RegisterDescription {
+    name: "fcsr",
+    _kind: RegisterKind::Fp,
+    /// This is a CSR register
+    id: RegisterId(0x003),
+    _type: RegisterDataType::UnsignedInteger,
+    size_in_bits: 32,
+};
+
+/// Registers of harts with the F extension, with single precision floating point registers.
+pub(super) static RISCV_F_REGISTERS: RegisterFile = RegisterFile {
+    fp_status: Some(&FCSR),
+    fp_registers: Some(&[
+        RegisterDescription {
+            name: "ft0",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1020),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "ft1",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1021),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "ft2",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1022),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "ft3",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1023),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "ft4",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1024),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "ft5",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1025),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "ft6",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1026),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "ft7",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1027),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fs0",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1028),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fs1",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1029),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fa0",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x102A),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fa1",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x102B),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fa2",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x102C),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fa3",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x102D),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fa4",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x102E),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fa5",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x102F),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fa6",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1030),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fa7",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1031),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fs2",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1032),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fs3",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1033),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fs4",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1034),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fs5",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1035),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fs6",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1036),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fs7",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1037),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fs8",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1038),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fs9",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1039),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fs10",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x103A),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "fs11",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x103B),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "ft8",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x103C),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "ft9",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x103D),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "ft10",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x103E),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "ft11",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x103F),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 32,
+        },
+    ]),
+
+    ..RISCV_REGISTERS
+};
+
+/// Registers of harts with the D extension, with double precision floating point registers.
+pub(super) static RISCV_D_REGISTERS: RegisterFile = RegisterFile {
+    fp_status: Some(&FCSR),
+    fp_registers: Some(&[
+        RegisterDescription {
+            name: "ft0",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1020),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "ft1",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1021),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "ft2",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1022),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "ft3",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1023),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "ft4",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1024),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "ft5",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1025),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "ft6",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1026),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "ft7",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1027),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fs0",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1028),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fs1",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1029),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fa0",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x102A),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fa1",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x102B),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fa2",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x102C),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fa3",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x102D),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fa4",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x102E),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fa5",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x102F),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fa6",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1030),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fa7",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1031),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fs2",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1032),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fs3",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1033),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fs4",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1034),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fs5",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1035),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fs6",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1036),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fs7",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1037),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fs8",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1038),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fs9",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x1039),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fs10",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x103A),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "fs11",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x103B),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "ft8",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x103C),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "ft9",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x103D),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "ft10",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x103E),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+        RegisterDescription {
+            name: "ft11",
+            _kind: RegisterKind::Fp,
+            id: RegisterId(0x103F),
+            _type: RegisterDataType::FloatingPoint,
+            size_in_bits: 64,
+        },
+    ]),
+
+    ..RISCV_REGISTERS
+};
+
+/// The first register number of the vector registers `v0` to `v31`.
+///
+/// Vector registers can't be accessed using abstract commands, so they have no register
+/// number in the debug specification. The numbers after the floating point registers,
+/// which are reserved in the specification, are used instead.
+pub(super) const VECTOR_REGISTERS_START: u16 = 0x1040;
+
+/// The vector registers, which are VLEN bits wide.
+///
+/// Only harts with a VLEN of up to 128 bits are supported.
+static VECTOR_REGISTERS: &[RegisterDescription] = &[
+    RegisterDescription {
+        name: "v0",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1040),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v1",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1041),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v2",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1042),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v3",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1043),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v4",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1044),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v5",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1045),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v6",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1046),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v7",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1047),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v8",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1048),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v9",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1049),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v10",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x104A),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v11",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x104B),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v12",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x104C),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v13",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x104D),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v14",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x104E),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v15",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x104F),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v16",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1050),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v17",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1051),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v18",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1052),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v19",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1053),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v20",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1054),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v21",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1055),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v22",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1056),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v23",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1057),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v24",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1058),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v25",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x1059),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v26",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x105A),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v27",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x105B),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v28",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x105C),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v29",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x105D),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v30",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x105E),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+    RegisterDescription {
+        name: "v31",
+        _kind: RegisterKind::General,
+        id: RegisterId(0x105F),
+        _type: RegisterDataType::UnsignedInteger,
+        size_in_bits: 128,
+    },
+];
+
+/// Registers of harts with the V extension.
+pub(super) static RISCV_V_REGISTERS: RegisterFile = RegisterFile {
+    other: VECTOR_REGISTERS,
+
+    ..RISCV_REGISTERS
+};
+
+/// Registers of harts with the F and the V extension.
+pub(super) static RISCV_FV_REGISTERS: RegisterFile = RegisterFile {
+    other: VECTOR_REGISTERS,
+
+    ..RISCV_F_REGISTERS
+};
+
+/// Registers of harts with the D and the V extension.
+pub(super) static RISCV_DV_REGISTERS: RegisterFile = RegisterFile {
+    other: VECTOR_REGISTERS,
+
+    ..RISCV_D_REGISTERS
+};
@@ -0,0 +1,54 @@
+//! Sequences for the ESP32C6.
+
+use std::sync::Arc;
+
+use super::RiscvDebugSequence;
+use crate::MemoryInterface;
+
+/// The debug sequence implementation for the ESP32C6.
+pub struct ESP32C6(());
+
+impl ESP32C6 {
+    /// Creates a new debug sequence handle for the ESP32C6.
+    pub fn create() -> Arc<dyn RiscvDebugSequence> {
+        Arc::new(Self(()))
+    }
+}
+
+impl RiscvDebugSequence for ESP32C6 {
+    fn on_connect(
+        &self,
+        interface: &mut crate::architecture::riscv::communication_interface::RiscvCommunicationInterface,
+    ) -> Result<(), crate::Error> {
+        log::info!("Disabling esp32c6 watchdogs...");
+        disable_watchdogs(interface)
+    }
+}
+
+/// Disable the watchdogs of the ESP32C6.
+///
+/// They are enabled again by a reset, so this is also done before flashing.
+pub(crate) fn disable_watchdogs(memory: &mut impl MemoryInterface) -> Result<(), crate::Error> {
+    // disable super wdt
+    memory.write_word_32(0x600B1C20, 0x50D83AA1u32)?; // write protection off
+    let current = memory.read_word_32(0x600B1C1C)?;
+    memory.write_word_32(0x600B1C1C, current | 1 << 18)?; // set LP_WDT_SWD_AUTO_FEED_EN
+    memory.write_word_32(0x600B1C20, 0x0)?; // write protection on
+
+    // tg0 wdg
+    memory.write_word_32(0x60008064, 0x50D83AA1u32)?; // write protection off
+    memory.write_word_32(0x60008048, 0x0)?;
+    memory.write_word_32(0x60008064, 0x0)?; // write protection on
+
+    // tg1 wdg
+    memory.write_word_32(0x60009064, 0x50D83AA1u32)?; // write protection off
+    memory.write_word_32(0x60009048, 0x0)?;
+    memory.write_word_32(0x60009064, 0x0)?; // write protection on
+
+    // rtc wdg
+    memory.write_word_32(0x600B1C18, 0x50D83AA1u32)?; // write protection off
+    memory.write_word_32(0x600B1C00, 0x0)?;
+    memory.write_word_32(0x600B1C18, 0x0)?; // write protection on
+
+    Ok(())
+}
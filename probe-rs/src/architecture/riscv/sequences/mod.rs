@@ -4,6 +4,7 @@ use super::communication_interface::RiscvCommunicationInterface;
 use std::sync::Arc;
 
 pub mod esp32c3;
+pub mod esp32c6;
 
 /// A interface to operate debug sequences for RISC-V targets.
 ///
@@ -0,0 +1,259 @@
+//! Encoding of the triggers of the trigger module, see chapter 5 of the RISC-V debug specification 0.13.
+
+use super::Mcontrol;
+use crate::{VectorCatch, WatchpointAccess, WatchpointConfig, WatchpointSize};
+use bitfield::bitfield;
+
+/// Trigger Select, which selects the trigger accessed through the other trigger registers.
+pub(super) const TSELECT: u16 = 0x7a0;
+/// Trigger Data 1, which contains the type and the configuration of the selected trigger.
+pub(super) const TDATA1: u16 = 0x7a1;
+/// Trigger Data 2, which contains trigger specific data, e.g. the compared address.
+pub(super) const TDATA2: u16 = 0x7a2;
+
+/// Address and data match trigger.
+pub(super) const TYPE_MCONTROL: u32 = 2;
+/// Instruction count trigger.
+pub(super) const TYPE_ICOUNT: u32 = 3;
+/// Interrupt trigger.
+pub(super) const TYPE_ITRIGGER: u32 = 4;
+/// Exception trigger.
+pub(super) const TYPE_ETRIGGER: u32 = 5;
+/// A trigger which exists, but is currently disabled.
+const TYPE_DISABLED: u32 = 15;
+
+/// The action of a trigger which enters Debug Mode.
+const ACTION_DEBUG_MODE: u32 = 1;
+
+bitfield! {
+    /// `tdata1` of an instruction count trigger (icount).
+    pub(super) struct Icount(u32);
+    impl Debug;
+
+    type_, set_type: 31, 28;
+    dmode, set_dmode: 27;
+    hit, set_hit: 24;
+    count, set_count: 23, 10;
+    m, set_m: 9;
+    s, set_s: 7;
+    u, set_u: 6;
+    action, set_action: 5, 0;
+}
+
+bitfield! {
+    /// `tdata1` of an interrupt (itrigger) or exception trigger (etrigger).
+    ///
+    /// `tdata2` contains a bit for each interrupt or exception cause which fires the trigger.
+    pub(super) struct TrapTrigger(u32);
+    impl Debug;
+
+    type_, set_type: 31, 28;
+    dmode, set_dmode: 27;
+    hit, set_hit: 26;
+    m, set_m: 9;
+    s, set_s: 7;
+    u, set_u: 6;
+    action, set_action: 5, 0;
+}
+
+/// Returns `true` if the trigger with the given `tdata1` value is not in use.
+pub(super) fn trigger_unused(tdata1: u32) -> bool {
+    match tdata1 >> 28 {
+        // No mode, and nothing to match on.
+        TYPE_MCONTROL => tdata1 & 0b101_1111 == 0,
+        // No mode.
+        TYPE_ICOUNT | TYPE_ITRIGGER | TYPE_ETRIGGER => tdata1 & (1 << 9 | 1 << 7 | 1 << 6) == 0,
+        TYPE_DISABLED => true,
+        _ => false,
+    }
+}
+
+/// The number of topmost bits of `tdata1` whose position depends on XLEN.
+fn tdata1_xlen_bits(trigger_type: u32) -> u32 {
+    match trigger_type {
+        // type, dmode and maskmax
+        TYPE_MCONTROL => 11,
+        // type, dmode and hit
+        TYPE_ITRIGGER | TYPE_ETRIGGER => 6,
+        // type and dmode
+        _ => 5,
+    }
+}
+
+/// Convert the `tdata1` value of an RV64 hart to the RV32 layout.
+pub(super) fn tdata1_from_rv64(tdata1: u64) -> u32 {
+    let low_mask = u32::MAX >> tdata1_xlen_bits((tdata1 >> 60) as u32);
+
+    ((tdata1 >> 32) as u32 & !low_mask) | (tdata1 as u32 & low_mask)
+}
+
+/// Convert a `tdata1` value in the RV32 layout to the layout of an RV64 hart.
+pub(super) fn tdata1_to_rv64(tdata1: u32) -> u64 {
+    let low_mask = u32::MAX >> tdata1_xlen_bits(tdata1 >> 28);
+
+    ((tdata1 & !low_mask) as u64) << 32 | (tdata1 & low_mask) as u64
+}
+
+/// The `tdata1` and `tdata2` values of the triggers for a watchpoint.
+///
+/// A watchpoint with a value uses a chain of two triggers, the first one matching the address,
+/// and the second one matching the data value.
+pub(super) fn watchpoint_triggers(config: &WatchpointConfig, address: u64) -> Vec<(u32, u64)> {
+    let mut address_trigger = Mcontrol(0);
+    address_trigger.set_type(TYPE_MCONTROL);
+    address_trigger.set_dmode(true);
+    address_trigger.set_action(ACTION_DEBUG_MODE);
+    address_trigger.set_m(true);
+    address_trigger.set_s(true);
+    address_trigger.set_u(true);
+    address_trigger.set_load(config.access != WatchpointAccess::Write);
+    address_trigger.set_store(config.access != WatchpointAccess::Read);
+
+    // Larger accesses are matched using a naturally aligned power-of-2 range,
+    // which is encoded in the lowest bits of the address.
+    let address_value = match config.size {
+        WatchpointSize::Byte => address,
+        size => {
+            address_trigger.set_match(1);
+            address | (size.bytes() / 2 - 1)
+        }
+    };
+
+    let value = match config.value {
+        Some(value) => value,
+        None => return vec![(address_trigger.0, address_value)],
+    };
+
+    address_trigger.set_chain(true);
+
+    let mut value_trigger = Mcontrol(address_trigger.0);
+    value_trigger.set_chain(false);
+    value_trigger.set_match(0);
+    value_trigger.set_select(true);
+    // The loaded value is only available after the access.
+    value_trigger.set_timing(true);
+
+    let value = match config.size {
+        WatchpointSize::Byte => {
+            value_trigger.set_sizelo(1);
+            value & 0xff
+        }
+        WatchpointSize::HalfWord => {
+            value_trigger.set_sizelo(2);
+            value & 0xffff
+        }
+        WatchpointSize::Word => {
+            value_trigger.set_sizelo(3);
+            value
+        }
+    };
+
+    vec![
+        (address_trigger.0, address_value),
+        (value_trigger.0, value as u64),
+    ]
+}
+
+/// The `tdata1` value of an icount trigger, which enters Debug Mode after `count` instructions.
+pub(super) fn icount_tdata1(count: u32) -> u32 {
+    let mut icount = Icount(0);
+    icount.set_type(TYPE_ICOUNT);
+    icount.set_dmode(true);
+    icount.set_count(count);
+    icount.set_m(true);
+    icount.set_s(true);
+    icount.set_u(true);
+    icount.set_action(ACTION_DEBUG_MODE);
+    icount.0
+}
+
+/// The `tdata1` value of an itrigger or etrigger, which enters Debug Mode for the causes set in `tdata2`.
+pub(super) fn trap_tdata1(trigger_type: u32) -> u32 {
+    let mut trigger = TrapTrigger(0);
+    trigger.set_type(trigger_type);
+    trigger.set_dmode(true);
+    trigger.set_m(true);
+    trigger.set_s(true);
+    trigger.set_u(true);
+    trigger.set_action(ACTION_DEBUG_MODE);
+    trigger.0
+}
+
+/// The trigger type and the interrupt or exception causes used to catch the given condition.
+pub(super) fn vector_catch_causes(condition: VectorCatch) -> Option<(u32, u32)> {
+    // Exception causes, see the RISC-V privileged specification, 3.1.15
+    const MISALIGNED: u32 = 1 << 0 | 1 << 4 | 1 << 6;
+    const ACCESS_FAULT: u32 = 1 << 1 | 1 << 5 | 1 << 7;
+    const ILLEGAL_INSTRUCTION: u32 = 1 << 2;
+    const PAGE_FAULT: u32 = 1 << 12 | 1 << 13 | 1 << 15;
+
+    match condition {
+        VectorCatch::HardFault => Some((
+            TYPE_ETRIGGER,
+            MISALIGNED | ACCESS_FAULT | ILLEGAL_INSTRUCTION | PAGE_FAULT,
+        )),
+        VectorCatch::MemoryManagement => Some((TYPE_ETRIGGER, PAGE_FAULT)),
+        VectorCatch::BusFault => Some((TYPE_ETRIGGER, ACCESS_FAULT)),
+        VectorCatch::StateError => Some((TYPE_ETRIGGER, ILLEGAL_INSTRUCTION)),
+        VectorCatch::CheckError => Some((TYPE_ETRIGGER, MISALIGNED)),
+        VectorCatch::Interrupt => Some((TYPE_ITRIGGER, u32::MAX)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        icount_tdata1, tdata1_from_rv64, tdata1_to_rv64, trap_tdata1, trigger_unused,
+        watchpoint_triggers, TYPE_ETRIGGER,
+    };
+    use crate::{WatchpointAccess, WatchpointConfig, WatchpointSize};
+
+    #[test]
+    fn address_watchpoint() {
+        let config = WatchpointConfig::new(0x8000_0010, WatchpointAccess::Write);
+
+        assert_eq!(
+            watchpoint_triggers(&config, 0x8000_0010),
+            vec![(0x2800_10da, 0x8000_0011)]
+        );
+    }
+
+    #[test]
+    fn value_watchpoint() {
+        let config = WatchpointConfig::new(0x8000_0010, WatchpointAccess::Read)
+            .with_size(WatchpointSize::Byte)
+            .with_value(0x1234);
+
+        assert_eq!(
+            watchpoint_triggers(&config, 0x8000_0010),
+            vec![(0x2800_1859, 0x8000_0010), (0x280d_1059, 0x34)]
+        );
+    }
+
+    #[test]
+    fn unused_triggers() {
+        assert!(trigger_unused(0x2800_0000));
+        assert!(!trigger_unused(0x2800_10da));
+        assert!(!trigger_unused(icount_tdata1(1)));
+        assert!(trigger_unused(0xf000_0000));
+        assert!(!trigger_unused(0));
+    }
+
+    #[test]
+    fn rv64_tdata1() {
+        // mcontrol, with maskmax = 63
+        assert_eq!(tdata1_to_rv64(0x2fe0_10da), 0x2fe0_0000_0000_10da);
+        assert_eq!(tdata1_from_rv64(0x2fe0_0000_0000_10da), 0x2fe0_10da);
+
+        // icount
+        let icount = icount_tdata1(1);
+        assert_eq!(tdata1_to_rv64(icount), 0x3800_0000_0000_06c1);
+        assert_eq!(tdata1_from_rv64(tdata1_to_rv64(icount)), icount);
+
+        // etrigger, with hit set
+        let etrigger = trap_tdata1(TYPE_ETRIGGER) | 1 << 26;
+        assert_eq!(tdata1_to_rv64(etrigger), 0x5c00_0000_0000_02c1);
+        assert_eq!(tdata1_from_rv64(tdata1_to_rv64(etrigger)), etrigger);
+    }
+}
@@ -0,0 +1,55 @@
+//! Encoding of the Xtensa instructions executed by the debug module.
+//!
+//! All of them use the 24 bit format, which is written to the low bits of DIR0.
+
+/// Return from the debug exception, which resumes the core.
+pub const RFDO: u32 = 0xf1e000;
+
+/// Assemble a `rsr` instruction, which reads the special register `sr` into `at`.
+pub const fn rsr(sr: u8, at: u8) -> u32 {
+    0x030000 | (sr as u32) << 8 | ((at & 0xf) as u32) << 4
+}
+
+/// Assemble a `wsr` instruction, which writes `at` to the special register `sr`.
+pub const fn wsr(sr: u8, at: u8) -> u32 {
+    0x130000 | (sr as u32) << 8 | ((at & 0xf) as u32) << 4
+}
+
+/// Assemble a `lddr32.p` instruction, which loads the word at the address in `at`
+/// into DDR, and increments `at` by 4.
+pub const fn lddr32_p(at: u8) -> u32 {
+    0x0070e0 | ((at & 0xf) as u32) << 8
+}
+
+/// Assemble a `sddr32.p` instruction, which stores DDR to the address in `at`,
+/// and increments `at` by 4.
+pub const fn sddr32_p(at: u8) -> u32 {
+    0x0070f0 | ((at & 0xf) as u32) << 8
+}
+
+#[cfg(test)]
+mod test {
+    use super::{lddr32_p, rsr, sddr32_p, wsr};
+
+    #[test]
+    fn assemble_special_register_access() {
+        // Assembly output of 'rsr a3, ddr'
+        //
+        // ddr number: 104
+        assert_eq!(rsr(104, 3), 0x036830);
+
+        // Assembly output of 'wsr a5, epc6'
+        //
+        // epc6 number: 182
+        assert_eq!(wsr(182, 5), 0x13b650);
+    }
+
+    #[test]
+    fn assemble_ddr_memory_access() {
+        // Assembly output of 'lddr32.p a3'
+        assert_eq!(lddr32_p(3), 0x0073e0);
+
+        // Assembly output of 'sddr32.p a3'
+        assert_eq!(sddr32_p(3), 0x0073f0);
+    }
+}
@@ -0,0 +1,479 @@
+//! Debug Module Communication
+//!
+//! This module implements communication with the Xtensa Debug Module (XDM),
+//! which is used for on-chip debugging (OCD) of Xtensa cores.
+//!
+//! While the core is halted, the debug module can execute single instructions on the core.
+//! Data is transferred between the debugger and the core through the Debug Data Register (DDR),
+//! which is also available to the core as the special register `DDR`.
+
+use std::time::{Duration, Instant};
+
+use super::{
+    assembly::{lddr32_p, rsr, sddr32_p, wsr, RFDO},
+    xdm::{
+        DebugRegister, Xdm, DCR_DEBUGINTERRUPT, DCR_ENABLEOCD, DSR_EXECBUSY, DSR_EXECDONE,
+        DSR_EXECEXCEPTION, DSR_EXECOVERRUN, DSR_STOPPED,
+    },
+};
+use crate::{probe::JTAGAccess, DebugProbeError, Error as ProbeRsError, MemoryInterface, Probe};
+
+/// The special register number of DDR.
+const DDR: u8 = 104;
+
+/// The address register which is used as scratch register by the debugger.
+///
+/// Its value is saved and restored by every operation that uses it.
+const SCRATCH: u8 = 3;
+
+/// Timeout for the execution of a single instruction.
+const EXECUTION_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Something error occurered when working with the Xtensa core.
+#[derive(thiserror::Error, Debug)]
+pub enum XtensaError {
+    /// An error with operating the debug probe occurred.
+    #[error("Debug Probe Error")]
+    DebugProbe(#[from] DebugProbeError),
+    /// The debug module did not respond, the OCDID register read the given value.
+    #[error("No Xtensa debug module found, OCDID read as {0:#010x}.")]
+    DebugModuleNotFound(u32),
+    /// The core has to be halted for the requested operation.
+    #[error("The core has to be halted to execute instructions.")]
+    NotHalted,
+    /// An instruction executed by the debugger did not finish in time.
+    #[error("Timeout during execution of instruction {0:#08x}.")]
+    Timeout(u32),
+    /// An instruction executed by the debugger caused an exception.
+    #[error("Exception during execution of instruction {0:#08x}.")]
+    ExecutionException(u32),
+    /// An instruction was written while the previous one was still executing.
+    #[error("Overrun during execution of instruction {0:#08x}.")]
+    ExecutionOverrun(u32),
+    /// A word access to an address which is not word aligned was requested.
+    #[error("Address {0:#010x} is not aligned for a word access.")]
+    UnalignedAccess(u64),
+    /// The address can not be accessed by the 32 bit core.
+    #[error("Address {0:#010x} is out of range.")]
+    AddressOutOfRange(u64),
+}
+
+impl From<XtensaError> for ProbeRsError {
+    fn from(err: XtensaError) -> Self {
+        match err {
+            XtensaError::DebugProbe(e) => e.into(),
+            other => ProbeRsError::ArchitectureSpecific(Box::new(other)),
+        }
+    }
+}
+
+/// A interface that implements controls for Xtensa cores.
+#[derive(Debug)]
+pub struct XtensaCommunicationInterface {
+    /// The debug module, accessed through the JTAG TAP of the core.
+    xdm: Xdm,
+}
+
+impl XtensaCommunicationInterface {
+    /// Creates a new Xtensa communication interface with a given probe driver.
+    pub fn new(probe: Box<dyn JTAGAccess>) -> Result<Self, (Box<dyn JTAGAccess>, DebugProbeError)> {
+        let xdm = Xdm::new(probe).map_err(|(probe, e)| match e {
+            XtensaError::DebugProbe(err) => (probe, err),
+            other_error => (
+                probe,
+                DebugProbeError::ArchitectureSpecific(Box::new(other_error)),
+            ),
+        })?;
+
+        Ok(Self { xdm })
+    }
+
+    /// Deassert the target reset.
+    pub fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.xdm.target_reset_deassert()
+    }
+
+    /// Destruct the interface and return the stored probe driver.
+    pub fn close(self) -> Probe {
+        Probe::from_attached_probe(self.xdm.probe.into_probe())
+    }
+
+    /// Check if the core is halted in the debug exception handler.
+    pub fn is_halted(&mut self) -> Result<bool, XtensaError> {
+        let dsr = self.xdm.read_register(DebugRegister::Dsr)?;
+
+        Ok(dsr & DSR_STOPPED != 0)
+    }
+
+    /// Request the core to halt, by raising a debug interrupt.
+    ///
+    /// The interrupt stays pending until the core is resumed,
+    /// so the core also halts directly after a reset.
+    pub fn halt(&mut self) -> Result<(), XtensaError> {
+        self.xdm
+            .write_register(DebugRegister::DcrSet, DCR_DEBUGINTERRUPT)
+    }
+
+    /// Wait until the core is halted.
+    pub fn wait_for_halted(&mut self, timeout: Duration) -> Result<(), XtensaError> {
+        let start = Instant::now();
+
+        while start.elapsed() < timeout {
+            if self.is_halted()? {
+                return Ok(());
+            }
+        }
+
+        Err(XtensaError::DebugProbe(DebugProbeError::Timeout))
+    }
+
+    /// Resume the core by returning from the debug exception.
+    pub fn resume(&mut self) -> Result<(), XtensaError> {
+        self.xdm
+            .write_register(DebugRegister::DcrClr, DCR_DEBUGINTERRUPT)?;
+
+        self.clear_execution_status()?;
+
+        // The core leaves the debug exception handler, so the instruction never reports completion.
+        self.xdm.write_register(DebugRegister::Dir0Exec, RFDO)
+    }
+
+    /// Reset the core, and halt it directly after the reset if `halt` is set.
+    pub fn reset(&mut self, halt: bool) -> Result<(), XtensaError> {
+        if halt {
+            self.halt()?;
+        } else {
+            self.xdm
+                .write_register(DebugRegister::DcrClr, DCR_DEBUGINTERRUPT)?;
+        }
+
+        self.xdm.set_core_reset(true)?;
+        self.xdm.set_core_reset(false)?;
+
+        // Make sure the debug module is still enabled after the reset.
+        self.xdm
+            .write_register(DebugRegister::DcrSet, DCR_ENABLEOCD)
+    }
+
+    /// Execute a single instruction on the halted core, and wait until it is done.
+    pub fn execute_instruction(&mut self, instruction: u32) -> Result<(), XtensaError> {
+        self.xdm
+            .write_register(DebugRegister::Dir0Exec, instruction)?;
+
+        let start = Instant::now();
+
+        loop {
+            let dsr = self.xdm.read_register(DebugRegister::Dsr)?;
+
+            if dsr & DSR_STOPPED == 0 {
+                return Err(XtensaError::NotHalted);
+            }
+
+            self.check_execution_status(dsr, instruction)?;
+
+            if dsr & DSR_EXECDONE != 0 && dsr & DSR_EXECBUSY == 0 {
+                return self.clear_execution_status();
+            }
+
+            if start.elapsed() > EXECUTION_TIMEOUT {
+                return Err(XtensaError::Timeout(instruction));
+            }
+        }
+    }
+
+    /// Return an error if the debug status register reports an exception or an overrun,
+    /// after clearing the status.
+    fn check_execution_status(&mut self, dsr: u32, instruction: u32) -> Result<(), XtensaError> {
+        if dsr & DSR_EXECEXCEPTION != 0 {
+            self.clear_execution_status()?;
+            return Err(XtensaError::ExecutionException(instruction));
+        }
+
+        if dsr & DSR_EXECOVERRUN != 0 {
+            self.clear_execution_status()?;
+            return Err(XtensaError::ExecutionOverrun(instruction));
+        }
+
+        Ok(())
+    }
+
+    /// Clear the sticky execution status bits in the debug status register.
+    fn clear_execution_status(&mut self) -> Result<(), XtensaError> {
+        self.xdm.write_register(
+            DebugRegister::Dsr,
+            DSR_EXECDONE | DSR_EXECEXCEPTION | DSR_EXECOVERRUN,
+        )
+    }
+
+    /// Read the address register `register` (`a0` to `a15`) of the current register window.
+    pub fn read_address_register(&mut self, register: u8) -> Result<u32, XtensaError> {
+        self.execute_instruction(wsr(DDR, register))?;
+
+        self.xdm.read_register(DebugRegister::Ddr)
+    }
+
+    /// Write the address register `register` (`a0` to `a15`) of the current register window.
+    pub fn write_address_register(&mut self, register: u8, value: u32) -> Result<(), XtensaError> {
+        self.xdm.write_register(DebugRegister::Ddr, value)?;
+
+        self.execute_instruction(rsr(DDR, register))
+    }
+
+    /// Read the special register with the number `register`.
+    pub fn read_special_register(&mut self, register: u8) -> Result<u32, XtensaError> {
+        self.with_scratch_register(|interface| {
+            interface.execute_instruction(rsr(register, SCRATCH))?;
+
+            interface.read_address_register(SCRATCH)
+        })
+    }
+
+    /// Write the special register with the number `register`.
+    pub fn write_special_register(&mut self, register: u8, value: u32) -> Result<(), XtensaError> {
+        self.with_scratch_register(|interface| {
+            interface.write_address_register(SCRATCH, value)?;
+
+            interface.execute_instruction(wsr(register, SCRATCH))
+        })
+    }
+
+    /// Run `operation`, which may overwrite the scratch register, and restore the register afterwards.
+    fn with_scratch_register<T>(
+        &mut self,
+        operation: impl FnOnce(&mut Self) -> Result<T, XtensaError>,
+    ) -> Result<T, XtensaError> {
+        let saved = self.read_address_register(SCRATCH)?;
+
+        let result = operation(self);
+
+        self.write_address_register(SCRATCH, saved)?;
+
+        result
+    }
+
+    /// Read consecutive words, starting at the word aligned `address`.
+    ///
+    /// `lddr32.p` loads a word into DDR and increments the address, so after it was
+    /// executed once, every read of DDR through `DDREXEC` returns a word and loads the next one.
+    fn read_words(&mut self, address: u32, data: &mut [u32]) -> Result<(), XtensaError> {
+        let (last, words) = match data.split_last_mut() {
+            Some(split) => split,
+            None => return Ok(()),
+        };
+
+        self.with_scratch_register(|interface| {
+            interface.write_address_register(SCRATCH, address)?;
+
+            let instruction = lddr32_p(SCRATCH);
+            interface.execute_instruction(instruction)?;
+
+            for word in words.iter_mut() {
+                *word = interface.xdm.read_register(DebugRegister::DdrExec)?;
+            }
+
+            // Reading the last word without executing the load again
+            // avoids an access beyond the requested range.
+            *last = interface.xdm.read_register(DebugRegister::Ddr)?;
+
+            let dsr = interface.xdm.read_register(DebugRegister::Dsr)?;
+            interface.check_execution_status(dsr, instruction)
+        })
+    }
+
+    /// Write consecutive words, starting at the word aligned `address`.
+    ///
+    /// Every write of DDR through `DDREXEC` executes the `sddr32.p` in DIR0,
+    /// which stores the word and increments the address.
+    fn write_words(&mut self, address: u32, data: &[u32]) -> Result<(), XtensaError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        self.with_scratch_register(|interface| {
+            interface.write_address_register(SCRATCH, address)?;
+
+            let instruction = sddr32_p(SCRATCH);
+            interface
+                .xdm
+                .write_register(DebugRegister::Dir0, instruction)?;
+
+            for word in data {
+                interface
+                    .xdm
+                    .write_register(DebugRegister::DdrExec, *word)?;
+            }
+
+            let dsr = interface.xdm.read_register(DebugRegister::Dsr)?;
+            interface.check_execution_status(dsr, instruction)
+        })
+    }
+
+    /// Read bytes from an arbitrary address, using the word accesses which cover them.
+    fn read_bytes(&mut self, address: u64, data: &mut [u8]) -> Result<(), XtensaError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let (start, mut words) = self.covering_words(address, data.len())?;
+        self.read_words(start, &mut words)?;
+
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        let offset = (address - start as u64) as usize;
+
+        data.copy_from_slice(&bytes[offset..offset + data.len()]);
+
+        Ok(())
+    }
+
+    /// Write bytes to an arbitrary address, using a read-modify-write of the words which cover them.
+    fn write_bytes(&mut self, address: u64, data: &[u8]) -> Result<(), XtensaError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let (start, mut words) = self.covering_words(address, data.len())?;
+
+        // Only the first and the last word can contain bytes which are not overwritten.
+        let last = words.len() - 1;
+        self.read_words(start, &mut words[..1])?;
+        if last > 0 {
+            self.read_words(start + last as u32 * 4, &mut words[last..])?;
+        }
+
+        let mut bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        let offset = (address - start as u64) as usize;
+        bytes[offset..offset + data.len()].copy_from_slice(data);
+
+        let words: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+
+        self.write_words(start, &words)
+    }
+
+    /// The start address and a buffer for the words which cover `len` bytes at `address`.
+    fn covering_words(&self, address: u64, len: usize) -> Result<(u32, Vec<u32>), XtensaError> {
+        let end = address + len as u64;
+
+        if end > u32::MAX as u64 + 1 {
+            return Err(XtensaError::AddressOutOfRange(address));
+        }
+
+        let start = address & !0b11;
+        let word_count = (end - start + 3) / 4;
+
+        Ok((start as u32, vec![0; word_count as usize]))
+    }
+
+    fn valid_address(&self, address: u64) -> Result<u32, XtensaError> {
+        address
+            .try_into()
+            .map_err(|_| XtensaError::AddressOutOfRange(address))
+    }
+}
+
+impl MemoryInterface for XtensaCommunicationInterface {
+    fn supports_native_64bit_access(&mut self) -> bool {
+        false
+    }
+
+    fn read_word_64(&mut self, address: u64) -> Result<u64, ProbeRsError> {
+        let mut data = [0u8; 8];
+        self.read_8(address, &mut data)?;
+
+        Ok(u64::from_le_bytes(data))
+    }
+
+    fn read_word_32(&mut self, address: u64) -> Result<u32, ProbeRsError> {
+        let mut data = [0u32; 1];
+        self.read_32(address, &mut data)?;
+
+        Ok(data[0])
+    }
+
+    fn read_word_8(&mut self, address: u64) -> Result<u8, ProbeRsError> {
+        let mut data = [0u8; 1];
+        self.read_8(address, &mut data)?;
+
+        Ok(data[0])
+    }
+
+    fn read_64(&mut self, address: u64, data: &mut [u64]) -> Result<(), ProbeRsError> {
+        log::debug!("read_64 from {:#08x}", address);
+
+        for (i, d) in data.iter_mut().enumerate() {
+            *d = self.read_word_64(address + (i as u64 * 8))?;
+        }
+
+        Ok(())
+    }
+
+    fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), ProbeRsError> {
+        log::debug!("read_32 from {:#08x}", address);
+
+        if address % 4 != 0 {
+            return Err(XtensaError::UnalignedAccess(address).into());
+        }
+
+        let address = self.valid_address(address)?;
+        self.read_words(address, data)?;
+
+        Ok(())
+    }
+
+    fn read_8(&mut self, address: u64, data: &mut [u8]) -> Result<(), ProbeRsError> {
+        log::debug!("read_8 from {:#08x}", address);
+
+        self.read_bytes(address, data)?;
+
+        Ok(())
+    }
+
+    fn write_word_64(&mut self, address: u64, data: u64) -> Result<(), ProbeRsError> {
+        self.write_8(address, &data.to_le_bytes())
+    }
+
+    fn write_word_32(&mut self, address: u64, data: u32) -> Result<(), ProbeRsError> {
+        self.write_32(address, &[data])
+    }
+
+    fn write_word_8(&mut self, address: u64, data: u8) -> Result<(), ProbeRsError> {
+        self.write_8(address, &[data])
+    }
+
+    fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), ProbeRsError> {
+        log::debug!("write_64 to {:#08x}", address);
+
+        for (i, d) in data.iter().enumerate() {
+            self.write_word_64(address + (i as u64 * 8), *d)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), ProbeRsError> {
+        log::debug!("write_32 to {:#08x}", address);
+
+        if address % 4 != 0 {
+            return Err(XtensaError::UnalignedAccess(address).into());
+        }
+
+        let address = self.valid_address(address)?;
+        self.write_words(address, data)?;
+
+        Ok(())
+    }
+
+    fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), ProbeRsError> {
+        log::debug!("write_8 to {:#08x}", address);
+
+        self.write_bytes(address, data)?;
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), ProbeRsError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,435 @@
+//! All the interface bits for Xtensa.
+
+use crate::core::{Architecture, BreakpointCause, CoreInformation, RegisterFile, RegisterValue};
+use crate::{
+    CoreInterface, CoreStatus, CoreType, Error, HaltReason, InstructionSet, MemoryInterface,
+    RegisterId,
+};
+use communication_interface::{XtensaCommunicationInterface, XtensaError};
+use register::{PC_ID, PS_ID, SPECIAL_REGISTERS_START, XTENSA_REGISTERS};
+use std::time::Duration;
+
+mod assembly;
+mod register;
+mod xdm;
+
+pub mod communication_interface;
+pub mod sequences;
+
+/// Enables the instruction breakpoints, one bit per breakpoint unit.
+const IBREAKENABLE: u8 = 96;
+/// The address of the first instruction breakpoint unit.
+const IBREAKA0: u8 = 128;
+/// The exception PC of interrupt level `n`, `EPCn`, is the special register `EPC_BASE + n`.
+const EPC_BASE: u8 = 176;
+/// The exception processor state of interrupt level `n`, `EPSn`, is the special register `EPS_BASE + n`.
+const EPS_BASE: u8 = 192;
+/// The cause of the last debug exception.
+const DEBUGCAUSE: u8 = 233;
+/// The instruction counter, which raises a debug exception when it overflows.
+const ICOUNT: u8 = 236;
+/// Instructions are only counted below this interrupt level.
+const ICOUNTLEVEL: u8 = 237;
+
+/// The number of instruction breakpoint units of the ESP32 and ESP32-S3.
+const NUM_IBREAK_UNITS: u32 = 2;
+
+/// `DEBUGCAUSE` bits
+const DEBUGCAUSE_ICOUNT: u32 = 1 << 0;
+const DEBUGCAUSE_IBREAK: u32 = 1 << 1;
+const DEBUGCAUSE_DBREAK: u32 = 1 << 2;
+const DEBUGCAUSE_BREAK: u32 = 1 << 3;
+const DEBUGCAUSE_BREAKN: u32 = 1 << 4;
+const DEBUGCAUSE_DEBUGINT: u32 = 1 << 5;
+
+/// A interface to operate Xtensa cores.
+pub struct Xtensa<'probe> {
+    interface: &'probe mut XtensaCommunicationInterface,
+    state: &'probe mut XtensaState,
+    /// The interrupt level of the debug exception.
+    debug_level: u8,
+}
+
+impl<'probe> Xtensa<'probe> {
+    /// Create a new Xtensa interface, for a core with the given debug interrupt level.
+    pub fn new(
+        interface: &'probe mut XtensaCommunicationInterface,
+        state: &'probe mut XtensaState,
+        debug_level: u8,
+    ) -> Self {
+        Self {
+            interface,
+            state,
+            debug_level,
+        }
+    }
+
+    fn read_special_register(&mut self, register: u8) -> Result<u32, XtensaError> {
+        self.interface.read_special_register(register)
+    }
+
+    fn write_special_register(&mut self, register: u8, value: u32) -> Result<(), XtensaError> {
+        self.interface.write_special_register(register, value)
+    }
+
+    /// The number of the special register which saves the program counter on a debug exception.
+    fn debug_epc(&self) -> u8 {
+        EPC_BASE + self.debug_level
+    }
+
+    /// The number of the special register which saves the processor state on a debug exception.
+    fn debug_eps(&self) -> u8 {
+        EPS_BASE + self.debug_level
+    }
+
+    fn read_pc(&mut self) -> Result<u32, XtensaError> {
+        self.read_special_register(self.debug_epc())
+    }
+
+    /// The enabled instruction breakpoints, including those which are suspended
+    /// while breakpoints are disabled.
+    fn breakpoint_mask(&mut self) -> Result<u32, XtensaError> {
+        if self.state.hw_breakpoints_enabled {
+            self.read_special_register(IBREAKENABLE)
+        } else {
+            Ok(self.state.suspended_breakpoints)
+        }
+    }
+
+    fn write_breakpoint_mask(&mut self, mask: u32) -> Result<(), XtensaError> {
+        if self.state.hw_breakpoints_enabled {
+            self.write_special_register(IBREAKENABLE, mask)
+        } else {
+            self.state.suspended_breakpoints = mask;
+            Ok(())
+        }
+    }
+
+    /// Determine why the core entered the debug exception.
+    fn halt_reason(&mut self) -> Result<HaltReason, XtensaError> {
+        let cause = self.read_special_register(DEBUGCAUSE)?;
+
+        let reasons = [
+            (DEBUGCAUSE_ICOUNT, HaltReason::Step),
+            (
+                DEBUGCAUSE_IBREAK,
+                HaltReason::Breakpoint(BreakpointCause::Hardware),
+            ),
+            (DEBUGCAUSE_DBREAK, HaltReason::Watchpoint),
+            (
+                DEBUGCAUSE_BREAK | DEBUGCAUSE_BREAKN,
+                HaltReason::Breakpoint(BreakpointCause::Software),
+            ),
+            (DEBUGCAUSE_DEBUGINT, HaltReason::Request),
+        ];
+
+        let mut matching = reasons.iter().filter(|(mask, _)| cause & mask != 0);
+
+        let reason = match (matching.next(), matching.next()) {
+            (Some((_, reason)), None) => *reason,
+            (Some(_), Some(_)) => HaltReason::Multiple,
+            (None, _) => HaltReason::Unknown,
+        };
+
+        Ok(reason)
+    }
+}
+
+impl<'probe> CoreInterface for Xtensa<'probe> {
+    fn wait_for_core_halted(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.interface.wait_for_halted(timeout)?;
+
+        Ok(())
+    }
+
+    fn core_halted(&mut self) -> Result<bool, Error> {
+        Ok(self.interface.is_halted()?)
+    }
+
+    fn status(&mut self) -> Result<CoreStatus, Error> {
+        if self.interface.is_halted()? {
+            Ok(CoreStatus::Halted(self.halt_reason()?))
+        } else {
+            Ok(CoreStatus::Running)
+        }
+    }
+
+    fn halt(&mut self, timeout: Duration) -> Result<CoreInformation, Error> {
+        self.interface.halt()?;
+        self.interface.wait_for_halted(timeout)?;
+
+        let pc = self.read_pc()?;
+
+        Ok(CoreInformation { pc: pc.into() })
+    }
+
+    fn run(&mut self) -> Result<(), Error> {
+        // Step over a breakpoint on the current instruction first, so it doesn't halt the core again.
+        if matches!(
+            self.status()?,
+            CoreStatus::Halted(HaltReason::Breakpoint(_))
+        ) {
+            self.step()?;
+        }
+
+        self.interface.resume()?;
+
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        self.interface.reset(false)?;
+
+        Ok(())
+    }
+
+    fn reset_and_halt(&mut self, timeout: Duration) -> Result<CoreInformation, Error> {
+        self.interface.reset(true)?;
+        self.interface.wait_for_halted(timeout)?;
+
+        let pc = self.read_pc()?;
+
+        Ok(CoreInformation { pc: pc.into() })
+    }
+
+    fn step(&mut self) -> Result<CoreInformation, Error> {
+        let cause = self.read_special_register(DEBUGCAUSE)?;
+
+        // The debug exception of a break instruction is taken on the instruction itself,
+        // so it is skipped instead of being executed again.
+        let break_size = if cause & DEBUGCAUSE_BREAK != 0 {
+            Some(3)
+        } else if cause & DEBUGCAUSE_BREAKN != 0 {
+            Some(2)
+        } else {
+            None
+        };
+
+        if let Some(size) = break_size {
+            if self.state.hw_breakpoints_enabled {
+                let pc = self.read_pc()?.wrapping_add(size);
+                self.write_special_register(self.debug_epc(), pc)?;
+
+                return Ok(CoreInformation { pc: pc.into() });
+            }
+        }
+
+        // An instruction breakpoint on the current instruction would halt the core before the step.
+        let breakpoints_enabled = self.state.hw_breakpoints_enabled;
+        if breakpoints_enabled {
+            self.enable_breakpoints(false)?;
+        }
+
+        // The counter is incremented for every instruction executed below the debug level,
+        // and raises the debug exception when it overflows after the step.
+        self.write_special_register(ICOUNTLEVEL, self.debug_level as u32)?;
+        self.write_special_register(ICOUNT, -2i32 as u32)?;
+
+        self.interface.resume()?;
+        self.interface.wait_for_halted(Duration::from_millis(100))?;
+
+        self.write_special_register(ICOUNTLEVEL, 0)?;
+
+        if breakpoints_enabled {
+            self.enable_breakpoints(true)?;
+        }
+
+        let pc = self.read_pc()?;
+
+        Ok(CoreInformation { pc: pc.into() })
+    }
+
+    fn read_core_reg(&mut self, address: RegisterId) -> Result<RegisterValue, Error> {
+        let value = match address.0 {
+            register @ 0..=15 => self.interface.read_address_register(register as u8)?,
+            PC_ID => self.read_pc()?,
+            PS_ID => self.read_special_register(self.debug_eps())?,
+            register
+                if (SPECIAL_REGISTERS_START..SPECIAL_REGISTERS_START + 256).contains(&register) =>
+            {
+                self.read_special_register((register - SPECIAL_REGISTERS_START) as u8)?
+            }
+            _ => {
+                return Err(Error::Other(anyhow::anyhow!(
+                    "Unknown register {:?}",
+                    address
+                )))
+            }
+        };
+
+        Ok(RegisterValue::U32(value))
+    }
+
+    fn write_core_reg(&mut self, address: RegisterId, value: RegisterValue) -> Result<(), Error> {
+        let value: u32 = value.try_into()?;
+
+        match address.0 {
+            register @ 0..=15 => self
+                .interface
+                .write_address_register(register as u8, value)?,
+            PC_ID => self.write_special_register(self.debug_epc(), value)?,
+            PS_ID => self.write_special_register(self.debug_eps(), value)?,
+            register
+                if (SPECIAL_REGISTERS_START..SPECIAL_REGISTERS_START + 256).contains(&register) =>
+            {
+                self.write_special_register((register - SPECIAL_REGISTERS_START) as u8, value)?
+            }
+            _ => {
+                return Err(Error::Other(anyhow::anyhow!(
+                    "Unknown register {:?}",
+                    address
+                )))
+            }
+        }
+
+        Ok(())
+    }
+
+    fn available_breakpoint_units(&mut self) -> Result<u32, Error> {
+        Ok(NUM_IBREAK_UNITS)
+    }
+
+    fn hw_breakpoints(&mut self) -> Result<Vec<Option<u64>>, Error> {
+        let mask = self.breakpoint_mask()?;
+
+        let mut breakpoints = vec![];
+        for unit in 0..NUM_IBREAK_UNITS {
+            if mask & (1 << unit) != 0 {
+                let address = self.read_special_register(IBREAKA0 + unit as u8)?;
+                breakpoints.push(Some(address.into()));
+            } else {
+                breakpoints.push(None);
+            }
+        }
+
+        Ok(breakpoints)
+    }
+
+    fn enable_breakpoints(&mut self, state: bool) -> Result<(), Error> {
+        if state == self.state.hw_breakpoints_enabled {
+            return Ok(());
+        }
+
+        if state {
+            let mask = self.read_special_register(IBREAKENABLE)? | self.state.suspended_breakpoints;
+            self.write_special_register(IBREAKENABLE, mask)?;
+            self.state.suspended_breakpoints = 0;
+        } else {
+            self.state.suspended_breakpoints = self.read_special_register(IBREAKENABLE)?;
+            self.write_special_register(IBREAKENABLE, 0)?;
+        }
+
+        self.state.hw_breakpoints_enabled = state;
+
+        Ok(())
+    }
+
+    fn set_hw_breakpoint(&mut self, unit_index: usize, addr: u64) -> Result<(), Error> {
+        let address: u32 = addr
+            .try_into()
+            .map_err(|_| XtensaError::AddressOutOfRange(addr))?;
+
+        self.write_special_register(IBREAKA0 + unit_index as u8, address)?;
+
+        let mask = self.breakpoint_mask()?;
+        self.write_breakpoint_mask(mask | 1 << unit_index)?;
+
+        Ok(())
+    }
+
+    fn clear_hw_breakpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        let mask = self.breakpoint_mask()?;
+        self.write_breakpoint_mask(mask & !(1 << unit_index))?;
+
+        Ok(())
+    }
+
+    fn registers(&self) -> &'static RegisterFile {
+        &XTENSA_REGISTERS
+    }
+
+    fn hw_breakpoints_enabled(&self) -> bool {
+        self.state.hw_breakpoints_enabled
+    }
+
+    fn architecture(&self) -> Architecture {
+        Architecture::Xtensa
+    }
+
+    fn core_type(&self) -> CoreType {
+        CoreType::Xtensa
+    }
+
+    fn instruction_set(&mut self) -> Result<InstructionSet, Error> {
+        Ok(InstructionSet::Xtensa)
+    }
+
+    fn fpu_support(&mut self) -> Result<bool, Error> {
+        // The registers of the floating point coprocessor are not accessible yet.
+        Ok(false)
+    }
+}
+
+impl<'probe> MemoryInterface for Xtensa<'probe> {
+    fn supports_native_64bit_access(&mut self) -> bool {
+        self.interface.supports_native_64bit_access()
+    }
+    fn read_word_64(&mut self, address: u64) -> Result<u64, Error> {
+        self.interface.read_word_64(address)
+    }
+    fn read_word_32(&mut self, address: u64) -> Result<u32, Error> {
+        self.interface.read_word_32(address)
+    }
+    fn read_word_8(&mut self, address: u64) -> Result<u8, Error> {
+        self.interface.read_word_8(address)
+    }
+    fn read_64(&mut self, address: u64, data: &mut [u64]) -> Result<(), Error> {
+        self.interface.read_64(address, data)
+    }
+    fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), Error> {
+        self.interface.read_32(address, data)
+    }
+    fn read_8(&mut self, address: u64, data: &mut [u8]) -> Result<(), Error> {
+        self.interface.read_8(address, data)
+    }
+    fn write_word_64(&mut self, address: u64, data: u64) -> Result<(), Error> {
+        self.interface.write_word_64(address, data)
+    }
+    fn write_word_32(&mut self, address: u64, data: u32) -> Result<(), Error> {
+        self.interface.write_word_32(address, data)
+    }
+    fn write_word_8(&mut self, address: u64, data: u8) -> Result<(), Error> {
+        self.interface.write_word_8(address, data)
+    }
+    fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), Error> {
+        self.interface.write_64(address, data)
+    }
+    fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), Error> {
+        self.interface.write_32(address, data)
+    }
+    fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), Error> {
+        self.interface.write_8(address, data)
+    }
+    fn flush(&mut self) -> Result<(), Error> {
+        self.interface.flush()
+    }
+}
+
+#[derive(Debug)]
+/// Flags used to control the [`SpecificCoreState`](crate::core::SpecificCoreState) for Xtensa architecture
+pub struct XtensaState {
+    /// A flag to remember whether we want to use hw_breakpoints during stepping of the core.
+    hw_breakpoints_enabled: bool,
+
+    /// The instruction breakpoints which were enabled before breakpoints were disabled.
+    suspended_breakpoints: u32,
+}
+
+impl XtensaState {
+    pub(crate) fn new() -> Self {
+        Self {
+            hw_breakpoints_enabled: false,
+            suspended_breakpoints: 0,
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! The registers of Xtensa cores.
+//!
+//! Address registers `a0` to `a15` are those of the current register window,
+//! and are numbered from 0 to 15. Special registers are numbered from
+//! [`SPECIAL_REGISTERS_START`] upwards, using their special register number.
+//! The program counter and the processor state are saved by the debug exception,
+//! and are accessed through `EPC` and `EPS` of the debug level.
+
+use crate::{
+    core::{RegisterDataType, RegisterDescription, RegisterFile, RegisterKind},
+    RegisterId,
+};
+
+/// The first register ID of the special registers.
+pub(super) const SPECIAL_REGISTERS_START: u16 = 0x100;
+
+/// The register ID of the program counter, at the time the core was halted.
+pub(super) const PC_ID: u16 = 0x200;
+/// The register ID of the processor state, at the time the core was halted.
+pub(super) const PS_ID: u16 = 0x201;
+
+/// The shift amount register.
+pub(super) const SAR: u8 = 3;
+/// The index of the current register window, in units of four registers.
+pub(super) const WINDOWBASE: u8 = 72;
+/// One bit per group of four registers, set if the group belongs to a valid window.
+pub(super) const WINDOWSTART: u8 = 73;
+
+static PC: RegisterDescription = RegisterDescription {
+    name: "pc",
+    _kind: RegisterKind::PC,
+    id: RegisterId(PC_ID),
+    _type: RegisterDataType::UnsignedInteger,
+    size_in_bits: 32,
+};
+
+static A0: RegisterDescription = RegisterDescription {
+    name: "a0",
+    _kind: RegisterKind::General,
+    id: RegisterId(0),
+    _type: RegisterDataType::UnsignedInteger,
+    size_in_bits: 32,
+};
+
+static A1: RegisterDescription = RegisterDescription {
+    name: "a1",
+    _kind: RegisterKind::General,
+    id: RegisterId(1),
+    _type: RegisterDataType::UnsignedInteger,
+    size_in_bits: 32,
+};
+
+static A7: RegisterDescription = RegisterDescription {
+    name: "a7",
+    _kind: RegisterKind::General,
+    id: RegisterId(7),
+    _type: RegisterDataType::UnsignedInteger,
+    size_in_bits: 32,
+};
+
+pub(super) static XTENSA_REGISTERS: RegisterFile = RegisterFile {
+    platform_registers: &[
+        RegisterDescription {
+            name: "a0",
+            _kind: RegisterKind::General,
+            id: RegisterId(0),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a1",
+            _kind: RegisterKind::General,
+            id: RegisterId(1),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a2",
+            _kind: RegisterKind::General,
+            id: RegisterId(2),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a3",
+            _kind: RegisterKind::General,
+            id: RegisterId(3),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a4",
+            _kind: RegisterKind::General,
+            id: RegisterId(4),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a5",
+            _kind: RegisterKind::General,
+            id: RegisterId(5),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a6",
+            _kind: RegisterKind::General,
+            id: RegisterId(6),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a7",
+            _kind: RegisterKind::General,
+            id: RegisterId(7),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a8",
+            _kind: RegisterKind::General,
+            id: RegisterId(8),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a9",
+            _kind: RegisterKind::General,
+            id: RegisterId(9),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a10",
+            _kind: RegisterKind::General,
+            id: RegisterId(10),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a11",
+            _kind: RegisterKind::General,
+            id: RegisterId(11),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a12",
+            _kind: RegisterKind::General,
+            id: RegisterId(12),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a13",
+            _kind: RegisterKind::General,
+            id: RegisterId(13),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a14",
+            _kind: RegisterKind::General,
+            id: RegisterId(14),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a15",
+            _kind: RegisterKind::General,
+            id: RegisterId(15),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+    ],
+
+    program_counter: &PC,
+
+    stack_pointer: &A1,
+
+    return_address: &A0,
+
+    // The windowed ABI uses a7 as frame pointer, if one is needed.
+    frame_pointer: &A7,
+
+    // Arguments are passed in a2 to a7 of the called function.
+    argument_registers: &[
+        RegisterDescription {
+            name: "a2",
+            _kind: RegisterKind::General,
+            id: RegisterId(2),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a3",
+            _kind: RegisterKind::General,
+            id: RegisterId(3),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a4",
+            _kind: RegisterKind::General,
+            id: RegisterId(4),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a5",
+            _kind: RegisterKind::General,
+            id: RegisterId(5),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a6",
+            _kind: RegisterKind::General,
+            id: RegisterId(6),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a7",
+            _kind: RegisterKind::General,
+            id: RegisterId(7),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+    ],
+
+    // Results are returned in a2 to a5.
+    result_registers: &[
+        RegisterDescription {
+            name: "a2",
+            _kind: RegisterKind::General,
+            id: RegisterId(2),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a3",
+            _kind: RegisterKind::General,
+            id: RegisterId(3),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a4",
+            _kind: RegisterKind::General,
+            id: RegisterId(4),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "a5",
+            _kind: RegisterKind::General,
+            id: RegisterId(5),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+    ],
+
+    msp: None,
+
+    psp: None,
+
+    psr: None,
+
+    fp_status: None,
+
+    fp_registers: None,
+
+    fp_double_registers: None,
+
+    other: &[
+        RegisterDescription {
+            name: "ps",
+            _kind: RegisterKind::General,
+            id: RegisterId(PS_ID),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "sar",
+            _kind: RegisterKind::General,
+            id: RegisterId(SPECIAL_REGISTERS_START + SAR as u16),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "windowbase",
+            _kind: RegisterKind::General,
+            id: RegisterId(SPECIAL_REGISTERS_START + WINDOWBASE as u16),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+        RegisterDescription {
+            name: "windowstart",
+            _kind: RegisterKind::General,
+            id: RegisterId(SPECIAL_REGISTERS_START + WINDOWSTART as u16),
+            _type: RegisterDataType::UnsignedInteger,
+            size_in_bits: 32,
+        },
+    ],
+};
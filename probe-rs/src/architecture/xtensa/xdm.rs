@@ -0,0 +1,182 @@
+//! Access to the Xtensa Debug Module (XDM) over JTAG.
+//!
+//! The registers of the debug module are not mapped to separate JTAG data registers.
+//! They are accessed through the `NARSEL` instruction, which first shifts the
+//! Nexus Address Register (NAR) to select a register, and then the 32 bit
+//! Nexus Data Register (NDR) with the value to read or write.
+
+use std::convert::TryInto;
+
+use super::communication_interface::XtensaError;
+use crate::{probe::JTAGAccess, DebugProbeError};
+
+/// The JTAG instruction to access the registers of the debug module.
+const NARSEL: u32 = 0x1c;
+/// The JTAG instruction to access the power control register.
+const PWRCTL: u32 = 0x08;
+/// The JTAG instruction to access the power status register.
+const PWRSTAT: u32 = 0x09;
+
+const NAR_WIDTH: u32 = 8;
+const NDR_WIDTH: u32 = 32;
+const PWR_WIDTH: u32 = 8;
+
+/// The registers of the debug module, addressed through the NAR.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum DebugRegister {
+    /// The OCD ID register, which identifies the debug module.
+    OcdId = 0x40,
+    /// Clears the set bits in the Debug Control Register.
+    DcrClr = 0x42,
+    /// Sets the set bits in the Debug Control Register.
+    DcrSet = 0x43,
+    /// The Debug Status Register.
+    Dsr = 0x44,
+    /// The Debug Data Register, shared with the `DDR` special register of the core.
+    Ddr = 0x45,
+    /// Accesses the DDR, and executes the instruction in DIR0 afterwards.
+    DdrExec = 0x46,
+    /// Writes the Debug Instruction Register, and executes the instruction.
+    Dir0Exec = 0x47,
+    /// The Debug Instruction Register.
+    Dir0 = 0x48,
+}
+
+/// Enables the debug module, `DCR` bit 0.
+pub(super) const DCR_ENABLEOCD: u32 = 1 << 0;
+/// Requests a debug interrupt, which halts the core, `DCR` bit 1.
+pub(super) const DCR_DEBUGINTERRUPT: u32 = 1 << 1;
+
+/// The instruction in DIR0 was executed, `DSR` bit 0.
+pub(super) const DSR_EXECDONE: u32 = 1 << 0;
+/// The instruction in DIR0 caused an exception, `DSR` bit 1.
+pub(super) const DSR_EXECEXCEPTION: u32 = 1 << 1;
+/// The instruction in DIR0 is still executing, `DSR` bit 2.
+pub(super) const DSR_EXECBUSY: u32 = 1 << 2;
+/// An instruction was written to DIR0 while another one was executing, `DSR` bit 3.
+pub(super) const DSR_EXECOVERRUN: u32 = 1 << 3;
+/// The core is halted in the debug exception handler, `DSR` bit 4.
+pub(super) const DSR_STOPPED: u32 = 1 << 4;
+
+/// Keeps the power domains on while the debugger is attached, `PWRCTL` bit 7.
+const PWRCTL_JTAGDEBUGUSE: u8 = 1 << 7;
+/// Resets the core, `PWRCTL` bit 4.
+const PWRCTL_CORERESET: u8 = 1 << 4;
+/// Powers up the debug module, `PWRCTL` bit 2.
+const PWRCTL_DEBUGWAKEUP: u8 = 1 << 2;
+/// Powers up the memories, `PWRCTL` bit 1.
+const PWRCTL_MEMWAKEUP: u8 = 1 << 1;
+/// Powers up the core, `PWRCTL` bit 0.
+const PWRCTL_COREWAKEUP: u8 = 1 << 0;
+
+/// The power control value used while the debugger is attached.
+const PWRCTL_ATTACHED: u8 =
+    PWRCTL_JTAGDEBUGUSE | PWRCTL_DEBUGWAKEUP | PWRCTL_MEMWAKEUP | PWRCTL_COREWAKEUP;
+
+/// Access to the Xtensa Debug Module over the JTAG TAP of a core.
+#[derive(Debug)]
+pub(super) struct Xdm {
+    pub probe: Box<dyn JTAGAccess>,
+}
+
+impl Xdm {
+    pub fn new(mut probe: Box<dyn JTAGAccess>) -> Result<Self, (Box<dyn JTAGAccess>, XtensaError)> {
+        // The Xtensa TAP has a 5 bit instruction register
+        probe.set_ir_len(5);
+
+        let mut xdm = Self { probe };
+
+        if let Err(error) = xdm.power_up() {
+            return Err((xdm.probe, error));
+        }
+
+        Ok(xdm)
+    }
+
+    /// Power up the core and the debug module, and enable the debug module.
+    fn power_up(&mut self) -> Result<(), XtensaError> {
+        self.write_power_control(PWRCTL_ATTACHED)?;
+
+        let pwrstat = self.read_power_status()?;
+        log::debug!("PWRSTAT: {:#04x}", pwrstat);
+
+        let ocdid = self.read_register(DebugRegister::OcdId)?;
+        log::debug!("OCDID: {:#010x}", ocdid);
+
+        // A missing TAP or a powered down debug module reads as all zeros or all ones.
+        if ocdid == 0 || ocdid == u32::MAX {
+            return Err(XtensaError::DebugModuleNotFound(ocdid));
+        }
+
+        self.write_register(DebugRegister::DcrSet, DCR_ENABLEOCD)?;
+
+        Ok(())
+    }
+
+    /// Read a register of the debug module.
+    pub fn read_register(&mut self, register: DebugRegister) -> Result<u32, XtensaError> {
+        self.select_register(register, false)?;
+
+        let value = self.probe.write_register(NARSEL, &[0; 4], NDR_WIDTH)?;
+        let value = u32::from_le_bytes((&value[..]).try_into().unwrap());
+
+        log::trace!("Read {:?}: {:#010x}", register, value);
+
+        Ok(value)
+    }
+
+    /// Write a register of the debug module.
+    pub fn write_register(
+        &mut self,
+        register: DebugRegister,
+        value: u32,
+    ) -> Result<(), XtensaError> {
+        log::trace!("Write {:?}: {:#010x}", register, value);
+
+        self.select_register(register, true)?;
+
+        self.probe
+            .write_register(NARSEL, &value.to_le_bytes(), NDR_WIDTH)?;
+
+        Ok(())
+    }
+
+    /// Shift the NAR, which selects the register accessed by the following NDR scan.
+    fn select_register(&mut self, register: DebugRegister, write: bool) -> Result<(), XtensaError> {
+        let nar = (register as u8) << 1 | write as u8;
+
+        self.probe.write_register(NARSEL, &[nar], NAR_WIDTH)?;
+
+        Ok(())
+    }
+
+    fn write_power_control(&mut self, value: u8) -> Result<(), XtensaError> {
+        self.probe.write_register(PWRCTL, &[value], PWR_WIDTH)?;
+
+        Ok(())
+    }
+
+    fn read_power_status(&mut self) -> Result<u8, XtensaError> {
+        let value = self.probe.read_register(PWRSTAT, PWR_WIDTH)?;
+
+        Ok(value[0])
+    }
+
+    /// Assert or deassert the reset of the core.
+    ///
+    /// The debug module is not affected, so a pending debug interrupt
+    /// halts the core directly after the reset.
+    pub fn set_core_reset(&mut self, reset: bool) -> Result<(), XtensaError> {
+        let value = if reset {
+            PWRCTL_ATTACHED | PWRCTL_CORERESET
+        } else {
+            PWRCTL_ATTACHED
+        };
+
+        self.write_power_control(value)
+    }
+
+    pub fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.probe.target_reset_deassert()
+    }
+}
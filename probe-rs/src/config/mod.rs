@@ -27,8 +27,9 @@ mod registry;
 mod target;
 
 pub use probe_rs_target::{
-    Chip, ChipFamily, Core, CoreType, FlashProperties, GenericRegion, InstructionSet, MemoryRange,
-    MemoryRegion, NvmRegion, PageInfo, RamRegion, RawFlashAlgorithm, SectorDescription, SectorInfo,
+    ArmCoreAccessOptions, Chip, ChipFamily, Core, CoreAccessOptions, CoreType, FlashProperties,
+    GenericRegion, InstructionSet, MemoryRange, MemoryRegion, NvmRegion, PageCompression, PageInfo,
+    RamRegion, RawFlashAlgorithm, SectorDescription, SectorInfo, SwjSequence, SwjSequences,
     TargetDescriptionSource,
 };
 
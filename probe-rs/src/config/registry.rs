@@ -3,7 +3,7 @@
 use super::{Chip, ChipFamily, ChipInfo, Core, Target, TargetDescriptionSource};
 use crate::config::CoreType;
 use once_cell::sync::Lazy;
-use probe_rs_target::{CoreAccessOptions, RiscvCoreAccessOptions};
+use probe_rs_target::{CoreAccessOptions, RiscvCoreAccessOptions, SwjSequences};
 use std::fs::File;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
@@ -102,10 +102,14 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
                 cores: vec![Core {
                     name: "core".to_owned(),
                     core_type: CoreType::Riscv,
-                    core_access_options: CoreAccessOptions::Riscv(RiscvCoreAccessOptions {}),
+                    core_access_options: CoreAccessOptions::Riscv(RiscvCoreAccessOptions {
+                        hart_id: 0,
+                    }),
                 }],
                 memory_map: vec![],
                 flash_algorithms: vec![],
+                swj_sequences: SwjSequences::default(),
+                rtt_scan_regions: vec![],
             }],
             flash_algorithms: vec![],
             source: TargetDescriptionSource::Generic,
@@ -1,19 +1,29 @@
 use probe_rs_target::{Architecture, ChipFamily};
 
-use super::{Core, MemoryRegion, RawFlashAlgorithm, RegistryError, TargetDescriptionSource};
+use super::{
+    Core, MemoryRange, MemoryRegion, NvmRegion, RawFlashAlgorithm, RegistryError, SwjSequences,
+    TargetDescriptionSource,
+};
 use crate::architecture::arm::sequences::{
     atsame5x::AtSAME5x,
+    kinetis::Kinetis,
     nrf52::Nrf52,
     nrf53::Nrf5340,
     nrf91::Nrf9160,
     nxp::{MIMXRT10xx, LPC55S69},
+    psoc6::Psoc6,
+    rp2040::Rp2040,
+    silabs::EfmSeries1,
     stm32f_series::Stm32fSeries,
     stm32h7::Stm32h7,
     ArmDebugSequence,
 };
 use crate::architecture::riscv::sequences::esp32c3::ESP32C3;
+use crate::architecture::riscv::sequences::esp32c6::ESP32C6;
 use crate::architecture::riscv::sequences::{DefaultRiscvSequence, RiscvDebugSequence};
+use crate::architecture::xtensa::sequences::{DefaultXtensaSequence, XtensaDebugSequence};
 use crate::flashing::FlashLoader;
+use std::ops::Range;
 use std::sync::Arc;
 
 use crate::architecture::arm::sequences::DefaultArmSequence;
@@ -33,6 +43,10 @@ pub struct Target {
     pub(crate) source: TargetDescriptionSource,
     /// Debug sequences for the given target.
     pub debug_sequence: DebugSequence,
+    /// SWJ sequences which are sent when attaching to an ARM target.
+    pub swj_sequences: SwjSequences,
+    /// The memory ranges which are scanned for the RTT control block. If empty, all RAM is scanned.
+    pub rtt_scan_regions: Vec<Range<u64>>,
 }
 
 impl std::fmt::Debug for Target {
@@ -92,6 +106,7 @@ impl Target {
         let mut debug_sequence = match chip.cores[0].core_type.architecture() {
             Architecture::Arm => DebugSequence::Arm(DefaultArmSequence::create()),
             Architecture::Riscv => DebugSequence::Riscv(DefaultRiscvSequence::create()),
+            Architecture::Xtensa => DebugSequence::Xtensa(DefaultXtensaSequence::create()),
         };
 
         if chip.name.starts_with("MIMXRT10") {
@@ -103,6 +118,12 @@ impl Target {
         } else if chip.name.starts_with("esp32c3") {
             log::warn!("Using custom sequence for ESP32c3");
             debug_sequence = DebugSequence::Riscv(ESP32C3::create());
+        } else if chip.name.starts_with("esp32c6") {
+            log::warn!("Using custom sequence for ESP32c6");
+            debug_sequence = DebugSequence::Riscv(ESP32C6::create());
+        } else if chip.name.starts_with("RP2040") {
+            log::warn!("Using custom sequence for RP2040");
+            debug_sequence = DebugSequence::Arm(Rp2040::create());
         } else if chip.name.starts_with("nRF5340") {
             log::warn!("Using custom sequence for nRF5340");
             debug_sequence = DebugSequence::Arm(Nrf5340::create());
@@ -112,6 +133,13 @@ impl Target {
         } else if chip.name.starts_with("nRF9160") {
             log::warn!("Using custom sequence for nRF9160");
             debug_sequence = DebugSequence::Arm(Nrf9160::create());
+        } else if chip.name.starts_with("STM32H745")
+            || chip.name.starts_with("STM32H747")
+            || chip.name.starts_with("STM32H755")
+            || chip.name.starts_with("STM32H757")
+        {
+            log::warn!("Using custom sequence for dual core STM32H7");
+            debug_sequence = DebugSequence::Arm(Stm32h7::create_dual_core());
         } else if chip.name.starts_with("STM32H7") {
             log::warn!("Using custom sequence for STM32H7");
             debug_sequence = DebugSequence::Arm(Stm32h7::create());
@@ -121,7 +149,26 @@ impl Target {
         {
             log::warn!("Using custom sequence for STM32F2/4/7");
             debug_sequence = DebugSequence::Arm(Stm32fSeries::create());
-        } else if chip.name.starts_with("ATSAMD5") || chip.name.starts_with("ATSAME5") {
+        } else if chip.name.starts_with("EFM32")
+            || (chip.name.starts_with("EFR32") && chip.name.get(7..8) == Some("1"))
+        {
+            // The Series 2 devices are locked using the secure element, and have no AAP.
+            log::warn!("Using custom sequence for EFM32/EFR32 Series 0/1");
+            debug_sequence = DebugSequence::Arm(EfmSeries1::create());
+        } else if chip.name.starts_with("CY8C6") {
+            log::warn!("Using custom sequence for PSoC 6");
+            debug_sequence = DebugSequence::Arm(Psoc6::create());
+        } else if chip.name.starts_with("MK") {
+            log::warn!("Using custom sequence for Kinetis");
+            debug_sequence = DebugSequence::Arm(Kinetis::create());
+        } else if chip.name.starts_with("ATSAMD1")
+            || chip.name.starts_with("ATSAMD2")
+            || chip.name.starts_with("ATSAMDA1")
+            || chip.name.starts_with("ATSAMD5")
+            || chip.name.starts_with("ATSAME5")
+            || chip.name.starts_with("ATSAML2")
+            || chip.name.starts_with("ATSAMC2")
+        {
             log::warn!("Using custom sequence for {}", chip.name);
             debug_sequence = DebugSequence::Arm(AtSAME5x::create());
         }
@@ -133,6 +180,8 @@ impl Target {
             source: family.source.clone(),
             memory_map: chip.memory_map.clone(),
             debug_sequence,
+            swj_sequences: chip.swj_sequences.clone(),
+            rtt_scan_regions: chip.rtt_scan_regions.clone(),
         })
     }
 
@@ -159,8 +208,55 @@ impl Target {
 
     /// Create a [FlashLoader] for this target, which can be used
     /// to program its non-volatile memory.
+    ///
+    /// Data for the OTP memory of the target is programmed separately, see [`otp_regions`](crate::flashing::otp_regions).
     pub fn flash_loader(&self) -> FlashLoader {
         FlashLoader::new(self.memory_map.clone(), self.source.clone())
+            .with_otp_regions(crate::flashing::otp_regions(self))
+    }
+
+    /// Add a flash algorithm to the target, e.g. one loaded with [`load_flm`](crate::flashing::load_flm).
+    ///
+    /// The algorithm replaces an existing algorithm with the same name, and becomes the default
+    /// algorithm for its address range. If no NVM region of the memory map covers the address range,
+    /// a region is added for it. If the algorithm has no cores, it can be used by all cores of the target.
+    pub fn add_flash_algorithm(&mut self, mut algorithm: RawFlashAlgorithm) {
+        let range = algorithm.flash_properties.address_range.clone();
+
+        if algorithm.cores.is_empty() {
+            algorithm.cores = self.cores.iter().map(|core| core.name.clone()).collect();
+        }
+
+        self.flash_algorithms
+            .retain(|existing| existing.name != algorithm.name);
+
+        for existing in &mut self.flash_algorithms {
+            if existing
+                .flash_properties
+                .address_range
+                .intersects_range(&range)
+            {
+                existing.default = false;
+            }
+        }
+
+        algorithm.default = true;
+
+        let has_region = self.memory_map.iter().any(|region| match region {
+            MemoryRegion::Nvm(region) => region.range.intersects_range(&range),
+            _ => false,
+        });
+
+        if !has_region {
+            self.memory_map.push(MemoryRegion::Nvm(NvmRegion {
+                name: Some(algorithm.name.clone()),
+                range,
+                is_boot_memory: false,
+                cores: algorithm.cores.clone(),
+            }));
+        }
+
+        self.flash_algorithms.push(algorithm);
     }
 
     /// Gets a [RawFlashAlgorithm] by name.
@@ -239,4 +335,6 @@ pub enum DebugSequence {
     Arm(Arc<dyn ArmDebugSequence>),
     /// A RISC-V debug sequence.
     Riscv(Arc<dyn RiscvDebugSequence>),
+    /// An Xtensa debug sequence.
+    Xtensa(Arc<dyn XtensaDebugSequence>),
 }
@@ -1,16 +1,19 @@
 pub(crate) mod communication_interface;
 
 use crate::architecture::riscv::RiscVState;
+use crate::architecture::xtensa::XtensaState;
 use crate::{CoreType, InstructionSet};
 pub use communication_interface::CommunicationInterface;
 use num_traits::Zero;
 pub use probe_rs_target::{Architecture, CoreAccessOptions};
 
 use crate::architecture::{
-    arm::core::CortexAState, arm::core::CortexMState,
+    arm::core::cortex_m::FaultStatus, arm::core::CortexAState, arm::core::CortexMState,
     riscv::communication_interface::RiscvCommunicationInterface,
+    xtensa::communication_interface::XtensaCommunicationInterface,
 };
 use crate::error;
+use crate::semihosting::SemihostingCommand;
 use crate::Target;
 use crate::{Error, Memory, MemoryInterface};
 use anyhow::{anyhow, Result};
@@ -351,6 +354,8 @@ pub struct RegisterFile {
 
     pub(crate) fp_registers: Option<&'static [RegisterDescription]>,
 
+    pub(crate) fp_double_registers: Option<&'static [RegisterDescription]>,
+
     pub(crate) other: &'static [RegisterDescription],
 }
 
@@ -470,6 +475,14 @@ impl RegisterFile {
     pub fn get_fpu_register(&self, index: usize) -> Option<&RegisterDescription> {
         self.fp_registers.and_then(|r| r.get(index))
     }
+
+    /// Returns an iterator over the double precision views of the fpu registers,
+    /// if the core provides them in addition to the single precision registers.
+    ///
+    /// For example, on Cortex-M cores the register D0 is made up of S0 and S1.
+    pub fn fpu_double_registers(&self) -> Option<impl Iterator<Item = &RegisterDescription>> {
+        self.fp_double_registers.map(|r| r.iter())
+    }
 }
 
 /// A generic interface to control a MCU core.
@@ -562,6 +575,74 @@ pub trait CoreInterface: MemoryInterface {
     /// decision for some core types.
     fn fpu_support(&mut self) -> Result<bool, error::Error>;
 
+    /// Returns the current security state of the core.
+    ///
+    /// This is `None` for cores which don't implement a security extension.
+    fn security_state(&mut self) -> Result<Option<SecurityState>, error::Error> {
+        Ok(None)
+    }
+
+    /// Read and decode the fault status registers of the core.
+    ///
+    /// This is `None` for cores which don't have fault status registers.
+    fn fault_status(&mut self) -> Result<Option<FaultStatus>, error::Error> {
+        Ok(None)
+    }
+
+    /// Returns `true` if the core was reset since the last call.
+    ///
+    /// Cores which can't detect a reset always return `false`.
+    fn reset_detected(&mut self) -> Result<bool, error::Error> {
+        Ok(false)
+    }
+
+    /// Enable or disable the cache maintenance on memory writes, for cores with caches.
+    fn set_cache_maintenance(&mut self, _enabled: bool) {}
+
+    /// Configure a data watchpoint, and return the index of the unit used for it.
+    fn set_watchpoint(&mut self, _config: &WatchpointConfig) -> Result<usize, error::Error> {
+        Err(error::Error::ArchitectureRequired(&[
+            "ARMv6-M", "ARMv7-M", "ARMv8-M", "Riscv",
+        ]))
+    }
+
+    /// Clear the data watchpoint configured in unit `unit_index`.
+    fn clear_watchpoint(&mut self, _unit_index: usize) -> Result<(), error::Error> {
+        Err(error::Error::ArchitectureRequired(&[
+            "ARMv6-M", "ARMv7-M", "ARMv8-M", "Riscv",
+        ]))
+    }
+
+    /// Halt the core when the given exception or reset occurs.
+    fn enable_vector_catch(&mut self, _condition: VectorCatch) -> Result<(), error::Error> {
+        Err(error::Error::ArchitectureRequired(&[
+            "ARMv6-M", "ARMv7-M", "ARMv8-M", "Riscv",
+        ]))
+    }
+
+    /// Stop halting the core when the given exception or reset occurs.
+    fn disable_vector_catch(&mut self, _condition: VectorCatch) -> Result<(), error::Error> {
+        Err(error::Error::ArchitectureRequired(&[
+            "ARMv6-M", "ARMv7-M", "ARMv8-M", "Riscv",
+        ]))
+    }
+
+    /// Read the control and status register (CSR) at `address`.
+    fn read_csr(&mut self, _address: u16) -> Result<u64, error::Error> {
+        Err(error::Error::ArchitectureRequired(&["Riscv"]))
+    }
+
+    /// Write the control and status register (CSR) at `address`.
+    fn write_csr(&mut self, _address: u16, _value: u64) -> Result<(), error::Error> {
+        Err(error::Error::ArchitectureRequired(&["Riscv"]))
+    }
+
+    /// Return `return_value` to a semihosting request, and continue after the semihosting sequence
+    /// once the core is resumed.
+    fn finish_semihosting(&mut self, _return_value: u64) -> Result<(), error::Error> {
+        Err(error::Error::ArchitectureRequired(&["Riscv"]))
+    }
+
     /// Called during session stop to do any pending cleanup
     fn on_session_stop(&mut self) -> Result<(), Error> {
         Ok(())
@@ -668,6 +749,8 @@ pub enum SpecificCoreState {
     Armv8m(CortexMState),
     /// The state of an RISC-V core.
     Riscv(RiscVState),
+    /// The state of an Xtensa core.
+    Xtensa(XtensaState),
 }
 
 impl SpecificCoreState {
@@ -680,6 +763,7 @@ impl SpecificCoreState {
             CoreType::Armv8a => SpecificCoreState::Armv8a(CortexAState::new()),
             CoreType::Armv8m => SpecificCoreState::Armv8m(CortexMState::new()),
             CoreType::Riscv => SpecificCoreState::Riscv(RiscVState::new()),
+            CoreType::Xtensa => SpecificCoreState::Xtensa(XtensaState::new()),
         }
     }
 
@@ -692,6 +776,7 @@ impl SpecificCoreState {
             SpecificCoreState::Armv8a(_) => CoreType::Armv8a,
             SpecificCoreState::Armv8m(_) => CoreType::Armv8m,
             SpecificCoreState::Riscv(_) => CoreType::Riscv,
+            SpecificCoreState::Xtensa(_) => CoreType::Xtensa,
         }
     }
 
@@ -703,7 +788,7 @@ impl SpecificCoreState {
     ) -> Result<Core<'probe>, Error> {
         let debug_sequence = match &target.debug_sequence {
             crate::config::DebugSequence::Arm(sequence) => sequence.clone(),
-            crate::config::DebugSequence::Riscv(_) => {
+            crate::config::DebugSequence::Riscv(_) | crate::config::DebugSequence::Xtensa(_) => {
                 return Err(Error::UnableToOpenProbe(
                     "Core architecture and Probe mismatch.",
                 ))
@@ -712,7 +797,7 @@ impl SpecificCoreState {
 
         let options = match &state.core_access_options {
             CoreAccessOptions::Arm(options) => options,
-            CoreAccessOptions::Riscv(_) => {
+            CoreAccessOptions::Riscv(_) | CoreAccessOptions::Xtensa(_) => {
                 return Err(Error::UnableToOpenProbe(
                     "Core architecture and Probe mismatch.",
                 ))
@@ -763,10 +848,34 @@ impl SpecificCoreState {
         &'probe mut self,
         state: &'probe mut CoreState,
         interface: &'probe mut RiscvCommunicationInterface,
+        hart_id: u32,
     ) -> Result<Core<'probe>, Error> {
         Ok(match self {
-            SpecificCoreState::Riscv(s) => Core::new(
-                crate::architecture::riscv::Riscv32::new(interface, s),
+            SpecificCoreState::Riscv(s) => {
+                interface.select_hart(hart_id)?;
+
+                Core::new(
+                    crate::architecture::riscv::Riscv32::new(interface, s),
+                    state,
+                )
+            }
+            _ => {
+                return Err(Error::UnableToOpenProbe(
+                    "Core architecture and Probe mismatch.",
+                ))
+            }
+        })
+    }
+
+    pub(crate) fn attach_xtensa<'probe>(
+        &'probe mut self,
+        state: &'probe mut CoreState,
+        interface: &'probe mut XtensaCommunicationInterface,
+        debug_level: u8,
+    ) -> Result<Core<'probe>, Error> {
+        Ok(match self {
+            SpecificCoreState::Xtensa(s) => Core::new(
+                crate::architecture::xtensa::Xtensa::new(interface, s, debug_level),
                 state,
             ),
             _ => {
@@ -1026,6 +1135,106 @@ impl<'probe> Core<'probe> {
         self.inner.fpu_support()
     }
 
+    /// Returns the current security state of the core.
+    ///
+    /// This is `None` for cores which don't implement a security extension,
+    /// like TrustZone for ARMv8-M.
+    pub fn security_state(&mut self) -> Result<Option<SecurityState>, error::Error> {
+        self.inner.security_state()
+    }
+
+    /// Read and decode the fault status registers of the core.
+    ///
+    /// This is `None` for cores which don't have fault status registers,
+    /// which are only available on ARMv7-M and ARMv8-M Mainline cores.
+    pub fn fault_status(&mut self) -> Result<Option<FaultStatus>, error::Error> {
+        self.inner.fault_status()
+    }
+
+    /// Returns `true` if the core was reset since the last call.
+    ///
+    /// Cortex-M cores detect a reset by the sticky reset status of DHCSR, which is recorded whenever
+    /// probe-rs reads DHCSR, so polling the status of the core doesn't hide a reset. The first call
+    /// reports resets since the core was attached. Other cores can't detect a reset, and always
+    /// return `false`.
+    pub fn reset_detected(&mut self) -> Result<bool, error::Error> {
+        self.inner.reset_detected()
+    }
+
+    /// Enable or disable the cache maintenance on memory writes.
+    ///
+    /// On cores with caches, like the Cortex-M7 and Cortex-M55, the data cache lines of
+    /// written memory are cleaned and invalidated, and the instruction cache is invalidated
+    /// after each write, so that the core sees the written data and code. This is enabled
+    /// by default, and can be disabled if the caches are managed by the user.
+    pub fn set_cache_maintenance(&mut self, enabled: bool) {
+        self.inner.set_cache_maintenance(enabled)
+    }
+
+    /// Set a data watchpoint, which halts the core when the watched address is accessed.
+    ///
+    /// If [`WatchpointConfig::value`] is set, the core only halts if that value is read or
+    /// written. This is not supported by ARMv6-M cores, and on ARMv7-M cores only a single
+    /// watchpoint with a value can be set at a time.
+    ///
+    /// On RISC-V cores, watchpoints use the same triggers as hardware breakpoints, and a
+    /// watchpoint with a value uses two chained triggers.
+    ///
+    /// Returns the index of the unit used for the watchpoint, which is used to clear it
+    /// with [`Core::clear_watchpoint`].
+    pub fn set_watchpoint(&mut self, config: WatchpointConfig) -> Result<usize, error::Error> {
+        self.inner.set_watchpoint(&config)
+    }
+
+    /// Clear the data watchpoint in unit `unit_index`, as returned by [`Core::set_watchpoint`].
+    pub fn clear_watchpoint(&mut self, unit_index: usize) -> Result<(), error::Error> {
+        self.inner.clear_watchpoint(unit_index)
+    }
+
+    /// Halt the core when the given exception or reset occurs.
+    ///
+    /// Not all conditions are supported by all cores, e.g. ARMv6-M cores only
+    /// support [`VectorCatch::HardFault`] and [`VectorCatch::CoreReset`].
+    ///
+    /// On RISC-V cores, the fault conditions halt on the corresponding exceptions using
+    /// an exception trigger, and [`VectorCatch::Interrupt`] uses an interrupt trigger.
+    pub fn enable_vector_catch(&mut self, condition: VectorCatch) -> Result<(), error::Error> {
+        self.inner.enable_vector_catch(condition)
+    }
+
+    /// Stop halting the core when the given exception or reset occurs.
+    pub fn disable_vector_catch(&mut self, condition: VectorCatch) -> Result<(), error::Error> {
+        self.inner.disable_vector_catch(condition)
+    }
+
+    /// Read a control and status register (CSR) of a RISC-V core, like `mstatus` or `mcause`.
+    ///
+    /// The address of a CSR can be looked up by its name using
+    /// [`csr_address`](crate::architecture::riscv::csr_address). The whole register is read,
+    /// which is 64 bits wide on RV64 cores. The CSR is read using an abstract command if
+    /// possible, and using the program buffer otherwise. The core has to be halted.
+    pub fn read_csr(&mut self, address: u16) -> Result<u64, error::Error> {
+        self.inner.read_csr(address)
+    }
+
+    /// Write a control and status register (CSR) of a RISC-V core.
+    ///
+    /// See [`Core::read_csr`]. Writing CSRs used by probe-rs, like the trigger or the
+    /// debug registers, can interfere with breakpoints and stepping.
+    pub fn write_csr(&mut self, address: u16, value: u64) -> Result<(), error::Error> {
+        self.inner.write_csr(address, value)
+    }
+
+    /// Complete the semihosting request the core is halted on, see
+    /// [`BreakpointCause::Semihosting`].
+    ///
+    /// The `return_value` is passed to the target, e.g. `0` for a successful
+    /// `SYS_WRITE` and `-1` for an unsupported operation. The core continues after the
+    /// semihosting sequence when it is resumed.
+    pub fn finish_semihosting(&mut self, return_value: u64) -> Result<(), error::Error> {
+        self.inner.finish_semihosting(return_value)
+    }
+
     /// Called during session tear down to do any pending cleanup
     pub(crate) fn on_session_stop(&mut self) -> Result<(), Error> {
         self.inner.on_session_stop()
@@ -1043,6 +1252,112 @@ impl BreakpointId {
     }
 }
 
+/// The security state of a core which implements a security extension.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SecurityState {
+    /// The core is executing in the Secure state.
+    Secure,
+    /// The core is executing in the Non-secure state.
+    NonSecure,
+}
+
+/// A condition which halts the core if vector catch is enabled for it, see [`Core::enable_vector_catch`].
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum VectorCatch {
+    /// Halt on a core reset, before the first instruction is executed.
+    CoreReset,
+    /// Halt on a HardFault.
+    HardFault,
+    /// Halt on a MemManage fault.
+    MemoryManagement,
+    /// Halt on a BusFault.
+    BusFault,
+    /// Halt on a UsageFault caused by a state information error, e.g. an undefined instruction.
+    StateError,
+    /// Halt on a UsageFault caused by a checking error, e.g. an unaligned access.
+    CheckError,
+    /// Halt on a UsageFault caused by an access to a missing or disabled coprocessor.
+    CoprocessorError,
+    /// Halt on a fault during exception entry or exception return.
+    InterruptError,
+    /// Halt on a SecureFault, on ARMv8-M cores with the Security Extension.
+    SecureFault,
+    /// Halt when an interrupt is taken, on RISC-V cores with an interrupt trigger.
+    Interrupt,
+}
+
+/// The kind of data access which triggers a watchpoint.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum WatchpointAccess {
+    /// Trigger on reads of the watched address.
+    Read,
+    /// Trigger on writes to the watched address.
+    Write,
+    /// Trigger on both reads and writes.
+    ReadWrite,
+}
+
+/// The size of the data access which is watched.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum WatchpointSize {
+    /// A single byte.
+    Byte,
+    /// A halfword, i.e. two bytes.
+    HalfWord,
+    /// A word, i.e. four bytes.
+    Word,
+}
+
+impl WatchpointSize {
+    /// The number of bytes covered by the watchpoint.
+    pub fn bytes(&self) -> u64 {
+        match self {
+            WatchpointSize::Byte => 1,
+            WatchpointSize::HalfWord => 2,
+            WatchpointSize::Word => 4,
+        }
+    }
+}
+
+/// The configuration of a data watchpoint, see [`Core::set_watchpoint`].
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct WatchpointConfig {
+    /// The watched address, which has to be aligned to `size`.
+    pub address: u64,
+    /// The kind of access which triggers the watchpoint.
+    pub access: WatchpointAccess,
+    /// The size of the watched data.
+    pub size: WatchpointSize,
+    /// If set, the watchpoint only triggers if this value is read or written.
+    ///
+    /// Only the lower bits, according to `size`, are compared.
+    pub value: Option<u32>,
+}
+
+impl WatchpointConfig {
+    /// Creates a watchpoint for word accesses to `address`, which triggers for any value.
+    pub fn new(address: u64, access: WatchpointAccess) -> Self {
+        WatchpointConfig {
+            address,
+            access,
+            size: WatchpointSize::Word,
+            value: None,
+        }
+    }
+
+    /// Set the size of the watched data.
+    pub fn with_size(mut self, size: WatchpointSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Only trigger the watchpoint if `value` is read or written.
+    pub fn with_value(mut self, value: u32) -> Self {
+        self.value = Some(value);
+        self
+    }
+}
+
 /// The status of the core.
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum CoreStatus {
@@ -1074,6 +1389,8 @@ pub enum BreakpointCause {
     Software,
     /// We were not able to distinguish if this was a hardware or software breakpoint.
     Unknown,
+    /// The target requested a semihosting operation from the debugger.
+    Semihosting(SemihostingCommand),
 }
 
 /// The reason why a core was halted.
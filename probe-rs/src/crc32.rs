@@ -0,0 +1,263 @@
+//! Computing the CRC32 of target memory on the target itself.
+//!
+//! Reading back large memory ranges is slow with probes on a full-speed USB connection. Instead, a
+//! small stub is loaded into the RAM, which computes the CRC32 at the speed of the core, so only
+//! the result has to be transferred.
+//!
+//! The stubs compute the CRC32 used by zlib and Ethernet, with the reflected polynomial `0xEDB88320`,
+//! bit by bit to avoid a lookup table. They get the address in the first argument register, the length
+//! in the second, the initial value in the third and the polynomial in the fourth, and stop on a
+//! breakpoint with the result in the first result register.
+
+use std::ops::Range;
+use std::time::Duration;
+
+use probe_rs_target::MemoryRegion;
+
+use crate::{Core, CoreType, Error, MemoryInterface, RegisterId};
+
+/// The reflected CRC32 polynomial.
+const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+/// The stub for the Cortex-M cores, which only uses ARMv6-M instructions:
+///
+/// ```text
+/// loop:
+///     cmp   r1, #0
+///     beq   done
+///     ldrb  r4, [r0]
+///     adds  r0, #1
+///     eors  r2, r4
+///     movs  r5, #8
+/// bit:
+///     lsrs  r2, r2, #1
+///     bcc   skip
+///     eors  r2, r3
+/// skip:
+///     subs  r5, #1
+///     bne   bit
+///     subs  r1, #1
+///     b     loop
+/// done:
+///     mvns  r0, r2
+///     bkpt  #0
+///     nop
+/// ```
+const THUMB_STUB: [u32; 8] = [
+    0xD00A_2900,
+    0x3001_7804,
+    0x2508_4062,
+    0xD300_0852,
+    0x3D01_405A,
+    0x3901_D1FA,
+    0x43D0_E7F2,
+    0xBF00_BE00,
+];
+
+/// The registers used by the Cortex-M stub besides the argument registers.
+const THUMB_SCRATCH: &[usize] = &[4, 5];
+
+/// The stub for the RISC-V cores, which only uses RV32I instructions:
+///
+/// ```text
+/// loop:
+///     beqz  a1, done
+///     lbu   t0, 0(a0)
+///     addi  a0, a0, 1
+///     xor   a2, a2, t0
+///     li    t1, 8
+/// bit:
+///     andi  t0, a2, 1
+///     srli  a2, a2, 1
+///     beqz  t0, skip
+///     xor   a2, a2, a3
+/// skip:
+///     addi  t1, t1, -1
+///     bnez  t1, bit
+///     addi  a1, a1, -1
+///     j     loop
+/// done:
+///     not   a0, a2
+///     ebreak
+/// ```
+const RISCV_STUB: [u32; 15] = [
+    0x0205_8A63,
+    0x0005_4283,
+    0x0015_0513,
+    0x0056_4633,
+    0x0080_0313,
+    0x0016_7293,
+    0x0016_5613,
+    0x0002_8463,
+    0x00D6_4633,
+    0xFFF3_0313,
+    0xFE03_16E3,
+    0xFFF5_8593,
+    0xFD1F_F06F,
+    0xFFF6_4513,
+    0x0010_0073,
+];
+
+/// The registers used by the RISC-V stub besides the argument registers, `t0` and `t1`.
+const RISCV_SCRATCH: &[usize] = &[5, 6];
+
+/// Find the address of RAM for the stub of `size` bytes, which is accessible by the core
+/// `core_name` and doesn't overlap `range`.
+pub(crate) fn stub_address(
+    memory_map: &[MemoryRegion],
+    core_name: &str,
+    range: &Range<u64>,
+    size: u64,
+) -> Option<u64> {
+    memory_map.iter().find_map(|region| match region {
+        MemoryRegion::Ram(region)
+            if region.cores.iter().any(|core| core == core_name)
+                && region.range.end - region.range.start >= size =>
+        {
+            // The stub is placed at the end of the region, if the start overlaps the range.
+            [region.range.start, (region.range.end - size) & !0x3]
+                .into_iter()
+                .find(|&address| address >= range.end || address + size <= range.start)
+                .filter(|&address| address >= region.range.start)
+        }
+        _ => None,
+    })
+}
+
+/// The size of the stub for cores of type `core_type`.
+pub(crate) fn stub_size(core_type: CoreType) -> Result<u64, Error> {
+    Ok(4 * stub(core_type)?.0.len() as u64)
+}
+
+/// The code of the stub and the scratch registers it uses.
+fn stub(core_type: CoreType) -> Result<(&'static [u32], &'static [usize]), Error> {
+    if core_type.is_cortex_m() {
+        Ok((&THUMB_STUB, THUMB_SCRATCH))
+    } else if core_type == CoreType::Riscv {
+        Ok((&RISCV_STUB, RISCV_SCRATCH))
+    } else {
+        Err(Error::ArchitectureRequired(&[
+            "ARMv6-M", "ARMv7-M", "ARMv8-M", "RISC-V",
+        ]))
+    }
+}
+
+/// Compute the CRC32 of `range` with the stub at the address `ram`.
+///
+/// The contents of the RAM used by the stub and the registers of the core are restored afterwards,
+/// and the core is resumed if it was running.
+pub(crate) fn crc32(core: &mut Core, ram: u64, range: Range<u64>) -> Result<u32, Error> {
+    let (code, scratch) = stub(core.core_type())?;
+
+    let length: u32 = (range.end - range.start)
+        .try_into()
+        .map_err(|_| Error::Other(anyhow::anyhow!("The range {:#x?} is too large", range)))?;
+    let address: u32 = range.start.try_into().map_err(|_| {
+        Error::Other(anyhow::anyhow!(
+            "The range {:#x?} is not addressable",
+            range
+        ))
+    })?;
+
+    let was_running = !core.core_halted()?;
+    if was_running {
+        core.halt(Duration::from_millis(100))?;
+    }
+
+    let registers = core.registers();
+
+    let mut saved_registers: Vec<RegisterId> = vec![registers.program_counter().id];
+    saved_registers.extend(registers.psr().map(|psr| psr.id));
+    saved_registers.extend((0..4).map(|index| registers.argument_register(index).id));
+    saved_registers.extend(
+        scratch
+            .iter()
+            .map(|&index| registers.platform_register(index).id),
+    );
+
+    let mut saved_values = Vec::with_capacity(saved_registers.len());
+    for &register in &saved_registers {
+        saved_values.push(core.read_core_reg::<u32>(register)?);
+    }
+
+    let mut saved_ram = vec![0; code.len()];
+    core.read_32(ram, &mut saved_ram)?;
+
+    core.write_32(ram, code)?;
+
+    let result = run_stub(core, ram, address, length);
+
+    // The state is restored even if the stub failed, as far as the core allows it.
+    core.write_32(ram, &saved_ram)?;
+    for (&register, &value) in saved_registers.iter().zip(&saved_values) {
+        core.write_core_reg(register, value)?;
+    }
+
+    if was_running {
+        core.run()?;
+    }
+
+    result
+}
+
+fn run_stub(core: &mut Core, ram: u64, address: u32, length: u32) -> Result<u32, Error> {
+    let registers = core.registers();
+
+    core.write_core_reg(registers.argument_register(0).id, address)?;
+    core.write_core_reg(registers.argument_register(1).id, length)?;
+    core.write_core_reg(registers.argument_register(2).id, 0xFFFF_FFFFu32)?;
+    core.write_core_reg(registers.argument_register(3).id, POLYNOMIAL)?;
+    core.write_core_reg(registers.program_counter().id, ram)?;
+
+    // The RISC-V stub stops on `ebreak`, which only enters the debug mode if enabled.
+    core.debug_on_sw_breakpoint(true)?;
+    core.run()?;
+
+    // Even slow cores process more than 100 kB per second.
+    let timeout = Duration::from_secs(1) + Duration::from_millis(length as u64 / 100);
+
+    if let Err(error) = core.wait_for_core_halted(timeout) {
+        core.halt(Duration::from_millis(100))?;
+        return Err(error);
+    }
+
+    core.read_core_reg(registers.result_register(0).id)
+}
+
+#[cfg(test)]
+mod test {
+    use probe_rs_target::{MemoryRegion, RamRegion};
+
+    use super::stub_address;
+
+    #[test]
+    fn stub_outside_of_range() {
+        let memory_map = vec![
+            MemoryRegion::Ram(RamRegion {
+                name: None,
+                range: 0x1000_0000..0x1000_0100,
+                is_boot_memory: false,
+                cores: vec!["other".to_owned()],
+            }),
+            MemoryRegion::Ram(RamRegion {
+                name: None,
+                range: 0x2000_0000..0x2000_1000,
+                is_boot_memory: false,
+                cores: vec!["main".to_owned()],
+            }),
+        ];
+
+        assert_eq!(
+            stub_address(&memory_map, "main", &(0x0..0x1000), 0x40),
+            Some(0x2000_0000)
+        );
+        assert_eq!(
+            stub_address(&memory_map, "main", &(0x2000_0000..0x2000_0800), 0x40),
+            Some(0x2000_0FC0)
+        );
+        assert_eq!(
+            stub_address(&memory_map, "main", &(0x2000_0000..0x2000_1000), 0x40),
+            None
+        );
+    }
+}
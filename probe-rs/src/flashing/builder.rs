@@ -135,6 +135,45 @@ impl FlashLayout {
     pub fn visualize(&self) -> FlashVisualizer {
         FlashVisualizer::new(self)
     }
+
+    /// Remove the sectors at the given addresses, together with their pages and fills.
+    pub(super) fn remove_sectors(&mut self, addresses: &[u64]) {
+        let removed = self
+            .sectors
+            .iter()
+            .filter(|sector| addresses.contains(&sector.address))
+            .map(|sector| sector.address..sector.address + sector.size)
+            .collect::<Vec<_>>();
+
+        let is_removed = |address: u64| removed.iter().any(|range| range.contains(&address));
+
+        self.sectors.retain(|sector| !is_removed(sector.address));
+
+        // The fills refer to their pages by index, which changes when pages are removed.
+        let mut page_indices = Vec::with_capacity(self.pages.len());
+        let mut next_index = 0;
+        for page in &self.pages {
+            if is_removed(page.address) {
+                page_indices.push(None);
+            } else {
+                page_indices.push(Some(next_index));
+                next_index += 1;
+            }
+        }
+
+        self.pages.retain(|page| !is_removed(page.address));
+
+        self.fills = self
+            .fills
+            .drain(..)
+            .filter_map(|mut fill| {
+                page_indices[fill.page_index].map(|index| {
+                    fill.page_index = index;
+                    fill
+                })
+            })
+            .collect();
+    }
 }
 
 /// A block of data that is to be written to flash.
@@ -213,6 +252,43 @@ impl FlashBuilder {
         Ok(())
     }
 
+    /// Overwrite the staged data at `address` with `data`.
+    ///
+    /// Returns `false` without changing anything, if the staged data doesn't cover the whole range.
+    pub(super) fn patch(&mut self, address: u64, data: &[u8]) -> bool {
+        let end = address + data.len() as u64;
+
+        // The data may be split into multiple chunks, which have to be contiguous.
+        let mut covered = address;
+        for (&chunk_address, chunk) in self.data.range(..end) {
+            let chunk_end = chunk_address + chunk.len() as u64;
+
+            if chunk_end <= covered {
+                continue;
+            }
+            if chunk_address > covered {
+                return false;
+            }
+            covered = chunk_end;
+        }
+
+        if covered < end {
+            return false;
+        }
+
+        for (&chunk_address, chunk) in self.data.range_mut(..end) {
+            let start = address.max(chunk_address);
+            let stop = end.min(chunk_address + chunk.len() as u64);
+
+            if start < stop {
+                chunk[(start - chunk_address) as usize..(stop - chunk_address) as usize]
+                    .copy_from_slice(&data[(start - address) as usize..(stop - address) as usize]);
+            }
+        }
+
+        true
+    }
+
     /// Check whether there is staged data for a given address range.
     pub(crate) fn has_data_in_range(&self, range: &Range<u64>) -> bool {
         self.data_in_range(range).next().is_some()
@@ -258,6 +334,38 @@ impl FlashBuilder {
             })
     }
 
+    /// Create a copy of the staged data, without the data inside of `ranges`.
+    pub(super) fn without_ranges(&self, ranges: &[Range<u64>]) -> Result<FlashBuilder, FlashError> {
+        let mut builder = FlashBuilder::new();
+
+        for (&address, data) in &self.data {
+            let end = address + data.len() as u64;
+            let mut start = address;
+
+            while start < end {
+                // The first range which overlaps the remaining part of the chunk.
+                let next = ranges
+                    .iter()
+                    .filter(|range| range.start < end && range.end > start)
+                    .min_by_key(|range| range.start);
+
+                let chunk_end = next.map(|range| range.start.max(start)).unwrap_or(end);
+
+                builder.add_data(
+                    start,
+                    &data[(start - address) as usize..(chunk_end - address) as usize],
+                )?;
+
+                match next {
+                    Some(range) => start = range.end.min(end),
+                    None => break,
+                }
+            }
+        }
+
+        Ok(builder)
+    }
+
     /// Layouts the contents of a flash memory according to the contents of the flash loader.
     pub(super) fn build_sectors_and_pages(
         &self,
@@ -1139,4 +1247,75 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn remove_unchanged_sector() {
+        let (region, flash_algorithm) = assemble_demo_flash1();
+        let mut flash_builder = FlashBuilder::new();
+        flash_builder.add_data(0x0FFF, &[1, 2]).unwrap();
+        let mut flash_layout = flash_builder
+            .build_sectors_and_pages(&region, &flash_algorithm, true)
+            .unwrap();
+
+        flash_layout.remove_sectors(&[0x0000]);
+
+        assert_eq!(
+            flash_layout.sectors(),
+            &[FlashSector {
+                address: 0x1000,
+                size: 0x1000,
+            }]
+        );
+        assert_eq!(
+            flash_layout
+                .pages()
+                .iter()
+                .map(|page| page.address())
+                .collect::<Vec<_>>(),
+            vec![0x1000, 0x1400, 0x1800, 0x1C00]
+        );
+        assert!(flash_layout
+            .fills()
+            .iter()
+            .all(|fill| fill.address() >= 0x1000
+                && flash_layout.pages()[fill.page_index()].address() <= fill.address()));
+    }
+
+    #[test]
+    fn remove_preserved_ranges() {
+        let mut flash_builder = FlashBuilder::new();
+        flash_builder.add_data(0, &[1; 16]).unwrap();
+        flash_builder.add_data(32, &[2; 8]).unwrap();
+
+        let builder = flash_builder
+            .without_ranges(&[4..8, 12..34, 38..64])
+            .unwrap();
+
+        assert_eq!(
+            builder.data.into_iter().collect::<Vec<_>>(),
+            vec![(0, vec![1; 4]), (8, vec![1; 4]), (34, vec![2; 4])]
+        );
+    }
+
+    #[test]
+    fn patch_data() {
+        let mut builder = FlashBuilder::new();
+        builder.add_data(0x100, &[0; 8]).unwrap();
+        builder.add_data(0x108, &[0; 8]).unwrap();
+        builder.add_data(0x200, &[0; 8]).unwrap();
+
+        assert!(builder.patch(0x106, &[1, 2, 3, 4]));
+        assert_eq!(
+            builder.data_in_range(&(0x104..0x10c)).collect::<Vec<_>>(),
+            vec![(0x104, &[0, 0, 1, 2, 3, 4, 0, 0][..])]
+        );
+
+        // Patches outside of the data or across gaps are rejected.
+        assert!(!builder.patch(0x1fe, &[1, 2, 3, 4]));
+        assert!(!builder.patch(0x10e, &[0; 0x100]));
+        assert_eq!(
+            builder.data_in_range(&(0x200..0x208)).collect::<Vec<_>>(),
+            vec![(0x200, &[0; 8][..])]
+        );
+    }
 }
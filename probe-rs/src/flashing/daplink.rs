@@ -0,0 +1,259 @@
+//! Flashing through the mass storage interface of DAPLink probes.
+//!
+//! DAPLink probes show up as a USB drive, and program the target when a
+//! firmware image is copied onto that drive. This doesn't need a flash
+//! algorithm in probe-rs, so it can be used as a fallback for targets
+//! which are not fully supported yet.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use ihex::Record;
+
+use super::FlashLoader;
+
+/// Name of the file which DAPLink uses to describe itself.
+const DETAILS_FILE: &str = "DETAILS.TXT";
+
+/// Name of the file which DAPLink creates if programming failed.
+const FAIL_FILE: &str = "FAIL.TXT";
+
+/// Name of the image file written to the drive.
+const IMAGE_FILE: &str = "image.hex";
+
+/// An error which occurred while flashing through a DAPLink drive.
+#[derive(Debug, thiserror::Error)]
+pub enum DaplinkDriveError {
+    /// Writing the image to the drive failed.
+    #[error("Failed to write the image to the DAPLink drive")]
+    Io(#[from] std::io::Error),
+    /// The image could not be converted to the Intel HEX format.
+    #[error("Failed to create the Intel HEX image")]
+    Image(#[from] ihex::WriterError),
+    /// The drive did not show up again after the image was written.
+    #[error("The DAPLink drive did not show up again after programming")]
+    Timeout,
+    /// DAPLink reported an error.
+    #[error("DAPLink failed to program the target: {0}")]
+    ProgrammingFailed(String),
+}
+
+/// The mass storage drive of a DAPLink probe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DaplinkDrive {
+    path: PathBuf,
+    unique_id: Option<String>,
+}
+
+impl DaplinkDrive {
+    /// List all mounted DAPLink drives.
+    pub fn list() -> Vec<DaplinkDrive> {
+        mount_point_candidates()
+            .into_iter()
+            .filter_map(|path| Self::from_path(&path))
+            .collect()
+    }
+
+    /// Find the drive of the DAPLink probe with the given serial number.
+    ///
+    /// If no serial number is given, the drive is only returned
+    /// if there is exactly one DAPLink drive.
+    pub fn find(serial_number: Option<&str>) -> Option<DaplinkDrive> {
+        let mut drives = Self::list();
+
+        match serial_number {
+            Some(serial_number) => drives
+                .into_iter()
+                .find(|drive| drive.unique_id() == Some(serial_number)),
+            None if drives.len() == 1 => drives.pop(),
+            None => None,
+        }
+    }
+
+    /// Check if `path` is the mount point of a DAPLink drive.
+    pub fn from_path(path: &Path) -> Option<DaplinkDrive> {
+        let details = fs::read_to_string(path.join(DETAILS_FILE)).ok()?;
+
+        if !details.contains("DAPLink") {
+            return None;
+        }
+
+        Some(DaplinkDrive {
+            path: path.to_path_buf(),
+            unique_id: parse_unique_id(&details),
+        })
+    }
+
+    /// The path at which the drive is mounted.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The unique ID of the probe, which is the same as its USB serial number.
+    pub fn unique_id(&self) -> Option<&str> {
+        self.unique_id.as_deref()
+    }
+
+    /// Program the data of `loader` by copying it onto the drive.
+    ///
+    /// DAPLink remounts the drive after programming, this waits up to `timeout`
+    /// for the drive to show up again, and checks if programming was successful.
+    pub fn download(
+        &self,
+        loader: &FlashLoader,
+        timeout: Duration,
+    ) -> Result<(), DaplinkDriveError> {
+        let image = create_hex_image(loader)?;
+
+        log::info!("Copying image to DAPLink drive at {}", self.path.display());
+
+        {
+            let mut file = fs::File::create(self.path.join(IMAGE_FILE))?;
+            file.write_all(image.as_bytes())?;
+            file.sync_all()?;
+        }
+
+        self.wait_for_remount(timeout)?.check_result()
+    }
+
+    /// Wait for the drive to disappear and show up again.
+    ///
+    /// Depending on the OS, the drive might be mounted at a different
+    /// path afterwards, so it is searched again using its unique ID.
+    fn wait_for_remount(&self, timeout: Duration) -> Result<DaplinkDrive, DaplinkDriveError> {
+        let start = Instant::now();
+
+        // Some operating systems only notice that the drive was removed
+        // after a short while, so don't wait too long for it to disappear.
+        while start.elapsed() < Duration::from_secs(5) && Self::from_path(&self.path).is_some() {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        while start.elapsed() < timeout {
+            let drive = match &self.unique_id {
+                Some(unique_id) => Self::find(Some(unique_id)),
+                None => Self::from_path(&self.path),
+            };
+
+            if let Some(drive) = drive {
+                return Ok(drive);
+            }
+
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        Err(DaplinkDriveError::Timeout)
+    }
+
+    /// Check the result of the last programming operation.
+    fn check_result(&self) -> Result<(), DaplinkDriveError> {
+        match fs::read_to_string(self.path.join(FAIL_FILE)) {
+            Ok(reason) => Err(DaplinkDriveError::ProgrammingFailed(
+                reason.trim().to_string(),
+            )),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+/// Parse the unique ID from the contents of `DETAILS.TXT`.
+fn parse_unique_id(details: &str) -> Option<String> {
+    details.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+
+        if key.trim() == "Unique ID" {
+            Some(value.trim().to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Convert the data of the loader to the Intel HEX format.
+fn create_hex_image(loader: &FlashLoader) -> Result<String, ihex::WriterError> {
+    let mut records = Vec::new();
+    let mut upper_address = None;
+
+    for (address, data) in loader.data() {
+        for (i, chunk) in data.chunks(16).enumerate() {
+            let chunk_address = address + i as u64 * 16;
+
+            // Records can't cross a 64 KiB boundary.
+            let split = usize::min(chunk.len(), (0x1_0000 - (chunk_address & 0xffff)) as usize);
+
+            for (offset, part) in [(0, &chunk[..split]), (split, &chunk[split..])] {
+                if part.is_empty() {
+                    continue;
+                }
+
+                let part_address = chunk_address + offset as u64;
+                let upper = (part_address >> 16) as u16;
+
+                if upper_address != Some(upper) {
+                    records.push(Record::ExtendedLinearAddress(upper));
+                    upper_address = Some(upper);
+                }
+
+                records.push(Record::Data {
+                    offset: part_address as u16,
+                    value: part.to_vec(),
+                });
+            }
+        }
+    }
+
+    records.push(Record::EndOfFile);
+
+    ihex::create_object_file_representation(&records)
+}
+
+/// Directories at which removable drives are usually mounted.
+fn mount_point_candidates() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+
+    if cfg!(target_os = "macos") {
+        roots.push(PathBuf::from("/Volumes"));
+    } else if cfg!(target_os = "windows") {
+        return (b'A'..=b'Z')
+            .map(|letter| PathBuf::from(format!("{}:\\", letter as char)))
+            .collect();
+    } else {
+        if let Ok(user) = std::env::var("USER") {
+            roots.push(Path::new("/media").join(&user));
+            roots.push(Path::new("/run/media").join(&user));
+        }
+        roots.push(PathBuf::from("/media"));
+        roots.push(PathBuf::from("/mnt"));
+    }
+
+    roots
+        .iter()
+        .filter_map(|root| fs::read_dir(root).ok())
+        .flat_map(|entries| entries.filter_map(|entry| entry.ok().map(|entry| entry.path())))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_unique_id;
+
+    #[test]
+    fn unique_id_is_parsed() {
+        let details = "# DAPLink Firmware - see https://mbed.com/daplink\r\n\
+            Unique ID: 0240000032044e4500257009997b00386781000097969900\r\n\
+            HIC ID: 97969900\r\n\
+            Auto Reset: 0\r\n";
+
+        assert_eq!(
+            parse_unique_id(details).as_deref(),
+            Some("0240000032044e4500257009997b00386781000097969900")
+        );
+    }
+
+    #[test]
+    fn missing_unique_id() {
+        assert_eq!(parse_unique_id("Version: 0254\r\n"), None);
+    }
+}
@@ -1,10 +1,10 @@
 use object::{
     elf::FileHeader32, elf::PT_LOAD, read::elf::FileHeader, read::elf::ProgramHeader, Endianness,
-    Object, ObjectSection,
+    Object, ObjectSection, ObjectSymbol,
 };
 use probe_rs_target::MemoryRange;
 
-use std::{fs::File, path::Path, str::FromStr};
+use std::{ops::Range, path::Path, path::PathBuf, str::FromStr};
 
 use super::*;
 use crate::session::Session;
@@ -18,6 +18,43 @@ pub struct BinOptions {
     pub skip: u32,
 }
 
+/// Which address of the ELF segments the data is programmed to.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum ElfAddress {
+    /// The physical address (LMA) of the segment. This is where the linker places the initial
+    /// contents of data which is copied to RAM on startup, so it is right for almost all images.
+    Physical,
+    /// The virtual address (VMA) of the segment, where the data is used when the program runs.
+    ///
+    /// This is needed for images which are linked by tools which don't set the physical address.
+    Virtual,
+}
+
+impl Default for ElfAddress {
+    fn default() -> Self {
+        ElfAddress::Physical
+    }
+}
+
+/// Extended options for flashing an ELF file.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct ElfOptions {
+    /// Which address of the segments the data is programmed to.
+    pub address: ElfAddress,
+    /// If not empty, only the sections with these names are flashed.
+    pub sections: Vec<String>,
+    /// The sections with these names are not flashed, e.g. a section with data which is provisioned separately.
+    pub skipped_sections: Vec<String>,
+}
+
+impl ElfOptions {
+    /// Check if the section with the given name is flashed.
+    fn is_section_selected(&self, name: &str) -> bool {
+        (self.sections.is_empty() || self.sections.iter().any(|section| section == name))
+            && !self.skipped_sections.iter().any(|section| section == name)
+    }
+}
+
 /// A finite list of all the available binary formats probe-rs understands.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub enum Format {
@@ -28,7 +65,12 @@ pub enum Format {
     /// Marks a file in [Intel HEX](https://en.wikipedia.org/wiki/Intel_HEX) format.
     Hex,
     /// Marks a file in the [ELF](https://en.wikipedia.org/wiki/Executable_and_Linkable_Format) format.
-    Elf,
+    /// [ElfOptions] can be used to select the sections and the addresses which are flashed.
+    Elf(ElfOptions),
+    /// Marks a file in the [UF2](https://github.com/microsoft/uf2) format.
+    ///
+    /// Only the blocks for the family of the target are flashed.
+    Uf2,
 }
 
 impl FromStr for Format {
@@ -41,7 +83,8 @@ impl FromStr for Format {
                 skip: 0,
             })),
             "hex" | "ihex" | "intelhex" => Ok(Format::Hex),
-            "elf" => Ok(Format::Elf),
+            "elf" => Ok(Format::Elf(ElfOptions::default())),
+            "uf2" => Ok(Format::Uf2),
             _ => Err(format!("Format '{}' is unknown.", s)),
         }
     }
@@ -61,6 +104,9 @@ pub enum FileDownloadError {
     /// Reading and decoding the IHEX file has failed due to the given error.
     #[error("Could not read ihex format")]
     IhexRead(#[from] ihex::ReaderError),
+    /// Reading and decoding the UF2 file has failed due to the given error.
+    #[error("Could not read UF2 file")]
+    Uf2(#[from] Uf2Error),
     /// An IO error has occurred while reading the firmware file.
     #[error("I/O error")]
     IO(#[from] std::io::Error),
@@ -75,6 +121,16 @@ pub enum FileDownloadError {
     /// This is most likely because of a bad linker script.
     #[error("No loadable ELF sections were found.")]
     NoLoadableSegments,
+    /// Two of the loaded files contain data for the same addresses.
+    #[error("The data of '{path}' at {addresses:#010x?} overlaps the data of '{existing_path}'.")]
+    FilesOverlap {
+        /// The file which was loaded last.
+        path: PathBuf,
+        /// The previously loaded file.
+        existing_path: PathBuf,
+        /// The addresses which are contained in both files.
+        addresses: Range<u64>,
+    },
 }
 
 /// Options for downloading a file onto a target chip.
@@ -115,6 +171,27 @@ pub struct DownloadOptions<'progress> {
     pub verify: bool,
     /// Disable double buffering when loading flash.
     pub disable_double_buffering: bool,
+    /// Read the sectors before erasing them, and skip the sectors which already contain the data.
+    ///
+    /// This makes flashing an image which mostly didn't change much faster.
+    pub skip_unchanged_sectors: bool,
+    /// Ranges of the flash which keep their current contents, e.g. a bootloader or calibration data.
+    ///
+    /// The ranges are read before the sectors containing them are erased, and written back afterwards.
+    /// Data of the image inside of these ranges is ignored.
+    pub preserved_ranges: Vec<Range<u64>>,
+    /// The bank of a dual bank flash, to which the image is flashed.
+    ///
+    /// The image has to be linked for the start of the flash. If no bank is given,
+    /// the image is flashed to the bank which is currently mapped at the start of the flash.
+    pub bank: Option<FlashBank>,
+    /// Swap the banks of a dual bank flash after flashing, so the chip boots from the other bank after the next reset.
+    pub swap_banks: bool,
+    /// A file which records the programmed sectors, so an interrupted download can be resumed.
+    ///
+    /// If the file contains the progress of a download of the same image to the same chip,
+    /// the sectors which were already programmed are skipped. The file is removed after the download succeeded.
+    pub resume_file: Option<PathBuf>,
 }
 
 impl<'progress> DownloadOptions<'progress> {
@@ -148,18 +225,23 @@ pub fn download_file_with_options<P: AsRef<Path>>(
     format: Format,
     options: DownloadOptions<'_>,
 ) -> Result<(), FileDownloadError> {
-    let mut file = match File::open(path.as_ref()) {
-        Ok(file) => file,
-        Err(e) => return Err(FileDownloadError::IO(e)),
-    };
+    download_files_with_options(session, &[(path, format)], options)
+}
 
+/// Downloads multiple files to the flash of the target given in `session`, e.g. a bootloader and an application.
+///
+/// The data of all files is combined, so each sector is erased and programmed only once.
+/// The files must not contain data for the same addresses, otherwise [`FileDownloadError::FilesOverlap`] is returned.
+pub fn download_files_with_options<P: AsRef<Path>>(
+    session: &mut Session,
+    files: &[(P, Format)],
+    options: DownloadOptions<'_>,
+) -> Result<(), FileDownloadError> {
     let mut loader = session.target().flash_loader();
 
-    match format {
-        Format::Bin(options) => loader.load_bin_data(&mut file, options),
-        Format::Elf => loader.load_elf_data(&mut file),
-        Format::Hex => loader.load_hex_data(&mut file),
-    }?;
+    for (path, format) in files {
+        loader.load_file(path.as_ref(), format.clone(), session.target())?;
+    }
 
     loader
         .commit(session, options)
@@ -191,9 +273,14 @@ impl std::fmt::Debug for ExtractedFlashData<'_> {
     }
 }
 
+/// Extract the data of the loadable segments of an ELF file.
+///
+/// Only the sections selected by `options` are extracted. If all sections of a segment are selected,
+/// the whole segment is extracted, including any padding between the sections.
 pub(super) fn extract_from_elf<'data>(
     extracted_data: &mut Vec<ExtractedFlashData<'data>>,
     elf_data: &'data [u8],
+    options: &ElfOptions,
 ) -> Result<usize, FileDownloadError> {
     let file_kind = object::FileKind::parse(elf_data)?;
 
@@ -209,6 +296,7 @@ pub(super) fn extract_from_elf<'data>(
     let endian = elf_header.endian()?;
 
     let mut extracted_sections = 0;
+    let mut found_sections = Vec::new();
 
     for segment in elf_header.program_headers(elf_header.endian()?, elf_data)? {
         // Get the physical address of the segment. The data will be programmed to that location.
@@ -222,8 +310,7 @@ pub(super) fn extract_from_elf<'data>(
             .data(endian, elf_data)
             .map_err(|_| FileDownloadError::Object("Failed to access data for an ELF segment."))?;
 
-        let mut elf_section = Vec::new();
-
+        // Sections without contents in the file, like `.bss` or `NOLOAD` sections, have no data in the segment.
         if !segment_data.is_empty() && segment.p_type(endian) == PT_LOAD {
             log::info!(
                 "Found loadable segment, physical address: {:#010x}, virtual address: {:#010x}, flags: {:#x}",
@@ -232,10 +319,18 @@ pub(super) fn extract_from_elf<'data>(
                 flags
             );
 
+            let segment_address = match options.address {
+                ElfAddress::Physical => p_paddr,
+                ElfAddress::Virtual => p_vaddr,
+            };
+
             let (segment_offset, segment_filesize) = segment.file_range(endian);
 
             let sector: core::ops::Range<u64> = segment_offset..segment_offset + segment_filesize;
 
+            let mut matching_sections = 0;
+            let mut selected_sections = Vec::new();
+
             for section in binary.sections() {
                 let (section_offset, section_filesize) = match section.file_range() {
                     Some(range) => range,
@@ -243,7 +338,8 @@ pub(super) fn extract_from_elf<'data>(
                 };
 
                 if sector.contains_range(&(section_offset..section_offset + section_filesize)) {
-                    log::info!("Matching section: {:?}", section.name()?);
+                    let name = section.name()?;
+                    log::info!("Matching section: {:?}", name);
 
                     #[cfg(feature = "hexdump")]
                     for line in hexdump::hexdump_iter(section.data()?) {
@@ -254,35 +350,129 @@ pub(super) fn extract_from_elf<'data>(
                         log::info!("Relocation: offset={}, relocation={:?}", offset, relocation);
                     }
 
-                    elf_section.push(section.name()?.to_owned());
+                    matching_sections += 1;
+                    found_sections.push(name.to_owned());
+
+                    if options.is_section_selected(name) {
+                        selected_sections.push((
+                            name.to_owned(),
+                            section_offset - segment_offset,
+                            section_filesize,
+                        ));
+                    } else {
+                        log::info!("Skipping section {:?}", name);
+                    }
                 }
             }
 
-            if elf_section.is_empty() {
+            if selected_sections.is_empty() {
                 log::info!("Not adding segment, no matching sections found.");
-            } else {
+            } else if selected_sections.len() == matching_sections {
                 let section_data =
                     &elf_data[segment_offset as usize..][..segment_filesize as usize];
 
                 extracted_data.push(ExtractedFlashData {
-                    section_names: elf_section,
-                    address: p_paddr as u32,
+                    section_names: selected_sections
+                        .into_iter()
+                        .map(|(name, _, _)| name)
+                        .collect(),
+                    address: segment_address as u32,
                     data: section_data,
                 });
 
                 extracted_sections += 1;
+            } else {
+                for (name, offset, size) in selected_sections {
+                    let section_data = &segment_data[offset as usize..][..size as usize];
+
+                    extracted_data.push(ExtractedFlashData {
+                        section_names: vec![name],
+                        address: (segment_address + offset) as u32,
+                        data: section_data,
+                    });
+
+                    extracted_sections += 1;
+                }
             }
         }
     }
 
+    for section in &options.sections {
+        if !found_sections.contains(section) {
+            log::warn!(
+                "The section {:?} was not found in a loadable segment of the ELF file.",
+                section
+            );
+        }
+    }
+
     Ok(extracted_sections)
 }
 
+/// The entry point of an ELF file.
+pub(super) fn entry_point(elf_data: &[u8]) -> Result<u64, FileDownloadError> {
+    let elf_header = FileHeader32::<Endianness>::parse(elf_data)?;
+
+    Ok(elf_header.e_entry(elf_header.endian()?).into())
+}
+
+/// Extract the symbols of an ELF file, which have data in a loadable segment.
+///
+/// The address of each symbol is the address its data is flashed to, according to `options`.
+pub(super) fn extract_symbols(
+    elf_data: &[u8],
+    options: &ElfOptions,
+) -> Result<Vec<(String, Range<u64>)>, FileDownloadError> {
+    let elf_header = FileHeader32::<Endianness>::parse(elf_data)?;
+
+    let binary = object::read::elf::ElfFile::<FileHeader32<Endianness>>::parse(elf_data)?;
+
+    let endian = elf_header.endian()?;
+
+    // The virtual address range of the data of each segment, and the address it is flashed to.
+    let mut segments = Vec::new();
+    for segment in elf_header.program_headers(endian, elf_data)? {
+        if segment.p_type(endian) != PT_LOAD {
+            continue;
+        }
+
+        let p_vaddr: u64 = segment.p_vaddr(endian).into();
+        let p_filesz: u64 = segment.p_filesz(endian).into();
+
+        let address = match options.address {
+            ElfAddress::Physical => segment.p_paddr(endian).into(),
+            ElfAddress::Virtual => p_vaddr,
+        };
+
+        segments.push((p_vaddr..p_vaddr + p_filesz, address));
+    }
+
+    let mut symbols = Vec::new();
+    for symbol in binary.symbols() {
+        let name = match symbol.name() {
+            Ok(name) if !name.is_empty() && symbol.size() > 0 => name,
+            _ => continue,
+        };
+
+        let range = symbol.address()..symbol.address() + symbol.size();
+
+        if let Some((segment, address)) = segments
+            .iter()
+            .find(|(segment, _)| segment.contains_range(&range))
+        {
+            let start = address + (range.start - segment.start);
+            symbols.push((name.to_owned(), start..start + symbol.size()));
+        }
+    }
+
+    Ok(symbols)
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
 
-    use super::{BinOptions, Format};
+    use super::{extract_from_elf, extract_symbols, BinOptions, ElfOptions, Format};
 
     #[test]
     fn parse_format() {
@@ -295,6 +485,8 @@ mod tests {
         assert_eq!(Format::from_str("intelhex"), Ok(Format::Hex));
         assert_eq!(Format::from_str("intelHex"), Ok(Format::Hex));
         assert_eq!(Format::from_str("Intelhex"), Ok(Format::Hex));
+        assert_eq!(Format::from_str("uf2"), Ok(Format::Uf2));
+        assert_eq!(Format::from_str("UF2"), Ok(Format::Uf2));
         assert_eq!(
             Format::from_str("bin"),
             Ok(Format::Bin(BinOptions {
@@ -323,8 +515,14 @@ mod tests {
                 skip: 0
             }))
         );
-        assert_eq!(Format::from_str("Elf"), Ok(Format::Elf));
-        assert_eq!(Format::from_str("elf"), Ok(Format::Elf));
+        assert_eq!(
+            Format::from_str("Elf"),
+            Ok(Format::Elf(ElfOptions::default()))
+        );
+        assert_eq!(
+            Format::from_str("elf"),
+            Ok(Format::Elf(ElfOptions::default()))
+        );
         assert_eq!(
             Format::from_str("elfbin"),
             Err("Format 'elfbin' is unknown.".to_string())
@@ -338,4 +536,63 @@ mod tests {
             Err("Format 'asdasdf' is unknown.".to_string())
         );
     }
+
+    fn extract(options: &ElfOptions) -> Vec<(Vec<String>, u32, usize)> {
+        let elf_data = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/inlined-function"
+        ))
+        .unwrap();
+
+        let mut extracted = Vec::new();
+        extract_from_elf(&mut extracted, &elf_data, options).unwrap();
+
+        extracted
+            .into_iter()
+            .map(|data| (data.section_names, data.address, data.data.len()))
+            .collect()
+    }
+
+    #[test]
+    fn extract_all_sections() {
+        assert_eq!(
+            extract(&ElfOptions::default()),
+            vec![
+                (vec![".vector_table".to_owned()], 0x0, 0xa8),
+                (vec![".text".to_owned()], 0xa8, 0x13c8),
+                (vec![".rodata".to_owned()], 0x1470, 0x2d4),
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_selected_sections() {
+        let options = ElfOptions {
+            sections: vec![".text".to_owned(), ".rodata".to_owned()],
+            skipped_sections: vec![".rodata".to_owned()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            extract(&options),
+            vec![(vec![".text".to_owned()], 0xa8, 0x13c8)]
+        );
+    }
+
+    #[test]
+    fn extract_symbols_with_data() {
+        let elf_data = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/inlined-function"
+        ))
+        .unwrap();
+
+        let symbols = extract_symbols(&elf_data, &ElfOptions::default()).unwrap();
+
+        // The vector table is flashed, the `.bss` data isn't.
+        assert!(symbols
+            .iter()
+            .any(|(name, range)| name == "__RESET_VECTOR" && *range == (0x4..0x8)));
+        assert!(symbols.iter().all(|(_, range)| range.end <= 0x1744));
+    }
 }
@@ -0,0 +1,285 @@
+//! Dual bank flash of the STM32H7 and STM32L4.
+//!
+//! The flash of these chips consists of two banks of equal size. Swapping the banks maps the second bank
+//! to the start of the flash, so the chip boots from it, and the first bank behind it. This allows updating
+//! the firmware in the inactive bank, while the active bank still contains a working firmware.
+//!
+//! Images are linked for the start of the flash. When a bank is selected, the data of the image is moved
+//! to the address at which the selected bank is currently mapped.
+
+use probe_rs_target::MemoryRegion;
+
+use super::builder::FlashBuilder;
+use super::{program_option_bytes, read_option_bytes, FlashError};
+use crate::{MemoryInterface, Session};
+
+/// The start of the flash, at which the active bank is mapped.
+const FLASH_START: u64 = 0x0800_0000;
+
+/// A bank of a dual bank flash.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FlashBank {
+    /// The first physical bank.
+    Bank1,
+    /// The second physical bank.
+    Bank2,
+    /// The bank which is currently not mapped at the start of the flash.
+    Inactive,
+}
+
+/// A family of chips with a dual bank flash.
+#[derive(Debug)]
+struct DualBankFamily {
+    /// The prefixes of the names of the chips of the family.
+    prefixes: &'static [&'static str],
+    /// The address of the register, which shows if the banks are currently swapped.
+    swap_register: u64,
+    /// The bit of the register, which is set if the banks are currently swapped.
+    swap_bit: u32,
+    /// The option byte field, which swaps the banks after the next reset.
+    swap_field: &'static str,
+}
+
+static FAMILIES: &[DualBankFamily] = &[
+    DualBankFamily {
+        prefixes: &[
+            "STM32H742",
+            "STM32H743",
+            "STM32H745",
+            "STM32H747",
+            "STM32H753",
+            "STM32H755",
+            "STM32H757",
+            "STM32H7A3",
+            "STM32H7B3",
+        ],
+        // `SWAP_BANK` in `FLASH_OPTCR`
+        swap_register: 0x5200_2018,
+        swap_bit: 1 << 31,
+        swap_field: "SWAP_BANK_OPT",
+    },
+    DualBankFamily {
+        prefixes: &["STM32L47", "STM32L48", "STM32L49", "STM32L4A"],
+        // `BFB2` in `FLASH_OPTR`, the bootloader maps bank 2 at the start of the flash if it is set.
+        swap_register: 0x4002_2020,
+        swap_bit: 1 << 20,
+        swap_field: "BFB2",
+    },
+];
+
+fn family(name: &str) -> Option<&'static DualBankFamily> {
+    FAMILIES.iter().find(|family| {
+        family
+            .prefixes
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    })
+}
+
+/// Check if the chip is one of the STM32 families with a dual bank flash.
+pub(super) fn is_supported(name: &str) -> bool {
+    family(name).is_some()
+}
+
+/// The current mapping of the banks.
+#[derive(Debug)]
+struct Layout {
+    /// The size of each bank.
+    bank_size: u64,
+    /// The second bank is mapped at the start of the flash.
+    swapped: bool,
+}
+
+impl Layout {
+    /// The address at which the bank is currently mapped.
+    fn bank_address(&self, bank: FlashBank) -> u64 {
+        let second_bank = match bank {
+            FlashBank::Bank1 => false,
+            FlashBank::Bank2 => true,
+            FlashBank::Inactive => !self.swapped,
+        };
+
+        if second_bank != self.swapped {
+            FLASH_START + self.bank_size
+        } else {
+            FLASH_START
+        }
+    }
+}
+
+fn read_layout(session: &mut Session) -> Result<Layout, FlashError> {
+    let family = family(&session.target().name)
+        .ok_or_else(|| FlashError::DualBankNotSupported(session.target().name.clone()))?;
+
+    let size = session
+        .target()
+        .memory_map
+        .iter()
+        .find_map(|region| match region {
+            MemoryRegion::Nvm(region) if region.range.start == FLASH_START => {
+                Some(region.range.end - region.range.start)
+            }
+            _ => None,
+        })
+        .ok_or_else(|| FlashError::NoSuitableNvm {
+            start: FLASH_START,
+            end: FLASH_START,
+            description_source: session.target().source.clone(),
+        })?;
+
+    let mut core = session.core(0).map_err(FlashError::Core)?;
+    let swap_register = core
+        .read_word_32(family.swap_register)
+        .map_err(FlashError::Core)?;
+
+    Ok(Layout {
+        bank_size: size / 2,
+        swapped: swap_register & family.swap_bit != 0,
+    })
+}
+
+/// Move the data, which is linked for the start of the flash, to the address of the bank.
+fn relocate(
+    builder: &FlashBuilder,
+    layout: &Layout,
+    bank: FlashBank,
+) -> Result<FlashBuilder, FlashError> {
+    let bank_address = layout.bank_address(bank);
+    let linked_range = FLASH_START..FLASH_START + layout.bank_size;
+
+    let mut relocated = FlashBuilder::new();
+
+    for (&address, data) in &builder.data {
+        if !linked_range.contains(&address) {
+            relocated.add_data(address, data)?;
+            continue;
+        }
+
+        let end = address + data.len() as u64;
+        if end > linked_range.end {
+            return Err(FlashError::BankOverflow {
+                start: address,
+                end,
+            });
+        }
+
+        relocated.add_data(bank_address + (address - FLASH_START), data)?;
+    }
+
+    Ok(relocated)
+}
+
+/// Move the data of the image to the address at which the bank is currently mapped.
+pub(super) fn assign_bank(
+    session: &mut Session,
+    builder: &FlashBuilder,
+    bank: FlashBank,
+) -> Result<FlashBuilder, FlashError> {
+    let layout = read_layout(session)?;
+
+    log::info!(
+        "Flashing {:?} at {:#010x}, the banks are {}swapped",
+        bank,
+        layout.bank_address(bank),
+        if layout.swapped { "" } else { "not " }
+    );
+
+    relocate(builder, &layout, bank)
+}
+
+/// Log the current mapping of the banks, if they are swapped.
+pub(super) fn log_swapped_banks(session: &mut Session) -> Result<(), FlashError> {
+    let layout = read_layout(session)?;
+
+    if layout.swapped {
+        log::info!(
+            "The flash banks are swapped, the image is flashed to bank 2 at {:#010x}",
+            FLASH_START
+        );
+    }
+
+    Ok(())
+}
+
+/// Toggle the bank swap option bit, so the other bank is mapped at the start of the flash after the next reset.
+pub(super) fn swap_banks(session: &mut Session) -> Result<(), FlashError> {
+    let family = family(&session.target().name)
+        .ok_or_else(|| FlashError::DualBankNotSupported(session.target().name.clone()))?;
+
+    let mut option_bytes = read_option_bytes(session).map_err(FlashError::OptionBytes)?;
+
+    let swap = option_bytes
+        .get(family.swap_field)
+        .map_err(FlashError::OptionBytes)?;
+
+    log::info!(
+        "Swapping the flash banks, {} = {}",
+        family.swap_field,
+        swap ^ 1
+    );
+
+    option_bytes
+        .set(family.swap_field, swap ^ 1)
+        .map_err(FlashError::OptionBytes)?;
+
+    program_option_bytes(session, &option_bytes).map_err(FlashError::OptionBytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{relocate, FlashBank, Layout, FLASH_START};
+    use crate::flashing::{builder::FlashBuilder, FlashError};
+
+    #[test]
+    fn bank_addresses() {
+        let layout = Layout {
+            bank_size: 0x10_0000,
+            swapped: false,
+        };
+
+        assert_eq!(layout.bank_address(FlashBank::Bank1), 0x0800_0000);
+        assert_eq!(layout.bank_address(FlashBank::Bank2), 0x0810_0000);
+        assert_eq!(layout.bank_address(FlashBank::Inactive), 0x0810_0000);
+
+        let layout = Layout {
+            bank_size: 0x10_0000,
+            swapped: true,
+        };
+
+        assert_eq!(layout.bank_address(FlashBank::Bank1), 0x0810_0000);
+        assert_eq!(layout.bank_address(FlashBank::Bank2), 0x0800_0000);
+        assert_eq!(layout.bank_address(FlashBank::Inactive), 0x0810_0000);
+    }
+
+    #[test]
+    fn relocate_to_bank() {
+        let layout = Layout {
+            bank_size: 0x8_0000,
+            swapped: false,
+        };
+
+        let mut builder = FlashBuilder::new();
+        builder
+            .add_data(FLASH_START + 0x100, &[1, 2, 3, 4])
+            .unwrap();
+        builder.add_data(0x2000_0000, &[5, 6]).unwrap();
+
+        let relocated = relocate(&builder, &layout, FlashBank::Bank2).unwrap();
+
+        assert_eq!(
+            relocated.data.keys().copied().collect::<Vec<_>>(),
+            vec![0x0808_0100, 0x2000_0000]
+        );
+
+        // The data has to fit into a single bank.
+        let mut builder = FlashBuilder::new();
+        builder
+            .add_data(FLASH_START + 0x7_FFFE, &[1, 2, 3, 4])
+            .unwrap();
+
+        assert!(matches!(
+            relocate(&builder, &layout, FlashBank::Bank2),
+            Err(FlashError::BankOverflow { .. })
+        ));
+    }
+}
@@ -1,18 +1,110 @@
 use std::collections::HashMap;
+use std::ops::Range;
 
-use probe_rs_target::{MemoryRange, MemoryRegion, NvmRegion};
+use probe_rs_target::{MemoryRange, MemoryRegion, NvmRegion, SectorInfo};
 
-use crate::flashing::{flasher::Flasher, FlashError, FlashLoader};
+use crate::flashing::{flasher::Flasher, stm32_trustzone, FlashAlgorithm, FlashError, FlashLoader};
 use crate::Session;
 
+/// The NVM regions, grouped by the name of the flash algorithm and the name of the core used to erase them.
+type RegionsByAlgorithm = HashMap<(String, String), Vec<NvmRegion>>;
+
 /// Mass-erase all nonvolatile memory.
+///
+/// For each flash algorithm, the chip erase function is used if it has one, otherwise all sectors are
+/// erased one after another.
 pub fn erase_all(session: &mut Session) -> Result<(), FlashError> {
     log::debug!("Erasing all...");
 
-    let mut algos: HashMap<(String, String), Vec<NvmRegion>> = HashMap::new();
+    let algos = regions_by_algorithm(session, None)?;
+
+    erase(session, algos, None)
+}
+
+/// Erase the nonvolatile memory in the range `start..end`.
+///
+/// The range has to be covered by NVM regions, and its start and end have to be on sector boundaries,
+/// so that no data outside of the range is erased. If the range covers the whole flash of a flash
+/// algorithm, its chip erase function is used if it has one, otherwise the sectors are erased one
+/// after another.
+pub fn erase_range(session: &mut Session, start: u64, end: u64) -> Result<(), FlashError> {
+    log::debug!("Erasing {:#010x}..{:#010x}", start, end);
+
+    let range = start..end;
+    if range.is_empty() {
+        return Ok(());
+    }
+
+    let algos = regions_by_algorithm(session, Some(&range))?;
+
+    // Check the whole range before anything is erased.
+    let mut covered = algos
+        .values()
+        .flatten()
+        .map(|region| region.range.clone())
+        .collect::<Vec<_>>();
+    covered.sort_by_key(|region| region.start);
+
+    let mut address = start;
+    for region in covered {
+        if region.start <= address {
+            address = address.max(region.end);
+        }
+    }
+
+    if address < end {
+        return Err(FlashError::NoSuitableNvm {
+            start,
+            end,
+            description_source: session.target().source.clone(),
+        });
+    }
+
+    for ((algo_name, _), regions) in &algos {
+        // This can't fail, algo_name comes from the target.
+        let raw = session.target().flash_algorithm_by_name(algo_name).unwrap();
+        let algo = FlashAlgorithm {
+            flash_properties: raw.flash_properties.clone(),
+            ..Default::default()
+        };
+
+        let sectors = sectors_to_erase(&algo, regions, Some(&range));
+
+        if let Some(aligned_start) = sectors.first().map(|sector| sector.base_address) {
+            let aligned_end = sectors
+                .last()
+                .map_or(end, |sector| sector.base_address + sector.size);
+
+            if aligned_start < start || aligned_end > end {
+                return Err(FlashError::EraseRangeNotAligned {
+                    start,
+                    end,
+                    aligned: aligned_start.min(start)..aligned_end.max(end),
+                });
+            }
+        }
+    }
+
+    erase(session, algos, Some(range))
+}
+
+/// Find the NVM regions intersecting `range`, or all of them, and the flash algorithms to erase them.
+fn regions_by_algorithm(
+    session: &mut Session,
+    range: Option<&Range<u64>>,
+) -> Result<RegionsByAlgorithm, FlashError> {
+    // The secure alias of the flash only exists if TrustZone is enabled.
+    let skip_secure_alias = stm32_trustzone::is_supported(&session.target().name)
+        && !stm32_trustzone::is_enabled(session)?;
+
+    let mut algos = RegionsByAlgorithm::new();
     log::debug!("Regions:");
     for region in &session.target().memory_map {
         if let MemoryRegion::Nvm(region) = region {
+            if range.map_or(false, |range| !region.range.intersects_range(range)) {
+                continue;
+            }
+
             log::debug!(
                 "    region: {:08x}-{:08x} ({} bytes)",
                 region.range.start,
@@ -20,6 +112,11 @@ pub fn erase_all(session: &mut Session) -> Result<(), FlashError> {
                 region.range.end - region.range.start
             );
 
+            if skip_secure_alias && stm32_trustzone::is_secure_alias(&region.range) {
+                log::debug!("     -- secure alias, TrustZone is disabled, ignoring!");
+                continue;
+            }
+
             let algo = FlashLoader::get_flash_algorithm_for_region(region, session.target())?;
 
             // Get the first core that can access the region
@@ -37,6 +134,29 @@ pub fn erase_all(session: &mut Session) -> Result<(), FlashError> {
         }
     }
 
+    Ok(algos)
+}
+
+/// The sectors of `algo` in `regions`, which intersect `range`, in ascending order.
+fn sectors_to_erase(
+    algo: &FlashAlgorithm,
+    regions: &[NvmRegion],
+    range: Option<&Range<u64>>,
+) -> Vec<SectorInfo> {
+    algo.iter_sectors()
+        .filter(|info| {
+            let sector = info.base_address..info.base_address + info.size;
+            regions.iter().any(|r| r.range.contains_range(&sector))
+                && range.map_or(true, |range| range.intersects_range(&sector))
+        })
+        .collect()
+}
+
+fn erase(
+    session: &mut Session,
+    algos: RegionsByAlgorithm,
+    range: Option<Range<u64>>,
+) -> Result<(), FlashError> {
     for ((algo_name, core_name), regions) in algos {
         log::debug!("Erasing with algorithm: {}", algo_name);
 
@@ -47,7 +167,12 @@ pub fn erase_all(session: &mut Session) -> Result<(), FlashError> {
         let core_index = session.target().core_index_by_name(&core_name).unwrap();
         let mut flasher = Flasher::new(session, core_index, &algo)?;
 
-        if flasher.is_chip_erase_supported() {
+        // The chip erase erases the whole flash of the algorithm, not only the requested range.
+        let whole_flash = range.as_ref().map_or(true, |range| {
+            range.contains_range(&flasher.flash_algorithm().flash_properties.address_range)
+        });
+
+        if flasher.is_chip_erase_supported() && whole_flash {
             log::debug!("     -- chip erase supported, doing it.");
             flasher.run_erase(|active| active.erase_all())?;
         } else {
@@ -55,14 +180,7 @@ pub fn erase_all(session: &mut Session) -> Result<(), FlashError> {
 
             // loop over all sectors erasing them individually instead.
 
-            let sectors = flasher
-                .flash_algorithm()
-                .iter_sectors()
-                .filter(|info| {
-                    let range = info.base_address..info.base_address + info.size;
-                    regions.iter().any(|r| r.range.contains_range(&range))
-                })
-                .collect::<Vec<_>>();
+            let sectors = sectors_to_erase(flasher.flash_algorithm(), &regions, range.as_ref());
 
             flasher.run_erase(|active| {
                 for info in sectors {
@@ -82,3 +200,46 @@ pub fn erase_all(session: &mut Session) -> Result<(), FlashError> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use probe_rs_target::{FlashProperties, NvmRegion, SectorDescription};
+
+    use super::sectors_to_erase;
+    use crate::flashing::FlashAlgorithm;
+
+    #[test]
+    fn sectors_in_range() {
+        let algo = FlashAlgorithm {
+            flash_properties: FlashProperties {
+                address_range: 0x0800_0000..0x0801_0000,
+                sectors: vec![
+                    SectorDescription {
+                        size: 0x1000,
+                        address: 0x0,
+                    },
+                    SectorDescription {
+                        size: 0x4000,
+                        address: 0x4000,
+                    },
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let regions = [NvmRegion {
+            name: None,
+            range: 0x0800_0000..0x0801_0000,
+            is_boot_memory: true,
+            cores: vec!["main".to_owned()],
+        }];
+
+        let sectors = sectors_to_erase(&algo, &regions, Some(&(0x0800_3000..0x0800_4001)))
+            .into_iter()
+            .map(|sector| (sector.base_address, sector.size))
+            .collect::<Vec<_>>();
+
+        assert_eq!(sectors, vec![(0x0800_3000, 0x1000), (0x0800_4000, 0x4000)]);
+        assert_eq!(sectors_to_erase(&algo, &regions, None).len(), 7);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::config::{NvmRegion, RamRegion, TargetDescriptionSource};
 use crate::error;
 use std::ops::Range;
+use std::path::PathBuf;
 
 /// Describes any error that happened during the or in preparation for the flashing procedure.
 #[derive(thiserror::Error, Debug)]
@@ -41,6 +42,15 @@ pub enum FlashError {
         #[source]
         source: Box<dyn std::error::Error + 'static + Send + Sync>,
     },
+    /// Reading the flash contents through the flash algorithm failed.
+    #[error("Reading the flash at address {address:#010x} failed.")]
+    FlashRead {
+        /// The address of the flash that should have been read.
+        address: u64,
+        /// The source error of this error.
+        #[source]
+        source: Box<dyn std::error::Error + 'static + Send + Sync>,
+    },
     /// Initializing the flash algorithm failed.
     #[error("The initialization of the flash algorithm failed.")]
     Init(#[source] Box<dyn std::error::Error + 'static + Send + Sync>),
@@ -151,4 +161,79 @@ pub enum FlashError {
     /// The register value supplied for this flash algorithm is out of the supported range.
     #[error("The register value {0:08X?} is out of the supported range.")]
     RegisterValueNotSupported(u64),
+    /// The chip has no dual bank flash.
+    #[error("The chip '{0}' has no dual bank flash, which can be selected or swapped.")]
+    DualBankNotSupported(String),
+    /// The data doesn't fit into a single bank of the flash.
+    #[error("The data at {start:#010x}..{end:#010x} does not fit into a single flash bank.")]
+    BankOverflow {
+        /// The start of the data.
+        start: u64,
+        /// The end of the data.
+        end: u64,
+    },
+    /// Reading or programming the option bytes failed.
+    #[error("Changing the option bytes failed.")]
+    OptionBytes(#[source] super::OptionByteError),
+    /// The chip has no OTP memory, which can be programmed.
+    #[error("Programming the OTP memory of the chip '{0}' is not supported.")]
+    OtpNotSupported(String),
+    /// Programming the OTP memory failed.
+    #[error("Programming the OTP memory failed.")]
+    Otp(#[source] super::OtpError),
+    /// The patched range is not completely inside of the loaded data.
+    #[error(
+        "The patch at {address:#010x} ({size} bytes) is not completely inside of the loaded data."
+    )]
+    PatchOutsideData {
+        /// The address of the patch.
+        address: u64,
+        /// The size of the patch in bytes.
+        size: usize,
+    },
+    /// The symbol to patch was not found in the loaded ELF files.
+    #[error("The symbol '{0}' was not found in the loaded ELF files.")]
+    SymbolNotFound(String),
+    /// The patch is larger than the patched symbol.
+    #[error(
+        "The patch of {size} bytes does not fit into the symbol '{symbol}' of {symbol_size} bytes."
+    )]
+    PatchTooLarge {
+        /// The name of the symbol.
+        symbol: String,
+        /// The size of the patch in bytes.
+        size: usize,
+        /// The size of the symbol in bytes.
+        symbol_size: u64,
+    },
+    /// The data can't be run from RAM, because it is not completely inside of the RAM.
+    #[error("The data at {start:#010x}..{end:#010x} is not inside of the RAM.")]
+    NotInRam {
+        /// The start of the data.
+        start: u64,
+        /// The end of the data.
+        end: u64,
+    },
+    /// The start address of the loaded image is unknown.
+    #[error("The loaded image has no entry point.")]
+    NoEntryPoint,
+    /// The range to erase does not start and end on sector boundaries.
+    #[error("The range {start:#010x}..{end:#010x} is not aligned to the flash sectors, the sectors containing it span {aligned:#010x?}.")]
+    EraseRangeNotAligned {
+        /// The start of the range.
+        start: u64,
+        /// The end of the range.
+        end: u64,
+        /// The range of the sectors containing the range.
+        aligned: Range<u64>,
+    },
+    /// Writing the file, which records the progress of the download, failed.
+    #[error("Failed to write the resume file '{path}'.")]
+    ResumeFile {
+        /// The path of the file.
+        path: PathBuf,
+        /// The source error of this error.
+        #[source]
+        source: std::io::Error,
+    },
 }
@@ -1,4 +1,6 @@
-use probe_rs_target::{FlashProperties, PageInfo, RamRegion, RawFlashAlgorithm, SectorInfo};
+use probe_rs_target::{
+    FlashProperties, PageCompression, PageInfo, RamRegion, RawFlashAlgorithm, SectorInfo,
+};
 
 use super::FlashError;
 use crate::core::Architecture;
@@ -31,6 +33,10 @@ pub struct FlashAlgorithm {
     pub pc_erase_sector: u64,
     /// Address of the `EraseAll()` entry point. Optional.
     pub pc_erase_all: Option<u64>,
+    /// Address of the `Read()` entry point. Optional.
+    pub pc_read: Option<u64>,
+    /// Address of the `Verify()` entry point. Optional.
+    pub pc_verify: Option<u64>,
     /// Initial value of the R9 register for calling flash algo entry points, which
     /// determines where the position-independent data resides.
     pub static_base: u64,
@@ -42,6 +48,8 @@ pub struct FlashAlgorithm {
     /// least as large as the region's `page_size` attribute. If at least 2 buffers are included in
     /// the list, then double buffered programming will be enabled.
     pub page_buffers: Vec<u64>,
+    /// The compression of the page data, which the `ProgramPage()` entry point accepts.
+    pub page_compression: Option<PageCompression>,
 
     /// The properties of the flash on the device.
     pub flash_properties: FlashProperties,
@@ -160,6 +168,9 @@ impl FlashAlgorithm {
     // Header for RISCV Flash Algorithms
     const RISCV_FLASH_BLOB_HEADER: [u32; 2] = [riscv::assembly::EBREAK, riscv::assembly::EBREAK];
 
+    // Xtensa flash algorithms return to a `break` instruction of their own, so no header is needed.
+    const XTENSA_FLASH_BLOB_HEADER: [u32; 0] = [];
+
     const ARM_FLASH_BLOB_HEADER: [u32; 8] = [
         0xE00A_BE00,
         0x062D_780D,
@@ -175,6 +186,7 @@ impl FlashAlgorithm {
         match architecture {
             Architecture::Arm => &Self::ARM_FLASH_BLOB_HEADER,
             Architecture::Riscv => &Self::RISCV_FLASH_BLOB_HEADER,
+            Architecture::Xtensa => &Self::XTENSA_FLASH_BLOB_HEADER,
         }
     }
 
@@ -271,10 +283,13 @@ impl FlashAlgorithm {
             pc_program_page: code_start + raw.pc_program_page,
             pc_erase_sector: code_start + raw.pc_erase_sector,
             pc_erase_all: raw.pc_erase_all.map(|v| code_start + v),
+            pc_read: raw.pc_read.map(|v| code_start + v),
+            pc_verify: raw.pc_verify.map(|v| code_start + v),
             static_base: code_start + raw.data_section_offset,
             begin_stack: addr_stack,
             begin_data: page_buffers[0],
             page_buffers: page_buffers.clone(),
+            page_compression: raw.page_compression,
             flash_properties: raw.flash_properties.clone(),
         })
     }
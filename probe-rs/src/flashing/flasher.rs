@@ -1,12 +1,19 @@
-use probe_rs_target::{MemoryRegion, RawFlashAlgorithm};
+use probe_rs_target::{MemoryRegion, PageCompression, RawFlashAlgorithm};
 
+use super::resume::ResumeState;
+use super::watchdog::{self, FeedHook};
 use super::{
     FlashAlgorithm, FlashBuilder, FlashError, FlashFill, FlashLayout, FlashPage, FlashProgress,
 };
 use crate::config::NvmRegion;
 use crate::memory::MemoryInterface;
-use crate::{core::RegisterFile, session::Session, Core, InstructionSet};
-use std::{fmt::Debug, time::Duration};
+use crate::{core::RegisterFile, session::Session, Core, DebugProbeError, InstructionSet};
+use std::{
+    borrow::Cow,
+    fmt::Debug,
+    ops::Range,
+    time::{Duration, Instant},
+};
 
 pub(super) trait Operation {
     fn operation() -> u32;
@@ -104,6 +111,7 @@ impl<'session> Flasher<'session> {
     fn load(&mut self) -> Result<(), FlashError> {
         log::debug!("Initializing the flash algorithm.");
         let algo = &mut self.flash_algorithm;
+        let name = self.session.target().name.clone();
 
         // Attach to memory and core.
         let mut core = self
@@ -121,6 +129,10 @@ impl<'session> Flasher<'session> {
         core.reset_and_halt(Duration::from_millis(500))
             .map_err(FlashError::Core)?;
 
+        // A running watchdog would reset the chip in the middle of programming. The RAM of the
+        // algorithm isn't used yet, so it can be used to disable the watchdog.
+        watchdog::disable(&mut core, &name, algo.load_address).map_err(FlashError::Core)?;
+
         // TODO: Possible special preparation of the target such as enabling faster clocks for the flash e.g.
 
         // Load flash algorithm code into target RAM.
@@ -162,6 +174,8 @@ impl<'session> Flasher<'session> {
         &mut self,
         clock: Option<u32>,
     ) -> Result<ActiveFlasher<'_, O>, FlashError> {
+        let watchdog_feed = watchdog::feed_hook(&self.session.target().name);
+
         // Attach to memory and core.
         let core = self
             .session
@@ -172,6 +186,7 @@ impl<'session> Flasher<'session> {
         let mut flasher = ActiveFlasher::<O> {
             core,
             flash_algorithm: self.flash_algorithm.clone(),
+            watchdog_feed,
             _operation: core::marker::PhantomData,
         };
 
@@ -222,6 +237,14 @@ impl<'session> Flasher<'session> {
     /// If `restore_unwritten_bytes` is `true`, all bytes of a sector,
     /// that are not to be written during flashing will be read from the flash first
     /// and written again once the sector is erased.
+    ///
+    /// If `skip_unchanged_sectors` is `true`, sectors which already contain the data are not erased and programmed.
+    ///
+    /// The contents of the `preserved_ranges` are read from the flash before erasing, and written back afterwards.
+    ///
+    /// If `resume` is given, the sectors which were completed by a previous download are skipped,
+    /// and the completed sectors are recorded.
+    #[allow(clippy::too_many_arguments)]
     pub(super) fn program(
         &mut self,
         region: &NvmRegion,
@@ -229,16 +252,39 @@ impl<'session> Flasher<'session> {
         restore_unwritten_bytes: bool,
         enable_double_buffering: bool,
         skip_erasing: bool,
+        skip_unchanged_sectors: bool,
+        preserved_ranges: &[Range<u64>],
         progress: &FlashProgress,
+        mut resume: Option<&mut ResumeState>,
     ) -> Result<(), FlashError> {
         log::debug!("Starting program procedure.");
         // Convert the list of flash operations into flash sectors and pages.
+        // The preserved ranges can be anywhere in the erased sectors, so all pages of the sectors are needed.
         let mut flash_layout = flash_builder.build_sectors_and_pages(
             region,
             &self.flash_algorithm,
-            restore_unwritten_bytes,
+            restore_unwritten_bytes || !preserved_ranges.is_empty(),
         )?;
 
+        // Sectors are only skipped if they would be erased, otherwise they are known to be erased already.
+        if skip_unchanged_sectors && !skip_erasing {
+            let unchanged = self.unchanged_sectors(
+                &flash_layout,
+                flash_builder,
+                restore_unwritten_bytes,
+                preserved_ranges,
+            )?;
+
+            if !unchanged.is_empty() {
+                log::info!("Skipping {} unchanged sectors", unchanged.len());
+                flash_layout.remove_sectors(&unchanged);
+            }
+        }
+
+        if let Some(resume) = resume.as_deref_mut() {
+            resume.prepare(&mut flash_layout);
+        }
+
         progress.initialized(flash_layout.clone());
 
         log::debug!("Double Buffering enabled: {:?}", enable_double_buffering);
@@ -262,7 +308,7 @@ impl<'session> Flasher<'session> {
                     progress.failed_filling();
                     return result;
                 } else {
-                    progress.page_filled(fill.size(), t.elapsed());
+                    progress.page_filled(fill.address(), fill.size(), t.elapsed());
                 }
             }
         }
@@ -270,6 +316,10 @@ impl<'session> Flasher<'session> {
         // We successfully finished filling.
         progress.finished_filling();
 
+        if !preserved_ranges.is_empty() {
+            self.read_preserved_ranges(&mut flash_layout, preserved_ranges)?;
+        }
+
         // Skip erase if necessary
         if !skip_erasing {
             // Erase all necessary sectors
@@ -278,14 +328,69 @@ impl<'session> Flasher<'session> {
 
         // Flash all necessary pages.
         if self.double_buffering_supported() && enable_double_buffering {
-            self.program_double_buffer(&flash_layout, progress)?;
+            self.program_double_buffer(&flash_layout, progress, resume)?;
         } else {
-            self.program_simple(&flash_layout, progress)?;
+            self.program_simple(&flash_layout, progress, resume)?;
         };
 
         Ok(())
     }
 
+    /// Find the sectors of `flash_layout`, which already contain the data of `flash_builder`.
+    ///
+    /// If `restore_unwritten_bytes` is `false`, the bytes of the sector without data have to be erased as well,
+    /// except for the bytes in `preserved_ranges`.
+    fn unchanged_sectors(
+        &mut self,
+        flash_layout: &FlashLayout,
+        flash_builder: &FlashBuilder,
+        restore_unwritten_bytes: bool,
+        preserved_ranges: &[Range<u64>],
+    ) -> Result<Vec<u64>, FlashError> {
+        let erased_byte_value = self.flash_algorithm.flash_properties.erased_byte_value;
+
+        self.run_verify(|active| {
+            let mut unchanged = Vec::new();
+
+            for sector in flash_layout.sectors() {
+                let range = sector.address()..sector.address() + sector.size();
+
+                let mut current = vec![0; sector.size() as usize];
+                active.read_flash(sector.address(), &mut current)?;
+
+                // Bytes which are restored keep their current value.
+                let mut expected = if restore_unwritten_bytes {
+                    current.clone()
+                } else {
+                    vec![erased_byte_value; current.len()]
+                };
+
+                for (address, data) in flash_builder.data_in_range(&range) {
+                    let offset = (address - sector.address()) as usize;
+                    expected[offset..offset + data.len()].copy_from_slice(data);
+                }
+
+                for preserved in preserved_ranges {
+                    let start = preserved.start.max(range.start);
+                    let end = preserved.end.min(range.end);
+
+                    if start < end {
+                        let start = (start - range.start) as usize;
+                        let end = (end - range.start) as usize;
+                        expected[start..end].copy_from_slice(&current[start..end]);
+                    }
+                }
+
+                if current == expected {
+                    log::debug!("Sector at {:#010x} is unchanged", sector.address());
+                    unchanged.push(sector.address());
+                }
+            }
+
+            Ok(unchanged)
+        })
+    }
+
     /// Fills all the bytes of `current_page`.
     ///
     /// If `restore_unwritten_bytes` is `true`, all bytes of the page,
@@ -298,11 +403,70 @@ impl<'session> Flasher<'session> {
     ) -> Result<(), FlashError> {
         let page_offset = (fill.address() - page.address()) as usize;
         let page_slice = &mut page.data_mut()[page_offset..page_offset + fill.size() as usize];
+        self.run_verify(|active| active.read_flash(fill.address(), page_slice))
+    }
+
+    /// Reads the contents of the `preserved_ranges` from the flash into the pages of `flash_layout`,
+    /// so they are written again once the sectors are erased.
+    fn read_preserved_ranges(
+        &mut self,
+        flash_layout: &mut FlashLayout,
+        preserved_ranges: &[Range<u64>],
+    ) -> Result<(), FlashError> {
+        self.run_verify(|active| {
+            for page in flash_layout.pages_mut() {
+                let page_range = page.address()..page.address() + page.size() as u64;
+
+                for preserved in preserved_ranges {
+                    let start = preserved.start.max(page_range.start);
+                    let end = preserved.end.min(page_range.end);
+
+                    if start >= end {
+                        continue;
+                    }
+
+                    log::debug!("Preserving {:#010x}-{:#010x}", start, end);
+
+                    let offset = (start - page_range.start) as usize;
+                    let page_slice = &mut page.data_mut()[offset..offset + (end - start) as usize];
+                    active.read_flash(start, page_slice)?;
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Reads back the data of `flash_builder` in `region` and compares it
+    /// with the data which should have been written.
+    ///
+    /// This is only required if the flash algorithm has to be used to read the flash,
+    /// otherwise the flash is verified by reading it directly.
+    ///
+    /// If the flash algorithm has a `Verify()` entry point, the comparison is done by the flash algorithm.
+    pub(super) fn verify(
+        &mut self,
+        region: &NvmRegion,
+        flash_builder: &FlashBuilder,
+    ) -> Result<(), FlashError> {
+        let use_verify_function = self.flash_algorithm.pc_verify.is_some();
+
         self.run_verify(|active| {
-            active
-                .core
-                .read(fill.address() as u64, page_slice)
-                .map_err(FlashError::Core)
+            for (address, data) in flash_builder.data_in_range(&region.range) {
+                if use_verify_function {
+                    active.verify_flash(address, data)?;
+                    continue;
+                }
+
+                let mut written_data = vec![0; data.len()];
+                active.read_flash(address, &mut written_data)?;
+
+                if written_data != data {
+                    return Err(FlashError::Verify);
+                }
+            }
+
+            Ok(())
         })
     }
 
@@ -311,6 +475,7 @@ impl<'session> Flasher<'session> {
         &mut self,
         flash_layout: &FlashLayout,
         progress: &FlashProgress,
+        mut resume: Option<&mut ResumeState>,
     ) -> Result<(), FlashError> {
         progress.started_programming();
 
@@ -323,8 +488,12 @@ impl<'session> Flasher<'session> {
                         page_address: page.address(),
                         source: Box::new(error),
                     })?;
-                progress.page_programmed(page.size(), t.elapsed());
+                progress.page_programmed(page.address(), page.size(), t.elapsed());
                 t = std::time::Instant::now();
+
+                if let Some(resume) = resume.as_deref_mut() {
+                    resume.page_programmed(page.address(), page.size())?;
+                }
             }
             Ok(())
         });
@@ -356,7 +525,7 @@ impl<'session> Flasher<'session> {
                         source: Box::new(e),
                     })?;
 
-                progress.sector_erased(sector.size(), t.elapsed());
+                progress.sector_erased(sector.address(), sector.size(), t.elapsed());
                 t = std::time::Instant::now();
             }
             Ok(())
@@ -384,6 +553,7 @@ impl<'session> Flasher<'session> {
         &mut self,
         flash_layout: &FlashLayout,
         progress: &FlashProgress,
+        mut resume: Option<&mut ResumeState>,
     ) -> Result<(), FlashError> {
         let mut current_buf = 0;
 
@@ -391,10 +561,11 @@ impl<'session> Flasher<'session> {
 
         let mut t = std::time::Instant::now();
         let result = self.run_program(|active| {
-            let mut last_page_address = 0;
+            let mut last_page: Option<&FlashPage> = None;
             for page in flash_layout.pages() {
                 // At the start of each loop cycle load the next page buffer into RAM.
-                active.load_page_buffer(page.address(), page.data(), current_buf)?;
+                let compressed_size =
+                    active.load_page_buffer(page.address(), page.data(), current_buf)?;
 
                 // Then wait for the active RAM -> Flash copy process to finish.
                 // Also check if it finished properly. If it didn't, return an error.
@@ -402,12 +573,11 @@ impl<'session> Flasher<'session> {
                     active
                         .wait_for_completion(Duration::from_secs(2))
                         .map_err(|error| FlashError::PageWrite {
-                            page_address: last_page_address,
+                            page_address: last_page.map_or(0, |page| page.address()),
                             source: Box::new(error),
                         })?;
 
-                last_page_address = page.address();
-                progress.page_programmed(page.size(), t.elapsed());
+                progress.page_programmed(page.address(), page.size(), t.elapsed());
                 t = std::time::Instant::now();
                 if result != 0 {
                     return Err(FlashError::RoutineCallFailed {
@@ -416,8 +586,18 @@ impl<'session> Flasher<'session> {
                     });
                 }
 
+                // Only the previous page is known to be programmed, the current one is programmed next.
+                if let (Some(resume), Some(last_page)) = (resume.as_deref_mut(), last_page) {
+                    resume.page_programmed(last_page.address(), last_page.size())?;
+                }
+                last_page = Some(page);
+
                 // Start the next copy process.
-                active.start_program_page_with_buffer(page.address(), current_buf)?;
+                active.start_program_page_with_buffer(
+                    page.address(),
+                    current_buf,
+                    compressed_size,
+                )?;
 
                 // Swap the buffers
                 if current_buf == 1 {
@@ -430,7 +610,7 @@ impl<'session> Flasher<'session> {
             let result = active
                 .wait_for_completion(Duration::from_secs(2))
                 .map_err(|error| FlashError::PageWrite {
-                    page_address: last_page_address,
+                    page_address: last_page.map_or(0, |page| page.address()),
                     source: Box::new(error),
                 })?;
 
@@ -440,6 +620,9 @@ impl<'session> Flasher<'session> {
                     error_code: result,
                 })
             } else {
+                if let (Some(resume), Some(last_page)) = (resume.as_deref_mut(), last_page) {
+                    resume.page_programmed(last_page.address(), last_page.size())?;
+                }
                 Ok(0)
             }
         });
@@ -490,6 +673,8 @@ impl From<FlashError> for crate::Error {
 pub(super) struct ActiveFlasher<'probe, O: Operation> {
     core: Core<'probe>,
     flash_algorithm: FlashAlgorithm,
+    /// Reloads the watchdogs of the chip, which can't be disabled while flashing.
+    watchdog_feed: Option<FeedHook>,
     _operation: core::marker::PhantomData<O>,
 }
 
@@ -573,6 +758,10 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
     fn call_function(&mut self, registers: &Registers, init: bool) -> Result<(), crate::Error> {
         log::debug!("Calling routine {:?}, init={})", &registers, init);
 
+        if let Some(feed) = self.watchdog_feed {
+            feed(&mut self.core)?;
+        }
+
         let algo = &self.flash_algorithm;
         let regs: &'static RegisterFile = self.core.registers();
 
@@ -639,7 +828,27 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
         log::debug!("Waiting for routine call completion.");
         let regs = self.core.registers();
 
-        self.core.wait_for_core_halted(timeout)?;
+        match self.watchdog_feed {
+            Some(feed) => {
+                // The watchdog has to be reloaded while the routine runs, e.g. during a long sector erase.
+                let start = Instant::now();
+                let mut last_feed = start;
+
+                while !self.core.core_halted()? {
+                    if start.elapsed() >= timeout {
+                        return Err(crate::Error::Probe(DebugProbeError::Timeout));
+                    }
+
+                    if last_feed.elapsed() >= watchdog::FEED_INTERVAL {
+                        feed(&mut self.core)?;
+                        last_feed = Instant::now();
+                    }
+
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            }
+            None => self.core.wait_for_core_halted(timeout)?,
+        }
 
         let r: u32 = self.core.read_core_reg(regs.result_register(0).id)?;
         Ok(r)
@@ -723,6 +932,126 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
     }
 }
 
+impl<'p> ActiveFlasher<'p, Verify> {
+    /// Reads the flash contents at `address` into `data`.
+    ///
+    /// If the flash algorithm has a `Read()` entry point, it is used to read the flash
+    /// page by page into the page buffer. Otherwise the flash is read directly by the core.
+    pub(super) fn read_flash(&mut self, address: u64, data: &mut [u8]) -> Result<(), FlashError> {
+        let pc_read = match self.flash_algorithm.pc_read {
+            Some(pc_read) => pc_read,
+            None => return self.core.read(address, data).map_err(FlashError::Core),
+        };
+
+        let page_size = self.flash_algorithm.flash_properties.page_size as usize;
+
+        for (index, chunk) in data.chunks_mut(page_size).enumerate() {
+            let chunk_address = address + (index * page_size) as u64;
+
+            log::debug!(
+                "Reading flash at address {:#08x} with size: {}",
+                chunk_address,
+                chunk.len()
+            );
+
+            let result = self
+                .call_function_and_wait(
+                    &Registers {
+                        pc: into_reg(pc_read)?,
+                        r0: Some(into_reg(chunk_address)?),
+                        r1: Some(chunk.len() as u32),
+                        r2: Some(into_reg(self.flash_algorithm.begin_data)?),
+                        r3: None,
+                    },
+                    false,
+                    Duration::from_millis(
+                        self.flash_algorithm.flash_properties.program_page_timeout as u64,
+                    ),
+                )
+                .map_err(|error| FlashError::FlashRead {
+                    address: chunk_address,
+                    source: Box::new(error),
+                })?;
+
+            if result != 0 {
+                return Err(FlashError::FlashRead {
+                    address: chunk_address,
+                    source: Box::new(FlashError::RoutineCallFailed {
+                        name: "read",
+                        error_code: result,
+                    }),
+                });
+            }
+
+            // Transfer the bytes from RAM.
+            self.core
+                .read_8(self.flash_algorithm.begin_data, chunk)
+                .map_err(FlashError::Core)?;
+        }
+
+        Ok(())
+    }
+
+    /// Compares the flash contents at `address` with `data`, using the `Verify()` entry point of the flash algorithm.
+    ///
+    /// The data is transferred page by page into the page buffer. The entry point returns
+    /// the end of the compared range if the contents match, and the address of the first mismatch otherwise.
+    pub(super) fn verify_flash(&mut self, address: u64, data: &[u8]) -> Result<(), FlashError> {
+        let pc_verify = match self.flash_algorithm.pc_verify {
+            Some(pc_verify) => pc_verify,
+            None => return Err(FlashError::Verify),
+        };
+
+        let page_size = self.flash_algorithm.flash_properties.page_size as usize;
+
+        for (index, chunk) in data.chunks(page_size).enumerate() {
+            let chunk_address = address + (index * page_size) as u64;
+
+            log::debug!(
+                "Verifying flash at address {:#08x} with size: {}",
+                chunk_address,
+                chunk.len()
+            );
+
+            // Transfer the bytes to RAM.
+            self.core
+                .write_8(self.flash_algorithm.begin_data, chunk)
+                .map_err(FlashError::Core)?;
+
+            let result = self
+                .call_function_and_wait(
+                    &Registers {
+                        pc: into_reg(pc_verify)?,
+                        r0: Some(into_reg(chunk_address)?),
+                        r1: Some(chunk.len() as u32),
+                        r2: Some(into_reg(self.flash_algorithm.begin_data)?),
+                        r3: None,
+                    },
+                    false,
+                    Duration::from_millis(
+                        self.flash_algorithm.flash_properties.program_page_timeout as u64,
+                    ),
+                )
+                .map_err(|error| FlashError::FlashRead {
+                    address: chunk_address,
+                    source: Box::new(error),
+                })?;
+
+            let end = chunk_address + chunk.len() as u64;
+            if result as u64 != end {
+                log::debug!(
+                    "Verification failed at address {:#08x}, expected {:#08x}",
+                    result,
+                    end
+                );
+                return Err(FlashError::Verify);
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl<'p> ActiveFlasher<'p, Program> {
     pub(super) fn program_page(&mut self, address: u64, bytes: &[u8]) -> Result<(), FlashError> {
         let t1 = std::time::Instant::now();
@@ -733,9 +1062,11 @@ impl<'p> ActiveFlasher<'p, Program> {
             bytes.len()
         );
 
+        let (data, compressed_size) = compress_page(self.flash_algorithm.page_compression, bytes);
+
         // Transfer the bytes to RAM.
         self.core
-            .write_8(self.flash_algorithm.begin_data as u64, bytes)
+            .write_8(self.flash_algorithm.begin_data as u64, &data)
             .map_err(FlashError::Core)?;
 
         let result = self
@@ -745,7 +1076,7 @@ impl<'p> ActiveFlasher<'p, Program> {
                     r0: Some(into_reg(address)?),
                     r1: Some(bytes.len() as u32),
                     r2: Some(into_reg(self.flash_algorithm.begin_data)?),
-                    r3: None,
+                    r3: compressed_size,
                 },
                 false,
                 Duration::from_millis(
@@ -771,10 +1102,14 @@ impl<'p> ActiveFlasher<'p, Program> {
         }
     }
 
+    /// Start programming the page at `address` from the page buffer `buffer_number`.
+    ///
+    /// `compressed_size` is the value returned by [`ActiveFlasher::load_page_buffer`] for the buffer.
     pub(super) fn start_program_page_with_buffer(
         &mut self,
         address: u64,
         buffer_number: usize,
+        compressed_size: Option<u32>,
     ) -> Result<(), FlashError> {
         // Ensure the buffer number is valid, otherwise there is a bug somewhere
         // in the flashing code.
@@ -792,7 +1127,7 @@ impl<'p> ActiveFlasher<'p, Program> {
                 r2: Some(into_reg(
                     self.flash_algorithm.page_buffers[buffer_number as usize],
                 )?),
-                r3: None,
+                r3: compressed_size,
             },
             false,
         )
@@ -804,12 +1139,16 @@ impl<'p> ActiveFlasher<'p, Program> {
         Ok(())
     }
 
+    /// Transfer the data of a page into the page buffer `buffer_number`.
+    ///
+    /// The data is compressed if the flash algorithm supports it. The returned value has to be passed to
+    /// `ProgramPage()`, see [`compress_page`].
     pub(super) fn load_page_buffer(
         &mut self,
         _address: u64,
         bytes: &[u8],
         buffer_number: usize,
-    ) -> Result<(), FlashError> {
+    ) -> Result<Option<u32>, FlashError> {
         let algo = &self.flash_algorithm;
 
         // Ensure the buffer number is valid, otherwise there is a bug somewhere
@@ -820,11 +1159,17 @@ impl<'p> ActiveFlasher<'p, Program> {
             buffer_number, algo.page_buffers.len()
         );
 
+        let (data, compressed_size) = compress_page(algo.page_compression, bytes);
+
         // TODO: Prevent security settings from locking the device.
-        // Transfer the buffer bytes to RAM.
-        let words: Vec<u32> = bytes
-            .chunks_exact(core::mem::size_of::<u32>())
-            .map(|a| u32::from_le_bytes([a[0], a[1], a[2], a[3]]))
+        // Transfer the buffer bytes to RAM. Compressed data is padded to whole words.
+        let words: Vec<u32> = data
+            .chunks(core::mem::size_of::<u32>())
+            .map(|a| {
+                let mut word = [0; 4];
+                word[..a.len()].copy_from_slice(a);
+                u32::from_le_bytes(word)
+            })
             .collect();
 
         let t1 = std::time::Instant::now();
@@ -835,9 +1180,66 @@ impl<'p> ActiveFlasher<'p, Program> {
         log::info!(
             "Took {:?} to download {} byte page into ram",
             t1.elapsed(),
-            bytes.len()
+            data.len()
         );
 
-        Ok(())
+        Ok(compressed_size)
+    }
+}
+
+/// Compress the data of a page, if the flash algorithm accepts compressed data.
+///
+/// Returns the data to transfer, and the fourth argument of `ProgramPage()`: the size of the compressed
+/// data, `0` if the data is transferred uncompressed because it doesn't get smaller, or `None` if the
+/// flash algorithm doesn't accept compressed data.
+fn compress_page(
+    compression: Option<PageCompression>,
+    bytes: &[u8],
+) -> (Cow<'_, [u8]>, Option<u32>) {
+    let compressed = match compression {
+        Some(PageCompression::Lz4) => lz4_flex::block::compress(bytes),
+        None => return (Cow::Borrowed(bytes), None),
+    };
+
+    if compressed.len() < bytes.len() {
+        log::debug!(
+            "Compressed the page from {} to {} bytes",
+            bytes.len(),
+            compressed.len()
+        );
+
+        let size = compressed.len() as u32;
+        (Cow::Owned(compressed), Some(size))
+    } else {
+        (Cow::Borrowed(bytes), Some(0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use probe_rs_target::PageCompression;
+
+    use super::compress_page;
+
+    #[test]
+    fn page_compression() {
+        let erased = [0xFF; 1024];
+        assert_eq!(compress_page(None, &erased), (erased[..].into(), None));
+
+        let (data, size) = compress_page(Some(PageCompression::Lz4), &erased);
+        assert_eq!(size, Some(data.len() as u32));
+        assert_eq!(
+            lz4_flex::block::decompress(&data, erased.len()).unwrap(),
+            erased
+        );
+
+        // Data which doesn't get smaller is transferred uncompressed.
+        let random = (0..64u32)
+            .map(|i| (i.wrapping_mul(0x9E37_79B9) >> 24) as u8)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            compress_page(Some(PageCompression::Lz4), &random),
+            (random[..].into(), Some(0))
+        );
     }
 }
@@ -0,0 +1,250 @@
+//! Loading of CMSIS-Pack flash algorithms (`.FLM` files) at runtime.
+//!
+//! An FLM file is an ELF file, which contains the position independent code of the flash algorithm
+//! in the `PrgCode` section, its data in the `PrgData` sections, and the description of the flash
+//! in the `FlashDevice` symbol.
+
+use object::{
+    read::elf::ElfFile32, Endianness, Object, ObjectSection, ObjectSegment, ObjectSymbol,
+    SectionKind,
+};
+use probe_rs_target::{FlashProperties, RawFlashAlgorithm, SectorDescription};
+
+const CODE_SECTION: &str = "PrgCode";
+const DATA_SECTION: &str = "PrgData";
+
+/// The size of the `FlashDevice` struct, without the list of sectors following it.
+const FLASH_DEVICE_SIZE: usize = 160;
+/// The maximum length of the device name in the `FlashDevice` struct.
+const MAX_NAME_LENGTH: usize = 128;
+/// The size and address of the sector, which marks the end of the list of sectors.
+const SECTOR_END: u32 = 0xFFFF_FFFF;
+
+/// Errors which can occur while loading a flash algorithm from an FLM file.
+#[derive(Debug, thiserror::Error)]
+pub enum FlmError {
+    /// The FLM file is not a valid ELF file.
+    #[error("Could not read the FLM file")]
+    Elf(#[from] object::read::Error),
+    /// A section which is required for the flash algorithm is missing.
+    #[error("The FLM file has no '{0}' section.")]
+    MissingSection(&'static str),
+    /// A section of the flash algorithm is located before its code.
+    #[error("The '{0}' section of the FLM file is located before the '{CODE_SECTION}' section.")]
+    MisplacedSection(&'static str),
+    /// A symbol which is required for the flash algorithm is missing.
+    #[error("The FLM file has no '{0}' symbol.")]
+    MissingSymbol(&'static str),
+    /// The `FlashDevice` struct is not part of the loaded data of the file.
+    #[error("The 'FlashDevice' description at {0:#010x} is incomplete.")]
+    InvalidFlashDevice(u64),
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Parse the `FlashDevice` struct and the list of sectors following it.
+///
+/// Returns the name of the device and the properties of the flash.
+fn parse_flash_device(data: &[u8]) -> Option<(String, FlashProperties)> {
+    if data.len() < FLASH_DEVICE_SIZE {
+        return None;
+    }
+
+    let name = &data[2..2 + MAX_NAME_LENGTH];
+    let name_length = name.iter().position(|&c| c == 0).unwrap_or(MAX_NAME_LENGTH);
+    let name = String::from_utf8_lossy(&name[..name_length]).to_string();
+
+    let mut sectors = Vec::new();
+    for sector in data[FLASH_DEVICE_SIZE..].chunks_exact(8) {
+        let size = read_u32(sector, 0);
+        let address = read_u32(sector, 4);
+
+        if size == SECTOR_END || address == SECTOR_END {
+            break;
+        }
+
+        sectors.push(SectorDescription {
+            size: size.into(),
+            address: address.into(),
+        });
+    }
+
+    let start = read_u32(data, 132) as u64;
+    let size = read_u32(data, 136) as u64;
+
+    let properties = FlashProperties {
+        address_range: start..start + size,
+        page_size: read_u32(data, 140),
+        erased_byte_value: data[148],
+        program_page_timeout: read_u32(data, 152),
+        erase_sector_timeout: read_u32(data, 156),
+        sectors,
+    };
+
+    Some((name, properties))
+}
+
+/// Load a flash algorithm from the contents of an FLM file, e.g. from a CMSIS-Pack.
+///
+/// The algorithm is named `name`. It can be added to a target using [`Target::add_flash_algorithm`](crate::Target::add_flash_algorithm).
+pub fn load_flm(data: &[u8], name: &str) -> Result<RawFlashAlgorithm, FlmError> {
+    let elf = ElfFile32::<Endianness>::parse(data)?;
+
+    let code = elf
+        .section_by_name(CODE_SECTION)
+        .ok_or(FlmError::MissingSection(CODE_SECTION))?;
+    let code_start = code.address();
+
+    let mut data_sections = elf
+        .sections()
+        .filter(|section| matches!(section.name(), Ok(DATA_SECTION)))
+        .collect::<Vec<_>>();
+    data_sections.sort_by_key(|section| section.address());
+
+    // The code is followed by the data, and the zero initialized data.
+    let mut instructions = code.data()?.to_vec();
+    let mut data_section_offset = None;
+
+    for section in data_sections {
+        let offset = section
+            .address()
+            .checked_sub(code_start)
+            .ok_or(FlmError::MisplacedSection(DATA_SECTION))?;
+
+        data_section_offset.get_or_insert(offset);
+
+        instructions.resize(offset as usize, 0);
+
+        if section.kind() == SectionKind::UninitializedData {
+            instructions.resize((offset + section.size()) as usize, 0);
+        } else {
+            instructions.extend_from_slice(section.data()?);
+        }
+    }
+
+    let mut algorithm = RawFlashAlgorithm {
+        name: name.to_owned(),
+        data_section_offset: data_section_offset.unwrap_or(instructions.len() as u64),
+        instructions,
+        ..Default::default()
+    };
+
+    let mut pc_program_page = None;
+    let mut pc_erase_sector = None;
+    let mut flash_device = None;
+
+    for symbol in elf.symbols() {
+        let offset = symbol.address().wrapping_sub(code_start);
+
+        match symbol.name()? {
+            "Init" => algorithm.pc_init = Some(offset),
+            "UnInit" => algorithm.pc_uninit = Some(offset),
+            "EraseChip" => algorithm.pc_erase_all = Some(offset),
+            "Read" => algorithm.pc_read = Some(offset),
+            "Verify" => algorithm.pc_verify = Some(offset),
+            "EraseSector" => pc_erase_sector = Some(offset),
+            "ProgramPage" => pc_program_page = Some(offset),
+            "FlashDevice" => flash_device = Some(symbol.address()),
+            _ => {}
+        }
+    }
+
+    algorithm.pc_program_page = pc_program_page.ok_or(FlmError::MissingSymbol("ProgramPage"))?;
+    algorithm.pc_erase_sector = pc_erase_sector.ok_or(FlmError::MissingSymbol("EraseSector"))?;
+
+    let device_address = flash_device.ok_or(FlmError::MissingSymbol("FlashDevice"))?;
+
+    let device_data = elf
+        .segments()
+        .find_map(|segment| {
+            let start = segment.address();
+            let data = segment.data().ok()?;
+
+            (start..start + data.len() as u64)
+                .contains(&device_address)
+                .then(|| &data[(device_address - start) as usize..])
+        })
+        .ok_or(FlmError::InvalidFlashDevice(device_address))?;
+
+    let (description, flash_properties) =
+        parse_flash_device(device_data).ok_or(FlmError::InvalidFlashDevice(device_address))?;
+
+    log::debug!(
+        "Loaded flash algorithm '{}' for {}: {:#010x}-{:#010x}",
+        name,
+        description,
+        flash_properties.address_range.start,
+        flash_properties.address_range.end
+    );
+
+    algorithm.description = description;
+    algorithm.flash_properties = flash_properties;
+
+    Ok(algorithm)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_flash_device, FLASH_DEVICE_SIZE};
+    use probe_rs_target::SectorDescription;
+
+    fn flash_device(sectors: &[(u32, u32)]) -> Vec<u8> {
+        let mut data = vec![0; FLASH_DEVICE_SIZE];
+
+        data[0..2].copy_from_slice(&0x0101u16.to_le_bytes());
+        data[2..2 + 9].copy_from_slice(b"QSPI NOR\0");
+        data[132..136].copy_from_slice(&0x9000_0000u32.to_le_bytes());
+        data[136..140].copy_from_slice(&0x0080_0000u32.to_le_bytes());
+        data[140..144].copy_from_slice(&0x100u32.to_le_bytes());
+        data[148] = 0xFF;
+        data[152..156].copy_from_slice(&100u32.to_le_bytes());
+        data[156..160].copy_from_slice(&3000u32.to_le_bytes());
+
+        for &(size, address) in sectors {
+            data.extend_from_slice(&size.to_le_bytes());
+            data.extend_from_slice(&address.to_le_bytes());
+        }
+
+        data
+    }
+
+    #[test]
+    fn parse_device() {
+        let data = flash_device(&[
+            (0x1000, 0x0),
+            (0x1_0000, 0x1_0000),
+            (0xFFFF_FFFF, 0xFFFF_FFFF),
+        ]);
+
+        let (name, properties) = parse_flash_device(&data).unwrap();
+
+        assert_eq!(name, "QSPI NOR");
+        assert_eq!(properties.address_range, 0x9000_0000..0x9080_0000);
+        assert_eq!(properties.page_size, 0x100);
+        assert_eq!(properties.erased_byte_value, 0xFF);
+        assert_eq!(properties.erase_sector_timeout, 3000);
+        assert_eq!(
+            properties.sectors,
+            vec![
+                SectorDescription {
+                    size: 0x1000,
+                    address: 0x0
+                },
+                SectorDescription {
+                    size: 0x1_0000,
+                    address: 0x1_0000
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_device() {
+        let data = flash_device(&[]);
+
+        assert!(parse_flash_device(&data[..FLASH_DEVICE_SIZE - 1]).is_none());
+        assert!(parse_flash_device(&data).unwrap().1.sectors.is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+//! Checks for bootable images of the i.MX RT10xx.
+//!
+//! The boot ROM of the i.MX RT10xx only boots an image from the FlexSPI NOR flash, if
+//! the flash starts with a FlexSPI Configuration Block (FCB), which configures the FlexSPI
+//! for the connected flash, followed by an Image Vector Table (IVT) at offset 0x1000.
+//!
+//! The flash algorithm configures the FlexSPI itself, so an image without these headers can
+//! be programmed without problems, but it won't boot afterwards.
+
+use super::builder::FlashBuilder;
+
+/// The address at which the FlexSPI NOR flash is mapped.
+const FLEXSPI_BASE: u64 = 0x6000_0000;
+
+/// The offset of the FCB in the flash.
+const FCB_OFFSET: usize = 0x0000;
+/// The tag at the start of the FCB, `FCFB` in little endian.
+const FCB_TAG: u32 = 0x4246_4346;
+
+/// The offset of the IVT in the flash.
+const IVT_OFFSET: usize = 0x1000;
+/// The tag in the first byte of the IVT header.
+const IVT_TAG: u8 = 0xD1;
+/// The length of the IVT, which is stored big endian in the header.
+const IVT_LENGTH: u16 = 0x0020;
+
+/// The number of bytes at the start of the flash, which contain the boot headers.
+const BOOT_HEADER_LENGTH: usize = IVT_OFFSET + IVT_LENGTH as usize;
+
+/// Describes a problem with the boot headers of an image.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+enum BootHeaderError {
+    #[error("The image has no FlexSPI Configuration Block (FCB) at offset {FCB_OFFSET:#x}.")]
+    MissingFcb,
+    #[error("The image has no Image Vector Table (IVT) at offset {IVT_OFFSET:#x}.")]
+    MissingIvt,
+    #[error("The IVT is located at {expected:#010x}, but it points to itself at {found:#010x}.")]
+    IvtSelfMismatch { expected: u32, found: u32 },
+    #[error("The IVT has no entry point.")]
+    MissingEntry,
+}
+
+/// The parts of the IVT which are relevant for the checks.
+#[derive(Debug, PartialEq, Eq)]
+struct Ivt {
+    entry: u32,
+    boot_data: u32,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Parse the boot headers at the start of the flash.
+///
+/// `data` has to contain at least [`BOOT_HEADER_LENGTH`] bytes.
+fn parse_boot_headers(data: &[u8]) -> Result<Ivt, BootHeaderError> {
+    if read_u32(data, FCB_OFFSET) != FCB_TAG {
+        return Err(BootHeaderError::MissingFcb);
+    }
+
+    let ivt = &data[IVT_OFFSET..];
+
+    let length = u16::from_be_bytes([ivt[1], ivt[2]]);
+    let version = ivt[3];
+
+    if ivt[0] != IVT_TAG || length != IVT_LENGTH || version & 0xF0 != 0x40 {
+        return Err(BootHeaderError::MissingIvt);
+    }
+
+    let expected = (FLEXSPI_BASE + IVT_OFFSET as u64) as u32;
+    let found = read_u32(ivt, 0x14);
+
+    if found != expected {
+        return Err(BootHeaderError::IvtSelfMismatch { expected, found });
+    }
+
+    let entry = read_u32(ivt, 0x04);
+
+    if entry == 0 {
+        return Err(BootHeaderError::MissingEntry);
+    }
+
+    Ok(Ivt {
+        entry,
+        boot_data: read_u32(ivt, 0x0C),
+    })
+}
+
+/// Warn if an image, which is programmed to the start of the FlexSPI NOR flash, isn't bootable.
+pub(super) fn check_boot_headers(builder: &FlashBuilder) {
+    let range = FLEXSPI_BASE..FLEXSPI_BASE + BOOT_HEADER_LENGTH as u64;
+
+    if !builder.has_data_in_range(&range) {
+        return;
+    }
+
+    // Missing data is erased flash.
+    let mut headers = vec![0xFF; BOOT_HEADER_LENGTH];
+    for (address, data) in builder.data_in_range(&range) {
+        let offset = (address - FLEXSPI_BASE) as usize;
+        headers[offset..offset + data.len()].copy_from_slice(data);
+    }
+
+    match parse_boot_headers(&headers) {
+        Ok(ivt) => log::debug!(
+            "Found boot headers, entry point {:#010x}, boot data at {:#010x}",
+            ivt.entry,
+            ivt.boot_data
+        ),
+        Err(e) => {
+            log::warn!("{}", e);
+            log::warn!("The image is programmed to the start of the FlexSPI flash, but it won't boot. Add the boot headers to the image, e.g. using the imxrt-rt crate.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        parse_boot_headers, BootHeaderError, Ivt, BOOT_HEADER_LENGTH, FCB_TAG, IVT_OFFSET,
+    };
+
+    fn boot_headers() -> Vec<u8> {
+        let mut data = vec![0xFF; BOOT_HEADER_LENGTH];
+        data[0..4].copy_from_slice(&FCB_TAG.to_le_bytes());
+
+        let ivt = [
+            0x4100_20D1u32, // header
+            0x6000_2001,    // entry
+            0,              // reserved
+            0,              // dcd
+            0x6000_1020,    // boot data
+            0x6000_1000,    // self
+            0,              // csf
+            0,              // reserved
+        ];
+
+        for (i, word) in ivt.iter().enumerate() {
+            data[IVT_OFFSET + i * 4..IVT_OFFSET + i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+        }
+
+        data
+    }
+
+    #[test]
+    fn valid_boot_headers() {
+        assert_eq!(
+            parse_boot_headers(&boot_headers()),
+            Ok(Ivt {
+                entry: 0x6000_2001,
+                boot_data: 0x6000_1020
+            })
+        );
+    }
+
+    #[test]
+    fn missing_fcb() {
+        let mut data = boot_headers();
+        data[0] = 0xFF;
+
+        assert_eq!(parse_boot_headers(&data), Err(BootHeaderError::MissingFcb));
+    }
+
+    #[test]
+    fn misplaced_ivt() {
+        let mut data = boot_headers();
+        data[IVT_OFFSET + 0x15] = 0x20;
+
+        assert_eq!(
+            parse_boot_headers(&data),
+            Err(BootHeaderError::IvtSelfMismatch {
+                expected: 0x6000_1000,
+                found: 0x6000_2000
+            })
+        );
+    }
+}
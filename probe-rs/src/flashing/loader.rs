@@ -3,13 +3,18 @@ use probe_rs_target::{
     MemoryRange, MemoryRegion, NvmRegion, RawFlashAlgorithm, TargetDescriptionSource,
 };
 use std::collections::HashMap;
+use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
 use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
 
 use super::builder::FlashBuilder;
+use super::resume::ResumeState;
 use super::{
-    extract_from_elf, BinOptions, DownloadOptions, FileDownloadError, FlashError, FlashProgress,
-    Flasher,
+    dual_bank, entry_point, extract_from_elf, extract_symbols, imxrt, manifest, otp,
+    stm32_trustzone, uf2, BinOptions, DownloadOptions, ElfOptions, FileDownloadError, FlashError,
+    FlashProgress, Flasher, Format, OtpRegion, OtpReport, VerificationManifest,
 };
 use crate::memory::MemoryInterface;
 use crate::session::Session;
@@ -25,6 +30,19 @@ pub struct FlashLoader {
     memory_map: Vec<MemoryRegion>,
     builder: FlashBuilder,
 
+    /// The OTP areas of the target, and the data which is programmed to them.
+    otp_regions: &'static [OtpRegion],
+    otp: FlashBuilder,
+
+    /// The files loaded with [load_file()](FlashLoader::load_file), and the address ranges of their data.
+    files: Vec<(PathBuf, Vec<Range<u64>>)>,
+
+    /// The symbols of the loaded ELF files, at the addresses where their data is flashed.
+    symbols: HashMap<String, Range<u64>>,
+
+    /// The entry point of the first loaded ELF file.
+    entry_point: Option<u64>,
+
     /// Source of the flash description,
     /// used for diagnostics.
     source: TargetDescriptionSource,
@@ -36,10 +54,25 @@ impl FlashLoader {
         Self {
             memory_map,
             builder: FlashBuilder::new(),
+            otp_regions: &[],
+            otp: FlashBuilder::new(),
+            files: Vec::new(),
+            symbols: HashMap::new(),
+            entry_point: None,
             source,
         }
     }
 
+    /// Program data inside of the given OTP areas to the OTP memory, instead of rejecting it.
+    ///
+    /// [`Target::flash_loader`] uses the OTP areas of the target.
+    pub fn with_otp_regions(self, otp_regions: &'static [OtpRegion]) -> Self {
+        Self {
+            otp_regions,
+            ..self
+        }
+    }
+
     /// Check the given address range is completely covered by the memory map,
     /// possibly by multiple memory regions.
     fn check_data_in_memory_map(&mut self, range: Range<u64>) -> Result<(), FlashError> {
@@ -60,9 +93,22 @@ impl FlashLoader {
         Ok(())
     }
 
+    /// Check if any part of the range is in a memory region or OTP area of the target.
+    fn is_in_known_memory(&self, range: &Range<u64>) -> bool {
+        self.otp_regions
+            .iter()
+            .any(|region| region.range().intersects_range(range))
+            || self.memory_map.iter().any(|region| match region {
+                MemoryRegion::Nvm(region) => region.range.intersects_range(range),
+                MemoryRegion::Ram(region) => region.range.intersects_range(range),
+                MemoryRegion::Generic(_) => false,
+            })
+    }
+
     /// Stages a chunk of data to be programmed.
     ///
     /// The chunk can cross flash boundaries as long as one flash region connects to another flash region.
+    /// A chunk inside of an OTP area is programmed to the OTP memory, see [`FlashLoader::with_otp_regions`].
     pub fn add_data(&mut self, address: u64, data: &[u8]) -> Result<(), FlashError> {
         log::trace!(
             "Adding data at address {:#010x} with size {} bytes",
@@ -70,10 +116,80 @@ impl FlashLoader {
             data.len()
         );
 
+        let range = address..address + data.len() as u64;
+        if self
+            .otp_regions
+            .iter()
+            .any(|region| region.range().contains_range(&range))
+        {
+            return self.otp.add_data(address, data);
+        }
+
         self.check_data_in_memory_map(address..address + data.len() as u64)?;
         self.builder.add_data(address, data)
     }
 
+    /// Overwrite the loaded data at `address` with `data`.
+    ///
+    /// This is used to program data which is different for each device into an image before flashing it,
+    /// e.g. a serial number, a MAC address or a provisioning key. The data must be completely inside of
+    /// the loaded data, use [add_data()](FlashLoader::add_data) to add data outside of the image.
+    pub fn patch(&mut self, address: u64, data: &[u8]) -> Result<(), FlashError> {
+        log::debug!("Patching {} bytes at address {:#010x}", data.len(), address);
+
+        let range = address..address + data.len() as u64;
+        let builder = if self
+            .otp_regions
+            .iter()
+            .any(|region| region.range().contains_range(&range))
+        {
+            &mut self.otp
+        } else {
+            &mut self.builder
+        };
+
+        if builder.patch(address, data) {
+            Ok(())
+        } else {
+            Err(FlashError::PatchOutsideData {
+                address,
+                size: data.len(),
+            })
+        }
+    }
+
+    /// Overwrite the data of the symbol `name` of a loaded ELF file with `data`.
+    ///
+    /// The data may be shorter than the symbol, the remaining bytes of the symbol keep their value.
+    /// See [patch()](FlashLoader::patch).
+    pub fn patch_symbol(&mut self, name: &str, data: &[u8]) -> Result<(), FlashError> {
+        let range = self
+            .symbol(name)
+            .ok_or_else(|| FlashError::SymbolNotFound(name.to_owned()))?;
+
+        if data.len() as u64 > range.end - range.start {
+            return Err(FlashError::PatchTooLarge {
+                symbol: name.to_owned(),
+                size: data.len(),
+                symbol_size: range.end - range.start,
+            });
+        }
+
+        self.patch(range.start, data)
+    }
+
+    /// The entry point of the first loaded ELF file, see [`run_from_ram`](super::run_from_ram).
+    pub fn entry_point(&self) -> Option<u64> {
+        self.entry_point
+    }
+
+    /// The address range where the data of the symbol `name` of a loaded ELF file is flashed to.
+    ///
+    /// Only symbols which have data in the file are available, e.g. no zero initialized variables.
+    pub fn symbol(&self, name: &str) -> Option<Range<u64>> {
+        self.symbols.get(name).cloned()
+    }
+
     pub(super) fn get_region_for_address(
         memory_map: &[MemoryRegion],
         address: u64,
@@ -119,8 +235,13 @@ impl FlashLoader {
 
     /// Reads the HEX data segments and adds them as loadable data blocks to the loader.
     /// This does not create and flash loader instructions yet.
+    ///
+    /// Both the segment (I16HEX) and the linear (I32HEX) addressing are supported.
+    /// The start address records don't contain any data, they are ignored.
     pub fn load_hex_data<T: Read + Seek>(&mut self, file: &mut T) -> Result<(), FileDownloadError> {
         let mut base_address = 0;
+        // With segment addressing, the offset wraps around at the end of the 64 KiB segment.
+        let mut wrap_offset = false;
 
         let mut data = String::new();
         file.read_to_string(&mut data)?;
@@ -130,32 +251,133 @@ impl FlashLoader {
             use Record::*;
             match record {
                 Data { offset, value } => {
-                    let offset = base_address + offset as u64;
-                    self.add_data(offset, &value)?;
+                    let end = offset as usize + value.len();
+
+                    if wrap_offset && end > 0x1_0000 {
+                        let (first, wrapped) = value.split_at(0x1_0000 - offset as usize);
+
+                        self.add_data(base_address + offset as u64, first)?;
+                        self.add_data(base_address, wrapped)?;
+                    } else {
+                        self.add_data(base_address + offset as u64, &value)?;
+                    }
                 }
                 EndOfFile => (),
                 ExtendedSegmentAddress(address) => {
                     base_address = (address as u64) * 16;
+                    wrap_offset = true;
+                }
+                StartSegmentAddress { cs, ip } => {
+                    log::debug!("Ignoring start segment address {:#06x}:{:#06x}", cs, ip);
                 }
-                StartSegmentAddress { .. } => (),
                 ExtendedLinearAddress(address) => {
                     base_address = (address as u64) << 16;
+                    wrap_offset = false;
+                }
+                StartLinearAddress(address) => {
+                    log::debug!("Ignoring start linear address {:#010x}", address);
                 }
-                StartLinearAddress(_) => (),
             };
         }
         Ok(())
     }
 
+    /// Reads the blocks of a UF2 file and adds them as loadable data blocks to the loader.
+    /// This does not create and flash loader instructions yet.
+    ///
+    /// If the file contains blocks for multiple chip families, only the blocks for the family of `target` are used.
+    pub fn load_uf2_data<T: Read>(
+        &mut self,
+        file: &mut T,
+        target: &Target,
+    ) -> Result<(), FileDownloadError> {
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        for block in uf2::blocks_for_target(&data, &target.name)? {
+            self.add_data(block.target_address.into(), block.data)?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads the data of the file at `path` in the given `format`.
+    ///
+    /// Multiple files can be loaded, e.g. a bootloader, an application and a file system image,
+    /// which are then flashed in a single erase and program pass. A file which contains data for
+    /// the same addresses as a previously loaded file is rejected.
+    pub fn load_file(
+        &mut self,
+        path: &Path,
+        format: Format,
+        target: &Target,
+    ) -> Result<(), FileDownloadError> {
+        let mut file = File::open(path)?;
+
+        let mut loader = FlashLoader::new(self.memory_map.clone(), self.source.clone())
+            .with_otp_regions(self.otp_regions);
+
+        match format {
+            Format::Bin(options) => loader.load_bin_data(&mut file, options),
+            Format::Elf(options) => loader.load_elf_data(&mut file, options),
+            Format::Hex => loader.load_hex_data(&mut file),
+            Format::Uf2 => loader.load_uf2_data(&mut file, target),
+        }?;
+
+        let ranges = loader
+            .data()
+            .chain(loader.otp_data())
+            .map(|(address, data)| address..address + data.len() as u64)
+            .collect::<Vec<_>>();
+
+        for range in &ranges {
+            for (existing_path, existing_ranges) in &self.files {
+                if let Some(existing) = existing_ranges
+                    .iter()
+                    .find(|existing| existing.intersects_range(range))
+                {
+                    return Err(FileDownloadError::FilesOverlap {
+                        path: path.to_owned(),
+                        existing_path: existing_path.clone(),
+                        addresses: range.start.max(existing.start)..range.end.min(existing.end),
+                    });
+                }
+            }
+        }
+
+        for (address, data) in loader.data() {
+            self.builder.add_data(address, data)?;
+        }
+
+        for (address, data) in loader.otp_data() {
+            self.otp.add_data(address, data)?;
+        }
+
+        self.files.push((path.to_owned(), ranges));
+        self.symbols.extend(loader.symbols);
+        self.entry_point = self.entry_point.or(loader.entry_point);
+
+        Ok(())
+    }
+
     /// Prepares the data sections that have to be loaded into flash from an ELF file.
     /// This will validate the ELF file and transform all its data into sections but no flash loader commands yet.
-    pub fn load_elf_data<T: Read>(&mut self, file: &mut T) -> Result<(), FileDownloadError> {
+    ///
+    /// Segments which are completely outside of the memory regions of the target are skipped with a warning.
+    pub fn load_elf_data<T: Read>(
+        &mut self,
+        file: &mut T,
+        options: ElfOptions,
+    ) -> Result<(), FileDownloadError> {
         let mut elf_buffer = Vec::new();
         file.read_to_end(&mut elf_buffer)?;
 
         let mut extracted_data = Vec::new();
 
-        let num_sections = extract_from_elf(&mut extracted_data, &elf_buffer)?;
+        let num_sections = extract_from_elf(&mut extracted_data, &elf_buffer, &options)?;
+
+        self.symbols.extend(extract_symbols(&elf_buffer, &options)?);
+        self.entry_point = self.entry_point.or(Some(entry_point(&elf_buffer)?));
 
         if num_sections == 0 {
             log::warn!("No loadable segments were found in the ELF file.");
@@ -182,8 +404,27 @@ impl FlashLoader {
             );
         }
 
+        let mut added = 0;
         for data in extracted_data {
-            self.add_data(data.address.into(), data.data)?;
+            let address = u64::from(data.address);
+            let range = address..address + data.data.len() as u64;
+
+            if !self.is_in_known_memory(&range) {
+                log::warn!(
+                    "Skipping {} at {:#010x}..{:#010x}, it is outside of the memory regions of the target.",
+                    data.section_names.join(", "),
+                    range.start,
+                    range.end
+                );
+                continue;
+            }
+
+            self.add_data(address, data.data)?;
+            added += 1;
+        }
+
+        if added == 0 {
+            log::warn!("None of the loadable segments are in the memory regions of the target.");
         }
 
         Ok(())
@@ -194,15 +435,133 @@ impl FlashLoader {
     /// Requires a session with an attached target that has a known flash algorithm.
     ///
     /// If `do_chip_erase` is `true` the entire flash will be erased.
+    ///
+    /// Data for the OTP memory is programmed after the rest of the image, and requires the
+    /// `otp_programming` permission of the session. The changes are checked before anything is flashed.
     pub fn commit(
         &self,
         session: &mut Session,
         options: DownloadOptions<'_>,
+    ) -> Result<(), FlashError> {
+        self.commit_with(session, options, |_, _| Ok(()))
+    }
+
+    /// Writes all the stored data chunks to flash like [`FlashLoader::commit`], and creates a
+    /// [`VerificationManifest`] of the programmed data.
+    ///
+    /// The programmed data is read back from the chip to compute the digests of the manifest,
+    /// unless it is a dry run.
+    pub fn commit_with_manifest(
+        &self,
+        session: &mut Session,
+        options: DownloadOptions<'_>,
+    ) -> Result<VerificationManifest, FlashError> {
+        let started = SystemTime::now();
+        let start = Instant::now();
+
+        let dry_run = options.dry_run;
+        let skipped = manifest::skipped_ranges(&self.builder, &options.preserved_ranges);
+
+        let mut manifest = None;
+
+        self.commit_with(session, options, |session, builder| {
+            let flash_duration = start.elapsed();
+
+            manifest = Some(manifest::create(
+                session,
+                builder,
+                skipped,
+                dry_run,
+                started,
+                flash_duration,
+            )?);
+
+            Ok(())
+        })?;
+
+        // The manifest is always created if programming succeeded.
+        Ok(manifest.unwrap())
+    }
+
+    /// Writes all the stored data chunks to flash, and calls `programmed` with the
+    /// data which was programmed, before the flash banks are swapped.
+    fn commit_with(
+        &self,
+        session: &mut Session,
+        options: DownloadOptions<'_>,
+        programmed: impl FnOnce(&mut Session, &FlashBuilder) -> Result<(), FlashError>,
+    ) -> Result<(), FlashError> {
+        // The preserved ranges keep the contents of the flash instead of the data of the image.
+        let mut builder = self.builder.without_ranges(&options.preserved_ranges)?;
+
+        // The alias of the flash which has to be used for the data depends on the option bytes.
+        if stm32_trustzone::is_supported(&session.target().name) {
+            builder = stm32_trustzone::assign_aliases(session, &builder)?;
+        }
+
+        let dual_bank = dual_bank::is_supported(&session.target().name);
+
+        if (options.bank.is_some() || options.swap_banks) && !dual_bank {
+            return Err(FlashError::DualBankNotSupported(
+                session.target().name.clone(),
+            ));
+        }
+
+        if let Some(bank) = options.bank {
+            builder = dual_bank::assign_bank(session, &builder, bank)?;
+        } else if dual_bank {
+            dual_bank::log_swapped_banks(session)?;
+        }
+
+        let swap_banks = options.swap_banks && !options.dry_run;
+
+        let otp_report = if self.otp.data.is_empty() {
+            None
+        } else {
+            let report = otp::report(session, &self.otp)?;
+            if !report.is_empty() {
+                log::warn!("Changes of the OTP memory:\n{}", report);
+            }
+
+            if options.dry_run {
+                log::info!("Skipping programming the OTP memory, dry run!");
+                None
+            } else {
+                session
+                    .permissions()
+                    .otp_programming()
+                    .map_err(FlashError::Core)?;
+                Some(report)
+            }
+        };
+
+        self.commit_builder(&builder, session, options)?;
+
+        if let Some(report) = otp_report {
+            otp::program(session, &report)?;
+        }
+
+        // Swapping the banks changes the addresses of the programmed data.
+        programmed(session, &builder)?;
+
+        if swap_banks {
+            dual_bank::swap_banks(session)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the data chunks of `builder` to flash.
+    fn commit_builder(
+        &self,
+        builder: &FlashBuilder,
+        session: &mut Session,
+        options: DownloadOptions<'_>,
     ) -> Result<(), FlashError> {
         log::debug!("committing FlashLoader!");
 
         log::debug!("Contents of builder:");
-        for (&address, data) in &self.builder.data {
+        for (&address, data) in &builder.data {
             log::debug!(
                 "    data: {:08x}-{:08x} ({} bytes)",
                 address,
@@ -224,6 +583,10 @@ impl FlashLoader {
             );
         }
 
+        if session.target().name.starts_with("MIMXRT10") {
+            imxrt::check_boot_headers(builder);
+        }
+
         // Iterate over all memory regions, and program their data.
 
         if self.memory_map != session.target().memory_map {
@@ -253,7 +616,7 @@ impl FlashLoader {
 
                 // If we have no data in this region, ignore it.
                 // This avoids uselessly initializing and deinitializing its flash algorithm.
-                if !self.builder.has_data_in_range(&region.range) {
+                if !builder.has_data_in_range(&region.range) {
                     log::debug!("     -- empty, ignoring!");
                     continue;
                 }
@@ -288,6 +651,11 @@ impl FlashLoader {
             return Ok(());
         }
 
+        let mut resume = options
+            .resume_file
+            .as_deref()
+            .map(|path| ResumeState::open(path, &session.target().name, builder));
+
         // Iterate all flash algorithms we need to use.
         for ((algo_name, core_name), regions) in algos {
             log::debug!("Flashing ranges for algo: {}", algo_name);
@@ -313,6 +681,19 @@ impl FlashLoader {
                 log::warn!("A manual sector erase will be performed.");
             }
 
+            // A chip erase would also erase the preserved ranges outside of the programmed sectors.
+            if do_chip_erase && !options.preserved_ranges.is_empty() {
+                do_chip_erase = false;
+                log::warn!("Chip erase was the selected method to erase the sectors but some ranges of the flash have to be preserved.");
+                log::warn!("A manual sector erase will be performed.");
+            }
+
+            // A chip erase would also erase the sectors programmed by the interrupted download.
+            if do_chip_erase && resume.as_ref().map_or(false, ResumeState::is_resumed) {
+                do_chip_erase = false;
+                log::info!("Resuming a download, the remaining sectors are erased instead of the whole chip.");
+            }
+
             if do_chip_erase {
                 log::debug!("    Doing chip erase...");
                 flasher.run_erase(|active| active.erase_all())?;
@@ -328,7 +709,7 @@ impl FlashLoader {
                 do_use_double_buffering = false;
             }
 
-            for region in regions {
+            for region in &regions {
                 log::debug!(
                     "    programming region: {:08x}-{:08x} ({} bytes)",
                     region.range.start,
@@ -338,14 +719,31 @@ impl FlashLoader {
 
                 // Program the data.
                 flasher.program(
-                    &region,
-                    &self.builder,
+                    region,
+                    builder,
                     options.keep_unwritten_bytes,
                     do_use_double_buffering,
                     options.skip_erase || do_chip_erase,
+                    options.skip_unchanged_sectors,
+                    &options.preserved_ranges,
                     options.progress.unwrap_or(&FlashProgress::new(|_| {})),
+                    resume.as_mut(),
                 )?;
             }
+
+            // Flash which can only be read or verified through the flash algorithm has to be
+            // verified while the algorithm is still loaded.
+            if options.verify && (algo.pc_read.is_some() || algo.pc_verify.is_some()) {
+                log::debug!("    Verifying through the flash algorithm...");
+                for region in &regions {
+                    flasher.verify(region, builder)?;
+                }
+            }
+        }
+
+        // The flash is completely programmed, so a following download starts from scratch.
+        if let Some(resume) = resume {
+            resume.finish()?;
         }
 
         log::debug!("committing RAM!");
@@ -373,7 +771,7 @@ impl FlashLoader {
                 let mut core = session.core(region_core_index).map_err(FlashError::Core)?;
 
                 let mut some = false;
-                for (address, data) in self.builder.data_in_range(&region.range) {
+                for (address, data) in builder.data_in_range(&region.range) {
                     some = true;
                     log::debug!(
                         "     -- writing: {:08x}-{:08x} ({} bytes)",
@@ -394,7 +792,7 @@ impl FlashLoader {
 
         if options.verify {
             log::debug!("Verifying!");
-            for (&address, data) in &self.builder.data {
+            for (&address, data) in &builder.data {
                 log::debug!(
                     "    data: {:08x}-{:08x} ({} bytes)",
                     address,
@@ -406,6 +804,14 @@ impl FlashLoader {
                     .target()
                     .get_memory_region_by_address(address)
                     .unwrap();
+
+                // Regions which are verified through the flash algorithm were already verified.
+                if let MemoryRegion::Nvm(region) = associated_region {
+                    let algo = Self::get_flash_algorithm_for_region(region, session.target())?;
+                    if algo.pc_read.is_some() || algo.pc_verify.is_some() {
+                        continue;
+                    }
+                }
                 let core_name = match associated_region {
                     MemoryRegion::Ram(r) => &r.cores,
                     MemoryRegion::Generic(r) => &r.cores,
@@ -475,10 +881,84 @@ impl FlashLoader {
     }
 
     /// Return data chunks stored in the `FlashLoader` as pairs of address and bytes.
+    ///
+    /// Data for the OTP memory is not included, see [`FlashLoader::otp_data`].
     pub fn data(&self) -> impl Iterator<Item = (u64, &[u8])> {
         self.builder
             .data
             .iter()
             .map(|(address, data)| (*address, data.as_slice()))
     }
+
+    /// Return the data chunks for the OTP memory as pairs of address and bytes.
+    pub fn otp_data(&self) -> impl Iterator<Item = (u64, &[u8])> {
+        self.otp
+            .data
+            .iter()
+            .map(|(address, data)| (*address, data.as_slice()))
+    }
+
+    /// Compare the data for the OTP memory with the current contents of the chip.
+    ///
+    /// The report lists the changes which [`FlashLoader::commit`] makes to the OTP memory.
+    /// Changes which are not possible, e.g. because the memory is already programmed, result in an error.
+    pub fn otp_report(&self, session: &mut Session) -> Result<OtpReport, FlashError> {
+        otp::report(session, &self.otp)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use probe_rs_target::{MemoryRegion, NvmRegion, TargetDescriptionSource};
+
+    use super::FlashLoader;
+
+    fn loader() -> FlashLoader {
+        let memory_map = vec![MemoryRegion::Nvm(NvmRegion {
+            name: None,
+            range: 0..0x1000_0000,
+            is_boot_memory: true,
+            cores: vec!["main".to_owned()],
+        })];
+
+        FlashLoader::new(memory_map, TargetDescriptionSource::BuiltIn)
+    }
+
+    fn load_hex(hex: &str) -> Vec<(u64, Vec<u8>)> {
+        let mut loader = loader();
+
+        loader
+            .load_hex_data(&mut Cursor::new(hex.as_bytes()))
+            .unwrap();
+
+        loader
+            .data()
+            .map(|(address, data)| (address, data.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn hex_extended_linear_address() {
+        let data = load_hex(
+            ":020000040800F2\n\
+             :0400000001020304F2\n\
+             :04000005080001A14D\n\
+             :00000001FF\n",
+        );
+
+        assert_eq!(data, vec![(0x0800_0000, vec![1, 2, 3, 4])]);
+    }
+
+    #[test]
+    fn hex_extended_segment_address_wraps() {
+        let data = load_hex(
+            ":020000021000EC\n\
+             :04FFFE0001020304F5\n\
+             :00000001FF\n",
+        );
+
+        assert_eq!(data, vec![(0x1_0000, vec![3, 4]), (0x1_FFFE, vec![1, 2])]);
+    }
 }
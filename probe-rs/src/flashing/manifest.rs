@@ -0,0 +1,342 @@
+//! Verification manifests, which document what was programmed to a device.
+//!
+//! The manifest contains the SHA-256 digest and the CRC32 of each programmed range, as read back
+//! from the device, the ranges of the image which were skipped, the unique ID of the device and the
+//! timing of the flashing. It can be serialized, e.g. to JSON, for manufacturing traceability.
+
+use std::ops::Range;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use probe_rs_target::MemoryRegion;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use super::builder::FlashBuilder;
+use super::{read_image, FlashError};
+use crate::{MemoryInterface, Session};
+
+/// The manifest of a flashing operation, see [`FlashLoader::commit_with_manifest`](super::FlashLoader::commit_with_manifest).
+#[derive(Debug, Clone, Serialize)]
+pub struct VerificationManifest {
+    /// The name of the chip.
+    pub chip: String,
+    /// The unique ID of the device as hex string, if it is known for the chip.
+    pub unique_id: Option<String>,
+    /// The time at which the flashing started, in seconds since the Unix epoch.
+    pub started: u64,
+    /// The time it took to erase and program the flash, in milliseconds.
+    pub flash_duration_ms: u64,
+    /// The time it took to read back the programmed data, in milliseconds.
+    pub verify_duration_ms: u64,
+    /// Nothing was programmed, and the digests are computed from the image.
+    pub dry_run: bool,
+    /// The programmed ranges, in ascending address order.
+    pub programmed: Vec<ProgrammedRange>,
+    /// The ranges of the image which were not programmed.
+    pub skipped: Vec<SkippedRange>,
+}
+
+impl VerificationManifest {
+    /// Check if the data read back from the device matches the image for all ranges.
+    pub fn is_verified(&self) -> bool {
+        self.programmed
+            .iter()
+            .all(|range| range.verified != Some(false))
+    }
+}
+
+/// A continuous range of programmed data.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgrammedRange {
+    /// The name of the memory region containing the range, if it has one.
+    pub region: Option<String>,
+    /// The start address of the range.
+    pub start: u64,
+    /// The end address of the range, exclusive.
+    pub end: u64,
+    /// The SHA-256 digest of the data as hex string.
+    pub sha256: String,
+    /// The CRC32 of the data.
+    pub crc32: u32,
+    /// Whether the data read back from the device matches the image. `None` for a dry run.
+    pub verified: Option<bool>,
+}
+
+/// Why a range of the image was not programmed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The range is part of the preserved ranges of the [`DownloadOptions`](super::DownloadOptions).
+    Preserved,
+}
+
+/// A range of the image, which was not programmed.
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedRange {
+    /// The start address of the range.
+    pub start: u64,
+    /// The end address of the range, exclusive.
+    pub end: u64,
+    /// Why the range was skipped.
+    pub reason: SkipReason,
+}
+
+/// The location of the unique ID of a chip family.
+struct UniqueId {
+    /// The prefixes of the names of the chips of the family.
+    prefixes: &'static [&'static str],
+    address: u64,
+    size: usize,
+}
+
+static UNIQUE_IDS: &[UniqueId] = &[
+    UniqueId {
+        prefixes: &["STM32F0", "STM32F3"],
+        address: 0x1FFF_F7AC,
+        size: 12,
+    },
+    UniqueId {
+        prefixes: &["STM32F1"],
+        address: 0x1FFF_F7E8,
+        size: 12,
+    },
+    UniqueId {
+        prefixes: &["STM32F2", "STM32F4"],
+        address: 0x1FFF_7A10,
+        size: 12,
+    },
+    UniqueId {
+        prefixes: &["STM32F7"],
+        address: 0x1FF0_F420,
+        size: 12,
+    },
+    UniqueId {
+        prefixes: &["STM32H7"],
+        address: 0x1FF1_E800,
+        size: 12,
+    },
+    UniqueId {
+        prefixes: &["STM32L4", "STM32G0", "STM32G4", "STM32WB"],
+        address: 0x1FFF_7590,
+        size: 12,
+    },
+    // `DEVICEID` in the FICR
+    UniqueId {
+        prefixes: &["nRF51", "nRF52"],
+        address: 0x1000_0060,
+        size: 8,
+    },
+];
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Read the unique ID of the device, if its location is known.
+fn read_unique_id(session: &mut Session) -> Result<Option<String>, FlashError> {
+    let name = session.target().name.clone();
+
+    let unique_id = match UNIQUE_IDS
+        .iter()
+        .find(|id| id.prefixes.iter().any(|prefix| name.starts_with(prefix)))
+    {
+        Some(unique_id) => unique_id,
+        None => return Ok(None),
+    };
+
+    let mut data = vec![0; unique_id.size];
+    session
+        .core(0)
+        .and_then(|mut core| core.read_8(unique_id.address, &mut data))
+        .map_err(FlashError::Core)?;
+
+    Ok(Some(to_hex(&data)))
+}
+
+/// The ranges of the image, which are skipped because they are preserved.
+pub(super) fn skipped_ranges(
+    builder: &FlashBuilder,
+    preserved: &[Range<u64>],
+) -> Vec<SkippedRange> {
+    let mut skipped = Vec::new();
+
+    for (&address, data) in &builder.data {
+        let end = address + data.len() as u64;
+
+        for range in preserved {
+            if range.start < end && address < range.end {
+                skipped.push(SkippedRange {
+                    start: range.start.max(address),
+                    end: range.end.min(end),
+                    reason: SkipReason::Preserved,
+                });
+            }
+        }
+    }
+
+    skipped.sort_by_key(|range| range.start);
+    skipped
+}
+
+/// Compute the digests of the data.
+fn digests(data: &[u8]) -> (String, u32) {
+    (to_hex(&Sha256::digest(data)), crc32fast::hash(data))
+}
+
+/// Split the chunks of `builder` at the boundaries of the memory regions.
+fn programmed_chunks<'data>(
+    memory_map: &[MemoryRegion],
+    builder: &'data FlashBuilder,
+) -> Vec<(Option<String>, u64, &'data [u8])> {
+    let mut chunks = Vec::new();
+
+    for region in memory_map {
+        let (name, range) = match region {
+            MemoryRegion::Nvm(region) => (&region.name, &region.range),
+            MemoryRegion::Ram(region) => (&region.name, &region.range),
+            MemoryRegion::Generic(region) => (&region.name, &region.range),
+        };
+
+        for (address, data) in builder.data_in_range(range) {
+            chunks.push((name.clone(), address, data));
+        }
+    }
+
+    chunks.sort_by_key(|(_, address, _)| *address);
+    chunks
+}
+
+/// Create the manifest for the data of `builder`, which was just programmed.
+pub(super) fn create(
+    session: &mut Session,
+    builder: &FlashBuilder,
+    skipped: Vec<SkippedRange>,
+    dry_run: bool,
+    started: SystemTime,
+    flash_duration: Duration,
+) -> Result<VerificationManifest, FlashError> {
+    let verify_start = Instant::now();
+
+    let memory_map = session.target().memory_map.clone();
+
+    let mut programmed = Vec::new();
+
+    for (region, address, data) in programmed_chunks(&memory_map, builder) {
+        let end = address + data.len() as u64;
+
+        // The digests document what is on the device, so they are computed from the data read back.
+        let (contents, verified) = if dry_run {
+            (data.to_vec(), None)
+        } else {
+            let contents = read_image(session, address..end)?;
+            let verified = contents == data;
+
+            if !verified {
+                log::warn!(
+                    "The flash contents at {:#010x}..{:#010x} don't match the image.",
+                    address,
+                    end
+                );
+            }
+
+            (contents, Some(verified))
+        };
+
+        let (sha256, crc32) = digests(&contents);
+
+        programmed.push(ProgrammedRange {
+            region,
+            start: address,
+            end,
+            sha256,
+            crc32,
+            verified,
+        });
+    }
+
+    let unique_id = if dry_run {
+        None
+    } else {
+        read_unique_id(session)?
+    };
+
+    Ok(VerificationManifest {
+        chip: session.target().name.clone(),
+        unique_id,
+        started: started
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        flash_duration_ms: flash_duration.as_millis() as u64,
+        verify_duration_ms: verify_start.elapsed().as_millis() as u64,
+        dry_run,
+        programmed,
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use probe_rs_target::{MemoryRegion, NvmRegion, RamRegion};
+
+    use super::{digests, programmed_chunks, skipped_ranges, SkipReason};
+    use crate::flashing::builder::FlashBuilder;
+
+    #[test]
+    fn digests_of_data() {
+        let (sha256, crc32) = digests(b"123456789");
+
+        assert_eq!(
+            sha256,
+            "15e2b0d3c33891ebb0f1ef609ec419420c20e320ce94c65fbc8c3312448eb225"
+        );
+        assert_eq!(crc32, 0xCBF4_3926);
+    }
+
+    #[test]
+    fn chunks_are_split_at_regions() {
+        let memory_map = vec![
+            MemoryRegion::Nvm(NvmRegion {
+                name: Some("FLASH".to_owned()),
+                range: 0x0..0x1000,
+                is_boot_memory: true,
+                cores: vec!["main".to_owned()],
+            }),
+            MemoryRegion::Ram(RamRegion {
+                name: None,
+                range: 0x1000..0x2000,
+                is_boot_memory: false,
+                cores: vec!["main".to_owned()],
+            }),
+        ];
+
+        let mut builder = FlashBuilder::new();
+        builder.add_data(0xFFE, &[1, 2, 3, 4]).unwrap();
+
+        let chunks = programmed_chunks(&memory_map, &builder);
+
+        assert_eq!(
+            chunks,
+            vec![
+                (Some("FLASH".to_owned()), 0xFFE, &[1, 2][..]),
+                (None, 0x1000, &[3, 4][..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn preserved_ranges_are_skipped() {
+        let mut builder = FlashBuilder::new();
+        builder.add_data(0x0800_0000, &[0; 0x2000]).unwrap();
+
+        let skipped = skipped_ranges(
+            &builder,
+            &[0x0800_1000..0x0800_4000, 0x0900_0000..0x0900_1000],
+        );
+
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].start, 0x0800_1000);
+        assert_eq!(skipped[0].end, 0x0800_2000);
+        assert_eq!(skipped[0].reason, SkipReason::Preserved);
+    }
+}
@@ -44,22 +44,46 @@
 //!
 
 mod builder;
+mod daplink;
 mod download;
+mod dual_bank;
 mod erase;
 mod error;
 mod flash_algorithm;
 mod flasher;
+mod flm;
+mod imxrt;
 mod loader;
+mod manifest;
+mod option_bytes;
+mod otp;
+mod parallel;
 mod progress;
+mod ram;
+mod read;
+mod resume;
+mod stm32_trustzone;
+mod uf2;
 mod visualizer;
+mod watchdog;
 
 use builder::*;
 use flasher::*;
 
+pub use daplink::*;
 pub use download::*;
+pub use dual_bank::FlashBank;
 pub use erase::*;
 pub use error::*;
 pub use flash_algorithm::*;
+pub use flm::{load_flm, FlmError};
 pub use loader::*;
+pub use manifest::*;
+pub use option_bytes::*;
+pub use otp::{otp_regions, OtpChange, OtpError, OtpRegion, OtpReport};
+pub use parallel::*;
 pub use progress::*;
+pub use ram::run_from_ram;
+pub use read::*;
+pub use uf2::Uf2Error;
 pub use visualizer::*;
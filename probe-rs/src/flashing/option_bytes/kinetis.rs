@@ -0,0 +1,222 @@
+//! The flash configuration field of the Kinetis KL families.
+//!
+//! The flash configuration field is stored at 0x400 in the program flash, in the same sector
+//! as the vector table. It contains the flash protection (`FPROT`), the security byte (`FSEC`)
+//! and the flash options (`FOPT`), which are loaded on reset.
+//!
+//! The words are programmed using the `FTFA` flash interface. A word can only be programmed
+//! when it is erased, changing a programmed word requires erasing the whole sector, so in this case
+//! the configuration has to be changed in the flashed image.
+
+use std::time::{Duration, Instant};
+
+use super::{
+    OptionByteAccess, OptionByteError, OptionField, OptionRegister, ReadoutProtection,
+    WriteProtection, PROGRAM_TIMEOUT,
+};
+use crate::{Core, MemoryInterface};
+
+/// The addresses of the option registers in the flash.
+const ADDRESSES: [u64; 2] = [0x408, 0x40C];
+
+const FTFA_BASE: u64 = 0x4002_0000;
+const FTFA_FSTAT: u64 = 0x00;
+/// `FCCOB3` to `FCCOB0`, which contain the address and the command.
+const FTFA_FCCOB_COMMAND: u64 = 0x04;
+/// `FCCOB7` to `FCCOB4`, which contain the data.
+const FTFA_FCCOB_DATA: u64 = 0x08;
+
+const FSTAT_CCIF: u8 = 1 << 7;
+const FSTAT_ACCERR: u8 = 1 << 5;
+const FSTAT_FPVIOL: u8 = 1 << 4;
+const FSTAT_MGSTAT0: u8 = 1 << 0;
+
+const CMD_PROGRAM_LONGWORD: u32 = 0x06;
+
+const ERASED: u32 = 0xFFFF_FFFF;
+
+/// The `SEC` value for an unsecured chip. All other values secure the chip.
+const SEC_UNSECURE: u32 = 0b10;
+/// The `SEC` value which is used when securing the chip.
+const SEC_SECURE: u32 = 0b11;
+/// The `MEEN` value which disables the mass erase. All other values enable it.
+const MEEN_DISABLED: u32 = 0b10;
+
+static REGISTERS: &[OptionRegister] = &[
+    OptionRegister {
+        name: "FPROT",
+        fields: &[OptionField::new("FPROT", 0, 32)],
+    },
+    OptionRegister {
+        name: "FSEC_FOPT",
+        fields: &[
+            OptionField::new("SEC", 0, 2),
+            OptionField::new("FSLACC", 2, 2),
+            OptionField::new("MEEN", 4, 2),
+            OptionField::new("KEYEN", 6, 2),
+            OptionField::new("FOPT", 8, 8),
+            OptionField::new("FEPROT", 16, 8),
+            OptionField::new("FDPROT", 24, 8),
+        ],
+    },
+];
+
+const SEC: OptionField = OptionField::new("SEC", 0, 2);
+const MEEN: OptionField = OptionField::new("MEEN", 4, 2);
+
+/// The flash configuration field of a Kinetis KL chip.
+#[derive(Debug)]
+pub(super) struct KinetisFamily;
+
+/// Find the Kinetis family of the chip `name`.
+pub(super) fn family(name: &str) -> Option<&'static KinetisFamily> {
+    name.starts_with("MKL").then_some(&KinetisFamily)
+}
+
+impl KinetisFamily {
+    fn wait_command_complete(&self, core: &mut Core<'_>) -> Result<u8, OptionByteError> {
+        let start = Instant::now();
+
+        loop {
+            let status = core.read_word_8(FTFA_BASE + FTFA_FSTAT)?;
+
+            if status & FSTAT_CCIF != 0 {
+                return Ok(status);
+            }
+
+            if start.elapsed() > PROGRAM_TIMEOUT {
+                return Err(OptionByteError::Timeout);
+            }
+        }
+    }
+
+    fn program_longword(
+        &self,
+        core: &mut Core<'_>,
+        address: u64,
+        value: u32,
+    ) -> Result<(), OptionByteError> {
+        self.wait_command_complete(core)?;
+
+        // Clear the errors of previous commands.
+        core.write_word_8(FTFA_BASE + FTFA_FSTAT, FSTAT_ACCERR | FSTAT_FPVIOL)?;
+
+        core.write_word_32(
+            FTFA_BASE + FTFA_FCCOB_COMMAND,
+            (CMD_PROGRAM_LONGWORD << 24) | address as u32,
+        )?;
+        core.write_word_32(FTFA_BASE + FTFA_FCCOB_DATA, value)?;
+
+        // Launch the command.
+        core.write_word_8(FTFA_BASE + FTFA_FSTAT, FSTAT_CCIF)?;
+
+        let status = self.wait_command_complete(core)?;
+        if status & (FSTAT_ACCERR | FSTAT_FPVIOL | FSTAT_MGSTAT0) != 0 {
+            return Err(OptionByteError::ProgrammingFailed(status.into()));
+        }
+
+        Ok(())
+    }
+}
+
+impl OptionByteAccess for KinetisFamily {
+    fn registers(&self) -> &'static [OptionRegister] {
+        REGISTERS
+    }
+
+    fn protection_fields(&self) -> &'static [&'static str] {
+        &["SEC", "MEEN"]
+    }
+
+    fn write_protection(&self) -> WriteProtection {
+        // Each bit protects 1/32 of the program flash.
+        WriteProtection::Regions {
+            field: "FPROT",
+            active_low: true,
+        }
+    }
+
+    fn read(&self, core: &mut Core<'_>) -> Result<Vec<u32>, OptionByteError> {
+        ADDRESSES
+            .iter()
+            .map(|address| Ok(core.read_word_32(*address)?))
+            .collect()
+    }
+
+    fn program(&self, core: &mut Core<'_>, values: &[u32]) -> Result<(), OptionByteError> {
+        let current = self.read(core)?;
+
+        for ((register, address), (current, value)) in REGISTERS
+            .iter()
+            .zip(ADDRESSES)
+            .zip(current.iter().zip(values))
+        {
+            if current == value {
+                continue;
+            }
+
+            if *current != ERASED {
+                return Err(OptionByteError::EraseRequired(register.name));
+            }
+
+            self.program_longword(core, address, *value)?;
+        }
+
+        Ok(())
+    }
+
+    fn launch(&self, core: &mut Core<'_>) -> Result<(), OptionByteError> {
+        core.reset_and_halt(Duration::from_millis(500))?;
+        Ok(())
+    }
+
+    fn readout_protection(&self, values: &[u32]) -> ReadoutProtection {
+        if SEC.get(values[1]) == SEC_UNSECURE {
+            ReadoutProtection::Level0
+        } else if MEEN.get(values[1]) == MEEN_DISABLED {
+            // A secured chip without mass erase can't be unsecured by the debugger.
+            ReadoutProtection::Level2
+        } else {
+            ReadoutProtection::Level1
+        }
+    }
+
+    fn set_readout_protection(&self, values: &mut [u32], level: ReadoutProtection) {
+        let sec = match level {
+            ReadoutProtection::Level0 => SEC_UNSECURE,
+            ReadoutProtection::Level1 | ReadoutProtection::Level2 => SEC_SECURE,
+        };
+
+        values[1] = SEC.set(values[1], sec);
+
+        if level == ReadoutProtection::Level2 {
+            values[1] = MEEN.set(values[1], MEEN_DISABLED);
+        } else if MEEN.get(values[1]) == MEEN_DISABLED {
+            values[1] = MEEN.set(values[1], 0b11);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{family, OptionByteAccess, ReadoutProtection};
+
+    #[test]
+    fn security_encoding() {
+        let kl = family("MKL25Z128xxx4").unwrap();
+
+        // Default flash configuration field of the SDK
+        let mut values = [0xFFFF_FFFF, 0xFFFF_3FFE];
+        assert_eq!(kl.readout_protection(&values), ReadoutProtection::Level0);
+
+        kl.set_readout_protection(&mut values, ReadoutProtection::Level1);
+        assert_eq!(values[1], 0xFFFF_3FFF);
+        assert_eq!(kl.readout_protection(&values), ReadoutProtection::Level1);
+
+        // Secured, with the mass erase disabled
+        assert_eq!(
+            kl.readout_protection(&[0xFFFF_FFFF, 0xFFFF_3FEF]),
+            ReadoutProtection::Level2
+        );
+    }
+}
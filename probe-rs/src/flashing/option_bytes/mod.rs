@@ -0,0 +1,714 @@
+//! Reading and programming the option bytes of a chip.
+//!
+//! The option bytes configure the chip, e.g. the readout protection, the brown out reset level,
+//! the write protection of the flash and the boot configuration. They are accessed through
+//! option registers of the flash interface, which are split into named fields.
+//!
+//! The following configurations are supported:
+//!
+//! - The option bytes of the STM32 families.
+//! - The user information configuration registers (UICR) of the nRF52 family.
+//! - The flash configuration field (`FSEC` and `FOPT`) of the Kinetis KL families.
+//!
+//! Changes which permanently lock the chip are rejected for all families.
+//!
+//! Use [`read_option_bytes`] to read the current option bytes, modify them using
+//! [`OptionBytes::set`], [`OptionBytes::set_readout_protection`] or
+//! [`OptionBytes::set_write_protection`], and program them using [`program_option_bytes`].
+
+mod kinetis;
+mod nrf;
+mod stm32;
+
+use std::{ops::Range, time::Duration};
+
+use probe_rs_target::{FlashProperties, MemoryRegion};
+
+use crate::{Core, Session, Target};
+
+/// Describes an error that happened while reading or programming the option bytes.
+#[derive(thiserror::Error, Debug)]
+pub enum OptionByteError {
+    /// Programming the option bytes of the given chip is not supported.
+    #[error("Option bytes are not supported for the chip '{0}'.")]
+    NotSupported(String),
+    /// There is no field with the given name.
+    #[error("The option bytes have no field named '{0}'.")]
+    UnknownField(String),
+    /// The field can't be set using [`OptionBytes::set`].
+    #[error("The field '{0}' can't be set directly.")]
+    ReservedField(&'static str),
+    /// The value doesn't fit into the field.
+    #[error("The value {value:#x} does not fit into the {width} bit field '{field}'.")]
+    ValueOutOfRange {
+        /// The name of the field.
+        field: &'static str,
+        /// The width of the field in bits.
+        width: u8,
+        /// The value which should have been written.
+        value: u32,
+    },
+    /// Setting readout protection level 2 is not supported, because it can't be reverted.
+    #[error(
+        "Readout protection level 2 permanently disables the debug access, and is not supported."
+    )]
+    PermanentProtection,
+    /// The write protection of the flash can't be changed using the option bytes.
+    #[error("The flash of the chip '{0}' can't be write protected using the option bytes.")]
+    WriteProtectionNotSupported(String),
+    /// The address range is not part of the flash, which can be write protected.
+    #[error("The range {start:#010x}..{end:#010x} is not part of the write protectable flash.")]
+    WriteProtectionOutOfRange {
+        /// The start of the range.
+        start: u64,
+        /// The end of the range.
+        end: u64,
+    },
+    /// The write protection consists of more separate areas than the option bytes can describe.
+    #[error("The option bytes can only write protect {0} separate areas of the flash.")]
+    TooManyWriteProtectionAreas(usize),
+    /// The register can only be changed by erasing the flash sector containing it.
+    #[error("Changing the register '{0}' requires erasing its flash sector, change it in the flashed image instead.")]
+    EraseRequired(&'static str),
+    /// The flash interface reported an error after programming the option bytes.
+    #[error("Programming the option bytes failed, the flash status register is {0:#010x}.")]
+    ProgrammingFailed(u32),
+    /// The flash interface did not finish in time.
+    #[error("Timeout while waiting for the flash interface.")]
+    Timeout,
+    /// An error occurred while accessing the chip.
+    #[error("Error while accessing the chip.")]
+    Core(#[from] crate::Error),
+}
+
+/// The readout protection level of a chip.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReadoutProtection {
+    /// The flash can be read using the debugger.
+    Level0,
+    /// The flash can't be read using the debugger. Returning to level 0 erases the flash.
+    Level1,
+    /// The debug access is permanently disabled.
+    Level2,
+}
+
+/// A named field of an option register.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct OptionField {
+    /// The name of the field, as used in the reference manual of the chip.
+    pub name: &'static str,
+    /// The offset of the lowest bit of the field in the register.
+    pub offset: u8,
+    /// The width of the field in bits.
+    pub width: u8,
+}
+
+impl OptionField {
+    const fn new(name: &'static str, offset: u8, width: u8) -> Self {
+        Self {
+            name,
+            offset,
+            width,
+        }
+    }
+
+    fn mask(&self) -> u32 {
+        (u32::MAX >> (32 - self.width as u32)) << self.offset
+    }
+
+    fn get(&self, register: u32) -> u32 {
+        (register & self.mask()) >> self.offset
+    }
+
+    fn set(&self, register: u32, value: u32) -> u32 {
+        (register & !self.mask()) | ((value << self.offset) & self.mask())
+    }
+}
+
+/// How the option bytes protect the flash against writing and erasing.
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum WriteProtection {
+    /// The flash can't be write protected using the option bytes.
+    None,
+    /// Each bit of the field protects one sector of the flash, starting with the first sector.
+    Sectors {
+        /// The name of the field.
+        field: &'static str,
+        /// A cleared bit protects the sector.
+        active_low: bool,
+    },
+    /// Each bit of the field protects an equally sized region of the flash.
+    Regions {
+        /// The name of the field.
+        field: &'static str,
+        /// A cleared bit protects the region.
+        active_low: bool,
+    },
+    /// Each pair of start and end fields protects the sectors from the start to the end sector,
+    /// including the end sector. The area is disabled if the start is after the end.
+    Areas(&'static [(&'static str, &'static str)]),
+}
+
+/// An option register, which contains a part of the option bytes.
+#[derive(Debug)]
+pub struct OptionRegister {
+    /// The name of the register, as used in the reference manual of the chip.
+    pub name: &'static str,
+    /// The fields of the register.
+    pub fields: &'static [OptionField],
+}
+
+impl OptionRegister {
+    /// The mask of all bits which belong to a field.
+    fn mask(&self) -> u32 {
+        self.fields
+            .iter()
+            .fold(0, |mask, field| mask | field.mask())
+    }
+}
+
+/// Access to the option bytes of a chip family.
+pub(crate) trait OptionByteAccess: Send + Sync {
+    /// The option registers of the chip.
+    fn registers(&self) -> &'static [OptionRegister];
+
+    /// The fields which encode the readout protection.
+    ///
+    /// They can only be changed using [`OptionByteAccess::set_readout_protection`].
+    fn protection_fields(&self) -> &'static [&'static str] {
+        &["RDP"]
+    }
+
+    /// How the option bytes protect the flash against writing.
+    fn write_protection(&self) -> WriteProtection {
+        WriteProtection::None
+    }
+
+    /// Read the current value of all option registers.
+    fn read(&self, core: &mut Core<'_>) -> Result<Vec<u32>, OptionByteError>;
+
+    /// Program the option registers with the given values.
+    fn program(&self, core: &mut Core<'_>, values: &[u32]) -> Result<(), OptionByteError>;
+
+    /// Make the programmed option bytes take effect.
+    ///
+    /// This resets the chip.
+    fn launch(&self, core: &mut Core<'_>) -> Result<(), OptionByteError>;
+
+    /// Decode the readout protection level.
+    fn readout_protection(&self, values: &[u32]) -> ReadoutProtection;
+
+    /// Encode the readout protection level.
+    fn set_readout_protection(&self, values: &mut [u32], level: ReadoutProtection);
+}
+
+/// The time to wait for programming the option bytes to finish.
+///
+/// Returning to readout protection level 0 erases the whole flash, which takes a while.
+const PROGRAM_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The option bytes of a chip.
+pub struct OptionBytes {
+    access: &'static dyn OptionByteAccess,
+    values: Vec<u32>,
+    /// The address ranges of the flash, which are protected by the [`WriteProtection`] units.
+    protection_units: Vec<Range<u64>>,
+    /// The name of the chip.
+    chip: String,
+}
+
+impl std::fmt::Debug for OptionBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut list = f.debug_map();
+
+        for (register, value) in self.access.registers().iter().zip(&self.values) {
+            list.entry(&register.name, &format_args!("{:#010x}", value));
+        }
+
+        list.finish()
+    }
+}
+
+impl OptionBytes {
+    /// The option registers of the chip.
+    pub fn registers(&self) -> &'static [OptionRegister] {
+        self.access.registers()
+    }
+
+    /// The raw values of the option registers, in the order of [`OptionBytes::registers`].
+    pub fn raw(&self) -> &[u32] {
+        &self.values
+    }
+
+    /// Iterate over all fields, and their values.
+    pub fn fields(&self) -> impl Iterator<Item = (&'static OptionField, u32)> + '_ {
+        self.access
+            .registers()
+            .iter()
+            .zip(&self.values)
+            .flat_map(|(register, value)| {
+                register
+                    .fields
+                    .iter()
+                    .map(move |field| (field, field.get(*value)))
+            })
+    }
+
+    fn field_max(&self, name: &str) -> Result<u32, OptionByteError> {
+        let (_, field) = self.find_field(name)?;
+
+        Ok(field.mask() >> field.offset)
+    }
+
+    fn find_field(&self, name: &str) -> Result<(usize, &'static OptionField), OptionByteError> {
+        self.access
+            .registers()
+            .iter()
+            .enumerate()
+            .find_map(|(index, register)| {
+                register
+                    .fields
+                    .iter()
+                    .find(|field| field.name.eq_ignore_ascii_case(name))
+                    .map(|field| (index, field))
+            })
+            .ok_or_else(|| OptionByteError::UnknownField(name.to_string()))
+    }
+
+    /// Get the value of the field `name`.
+    pub fn get(&self, name: &str) -> Result<u32, OptionByteError> {
+        let (index, field) = self.find_field(name)?;
+
+        Ok(field.get(self.values[index]))
+    }
+
+    /// Set the value of the field `name`.
+    ///
+    /// The readout protection can't be changed using this function,
+    /// use [`OptionBytes::set_readout_protection`] instead.
+    pub fn set(&mut self, name: &str, value: u32) -> Result<(), OptionByteError> {
+        let (index, field) = self.find_field(name)?;
+
+        if self.access.protection_fields().contains(&field.name) {
+            return Err(OptionByteError::ReservedField(field.name));
+        }
+
+        if value > field.mask() >> field.offset {
+            return Err(OptionByteError::ValueOutOfRange {
+                field: field.name,
+                width: field.width,
+                value,
+            });
+        }
+
+        self.values[index] = field.set(self.values[index], value);
+
+        Ok(())
+    }
+
+    /// Get for each write protection unit, if it is protected.
+    fn protected_units(&self) -> Result<Vec<bool>, OptionByteError> {
+        let units = self.protection_units.len();
+
+        match self.access.write_protection() {
+            WriteProtection::None => Ok(vec![false; units]),
+            WriteProtection::Sectors { field, active_low }
+            | WriteProtection::Regions { field, active_low } => {
+                let value = self.get(field)?;
+
+                Ok((0..units)
+                    .map(|unit| ((value >> unit) & 1 == 1) != active_low)
+                    .collect())
+            }
+            WriteProtection::Areas(areas) => {
+                let mut protected = vec![false; units];
+
+                for (start, end) in areas {
+                    let start = self.get(start)? as usize;
+                    let end = self.get(end)? as usize;
+
+                    for unit in protected.iter_mut().take(end + 1).skip(start) {
+                        *unit = true;
+                    }
+                }
+
+                Ok(protected)
+            }
+        }
+    }
+
+    /// Set the write protection of all units.
+    fn set_protected_units(&mut self, protected: &[bool]) -> Result<(), OptionByteError> {
+        match self.access.write_protection() {
+            WriteProtection::None => {}
+            WriteProtection::Sectors { field, active_low }
+            | WriteProtection::Regions { field, active_low } => {
+                let mut value = self.get(field)?;
+
+                for (unit, protected) in protected.iter().enumerate() {
+                    if *protected != active_low {
+                        value |= 1 << unit;
+                    } else {
+                        value &= !(1 << unit);
+                    }
+                }
+
+                self.set(field, value)?;
+            }
+            WriteProtection::Areas(areas) => {
+                // The continuous runs of protected units.
+                let mut runs: Vec<(usize, usize)> = Vec::new();
+                for (unit, protected) in protected.iter().enumerate() {
+                    match runs.last_mut() {
+                        Some((_, end)) if *protected && *end + 1 == unit => *end = unit,
+                        _ if *protected => runs.push((unit, unit)),
+                        _ => {}
+                    }
+                }
+
+                if runs.len() > areas.len() {
+                    return Err(OptionByteError::TooManyWriteProtectionAreas(areas.len()));
+                }
+
+                for (index, (start, end)) in areas.iter().enumerate() {
+                    let (start_value, end_value) = match runs.get(index) {
+                        Some((first, last)) => (*first as u32, *last as u32),
+                        None => (self.field_max(start)?, 0),
+                    };
+
+                    self.set(start, start_value)?;
+                    self.set(end, end_value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The address ranges of the flash, which are write protected.
+    ///
+    /// Adjacent protected sectors are merged into a single range.
+    pub fn write_protected_ranges(&self) -> Result<Vec<Range<u64>>, OptionByteError> {
+        let mut ranges: Vec<Range<u64>> = Vec::new();
+
+        for (unit, protected) in self.protection_units.iter().zip(self.protected_units()?) {
+            if !protected {
+                continue;
+            }
+
+            match ranges.last_mut() {
+                Some(range) if range.end == unit.start => range.end = unit.end,
+                _ => ranges.push(unit.clone()),
+            }
+        }
+
+        Ok(ranges)
+    }
+
+    /// Enable or disable the write protection of the flash in the address range.
+    ///
+    /// The write protection is changed for all sectors, which overlap with the range.
+    pub fn set_write_protection(
+        &mut self,
+        range: Range<u64>,
+        protected: bool,
+    ) -> Result<(), OptionByteError> {
+        if self.protection_units.is_empty() {
+            return Err(OptionByteError::WriteProtectionNotSupported(
+                self.chip.clone(),
+            ));
+        }
+
+        let mut units = self.protected_units()?;
+        let mut found = false;
+
+        for (unit, unit_protected) in self.protection_units.iter().zip(units.iter_mut()) {
+            if unit.start < range.end && range.start < unit.end {
+                *unit_protected = protected;
+                found = true;
+            }
+        }
+
+        if !found {
+            return Err(OptionByteError::WriteProtectionOutOfRange {
+                start: range.start,
+                end: range.end,
+            });
+        }
+
+        self.set_protected_units(&units)
+    }
+
+    /// The readout protection level.
+    pub fn readout_protection(&self) -> ReadoutProtection {
+        self.access.readout_protection(&self.values)
+    }
+
+    /// Set the readout protection level.
+    ///
+    /// Level 2 can't be reverted, so setting it is not supported.
+    pub fn set_readout_protection(
+        &mut self,
+        level: ReadoutProtection,
+    ) -> Result<(), OptionByteError> {
+        if level == ReadoutProtection::Level2 {
+            return Err(OptionByteError::PermanentProtection);
+        }
+
+        self.access.set_readout_protection(&mut self.values, level);
+
+        Ok(())
+    }
+}
+
+fn option_byte_access(target: &Target) -> Result<&'static dyn OptionByteAccess, OptionByteError> {
+    stm32::family(&target.name)
+        .map(|family| family as &'static dyn OptionByteAccess)
+        .or_else(|| nrf::family(&target.name).map(|family| family as _))
+        .or_else(|| kinetis::family(&target.name).map(|family| family as _))
+        .ok_or_else(|| OptionByteError::NotSupported(target.name.clone()))
+}
+
+/// The address ranges of the sectors of the flash.
+fn flash_sectors(properties: &FlashProperties) -> Vec<Range<u64>> {
+    let range = &properties.address_range;
+
+    let mut sectors = Vec::new();
+    let mut address = range.start;
+
+    for (index, sector) in properties.sectors.iter().enumerate() {
+        let end = properties
+            .sectors
+            .get(index + 1)
+            .map(|next| range.start + next.address)
+            .unwrap_or(range.end);
+
+        while sector.size > 0 && address < end {
+            sectors.push(address..address + sector.size);
+            address += sector.size;
+        }
+    }
+
+    sectors
+}
+
+/// The address ranges of the flash, which are protected by the units of the write protection.
+fn write_protection_units(access: &dyn OptionByteAccess, target: &Target) -> Vec<Range<u64>> {
+    let boot_memory = target
+        .memory_map
+        .iter()
+        .filter_map(|region| match region {
+            MemoryRegion::Nvm(region) => Some(region),
+            _ => None,
+        })
+        .min_by_key(|region| !region.is_boot_memory);
+
+    let properties = boot_memory.and_then(|region| {
+        target
+            .flash_algorithms
+            .iter()
+            .map(|algorithm| &algorithm.flash_properties)
+            .find(|properties| properties.address_range.contains(&region.range.start))
+    });
+
+    let properties = match properties {
+        Some(properties) => properties,
+        None => return Vec::new(),
+    };
+
+    let field_width = |name: &str| {
+        access
+            .registers()
+            .iter()
+            .flat_map(|register| register.fields)
+            .find(|field| field.name == name)
+            .map(|field| field.width as usize)
+            .unwrap_or_default()
+    };
+
+    match access.write_protection() {
+        WriteProtection::None => Vec::new(),
+        WriteProtection::Sectors { field, .. } => {
+            let mut sectors = flash_sectors(properties);
+            sectors.truncate(field_width(field));
+            sectors
+        }
+        WriteProtection::Regions { field, .. } => {
+            let range = &properties.address_range;
+            let regions = field_width(field) as u64;
+            if regions == 0 {
+                return Vec::new();
+            }
+
+            let size = (range.end - range.start) / regions;
+
+            (0..regions)
+                .map(|region| range.start + region * size..range.start + (region + 1) * size)
+                .collect()
+        }
+        WriteProtection::Areas(_) => flash_sectors(properties),
+    }
+}
+
+/// Read the option bytes of the chip.
+pub fn read_option_bytes(session: &mut Session) -> Result<OptionBytes, OptionByteError> {
+    let access = option_byte_access(session.target())?;
+    let protection_units = write_protection_units(access, session.target());
+    let chip = session.target().name.clone();
+
+    let mut core = session.core(0)?;
+    let values = access.read(&mut core)?;
+
+    Ok(OptionBytes {
+        access,
+        values,
+        protection_units,
+        chip,
+    })
+}
+
+/// Program the option bytes of the chip, and make them take effect.
+///
+/// This resets the chip, and leaves the core halted.
+///
+/// Returning from readout protection level 1 to level 0 erases the flash,
+/// so it requires the `erase_all` permission of the session. Option bytes which
+/// permanently lock the chip are never programmed.
+pub fn program_option_bytes(
+    session: &mut Session,
+    option_bytes: &OptionBytes,
+) -> Result<(), OptionByteError> {
+    let access = option_byte_access(session.target())?;
+
+    let current = {
+        let mut core = session.core(0)?;
+
+        // Make sure that the firmware doesn't interfere with the flash interface.
+        if !core.core_halted()? {
+            core.halt(Duration::from_millis(100))?;
+        }
+
+        access.read(&mut core)?
+    };
+
+    if access.readout_protection(&current) != ReadoutProtection::Level0
+        && option_bytes.readout_protection() == ReadoutProtection::Level0
+    {
+        log::warn!("Returning to readout protection level 0 erases the flash.");
+        session.permissions().erase_all()?;
+    }
+
+    // Bits which don't belong to a known field keep their current value.
+    let values: Vec<_> = access
+        .registers()
+        .iter()
+        .zip(current.iter().zip(&option_bytes.values))
+        .map(|(register, (current, value))| {
+            (current & !register.mask()) | (value & register.mask())
+        })
+        .collect();
+
+    if access.readout_protection(&values) == ReadoutProtection::Level2
+        && access.readout_protection(&current) != ReadoutProtection::Level2
+    {
+        return Err(OptionByteError::PermanentProtection);
+    }
+
+    log::debug!("Programming option bytes: {:x?}", values);
+
+    let mut core = session.core(0)?;
+    access.program(&mut core, &values)?;
+    access.launch(&mut core)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{stm32, OptionByteError, OptionBytes, OptionField};
+
+    #[test]
+    fn option_field_access() {
+        let field = OptionField::new("BOR_LEV", 2, 2);
+
+        assert_eq!(field.mask(), 0b1100);
+        assert_eq!(field.get(0xffff_aaf7), 0b01);
+        assert_eq!(field.set(0xffff_aaf7, 0b10), 0xffff_aafb);
+
+        let field = OptionField::new("IWDG_STOP", 31, 1);
+
+        assert_eq!(field.mask(), 0x8000_0000);
+        assert_eq!(field.set(0, 1), 0x8000_0000);
+    }
+
+    #[test]
+    fn write_protection_areas() {
+        // STM32L4 with 2 KiB pages
+        let mut option_bytes = OptionBytes {
+            access: stm32::family("STM32L476RGTx").unwrap(),
+            values: vec![0xffef_f8aa, 0xff00_ffff, 0xff00_ffff],
+            protection_units: (0..8)
+                .map(|page| 0x0800_0000 + page * 0x800..0x0800_0000 + (page + 1) * 0x800)
+                .collect(),
+            chip: "STM32L476RGTx".to_string(),
+        };
+
+        assert!(option_bytes.write_protected_ranges().unwrap().is_empty());
+
+        option_bytes
+            .set_write_protection(0x0800_0000..0x0800_1000, true)
+            .unwrap();
+        option_bytes
+            .set_write_protection(0x0800_2000..0x0800_2001, true)
+            .unwrap();
+
+        assert_eq!(option_bytes.get("WRP1A_STRT").unwrap(), 0);
+        assert_eq!(option_bytes.get("WRP1A_END").unwrap(), 1);
+        assert_eq!(option_bytes.get("WRP1B_STRT").unwrap(), 4);
+        assert_eq!(
+            option_bytes.write_protected_ranges().unwrap(),
+            vec![0x0800_0000..0x0800_1000, 0x0800_2000..0x0800_2800]
+        );
+
+        // A third area can't be described.
+        assert!(matches!(
+            option_bytes.set_write_protection(0x0800_3800..0x0800_4000, true),
+            Err(OptionByteError::TooManyWriteProtectionAreas(2))
+        ));
+
+        option_bytes
+            .set_write_protection(0x0800_0000..0x0800_4000, false)
+            .unwrap();
+        assert_eq!(option_bytes.get("WRP1A_STRT").unwrap(), 0xff);
+        assert_eq!(option_bytes.get("WRP1A_END").unwrap(), 0);
+        assert!(option_bytes.write_protected_ranges().unwrap().is_empty());
+    }
+
+    #[test]
+    fn write_protection_sectors() {
+        // STM32F407 with 16 KiB sectors at the start
+        let mut option_bytes = OptionBytes {
+            access: stm32::family("STM32F407VGTx").unwrap(),
+            values: vec![0x0fff_aaed],
+            protection_units: (0..4)
+                .map(|sector| 0x0800_0000 + sector * 0x4000..0x0800_0000 + (sector + 1) * 0x4000)
+                .collect(),
+            chip: "STM32F407VGTx".to_string(),
+        };
+
+        option_bytes
+            .set_write_protection(0x0800_4000..0x0800_8000, true)
+            .unwrap();
+
+        assert_eq!(option_bytes.get("nWRP").unwrap(), 0xffd);
+        assert_eq!(
+            option_bytes.write_protected_ranges().unwrap(),
+            vec![0x0800_4000..0x0800_8000]
+        );
+
+        assert!(matches!(
+            option_bytes.set_write_protection(0x0900_0000..0x0900_1000, true),
+            Err(OptionByteError::WriteProtectionOutOfRange { .. })
+        ));
+    }
+}
@@ -0,0 +1,305 @@
+//! The user information configuration registers (UICR) of the nRF52 family.
+//!
+//! The UICR is a flash page, which is programmed through the non-volatile memory controller (NVMC).
+//! Bits can only be changed from 1 to 0 by programming. Any other change requires erasing the whole UICR,
+//! so all programmed words of the UICR, including the ones which are not part of the option registers,
+//! are saved and programmed again afterwards.
+
+use std::time::{Duration, Instant};
+
+use super::{
+    OptionByteAccess, OptionByteError, OptionField, OptionRegister, ReadoutProtection,
+    PROGRAM_TIMEOUT,
+};
+use crate::{Core, MemoryInterface};
+
+const UICR_BASE: u64 = 0x1000_1000;
+/// The number of words at the start of the UICR, which are saved when erasing the UICR.
+///
+/// This covers all registers up to `REGOUT0`.
+const UICR_WORDS: usize = 0x308 / 4;
+
+const NVMC_BASE: u64 = 0x4001_E000;
+const NVMC_READY: u64 = 0x400;
+const NVMC_CONFIG: u64 = 0x504;
+const NVMC_ERASEUICR: u64 = 0x514;
+
+const CONFIG_REN: u32 = 0;
+const CONFIG_WEN: u32 = 1;
+const CONFIG_EEN: u32 = 2;
+
+/// The `PALL` value which disables the access port protection.
+const APPROTECT_DISABLED: u32 = 0xFF;
+/// The `PALL` value which enables the access port protection.
+const APPROTECT_ENABLED: u32 = 0x00;
+/// On newer revisions, this `PALL` value is required to disable the protection after a reset.
+const APPROTECT_HW_DISABLED: u32 = 0x5A;
+
+/// The UICR registers of an nRF52 chip.
+#[derive(Debug)]
+pub(super) struct NrfFamily {
+    /// The prefixes of the names of the chips of the family.
+    prefixes: &'static [&'static str],
+    /// The option registers. The first register is `APPROTECT`.
+    registers: &'static [OptionRegister],
+    /// The offsets of the option registers in the UICR.
+    offsets: &'static [u64],
+}
+
+static NRF52832_REGISTERS: &[OptionRegister] = &[
+    OptionRegister {
+        name: "APPROTECT",
+        fields: &[OptionField::new("PALL", 0, 8)],
+    },
+    OptionRegister {
+        name: "PSELRESET0",
+        fields: &[
+            OptionField::new("RESET0_PIN", 0, 5),
+            OptionField::new("RESET0_CONNECT", 31, 1),
+        ],
+    },
+    OptionRegister {
+        name: "PSELRESET1",
+        fields: &[
+            OptionField::new("RESET1_PIN", 0, 5),
+            OptionField::new("RESET1_CONNECT", 31, 1),
+        ],
+    },
+    OptionRegister {
+        name: "NFCPINS",
+        fields: &[OptionField::new("PROTECT", 0, 1)],
+    },
+];
+
+static NRF52840_REGISTERS: &[OptionRegister] = &[
+    OptionRegister {
+        name: "APPROTECT",
+        fields: &[OptionField::new("PALL", 0, 8)],
+    },
+    OptionRegister {
+        name: "PSELRESET0",
+        fields: &[
+            OptionField::new("RESET0_PIN", 0, 5),
+            OptionField::new("RESET0_PORT", 5, 1),
+            OptionField::new("RESET0_CONNECT", 31, 1),
+        ],
+    },
+    OptionRegister {
+        name: "PSELRESET1",
+        fields: &[
+            OptionField::new("RESET1_PIN", 0, 5),
+            OptionField::new("RESET1_PORT", 5, 1),
+            OptionField::new("RESET1_CONNECT", 31, 1),
+        ],
+    },
+    OptionRegister {
+        name: "NFCPINS",
+        fields: &[OptionField::new("PROTECT", 0, 1)],
+    },
+    OptionRegister {
+        name: "DEBUGCTRL",
+        fields: &[
+            OptionField::new("CPUNIDEN", 0, 8),
+            OptionField::new("CPUFPBEN", 8, 8),
+        ],
+    },
+    OptionRegister {
+        name: "REGOUT0",
+        fields: &[OptionField::new("VOUT", 0, 3)],
+    },
+];
+
+static FAMILIES: &[NrfFamily] = &[
+    NrfFamily {
+        prefixes: &["nRF52832"],
+        registers: NRF52832_REGISTERS,
+        offsets: &[0x208, 0x200, 0x204, 0x20C],
+    },
+    NrfFamily {
+        prefixes: &["nRF52833", "nRF52840"],
+        registers: NRF52840_REGISTERS,
+        offsets: &[0x208, 0x200, 0x204, 0x20C, 0x210, 0x304],
+    },
+];
+
+/// Find the nRF52 family of the chip `name`.
+pub(super) fn family(name: &str) -> Option<&'static NrfFamily> {
+    FAMILIES.iter().find(|family| {
+        family
+            .prefixes
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    })
+}
+
+/// Check if changing the registers from `current` to `values` sets any bit, which requires erasing the UICR.
+fn needs_erase(current: &[u32], values: &[u32]) -> bool {
+    current
+        .iter()
+        .zip(values)
+        .any(|(current, value)| value & !current != 0)
+}
+
+impl NrfFamily {
+    fn wait_ready(&self, core: &mut Core<'_>) -> Result<(), OptionByteError> {
+        let start = Instant::now();
+
+        while core.read_word_32(NVMC_BASE + NVMC_READY)? & 1 == 0 {
+            if start.elapsed() > PROGRAM_TIMEOUT {
+                return Err(OptionByteError::Timeout);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn erase_uicr(&self, core: &mut Core<'_>) -> Result<(), OptionByteError> {
+        log::debug!("Erasing the UICR");
+
+        core.write_word_32(NVMC_BASE + NVMC_CONFIG, CONFIG_EEN)?;
+        core.write_word_32(NVMC_BASE + NVMC_ERASEUICR, 1)?;
+        self.wait_ready(core)?;
+        core.write_word_32(NVMC_BASE + NVMC_CONFIG, CONFIG_REN)?;
+
+        Ok(())
+    }
+
+    /// Program the words at the given addresses of the UICR.
+    fn write_words(
+        &self,
+        core: &mut Core<'_>,
+        words: impl Iterator<Item = (u64, u32)>,
+    ) -> Result<(), OptionByteError> {
+        core.write_word_32(NVMC_BASE + NVMC_CONFIG, CONFIG_WEN)?;
+
+        for (address, value) in words {
+            core.write_word_32(address, value)?;
+            self.wait_ready(core)?;
+        }
+
+        core.write_word_32(NVMC_BASE + NVMC_CONFIG, CONFIG_REN)?;
+
+        Ok(())
+    }
+}
+
+impl OptionByteAccess for NrfFamily {
+    fn registers(&self) -> &'static [OptionRegister] {
+        self.registers
+    }
+
+    fn protection_fields(&self) -> &'static [&'static str] {
+        &["PALL"]
+    }
+
+    fn read(&self, core: &mut Core<'_>) -> Result<Vec<u32>, OptionByteError> {
+        self.offsets
+            .iter()
+            .map(|offset| Ok(core.read_word_32(UICR_BASE + offset)?))
+            .collect()
+    }
+
+    fn program(&self, core: &mut Core<'_>, values: &[u32]) -> Result<(), OptionByteError> {
+        let current = self.read(core)?;
+
+        if !needs_erase(&current, values) {
+            let changed = self
+                .offsets
+                .iter()
+                .zip(current.iter().zip(values))
+                .filter(|(_, (current, value))| current != value)
+                .map(|(offset, (_, value))| (UICR_BASE + offset, *value));
+
+            return self.write_words(core, changed);
+        }
+
+        let mut words = vec![0; UICR_WORDS];
+        core.read_32(UICR_BASE, &mut words)?;
+
+        for (offset, value) in self.offsets.iter().zip(values) {
+            words[*offset as usize / 4] = *value;
+        }
+
+        self.erase_uicr(core)?;
+
+        // Erased words don't have to be programmed.
+        let programmed = words
+            .iter()
+            .enumerate()
+            .filter(|(_, word)| **word != 0xFFFF_FFFF)
+            .map(|(index, word)| (UICR_BASE + index as u64 * 4, *word));
+
+        self.write_words(core, programmed)
+    }
+
+    fn launch(&self, core: &mut Core<'_>) -> Result<(), OptionByteError> {
+        core.reset_and_halt(Duration::from_millis(500))?;
+        Ok(())
+    }
+
+    fn readout_protection(&self, values: &[u32]) -> ReadoutProtection {
+        match values[0] & 0xFF {
+            APPROTECT_DISABLED | APPROTECT_HW_DISABLED => ReadoutProtection::Level0,
+            _ => ReadoutProtection::Level1,
+        }
+    }
+
+    fn set_readout_protection(&self, values: &mut [u32], level: ReadoutProtection) {
+        // There is no permanent protection, the protection is always removed by an erase all.
+        let pall = match level {
+            ReadoutProtection::Level0 => APPROTECT_DISABLED,
+            ReadoutProtection::Level1 | ReadoutProtection::Level2 => APPROTECT_ENABLED,
+        };
+
+        values[0] = (values[0] & !0xFF) | pall;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{family, needs_erase, OptionByteAccess, ReadoutProtection};
+
+    #[test]
+    fn access_port_protection() {
+        let nrf52840 = family("nRF52840_xxAA").unwrap();
+
+        // Erased UICR
+        let mut values = [0xFFFF_FFFF; 6];
+        assert_eq!(
+            nrf52840.readout_protection(&values),
+            ReadoutProtection::Level0
+        );
+
+        nrf52840.set_readout_protection(&mut values, ReadoutProtection::Level1);
+        assert_eq!(values[0], 0xFFFF_FF00);
+        assert_eq!(
+            nrf52840.readout_protection(&values),
+            ReadoutProtection::Level1
+        );
+
+        // Hardware disabled protection of newer revisions
+        assert_eq!(
+            nrf52840.readout_protection(&[0xFFFF_FF5A]),
+            ReadoutProtection::Level0
+        );
+    }
+
+    #[test]
+    fn erase_only_when_setting_bits() {
+        assert!(!needs_erase(
+            &[0xFFFF_FFFF, 0x7FFF_FF12],
+            &[0xFFFF_FF00, 0x7FFF_FF02]
+        ));
+        assert!(needs_erase(
+            &[0xFFFF_FF00, 0xFFFF_FFFF],
+            &[0xFFFF_FFFF, 0xFFFF_FFFF]
+        ));
+    }
+
+    #[test]
+    fn families() {
+        assert_eq!(family("nRF52832_xxAA").unwrap().registers().len(), 4);
+        assert_eq!(family("nRF52833_xxAA").unwrap().registers().len(), 6);
+        assert!(family("nRF51822_xxAA").is_none());
+    }
+}
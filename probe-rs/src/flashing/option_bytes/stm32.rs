@@ -0,0 +1,721 @@
+//! Option bytes of the STM32 families.
+//!
+//! There are three kinds of flash interfaces:
+//!
+//! - The F4 and F7 have an `OPTCR` register, which contains the option bytes and the control bits.
+//!   The option bytes are loaded on the next reset.
+//! - The H7 has separate registers for the current and the programmed option bytes.
+//!   The option bytes take effect directly after programming.
+//! - The L4, G0 and G4 have an `OPTR` register, which is programmed through the flash control register.
+//!   The option bytes are loaded using `OBL_LAUNCH`, which resets the chip.
+
+use std::time::{Duration, Instant};
+
+use super::{
+    OptionByteAccess, OptionByteError, OptionField, OptionRegister, ReadoutProtection,
+    WriteProtection, PROGRAM_TIMEOUT,
+};
+use crate::{Core, MemoryInterface};
+
+const OPTKEY1: u32 = 0x0819_2A3B;
+const OPTKEY2: u32 = 0x4C5D_6E7F;
+
+const KEY1: u32 = 0x4567_0123;
+const KEY2: u32 = 0xCDEF_89AB;
+
+/// The `RDP` value for level 0. All other values except [`RDP_LEVEL_2`] select level 1.
+const RDP_LEVEL_0: u32 = 0xAA;
+/// The `RDP` value for level 1, which is used when setting level 1.
+const RDP_LEVEL_1: u32 = 0xBB;
+/// The `RDP` value for level 2.
+const RDP_LEVEL_2: u32 = 0xCC;
+
+/// The time the chip needs to come out of reset after `OBL_LAUNCH`.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The flash interface of an STM32 family.
+#[derive(Debug)]
+enum FlashInterface {
+    /// `OPTCR` based interface of the F4 and F7.
+    Optcr {
+        /// Address of the `OPTCR1` register, if the family has one.
+        optcr1: Option<u64>,
+    },
+    /// The H7 interface, with separate current and programmed option registers.
+    H7,
+    /// `OPTR` based interface of the L4, G0 and G4.
+    Optr,
+}
+
+/// The option bytes of an STM32 family.
+#[derive(Debug)]
+pub(super) struct Stm32Family {
+    /// The prefixes of the names of the chips of the family.
+    prefixes: &'static [&'static str],
+    /// The base address of the flash interface.
+    flash_base: u64,
+    interface: FlashInterface,
+    /// The option registers. The first register contains the `RDP` field.
+    registers: &'static [OptionRegister],
+    /// The offsets of the option registers to read them.
+    read_offsets: &'static [u64],
+    /// The offsets of the option registers to program them.
+    write_offsets: &'static [u64],
+    write_protection: WriteProtection,
+}
+
+static STM32F4_REGISTERS: &[OptionRegister] = &[OptionRegister {
+    name: "OPTCR",
+    fields: &[
+        OptionField::new("BOR_LEV", 2, 2),
+        OptionField::new("WDG_SW", 5, 1),
+        OptionField::new("nRST_STOP", 6, 1),
+        OptionField::new("nRST_STDBY", 7, 1),
+        OptionField::new("RDP", 8, 8),
+        OptionField::new("nWRP", 16, 12),
+    ],
+}];
+
+static STM32F7_REGISTERS: &[OptionRegister] = &[
+    OptionRegister {
+        name: "OPTCR",
+        fields: &[
+            OptionField::new("BOR_LEV", 2, 2),
+            OptionField::new("WWDG_SW", 4, 1),
+            OptionField::new("IWDG_SW", 5, 1),
+            OptionField::new("nRST_STOP", 6, 1),
+            OptionField::new("nRST_STDBY", 7, 1),
+            OptionField::new("RDP", 8, 8),
+            OptionField::new("nWRP", 16, 8),
+            OptionField::new("IWDG_STDBY", 30, 1),
+            OptionField::new("IWDG_STOP", 31, 1),
+        ],
+    },
+    OptionRegister {
+        name: "OPTCR1",
+        fields: &[
+            OptionField::new("BOOT_ADD0", 0, 16),
+            OptionField::new("BOOT_ADD1", 16, 16),
+        ],
+    },
+];
+
+static STM32H7_REGISTERS: &[OptionRegister] = &[
+    OptionRegister {
+        name: "OPTSR",
+        fields: &[
+            OptionField::new("BOR_LEV", 2, 2),
+            OptionField::new("IWDG1_SW", 4, 1),
+            OptionField::new("NRST_STOP_D1", 6, 1),
+            OptionField::new("NRST_STBY_D1", 7, 1),
+            OptionField::new("RDP", 8, 8),
+            OptionField::new("FZ_IWDG_STOP", 17, 1),
+            OptionField::new("FZ_IWDG_SDBY", 18, 1),
+            OptionField::new("ST_RAM_SIZE", 19, 2),
+            OptionField::new("SECURITY", 21, 1),
+            OptionField::new("IO_HSLV", 29, 1),
+            OptionField::new("SWAP_BANK_OPT", 31, 1),
+        ],
+    },
+    OptionRegister {
+        name: "BOOT",
+        fields: &[
+            OptionField::new("BOOT_ADD0", 0, 16),
+            OptionField::new("BOOT_ADD1", 16, 16),
+        ],
+    },
+    OptionRegister {
+        name: "WPSN1",
+        fields: &[OptionField::new("WRPSn1", 0, 8)],
+    },
+];
+
+static STM32H7_DUAL_CORE_REGISTERS: &[OptionRegister] = &[
+    OptionRegister {
+        name: "OPTSR",
+        fields: &[
+            OptionField::new("BOR_LEV", 2, 2),
+            OptionField::new("IWDG1_SW", 4, 1),
+            OptionField::new("IWDG2_SW", 5, 1),
+            OptionField::new("NRST_STOP_D1", 6, 1),
+            OptionField::new("NRST_STBY_D1", 7, 1),
+            OptionField::new("RDP", 8, 8),
+            OptionField::new("FZ_IWDG_STOP", 17, 1),
+            OptionField::new("FZ_IWDG_SDBY", 18, 1),
+            OptionField::new("ST_RAM_SIZE", 19, 2),
+            OptionField::new("SECURITY", 21, 1),
+            OptionField::new("BCM4", 22, 1),
+            OptionField::new("BCM7", 23, 1),
+            OptionField::new("NRST_STOP_D2", 24, 1),
+            OptionField::new("NRST_STBY_D2", 25, 1),
+            OptionField::new("IO_HSLV", 29, 1),
+            OptionField::new("SWAP_BANK_OPT", 31, 1),
+        ],
+    },
+    OptionRegister {
+        name: "BOOT_CM7",
+        fields: &[
+            OptionField::new("BOOT_CM7_ADD0", 0, 16),
+            OptionField::new("BOOT_CM7_ADD1", 16, 16),
+        ],
+    },
+    OptionRegister {
+        name: "BOOT_CM4",
+        fields: &[
+            OptionField::new("BOOT_CM4_ADD0", 0, 16),
+            OptionField::new("BOOT_CM4_ADD1", 16, 16),
+        ],
+    },
+    OptionRegister {
+        name: "WPSN1",
+        fields: &[OptionField::new("WRPSn1", 0, 8)],
+    },
+];
+
+static STM32L4_REGISTERS: &[OptionRegister] = &[
+    OptionRegister {
+        name: "OPTR",
+        fields: &[
+            OptionField::new("RDP", 0, 8),
+            OptionField::new("BOR_LEV", 8, 3),
+            OptionField::new("nRST_STOP", 12, 1),
+            OptionField::new("nRST_STDBY", 13, 1),
+            OptionField::new("nRST_SHDW", 14, 1),
+            OptionField::new("IWDG_SW", 16, 1),
+            OptionField::new("IWDG_STOP", 17, 1),
+            OptionField::new("IWDG_STDBY", 18, 1),
+            OptionField::new("WWDG_SW", 19, 1),
+            OptionField::new("BFB2", 20, 1),
+            OptionField::new("DUALBANK", 21, 1),
+            OptionField::new("nBOOT1", 23, 1),
+            OptionField::new("SRAM2_PE", 24, 1),
+            OptionField::new("SRAM2_RST", 25, 1),
+            OptionField::new("nSWBOOT0", 26, 1),
+            OptionField::new("nBOOT0", 27, 1),
+        ],
+    },
+    OptionRegister {
+        name: "WRP1AR",
+        fields: &[
+            OptionField::new("WRP1A_STRT", 0, 8),
+            OptionField::new("WRP1A_END", 16, 8),
+        ],
+    },
+    OptionRegister {
+        name: "WRP1BR",
+        fields: &[
+            OptionField::new("WRP1B_STRT", 0, 8),
+            OptionField::new("WRP1B_END", 16, 8),
+        ],
+    },
+];
+
+static STM32G0_REGISTERS: &[OptionRegister] = &[
+    OptionRegister {
+        name: "OPTR",
+        fields: &[
+            OptionField::new("RDP", 0, 8),
+            OptionField::new("BOR_EN", 8, 1),
+            OptionField::new("BORF_LEV", 9, 2),
+            OptionField::new("BORR_LEV", 11, 2),
+            OptionField::new("nRST_STOP", 13, 1),
+            OptionField::new("nRST_STDBY", 14, 1),
+            OptionField::new("nRST_SHDW", 15, 1),
+            OptionField::new("IWDG_SW", 16, 1),
+            OptionField::new("IWDG_STOP", 17, 1),
+            OptionField::new("IWDG_STDBY", 18, 1),
+            OptionField::new("WWDG_SW", 19, 1),
+            OptionField::new("RAM_PARITY_CHECK", 22, 1),
+            OptionField::new("nBOOT_SEL", 24, 1),
+            OptionField::new("nBOOT1", 25, 1),
+            OptionField::new("nBOOT0", 26, 1),
+            OptionField::new("NRST_MODE", 27, 2),
+            OptionField::new("IRHEN", 29, 1),
+        ],
+    },
+    OptionRegister {
+        name: "WRP1AR",
+        fields: &[
+            OptionField::new("WRP1A_STRT", 0, 6),
+            OptionField::new("WRP1A_END", 16, 6),
+        ],
+    },
+    OptionRegister {
+        name: "WRP1BR",
+        fields: &[
+            OptionField::new("WRP1B_STRT", 0, 6),
+            OptionField::new("WRP1B_END", 16, 6),
+        ],
+    },
+];
+
+static STM32G4_REGISTERS: &[OptionRegister] = &[
+    OptionRegister {
+        name: "OPTR",
+        fields: &[
+            OptionField::new("RDP", 0, 8),
+            OptionField::new("BOR_LEV", 8, 3),
+            OptionField::new("nRST_STOP", 12, 1),
+            OptionField::new("nRST_STDBY", 13, 1),
+            OptionField::new("nRST_SHDW", 14, 1),
+            OptionField::new("IWDG_SW", 16, 1),
+            OptionField::new("IWDG_STOP", 17, 1),
+            OptionField::new("IWDG_STDBY", 18, 1),
+            OptionField::new("WWDG_SW", 19, 1),
+            OptionField::new("BFB2", 20, 1),
+            OptionField::new("DBANK", 22, 1),
+            OptionField::new("nBOOT1", 23, 1),
+            OptionField::new("SRAM_PE", 24, 1),
+            OptionField::new("CCMSRAM_RST", 25, 1),
+            OptionField::new("nSWBOOT0", 26, 1),
+            OptionField::new("nBOOT0", 27, 1),
+            OptionField::new("NRST_MODE", 28, 2),
+            OptionField::new("IRHEN", 30, 1),
+        ],
+    },
+    OptionRegister {
+        name: "WRP1AR",
+        fields: &[
+            OptionField::new("WRP1A_STRT", 0, 7),
+            OptionField::new("WRP1A_END", 16, 7),
+        ],
+    },
+    OptionRegister {
+        name: "WRP1BR",
+        fields: &[
+            OptionField::new("WRP1B_STRT", 0, 7),
+            OptionField::new("WRP1B_END", 16, 7),
+        ],
+    },
+];
+
+/// The sector write protection of the F4, F7 and H7, a cleared bit protects the sector.
+const NWRP: WriteProtection = WriteProtection::Sectors {
+    field: "nWRP",
+    active_low: true,
+};
+
+const WRPSN1: WriteProtection = WriteProtection::Sectors {
+    field: "WRPSn1",
+    active_low: true,
+};
+
+/// The two write protection areas of the L4, G0 and G4, in pages.
+const WRP_AREAS: WriteProtection =
+    WriteProtection::Areas(&[("WRP1A_STRT", "WRP1A_END"), ("WRP1B_STRT", "WRP1B_END")]);
+
+static FAMILIES: &[Stm32Family] = &[
+    Stm32Family {
+        prefixes: &["STM32F4"],
+        flash_base: 0x4002_3C00,
+        interface: FlashInterface::Optcr { optcr1: None },
+        registers: STM32F4_REGISTERS,
+        read_offsets: &[optcr::OPTCR],
+        write_offsets: &[optcr::OPTCR],
+        write_protection: NWRP,
+    },
+    Stm32Family {
+        prefixes: &["STM32F7"],
+        flash_base: 0x4002_3C00,
+        interface: FlashInterface::Optcr {
+            optcr1: Some(optcr::OPTCR1),
+        },
+        registers: STM32F7_REGISTERS,
+        read_offsets: &[optcr::OPTCR, optcr::OPTCR1],
+        write_offsets: &[optcr::OPTCR, optcr::OPTCR1],
+        write_protection: NWRP,
+    },
+    // The dual core parts have to be matched before the other H7 parts.
+    Stm32Family {
+        prefixes: &["STM32H745", "STM32H747", "STM32H755", "STM32H757"],
+        flash_base: 0x5200_2000,
+        interface: FlashInterface::H7,
+        registers: STM32H7_DUAL_CORE_REGISTERS,
+        read_offsets: &[h7::OPTSR_CUR, h7::BOOT_CURR, h7::BOOT4_CURR, h7::WPSN_CUR1R],
+        write_offsets: &[h7::OPTSR_PRG, h7::BOOT_PRGR, h7::BOOT4_PRGR, h7::WPSN_PRG1R],
+        write_protection: WRPSN1,
+    },
+    Stm32Family {
+        prefixes: &["STM32H7"],
+        flash_base: 0x5200_2000,
+        interface: FlashInterface::H7,
+        registers: STM32H7_REGISTERS,
+        read_offsets: &[h7::OPTSR_CUR, h7::BOOT_CURR, h7::WPSN_CUR1R],
+        write_offsets: &[h7::OPTSR_PRG, h7::BOOT_PRGR, h7::WPSN_PRG1R],
+        write_protection: WRPSN1,
+    },
+    Stm32Family {
+        prefixes: &["STM32L4"],
+        flash_base: 0x4002_2000,
+        interface: FlashInterface::Optr,
+        registers: STM32L4_REGISTERS,
+        read_offsets: &[optr::OPTR, optr::WRP1AR, optr::WRP1BR],
+        write_offsets: &[optr::OPTR, optr::WRP1AR, optr::WRP1BR],
+        write_protection: WRP_AREAS,
+    },
+    Stm32Family {
+        prefixes: &["STM32G0"],
+        flash_base: 0x4002_2000,
+        interface: FlashInterface::Optr,
+        registers: STM32G0_REGISTERS,
+        read_offsets: &[optr::OPTR, optr::WRP1AR, optr::WRP1BR],
+        write_offsets: &[optr::OPTR, optr::WRP1AR, optr::WRP1BR],
+        write_protection: WRP_AREAS,
+    },
+    Stm32Family {
+        prefixes: &["STM32G4"],
+        flash_base: 0x4002_2000,
+        interface: FlashInterface::Optr,
+        registers: STM32G4_REGISTERS,
+        read_offsets: &[optr::OPTR, optr::WRP1AR, optr::WRP1BR],
+        write_offsets: &[optr::OPTR, optr::WRP1AR, optr::WRP1BR],
+        write_protection: WRP_AREAS,
+    },
+];
+
+/// Find the STM32 family of the chip `name`.
+pub(super) fn family(name: &str) -> Option<&'static Stm32Family> {
+    FAMILIES.iter().find(|family| {
+        family
+            .prefixes
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    })
+}
+
+/// Registers of the `OPTCR` based flash interface.
+mod optcr {
+    pub const OPTKEYR: u64 = 0x08;
+    pub const SR: u64 = 0x0C;
+    pub const OPTCR: u64 = 0x14;
+    pub const OPTCR1: u64 = 0x18;
+
+    pub const SR_BSY: u32 = 1 << 16;
+    /// `OPERR`, `WRPERR`, `PGAERR`, `PGPERR` and `PGSERR`/`ERSERR`.
+    pub const SR_ERRORS: u32 = 0xF2;
+
+    pub const OPTCR_OPTLOCK: u32 = 1 << 0;
+    pub const OPTCR_OPTSTRT: u32 = 1 << 1;
+}
+
+/// Registers of the H7 flash interface.
+mod h7 {
+    pub const OPTKEYR: u64 = 0x08;
+    pub const OPTCR: u64 = 0x18;
+    pub const OPTSR_CUR: u64 = 0x1C;
+    pub const OPTSR_PRG: u64 = 0x20;
+    pub const OPTCCR: u64 = 0x24;
+    pub const WPSN_CUR1R: u64 = 0x38;
+    pub const WPSN_PRG1R: u64 = 0x3C;
+    pub const BOOT_CURR: u64 = 0x40;
+    pub const BOOT_PRGR: u64 = 0x44;
+    /// The boot address of the CM4 core of the dual core parts.
+    pub const BOOT4_CURR: u64 = 0x48;
+    pub const BOOT4_PRGR: u64 = 0x4C;
+
+    pub const OPTCR_OPTLOCK: u32 = 1 << 0;
+    pub const OPTCR_OPTSTART: u32 = 1 << 1;
+
+    pub const OPTSR_OPT_BUSY: u32 = 1 << 0;
+    pub const OPTSR_OPTCHANGEERR: u32 = 1 << 30;
+
+    pub const OPTCCR_CLR_OPTCHANGEERR: u32 = 1 << 30;
+}
+
+/// Registers of the `OPTR` based flash interface.
+mod optr {
+    pub const KEYR: u64 = 0x08;
+    pub const OPTKEYR: u64 = 0x0C;
+    pub const SR: u64 = 0x10;
+    pub const CR: u64 = 0x14;
+    pub const OPTR: u64 = 0x20;
+    pub const WRP1AR: u64 = 0x2C;
+    pub const WRP1BR: u64 = 0x30;
+
+    pub const SR_BSY: u32 = 1 << 16;
+    /// `OPERR`, `PROGERR`, `WRPERR`, `PGAERR`, `SIZERR`, `PGSERR`, `MISERR`, `FASTERR`, `RDERR` and `OPTVERR`.
+    pub const SR_ERRORS: u32 = 0xC3FA;
+
+    pub const CR_OPTSTRT: u32 = 1 << 17;
+    pub const CR_OBL_LAUNCH: u32 = 1 << 27;
+    pub const CR_OPTLOCK: u32 = 1 << 30;
+    pub const CR_LOCK: u32 = 1 << 31;
+}
+
+impl Stm32Family {
+    fn read_register(&self, core: &mut Core<'_>, offset: u64) -> Result<u32, OptionByteError> {
+        Ok(core.read_word_32(self.flash_base + offset)?)
+    }
+
+    fn write_register(
+        &self,
+        core: &mut Core<'_>,
+        offset: u64,
+        value: u32,
+    ) -> Result<(), OptionByteError> {
+        Ok(core.write_word_32(self.flash_base + offset, value)?)
+    }
+
+    /// Wait until `done` returns true for the value of the register at `offset`.
+    fn wait_for(
+        &self,
+        core: &mut Core<'_>,
+        offset: u64,
+        timeout: Duration,
+        done: impl Fn(u32) -> bool,
+    ) -> Result<u32, OptionByteError> {
+        let start = Instant::now();
+
+        loop {
+            let value = self.read_register(core, offset)?;
+
+            if done(value) {
+                return Ok(value);
+            }
+
+            if start.elapsed() > timeout {
+                return Err(OptionByteError::Timeout);
+            }
+        }
+    }
+
+    fn program_optcr(
+        &self,
+        core: &mut Core<'_>,
+        optcr1: Option<u64>,
+        values: &[u32],
+    ) -> Result<(), OptionByteError> {
+        use optcr::*;
+
+        self.wait_for(core, SR, PROGRAM_TIMEOUT, |sr| sr & SR_BSY == 0)?;
+
+        if self.read_register(core, OPTCR)? & OPTCR_OPTLOCK != 0 {
+            self.write_register(core, OPTKEYR, OPTKEY1)?;
+            self.write_register(core, OPTKEYR, OPTKEY2)?;
+        }
+
+        if let Some(optcr1) = optcr1 {
+            self.write_register(core, optcr1, values[1])?;
+        }
+
+        let optcr = values[0] & !(OPTCR_OPTLOCK | OPTCR_OPTSTRT);
+        self.write_register(core, OPTCR, optcr)?;
+        self.write_register(core, OPTCR, optcr | OPTCR_OPTSTRT)?;
+
+        let sr = self.wait_for(core, SR, PROGRAM_TIMEOUT, |sr| sr & SR_BSY == 0)?;
+
+        self.write_register(core, OPTCR, optcr | OPTCR_OPTLOCK)?;
+
+        if sr & SR_ERRORS != 0 {
+            self.write_register(core, SR, sr & SR_ERRORS)?;
+            return Err(OptionByteError::ProgrammingFailed(sr));
+        }
+
+        Ok(())
+    }
+
+    fn program_h7(&self, core: &mut Core<'_>, values: &[u32]) -> Result<(), OptionByteError> {
+        use h7::*;
+
+        self.wait_for(core, OPTSR_CUR, PROGRAM_TIMEOUT, |optsr| {
+            optsr & OPTSR_OPT_BUSY == 0
+        })?;
+
+        if self.read_register(core, OPTCR)? & OPTCR_OPTLOCK != 0 {
+            self.write_register(core, OPTKEYR, OPTKEY1)?;
+            self.write_register(core, OPTKEYR, OPTKEY2)?;
+        }
+
+        for (offset, value) in self.write_offsets.iter().zip(values) {
+            self.write_register(core, *offset, *value)?;
+        }
+
+        self.write_register(core, OPTCR, OPTCR_OPTSTART)?;
+
+        let optsr = self.wait_for(core, OPTSR_CUR, PROGRAM_TIMEOUT, |optsr| {
+            optsr & OPTSR_OPT_BUSY == 0
+        })?;
+
+        self.write_register(core, OPTCR, OPTCR_OPTLOCK)?;
+
+        if optsr & OPTSR_OPTCHANGEERR != 0 {
+            self.write_register(core, OPTCCR, OPTCCR_CLR_OPTCHANGEERR)?;
+            return Err(OptionByteError::ProgrammingFailed(optsr));
+        }
+
+        Ok(())
+    }
+
+    fn program_optr(&self, core: &mut Core<'_>, values: &[u32]) -> Result<(), OptionByteError> {
+        use optr::*;
+
+        self.wait_for(core, SR, PROGRAM_TIMEOUT, |sr| sr & SR_BSY == 0)?;
+
+        // Clear errors of previous operations, otherwise the programming doesn't start.
+        let sr = self.read_register(core, SR)?;
+        self.write_register(core, SR, sr & SR_ERRORS)?;
+
+        // The flash control register has to be unlocked before the option bytes.
+        let cr = self.read_register(core, CR)?;
+        if cr & CR_LOCK != 0 {
+            self.write_register(core, KEYR, KEY1)?;
+            self.write_register(core, KEYR, KEY2)?;
+        }
+
+        if cr & CR_OPTLOCK != 0 {
+            self.write_register(core, OPTKEYR, OPTKEY1)?;
+            self.write_register(core, OPTKEYR, OPTKEY2)?;
+        }
+
+        for (offset, value) in self.write_offsets.iter().zip(values) {
+            self.write_register(core, *offset, *value)?;
+        }
+
+        self.write_register(core, CR, CR_OPTSTRT)?;
+
+        let sr = self.wait_for(core, SR, PROGRAM_TIMEOUT, |sr| sr & SR_BSY == 0)?;
+
+        if sr & SR_ERRORS != 0 {
+            self.write_register(core, SR, sr & SR_ERRORS)?;
+            self.write_register(core, CR, CR_LOCK)?;
+            return Err(OptionByteError::ProgrammingFailed(sr));
+        }
+
+        Ok(())
+    }
+
+    fn launch_optr(&self, core: &mut Core<'_>) -> Result<(), OptionByteError> {
+        use optr::*;
+
+        log::debug!("Loading the option bytes using OBL_LAUNCH");
+
+        // The chip is reset while the write is executed, so the write is usually not acknowledged.
+        if let Err(error) = self.write_register(core, CR, CR_OBL_LAUNCH) {
+            log::debug!("Write of OBL_LAUNCH failed, as expected: {:?}", error);
+        }
+
+        // After the reset, the flash control register is locked again.
+        let start = Instant::now();
+        loop {
+            match self.read_register(core, CR) {
+                Ok(cr) if cr & CR_LOCK != 0 => break,
+                Ok(_) => {}
+                Err(error) => log::debug!("Chip is not accessible yet: {:?}", error),
+            }
+
+            if start.elapsed() > LAUNCH_TIMEOUT {
+                return Err(OptionByteError::Timeout);
+            }
+        }
+
+        core.halt(Duration::from_millis(100))?;
+
+        Ok(())
+    }
+}
+
+impl OptionByteAccess for Stm32Family {
+    fn registers(&self) -> &'static [OptionRegister] {
+        self.registers
+    }
+
+    fn write_protection(&self) -> WriteProtection {
+        self.write_protection
+    }
+
+    fn read(&self, core: &mut Core<'_>) -> Result<Vec<u32>, OptionByteError> {
+        self.read_offsets
+            .iter()
+            .map(|offset| self.read_register(core, *offset))
+            .collect()
+    }
+
+    fn program(&self, core: &mut Core<'_>, values: &[u32]) -> Result<(), OptionByteError> {
+        match self.interface {
+            FlashInterface::Optcr { optcr1 } => self.program_optcr(core, optcr1, values),
+            FlashInterface::H7 => self.program_h7(core, values),
+            FlashInterface::Optr => self.program_optr(core, values),
+        }
+    }
+
+    fn launch(&self, core: &mut Core<'_>) -> Result<(), OptionByteError> {
+        match self.interface {
+            FlashInterface::Optcr { .. } | FlashInterface::H7 => {
+                core.reset_and_halt(Duration::from_millis(500))?;
+                Ok(())
+            }
+            FlashInterface::Optr => self.launch_optr(core),
+        }
+    }
+
+    fn readout_protection(&self, values: &[u32]) -> ReadoutProtection {
+        let rdp = self.registers[0]
+            .fields
+            .iter()
+            .find(|field| field.name == "RDP")
+            .unwrap();
+
+        match rdp.get(values[0]) {
+            RDP_LEVEL_0 => ReadoutProtection::Level0,
+            RDP_LEVEL_2 => ReadoutProtection::Level2,
+            _ => ReadoutProtection::Level1,
+        }
+    }
+
+    fn set_readout_protection(&self, values: &mut [u32], level: ReadoutProtection) {
+        let rdp = self.registers[0]
+            .fields
+            .iter()
+            .find(|field| field.name == "RDP")
+            .unwrap();
+
+        let value = match level {
+            ReadoutProtection::Level0 => RDP_LEVEL_0,
+            ReadoutProtection::Level1 => RDP_LEVEL_1,
+            ReadoutProtection::Level2 => RDP_LEVEL_2,
+        };
+
+        values[0] = rdp.set(values[0], value);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{family, OptionByteAccess, ReadoutProtection};
+
+    #[test]
+    fn readout_protection_encoding() {
+        let l4 = family("STM32L476RGTx").unwrap();
+
+        // Reset value of OPTR
+        let mut values = [0xffef_f8aa, 0xff00_ffff, 0xff00_ffff];
+        assert_eq!(l4.readout_protection(&values), ReadoutProtection::Level0);
+
+        l4.set_readout_protection(&mut values, ReadoutProtection::Level1);
+        assert_eq!(values[0], 0xffef_f8bb);
+        assert_eq!(l4.readout_protection(&values), ReadoutProtection::Level1);
+
+        let f4 = family("STM32F407VGTx").unwrap();
+
+        // Reset value of OPTCR
+        let mut values = [0x0fff_aaed];
+        assert_eq!(f4.readout_protection(&values), ReadoutProtection::Level0);
+
+        f4.set_readout_protection(&mut values, ReadoutProtection::Level1);
+        assert_eq!(values[0], 0x0fff_bbed);
+    }
+
+    #[test]
+    fn dual_core_h7() {
+        let h745 = family("STM32H745ZITx").unwrap();
+        assert_eq!(h745.registers()[2].name, "BOOT_CM4");
+
+        let h743 = family("STM32H743ZITx").unwrap();
+        assert_eq!(h743.registers().len(), 3);
+    }
+
+    #[test]
+    fn unsupported_family() {
+        assert!(family("STM32F103C8").is_none());
+    }
+}
@@ -0,0 +1,788 @@
+//! Programming of one-time programmable (OTP) memory.
+//!
+//! The following areas are supported:
+//!
+//! - The OTP area of the STM32F2, F4, F7, L4, G0 and G4, including the lock bytes of the F2, F4 and F7.
+//! - The customer registers of the nRF52 UICR. Bits can only be cleared without erasing the whole chip.
+//! - The customer manufacturing (CMPA) and customer field (CFPA) programmable areas of the LPC55.
+//!
+//! Data of the image which is located in one of these areas is not flashed together with the
+//! rest of the image. Before programming, the current contents are read and compared with the data
+//! of the image, which results in an [`OtpReport`]. Programming requires the `otp_programming`
+//! permission of the session, see [`Permissions::allow_otp_programming`](crate::Permissions::allow_otp_programming).
+
+use std::fmt;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use super::builder::FlashBuilder;
+use super::FlashError;
+use crate::{Core, MemoryInterface, Session, Target};
+
+/// Describes an error that happened while programming OTP memory.
+#[derive(thiserror::Error, Debug)]
+pub enum OtpError {
+    /// The memory at the address is already programmed, and can't be changed anymore.
+    #[error("The OTP memory at {address:#010x} is already programmed, and can't be changed.")]
+    AlreadyProgrammed {
+        /// The address of the programmed memory.
+        address: u64,
+    },
+    /// The page has to be written as a whole, because it is erased before programming.
+    #[error("The page '{region}' at {address:#010x} has to be written as a whole.")]
+    PartialPage {
+        /// The name of the region.
+        region: &'static str,
+        /// The address of the page.
+        address: u64,
+    },
+    /// The area is sealed, and can't be changed anymore.
+    #[error("The area '{0}' is sealed, and can't be changed anymore.")]
+    Sealed(&'static str),
+    /// The version of the CFPA has to be incremented, otherwise the ROM ignores the new contents.
+    #[error("The CFPA version {new} has to be greater than the current version {current}.")]
+    VersionNotIncremented {
+        /// The version of the active CFPA page.
+        current: u32,
+        /// The version of the written page.
+        new: u32,
+    },
+    /// The flash interface reported an error after programming.
+    #[error("Programming the OTP memory failed, the flash status register is {0:#010x}.")]
+    ProgrammingFailed(u32),
+    /// The flash interface did not finish in time.
+    #[error("Timeout while waiting for the flash interface.")]
+    Timeout,
+    /// An error occurred while accessing the chip.
+    #[error("Error while accessing the chip.")]
+    Core(#[from] crate::Error),
+}
+
+/// An area of OTP memory.
+#[derive(Debug)]
+pub struct OtpRegion {
+    /// The name of the area, as used in the reference manual of the chip.
+    pub name: &'static str,
+    /// The address of the area.
+    pub address: u64,
+    /// The size of the area in bytes.
+    pub size: u64,
+}
+
+impl OtpRegion {
+    /// The address range of the area.
+    pub fn range(&self) -> Range<u64> {
+        self.address..self.address + self.size
+    }
+}
+
+/// How the OTP memory is programmed.
+#[derive(Debug)]
+enum OtpInterface {
+    /// Byte programming through the `CR` register of the F2, F4 and F7 flash interface.
+    ///
+    /// Only bytes which are still `0xFF` can be programmed.
+    Stm32Byte,
+    /// Double word programming through the `CR` register of the L4, G0 and G4 flash interface.
+    ///
+    /// Only double words which are still erased can be programmed.
+    Stm32DoubleWord,
+    /// Word programming through the NVMC of the nRF52, which can only clear bits.
+    NrfNvmc,
+    /// Page programming through the flash controller of the LPC55.
+    ///
+    /// The page is erased before it is programmed, so it has to be written as a whole.
+    Lpc55Page,
+}
+
+impl OtpInterface {
+    /// The number of bytes which are programmed at once.
+    fn unit(&self) -> u64 {
+        match self {
+            OtpInterface::Stm32Byte => 1,
+            OtpInterface::Stm32DoubleWord => 8,
+            OtpInterface::NrfNvmc => 4,
+            OtpInterface::Lpc55Page => lpc55::PAGE_SIZE,
+        }
+    }
+}
+
+/// The OTP memory of a chip family.
+#[derive(Debug)]
+struct OtpFamily {
+    /// The prefixes of the names of the chips of the family.
+    prefixes: &'static [&'static str],
+    /// The base address of the flash interface.
+    flash_base: u64,
+    interface: OtpInterface,
+    regions: &'static [OtpRegion],
+}
+
+static FAMILIES: &[OtpFamily] = &[
+    OtpFamily {
+        prefixes: &["STM32F2", "STM32F4"],
+        flash_base: 0x4002_3C00,
+        interface: OtpInterface::Stm32Byte,
+        regions: &[
+            OtpRegion {
+                name: "OTP",
+                address: 0x1FFF_7800,
+                size: 0x200,
+            },
+            OtpRegion {
+                name: "OTP_LOCK",
+                address: 0x1FFF_7A00,
+                size: 0x10,
+            },
+        ],
+    },
+    OtpFamily {
+        prefixes: &["STM32F7"],
+        flash_base: 0x4002_3C00,
+        interface: OtpInterface::Stm32Byte,
+        regions: &[
+            OtpRegion {
+                name: "OTP",
+                address: 0x1FF0_F000,
+                size: 0x400,
+            },
+            OtpRegion {
+                name: "OTP_LOCK",
+                address: 0x1FF0_F400,
+                size: 0x10,
+            },
+        ],
+    },
+    OtpFamily {
+        prefixes: &["STM32L4", "STM32G0", "STM32G4"],
+        flash_base: 0x4002_2000,
+        interface: OtpInterface::Stm32DoubleWord,
+        regions: &[OtpRegion {
+            name: "OTP",
+            address: 0x1FFF_7000,
+            size: 0x400,
+        }],
+    },
+    OtpFamily {
+        prefixes: &["nRF52"],
+        flash_base: nrf::NVMC_BASE,
+        interface: OtpInterface::NrfNvmc,
+        regions: &[OtpRegion {
+            name: "UICR_CUSTOMER",
+            address: 0x1000_1080,
+            size: 0x80,
+        }],
+    },
+    OtpFamily {
+        prefixes: &["LPC55S6", "LPC55S2", "LPC552", "LPC55S1", "LPC551"],
+        flash_base: lpc55::FLASH_BASE,
+        interface: OtpInterface::Lpc55Page,
+        regions: &[
+            // The ROM copies the scratch page to the ping or pong page on the next boot.
+            OtpRegion {
+                name: "CFPA",
+                address: lpc55::CFPA_SCRATCH,
+                size: lpc55::PAGE_SIZE,
+            },
+            OtpRegion {
+                name: "CMPA",
+                address: lpc55::CMPA,
+                size: lpc55::PAGE_SIZE,
+            },
+        ],
+    },
+];
+
+fn family(name: &str) -> Option<&'static OtpFamily> {
+    FAMILIES.iter().find(|family| {
+        family
+            .prefixes
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    })
+}
+
+/// The OTP areas of the chip, which can be programmed.
+pub fn otp_regions(target: &Target) -> &'static [OtpRegion] {
+    family(&target.name)
+        .map(|family| family.regions)
+        .unwrap_or_default()
+}
+
+/// A change of the contents of OTP memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtpChange {
+    /// The name of the area.
+    pub region: &'static str,
+    /// The address of the changed memory.
+    pub address: u64,
+    /// The current contents.
+    pub current: Vec<u8>,
+    /// The contents after programming.
+    pub new: Vec<u8>,
+}
+
+/// The changes of the OTP memory, which are made by programming an image.
+///
+/// These changes can't be reverted, so they should be shown to the user before programming.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OtpReport {
+    /// The changed memory, in ascending address order.
+    pub changes: Vec<OtpChange>,
+}
+
+impl OtpReport {
+    /// Check if the OTP memory already contains the data of the image.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for OtpReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(
+                f,
+                "{} {:#010x}..{:#010x}:",
+                change.region,
+                change.address,
+                change.address + change.new.len() as u64
+            )?;
+
+            for (offset, (current, new)) in change
+                .current
+                .chunks(16)
+                .zip(change.new.chunks(16))
+                .enumerate()
+            {
+                if current == new {
+                    continue;
+                }
+
+                writeln!(
+                    f,
+                    "    {:#010x}: {:02x?} -> {:02x?}",
+                    change.address + offset as u64 * 16,
+                    current,
+                    new
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Compute the changes of the OTP memory from the current contents and the data of the image.
+///
+/// The data is extended to the programming units of the interface using the current contents.
+fn changes(
+    family: &OtpFamily,
+    builder: &FlashBuilder,
+    mut read: impl FnMut(u64, &mut [u8]) -> Result<(), OtpError>,
+) -> Result<Vec<OtpChange>, OtpError> {
+    let unit = family.interface.unit();
+    let mut changes = Vec::new();
+
+    for region in family.regions {
+        for (address, data) in builder.data_in_range(&region.range()) {
+            let start = address - (address - region.address) % unit;
+            let end = address + data.len() as u64;
+            let end = end + (unit - (end - region.address) % unit) % unit;
+
+            if let OtpInterface::Lpc55Page = family.interface {
+                if start != address || end != address + data.len() as u64 {
+                    return Err(OtpError::PartialPage {
+                        region: region.name,
+                        address: start,
+                    });
+                }
+            }
+
+            let mut current = vec![0; (end - start) as usize];
+            read(start, &mut current)?;
+
+            let mut new = current.clone();
+            new[(address - start) as usize..][..data.len()].copy_from_slice(data);
+
+            // Only the units which change are programmed.
+            for (index, (current, new)) in current
+                .chunks(unit as usize)
+                .zip(new.chunks(unit as usize))
+                .enumerate()
+            {
+                if current == new {
+                    continue;
+                }
+
+                let address = start + index as u64 * unit;
+
+                match changes.last_mut() {
+                    Some(OtpChange {
+                        region: name,
+                        address: last,
+                        current: last_current,
+                        new: last_new,
+                    }) if *name == region.name && *last + last_new.len() as u64 == address => {
+                        last_current.extend_from_slice(current);
+                        last_new.extend_from_slice(new);
+                    }
+                    _ => changes.push(OtpChange {
+                        region: region.name,
+                        address,
+                        current: current.to_vec(),
+                        new: new.to_vec(),
+                    }),
+                }
+            }
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Check that the memory can be changed from the current contents to the new contents.
+fn check_change(interface: &OtpInterface, change: &OtpChange) -> Result<(), OtpError> {
+    let unit = interface.unit() as usize;
+
+    for (index, (current, new)) in change
+        .current
+        .chunks(unit)
+        .zip(change.new.chunks(unit))
+        .enumerate()
+    {
+        let address = change.address + (index * unit) as u64;
+
+        let programmable = match interface {
+            OtpInterface::Stm32Byte | OtpInterface::Stm32DoubleWord => {
+                current.iter().all(|byte| *byte == 0xFF)
+            }
+            OtpInterface::NrfNvmc => current.iter().zip(new).all(|(c, n)| n & !c == 0),
+            OtpInterface::Lpc55Page => true,
+        };
+
+        if !programmable {
+            return Err(OtpError::AlreadyProgrammed { address });
+        }
+    }
+
+    Ok(())
+}
+
+/// Compare the OTP data of the image with the current contents of the chip.
+pub(super) fn report(
+    session: &mut Session,
+    builder: &FlashBuilder,
+) -> Result<OtpReport, FlashError> {
+    let family = family(&session.target().name)
+        .ok_or_else(|| FlashError::OtpNotSupported(session.target().name.clone()))?;
+
+    let mut core = session.core(0).map_err(FlashError::Core)?;
+
+    let changes = changes(family, builder, |address, data| {
+        Ok(core.read_8(address, data)?)
+    })
+    .map_err(FlashError::Otp)?;
+
+    for change in &changes {
+        check_change(&family.interface, change).map_err(FlashError::Otp)?;
+
+        if let OtpInterface::Lpc55Page = family.interface {
+            lpc55::check_page(&mut core, change).map_err(FlashError::Otp)?;
+        }
+    }
+
+    Ok(OtpReport { changes })
+}
+
+/// Program the changes of the report.
+///
+/// The report has to be created using [`report`] directly before, so the
+/// changes are based on the current contents of the chip.
+pub(super) fn program(session: &mut Session, report: &OtpReport) -> Result<(), FlashError> {
+    let family = family(&session.target().name)
+        .ok_or_else(|| FlashError::OtpNotSupported(session.target().name.clone()))?;
+
+    let mut core = session.core(0).map_err(FlashError::Core)?;
+
+    // Make sure that the firmware doesn't interfere with the flash interface.
+    if !core.core_halted().map_err(FlashError::Core)? {
+        core.halt(Duration::from_millis(100))
+            .map_err(FlashError::Core)?;
+    }
+
+    for change in &report.changes {
+        log::info!(
+            "Programming {} at {:#010x} ({} bytes)",
+            change.region,
+            change.address,
+            change.new.len()
+        );
+
+        match family.interface {
+            OtpInterface::Stm32Byte => stm32::program_bytes(&mut core, family.flash_base, change),
+            OtpInterface::Stm32DoubleWord => {
+                stm32::program_double_words(&mut core, family.flash_base, change)
+            }
+            OtpInterface::NrfNvmc => nrf::program_words(&mut core, change),
+            OtpInterface::Lpc55Page => lpc55::program_pages(&mut core, change),
+        }
+        .map_err(FlashError::Otp)?;
+    }
+
+    Ok(())
+}
+
+/// The time to wait for a single programming operation.
+const PROGRAM_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Wait until `done` returns true for the value of the register at `address`.
+fn wait_for(
+    core: &mut Core<'_>,
+    address: u64,
+    done: impl Fn(u32) -> bool,
+) -> Result<u32, OtpError> {
+    let start = Instant::now();
+
+    loop {
+        let value = core.read_word_32(address)?;
+
+        if done(value) {
+            return Ok(value);
+        }
+
+        if start.elapsed() > PROGRAM_TIMEOUT {
+            return Err(OtpError::Timeout);
+        }
+    }
+}
+
+/// Programming through the STM32 flash interfaces.
+mod stm32 {
+    use super::{wait_for, OtpChange, OtpError};
+    use crate::{Core, MemoryInterface};
+
+    const KEY1: u32 = 0x4567_0123;
+    const KEY2: u32 = 0xCDEF_89AB;
+
+    const SR_BSY: u32 = 1 << 16;
+    const CR_PG: u32 = 1 << 0;
+    const CR_LOCK: u32 = 1 << 31;
+
+    /// The registers of a flash interface.
+    struct Registers {
+        keyr: u64,
+        sr: u64,
+        cr: u64,
+        sr_errors: u32,
+    }
+
+    /// The F2, F4 and F7 flash interface. `PSIZE` is left at 0, which selects byte programming.
+    const BYTE: Registers = Registers {
+        keyr: 0x04,
+        sr: 0x0C,
+        cr: 0x10,
+        // `OPERR`, `WRPERR`, `PGAERR`, `PGPERR` and `PGSERR`
+        sr_errors: 0xF2,
+    };
+
+    /// The L4, G0 and G4 flash interface.
+    const DOUBLE_WORD: Registers = Registers {
+        keyr: 0x08,
+        sr: 0x10,
+        cr: 0x14,
+        // `OPERR`, `PROGERR`, `WRPERR`, `PGAERR`, `SIZERR`, `PGSERR`, `MISERR`, `FASTERR`, `RDERR` and `OPTVERR`
+        sr_errors: 0xC3FA,
+    };
+
+    /// Run `write` with the programming of the flash interface enabled.
+    fn with_programming(
+        core: &mut Core<'_>,
+        flash_base: u64,
+        registers: &Registers,
+        write: impl FnOnce(&mut Core<'_>) -> Result<(), OtpError>,
+    ) -> Result<(), OtpError> {
+        let sr = flash_base + registers.sr;
+        let cr = flash_base + registers.cr;
+
+        wait_for(core, sr, |value| value & SR_BSY == 0)?;
+
+        if core.read_word_32(cr)? & CR_LOCK != 0 {
+            core.write_word_32(flash_base + registers.keyr, KEY1)?;
+            core.write_word_32(flash_base + registers.keyr, KEY2)?;
+        }
+
+        // Clear errors of previous operations, otherwise the programming doesn't start.
+        let status = core.read_word_32(sr)?;
+        core.write_word_32(sr, status & registers.sr_errors)?;
+
+        core.write_word_32(cr, CR_PG)?;
+        let result = write(core);
+        core.write_word_32(cr, CR_LOCK)?;
+
+        result
+    }
+
+    fn check_status(
+        core: &mut Core<'_>,
+        flash_base: u64,
+        registers: &Registers,
+    ) -> Result<(), OtpError> {
+        let sr = wait_for(core, flash_base + registers.sr, |value| value & SR_BSY == 0)?;
+
+        if sr & registers.sr_errors != 0 {
+            core.write_word_32(flash_base + registers.sr, sr & registers.sr_errors)?;
+            return Err(OtpError::ProgrammingFailed(sr));
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn program_bytes(
+        core: &mut Core<'_>,
+        flash_base: u64,
+        change: &OtpChange,
+    ) -> Result<(), OtpError> {
+        with_programming(core, flash_base, &BYTE, |core| {
+            for (offset, (current, new)) in change.current.iter().zip(&change.new).enumerate() {
+                if current == new {
+                    continue;
+                }
+
+                core.write_word_8(change.address + offset as u64, *new)?;
+                check_status(core, flash_base, &BYTE)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    pub(super) fn program_double_words(
+        core: &mut Core<'_>,
+        flash_base: u64,
+        change: &OtpChange,
+    ) -> Result<(), OtpError> {
+        with_programming(core, flash_base, &DOUBLE_WORD, |core| {
+            for (index, double_word) in change.new.chunks_exact(8).enumerate() {
+                let address = change.address + index as u64 * 8;
+
+                // The programming starts after the second word is written.
+                for (word, bytes) in double_word.chunks_exact(4).enumerate() {
+                    let value = u32::from_le_bytes(bytes.try_into().unwrap());
+                    core.write_word_32(address + word as u64 * 4, value)?;
+                }
+
+                check_status(core, flash_base, &DOUBLE_WORD)?;
+            }
+
+            Ok(())
+        })
+    }
+}
+
+/// Programming through the NVMC of the nRF52.
+mod nrf {
+    use super::{wait_for, OtpChange, OtpError};
+    use crate::{Core, MemoryInterface};
+
+    pub(super) const NVMC_BASE: u64 = 0x4001_E000;
+    const NVMC_READY: u64 = 0x400;
+    const NVMC_CONFIG: u64 = 0x504;
+
+    const CONFIG_REN: u32 = 0;
+    const CONFIG_WEN: u32 = 1;
+
+    pub(super) fn program_words(core: &mut Core<'_>, change: &OtpChange) -> Result<(), OtpError> {
+        core.write_word_32(NVMC_BASE + NVMC_CONFIG, CONFIG_WEN)?;
+
+        for (index, word) in change.new.chunks_exact(4).enumerate() {
+            let value = u32::from_le_bytes(word.try_into().unwrap());
+            core.write_word_32(change.address + index as u64 * 4, value)?;
+            wait_for(core, NVMC_BASE + NVMC_READY, |ready| ready & 1 != 0)?;
+        }
+
+        core.write_word_32(NVMC_BASE + NVMC_CONFIG, CONFIG_REN)?;
+
+        Ok(())
+    }
+}
+
+/// Programming of the protected flash pages through the flash controller of the LPC55.
+mod lpc55 {
+    use super::{wait_for, OtpChange, OtpError};
+    use crate::{Core, MemoryInterface};
+
+    pub(super) const PAGE_SIZE: u64 = 0x200;
+
+    pub(super) const CFPA_SCRATCH: u64 = 0x0009_DE00;
+    const CFPA_PING: u64 = 0x0009_E000;
+    const CFPA_PONG: u64 = 0x0009_E200;
+    pub(super) const CMPA: u64 = 0x0009_E400;
+
+    /// The offset of the `VERSION` field of the CFPA.
+    const CFPA_VERSION: u64 = 0x04;
+    /// The offset of the SHA-256 digest, which seals the CMPA.
+    const CMPA_DIGEST: u64 = 0x1E0;
+
+    pub(super) const FLASH_BASE: u64 = 0x4003_4000;
+    const CMD: u64 = 0x000;
+    const STARTA: u64 = 0x010;
+    const STOPA: u64 = 0x014;
+    const DATAW: u64 = 0x080;
+    const INT_STATUS: u64 = 0xFE0;
+    const INT_CLR_STATUS: u64 = 0xFE8;
+
+    const CMD_ERASE_RANGE: u32 = 4;
+    const CMD_WRITE: u32 = 8;
+    const CMD_PROGRAM: u32 = 12;
+
+    const STATUS_FAIL: u32 = 1 << 0;
+    const STATUS_ERR: u32 = 1 << 1;
+    const STATUS_DONE: u32 = 1 << 2;
+
+    /// Check that the ROM accepts the new contents of the page.
+    pub(super) fn check_page(core: &mut Core<'_>, change: &OtpChange) -> Result<(), OtpError> {
+        match change.address {
+            CFPA_SCRATCH => {
+                let current = core
+                    .read_word_32(CFPA_PING + CFPA_VERSION)?
+                    .max(core.read_word_32(CFPA_PONG + CFPA_VERSION)?);
+
+                let offset = CFPA_VERSION as usize;
+                let new = u32::from_le_bytes(change.new[offset..offset + 4].try_into().unwrap());
+
+                if new <= current {
+                    return Err(OtpError::VersionNotIncremented { current, new });
+                }
+            }
+            CMPA => {
+                let digest = &change.current[CMPA_DIGEST as usize..];
+
+                if digest.iter().any(|byte| *byte != 0) {
+                    return Err(OtpError::Sealed(change.region));
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn command(core: &mut Core<'_>, command: u32) -> Result<(), OtpError> {
+        core.write_word_32(FLASH_BASE + INT_CLR_STATUS, 0xF)?;
+        core.write_word_32(FLASH_BASE + CMD, command)?;
+
+        let status = wait_for(core, FLASH_BASE + INT_STATUS, |status| {
+            status & STATUS_DONE != 0
+        })?;
+
+        if status & (STATUS_FAIL | STATUS_ERR) != 0 {
+            return Err(OtpError::ProgrammingFailed(status));
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn program_pages(core: &mut Core<'_>, change: &OtpChange) -> Result<(), OtpError> {
+        for (index, page) in change.new.chunks_exact(PAGE_SIZE as usize).enumerate() {
+            let address = change.address + index as u64 * PAGE_SIZE;
+
+            // The flash controller addresses the flash in units of 16 bytes.
+            core.write_word_32(FLASH_BASE + STARTA, (address >> 4) as u32)?;
+            core.write_word_32(FLASH_BASE + STOPA, ((address + PAGE_SIZE - 1) >> 4) as u32)?;
+            command(core, CMD_ERASE_RANGE)?;
+
+            for (line, data) in page.chunks_exact(16).enumerate() {
+                for (word, bytes) in data.chunks_exact(4).enumerate() {
+                    let value = u32::from_le_bytes(bytes.try_into().unwrap());
+                    core.write_word_32(FLASH_BASE + DATAW + word as u64 * 4, value)?;
+                }
+
+                core.write_word_32(FLASH_BASE + STARTA, ((address >> 4) + line as u64) as u32)?;
+                command(core, CMD_WRITE)?;
+            }
+
+            core.write_word_32(FLASH_BASE + STARTA, (address >> 4) as u32)?;
+            command(core, CMD_PROGRAM)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{changes, check_change, family, OtpChange, OtpError, OtpInterface};
+    use crate::flashing::builder::FlashBuilder;
+
+    #[test]
+    fn changes_are_extended_to_units() {
+        let family = family("STM32L476RGTx").unwrap();
+
+        let mut builder = FlashBuilder::new();
+        builder.add_data(0x1FFF_7002, &[1, 2, 3, 4]).unwrap();
+        builder.add_data(0x1FFF_7010, &[0xFF; 8]).unwrap();
+
+        let changes = changes(family, &builder, |_, data| {
+            data.fill(0xFF);
+            Ok(())
+        })
+        .unwrap();
+
+        // The second chunk doesn't change anything.
+        assert_eq!(
+            changes,
+            vec![OtpChange {
+                region: "OTP",
+                address: 0x1FFF_7000,
+                current: vec![0xFF; 8],
+                new: vec![0xFF, 0xFF, 1, 2, 3, 4, 0xFF, 0xFF],
+            }]
+        );
+    }
+
+    #[test]
+    fn programmed_memory_is_rejected() {
+        let change = OtpChange {
+            region: "OTP",
+            address: 0x1FFF_7800,
+            current: vec![0xFF, 0x12],
+            new: vec![0x00, 0x34],
+        };
+
+        assert!(matches!(
+            check_change(&OtpInterface::Stm32Byte, &change),
+            Err(OtpError::AlreadyProgrammed {
+                address: 0x1FFF_7801
+            })
+        ));
+
+        // The UICR bits can be cleared, but not set.
+        let change = OtpChange {
+            region: "UICR_CUSTOMER",
+            address: 0x1000_1080,
+            current: vec![0xFF, 0xFF, 0xF0, 0xFF],
+            new: vec![0x00, 0xFF, 0x10, 0xFF],
+        };
+        assert!(check_change(&OtpInterface::NrfNvmc, &change).is_ok());
+
+        let change = OtpChange {
+            new: vec![0x00, 0xFF, 0x1F, 0xFF],
+            ..change
+        };
+        assert!(check_change(&OtpInterface::NrfNvmc, &change).is_err());
+    }
+
+    #[test]
+    fn lpc55_pages_are_written_as_a_whole() {
+        let family = family("LPC55S69JBD100").unwrap();
+
+        let mut builder = FlashBuilder::new();
+        builder.add_data(0x0009_E400, &[0; 0x100]).unwrap();
+
+        assert!(matches!(
+            changes(family, &builder, |_, _| Ok(())),
+            Err(OtpError::PartialPage { region: "CMPA", .. })
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+//! Flashing the same image to the targets of multiple probes at the same time.
+//!
+//! This is used for production programming, where many devices are attached to the host
+//! through their own probes. Each device is attached and flashed in its own thread, so the
+//! total time is about the time of the slowest device.
+
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{DownloadOptions, FlashError, FlashLoader, FlashProgress, ProgressEvent};
+use crate::{DebugProbeSelector, Permissions, Probe, Target};
+
+/// Describes an error that happened while flashing a single device.
+#[derive(thiserror::Error, Debug)]
+pub enum DeviceError {
+    /// Attaching to the target failed.
+    #[error("Attaching to the target failed.")]
+    Attach(#[source] crate::Error),
+    /// Flashing the target failed.
+    #[error("Flashing the target failed.")]
+    Flash(#[source] FlashError),
+    /// The thread which flashed the device panicked.
+    #[error("Flashing the target panicked.")]
+    Panicked,
+}
+
+/// The result of flashing a single device.
+#[derive(Debug)]
+pub struct DeviceReport {
+    /// The name of the probe the device is attached to.
+    pub probe: String,
+    /// The selector which was used to open the probe, if any.
+    pub selector: Option<DebugProbeSelector>,
+    /// Whether flashing the device succeeded.
+    pub result: Result<(), DeviceError>,
+    /// The time it took to attach to and flash the device.
+    pub duration: Duration,
+}
+
+/// The results of flashing multiple devices, see [`download_to_probes`].
+#[derive(Debug)]
+pub struct DownloadSummary {
+    /// The results of the devices, in the order of the probes.
+    pub devices: Vec<DeviceReport>,
+    /// The time it took to flash all devices.
+    pub duration: Duration,
+}
+
+impl DownloadSummary {
+    /// The number of devices which were flashed successfully.
+    pub fn succeeded(&self) -> usize {
+        self.devices
+            .iter()
+            .filter(|device| device.result.is_ok())
+            .count()
+    }
+
+    /// The number of devices which failed.
+    pub fn failed(&self) -> usize {
+        self.devices.len() - self.succeeded()
+    }
+
+    /// Check if all devices were flashed successfully.
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0
+    }
+}
+
+/// Flash the data of `loader` to the targets attached to all `probes` at the same time.
+///
+/// Each probe is attached to `target` using `permissions` in its own thread. The options for each device are
+/// created using `options`, any progress reporter set by it is replaced. The progress events of the devices are
+/// passed to `progress`, together with the index of the probe.
+///
+/// A failing device doesn't stop the other devices, the result of each device is part of the returned summary.
+///
+/// ```no_run
+/// use std::path::Path;
+///
+/// use probe_rs::{config::get_target_by_name, flashing::{download_to_probes, DownloadOptions, ElfOptions, Format}, Permissions, Probe};
+///
+/// let target = get_target_by_name("nrf52840_xxAA")?;
+///
+/// let mut loader = target.flash_loader();
+/// loader.load_file(Path::new("firmware.elf"), Format::Elf(ElfOptions::default()), &target)?;
+///
+/// let probes = Probe::list_all()
+///     .iter()
+///     .map(|info| Probe::open(info))
+///     .collect::<Result<Vec<_>, _>>()?;
+///
+/// let summary = download_to_probes(probes, target, Permissions::default(), loader, DownloadOptions::new, |_, _| {});
+///
+/// println!("{} devices flashed, {} failed", summary.succeeded(), summary.failed());
+///
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn download_to_probes(
+    probes: Vec<Probe>,
+    target: Target,
+    permissions: Permissions,
+    loader: FlashLoader,
+    options: impl Fn() -> DownloadOptions<'static> + Send + Sync + 'static,
+    progress: impl Fn(usize, ProgressEvent) + Send + Sync + 'static,
+) -> DownloadSummary {
+    let start = Instant::now();
+
+    let loader = Arc::new(loader);
+    let options = Arc::new(options);
+    let progress = Arc::new(progress);
+
+    let threads: Vec<_> = probes
+        .into_iter()
+        .enumerate()
+        .map(|(index, probe)| {
+            let name = probe.get_name();
+            let selector = probe.selector().cloned();
+
+            let target = target.clone();
+            let permissions = permissions.clone();
+            let loader = loader.clone();
+            let options = options.clone();
+            let progress = progress.clone();
+
+            let thread = thread::spawn(move || {
+                let start = Instant::now();
+
+                let result = (|| {
+                    let mut session = probe
+                        .attach(target, permissions)
+                        .map_err(DeviceError::Attach)?;
+
+                    let device_progress = FlashProgress::new(move |event| progress(index, event));
+
+                    let mut options: DownloadOptions<'_> = options();
+                    options.progress = Some(&device_progress);
+
+                    loader
+                        .commit(&mut session, options)
+                        .map_err(DeviceError::Flash)
+                })();
+
+                (result, start.elapsed())
+            });
+
+            (name, selector, thread)
+        })
+        .collect();
+
+    let devices = threads
+        .into_iter()
+        .map(|(probe, selector, thread)| {
+            let (result, duration) = thread
+                .join()
+                .unwrap_or_else(|_| (Err(DeviceError::Panicked), Duration::ZERO));
+
+            if let Err(error) = &result {
+                log::warn!("Flashing the target of {} failed: {}", probe, error);
+            }
+
+            DeviceReport {
+                probe,
+                selector,
+                result,
+                duration,
+            }
+        })
+        .collect();
+
+    DownloadSummary {
+        devices,
+        duration: start.elapsed(),
+    }
+}
@@ -1,5 +1,6 @@
 use super::FlashLayout;
-use std::time::Duration;
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
 
 /// A structure to manage the flashing procedure progress reporting.
 ///
@@ -16,6 +17,16 @@ use std::time::Duration;
 /// ```
 pub struct FlashProgress {
     handler: Box<dyn Fn(ProgressEvent)>,
+    /// The state of the fill, erase and program operations, indexed by [`ProgressOperation`].
+    state: RefCell<[OperationState; 3]>,
+}
+
+/// The number of processed bytes and the start time of an operation.
+#[derive(Default)]
+struct OperationState {
+    total: u64,
+    done: u64,
+    started: Option<Instant>,
 }
 
 impl FlashProgress {
@@ -23,6 +34,7 @@ impl FlashProgress {
     pub fn new(handler: impl Fn(ProgressEvent) + 'static) -> Self {
         Self {
             handler: Box::new(handler),
+            state: RefCell::new(Default::default()),
         }
     }
 
@@ -31,39 +43,99 @@ impl FlashProgress {
         (self.handler)(event);
     }
 
+    /// Start measuring the throughput of `operation`.
+    fn start(&self, operation: ProgressOperation) {
+        self.state.borrow_mut()[operation as usize].started = Some(Instant::now());
+    }
+
+    /// Add `size` bytes to the processed bytes of `operation`, and emit a progress report.
+    fn advance(&self, operation: ProgressOperation, address: u64, size: u64) {
+        let report = {
+            let mut state = self.state.borrow_mut();
+            let state = &mut state[operation as usize];
+
+            state.done += size;
+
+            let elapsed = state
+                .started
+                .map(|started| started.elapsed())
+                .unwrap_or_default();
+
+            ProgressReport::new(operation, address, state.done, state.total, elapsed)
+        };
+
+        self.emit(ProgressEvent::Progress(report));
+    }
+
     /// Signalize that the flashing algorithm was set up and is initialized.
     pub(super) fn initialized(&self, flash_layout: FlashLayout) {
+        let fill_size = flash_layout.fills().iter().map(|fill| fill.size()).sum();
+        let erase_size = flash_layout
+            .sectors()
+            .iter()
+            .map(|sector| sector.size())
+            .sum();
+        let program_size = flash_layout
+            .pages()
+            .iter()
+            .map(|page| page.size() as u64)
+            .sum();
+
+        *self.state.borrow_mut() =
+            [fill_size, erase_size, program_size].map(|total| OperationState {
+                total,
+                ..Default::default()
+            });
+
         self.emit(ProgressEvent::Initialized { flash_layout });
     }
 
     /// Signalize that the erasing procedure started.
     pub(super) fn started_erasing(&self) {
+        self.start(ProgressOperation::Erase);
         self.emit(ProgressEvent::StartedErasing);
     }
 
     /// Signalize that the filling procedure started.
     pub(super) fn started_filling(&self) {
+        self.start(ProgressOperation::Fill);
         self.emit(ProgressEvent::StartedFilling);
     }
 
     /// Signalize that the programing procedure started.
     pub(super) fn started_programming(&self) {
+        self.start(ProgressOperation::Program);
         self.emit(ProgressEvent::StartedProgramming);
     }
 
     /// Signalize that the page programming procedure has made progress.
-    pub(super) fn page_programmed(&self, size: u32, time: Duration) {
-        self.emit(ProgressEvent::PageProgrammed { size, time });
+    pub(super) fn page_programmed(&self, address: u64, size: u32, time: Duration) {
+        self.emit(ProgressEvent::PageProgrammed {
+            address,
+            size,
+            time,
+        });
+        self.advance(ProgressOperation::Program, address, size as u64);
     }
 
     /// Signalize that the sector erasing procedure has made progress.
-    pub(super) fn sector_erased(&self, size: u64, time: Duration) {
-        self.emit(ProgressEvent::SectorErased { size, time });
+    pub(super) fn sector_erased(&self, address: u64, size: u64, time: Duration) {
+        self.emit(ProgressEvent::SectorErased {
+            address,
+            size,
+            time,
+        });
+        self.advance(ProgressOperation::Erase, address, size);
     }
 
     /// Signalize that the page filling procedure has made progress.
-    pub(super) fn page_filled(&self, size: u64, time: Duration) {
-        self.emit(ProgressEvent::PageFilled { size, time });
+    pub(super) fn page_filled(&self, address: u64, size: u64, time: Duration) {
+        self.emit(ProgressEvent::PageFilled {
+            address,
+            size,
+            time,
+        });
+        self.advance(ProgressOperation::Fill, address, size);
     }
 
     /// Signalize that the programming procedure failed.
@@ -113,6 +185,9 @@ impl FlashProgress {
 /// * `PageProgrammed` for every page
 /// * `FinishedProgramming`
 ///
+/// Every `PageFilled`, `SectorErased` and `PageProgrammed` event is followed by a `Progress` event,
+/// which reports the overall progress of the operation.
+///
 /// If an erorr occurs in any stage, one of the `Failed*` event will be returned,
 /// and no further events will be returned.
 #[derive(Debug)]
@@ -129,6 +204,8 @@ pub enum ProgressEvent {
     /// This does not mean the page has been programmed yet.
     /// Only its contents are determined at this point!
     PageFilled {
+        /// The address of the filled part of the page.
+        address: u64,
         /// The size of the page in bytes.
         size: u64,
         /// The time it took to fill this flash page.
@@ -142,6 +219,8 @@ pub enum ProgressEvent {
     StartedErasing,
     /// A sector has been erased successfully.
     SectorErased {
+        /// The address of the sector.
+        address: u64,
         /// The size of the sector in bytes.
         size: u64,
         /// The time it took to erase this sector.
@@ -155,6 +234,8 @@ pub enum ProgressEvent {
     StartedProgramming,
     /// A flash page has been programmed successfully.
     PageProgrammed {
+        /// The address of the page.
+        address: u64,
         /// The size of this page in bytes.
         size: u32,
         /// The time it took to program this page.
@@ -164,4 +245,97 @@ pub enum ProgressEvent {
     FailedProgramming,
     /// Programming of the flash has finished successfully.
     FinishedProgramming,
+    /// The overall progress of the running operation.
+    Progress(ProgressReport),
+}
+
+/// The operations of the flashing procedure, which report their progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressOperation {
+    /// Reading the parts of the pages, which are restored after erasing.
+    Fill,
+    /// Erasing the sectors.
+    Erase,
+    /// Programming the pages.
+    Program,
+}
+
+/// The progress of an operation of the flashing procedure.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressReport {
+    /// The running operation.
+    pub operation: ProgressOperation,
+    /// The address of the page or sector which was processed last.
+    pub address: u64,
+    /// The number of bytes processed so far.
+    pub bytes_done: u64,
+    /// The number of bytes the operation processes in total.
+    pub bytes_total: u64,
+    /// The measured throughput in bytes per second.
+    pub throughput: f64,
+    /// The estimated time until the operation is finished, if it can be estimated yet.
+    pub eta: Option<Duration>,
+}
+
+impl ProgressReport {
+    /// Create a report, with the throughput and the ETA based on the time the operation has been running.
+    fn new(
+        operation: ProgressOperation,
+        address: u64,
+        bytes_done: u64,
+        bytes_total: u64,
+        elapsed: Duration,
+    ) -> Self {
+        let seconds = elapsed.as_secs_f64();
+
+        let throughput = if seconds > 0.0 {
+            bytes_done as f64 / seconds
+        } else {
+            0.0
+        };
+
+        let eta = if throughput > 0.0 {
+            let remaining = bytes_total.saturating_sub(bytes_done);
+            Some(Duration::from_secs_f64(remaining as f64 / throughput))
+        } else {
+            None
+        };
+
+        Self {
+            operation,
+            address,
+            bytes_done,
+            bytes_total,
+            throughput,
+            eta,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ProgressOperation, ProgressReport};
+    use std::time::Duration;
+
+    #[test]
+    fn throughput_and_eta() {
+        let report = ProgressReport::new(
+            ProgressOperation::Program,
+            0x0800_0400,
+            0x400,
+            0x1000,
+            Duration::from_millis(500),
+        );
+
+        assert_eq!(report.throughput, 2048.0);
+        assert_eq!(report.eta, Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn no_eta_before_progress() {
+        let report = ProgressReport::new(ProgressOperation::Erase, 0, 0, 0x1000, Duration::ZERO);
+
+        assert_eq!(report.throughput, 0.0);
+        assert_eq!(report.eta, None);
+    }
 }
@@ -0,0 +1,150 @@
+//! Running an image from RAM, without programming the flash.
+//!
+//! This is used for test firmware which is linked for the RAM, bring-up of chips without a flash
+//! algorithm, and small helpers, e.g. for provisioning, which shouldn't wear the flash.
+
+use std::ops::Range;
+use std::time::Duration;
+
+use probe_rs_target::MemoryRegion;
+
+use super::{FlashError, FlashLoader};
+use crate::{MemoryInterface, Session};
+
+/// The vector table offset register of the Cortex-M cores.
+const VTOR: u64 = 0xE000_ED08;
+
+/// Check if `range` is completely covered by the RAM regions of `memory_map`.
+fn is_in_ram(memory_map: &[MemoryRegion], range: &Range<u64>) -> bool {
+    let mut address = range.start;
+
+    while address < range.end {
+        match memory_map.iter().find_map(|region| match region {
+            MemoryRegion::Ram(region) if region.range.contains(&address) => Some(region),
+            _ => None,
+        }) {
+            Some(region) => address = region.range.end,
+            None => return false,
+        }
+    }
+
+    true
+}
+
+/// Load the data of `loader` into the RAM, and start it on the core with the index `core_index`.
+///
+/// The core is reset and halted before the data is written, and the flash is not touched.
+///
+/// For the Cortex-M cores, the vector table is expected at the lowest address of the data. The stack
+/// pointer and the program counter are set from its first two entries, and VTOR is set to its address.
+/// For the other cores, the program counter is set to the entry point of the loaded ELF file.
+pub fn run_from_ram(
+    session: &mut Session,
+    loader: &FlashLoader,
+    core_index: usize,
+) -> Result<(), FlashError> {
+    let memory_map = session.target().memory_map.clone();
+
+    for (address, data) in loader.data().chain(loader.otp_data()) {
+        let range = address..address + data.len() as u64;
+
+        if !is_in_ram(&memory_map, &range) {
+            return Err(FlashError::NotInRam {
+                start: range.start,
+                end: range.end,
+            });
+        }
+    }
+
+    let core_type = session.target().cores[core_index].core_type;
+
+    let mut core = session.core(core_index).map_err(FlashError::Core)?;
+
+    core.reset_and_halt(Duration::from_millis(500))
+        .map_err(FlashError::Core)?;
+
+    for (address, data) in loader.data() {
+        log::debug!(
+            "Loading {:#010x}..{:#010x} into the RAM",
+            address,
+            address + data.len() as u64
+        );
+
+        core.write_8(address, data).map_err(FlashError::Core)?;
+    }
+
+    let pc = core.registers().program_counter().id;
+
+    if core_type.is_cortex_m() {
+        let vector_table = loader
+            .data()
+            .next()
+            .map(|(address, _)| address)
+            .ok_or(FlashError::NoEntryPoint)?;
+
+        let mut vectors = [0; 2];
+        core.read_32(vector_table, &mut vectors)
+            .map_err(FlashError::Core)?;
+
+        log::info!(
+            "Starting from RAM with the vector table at {:#010x}, SP {:#010x}, PC {:#010x}",
+            vector_table,
+            vectors[0],
+            vectors[1]
+        );
+
+        let sp = core.registers().stack_pointer().id;
+
+        core.write_word_32(VTOR, vector_table as u32)
+            .map_err(FlashError::Core)?;
+        core.write_core_reg(sp, vectors[0])
+            .map_err(FlashError::Core)?;
+        // The Thumb bit of the reset vector is not part of the address.
+        core.write_core_reg(pc, vectors[1] & !1)
+            .map_err(FlashError::Core)?;
+    } else {
+        let entry_point = loader.entry_point().ok_or(FlashError::NoEntryPoint)?;
+
+        log::info!("Starting from RAM at {:#010x}", entry_point);
+
+        core.write_core_reg(pc, entry_point)
+            .map_err(FlashError::Core)?;
+    }
+
+    core.run().map_err(FlashError::Core)
+}
+
+#[cfg(test)]
+mod test {
+    use probe_rs_target::{MemoryRegion, NvmRegion, RamRegion};
+
+    use super::is_in_ram;
+
+    #[test]
+    fn data_in_ram() {
+        let memory_map = vec![
+            MemoryRegion::Nvm(NvmRegion {
+                name: None,
+                range: 0x0..0x1000,
+                is_boot_memory: true,
+                cores: vec!["main".to_owned()],
+            }),
+            MemoryRegion::Ram(RamRegion {
+                name: None,
+                range: 0x2000_0000..0x2000_1000,
+                is_boot_memory: false,
+                cores: vec!["main".to_owned()],
+            }),
+            MemoryRegion::Ram(RamRegion {
+                name: None,
+                range: 0x2000_1000..0x2000_2000,
+                is_boot_memory: false,
+                cores: vec!["main".to_owned()],
+            }),
+        ];
+
+        assert!(is_in_ram(&memory_map, &(0x2000_0f00..0x2000_1100)));
+        assert!(!is_in_ram(&memory_map, &(0x2000_1f00..0x2000_2100)));
+        assert!(!is_in_ram(&memory_map, &(0x0..0x100)));
+    }
+}
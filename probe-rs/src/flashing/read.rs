@@ -0,0 +1,96 @@
+use std::ops::Range;
+
+use probe_rs_target::MemoryRegion;
+
+use crate::flashing::{flasher::Flasher, stm32_trustzone, FlashError, FlashLoader};
+use crate::{MemoryInterface, Session};
+
+/// Read the contents of the memory in `range`.
+///
+/// NVM which can only be read through its flash algorithm, like external flash, is read using the
+/// flash algorithm. All other memory is read directly using block reads of the core.
+pub fn read_image(session: &mut Session, range: Range<u64>) -> Result<Vec<u8>, FlashError> {
+    log::debug!("Reading {:08x}-{:08x}", range.start, range.end);
+
+    let mut data = vec![0; (range.end - range.start) as usize];
+
+    let mut address = range.start;
+    while address < range.end {
+        let region = session
+            .target()
+            .get_memory_region_by_address(address)
+            .cloned()
+            .ok_or_else(|| FlashError::NoSuitableNvm {
+                start: range.start,
+                end: range.end,
+                description_source: session.target().source.clone(),
+            })?;
+
+        let (region_range, core_name) = match &region {
+            MemoryRegion::Ram(region) => (region.range.clone(), region.cores.first()),
+            MemoryRegion::Generic(region) => (region.range.clone(), region.cores.first()),
+            MemoryRegion::Nvm(region) => (region.range.clone(), region.cores.first()),
+        };
+
+        let end = region_range.end.min(range.end);
+        let chunk = &mut data[(address - range.start) as usize..(end - range.start) as usize];
+
+        let core_index = core_name
+            .and_then(|name| session.target().core_index_by_name(name))
+            .unwrap_or(0);
+
+        log::debug!("    region: {:08x}-{:08x}", address, end);
+
+        let algorithm = match &region {
+            MemoryRegion::Nvm(region) => {
+                let algorithm =
+                    FlashLoader::get_flash_algorithm_for_region(region, session.target())?;
+
+                algorithm.pc_read.map(|_| algorithm.clone())
+            }
+            _ => None,
+        };
+
+        match algorithm {
+            Some(algorithm) => {
+                log::debug!("     -- using algorithm: {}", algorithm.name);
+
+                let mut flasher = Flasher::new(session, core_index, &algorithm)?;
+                flasher.run_verify(|active| active.read_flash(address, chunk))?;
+            }
+            None => {
+                let mut core = session.core(core_index).map_err(FlashError::Core)?;
+                core.read(address, chunk).map_err(FlashError::Core)?;
+            }
+        }
+
+        address = end;
+    }
+
+    Ok(data)
+}
+
+/// Read the contents of all NVM regions of the target.
+///
+/// Returns the start address and the contents of each region.
+pub fn read_flash(session: &mut Session) -> Result<Vec<(u64, Vec<u8>)>, FlashError> {
+    // The secure alias of the flash only exists if TrustZone is enabled.
+    let skip_secure_alias = stm32_trustzone::is_supported(&session.target().name)
+        && !stm32_trustzone::is_enabled(session)?;
+
+    let ranges = session
+        .target()
+        .memory_map
+        .iter()
+        .filter_map(|region| match region {
+            MemoryRegion::Nvm(region) => Some(region.range.clone()),
+            _ => None,
+        })
+        .filter(|range| !(skip_secure_alias && stm32_trustzone::is_secure_alias(range)))
+        .collect::<Vec<_>>();
+
+    ranges
+        .into_iter()
+        .map(|range| Ok((range.start, read_image(session, range)?)))
+        .collect()
+}
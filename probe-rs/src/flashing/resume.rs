@@ -0,0 +1,233 @@
+//! Resuming interrupted downloads.
+//!
+//! The addresses of the sectors which were erased and programmed completely are recorded in a
+//! file, together with the SHA-256 digest of the image. If the download is interrupted, e.g. because
+//! the probe was disconnected, the next download of the same image to the same chip skips these
+//! sectors. This saves a lot of time when programming large external flashes.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{FlashBuilder, FlashError, FlashLayout};
+
+/// The contents of the resume file.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct ResumeFile {
+    /// The name of the chip.
+    chip: String,
+    /// The SHA-256 digest of the image as hex string.
+    image: String,
+    /// The addresses of the completed sectors.
+    sectors: BTreeSet<u64>,
+}
+
+/// The progress of a download, which is persisted in a file.
+pub(super) struct ResumeState {
+    path: PathBuf,
+    file: ResumeFile,
+    /// The number of bytes which still have to be programmed for the sectors of the current layout.
+    remaining: BTreeMap<u64, u64>,
+}
+
+impl ResumeState {
+    /// Open the resume file at `path` for the download of `builder` to `chip`.
+    ///
+    /// The completed sectors of the file are only used if it belongs to the same image and chip,
+    /// otherwise the download starts from scratch.
+    pub(super) fn open(path: &Path, chip: &str, builder: &FlashBuilder) -> Self {
+        let expected = ResumeFile {
+            chip: chip.to_owned(),
+            image: image_digest(builder),
+            sectors: BTreeSet::new(),
+        };
+
+        let file = match fs::read(path) {
+            Ok(contents) => match serde_yaml::from_slice::<ResumeFile>(&contents) {
+                Ok(file) if file.chip == expected.chip && file.image == expected.image => {
+                    log::info!(
+                        "Resuming the download, {} sectors are already programmed",
+                        file.sectors.len()
+                    );
+                    file
+                }
+                Ok(_) => {
+                    log::info!(
+                        "The resume file {} belongs to another image, starting from scratch",
+                        path.display()
+                    );
+                    expected
+                }
+                Err(error) => {
+                    // A file which was only partially written when the host lost power ends up here.
+                    log::warn!(
+                        "Ignoring the invalid resume file {}: {}",
+                        path.display(),
+                        error
+                    );
+                    expected
+                }
+            },
+            Err(_) => expected,
+        };
+
+        Self {
+            path: path.to_owned(),
+            file,
+            remaining: BTreeMap::new(),
+        }
+    }
+
+    /// Check if sectors of a previous download are skipped.
+    pub(super) fn is_resumed(&self) -> bool {
+        !self.file.sectors.is_empty()
+    }
+
+    /// Remove the completed sectors from `flash_layout`, and start tracking the programming of the others.
+    pub(super) fn prepare(&mut self, flash_layout: &mut FlashLayout) {
+        let completed = flash_layout
+            .sectors()
+            .iter()
+            .map(|sector| sector.address())
+            .filter(|address| self.file.sectors.contains(address))
+            .collect::<Vec<_>>();
+
+        if !completed.is_empty() {
+            log::info!("Skipping {} already programmed sectors", completed.len());
+            flash_layout.remove_sectors(&completed);
+        }
+
+        self.remaining = flash_layout
+            .sectors()
+            .iter()
+            .map(|sector| (sector.address(), 0))
+            .collect();
+
+        for page in flash_layout.pages() {
+            if let Some((_, remaining)) = self.remaining.range_mut(..=page.address()).next_back() {
+                *remaining += page.size() as u64;
+            }
+        }
+    }
+
+    /// Record that the page at `address` was programmed.
+    ///
+    /// If it was the last page of its sector, the sector is completed and the file is updated.
+    pub(super) fn page_programmed(&mut self, address: u64, size: u32) -> Result<(), FlashError> {
+        let sector = match self.remaining.range_mut(..=address).next_back() {
+            Some((&sector, remaining)) => {
+                *remaining = remaining.saturating_sub(size as u64);
+                if *remaining > 0 {
+                    return Ok(());
+                }
+                sector
+            }
+            None => return Ok(()),
+        };
+
+        self.remaining.remove(&sector);
+        self.file.sectors.insert(sector);
+        self.save()
+    }
+
+    /// Write the completed sectors to the file.
+    fn save(&self) -> Result<(), FlashError> {
+        let contents =
+            serde_yaml::to_string(&self.file).expect("The resume file can always be serialized");
+
+        // The file is replaced atomically, so an interruption never leaves a partial file behind.
+        let temporary = self.path.with_extension("tmp");
+        fs::write(&temporary, contents)
+            .and_then(|_| fs::rename(&temporary, &self.path))
+            .map_err(|source| FlashError::ResumeFile {
+                path: self.path.clone(),
+                source,
+            })
+    }
+
+    /// Remove the file after the download succeeded.
+    pub(super) fn finish(self) -> Result<(), FlashError> {
+        match fs::remove_file(&self.path) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                Err(FlashError::ResumeFile {
+                    path: self.path,
+                    source: error,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The SHA-256 digest of the data of `builder`, including the addresses.
+fn image_digest(builder: &FlashBuilder) -> String {
+    let mut hasher = Sha256::new();
+
+    for (address, data) in &builder.data {
+        hasher.update(address.to_le_bytes());
+        hasher.update((data.len() as u64).to_le_bytes());
+        hasher.update(data);
+    }
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{image_digest, ResumeState};
+    use crate::flashing::FlashBuilder;
+
+    fn builder(data: &[u8]) -> FlashBuilder {
+        let mut builder = FlashBuilder::new();
+        builder.add_data(0x0800_0000, data).unwrap();
+        builder
+    }
+
+    #[test]
+    fn digest_depends_on_address() {
+        let mut other = FlashBuilder::new();
+        other.add_data(0x0800_0004, &[1, 2, 3, 4]).unwrap();
+
+        assert_eq!(
+            image_digest(&builder(&[1, 2, 3, 4])),
+            image_digest(&builder(&[1, 2, 3, 4]))
+        );
+        assert_ne!(image_digest(&builder(&[1, 2, 3, 4])), image_digest(&other));
+    }
+
+    #[test]
+    fn completed_sectors_are_persisted() {
+        let path = std::env::temp_dir().join(format!("probe-rs-resume-{}", std::process::id()));
+
+        let mut state = ResumeState::open(&path, "STM32F407VGTx", &builder(&[1, 2, 3, 4]));
+        assert!(!state.is_resumed());
+
+        state.remaining.insert(0x0800_0000, 0x800);
+        state.remaining.insert(0x0800_4000, 0x800);
+
+        state.page_programmed(0x0800_0000, 0x400).unwrap();
+        state.page_programmed(0x0800_0400, 0x400).unwrap();
+        state.page_programmed(0x0800_4000, 0x400).unwrap();
+
+        let resumed = ResumeState::open(&path, "STM32F407VGTx", &builder(&[1, 2, 3, 4]));
+        assert!(resumed.is_resumed());
+        assert_eq!(
+            resumed.file.sectors.iter().copied().collect::<Vec<_>>(),
+            vec![0x0800_0000]
+        );
+
+        // Another image starts from scratch.
+        let other = ResumeState::open(&path, "STM32F407VGTx", &builder(&[5, 6, 7, 8]));
+        assert!(!other.is_resumed());
+
+        resumed.finish().unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,268 @@
+//! TrustZone aware flashing of the STM32L5 and STM32U5.
+//!
+//! The flash of these chips is mapped twice, at a non-secure and at a secure alias.
+//! When TrustZone is enabled using the `TZEN` option bit, the pages inside the secure watermarks
+//! can only be programmed through the secure alias, and all other pages only through the non-secure alias.
+//! When TrustZone is disabled, only the non-secure alias exists.
+//!
+//! The data of an image is moved to the alias which matches the security of its pages, so the image
+//! can be flashed regardless of the alias it was linked for. After a reset, the core runs in the secure state,
+//! so the secure flash algorithm can program the secure pages using the secure accesses of the AHB-AP.
+
+use std::ops::Range;
+
+use probe_rs_target::MemoryRegion;
+
+use super::builder::FlashBuilder;
+use super::FlashError;
+use crate::{MemoryInterface, Session};
+
+/// The start of the non-secure alias of the flash.
+const NON_SECURE_ALIAS: u64 = 0x0800_0000;
+/// The start of the secure alias of the flash.
+const SECURE_ALIAS: u64 = 0x0C00_0000;
+/// The size of the address space of each alias.
+const ALIAS_SIZE: u64 = 0x0400_0000;
+
+/// The non-secure alias of the flash interface, which can be read in both security states.
+const FLASH_BASE: u64 = 0x4002_2000;
+
+const OPTR: u64 = 0x40;
+/// The secure watermark registers of bank 1 and 2.
+const SECWM_R1: [u64; 2] = [0x50, 0x60];
+
+/// TrustZone is enabled.
+const OPTR_TZEN: u32 = 1 << 31;
+/// The STM32L5 flash is split into two banks.
+const OPTR_DBANK: u32 = 1 << 22;
+
+/// The mask of the `SECWMx_PSTRT` and `SECWMx_PEND` fields.
+const SECWM_PAGE_MASK: u32 = 0x7F;
+
+/// Check if the chip is one of the STM32 families with TrustZone.
+pub(super) fn is_supported(name: &str) -> bool {
+    name.starts_with("STM32L5") || name.starts_with("STM32U5")
+}
+
+/// Check if the range is part of the secure alias of the flash.
+pub(super) fn is_secure_alias(range: &Range<u64>) -> bool {
+    (SECURE_ALIAS..SECURE_ALIAS + ALIAS_SIZE).contains(&range.start)
+}
+
+/// The TrustZone configuration of the flash.
+struct Configuration {
+    /// The size of the flash.
+    size: u64,
+    /// The secure pages, as offsets into the flash.
+    secure: Vec<Range<u64>>,
+}
+
+/// Read the `OPTR` register of the flash interface.
+fn read_optr(session: &mut Session) -> Result<u32, FlashError> {
+    let mut core = session.core(0).map_err(FlashError::Core)?;
+
+    core.read_word_32(FLASH_BASE + OPTR)
+        .map_err(FlashError::Core)
+}
+
+/// Check if TrustZone is enabled.
+pub(super) fn is_enabled(session: &mut Session) -> Result<bool, FlashError> {
+    Ok(read_optr(session)? & OPTR_TZEN != 0)
+}
+
+/// The secure pages of a flash with the given layout, based on the secure watermark registers of each bank.
+fn secure_ranges(secwm: &[u32], bank_size: u64, page_size: u64) -> Vec<Range<u64>> {
+    secwm
+        .iter()
+        .enumerate()
+        .filter_map(|(bank, &secwm)| {
+            let start = (secwm & SECWM_PAGE_MASK) as u64;
+            let end = ((secwm >> 16) & SECWM_PAGE_MASK) as u64;
+
+            // The watermarks are inclusive, a start after the end means that there are no secure pages.
+            if start > end {
+                return None;
+            }
+
+            let bank_start = bank as u64 * bank_size;
+
+            Some(bank_start + start * page_size..bank_start + (end + 1) * page_size)
+        })
+        .collect()
+}
+
+/// Read the TrustZone configuration of the flash.
+fn read_configuration(session: &mut Session) -> Result<Configuration, FlashError> {
+    let size = session
+        .target()
+        .memory_map
+        .iter()
+        .find_map(|region| match region {
+            MemoryRegion::Nvm(region) if region.range.start == NON_SECURE_ALIAS => {
+                Some(region.range.end - region.range.start)
+            }
+            _ => None,
+        })
+        .ok_or_else(|| FlashError::NoSuitableNvm {
+            start: NON_SECURE_ALIAS,
+            end: NON_SECURE_ALIAS,
+            description_source: session.target().source.clone(),
+        })?;
+
+    let optr = read_optr(session)?;
+
+    if optr & OPTR_TZEN == 0 {
+        return Ok(Configuration {
+            size,
+            secure: Vec::new(),
+        });
+    }
+
+    let (banks, page_size) = if session.target().name.starts_with("STM32U5") {
+        (2, 0x2000)
+    } else if optr & OPTR_DBANK != 0 {
+        (2, 0x800)
+    } else {
+        (1, 0x1000)
+    };
+
+    let mut core = session.core(0).map_err(FlashError::Core)?;
+
+    let mut secwm = Vec::new();
+    for offset in &SECWM_R1[..banks] {
+        secwm.push(
+            core.read_word_32(FLASH_BASE + offset)
+                .map_err(FlashError::Core)?,
+        );
+    }
+
+    Ok(Configuration {
+        size,
+        secure: secure_ranges(&secwm, size / banks as u64, page_size),
+    })
+}
+
+/// Split the range of the flash into parts, which are either completely secure or non-secure.
+fn split_by_security(range: Range<u64>, secure: &[Range<u64>]) -> Vec<(Range<u64>, bool)> {
+    let mut boundaries = secure
+        .iter()
+        .flat_map(|secure| [secure.start, secure.end])
+        .filter(|&boundary| range.start < boundary && boundary < range.end)
+        .collect::<Vec<_>>();
+
+    boundaries.push(range.end);
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut start = range.start;
+    let mut parts = Vec::new();
+
+    for end in boundaries {
+        let is_secure = secure.iter().any(|secure| secure.contains(&start));
+        parts.push((start..end, is_secure));
+        start = end;
+    }
+
+    parts
+}
+
+/// Move the data in the flash to the alias which matches the security of its pages.
+pub(super) fn assign_aliases(
+    session: &mut Session,
+    builder: &FlashBuilder,
+) -> Result<FlashBuilder, FlashError> {
+    let configuration = read_configuration(session)?;
+
+    if configuration.secure.is_empty() {
+        log::debug!("There are no secure flash pages, using the non-secure alias of the flash");
+    } else {
+        log::debug!(
+            "TrustZone is enabled, secure flash pages: {:x?}",
+            configuration.secure
+        );
+    }
+
+    let mut assigned = FlashBuilder::new();
+
+    for (&address, data) in &builder.data {
+        let alias = [NON_SECURE_ALIAS, SECURE_ALIAS]
+            .into_iter()
+            .find(|&alias| (alias..alias + configuration.size).contains(&address));
+
+        let alias = match alias {
+            Some(alias) => alias,
+            None => {
+                assigned.add_data(address, data)?;
+                continue;
+            }
+        };
+
+        let offset = address - alias;
+
+        for (part, is_secure) in
+            split_by_security(offset..offset + data.len() as u64, &configuration.secure)
+        {
+            let part_alias = if is_secure {
+                SECURE_ALIAS
+            } else {
+                NON_SECURE_ALIAS
+            };
+
+            if part_alias != alias {
+                log::info!(
+                    "Moving the data at {:#010x}-{:#010x} to the {} alias of the flash",
+                    alias + part.start,
+                    alias + part.end,
+                    if is_secure { "secure" } else { "non-secure" }
+                );
+            }
+
+            let data = &data[(part.start - offset) as usize..(part.end - offset) as usize];
+
+            assigned.add_data(part_alias + part.start, data)?;
+        }
+    }
+
+    Ok(assigned)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{secure_ranges, split_by_security};
+
+    #[test]
+    fn watermarks() {
+        // Pages 0 to 3 of bank 1 are secure, bank 2 has no secure pages.
+        let secwm = [0x0003_0000, 0x0000_007F];
+
+        assert_eq!(secure_ranges(&secwm, 0x10_0000, 0x2000), vec![0..0x8000]);
+
+        // Pages 0x10 to 0x1F of bank 2 are secure.
+        let secwm = [0x0000_007F, 0x001F_0010];
+
+        assert_eq!(
+            secure_ranges(&secwm, 0x4_0000, 0x800),
+            vec![0x4_8000..0x5_0000]
+        );
+    }
+
+    #[test]
+    fn split_data() {
+        let secure = [0..0x8000];
+
+        assert_eq!(
+            split_by_security(0x7000..0x9000, &secure),
+            vec![(0x7000..0x8000, true), (0x8000..0x9000, false)]
+        );
+
+        assert_eq!(
+            split_by_security(0x9000..0xA000, &secure),
+            vec![(0x9000..0xA000, false)]
+        );
+
+        assert_eq!(
+            split_by_security(0x0..0x100, &[]),
+            vec![(0x0..0x100, false)]
+        );
+    }
+}
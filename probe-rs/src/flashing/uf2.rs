@@ -0,0 +1,301 @@
+//! Parsing of [UF2](https://github.com/microsoft/uf2) files.
+//!
+//! A UF2 file consists of 512 byte blocks, each of which contains the data for one address in the flash.
+//! A file can contain blocks for multiple chip families, which are distinguished by their family ID.
+
+use std::collections::BTreeSet;
+
+/// The size of a UF2 block.
+const BLOCK_SIZE: usize = 512;
+/// The maximum size of the payload of a block.
+const MAX_PAYLOAD_SIZE: u32 = 476;
+
+const MAGIC_START0: u32 = 0x0A32_4655;
+const MAGIC_START1: u32 = 0x9E5D_5157;
+const MAGIC_END: u32 = 0x0AB1_6F30;
+
+/// The block is not meant for the main flash, e.g. it contains comments.
+const FLAG_NOT_MAIN_FLASH: u32 = 0x0000_0001;
+/// The block contains a family ID instead of the file size.
+const FLAG_FAMILY_ID_PRESENT: u32 = 0x0000_2000;
+
+/// The family IDs of the chips, from the UF2 specification.
+///
+/// The prefixes of the more specific chip names have to come first.
+const FAMILY_IDS: &[(&str, u32)] = &[
+    ("ATSAMD21", 0x68ED_2B88),
+    ("ATSAMD51", 0x5511_4460),
+    ("MIMXRT10", 0x4FB2_D5BD),
+    ("RP2040", 0xE48B_FF56),
+    ("STM32F0", 0x6478_24B6),
+    ("STM32F1", 0x5EE2_1072),
+    ("STM32F2", 0x5D1A_0A2E),
+    ("STM32F3", 0x6B84_6188),
+    ("STM32F4", 0x5775_5A57),
+    ("STM32F7", 0x53B8_0F00),
+    ("STM32G0", 0x300F_5633),
+    ("STM32H7", 0x6DB6_6082),
+    ("STM32L4", 0x00FF_6919),
+    ("STM32WB", 0x70D1_6653),
+    ("nRF52833", 0x621E_937A),
+    ("nRF52840", 0xADA5_2840),
+    ("nRF52", 0x1B57_745F),
+];
+
+/// Errors which can occur while reading a UF2 file.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum Uf2Error {
+    /// The size of the file is not a multiple of the block size.
+    #[error("The size of the UF2 file ({0} bytes) is not a multiple of 512 bytes.")]
+    InvalidLength(usize),
+    /// The magic numbers of a block are wrong.
+    #[error("Block {0} of the UF2 file has invalid magic numbers.")]
+    InvalidMagic(usize),
+    /// The payload of a block is larger than the block.
+    #[error("Block {block} of the UF2 file has an invalid payload size of {size} bytes.")]
+    InvalidPayloadSize {
+        /// The index of the block in the file.
+        block: usize,
+        /// The payload size of the block.
+        size: u32,
+    },
+    /// The file contains no blocks for the family of the target.
+    #[error("The UF2 file contains no blocks for the family {expected:#010x} of the target, only for {found:#010x?}.")]
+    FamilyMismatch {
+        /// The family ID of the target.
+        expected: u32,
+        /// The family IDs found in the file.
+        found: Vec<u32>,
+    },
+    /// The file contains blocks for multiple families, and the family of the target is not known.
+    #[error("The UF2 file contains blocks for the families {0:#010x?}, but the family of the target is unknown.")]
+    AmbiguousFamily(Vec<u32>),
+    /// Some blocks of the file are missing.
+    #[error("The UF2 file is incomplete, it contains {found} of {expected} blocks.")]
+    MissingBlocks {
+        /// The number of blocks given in the blocks.
+        expected: u32,
+        /// The number of blocks found in the file.
+        found: usize,
+    },
+}
+
+/// A block of a UF2 file.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct Block<'data> {
+    flags: u32,
+    /// The address of the data in the flash.
+    pub(super) target_address: u32,
+    /// The payload of the block.
+    pub(super) data: &'data [u8],
+    block_no: u32,
+    num_blocks: u32,
+    family_id: Option<u32>,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Parse the block with the given index in the file.
+fn parse_block(index: usize, block: &[u8]) -> Result<Block<'_>, Uf2Error> {
+    if read_u32(block, 0) != MAGIC_START0
+        || read_u32(block, 4) != MAGIC_START1
+        || read_u32(block, BLOCK_SIZE - 4) != MAGIC_END
+    {
+        return Err(Uf2Error::InvalidMagic(index));
+    }
+
+    let flags = read_u32(block, 8);
+    let payload_size = read_u32(block, 16);
+
+    if payload_size > MAX_PAYLOAD_SIZE {
+        return Err(Uf2Error::InvalidPayloadSize {
+            block: index,
+            size: payload_size,
+        });
+    }
+
+    let family_id = if flags & FLAG_FAMILY_ID_PRESENT != 0 {
+        Some(read_u32(block, 28))
+    } else {
+        None
+    };
+
+    Ok(Block {
+        flags,
+        target_address: read_u32(block, 12),
+        data: &block[32..32 + payload_size as usize],
+        block_no: read_u32(block, 20),
+        num_blocks: read_u32(block, 24),
+        family_id,
+    })
+}
+
+/// The UF2 family ID of the chip `name`, if it is known.
+fn family_id(name: &str) -> Option<u32> {
+    FAMILY_IDS
+        .iter()
+        .find(|(prefix, _)| name.starts_with(prefix))
+        .map(|&(_, id)| id)
+}
+
+/// Parse a UF2 file, and return the blocks for the flash of the chip `target_name`.
+///
+/// If the file contains blocks for multiple families, only the blocks for the family of the chip are returned.
+pub(super) fn blocks_for_target<'data>(
+    data: &'data [u8],
+    target_name: &str,
+) -> Result<Vec<Block<'data>>, Uf2Error> {
+    if data.len() % BLOCK_SIZE != 0 {
+        return Err(Uf2Error::InvalidLength(data.len()));
+    }
+
+    let mut blocks = Vec::new();
+    for (index, block) in data.chunks(BLOCK_SIZE).enumerate() {
+        let block = parse_block(index, block)?;
+
+        if block.flags & FLAG_NOT_MAIN_FLASH == 0 {
+            blocks.push(block);
+        }
+    }
+
+    let families = blocks
+        .iter()
+        .filter_map(|block| block.family_id)
+        .collect::<BTreeSet<_>>();
+
+    let family = match family_id(target_name) {
+        Some(expected) if families.contains(&expected) => Some(expected),
+        // Blocks without a family ID are used for any target.
+        Some(_) if families.is_empty() => None,
+        Some(expected) => {
+            return Err(Uf2Error::FamilyMismatch {
+                expected,
+                found: families.into_iter().collect(),
+            })
+        }
+        None if families.len() > 1 => {
+            return Err(Uf2Error::AmbiguousFamily(families.into_iter().collect()))
+        }
+        None => {
+            log::debug!(
+                "The UF2 family of {} is unknown, using all blocks of the file",
+                target_name
+            );
+            families.into_iter().next()
+        }
+    };
+
+    blocks.retain(|block| block.family_id.is_none() || block.family_id == family);
+
+    if let Some(first) = blocks.first() {
+        let found = blocks
+            .iter()
+            .map(|block| block.block_no)
+            .collect::<BTreeSet<_>>()
+            .len();
+
+        if found != first.num_blocks as usize {
+            return Err(Uf2Error::MissingBlocks {
+                expected: first.num_blocks,
+                found,
+            });
+        }
+    }
+
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{blocks_for_target, Uf2Error, BLOCK_SIZE, FLAG_FAMILY_ID_PRESENT};
+
+    const RP2040: u32 = 0xE48B_FF56;
+    const NRF52840: u32 = 0xADA5_2840;
+
+    fn block(address: u32, block_no: u32, num_blocks: u32, family_id: Option<u32>) -> Vec<u8> {
+        let flags = family_id.map(|_| FLAG_FAMILY_ID_PRESENT).unwrap_or(0);
+
+        let header = [
+            0x0A32_4655,
+            0x9E5D_5157,
+            flags,
+            address,
+            4,
+            block_no,
+            num_blocks,
+            family_id.unwrap_or(0),
+        ];
+
+        let mut block = vec![0; BLOCK_SIZE];
+        for (i, word) in header.iter().enumerate() {
+            block[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+        }
+        block[32..36].copy_from_slice(&address.to_le_bytes());
+        block[BLOCK_SIZE - 4..].copy_from_slice(&0x0AB1_6F30u32.to_le_bytes());
+
+        block
+    }
+
+    #[test]
+    fn blocks_of_target_family() {
+        let file = [
+            block(0x1000_0000, 0, 2, Some(RP2040)),
+            block(0x1000_0004, 1, 2, Some(RP2040)),
+            block(0x0000_0000, 0, 1, Some(NRF52840)),
+        ]
+        .concat();
+
+        let blocks = blocks_for_target(&file, "RP2040").unwrap();
+
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[1].target_address, 0x1000_0004);
+        assert_eq!(blocks[1].data, &0x1000_0004u32.to_le_bytes());
+
+        let blocks = blocks_for_target(&file, "nRF52840_xxAA").unwrap();
+
+        assert_eq!(blocks.len(), 1);
+    }
+
+    #[test]
+    fn family_mismatch() {
+        let file = block(0x1000_0000, 0, 1, Some(RP2040));
+
+        assert_eq!(
+            blocks_for_target(&file, "STM32F407VGTx"),
+            Err(Uf2Error::FamilyMismatch {
+                expected: 0x5775_5A57,
+                found: vec![RP2040]
+            })
+        );
+    }
+
+    #[test]
+    fn missing_block() {
+        let file = [
+            block(0x1000_0000, 0, 3, Some(RP2040)),
+            block(0x1000_0008, 2, 3, Some(RP2040)),
+        ]
+        .concat();
+
+        assert_eq!(
+            blocks_for_target(&file, "RP2040"),
+            Err(Uf2Error::MissingBlocks {
+                expected: 3,
+                found: 2
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_length() {
+        let mut file = block(0x1000_0000, 0, 1, None);
+        file.pop();
+
+        assert_eq!(
+            blocks_for_target(&file, "RP2040"),
+            Err(Uf2Error::InvalidLength(511))
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! Handling of the hardware watchdogs while flashing.
+//!
+//! The flash algorithms run on the core, so a watchdog which was started by the firmware, the boot ROM
+//! or the option bytes keeps running during a sector erase or a page program. If it expires, the chip
+//! is reset in the middle of programming, which leaves a corrupted image behind.
+//!
+//! After the core was reset and halted for flashing, the watchdogs are disabled if the chip allows it,
+//! or stopped while the core is halted. Watchdogs which can't be disabled are reloaded before and while
+//! each routine of the flash algorithm runs.
+
+use std::time::Duration;
+
+use crate::architecture::riscv::sequences::{esp32c3, esp32c6};
+use crate::Core;
+
+/// How often a watchdog is reloaded while a routine of the flash algorithm runs.
+pub(super) const FEED_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A hook which disables or stops the watchdogs, called with the address of RAM which can be used.
+type DisableHook = fn(&mut Core, u64) -> Result<(), crate::Error>;
+
+/// A hook which reloads the watchdogs.
+pub(super) type FeedHook = fn(&mut Core) -> Result<(), crate::Error>;
+
+/// The watchdog handling of a chip family.
+struct Watchdogs {
+    /// The prefixes of the names of the chips of the family.
+    prefixes: &'static [&'static str],
+    disable: Option<DisableHook>,
+    feed: Option<FeedHook>,
+}
+
+static FAMILIES: &[Watchdogs] = &[
+    Watchdogs {
+        prefixes: &["STM32F0"],
+        disable: Some(stm32::freeze_f0),
+        feed: Some(stm32::feed_iwdg),
+    },
+    Watchdogs {
+        prefixes: &["STM32G0"],
+        disable: Some(stm32::freeze_g0),
+        feed: Some(stm32::feed_iwdg),
+    },
+    Watchdogs {
+        prefixes: &["STM32L0"],
+        disable: Some(stm32::freeze_l0),
+        feed: Some(stm32::feed_iwdg),
+    },
+    Watchdogs {
+        prefixes: &["STM32F1"],
+        disable: Some(stm32::freeze_f1),
+        feed: Some(stm32::feed_iwdg),
+    },
+    Watchdogs {
+        prefixes: &[
+            "STM32F2", "STM32F3", "STM32F4", "STM32F7", "STM32L1", "STM32L4", "STM32G4", "STM32WB",
+        ],
+        disable: Some(stm32::freeze_apb1),
+        feed: Some(stm32::feed_iwdg),
+    },
+    Watchdogs {
+        prefixes: &["STM32H7"],
+        disable: Some(stm32::freeze_h7),
+        feed: Some(stm32::feed_iwdg_h7),
+    },
+    Watchdogs {
+        prefixes: &["nRF51", "nRF52"],
+        disable: None,
+        feed: Some(nrf::feed_wdt),
+    },
+    Watchdogs {
+        prefixes: &["MKL"],
+        disable: Some(kinetis::disable_cop),
+        feed: None,
+    },
+    Watchdogs {
+        prefixes: &[
+            "MK0", "MK1", "MK2", "MK3", "MK4", "MK5", "MK6", "MK7", "MK8",
+        ],
+        disable: Some(kinetis::disable_wdog),
+        feed: None,
+    },
+    Watchdogs {
+        prefixes: &["esp32c3"],
+        disable: Some(|core, _| esp32c3::disable_watchdogs(core)),
+        feed: None,
+    },
+    Watchdogs {
+        prefixes: &["esp32c6"],
+        disable: Some(|core, _| esp32c6::disable_watchdogs(core)),
+        feed: None,
+    },
+];
+
+fn family(name: &str) -> Option<&'static Watchdogs> {
+    FAMILIES.iter().find(|family| {
+        family
+            .prefixes
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    })
+}
+
+/// Disable or stop the watchdogs of the chip `name`, after the core was reset and halted.
+///
+/// `ram` is the address of RAM which can be used before the flash algorithm is loaded.
+pub(super) fn disable(core: &mut Core, name: &str, ram: u64) -> Result<(), crate::Error> {
+    if let Some(disable) = family(name).and_then(|family| family.disable) {
+        log::debug!("Disabling the watchdogs of {}", name);
+        disable(core, ram)?;
+    }
+
+    Ok(())
+}
+
+/// The hook which reloads the watchdogs of the chip `name`, if they can't be disabled.
+pub(super) fn feed_hook(name: &str) -> Option<FeedHook> {
+    family(name).and_then(|family| family.feed)
+}
+
+mod stm32 {
+    use crate::{Core, MemoryInterface};
+
+    /// The value of the key register, which reloads the independent watchdog.
+    const IWDG_RELOAD: u32 = 0xAAAA;
+
+    /// Set `bits` in the freeze register at `address`, which stop the watchdogs while the core is halted.
+    fn freeze(core: &mut Core, address: u64, bits: u32) -> Result<(), crate::Error> {
+        let value = core.read_word_32(address)?;
+        core.write_word_32(address, value | bits)
+    }
+
+    /// The DBGMCU of the STM32F0, G0 and L0 is on the APB bus, and has to be clocked first.
+    fn freeze_apb(core: &mut Core, rcc_enable: u64, rcc_bit: u32) -> Result<(), crate::Error> {
+        freeze(core, rcc_enable, 1 << rcc_bit)?;
+
+        // DBG_IWDG_STOP and DBG_WWDG_STOP in DBGMCU_APB1_FZ.
+        freeze(core, 0x4001_5808, (1 << 12) | (1 << 11))
+    }
+
+    pub(super) fn freeze_f0(core: &mut Core, _ram: u64) -> Result<(), crate::Error> {
+        // RCC_APB2ENR.DBGMCUEN
+        freeze_apb(core, 0x4002_1018, 22)
+    }
+
+    pub(super) fn freeze_g0(core: &mut Core, _ram: u64) -> Result<(), crate::Error> {
+        // RCC_APBENR1.DBGEN
+        freeze_apb(core, 0x4002_103C, 27)
+    }
+
+    pub(super) fn freeze_l0(core: &mut Core, _ram: u64) -> Result<(), crate::Error> {
+        // RCC_APB2ENR.DBGMCUEN
+        freeze_apb(core, 0x4002_1034, 22)
+    }
+
+    pub(super) fn freeze_f1(core: &mut Core, _ram: u64) -> Result<(), crate::Error> {
+        // DBG_IWDG_STOP and DBG_WWDG_STOP in DBGMCU_CR.
+        freeze(core, 0xE004_2004, (1 << 8) | (1 << 9))
+    }
+
+    pub(super) fn freeze_apb1(core: &mut Core, _ram: u64) -> Result<(), crate::Error> {
+        // DBG_IWDG_STOP and DBG_WWDG_STOP in DBGMCU_APB1_FZ.
+        freeze(core, 0xE004_2008, (1 << 12) | (1 << 11))
+    }
+
+    pub(super) fn freeze_h7(core: &mut Core, _ram: u64) -> Result<(), crate::Error> {
+        // DBG_WWDG1 in DBGMCU_APB3FZ1, DBG_IWDG1 in DBGMCU_APB4FZ1.
+        freeze(core, 0x5C00_104C, 1 << 6)?;
+        freeze(core, 0x5C00_1054, 1 << 18)
+    }
+
+    pub(super) fn feed_iwdg(core: &mut Core) -> Result<(), crate::Error> {
+        // IWDG_KR
+        core.write_word_32(0x4000_3000, IWDG_RELOAD)
+    }
+
+    pub(super) fn feed_iwdg_h7(core: &mut Core) -> Result<(), crate::Error> {
+        // IWDG1_KR
+        core.write_word_32(0x5800_4800, IWDG_RELOAD)
+    }
+}
+
+mod nrf {
+    use crate::{Core, MemoryInterface};
+
+    /// The enabled reload request registers (RREN) of the WDT.
+    const WDT_RREN: u64 = 0x4001_0508;
+    /// The first reload request register (RR[0]) of the WDT.
+    const WDT_RR: u64 = 0x4001_0600;
+    /// The value which has to be written to the reload request registers.
+    const RELOAD: u32 = 0x6E52_4635;
+
+    /// The WDT can't be stopped once it is running, and isn't reset by a soft reset.
+    pub(super) fn feed_wdt(core: &mut Core) -> Result<(), crate::Error> {
+        let enabled = core.read_word_32(WDT_RREN)?;
+
+        for index in 0..8 {
+            if enabled & (1 << index) != 0 {
+                core.write_word_32(WDT_RR + 4 * index, RELOAD)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+mod kinetis {
+    use std::time::Duration;
+
+    use crate::{Core, MemoryInterface};
+
+    /// The COP control register (SIM_COPC) of the Kinetis KL, which can be written once after a reset.
+    const SIM_COPC: u64 = 0x4004_8100;
+
+    /// The code which unlocks the WDOG and clears `WDOG_STCTRLH.WDOGEN`.
+    ///
+    /// The two unlock writes have to be done within 20 bus clocks, which is only possible from the core:
+    ///
+    /// ```text
+    ///     ldr   r0, =0x4005200E   ; WDOG_UNLOCK
+    ///     ldr   r1, =0xC520
+    ///     ldr   r2, =0xD928
+    ///     ldr   r3, =0x01D2       ; WDOG_STCTRLH without WDOGEN
+    ///     strh  r1, [r0]
+    ///     strh  r2, [r0]
+    ///     subs  r0, #14           ; WDOG_STCTRLH
+    ///     strh  r3, [r0]
+    ///     bkpt  #0
+    /// ```
+    const DISABLE_WDOG: [u32; 9] = [
+        0x4905_4804,
+        0x4B06_4A05,
+        0x8002_8001,
+        0x8003_380E,
+        0xBF00_BE00,
+        0x4005_200E,
+        0x0000_C520,
+        0x0000_D928,
+        0x0000_01D2,
+    ];
+
+    pub(super) fn disable_cop(core: &mut Core, _ram: u64) -> Result<(), crate::Error> {
+        core.write_word_32(SIM_COPC, 0)
+    }
+
+    pub(super) fn disable_wdog(core: &mut Core, ram: u64) -> Result<(), crate::Error> {
+        core.write_32(ram, &DISABLE_WDOG)?;
+
+        let pc = core.registers().program_counter().id;
+        core.write_core_reg(pc, ram)?;
+        core.run()?;
+        core.wait_for_core_halted(Duration::from_millis(100))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{family, feed_hook};
+
+    #[test]
+    fn families() {
+        assert!(family("STM32F407VGTx").is_some());
+        assert!(family("MKL25Z128VLK4").unwrap().feed.is_none());
+        assert!(family("MK64FN1M0VLL12").unwrap().disable.is_some());
+        assert!(feed_hook("nRF52840_xxAA").is_some());
+        assert!(family("LPC55S69JBD100").is_none());
+    }
+}
@@ -73,6 +73,7 @@ pub mod config;
 
 #[warn(missing_docs)]
 mod core;
+mod crc32;
 pub mod debug;
 mod error;
 #[warn(missing_docs)]
@@ -82,19 +83,29 @@ mod memory;
 #[warn(missing_docs)]
 mod probe;
 #[warn(missing_docs)]
+pub mod semihosting;
+#[warn(missing_docs)]
 mod session;
 
 pub use crate::config::{CoreType, InstructionSet, Target};
 pub use crate::core::{
     Architecture, BreakpointCause, BreakpointId, CommunicationInterface, Core, CoreInformation,
     CoreInterface, CoreState, CoreStatus, HaltReason, MemoryMappedRegister, RegisterDescription,
-    RegisterFile, RegisterId, RegisterValue, SpecificCoreState,
+    RegisterFile, RegisterId, RegisterValue, SecurityState, SpecificCoreState, VectorCatch,
+    WatchpointAccess, WatchpointConfig, WatchpointSize,
 };
 pub use crate::error::Error;
 pub use crate::memory::{Memory, MemoryInterface};
+pub use crate::probe::buspirate::{BusPirate, BusPirateError};
+pub use crate::probe::remote::{RemoteProbe, RemoteProbeError, RemoteProbeServer};
+pub use crate::probe::remote_bitbang::RemoteBitbang;
+
+#[cfg(all(feature = "gpiod", target_os = "linux"))]
+pub use crate::probe::gpiod::{GpioProbe, GpioProbeConfig, GpioProbeError};
 pub use crate::probe::{
-    AttachMethod, DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, DebugProbeType,
-    Probe, ProbeCreationError, WireProtocol,
+    is_disconnect_error, register_probe_driver, AttachMethod, DebugProbe, DebugProbeError,
+    DebugProbeInfo, DebugProbeSelector, DebugProbeType, Probe, ProbeCapabilities,
+    ProbeCreationError, ProbeDriver, ProbeUart, ScanChainElement, WireProtocol,
 };
 pub use crate::session::{Permissions, Session};
 
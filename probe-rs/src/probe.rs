@@ -1,10 +1,21 @@
+pub(crate) mod arm_debug_interface;
+pub(crate) mod blackmagic;
+pub(crate) mod buspirate;
+pub(crate) mod cjtag;
 pub(crate) mod cmsisdap;
 pub(crate) mod espusbjtag;
 pub(crate) mod fake_probe;
 #[cfg(feature = "ftdi")]
 pub(crate) mod ftdi;
+#[cfg(all(feature = "gpiod", target_os = "linux"))]
+pub(crate) mod gpiod;
+pub(crate) mod icepick;
 pub(crate) mod jlink;
+pub(crate) mod remote;
+pub(crate) mod remote_bitbang;
+pub(crate) mod scan_chain;
 pub(crate) mod stlink;
+pub(crate) mod wlink;
 
 use self::espusbjtag::list_espjtag_devices;
 use crate::error::Error;
@@ -18,14 +29,22 @@ use crate::{
         arm::{
             communication_interface::DapProbe,
             sequences::{ArmDebugSequence, DefaultArmSequence},
-            PortType, SwoAccess,
+            PortType, RawDapAccess, SwoAccess, SwoMode,
         },
         riscv::communication_interface::RiscvCommunicationInterface,
+        xtensa::communication_interface::XtensaCommunicationInterface,
     },
     Permissions,
 };
 use jlink::list_jlink_devices;
-use std::{convert::TryFrom, fmt};
+use once_cell::sync::Lazy;
+pub use scan_chain::ScanChainElement;
+use std::{
+    convert::TryFrom,
+    fmt,
+    sync::{Arc, PoisonError, RwLock},
+    time::{Duration, Instant},
+};
 
 /// Used to log warnings when the measured target voltage is
 /// lower than 1.4V, if at all measureable.
@@ -42,6 +61,16 @@ pub enum WireProtocol {
     /// JTAG is a standard which is supported by many chips independent of architecture.
     /// See [`Wikipedia`](https://en.wikipedia.org/wiki/JTAG) for more info.
     Jtag,
+    /// Compact JTAG as specified in IEEE 1149.7, using only the two pins TCKC and TMSC.
+    /// The TAP is accessed like a standard JTAG TAP, using the OScan1 scan format.
+    Cjtag,
+}
+
+impl WireProtocol {
+    /// Check if the protocol is JTAG, either with four pins or as cJTAG.
+    pub fn is_jtag(&self) -> bool {
+        matches!(self, WireProtocol::Jtag | WireProtocol::Cjtag)
+    }
 }
 
 impl fmt::Display for WireProtocol {
@@ -49,6 +78,7 @@ impl fmt::Display for WireProtocol {
         match self {
             WireProtocol::Swd => write!(f, "SWD"),
             WireProtocol::Jtag => write!(f, "JTAG"),
+            WireProtocol::Cjtag => write!(f, "cJTAG"),
         }
     }
 }
@@ -60,8 +90,9 @@ impl std::str::FromStr for WireProtocol {
         match &s.to_ascii_lowercase()[..] {
             "swd" => Ok(WireProtocol::Swd),
             "jtag" => Ok(WireProtocol::Jtag),
+            "cjtag" => Ok(WireProtocol::Cjtag),
             _ => Err(format!(
-                "'{}' is not a valid protocol. Choose from [swd, jtag].",
+                "'{}' is not a valid protocol. Choose from [swd, jtag, cjtag].",
                 s
             )),
         }
@@ -200,6 +231,87 @@ pub enum ProbeCreationError {
     Other(&'static str),
 }
 
+/// A driver for debug probes which are not supported by probe-rs itself.
+///
+/// This allows crates using probe-rs to support proprietary or in-house probes. After a
+/// driver has been registered using [`register_probe_driver`], its probes are
+/// listed by [`Probe::list_all`] and can be opened using [`Probe::open`],
+/// just like the probes supported by probe-rs.
+pub trait ProbeDriver: Send + Sync + fmt::Debug {
+    /// List all connected probes which are handled by this driver.
+    ///
+    /// The [`DebugProbeInfo::probe_type`] of the returned probes should be [`DebugProbeType::Custom`].
+    fn list_probes(&self) -> Vec<DebugProbeInfo>;
+
+    /// Open the probe matching the selector.
+    ///
+    /// If the selector doesn't match any probe handled by this driver,
+    /// [`ProbeCreationError::NotFound`] has to be returned, so that the
+    /// remaining drivers are tried.
+    fn open(&self, selector: &DebugProbeSelector) -> Result<Box<dyn DebugProbe>, DebugProbeError>;
+}
+
+/// Drivers registered using [`register_probe_driver`].
+static PROBE_DRIVERS: Lazy<RwLock<Vec<Arc<dyn ProbeDriver>>>> = Lazy::new(Default::default);
+
+/// Register a driver for additional debug probes.
+///
+/// Registered drivers are tried before the drivers built into probe-rs when opening a probe,
+/// so they can also be used to handle probes which would otherwise be opened by a built-in driver.
+pub fn register_probe_driver(driver: impl ProbeDriver + 'static) {
+    log::debug!("Registering probe driver {:?}", driver);
+
+    PROBE_DRIVERS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(Arc::new(driver));
+}
+
+/// Get all registered probe drivers.
+///
+/// The list is copied, so that the lock isn't held while calling into the drivers.
+fn registered_probe_drivers() -> Vec<Arc<dyn ProbeDriver>> {
+    PROBE_DRIVERS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Check if an error was caused by the probe being disconnected.
+///
+/// The error and all its sources are checked, so this can be used with
+/// the errors returned by probe-rs, or errors which wrap them.
+///
+/// A probe which was disconnected can be opened again using [`Probe::reopen`],
+/// or [`Session::reconnect`] can be used to restore a complete session.
+pub fn is_disconnect_error(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut current = Some(error);
+
+    while let Some(error) = current {
+        if let Some(rusb::Error::NoDevice) = error.downcast_ref::<rusb::Error>() {
+            return true;
+        }
+
+        if let Some(error) = error.downcast_ref::<std::io::Error>() {
+            // Errors reported by network based probes when the connection is lost.
+            if matches!(
+                error.kind(),
+                std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::NotConnected
+                    | std::io::ErrorKind::UnexpectedEof
+            ) {
+                return true;
+            }
+        }
+
+        current = error.source();
+    }
+
+    false
+}
+
 /// The Probe struct is a generic wrapper over the different
 /// probes supported.
 ///
@@ -220,6 +332,8 @@ pub enum ProbeCreationError {
 pub struct Probe {
     inner: Box<dyn DebugProbe>,
     attached: bool,
+    /// The selector which was used to open the probe, if any.
+    selector: Option<DebugProbeSelector>,
 }
 
 impl Probe {
@@ -228,6 +342,7 @@ impl Probe {
         Self {
             inner: Box::new(probe),
             attached: false,
+            selector: None,
         }
     }
 
@@ -235,6 +350,7 @@ impl Probe {
         Self {
             inner: probe,
             attached: true,
+            selector: None,
         }
     }
 
@@ -243,6 +359,7 @@ impl Probe {
         Probe {
             inner: probe,
             attached: false,
+            selector: None,
         }
     }
 
@@ -261,6 +378,14 @@ impl Probe {
 
         list.extend(list_espjtag_devices());
 
+        list.extend(blackmagic::list_blackmagic_devices());
+
+        list.extend(wlink::list_wlink_devices());
+
+        for driver in registered_probe_drivers() {
+            list.extend(driver.list_probes());
+        }
+
         list
     }
 
@@ -268,6 +393,57 @@ impl Probe {
     /// [`Probe::list_all()`] function to get the information
     /// about all probes available.
     pub fn open(selector: impl Into<DebugProbeSelector> + Clone) -> Result<Self, DebugProbeError> {
+        let mut probe = Self::open_driver(selector.clone())?;
+        probe.selector = Some(selector.into());
+
+        Ok(probe)
+    }
+
+    /// Open the probe matching `selector` again, after it has been disconnected.
+    ///
+    /// Probes can disappear from the USB bus for a short while, for example when they are
+    /// powered by the target and the target is power cycled, or when they re-enumerate
+    /// after a firmware reset. This waits up to `timeout` for a matching probe to show up again.
+    ///
+    /// To make sure the same probe is opened again, the selector should contain a serial number.
+    pub fn reopen(
+        selector: &DebugProbeSelector,
+        timeout: Duration,
+    ) -> Result<Self, DebugProbeError> {
+        let start = Instant::now();
+
+        loop {
+            match Probe::open(selector.clone()) {
+                Ok(probe) => return Ok(probe),
+                // Opening the probe can also fail for a short while after it has been
+                // enumerated, e.g. until udev has updated the permissions, so all
+                // errors are retried.
+                Err(e) if start.elapsed() < timeout => {
+                    log::debug!("Probe {} not available yet: {}", selector, e);
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn open_driver(
+        selector: impl Into<DebugProbeSelector> + Clone,
+    ) -> Result<Self, DebugProbeError> {
+        let drivers = registered_probe_drivers();
+
+        if !drivers.is_empty() {
+            let selector = selector.clone().into();
+
+            for driver in drivers {
+                match driver.open(&selector) {
+                    Ok(link) => return Ok(Probe::from_specific_probe(link)),
+                    Err(DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::NotFound)) => {}
+                    Err(e) => return Err(e),
+                };
+            }
+        }
+
         match cmsisdap::CmsisDap::new_from_selector(selector.clone()) {
             Ok(link) => return Ok(Probe::from_specific_probe(link)),
             Err(DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::NotFound)) => {}
@@ -289,7 +465,17 @@ impl Probe {
             Err(DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::NotFound)) => {}
             Err(e) => return Err(e),
         };
-        match espusbjtag::EspUsbJtag::new_from_selector(selector) {
+        match espusbjtag::EspUsbJtag::new_from_selector(selector.clone()) {
+            Ok(link) => return Ok(Probe::from_specific_probe(link)),
+            Err(DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::NotFound)) => {}
+            Err(e) => return Err(e),
+        };
+        match blackmagic::BlackMagicProbe::new_from_selector(selector.clone()) {
+            Ok(link) => return Ok(Probe::from_specific_probe(link)),
+            Err(DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::NotFound)) => {}
+            Err(e) => return Err(e),
+        };
+        match wlink::WchLink::new_from_selector(selector) {
             Ok(link) => return Ok(Probe::from_specific_probe(link)),
             Err(DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::NotFound)) => {}
             Err(e) => return Err(e),
@@ -300,6 +486,13 @@ impl Probe {
         ))
     }
 
+    /// Get the selector which was used to open the probe.
+    ///
+    /// This is `None` if the probe was not opened using [`Probe::open`].
+    pub fn selector(&self) -> Option<&DebugProbeSelector> {
+        self.selector.as_ref()
+    }
+
     /// Get the human readable name for the probe.
     pub fn get_name(&self) -> String {
         self.inner.get_name().to_string()
@@ -380,6 +573,39 @@ impl Probe {
         }
     }
 
+    /// Set the JTAG scan chain, instead of detecting it when attaching.
+    ///
+    /// This is required if the TAPs in the chain don't capture the standard `0b01` value into
+    /// their IR registers, which is used to detect the IR lengths.
+    pub fn set_scan_chain(
+        &mut self,
+        scan_chain: Vec<ScanChainElement>,
+    ) -> Result<(), DebugProbeError> {
+        if !self.attached {
+            self.inner.set_scan_chain(scan_chain)
+        } else {
+            Err(DebugProbeError::Attached)
+        }
+    }
+
+    /// Select the TAP in the JTAG scan chain which is used as the debug target.
+    ///
+    /// The TAPs are numbered starting at the TAP closest to TDO of the probe. All other
+    /// TAPs are put into BYPASS. If no TAP is selected, the only TAP in the chain,
+    /// or the first TAP with a known IDCODE is used.
+    pub fn select_jtag_tap(&mut self, index: usize) -> Result<(), DebugProbeError> {
+        if !self.attached {
+            self.inner.select_jtag_tap(index)
+        } else {
+            Err(DebugProbeError::Attached)
+        }
+    }
+
+    /// The JTAG scan chain, as detected when attaching, or as set using [`Probe::set_scan_chain`].
+    pub fn scan_chain(&self) -> Result<&[ScanChainElement], DebugProbeError> {
+        self.inner.scan_chain()
+    }
+
     /// Leave debug mode
     pub fn detach(&mut self) -> Result<(), DebugProbeError> {
         self.attached = false;
@@ -428,6 +654,11 @@ impl Probe {
         self.inner.speed_khz()
     }
 
+    /// Get the currently selected transport protocol, if the probe reports it.
+    pub fn protocol(&self) -> Option<WireProtocol> {
+        self.inner.active_protocol()
+    }
+
     /// Check if the probe has an interface to
     /// debug ARM chips.
     pub fn has_arm_interface(&self) -> bool {
@@ -473,6 +704,29 @@ impl Probe {
         }
     }
 
+    /// Check if the probe has an interface to
+    /// debug Xtensa chips.
+    pub fn has_xtensa_interface(&self) -> bool {
+        self.inner.has_xtensa_interface()
+    }
+
+    /// Try to get a [`XtensaCommunicationInterface`], which can
+    /// can be used to communicate with chips using the Xtensa
+    /// architecture.
+    ///
+    /// If an error occurs while trying to connect, the probe is returned.
+    pub fn try_into_xtensa_interface(
+        self,
+    ) -> Result<XtensaCommunicationInterface, (Self, DebugProbeError)> {
+        if !self.attached {
+            Err((self, DebugProbeError::NotAttached))
+        } else {
+            self.inner
+                .try_get_xtensa_interface()
+                .map_err(|(probe, err)| (Probe::from_attached_probe(probe), err))
+        }
+    }
+
     /// Gets a SWO interface from the debug probe.
     ///
     /// This does not work on all probes.
@@ -487,6 +741,16 @@ impl Probe {
         self.inner.get_swo_interface_mut()
     }
 
+    /// Open the UART bridge of the debug probe, to access the console of the target.
+    ///
+    /// The returned port can be used independently of the probe, so it can
+    /// still be used after the probe was used to attach to the target.
+    ///
+    /// This does not work on all probes.
+    pub fn open_uart(&mut self, baud_rate: u32) -> Result<Box<dyn ProbeUart>, DebugProbeError> {
+        self.inner.open_uart(baud_rate)
+    }
+
     /// Gets a DAP interface from the debug probe.
     ///
     /// This does not work on all probes.
@@ -494,6 +758,28 @@ impl Probe {
         self.inner.try_as_dap_probe()
     }
 
+    /// Send a sequence of up to 64 bits on SWDIO/TMS, starting with the least significant bit.
+    ///
+    /// This can be used to send e.g. line resets, the dormant state wake up sequence or
+    /// vendor specific sequences before attaching to a target. Sequences which are always
+    /// required for a chip can also be given in the target description instead.
+    ///
+    /// This does not work on all probes.
+    pub fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        match self.inner.try_as_dap_probe() {
+            Some(probe) => probe.swj_sequence(bit_len, bits),
+            None => Err(DebugProbeError::CommandNotSupportedByProbe("swj_sequence")),
+        }
+    }
+
+    /// Get the capabilities of the debug probe.
+    ///
+    /// This can be used to check which features a probe supports
+    /// before trying to use them.
+    pub fn capabilities(&self) -> ProbeCapabilities {
+        self.inner.capabilities()
+    }
+
     /// Try reading the target voltage of via the connected volgate pin.
     ///
     /// This does not work on all probes.
@@ -568,6 +854,28 @@ pub trait DebugProbe: Send + fmt::Debug {
         false
     }
 
+    /// Set the JTAG scan chain, instead of detecting it when attaching.
+    fn set_scan_chain(
+        &mut self,
+        _scan_chain: Vec<ScanChainElement>,
+    ) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe(
+            "set_scan_chain",
+        ))
+    }
+
+    /// Select the TAP in the JTAG scan chain which is used as the debug target.
+    fn select_jtag_tap(&mut self, _index: usize) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe(
+            "select_jtag_tap",
+        ))
+    }
+
+    /// The JTAG scan chain, as detected when attaching, or as set using [`DebugProbe::set_scan_chain`].
+    fn scan_chain(&self) -> Result<&[ScanChainElement], DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe("scan_chain"))
+    }
+
     /// Get the dedicated interface to debug ARM chips. To check that the
     /// probe actually supports this, call [DebugProbe::has_arm_interface] first.
     fn try_get_arm_interface<'probe>(
@@ -596,6 +904,22 @@ pub trait DebugProbe: Send + fmt::Debug {
         false
     }
 
+    /// Get the dedicated interface to debug Xtensa chips. Ensure that the
+    /// probe actually supports this by calling [DebugProbe::has_xtensa_interface] first.
+    fn try_get_xtensa_interface(
+        self: Box<Self>,
+    ) -> Result<XtensaCommunicationInterface, (Box<dyn DebugProbe>, DebugProbeError)> {
+        Err((
+            self.into_probe(),
+            DebugProbeError::InterfaceNotAvailable("Xtensa"),
+        ))
+    }
+
+    /// Check if the probe offers an interface to debug Xtensa chips.
+    fn has_xtensa_interface(&self) -> bool {
+        false
+    }
+
     /// Get a SWO interface from the debug probe.
     ///
     /// This is not available on all debug probes.
@@ -620,11 +944,84 @@ pub trait DebugProbe: Send + fmt::Debug {
         None
     }
 
+    /// Open the UART bridge of the probe, if it has one.
+    fn open_uart(&mut self, _baud_rate: u32) -> Result<Box<dyn ProbeUart>, DebugProbeError> {
+        Err(DebugProbeError::InterfaceNotAvailable("UART"))
+    }
+
     /// Reads the target voltage in Volts, if possible. Returns `Ok(None)`
     /// if the probe doesn’t support reading the target voltage.
     fn get_target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
         Ok(None)
     }
+
+    /// Get the capabilities of the probe.
+    ///
+    /// The default implementation only reports the currently active protocol
+    /// and whether SWO is available. Drivers should override this if they
+    /// know more about the probe.
+    fn capabilities(&self) -> ProbeCapabilities {
+        let mut capabilities = ProbeCapabilities::default();
+
+        capabilities.protocols.extend(self.active_protocol());
+
+        if self.get_swo_interface().is_some() {
+            capabilities.swo_modes.push(SwoMode::Uart);
+        }
+
+        capabilities
+    }
+}
+
+/// The features supported by a debug probe, as reported by [`Probe::capabilities`].
+///
+/// Fields which the probe can't report are empty, `None` or `false`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ProbeCapabilities {
+    /// The highest clock speed in kHz the probe supports for the debug protocol.
+    pub max_speed_khz: Option<u32>,
+    /// The wire protocols supported by the probe.
+    pub protocols: Vec<WireProtocol>,
+    /// The SWO modes the probe can capture.
+    pub swo_modes: Vec<SwoMode>,
+    /// The number of hardware breakpoints the probe can manage on its own,
+    /// in addition to the ones provided by the target.
+    pub hardware_breakpoints: Option<u32>,
+    /// The probe can execute a sequence of commands atomically,
+    /// without being interrupted by other commands.
+    pub atomic_commands: bool,
+    /// The probe can switch the power supply of the target.
+    pub target_power: bool,
+    /// The probe can measure the voltage of the target.
+    pub target_voltage: bool,
+    /// The probe has a UART bridge, which can be opened using [`Probe::open_uart`].
+    pub uart: bool,
+}
+
+impl ProbeCapabilities {
+    /// Check if the probe supports the given wire protocol.
+    pub fn supports_protocol(&self, protocol: WireProtocol) -> bool {
+        self.protocols.contains(&protocol)
+    }
+
+    /// Check if the probe can capture SWO data.
+    pub fn supports_swo(&self) -> bool {
+        !self.swo_modes.is_empty()
+    }
+}
+
+/// A UART bridge of a debug probe, which can be used to access the console of the target.
+///
+/// Data is read and written using the [`std::io::Read`] and [`std::io::Write`] traits.
+/// Reads which don't receive any data until the timeout return an
+/// [`std::io::ErrorKind::TimedOut`] error.
+pub trait ProbeUart: std::io::Read + std::io::Write + Send + fmt::Debug {
+    /// Change the baud rate of the UART.
+    fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), DebugProbeError>;
+
+    /// Set the timeout for reads and writes.
+    fn set_timeout(&mut self, timeout: Duration);
 }
 
 /// Denotes the type of a given [`DebugProbe`].
@@ -640,6 +1037,14 @@ pub enum DebugProbeType {
     JLink,
     /// Built in RISC-V ESP JTAG debug probe
     EspJtag,
+    /// Black Magic Probe
+    BlackMagicProbe,
+    /// WCH-Link in RISC-V mode
+    WchLink,
+    /// A probe handled by a [`ProbeDriver`] registered using [`register_probe_driver`].
+    ///
+    /// Contains the name of the probe type.
+    Custom(String),
 }
 
 /// Gathers some information about a debug probe which was found during a scan.
@@ -1,3 +1,8 @@
+//! Implementation of the SWD and JTAG protocols for probes with raw access to the SWD and JTAG lines.
+//!
+//! Probes which can shift arbitrary bit sequences, like the J-Link, implement the
+//! [`RawProtocolIo`] trait, and get an implementation of [`RawDapAccess`] for free.
+
 use std::{iter, time::Duration};
 
 use crate::{
@@ -5,11 +10,16 @@ use crate::{
         dp::{Abort, Ctrl, RdBuff, DPIDR},
         DapError, DpAddress, Pins, PortType, RawDapAccess, Register,
     },
-    probe::JTAGAccess,
-    DebugProbe, DebugProbeError,
+    probe::{jlink::bits_to_byte, JTAGAccess},
+    DebugProbe, DebugProbeError, WireProtocol,
 };
 
-use super::{bits_to_byte, JLink};
+/// Sequence to switch a target from the dormant state to SWD,
+/// followed by a line reset.
+const DORMANT_TO_SWD_AND_LINE_RESET: [u8; 28] = [
+    0xff, 0x92, 0xf3, 0x09, 0x62, 0x95, 0x2d, 0x85, 0x86, 0xe9, 0xaf, 0xdd, 0xe3, 0xa2, 0x0e, 0xbc,
+    0x19, 0xa0, 0xf1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00,
+];
 
 #[derive(Debug)]
 pub struct SwdSettings {
@@ -105,7 +115,7 @@ impl ProbeStatistics {
         self.num_transfers += num_transfers;
     }
 
-    fn report_io(&mut self) {
+    pub(crate) fn report_io(&mut self) {
         self.num_io_calls += 1;
     }
 
@@ -123,8 +133,6 @@ impl ProbeStatistics {
     }
 }
 
-///! Implementation of the SWD and JTAG protocols for the JLink probe.
-
 // Constant to be written to ABORT
 const JTAG_ABORT_VALUE: u64 = 0x8;
 
@@ -493,7 +501,7 @@ fn perform_transfers<P: DebugProbe + RawProtocolIo + JTAGAccess>(
         crate::WireProtocol::Swd => {
             perform_swd_transfers(probe, &mut final_transfers[..])?;
         }
-        crate::WireProtocol::Jtag => {
+        crate::WireProtocol::Jtag | crate::WireProtocol::Cjtag => {
             perform_jtag_transfers(probe, &mut final_transfers[..])?;
         }
     }
@@ -655,7 +663,7 @@ enum TransferType {
 }
 
 fn build_swd_transfer(port: PortType, direction: TransferType, address: u8) -> IoSequence {
-    // JLink operates on raw SWD bit sequences.
+    // The probe operates on raw SWD bit sequences.
     // So we need to manually assemble the read and write bitsequences.
     // The following code with the comments hopefully explains well enough how it works.
     // `true` means `1` and `false` means `0` for the SWDIO sequence.
@@ -757,7 +765,8 @@ fn parse_swd_response(response: &[bool], direction: TransferDirection) -> Result
     // The device changes the value of SWDIO with the rising edge of the clock.
     //
     // It appears that the JLink probe samples this line with the falling edge of
-    // the clock. Therefore, the whole sequence seems to be leading by one bit,
+    // the clock. Other probes implementing `RawProtocolIo` have to sample
+    // the line in the same way. Therefore, the whole sequence seems to be leading by one bit,
     // which is why we don't discard the turnaround bit. It actually contains the
     // first ack bit.
 
@@ -832,49 +841,16 @@ pub trait RawProtocolIo {
     /// Try to perform a line reset, followed by a read of the DPIDR register.
     ///
     /// Returns Ok if the read of the DPIDR register was succesful, and Err
-    /// otherwise. In case of JLink Errors, the actual error is returned.
+    /// otherwise. In case of probe errors, the actual error is returned.
     ///
     /// If the first line reset fails, it is tried once again, as the target
     /// might be in the middle of a transfer the first time we try the reset.
     ///
     /// See section B4.3.3 in the ADIv5 Specification.
-    fn line_reset(&mut self) -> Result<(), DebugProbeError>;
-}
-
-impl RawProtocolIo for JLink {
-    fn jtag_io<M, I>(&mut self, tms: M, tdi: I) -> Result<Vec<bool>, DebugProbeError>
+    fn line_reset(&mut self) -> Result<(), DebugProbeError>
     where
-        M: IntoIterator<Item = bool>,
-        I: IntoIterator<Item = bool>,
+        Self: DebugProbe + JTAGAccess + Sized + 'static,
     {
-        if self.protocol.unwrap() == crate::WireProtocol::Swd {
-            panic!("Logic error, requested jtag_io when in SWD mode");
-        }
-
-        self.probe_statistics.report_io();
-
-        let iter = self.handle.jtag_io(tms, tdi)?;
-
-        Ok(iter.collect())
-    }
-
-    fn swd_io<D, S>(&mut self, dir: D, swdio: S) -> Result<Vec<bool>, DebugProbeError>
-    where
-        D: IntoIterator<Item = bool>,
-        S: IntoIterator<Item = bool>,
-    {
-        if self.protocol.unwrap() == crate::WireProtocol::Jtag {
-            panic!("Logic error, requested swd_io when in JTAG mode");
-        }
-
-        self.probe_statistics.report_io();
-
-        let iter = self.handle.swd_io(dir, swdio)?;
-
-        Ok(iter.collect())
-    }
-
-    fn line_reset(&mut self) -> Result<(), DebugProbeError> {
         log::debug!("Performing line reset!");
 
         const NUM_RESET_BITS: u8 = 50;
@@ -912,23 +888,61 @@ impl RawProtocolIo for JLink {
         // No acknowledge from the target, even if after line reset
         result
     }
-
-    fn swd_settings(&self) -> &SwdSettings {
-        &self.swd_settings
-    }
-
-    fn probe_statistics(&mut self) -> &mut ProbeStatistics {
-        &mut self.probe_statistics
-    }
 }
 
 impl<Probe: DebugProbe + RawProtocolIo + JTAGAccess + 'static> RawDapAccess for Probe {
     fn select_dp(&mut self, dp: DpAddress) -> Result<(), DebugProbeError> {
         match dp {
             DpAddress::Default => Ok(()), // nop
-            DpAddress::Multidrop(_) => Err(DebugProbeError::ProbeSpecific(
-                anyhow::anyhow!("JLink doesn't support multidrop SWD yet").into(),
-            )),
+            DpAddress::Multidrop(targetsel) => {
+                if self.active_protocol() != Some(WireProtocol::Swd) {
+                    return Err(DebugProbeError::NotImplemented("Multidrop over JTAG"));
+                }
+
+                let idle_cycles =
+                    std::cmp::max(1, self.swd_settings().num_idle_cycles_between_writes);
+
+                for _ in 0..5 {
+                    // dormant-to-swd + line reset
+                    for chunk in DORMANT_TO_SWD_AND_LINE_RESET.chunks(8) {
+                        let mut bits = [0u8; 8];
+                        bits[..chunk.len()].copy_from_slice(chunk);
+
+                        self.swj_sequence((chunk.len() * 8) as u8, u64::from_le_bytes(bits))?;
+                    }
+
+                    // TARGETSEL write.
+                    // The TARGETSEL write is not ACKed by design, so the whole
+                    // transfer is sent as a sequence, with the line driven by the probe.
+                    let parity = targetsel.count_ones() % 2;
+                    let sequence = (parity as u64) << 45 | (targetsel as u64) << 13 | 0x1f99;
+                    self.swj_sequence(48, sequence)?;
+
+                    // "A write to the TARGETSEL register must always be followed by a read of the DPIDR register or a line reset. If the
+                    // response to the DPIDR read is incorrect, or there is no response, the host must start the sequence again."
+                    //
+                    // `raw_read_register` can't be used here, because it performs a line reset on errors.
+                    let mut transfers = [DapTransfer::read(PortType::DebugPort, DPIDR::ADDRESS)];
+
+                    perform_transfers(self, &mut transfers, idle_cycles)?;
+
+                    match &transfers[0].status {
+                        TransferStatus::Ok => {
+                            log::debug!("DPIDR read {:08x}", transfers[0].value);
+                            return Ok(());
+                        }
+                        TransferStatus::Pending => {
+                            log::debug!("Unexpected pending status after TARGETSEL, retrying.");
+                        }
+                        TransferStatus::Failed(e) => {
+                            log::debug!("DPIDR read failed, retrying. Error: {:?}", e);
+                        }
+                    }
+                }
+
+                log::warn!("Giving up on TARGETSEL, too many retries.");
+                Err(DapError::NoAcknowledge.into())
+            }
         }
     }
 
@@ -1348,7 +1362,7 @@ impl<Probe: DebugProbe + RawProtocolIo + JTAGAccess + 'static> RawDapAccess for
         self.set_ir_len(4);
 
         match protocol {
-            crate::WireProtocol::Jtag => {
+            crate::WireProtocol::Jtag | crate::WireProtocol::Cjtag => {
                 self.jtag_io(
                     io_sequence.io_bits().to_owned(),
                     iter::repeat(false).take(bit_len.into()),
@@ -1372,15 +1386,15 @@ mod test {
     use std::iter;
 
     use crate::{
-        architecture::arm::{PortType, RawDapAccess},
+        architecture::arm::{DpAddress, PortType, RawDapAccess},
         probe::JTAGAccess,
         DebugProbe, DebugProbeError,
     };
 
     use super::{
-        parse_jtag_response, ProbeStatistics, RawProtocolIo, SwdSettings, JTAG_ABORT_IR_VALUE,
-        JTAG_ACCESS_PORT_IR_VALUE, JTAG_DEBUG_PORT_IR_VALUE, JTAG_DR_BIT_LENGTH, JTAG_STATUS_OK,
-        JTAG_STATUS_WAIT,
+        parse_jtag_response, ProbeStatistics, RawProtocolIo, SwdSettings,
+        DORMANT_TO_SWD_AND_LINE_RESET, JTAG_ABORT_IR_VALUE, JTAG_ACCESS_PORT_IR_VALUE,
+        JTAG_DEBUG_PORT_IR_VALUE, JTAG_DR_BIT_LENGTH, JTAG_STATUS_OK, JTAG_STATUS_WAIT,
     };
 
     use bitvec::prelude::*;
@@ -1406,6 +1420,8 @@ mod test {
     struct MockJaylink {
         direction_input: Option<Vec<bool>>,
         io_input: Option<Vec<bool>>,
+        /// All SWDIO sequences sent by the probe.
+        io_sequences: Vec<Vec<bool>>,
         transfer_responses: Vec<Vec<bool>>,
         jtag_transactions: Vec<ExpectedJtagTransaction>,
 
@@ -1423,6 +1439,7 @@ mod test {
             Self {
                 direction_input: None,
                 io_input: None,
+                io_sequences: vec![],
                 transfer_responses: vec![vec![]],
                 jtag_transactions: vec![],
 
@@ -1577,9 +1594,7 @@ mod test {
     }
 
     impl JTAGAccess for MockJaylink {
-        fn set_ir_len(&mut self, _len: u32) {
-            todo!()
-        }
+        fn set_ir_len(&mut self, _len: u32) {}
 
         fn read_register(&mut self, _address: u32, _len: u32) -> Result<Vec<u8>, DebugProbeError> {
             todo!()
@@ -1653,6 +1668,9 @@ mod test {
                 self.io_input.as_ref().unwrap().len()
             );
 
+            self.io_sequences
+                .push(self.io_input.as_ref().unwrap().clone());
+
             let transfer_response = self.transfer_responses.remove(0);
 
             assert_eq!(
@@ -1743,6 +1761,43 @@ mod test {
         }
     }
 
+    #[test]
+    fn select_multidrop_dp() {
+        let targetsel = 0x0100_2927;
+
+        let mut mock = MockJaylink::new();
+
+        // The dormant-to-SWD sequence and line reset is sent in chunks of 64 bits,
+        // followed by the TARGETSEL write, which isn't acknowledged.
+        for len in [64, 64, 64, 32, 48] {
+            mock.add_idle_cycles(len);
+            mock.add_transfer();
+        }
+
+        mock.add_read_response(DapAcknowledge::Ok, 0x0bc1_2477);
+        mock.add_idle_cycles(mock.swd_settings.idle_cycles_after_transfer);
+
+        mock.select_dp(DpAddress::Multidrop(targetsel)).unwrap();
+
+        let expected_sequence = DORMANT_TO_SWD_AND_LINE_RESET
+            .iter()
+            .flat_map(|byte| (0..8).map(move |bit| (byte >> bit) & 1 == 1))
+            .collect::<Vec<_>>();
+        assert_eq!(mock.io_sequences[..4].concat(), expected_sequence);
+
+        let targetsel_write = mock.io_sequences[4]
+            .iter()
+            .rev()
+            .fold(0u64, |value, &bit| value << 1 | u64::from(bit));
+        let parity = u64::from(targetsel.count_ones() % 2);
+        assert_eq!(
+            targetsel_write,
+            parity << 45 | u64::from(targetsel) << 13 | 0x1f99
+        );
+
+        assert_eq!(mock.performed_transfer_count, 6);
+    }
+
     #[test]
     fn read_register() {
         let read_value = 12;
@@ -1983,7 +2038,7 @@ mod test {
     mod transfer_handling {
         use crate::{
             architecture::arm::PortType,
-            probe::jlink::arm::{perform_transfers, DapTransfer, TransferStatus},
+            probe::arm_debug_interface::{perform_transfers, DapTransfer, TransferStatus},
         };
 
         use super::{DapAcknowledge, MockJaylink};
@@ -0,0 +1,790 @@
+//! Driver for the [Black Magic Probe](https://black-magic.org), using its remote protocol.
+//!
+//! The Black Magic Probe usually acts as a GDB server itself. It also offers a
+//! low-level remote protocol on the same serial port, which gives direct access
+//! to the SWD and JTAG lines. This driver uses the remote protocol, so that the
+//! debugging logic of probe-rs can be used instead of the one in the probe firmware.
+
+mod protocol;
+
+use std::time::Duration;
+
+use crate::{
+    architecture::{
+        arm::{
+            communication_interface::{DapProbe, UninitializedArmProbe},
+            dp::{Abort, Ctrl, RdBuff, DPIDR},
+            ArmCommunicationInterface, DapError, DpAddress, Pins, PortType, RawDapAccess, Register,
+            SwoAccess,
+        },
+        riscv::communication_interface::RiscvCommunicationInterface,
+    },
+    probe::JTAGAccess,
+    DebugProbe, DebugProbeError, DebugProbeSelector, ProbeCapabilities, WireProtocol,
+};
+
+use self::protocol::{parse_voltage, BlackMagicProbeError, ProtocolHandler};
+
+pub use protocol::list_blackmagic_devices;
+
+/// How often a transfer is retried when a WAIT response is received.
+const NUM_RETRIES_AFTER_WAIT: usize = 1000;
+
+/// Number of idle cycles inserted after a SWD write,
+/// to make sure that the write is clocked through.
+const IDLE_CYCLES_AFTER_WRITE: usize = 8;
+
+// IR values for JTAG registers
+const JTAG_ABORT_IR_VALUE: u32 = 0x8;
+const JTAG_DEBUG_PORT_IR_VALUE: u32 = 0xA;
+const JTAG_ACCESS_PORT_IR_VALUE: u32 = 0xB;
+
+// ARM JTAG-DP IR length
+const JTAG_ARM_IR_LEN: u32 = 4;
+
+// ARM DR accesses are always 35 bits wide
+const JTAG_DR_BIT_LENGTH: usize = 35;
+
+const JTAG_STATUS_WAIT: u32 = 0x1;
+const JTAG_STATUS_OK: u32 = 0x2;
+
+const SWD_ACK_OK: u32 = 0b001;
+const SWD_ACK_WAIT: u32 = 0b010;
+const SWD_ACK_FAULT: u32 = 0b100;
+const SWD_ACK_NONE: u32 = 0b111;
+
+/// Sequence to switch a target from dormant state to SWD, followed by a line reset.
+const DORMANT_TO_SWD_AND_LINE_RESET: [u8; 28] = [
+    0xff, 0x92, 0xf3, 0x09, 0x62, 0x95, 0x2d, 0x85, 0x86, 0xe9, 0xaf, 0xdd, 0xe3, 0xa2, 0x0e, 0xbc,
+    0x19, 0xa0, 0xf1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00,
+];
+
+#[derive(Debug)]
+pub(crate) struct BlackMagicProbe {
+    protocol: ProtocolHandler,
+
+    wire_protocol: Option<WireProtocol>,
+
+    speed_khz: u32,
+
+    /// Idle cycles necessary between consecutive
+    /// accesses to the DMI register
+    jtag_idle_cycles: u8,
+
+    ir_len: u32,
+
+    current_ir_reg: Option<u32>,
+}
+
+impl BlackMagicProbe {
+    /// Clock out a sequence of bits on SWDIO, in chunks of at most 32 bits.
+    fn swd_seq_out_bytes(
+        &mut self,
+        data: &[u8],
+        mut bit_len: usize,
+    ) -> Result<(), DebugProbeError> {
+        for chunk in data.chunks(4) {
+            if bit_len == 0 {
+                break;
+            }
+
+            let mut bytes = [0u8; 4];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+
+            let chunk_bits = bit_len.min(chunk.len() * 8);
+
+            self.protocol
+                .swd_seq_out(u32::from_le_bytes(bytes), chunk_bits)?;
+
+            bit_len -= chunk_bits;
+        }
+
+        Ok(())
+    }
+
+    /// Perform a single SWD transfer, without any retries.
+    fn swd_transfer(
+        &mut self,
+        port: PortType,
+        address: u8,
+        value: Option<u32>,
+    ) -> Result<Result<u32, DapError>, DebugProbeError> {
+        let request = build_swd_request(port, value.is_none(), address);
+
+        self.protocol.swd_seq_out(request as u32, 8)?;
+
+        // The probe inserts the turnaround cycles on its own.
+        let ack = self.protocol.swd_seq_in(3)?;
+
+        match ack {
+            SWD_ACK_OK => (),
+            SWD_ACK_WAIT => return Ok(Err(DapError::WaitResponse)),
+            SWD_ACK_FAULT => return Ok(Err(DapError::FaultResponse)),
+            SWD_ACK_NONE => return Ok(Err(DapError::NoAcknowledge)),
+            _ => {
+                log::debug!("Invalid SWD ACK: {:03b}", ack);
+                return Ok(Err(DapError::SwdProtocol));
+            }
+        }
+
+        match value {
+            None => {
+                let (value, parity_ok) = self.protocol.swd_seq_in_parity(32)?;
+
+                if parity_ok {
+                    Ok(Ok(value))
+                } else {
+                    Ok(Err(DapError::IncorrectParity))
+                }
+            }
+            Some(value) => {
+                self.protocol.swd_seq_out_parity(value, 32)?;
+                self.protocol.swd_seq_out(0, IDLE_CYCLES_AFTER_WRITE)?;
+
+                Ok(Ok(0))
+            }
+        }
+    }
+
+    /// Perform a SWD transfer, retrying it when the target responds with WAIT.
+    fn swd_transfer_with_retry(
+        &mut self,
+        port: PortType,
+        address: u8,
+        value: Option<u32>,
+    ) -> Result<u32, DebugProbeError> {
+        for retry in 0..NUM_RETRIES_AFTER_WAIT {
+            match self.swd_transfer(port, address, value)? {
+                Ok(value) => return Ok(value),
+                Err(DapError::WaitResponse) => {
+                    log::debug!(
+                        "DAP WAIT, retries remaining {}.",
+                        NUM_RETRIES_AFTER_WAIT - retry
+                    );
+                }
+                Err(DapError::FaultResponse) => {
+                    log::debug!("DAP FAULT");
+
+                    self.swd_clear_sticky_errors()?;
+
+                    return Err(DapError::FaultResponse.into());
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        log::error!("DAP transfer timeout.");
+        Err(DebugProbeError::Timeout)
+    }
+
+    /// Read the CTRL/STAT register after a FAULT response, and clear the sticky error flags.
+    fn swd_clear_sticky_errors(&mut self) -> Result<(), DebugProbeError> {
+        let ctrl = match self.swd_transfer(PortType::DebugPort, Ctrl::ADDRESS, None)? {
+            Ok(value) => Ctrl::from(value),
+            Err(e) => return Err(e.into()),
+        };
+
+        log::debug!("Ctrl/Stat register value is: {:#?}", ctrl);
+
+        if ctrl.sticky_orun() || ctrl.sticky_err() {
+            let mut abort = Abort(0);
+
+            abort.set_orunerrclr(ctrl.sticky_orun());
+            abort.set_stkerrclr(ctrl.sticky_err());
+
+            if let Err(e) =
+                self.swd_transfer(PortType::DebugPort, Abort::ADDRESS, Some(abort.into()))?
+            {
+                return Err(e.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Select the register `value` using the JTAG sequence commands of the remote protocol.
+    ///
+    /// Only a single TAP is supported, so unlike
+    /// [`transfer_register`](crate::probe::scan_chain::transfer_register) no BYPASS bits are added.
+    fn jtag_write_ir(&mut self, value: u32, len: u32) -> Result<(), DebugProbeError> {
+        if self.current_ir_reg == Some(value) {
+            return Ok(());
+        }
+
+        // Run-Test/Idle -> Select-DR-Scan -> Select-IR-Scan -> Capture-IR -> Shift-IR
+        self.protocol.jtag_tms_seq(0b0011, 4)?;
+
+        // The last bit is shifted when leaving Shift-IR, ending in Exit1-IR
+        self.protocol.jtag_tdi_tdo_seq(value, len as usize, true)?;
+
+        // Exit1-IR -> Update-IR -> Run-Test/Idle
+        self.protocol.jtag_tms_seq(0b01, 2)?;
+
+        self.current_ir_reg = Some(value);
+
+        Ok(())
+    }
+
+    /// Shift `data` through the selected register in chunks of 32 bits, the maximum of the
+    /// `jtag_tdi_tdo_seq` command, followed by the configured idle cycles.
+    fn jtag_shift_dr(
+        &mut self,
+        data: &[u8],
+        register_bits: usize,
+    ) -> Result<Vec<u8>, DebugProbeError> {
+        // Run-Test/Idle -> Select-DR-Scan -> Capture-DR -> Shift-DR
+        self.protocol.jtag_tms_seq(0b001, 3)?;
+
+        let mut result = vec![0u8; (register_bits + 7) / 8];
+
+        let mut offset = 0;
+
+        while offset < register_bits {
+            let chunk_bits = (register_bits - offset).min(32);
+            let last_chunk = offset + chunk_bits == register_bits;
+
+            let tdi = get_bits(data, offset, chunk_bits);
+            let tdo = self
+                .protocol
+                .jtag_tdi_tdo_seq(tdi, chunk_bits, last_chunk)?;
+
+            set_bits(&mut result, offset, chunk_bits, tdo);
+
+            offset += chunk_bits;
+        }
+
+        // Exit1-DR -> Update-DR -> Run-Test/Idle
+        self.protocol.jtag_tms_seq(0b01, 2)?;
+
+        // Stay in Run-Test/Idle for the configured amount of cycles.
+        let mut idle_cycles = self.jtag_idle_cycles as usize;
+        while idle_cycles > 0 {
+            let cycles = idle_cycles.min(32);
+            self.protocol.jtag_tms_seq(0, cycles)?;
+            idle_cycles -= cycles;
+        }
+
+        Ok(result)
+    }
+
+    /// Perform a single JTAG-DP scan, returning the acknowledge and the
+    /// captured data of the previous transaction.
+    fn jtag_dp_scan(
+        &mut self,
+        ir: u32,
+        address: u8,
+        value: Option<u32>,
+    ) -> Result<(u32, u32), DebugProbeError> {
+        self.jtag_write_ir(ir, JTAG_ARM_IR_LEN)?;
+
+        let payload = (value.unwrap_or(0) as u64) << 3
+            | ((address as u64 >> 1) & 0b110)
+            | value.is_none() as u64;
+
+        let response = self.jtag_shift_dr(&payload.to_le_bytes(), JTAG_DR_BIT_LENGTH)?;
+
+        let mut bytes = [0u8; 8];
+        bytes[..response.len()].copy_from_slice(&response);
+        let response = u64::from_le_bytes(bytes);
+
+        Ok(((response & 0b111) as u32, (response >> 3) as u32))
+    }
+
+    /// Perform a JTAG-DP transfer, and return the result of it.
+    ///
+    /// Because the result of a transaction is only returned in the next scan,
+    /// a read of the RDBUFF register is appended to every transfer.
+    fn jtag_dp_transfer(
+        &mut self,
+        port: PortType,
+        address: u8,
+        value: Option<u32>,
+    ) -> Result<u32, DebugProbeError> {
+        // Writes to the ABORT register use a separate instruction, and are not acknowledged.
+        if port == PortType::DebugPort && address == Abort::ADDRESS {
+            if let Some(value) = value {
+                self.jtag_dp_scan(JTAG_ABORT_IR_VALUE, 0, Some(value))?;
+                return Ok(0);
+            }
+        }
+
+        let ir = match port {
+            PortType::DebugPort => JTAG_DEBUG_PORT_IR_VALUE,
+            PortType::AccessPort => JTAG_ACCESS_PORT_IR_VALUE,
+        };
+
+        self.jtag_scan_with_retry(ir, address, value)?;
+
+        self.jtag_scan_with_retry(JTAG_DEBUG_PORT_IR_VALUE, RdBuff::ADDRESS, None)
+    }
+
+    fn jtag_scan_with_retry(
+        &mut self,
+        ir: u32,
+        address: u8,
+        value: Option<u32>,
+    ) -> Result<u32, DebugProbeError> {
+        for retry in 0..NUM_RETRIES_AFTER_WAIT {
+            let (ack, data) = self.jtag_dp_scan(ir, address, value)?;
+
+            match ack {
+                JTAG_STATUS_OK => return Ok(data),
+                JTAG_STATUS_WAIT => {
+                    log::debug!(
+                        "DAP WAIT, retries remaining {}.",
+                        NUM_RETRIES_AFTER_WAIT - retry
+                    );
+                }
+                _ => {
+                    log::debug!("Invalid JTAG-DP ACK: {:03b}", ack);
+                    return Err(DapError::NoAcknowledge.into());
+                }
+            }
+        }
+
+        log::error!("DAP transfer timeout.");
+        Err(DebugProbeError::Timeout)
+    }
+
+    /// Perform a transfer using the active protocol, returning the value read.
+    fn perform_transfer(
+        &mut self,
+        port: PortType,
+        address: u8,
+        value: Option<u32>,
+    ) -> Result<u32, DebugProbeError> {
+        match self.wire_protocol {
+            Some(WireProtocol::Swd) => {
+                let result = self.swd_transfer_with_retry(port, address, value)?;
+
+                // AP reads are posted, the value is returned by the next transfer.
+                if port == PortType::AccessPort && value.is_none() {
+                    self.swd_transfer_with_retry(PortType::DebugPort, RdBuff::ADDRESS, None)
+                } else {
+                    Ok(result)
+                }
+            }
+            Some(WireProtocol::Jtag | WireProtocol::Cjtag) => {
+                self.jtag_dp_transfer(port, address, value)
+            }
+            None => Err(DebugProbeError::NotAttached),
+        }
+    }
+}
+
+/// Build the 8 bit request of a SWD transfer.
+fn build_swd_request(port: PortType, read: bool, address: u8) -> u8 {
+    let ap_n_dp = port == PortType::AccessPort;
+    let a2 = (address >> 2) & 0x01 == 1;
+    let a3 = (address >> 3) & 0x01 == 1;
+
+    let parity = (ap_n_dp as u8 + read as u8 + a2 as u8 + a3 as u8) % 2;
+
+    // Start bit, followed by APnDP, RnW, A[2:3], parity, stop bit and park bit
+    1 | (ap_n_dp as u8) << 1
+        | (read as u8) << 2
+        | (a2 as u8) << 3
+        | (a3 as u8) << 4
+        | parity << 5
+        | 1 << 7
+}
+
+/// Extract up to 32 bits from a little endian bit buffer, starting at bit `offset`.
+fn get_bits(data: &[u8], offset: usize, len: usize) -> u32 {
+    let mut value = 0;
+
+    for bit in 0..len {
+        let index = offset + bit;
+
+        let set = data
+            .get(index / 8)
+            .map(|byte| (byte >> (index % 8)) & 1 == 1)
+            .unwrap_or(false);
+
+        value |= (set as u32) << bit;
+    }
+
+    value
+}
+
+/// Store up to 32 bits into a little endian bit buffer, starting at bit `offset`.
+fn set_bits(data: &mut [u8], offset: usize, len: usize, value: u32) {
+    for bit in 0..len {
+        let index = offset + bit;
+
+        if (value >> bit) & 1 == 1 {
+            data[index / 8] |= 1 << (index % 8);
+        }
+    }
+}
+
+impl DebugProbe for BlackMagicProbe {
+    fn new_from_selector(
+        selector: impl Into<DebugProbeSelector>,
+    ) -> Result<Box<Self>, DebugProbeError> {
+        let protocol = ProtocolHandler::new_from_selector(selector)?;
+
+        Ok(Box::new(BlackMagicProbe {
+            protocol,
+            wire_protocol: None,
+            speed_khz: 0,
+            jtag_idle_cycles: 0,
+            ir_len: JTAG_ARM_IR_LEN,
+            current_ir_reg: None,
+        }))
+    }
+
+    fn get_name(&self) -> &str {
+        "Black Magic Probe"
+    }
+
+    fn speed_khz(&self) -> u32 {
+        self.speed_khz
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        match self.protocol.set_frequency(speed_khz * 1000) {
+            Ok(()) => (),
+            // Older firmware versions cannot change the frequency.
+            Err(BlackMagicProbeError::NotSupported) => {
+                return Err(DebugProbeError::UnsupportedSpeed(speed_khz))
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        // The probe selects the closest frequency it supports.
+        self.speed_khz = match self.protocol.frequency() {
+            Ok(frequency) => frequency / 1000,
+            Err(_) => speed_khz,
+        };
+
+        Ok(self.speed_khz)
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        log::debug!("Attaching to Black Magic Probe");
+
+        let protocol = self.wire_protocol.unwrap_or(WireProtocol::Swd);
+
+        match self.get_target_voltage()? {
+            Some(voltage) if voltage < crate::probe::LOW_TARGET_VOLTAGE_WARNING_THRESHOLD => {
+                log::warn!(
+                    "Black Magic Probe: Target voltage is {:2.2} V. Is your target device powered?",
+                    voltage
+                );
+            }
+            Some(voltage) => log::info!("Black Magic Probe: Target voltage: {:2.2} V", voltage),
+            None => (),
+        }
+
+        match protocol {
+            WireProtocol::Swd => {
+                self.protocol.swd_init()?;
+
+                // Attaching is handled in sequence
+            }
+            WireProtocol::Jtag | WireProtocol::Cjtag => {
+                self.protocol.jtag_init()?;
+
+                log::debug!("Resetting JTAG chain");
+                self.protocol.jtag_reset()?;
+
+                // Test-Logic-Reset -> Run-Test/Idle
+                self.protocol.jtag_tms_seq(0, 1)?;
+                self.current_ir_reg = None;
+
+                // After reset, the IDCODE register is selected
+                let idcode = self.jtag_shift_dr(&[0; 4], 32)?;
+
+                log::info!(
+                    "JTAG IDCODE: {:#010x}",
+                    u32::from_le_bytes([idcode[0], idcode[1], idcode[2], idcode[3]])
+                );
+            }
+        }
+
+        self.wire_protocol = Some(protocol);
+
+        log::debug!("Attached succesfully");
+
+        Ok(())
+    }
+
+    fn detach(&mut self) -> Result<(), DebugProbeError> {
+        Ok(())
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::NotImplemented("target_reset"))
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        self.protocol.set_nrst(true)?;
+        Ok(())
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.protocol.set_nrst(false)?;
+        Ok(())
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        if protocol == WireProtocol::Cjtag {
+            return Err(DebugProbeError::UnsupportedProtocol(protocol));
+        }
+
+        self.wire_protocol = Some(protocol);
+        Ok(())
+    }
+
+    fn active_protocol(&self) -> Option<WireProtocol> {
+        self.wire_protocol
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        true
+    }
+
+    fn try_get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
+    {
+        Ok(Box::new(ArmCommunicationInterface::new(self, false)))
+    }
+
+    fn has_riscv_interface(&self) -> bool {
+        self.wire_protocol == Some(WireProtocol::Jtag)
+    }
+
+    fn try_get_riscv_interface(
+        self: Box<Self>,
+    ) -> Result<RiscvCommunicationInterface, (Box<dyn DebugProbe>, DebugProbeError)> {
+        if self.wire_protocol == Some(WireProtocol::Jtag) {
+            match RiscvCommunicationInterface::new(self) {
+                Ok(interface) => Ok(interface),
+                Err((probe, err)) => Err((probe.into_probe(), err)),
+            }
+        } else {
+            Err((
+                RawDapAccess::into_probe(self),
+                DebugProbeError::InterfaceNotAvailable("JTAG"),
+            ))
+        }
+    }
+
+    fn get_swo_interface(&self) -> Option<&dyn SwoAccess> {
+        // SWO is not available over the remote protocol.
+        None
+    }
+
+    fn get_swo_interface_mut(&mut self) -> Option<&mut dyn SwoAccess> {
+        // SWO is not available over the remote protocol.
+        None
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        Some(self)
+    }
+
+    fn get_target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        match self.protocol.target_voltage() {
+            Ok(voltage) => Ok(parse_voltage(&voltage)),
+            Err(BlackMagicProbeError::NotSupported) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn capabilities(&self) -> ProbeCapabilities {
+        let mut capabilities = ProbeCapabilities::default();
+
+        capabilities.protocols = vec![WireProtocol::Swd, WireProtocol::Jtag];
+        capabilities.target_voltage = true;
+
+        capabilities
+    }
+}
+
+impl RawDapAccess for BlackMagicProbe {
+    fn select_dp(&mut self, dp: DpAddress) -> Result<(), DebugProbeError> {
+        match dp {
+            DpAddress::Default => Ok(()), // nop
+            DpAddress::Multidrop(targetsel) => {
+                if self.wire_protocol != Some(WireProtocol::Swd) {
+                    return Err(DebugProbeError::NotImplemented("Multidrop over JTAG"));
+                }
+
+                for _ in 0..5 {
+                    // dormant-to-swd + line reset
+                    self.swd_seq_out_bytes(
+                        &DORMANT_TO_SWD_AND_LINE_RESET,
+                        DORMANT_TO_SWD_AND_LINE_RESET.len() * 8,
+                    )?;
+
+                    // TARGETSEL write.
+                    // The TARGETSEL write is not ACKed by design, so the ACK phase
+                    // is skipped by driving the line instead.
+                    self.protocol.swd_seq_out(0x99, 8)?;
+                    self.protocol.swd_seq_out(0x1f, 5)?;
+                    self.protocol.swd_seq_out_parity(targetsel, 32)?;
+
+                    // "A write to the TARGETSEL register must always be followed by a read of the DPIDR register or a line reset. If the
+                    // response to the DPIDR read is incorrect, or there is no response, the host must start the sequence again."
+                    match self.swd_transfer(PortType::DebugPort, DPIDR::ADDRESS, None)? {
+                        Ok(res) => {
+                            log::debug!("DPIDR read {:08x}", res);
+                            return Ok(());
+                        }
+                        Err(e) => {
+                            log::debug!("DPIDR read failed, retrying. Error: {:?}", e);
+                        }
+                    }
+                }
+
+                log::warn!("Giving up on TARGETSEL, too many retries.");
+                Err(DapError::NoAcknowledge.into())
+            }
+        }
+    }
+
+    fn raw_read_register(&mut self, port: PortType, address: u8) -> Result<u32, DebugProbeError> {
+        self.perform_transfer(port, address, None)
+    }
+
+    fn raw_write_register(
+        &mut self,
+        port: PortType,
+        address: u8,
+        value: u32,
+    ) -> Result<(), DebugProbeError> {
+        self.perform_transfer(port, address, Some(value))
+            .map(|_| ())
+    }
+
+    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        let protocol = self.wire_protocol.ok_or(DebugProbeError::NotAttached)?;
+
+        let mut remaining = bit_len as usize;
+        let mut bits = bits;
+
+        while remaining > 0 {
+            let chunk_bits = remaining.min(32);
+            let chunk = (bits & 0xffff_ffff) as u32;
+
+            match protocol {
+                WireProtocol::Swd => self.protocol.swd_seq_out(chunk, chunk_bits)?,
+                WireProtocol::Jtag | WireProtocol::Cjtag => {
+                    self.protocol.jtag_tms_seq(chunk, chunk_bits)?;
+
+                    // The TAP state is unknown after an arbitrary TMS sequence.
+                    self.current_ir_reg = None;
+                }
+            }
+
+            bits >>= chunk_bits;
+            remaining -= chunk_bits;
+        }
+
+        Ok(())
+    }
+
+    fn swj_pins(
+        &mut self,
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        let mut nreset = Pins(0);
+        nreset.set_nreset(true);
+        let nreset_mask = nreset.0 as u32;
+
+        // Only the reset pin can be controlled over the remote protocol.
+        if pin_select == nreset_mask {
+            if Pins(pin_out as u8).nreset() {
+                self.target_reset_deassert()?;
+            } else {
+                self.target_reset_assert()?;
+            }
+
+            // The probe cannot wait for the pins to settle, so we wait on the host instead.
+            std::thread::sleep(Duration::from_micros(pin_wait as u64));
+
+            // We signal that we cannot read the pin state.
+            Ok(0xFFFF_FFFF)
+        } else {
+            Err(DebugProbeError::CommandNotSupportedByProbe("swj_pins"))
+        }
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+}
+
+impl DapProbe for BlackMagicProbe {}
+
+impl JTAGAccess for BlackMagicProbe {
+    fn set_ir_len(&mut self, len: u32) {
+        self.ir_len = len;
+    }
+
+    fn read_register(&mut self, address: u32, len: u32) -> Result<Vec<u8>, DebugProbeError> {
+        self.jtag_write_ir(address, self.ir_len)?;
+
+        let data = vec![0u8; (len as usize + 7) / 8];
+
+        self.jtag_shift_dr(&data, len as usize)
+    }
+
+    fn write_register(
+        &mut self,
+        address: u32,
+        data: &[u8],
+        len: u32,
+    ) -> Result<Vec<u8>, DebugProbeError> {
+        self.jtag_write_ir(address, self.ir_len)?;
+
+        self.jtag_shift_dr(data, len as usize)
+    }
+
+    fn set_idle_cycles(&mut self, idle_cycles: u8) {
+        self.jtag_idle_cycles = idle_cycles;
+    }
+
+    fn get_idle_cycles(&self) -> u8 {
+        self.jtag_idle_cycles
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn swd_request_dpidr_read() {
+        // Well known request byte for reading DPIDR
+        assert_eq!(build_swd_request(PortType::DebugPort, true, 0x0), 0xa5);
+    }
+
+    #[test]
+    fn swd_request_ap_write() {
+        assert_eq!(build_swd_request(PortType::AccessPort, false, 0xc), 0xbb);
+    }
+
+    #[test]
+    fn bit_buffer_round_trip() {
+        let data = [0xef, 0xbe, 0xad, 0xde, 0x05];
+
+        assert_eq!(get_bits(&data, 0, 32), 0xdead_beef);
+        assert_eq!(get_bits(&data, 32, 3), 0x5);
+
+        let mut result = [0u8; 5];
+        set_bits(&mut result, 0, 32, 0xdead_beef);
+        set_bits(&mut result, 32, 3, 0x5);
+
+        assert_eq!(result, data);
+    }
+}
@@ -0,0 +1,471 @@
+//! Implementation of the Black Magic Probe remote protocol.
+//!
+//! The remote protocol is spoken over the same virtual COM port as the
+//! GDB server of the probe. Every request is framed as `!<packet>#`,
+//! and every response is framed as `&<status><payload>#`.
+//!
+//! Numeric payloads are transmitted as hexadecimal ASCII strings,
+//! most significant digit first.
+
+use std::{
+    io::{Read, Write},
+    time::Duration,
+};
+
+use serialport::{SerialPort, SerialPortType};
+
+use crate::{
+    probe::{DebugProbeInfo, DebugProbeType, ProbeCreationError},
+    DebugProbeError, DebugProbeSelector,
+};
+
+/// The USB VID of the Black Magic Probe.
+pub(crate) const BLACK_MAGIC_VID: u16 = 0x1d50;
+/// The USB PID of the Black Magic Probe in application mode.
+pub(crate) const BLACK_MAGIC_PID: u16 = 0x6018;
+
+/// Baud rate used for the virtual COM port. This is ignored by the probe,
+/// but has to be set to open the port on some operating systems.
+const BAUD_RATE: u32 = 115_200;
+
+/// Timeout for a single response from the probe.
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Maximum size of a single response packet.
+const MAX_RESPONSE_LENGTH: usize = 1024;
+
+const REMOTE_SOM: u8 = b'!';
+const REMOTE_EOM: u8 = b'#';
+const REMOTE_RESP: u8 = b'&';
+
+/// String used to switch the probe from GDB mode into remote mode.
+const REMOTE_START: &[u8] = b"+#!GA#";
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum BlackMagicProbeError {
+    #[error("The probe reported an error: {0:?}")]
+    ErrorResponse(String),
+    #[error("The command is not supported by the probe firmware.")]
+    NotSupported,
+    #[error("Parity error in data received from the target.")]
+    ParityError,
+    #[error("Received a malformed response from the probe: {0:?}")]
+    MalformedResponse(String),
+    #[error("Serial port error")]
+    Serial(#[from] serialport::Error),
+    #[error("IO error")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<BlackMagicProbeError> for DebugProbeError {
+    fn from(error: BlackMagicProbeError) -> Self {
+        DebugProbeError::ProbeSpecific(Box::new(error))
+    }
+}
+
+impl From<BlackMagicProbeError> for ProbeCreationError {
+    fn from(error: BlackMagicProbeError) -> Self {
+        ProbeCreationError::ProbeSpecific(Box::new(error))
+    }
+}
+
+/// A decoded response to a remote protocol request.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Response {
+    /// The request was successful, with the given payload.
+    Ok(String),
+    /// The request was successful, but a parity error was detected
+    /// in the data received from the target.
+    ParityError(String),
+    /// The request failed.
+    Error(String),
+    /// The request is not supported by the probe.
+    NotSupported,
+}
+
+impl Response {
+    /// Parse a response packet, without the leading `&` and the trailing `#`.
+    pub(crate) fn parse(packet: &[u8]) -> Result<Self, BlackMagicProbeError> {
+        let (status, payload) = packet
+            .split_first()
+            .ok_or_else(|| BlackMagicProbeError::MalformedResponse(String::new()))?;
+
+        let payload = String::from_utf8_lossy(payload).into_owned();
+
+        match status {
+            b'K' => Ok(Response::Ok(payload)),
+            b'P' => Ok(Response::ParityError(payload)),
+            b'E' => Ok(Response::Error(payload)),
+            b'N' => Ok(Response::NotSupported),
+            _ => Err(BlackMagicProbeError::MalformedResponse(
+                String::from_utf8_lossy(packet).into_owned(),
+            )),
+        }
+    }
+
+    /// Convert the response into the payload of a successful request.
+    pub(crate) fn into_payload(self) -> Result<String, BlackMagicProbeError> {
+        match self {
+            Response::Ok(payload) => Ok(payload),
+            Response::ParityError(_) => Err(BlackMagicProbeError::ParityError),
+            Response::Error(payload) => Err(BlackMagicProbeError::ErrorResponse(payload)),
+            Response::NotSupported => Err(BlackMagicProbeError::NotSupported),
+        }
+    }
+}
+
+/// Parse a hexadecimal payload, as used by the remote protocol for numeric values.
+pub(crate) fn parse_hex(payload: &str) -> Result<u64, BlackMagicProbeError> {
+    u64::from_str_radix(payload, 16)
+        .map_err(|_| BlackMagicProbeError::MalformedResponse(payload.to_owned()))
+}
+
+/// Parse the payload of the target voltage request, e.g. `3.3V`.
+///
+/// Returns `None` if the probe is not able to measure the voltage.
+pub(crate) fn parse_voltage(payload: &str) -> Option<f32> {
+    payload.trim().trim_end_matches('V').parse().ok()
+}
+
+pub(crate) struct ProtocolHandler {
+    port: Box<dyn SerialPort>,
+}
+
+impl std::fmt::Debug for ProtocolHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProtocolHandler")
+            .field("port", &self.port.name())
+            .finish()
+    }
+}
+
+impl ProtocolHandler {
+    pub fn new_from_selector(
+        selector: impl Into<DebugProbeSelector>,
+    ) -> Result<Self, ProbeCreationError> {
+        let selector = selector.into();
+
+        if selector.vendor_id != BLACK_MAGIC_VID || selector.product_id != BLACK_MAGIC_PID {
+            return Err(ProbeCreationError::NotFound);
+        }
+
+        let port_name = find_ports()
+            .into_iter()
+            .find(|(_, serial)| {
+                selector.serial_number.is_none() || selector.serial_number == *serial
+            })
+            .map(|(port, _)| port)
+            .ok_or(ProbeCreationError::NotFound)?;
+
+        log::debug!("Opening Black Magic Probe on port {}", port_name);
+
+        let port = serialport::new(&port_name, BAUD_RATE)
+            .timeout(RESPONSE_TIMEOUT)
+            .open()
+            .map_err(BlackMagicProbeError::from)?;
+
+        let mut protocol = Self { port };
+
+        let version = protocol.start()?;
+
+        log::info!("Black Magic Probe: Firmware version: {}", version);
+
+        Ok(protocol)
+    }
+
+    /// Switch the probe into remote mode, and return the firmware version.
+    fn start(&mut self) -> Result<String, BlackMagicProbeError> {
+        // Discard any stale data from a previous session.
+        self.port.clear(serialport::ClearBuffer::All)?;
+
+        self.port.write_all(REMOTE_START)?;
+        self.port.flush()?;
+
+        self.read_response()?.into_payload()
+    }
+
+    /// Send a single request and return the response.
+    pub fn command(&mut self, packet: &str) -> Result<Response, BlackMagicProbeError> {
+        log::trace!("Black Magic Probe request: !{}#", packet);
+
+        let mut request = Vec::with_capacity(packet.len() + 2);
+        request.push(REMOTE_SOM);
+        request.extend_from_slice(packet.as_bytes());
+        request.push(REMOTE_EOM);
+
+        self.port.write_all(&request)?;
+        self.port.flush()?;
+
+        let response = self.read_response()?;
+
+        log::trace!("Black Magic Probe response: {:?}", response);
+
+        Ok(response)
+    }
+
+    /// Send a request, and parse the hexadecimal payload of the response.
+    pub fn command_hex(&mut self, packet: &str) -> Result<u64, BlackMagicProbeError> {
+        let payload = self.command(packet)?.into_payload()?;
+
+        parse_hex(&payload)
+    }
+
+    fn read_response(&mut self) -> Result<Response, BlackMagicProbeError> {
+        let mut byte = [0u8];
+
+        // Skip everything until the start of a response.
+        loop {
+            self.port.read_exact(&mut byte)?;
+
+            if byte[0] == REMOTE_RESP {
+                break;
+            }
+        }
+
+        let mut packet = Vec::new();
+
+        loop {
+            self.port.read_exact(&mut byte)?;
+
+            if byte[0] == REMOTE_EOM {
+                break;
+            }
+
+            if packet.len() >= MAX_RESPONSE_LENGTH {
+                return Err(BlackMagicProbeError::MalformedResponse(
+                    String::from_utf8_lossy(&packet).into_owned(),
+                ));
+            }
+
+            packet.push(byte[0]);
+        }
+
+        Response::parse(&packet)
+    }
+
+    /// Initialise the SWD interface of the probe.
+    pub fn swd_init(&mut self) -> Result<(), BlackMagicProbeError> {
+        self.command("SS")?.into_payload().map(|_| ())
+    }
+
+    /// Clock in `clock_cycles` bits from the target, without parity check.
+    pub fn swd_seq_in(&mut self, clock_cycles: usize) -> Result<u32, BlackMagicProbeError> {
+        assert!(clock_cycles > 0 && clock_cycles <= 32);
+
+        self.command_hex(&format!("Si{:02x}", clock_cycles))
+            .map(|value| value as u32)
+    }
+
+    /// Clock in `clock_cycles` bits from the target, followed by a parity bit.
+    ///
+    /// Returns the value and whether the parity was correct.
+    pub fn swd_seq_in_parity(
+        &mut self,
+        clock_cycles: usize,
+    ) -> Result<(u32, bool), BlackMagicProbeError> {
+        assert!(clock_cycles > 0 && clock_cycles <= 32);
+
+        match self.command(&format!("SI{:02x}", clock_cycles))? {
+            Response::Ok(payload) => Ok((parse_hex(&payload)? as u32, true)),
+            Response::ParityError(payload) => Ok((parse_hex(&payload)? as u32, false)),
+            other => other.into_payload().map(|_| (0, false)),
+        }
+    }
+
+    /// Clock out the lowest `clock_cycles` bits of `value` to the target.
+    pub fn swd_seq_out(
+        &mut self,
+        value: u32,
+        clock_cycles: usize,
+    ) -> Result<(), BlackMagicProbeError> {
+        assert!(clock_cycles > 0 && clock_cycles <= 32);
+
+        self.command(&format!("So{:02x}{:x}", clock_cycles, value))?
+            .into_payload()
+            .map(|_| ())
+    }
+
+    /// Clock out the lowest `clock_cycles` bits of `value`, followed by a parity bit.
+    pub fn swd_seq_out_parity(
+        &mut self,
+        value: u32,
+        clock_cycles: usize,
+    ) -> Result<(), BlackMagicProbeError> {
+        assert!(clock_cycles > 0 && clock_cycles <= 32);
+
+        self.command(&format!("SO{:02x}{:x}", clock_cycles, value))?
+            .into_payload()
+            .map(|_| ())
+    }
+
+    /// Initialise the JTAG interface of the probe.
+    pub fn jtag_init(&mut self) -> Result<(), BlackMagicProbeError> {
+        self.command("JS")?.into_payload().map(|_| ())
+    }
+
+    /// Reset the JTAG state machine, using TRST if available.
+    pub fn jtag_reset(&mut self) -> Result<(), BlackMagicProbeError> {
+        self.command("JR")?.into_payload().map(|_| ())
+    }
+
+    /// Clock out the lowest `clock_cycles` bits of `tms` on the TMS line.
+    pub fn jtag_tms_seq(
+        &mut self,
+        tms: u32,
+        clock_cycles: usize,
+    ) -> Result<(), BlackMagicProbeError> {
+        assert!(clock_cycles > 0 && clock_cycles <= 32);
+
+        self.command(&format!("JT{:02x}{:x}", clock_cycles, tms))?
+            .into_payload()
+            .map(|_| ())
+    }
+
+    /// Shift the lowest `clock_cycles` bits of `tdi` through the TAP, and return the captured TDO bits.
+    ///
+    /// If `final_tms` is set, TMS is asserted during the last clock cycle,
+    /// leaving the shift state.
+    pub fn jtag_tdi_tdo_seq(
+        &mut self,
+        tdi: u32,
+        clock_cycles: usize,
+        final_tms: bool,
+    ) -> Result<u32, BlackMagicProbeError> {
+        assert!(clock_cycles > 0 && clock_cycles <= 32);
+
+        let command = if final_tms { 'D' } else { 'd' };
+
+        self.command_hex(&format!("J{}{:02x}{:x}", command, clock_cycles, tdi))
+            .map(|value| value as u32)
+    }
+
+    /// Read the target voltage, as a string like `3.3V`.
+    pub fn target_voltage(&mut self) -> Result<String, BlackMagicProbeError> {
+        self.command("GV")?.into_payload()
+    }
+
+    /// Set the state of the nRST line. `true` asserts the reset.
+    pub fn set_nrst(&mut self, assert: bool) -> Result<(), BlackMagicProbeError> {
+        self.command(&format!("GZ{}", if assert { '1' } else { '0' }))?
+            .into_payload()
+            .map(|_| ())
+    }
+
+    /// Set the clock frequency in Hz.
+    pub fn set_frequency(&mut self, frequency: u32) -> Result<(), BlackMagicProbeError> {
+        self.command(&format!("GF{:08x}", frequency))?
+            .into_payload()
+            .map(|_| ())
+    }
+
+    /// Get the clock frequency in Hz.
+    pub fn frequency(&mut self) -> Result<u32, BlackMagicProbeError> {
+        self.command_hex("Gf").map(|value| value as u32)
+    }
+}
+
+/// Find all serial ports belonging to a Black Magic Probe.
+///
+/// The probe exposes two serial ports, the first one is used for
+/// the GDB server and the remote protocol, the second one is a
+/// UART bridge. Only the first port of every probe is returned.
+fn find_ports() -> Vec<(String, Option<String>)> {
+    let mut ports = match serialport::available_ports() {
+        Ok(ports) => ports,
+        Err(e) => {
+            log::debug!("Failed to list serial ports: {}", e);
+            return vec![];
+        }
+    };
+
+    // The GDB port is always enumerated first, so it has the
+    // lower name, e.g. ttyACM0 vs ttyACM1, or usbmodem1 vs usbmodem3.
+    ports.sort_by(|a, b| a.port_name.cmp(&b.port_name));
+
+    let mut found: Vec<(String, Option<String>)> = Vec::new();
+
+    for port in ports {
+        if let SerialPortType::UsbPort(info) = port.port_type {
+            if info.vid != BLACK_MAGIC_VID || info.pid != BLACK_MAGIC_PID {
+                continue;
+            }
+
+            // On macOS, every port is listed twice, once as a "tty" and once as a "cu" device.
+            if port.port_name.starts_with("/dev/tty.") {
+                continue;
+            }
+
+            if found
+                .iter()
+                .any(|(_, serial)| *serial == info.serial_number)
+            {
+                continue;
+            }
+
+            found.push((port.port_name, info.serial_number));
+        }
+    }
+
+    found
+}
+
+/// Finds all connected Black Magic Probes.
+pub fn list_blackmagic_devices() -> Vec<DebugProbeInfo> {
+    find_ports()
+        .into_iter()
+        .map(|(_, serial_number)| {
+            DebugProbeInfo::new(
+                "Black Magic Probe",
+                BLACK_MAGIC_VID,
+                BLACK_MAGIC_PID,
+                serial_number,
+                DebugProbeType::BlackMagicProbe,
+                None,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_ok_response() {
+        assert_eq!(
+            Response::parse(b"K1234").unwrap(),
+            Response::Ok("1234".to_owned())
+        );
+    }
+
+    #[test]
+    fn parse_parity_error_response() {
+        assert_eq!(
+            Response::parse(b"Pdeadbeef").unwrap(),
+            Response::ParityError("deadbeef".to_owned())
+        );
+    }
+
+    #[test]
+    fn parse_not_supported_response() {
+        assert_eq!(Response::parse(b"N").unwrap(), Response::NotSupported);
+    }
+
+    #[test]
+    fn parse_invalid_response() {
+        assert!(Response::parse(b"X12").is_err());
+        assert!(Response::parse(b"").is_err());
+    }
+
+    #[test]
+    fn parse_hex_payload() {
+        assert_eq!(parse_hex("2ba01477").unwrap(), 0x2ba0_1477);
+        assert!(parse_hex("xyz").is_err());
+    }
+
+    #[test]
+    fn parse_voltage_payload() {
+        assert_eq!(parse_voltage("3.3V"), Some(3.3));
+        assert_eq!(parse_voltage("ABSENT!"), None);
+    }
+}
@@ -0,0 +1,695 @@
+//! Driver for the binary modes of the [Bus Pirate](http://dangerousprototypes.com/docs/Bus_Pirate).
+//!
+//! JTAG uses the OpenOCD mode of the firmware, which shifts TMS and TDI in bulk.
+//! The pins are used as TCK = CLK, TMS = CS, TDI = MOSI and TDO = MISO.
+//!
+//! SWD uses the raw-wire mode, with SWCLK = CLK and SWDIO = MOSI. In this mode the
+//! outputs are open drain, so the pull-up resistors of the Bus Pirate are enabled,
+//! and the Vpu pin has to be connected to the supply of the target.
+//!
+//! The nRESET line of the target is connected to AUX.
+//!
+//! Every bit is sent over the serial port, so this is very slow. It is only meant
+//! as a last resort if no real debug probe is available. Other serial adapters
+//! implementing the same binary modes can be used as well.
+
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use serialport::{ClearBuffer, SerialPort};
+
+use crate::{
+    architecture::{
+        arm::{
+            communication_interface::{DapProbe, UninitializedArmProbe},
+            ArmCommunicationInterface,
+        },
+        riscv::communication_interface::RiscvCommunicationInterface,
+    },
+    probe::{
+        arm_debug_interface::{ProbeStatistics, RawProtocolIo, SwdSettings},
+        scan_chain::{self, BitBangJtag, JtagChain},
+    },
+    DebugProbe, DebugProbeError, DebugProbeSelector, ProbeCapabilities, ProbeCreationError,
+    ScanChainElement, WireProtocol,
+};
+
+use super::JTAGAccess;
+
+/// Baud rate of the serial port of the Bus Pirate.
+const BAUD_RATE: u32 = 115_200;
+
+/// Timeout for a single response.
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Resets the Bus Pirate to the binary bitbang mode, from any other mode.
+const CMD_BINARY_RESET: u8 = 0x00;
+/// Enter the raw-wire mode from the binary bitbang mode.
+const CMD_ENTER_RAW_WIRE: u8 = 0x05;
+/// Enter the OpenOCD mode from the binary bitbang mode.
+const CMD_ENTER_OPENOCD: u8 = 0x06;
+
+/// OpenOCD mode: Set the output type of the pins.
+const OCD_PORT_MODE: u8 = 0x01;
+/// OpenOCD mode: Enable or disable a feature.
+const OCD_FEATURE: u8 = 0x02;
+/// OpenOCD mode: Shift TMS and TDI, and return TDO.
+const OCD_TAP_SHIFT: u8 = 0x05;
+/// Port mode with push-pull outputs.
+const OCD_PORT_MODE_NORMAL: u8 = 0x01;
+/// Feature controlling the reset line.
+const OCD_FEATURE_SRST: u8 = 0x08;
+/// Maximum number of bits shifted by a single TAP shift command.
+const OCD_MAX_SHIFT_BITS: usize = 1024;
+
+/// Raw-wire mode: Read a single bit, returns the value.
+const RAW_READ_BIT: u8 = 0x07;
+/// Raw-wire mode: Set MOSI high, which releases it in open drain mode.
+const RAW_DATA_HIGH: u8 = 0x0d;
+/// Raw-wire mode: Send 1 to 8 bits of the next byte.
+const RAW_BULK_BITS: u8 = 0x30;
+/// Raw-wire mode: Configure the power supply, the pull-ups, AUX and CS.
+const RAW_PERIPHERALS: u8 = 0x40;
+const RAW_PERIPHERALS_PULLUPS: u8 = 0x04;
+const RAW_PERIPHERALS_AUX: u8 = 0x02;
+/// Raw-wire mode: Set the clock speed.
+const RAW_SPEED: u8 = 0x60;
+/// Raw-wire mode: Configure the output type, the number of wires and the bit order.
+const RAW_CONFIG: u8 = 0x80;
+const RAW_CONFIG_LSB_FIRST: u8 = 0x02;
+/// Response to successful commands in the raw-wire mode.
+const RAW_OK: u8 = 0x01;
+
+/// Clock speeds of the raw-wire mode in kHz, indexed by the speed setting.
+const RAW_SPEEDS_KHZ: [u32; 4] = [5, 50, 100, 400];
+
+/// An error which occurred while communicating with the Bus Pirate.
+#[derive(thiserror::Error, Debug)]
+pub enum BusPirateError {
+    /// Opening or configuring the serial port failed.
+    #[error("Serial port error")]
+    Serial(#[from] serialport::Error),
+    /// Reading or writing the serial port failed.
+    #[error("IO error")]
+    Io(#[from] std::io::Error),
+    /// The Bus Pirate did not enter the binary mode.
+    #[error("The Bus Pirate did not enter the binary mode. Is a Bus Pirate connected?")]
+    NoBinaryMode,
+    /// The Bus Pirate sent an unexpected response.
+    #[error("Unexpected response from the Bus Pirate: {0:02x?}")]
+    UnexpectedResponse(Vec<u8>),
+}
+
+impl From<BusPirateError> for DebugProbeError {
+    fn from(error: BusPirateError) -> Self {
+        DebugProbeError::ProbeSpecific(Box::new(error))
+    }
+}
+
+/// The binary mode the Bus Pirate is currently in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Binary bitbang mode, or unknown.
+    Binary,
+    /// Raw-wire mode, used for SWD.
+    RawWire,
+    /// OpenOCD mode, used for JTAG.
+    OpenOcd,
+}
+
+/// Encode a TAP shift command of the OpenOCD mode.
+///
+/// TDI and TMS are sent as pairs of bytes, least significant bit first.
+fn encode_tap_shift(tms: &[bool], tdi: &[bool]) -> Vec<u8> {
+    let bits = tms.len();
+    let bytes = (bits + 7) / 8;
+
+    let mut command = vec![0u8; 3 + 2 * bytes];
+    command[0] = OCD_TAP_SHIFT;
+    command[1] = (bits >> 8) as u8;
+    command[2] = bits as u8;
+
+    for (i, (tms, tdi)) in tms.iter().zip(tdi).enumerate() {
+        command[3 + 2 * (i / 8)] |= u8::from(*tdi) << (i % 8);
+        command[4 + 2 * (i / 8)] |= u8::from(*tms) << (i % 8);
+    }
+
+    command
+}
+
+/// Decode the TDO bits from the response to a TAP shift command.
+fn decode_tap_shift(response: &[u8], bits: usize) -> Result<Vec<bool>, BusPirateError> {
+    if response.len() != 3 + (bits + 7) / 8 || response[0] != OCD_TAP_SHIFT {
+        return Err(BusPirateError::UnexpectedResponse(response.to_vec()));
+    }
+
+    Ok((0..bits)
+        .map(|i| response[3 + i / 8] & (1 << (i % 8)) != 0)
+        .collect())
+}
+
+/// Append bulk bit commands for the pending output bits.
+fn flush_bits(pending: &mut Vec<bool>, commands: &mut Vec<u8>, reads: &mut Vec<bool>) {
+    for chunk in pending.chunks(8) {
+        let byte = chunk
+            .iter()
+            .enumerate()
+            .fold(0u8, |byte, (i, bit)| byte | u8::from(*bit) << i);
+
+        commands.push(RAW_BULK_BITS | (chunk.len() - 1) as u8);
+        commands.push(byte);
+        reads.push(false);
+    }
+
+    pending.clear();
+}
+
+/// Encode a SWD bit sequence as raw-wire commands.
+///
+/// Every command returns a single byte. Returns the commands, and for every
+/// response whether it contains the value of a bit read from the target.
+fn encode_swd(dir: &[bool], swdio: &[bool]) -> (Vec<u8>, Vec<bool>) {
+    let mut commands = Vec::new();
+    let mut reads = Vec::new();
+
+    let mut pending: Vec<bool> = Vec::new();
+    let mut released = false;
+
+    for (output, value) in dir.iter().zip(swdio) {
+        if *output {
+            pending.push(*value);
+            released = false;
+        } else {
+            flush_bits(&mut pending, &mut commands, &mut reads);
+
+            // Let the target drive the line.
+            if !released {
+                commands.push(RAW_DATA_HIGH);
+                reads.push(false);
+                released = true;
+            }
+
+            commands.push(RAW_READ_BIT);
+            reads.push(true);
+        }
+    }
+
+    flush_bits(&mut pending, &mut commands, &mut reads);
+
+    (commands, reads)
+}
+
+/// A [Bus Pirate](http://dangerousprototypes.com/docs/Bus_Pirate) used as a SWD or JTAG probe.
+pub struct BusPirate {
+    port: Box<dyn SerialPort>,
+    mode: Mode,
+
+    protocol: WireProtocol,
+    /// The speed setting of the raw-wire mode.
+    raw_speed: u8,
+    /// The peripheral configuration of the raw-wire mode.
+    raw_peripherals: u8,
+
+    jtag: JtagChain,
+
+    swd_settings: SwdSettings,
+    probe_statistics: ProbeStatistics,
+}
+
+impl std::fmt::Debug for BusPirate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BusPirate")
+            .field("port", &self.port.name())
+            .field("mode", &self.mode)
+            .field("protocol", &self.protocol)
+            .finish()
+    }
+}
+
+impl BusPirate {
+    /// Open the Bus Pirate connected to the given serial port, e.g. `/dev/ttyUSB0`.
+    pub fn open(port_name: &str) -> Result<Box<Self>, DebugProbeError> {
+        let port = serialport::new(port_name, BAUD_RATE)
+            .timeout(RESPONSE_TIMEOUT)
+            .open()
+            .map_err(BusPirateError::from)?;
+
+        let mut probe = Self {
+            port,
+            mode: Mode::Binary,
+            protocol: WireProtocol::Swd,
+            raw_speed: RAW_SPEEDS_KHZ.len() as u8 - 1,
+            raw_peripherals: RAW_PERIPHERALS_PULLUPS | RAW_PERIPHERALS_AUX,
+            jtag: JtagChain::default(),
+            swd_settings: SwdSettings::default(),
+            probe_statistics: ProbeStatistics::default(),
+        };
+
+        probe.enter_binary_mode()?;
+
+        Ok(Box::new(probe))
+    }
+
+    /// Reset the Bus Pirate to the binary bitbang mode.
+    ///
+    /// If the Bus Pirate is in its interactive terminal, it takes up
+    /// to 20 zero bytes until it switches to the binary mode.
+    fn enter_binary_mode(&mut self) -> Result<(), BusPirateError> {
+        let mut received = Vec::new();
+
+        for _ in 0..20 {
+            self.port.write_all(&[CMD_BINARY_RESET])?;
+            std::thread::sleep(Duration::from_millis(10));
+
+            let available = self.port.bytes_to_read()? as usize;
+            let start = received.len();
+            received.resize(start + available, 0);
+            self.port.read_exact(&mut received[start..])?;
+
+            if received.windows(5).any(|window| window == b"BBIO1") {
+                // Discard the responses to the remaining reset commands.
+                std::thread::sleep(Duration::from_millis(10));
+                self.port.clear(ClearBuffer::Input)?;
+
+                self.mode = Mode::Binary;
+                return Ok(());
+            }
+        }
+
+        Err(BusPirateError::NoBinaryMode)
+    }
+
+    /// Switch to the binary mode used by the selected protocol.
+    fn enter_protocol_mode(&mut self) -> Result<(), BusPirateError> {
+        let mode = match self.protocol {
+            WireProtocol::Swd => Mode::RawWire,
+            _ => Mode::OpenOcd,
+        };
+
+        if self.mode == mode {
+            return Ok(());
+        }
+
+        if self.mode != Mode::Binary {
+            self.enter_binary_mode()?;
+        }
+
+        match mode {
+            Mode::RawWire => {
+                self.transfer(&[CMD_ENTER_RAW_WIRE], 4, Some(b"RAW1".as_slice()))?;
+
+                // Open drain outputs, 2-wire, least significant bit first
+                self.raw_command(RAW_CONFIG | RAW_CONFIG_LSB_FIRST)?;
+                self.raw_command(RAW_SPEED | self.raw_speed)?;
+                self.raw_command(RAW_PERIPHERALS | self.raw_peripherals)?;
+            }
+            Mode::OpenOcd => {
+                self.transfer(&[CMD_ENTER_OPENOCD], 4, Some(b"OCD1".as_slice()))?;
+
+                self.write(&[OCD_PORT_MODE, OCD_PORT_MODE_NORMAL])?;
+            }
+            Mode::Binary => unreachable!(),
+        }
+
+        self.mode = mode;
+
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), BusPirateError> {
+        self.port.write_all(data)?;
+        self.port.flush()?;
+
+        Ok(())
+    }
+
+    /// Send the data, and read a response of the given length.
+    ///
+    /// If `expected` is given, the response has to match it.
+    fn transfer(
+        &mut self,
+        data: &[u8],
+        response_len: usize,
+        expected: Option<&[u8]>,
+    ) -> Result<Vec<u8>, BusPirateError> {
+        self.write(data)?;
+
+        let mut response = vec![0u8; response_len];
+        self.port.read_exact(&mut response)?;
+
+        match expected {
+            Some(expected) if expected != response => {
+                Err(BusPirateError::UnexpectedResponse(response))
+            }
+            _ => Ok(response),
+        }
+    }
+
+    /// Send a raw-wire command, which responds with `0x01` if successful.
+    fn raw_command(&mut self, command: u8) -> Result<(), BusPirateError> {
+        self.transfer(&[command], 1, Some(&[RAW_OK][..]))?;
+
+        Ok(())
+    }
+
+    fn set_reset(&mut self, assert: bool) -> Result<(), BusPirateError> {
+        self.enter_protocol_mode()?;
+
+        match self.mode {
+            Mode::RawWire => {
+                if assert {
+                    self.raw_peripherals &= !RAW_PERIPHERALS_AUX;
+                } else {
+                    self.raw_peripherals |= RAW_PERIPHERALS_AUX;
+                }
+
+                self.raw_command(RAW_PERIPHERALS | self.raw_peripherals)
+            }
+            _ => self.write(&[OCD_FEATURE, OCD_FEATURE_SRST, u8::from(assert)]),
+        }
+    }
+}
+
+impl Drop for BusPirate {
+    fn drop(&mut self) {
+        // Return to the binary mode, and from there to the terminal,
+        // so the Bus Pirate can be used interactively again.
+        // We can't do anything about errors here.
+        let _ = self.write(&[CMD_BINARY_RESET, 0x0f]);
+    }
+}
+
+impl DebugProbe for BusPirate {
+    fn new_from_selector(
+        _selector: impl Into<DebugProbeSelector>,
+    ) -> Result<Box<Self>, DebugProbeError>
+    where
+        Self: Sized,
+    {
+        // The Bus Pirate uses a generic USB serial converter, so it can't be discovered.
+        Err(DebugProbeError::ProbeCouldNotBeCreated(
+            ProbeCreationError::NotFound,
+        ))
+    }
+
+    fn get_name(&self) -> &str {
+        "Bus Pirate"
+    }
+
+    fn speed_khz(&self) -> u32 {
+        RAW_SPEEDS_KHZ[self.raw_speed as usize]
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        let setting = RAW_SPEEDS_KHZ
+            .iter()
+            .rposition(|speed| *speed <= speed_khz)
+            .ok_or(DebugProbeError::UnsupportedSpeed(speed_khz))?;
+
+        self.raw_speed = setting as u8;
+
+        if self.mode == Mode::RawWire {
+            self.raw_command(RAW_SPEED | self.raw_speed)?;
+        }
+
+        Ok(self.speed_khz())
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        log::debug!("Attaching to Bus Pirate");
+
+        self.enter_protocol_mode()?;
+
+        if self.protocol == WireProtocol::Swd {
+            // Attaching is handled in sequence
+            return Ok(());
+        }
+
+        scan_chain::attach(self)?;
+
+        Ok(())
+    }
+
+    fn detach(&mut self) -> Result<(), DebugProbeError> {
+        Ok(())
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        self.target_reset_assert()?;
+        std::thread::sleep(Duration::from_millis(100));
+        self.target_reset_deassert()
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        self.set_reset(true)?;
+        Ok(())
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.set_reset(false)?;
+        Ok(())
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        if protocol == WireProtocol::Cjtag {
+            return Err(DebugProbeError::UnsupportedProtocol(protocol));
+        }
+
+        self.protocol = protocol;
+        Ok(())
+    }
+
+    fn active_protocol(&self) -> Option<WireProtocol> {
+        Some(self.protocol)
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: Vec<ScanChainElement>) -> Result<(), DebugProbeError> {
+        self.jtag.set_scan_chain(scan_chain);
+        Ok(())
+    }
+
+    fn select_jtag_tap(&mut self, index: usize) -> Result<(), DebugProbeError> {
+        self.jtag.select_tap(index);
+        Ok(())
+    }
+
+    fn scan_chain(&self) -> Result<&[ScanChainElement], DebugProbeError> {
+        Ok(self.jtag.scan_chain())
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        true
+    }
+
+    fn try_get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
+    {
+        Ok(Box::new(ArmCommunicationInterface::new(self, true)))
+    }
+
+    fn has_riscv_interface(&self) -> bool {
+        self.protocol == WireProtocol::Jtag
+    }
+
+    fn try_get_riscv_interface(
+        self: Box<Self>,
+    ) -> Result<RiscvCommunicationInterface, (Box<dyn DebugProbe>, DebugProbeError)> {
+        if self.protocol == WireProtocol::Swd {
+            return Err((
+                self,
+                DebugProbeError::UnsupportedProtocol(WireProtocol::Swd),
+            ));
+        }
+
+        match RiscvCommunicationInterface::new(self) {
+            Ok(interface) => Ok(interface),
+            Err((probe, err)) => Err((probe.into_probe(), err)),
+        }
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        Some(self)
+    }
+
+    fn capabilities(&self) -> ProbeCapabilities {
+        let mut capabilities = ProbeCapabilities::default();
+
+        capabilities.max_speed_khz = RAW_SPEEDS_KHZ.last().copied();
+        capabilities.protocols = vec![WireProtocol::Swd, WireProtocol::Jtag];
+
+        capabilities
+    }
+}
+
+impl DapProbe for BusPirate {}
+
+impl RawProtocolIo for BusPirate {
+    fn jtag_io<M, I>(&mut self, tms: M, tdi: I) -> Result<Vec<bool>, DebugProbeError>
+    where
+        M: IntoIterator<Item = bool>,
+        I: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+
+        let (tms, tdi): (Vec<bool>, Vec<bool>) = tms.into_iter().zip(tdi).unzip();
+
+        let mut tdo = Vec::with_capacity(tms.len());
+
+        for (tms, tdi) in tms
+            .chunks(OCD_MAX_SHIFT_BITS)
+            .zip(tdi.chunks(OCD_MAX_SHIFT_BITS))
+        {
+            let command = encode_tap_shift(tms, tdi);
+            let response = self.transfer(&command, 3 + (tms.len() + 7) / 8, None)?;
+
+            tdo.extend(decode_tap_shift(&response, tms.len())?);
+        }
+
+        Ok(tdo)
+    }
+
+    fn swd_io<D, S>(&mut self, dir: D, swdio: S) -> Result<Vec<bool>, DebugProbeError>
+    where
+        D: IntoIterator<Item = bool>,
+        S: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+
+        let (dir, swdio): (Vec<bool>, Vec<bool>) = dir.into_iter().zip(swdio).unzip();
+
+        let (commands, reads) = encode_swd(&dir, &swdio);
+        let response = self.transfer(&commands, reads.len(), None)?;
+
+        let mut values = Vec::new();
+        for (value, is_read) in response.iter().zip(reads) {
+            match (is_read, *value) {
+                (true, 0x00) => values.push(false),
+                (true, 0x01) => values.push(true),
+                (false, RAW_OK) => {}
+                _ => return Err(BusPirateError::UnexpectedResponse(response.clone()).into()),
+            }
+        }
+
+        // Output bits are reported as they were sent.
+        let mut values = values.into_iter();
+        Ok(dir
+            .iter()
+            .zip(swdio)
+            .map(|(dir, swdio)| if *dir { swdio } else { values.next().unwrap() })
+            .collect())
+    }
+
+    fn swd_settings(&self) -> &SwdSettings {
+        &self.swd_settings
+    }
+
+    fn probe_statistics(&mut self) -> &mut ProbeStatistics {
+        &mut self.probe_statistics
+    }
+}
+
+impl JTAGAccess for BusPirate {
+    fn set_ir_len(&mut self, len: u32) {
+        self.jtag.set_ir_len(len);
+    }
+
+    fn read_register(&mut self, address: u32, len: u32) -> Result<Vec<u8>, DebugProbeError> {
+        let data = vec![0u8; (len as usize + 7) / 8];
+
+        scan_chain::transfer_register(self, address, &data, len)
+    }
+
+    fn write_register(
+        &mut self,
+        address: u32,
+        data: &[u8],
+        len: u32,
+    ) -> Result<Vec<u8>, DebugProbeError> {
+        scan_chain::transfer_register(self, address, data, len)
+    }
+
+    fn set_idle_cycles(&mut self, idle_cycles: u8) {
+        self.jtag.set_idle_cycles(idle_cycles);
+    }
+
+    fn get_idle_cycles(&self) -> u8 {
+        self.jtag.idle_cycles()
+    }
+}
+
+impl BitBangJtag for BusPirate {
+    fn jtag_chain(&mut self) -> &mut JtagChain {
+        &mut self.jtag
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tap_shift_command() {
+        let tms = [true, false, false, false, false, false, false, false, true];
+        let tdi = [false, true, false, false, false, false, false, true, true];
+
+        assert_eq!(
+            encode_tap_shift(&tms, &tdi),
+            [OCD_TAP_SHIFT, 0x00, 0x09, 0x82, 0x01, 0x01, 0x01]
+        );
+    }
+
+    #[test]
+    fn tap_shift_response() {
+        let response = [OCD_TAP_SHIFT, 0x00, 0x0a, 0x05, 0x02];
+
+        assert_eq!(
+            decode_tap_shift(&response, 10).unwrap(),
+            [true, false, true, false, false, false, false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn tap_shift_response_with_wrong_length() {
+        let response = [OCD_TAP_SHIFT, 0x00, 0x0a, 0x05];
+
+        assert!(decode_tap_shift(&response, 10).is_err());
+    }
+
+    #[test]
+    fn swd_commands() {
+        // Request, turnaround and two ACK bits
+        let dir = [true, true, true, false, false, false];
+        let swdio = [true, false, true, false, false, false];
+
+        let (commands, reads) = encode_swd(&dir, &swdio);
+
+        assert_eq!(
+            commands,
+            [
+                RAW_BULK_BITS | 2,
+                0b101,
+                RAW_DATA_HIGH,
+                RAW_READ_BIT,
+                RAW_READ_BIT,
+                RAW_READ_BIT,
+            ]
+        );
+        assert_eq!(reads, [false, false, true, true, true]);
+    }
+
+    #[test]
+    fn swd_commands_are_split_into_bytes() {
+        let dir = [true; 10];
+        let swdio = [true; 10];
+
+        let (commands, reads) = encode_swd(&dir, &swdio);
+
+        assert_eq!(commands, [RAW_BULK_BITS | 7, 0xff, RAW_BULK_BITS | 1, 0x03]);
+        assert_eq!(reads, [false, false]);
+    }
+}
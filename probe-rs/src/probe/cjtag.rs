@@ -0,0 +1,274 @@
+//! Support for compact JTAG (cJTAG) as specified in IEEE 1149.7.
+//!
+//! cJTAG only uses the two pins TCKC and TMSC. In the OScan1 scan format,
+//! every cycle of the standard JTAG protocol is transferred as three bits on TMSC:
+//!
+//! 1. The inverted TDI value, driven by the probe.
+//! 2. The TMS value, driven by the probe.
+//! 3. The TDO value, driven by the target.
+//!
+//! Before the OScan1 format can be used, the TAP has to be selected and activated
+//! using escape sequences, which are generated by toggling TMSC while TCKC is high.
+//! Because this requires direct control over the pins, the activation is handled
+//! by the probe drivers, using the helpers in this module.
+//!
+//! Some devices, like the TI CC13xx/CC26xx, start in the two pin mode after power up,
+//! and have to be switched to the four pin mode using [`switch_to_4_wire`] before
+//! they can be used with a standard JTAG probe.
+
+use crate::probe::arm_debug_interface::RawProtocolIo;
+use crate::DebugProbeError;
+
+/// Number of TMSC edges for a reset escape, which puts the TAP into the standard
+/// protocol and resets the TAP controller.
+const RESET_ESCAPE_EDGES: usize = 10;
+
+/// Number of TMSC edges for a selection escape, which starts the
+/// online activation of the TAP.
+const SELECTION_ESCAPE_EDGES: usize = 6;
+
+/// Online Activation Code, selecting the advanced protocol.
+const OAC: u8 = 0b1100;
+
+/// Extension Code, selecting the OScan1 scan format.
+const EC: u8 = 0b1000;
+
+/// Check Packet, which is used by the TAP to validate the activation.
+const CP: u8 = OAC ^ EC;
+
+/// Number of TMSC bits used for every JTAG cycle in the OScan1 format.
+const OSCAN1_BITS_PER_CYCLE: usize = 3;
+
+/// Opcode of the Store Format (STFMT) command, given as the number of bits
+/// shifted during the first command part.
+const STFMT: usize = 3;
+
+/// The JScan0 scan format, which is the standard four pin JTAG protocol.
+const FORMAT_JSCAN0: usize = 0;
+
+/// The bits which have to be sent on TMSC after a selection escape,
+/// to activate the OScan1 format.
+///
+/// Every bit is sampled by the TAP on the rising edge of TCKC.
+fn activation_bits() -> Vec<bool> {
+    [OAC, EC, CP]
+        .iter()
+        .flat_map(|packet| (0..4).map(move |i| packet & (1 << i) != 0))
+        .collect()
+}
+
+/// Append an escape sequence with the given number of TMSC edges.
+///
+/// The states are given as `(tckc, tmsc)` pairs. TCKC is low at the end.
+fn escape(edges: usize, states: &mut Vec<(bool, bool)>) {
+    states.push((false, true));
+    states.push((true, true));
+
+    let mut tmsc = true;
+    for _ in 0..edges {
+        tmsc = !tmsc;
+        states.push((true, tmsc));
+    }
+
+    states.push((false, tmsc));
+}
+
+/// The pin states which switch a cJTAG TAP to the OScan1 format.
+///
+/// The states are given as `(tckc, tmsc)` pairs, and have to be applied in order,
+/// with TMSC driven by the probe. A reset escape puts the TAP into the
+/// Test-Logic-Reset state, and is followed by the online activation.
+pub(crate) fn activation_sequence() -> Vec<(bool, bool)> {
+    let mut states = Vec::new();
+
+    escape(RESET_ESCAPE_EDGES, &mut states);
+    escape(SELECTION_ESCAPE_EDGES, &mut states);
+
+    for bit in activation_bits() {
+        states.push((false, bit));
+        states.push((true, bit));
+    }
+
+    states.push((false, true));
+
+    states
+}
+
+/// Append the TMS values of a DR scan which shifts `len` bits, starting and ending in Run-Test/Idle.
+///
+/// In the two pin standard protocol, the DR scans are used to send the commands of
+/// the cJTAG control levels, which are encoded in the number of bits shifted.
+fn dr_scan(len: usize, tms: &mut Vec<bool>) {
+    // Run-Test/Idle -> Select-DR-Scan -> Capture-DR
+    tms.extend_from_slice(&[true, false]);
+
+    if len > 0 {
+        // Capture-DR -> Shift-DR, the last bit is shifted when leaving Shift-DR.
+        tms.push(false);
+        tms.extend((0..len).map(|i| i == len - 1));
+    } else {
+        // Capture-DR -> Exit1-DR, a zero bit scan.
+        tms.push(true);
+    }
+
+    // Exit1-DR -> Update-DR -> Run-Test/Idle
+    tms.extend_from_slice(&[true, false]);
+}
+
+/// The TMS values which switch a cJTAG TAP in the two pin standard protocol to four pin JTAG.
+///
+/// Two zero bit scans lock control level 2, in which the following DR scans are interpreted
+/// as the two parts of a command. The STFMT command selects the JScan0 format, and the
+/// control level is left by resetting the TAP.
+fn four_wire_switch_tms() -> Vec<bool> {
+    // Test-Logic-Reset -> Run-Test/Idle
+    let mut tms = vec![true; 5];
+    tms.push(false);
+
+    dr_scan(0, &mut tms);
+    dr_scan(0, &mut tms);
+
+    dr_scan(STFMT, &mut tms);
+    dr_scan(FORMAT_JSCAN0, &mut tms);
+
+    // Leave the control level by going to Test-Logic-Reset, then to Run-Test/Idle.
+    tms.extend_from_slice(&[true; 5]);
+    tms.push(false);
+
+    tms
+}
+
+/// Switch a cJTAG TAP, which is in the two pin standard protocol, to four pin JTAG.
+///
+/// This only uses TMS and TCK, which are TMSC and TCKC in the two pin mode.
+/// TAPs which already use four pin JTAG just perform some empty DR scans.
+pub(crate) fn switch_to_4_wire<P: RawProtocolIo + ?Sized>(
+    probe: &mut P,
+) -> Result<(), DebugProbeError> {
+    let tms = four_wire_switch_tms();
+    let tdi = vec![false; tms.len()];
+
+    probe.jtag_io(tms, tdi)?;
+
+    Ok(())
+}
+
+/// A sequence of TMSC bits in the OScan1 format.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Oscan1Sequence {
+    /// `true` for bits driven by the probe, `false` for bits driven by the target.
+    pub direction: Vec<bool>,
+    /// The values driven by the probe. Bits driven by the target are `false`.
+    pub tmsc: Vec<bool>,
+}
+
+impl Oscan1Sequence {
+    /// Encode the given TMS and TDI values as OScan1 bits.
+    pub fn encode(
+        tms: impl IntoIterator<Item = bool>,
+        tdi: impl IntoIterator<Item = bool>,
+    ) -> Self {
+        let mut sequence = Self::default();
+
+        for (tms, tdi) in tms.into_iter().zip(tdi) {
+            sequence.direction.extend_from_slice(&[true, true, false]);
+            sequence.tmsc.extend_from_slice(&[!tdi, tms, false]);
+        }
+
+        sequence
+    }
+}
+
+/// Extract the TDO values from the TMSC bits captured during an OScan1 sequence.
+pub(crate) fn decode_tdo(captured: &[bool]) -> Vec<bool> {
+    captured
+        .chunks_exact(OSCAN1_BITS_PER_CYCLE)
+        .map(|cycle| cycle[2])
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        activation_bits, activation_sequence, decode_tdo, dr_scan, escape, four_wire_switch_tms,
+        Oscan1Sequence, SELECTION_ESCAPE_EDGES,
+    };
+
+    #[test]
+    fn encode_oscan1() {
+        let sequence = Oscan1Sequence::encode([true, false], [false, true]);
+
+        assert_eq!(
+            sequence.direction,
+            vec![true, true, false, true, true, false]
+        );
+        assert_eq!(sequence.tmsc, vec![true, true, false, false, false, false]);
+    }
+
+    #[test]
+    fn decode_oscan1() {
+        let captured = [true, true, false, false, false, true, true, false, true];
+
+        assert_eq!(decode_tdo(&captured), vec![false, true, true]);
+    }
+
+    #[test]
+    fn activation_is_sent_lsb_first() {
+        let bits = activation_bits();
+
+        assert_eq!(
+            bits,
+            vec![
+                false, false, true, true, // OAC
+                false, false, false, true, // EC
+                false, false, true, false, // CP
+            ]
+        );
+    }
+
+    #[test]
+    fn escape_toggles_tmsc_while_tckc_is_high() {
+        let mut states = Vec::new();
+        escape(SELECTION_ESCAPE_EDGES, &mut states);
+
+        let edges = states
+            .windows(2)
+            .filter(|pair| pair[0].0 && pair[1].0 && pair[0].1 != pair[1].1)
+            .count();
+
+        assert_eq!(edges, SELECTION_ESCAPE_EDGES);
+        assert!(!states.last().unwrap().0);
+    }
+
+    #[test]
+    fn dr_scan_shifts_len_bits() {
+        let mut tms = Vec::new();
+        dr_scan(3, &mut tms);
+
+        assert_eq!(
+            tms,
+            vec![true, false, false, false, false, true, true, false]
+        );
+
+        let mut tms = Vec::new();
+        dr_scan(0, &mut tms);
+
+        assert_eq!(tms, vec![true, false, true, true, false]);
+    }
+
+    #[test]
+    fn four_wire_switch_ends_in_idle() {
+        let tms = four_wire_switch_tms();
+
+        assert_eq!(tms.len(), 6 + 5 + 5 + 8 + 5 + 6);
+        assert_eq!(
+            &tms[tms.len() - 6..],
+            &[true, true, true, true, true, false]
+        );
+    }
+
+    #[test]
+    fn activation_ends_with_tckc_low() {
+        assert_eq!(activation_sequence().last(), Some(&(false, true)));
+    }
+}
@@ -0,0 +1,94 @@
+//! A simulated CMSIS-DAP probe, to test the driver without hardware.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use super::SendError;
+
+/// Computes the response to a command packet, like the firmware of a probe.
+///
+/// The packet starts with the command ID, the response has to start with it as well.
+type Handler = Box<dyn FnMut(&[u8]) -> Vec<u8> + Send>;
+
+pub(crate) struct MockDevice {
+    pub(crate) packet_size: usize,
+    state: Mutex<State>,
+}
+
+struct State {
+    handler: Handler,
+    /// The responses which haven't been read yet.
+    responses: VecDeque<Vec<u8>>,
+    /// All command packets sent to the probe.
+    commands: Vec<Vec<u8>>,
+    /// The largest number of commands which were waiting for their response to be read.
+    max_in_flight: usize,
+}
+
+impl MockDevice {
+    pub(crate) fn new(
+        packet_size: usize,
+        handler: impl FnMut(&[u8]) -> Vec<u8> + Send + 'static,
+    ) -> Self {
+        Self {
+            packet_size,
+            state: Mutex::new(State {
+                handler: Box::new(handler),
+                responses: VecDeque::new(),
+                commands: vec![],
+                max_in_flight: 0,
+            }),
+        }
+    }
+
+    /// All command packets sent to the probe.
+    pub(crate) fn commands(&self) -> Vec<Vec<u8>> {
+        self.state.lock().unwrap().commands.clone()
+    }
+
+    /// The largest number of commands which were waiting for their response to be read.
+    pub(crate) fn max_in_flight(&self) -> usize {
+        self.state.lock().unwrap().max_in_flight
+    }
+
+    /// The number of responses which were not read.
+    pub(crate) fn pending_responses(&self) -> usize {
+        self.state.lock().unwrap().responses.len()
+    }
+
+    pub(super) fn read(&self, buf: &mut [u8]) -> Result<usize, SendError> {
+        let response = self
+            .state
+            .lock()
+            .unwrap()
+            .responses
+            .pop_front()
+            .ok_or(SendError::Timeout)?;
+
+        buf[..response.len()].copy_from_slice(&response);
+        Ok(response.len())
+    }
+
+    pub(super) fn write(&self, buf: &[u8]) -> Result<usize, SendError> {
+        let mut state = self.state.lock().unwrap();
+
+        // Skip the HID report ID, like for v2 devices.
+        let command = buf[1..].to_vec();
+
+        let response = (state.handler)(&command);
+        assert!(
+            response.len() <= self.packet_size,
+            "The response doesn't fit into a packet"
+        );
+
+        state.commands.push(command);
+        state.responses.push_back(response);
+        state.max_in_flight = state.max_in_flight.max(state.responses.len());
+
+        Ok(buf.len())
+    }
+
+    pub(super) fn drain(&self) {
+        self.state.lock().unwrap().responses.clear();
+    }
+}
@@ -4,6 +4,9 @@ pub mod swj;
 pub mod swo;
 pub mod transfer;
 
+#[cfg(test)]
+pub(crate) mod mock;
+
 use crate::probe::cmsisdap::commands::general::info::PacketSizeCommand;
 use crate::DebugProbeError;
 use std::str::Utf8Error;
@@ -39,7 +42,7 @@ pub enum SendError {
     #[error("Error in the USB HID access")]
     HidApi(#[from] hidapi::HidError),
     #[error("Error in the USB access")]
-    UsbError(rusb::Error),
+    UsbError(#[source] rusb::Error),
     #[error("Not enough data in response from probe")]
     NotEnoughData,
     #[error("Status can only be 0x00 or 0xFF")]
@@ -93,6 +96,10 @@ pub enum CmsisDapDevice {
         max_packet_size: usize,
         swo_ep: Option<(u8, usize)>,
     },
+
+    /// A simulated probe, used by the tests of the driver.
+    #[cfg(test)]
+    Mock(mock::MockDevice),
 }
 
 impl CmsisDapDevice {
@@ -108,6 +115,8 @@ impl CmsisDapDevice {
                 let timeout = Duration::from_millis(100);
                 Ok(handle.read_bulk(*in_ep, buf, timeout)?)
             }
+            #[cfg(test)]
+            CmsisDapDevice::Mock(device) => device.read(buf),
         }
     }
 
@@ -120,6 +129,8 @@ impl CmsisDapDevice {
                 // Skip first byte as it's set to 0 for HID transfers
                 Ok(handle.write_bulk(*out_ep, &buf[1..], timeout)?)
             }
+            #[cfg(test)]
+            CmsisDapDevice::Mock(device) => device.write(buf),
         }
     }
 
@@ -157,6 +168,9 @@ impl CmsisDapDevice {
                     }
                 }
             }
+
+            #[cfg(test)]
+            CmsisDapDevice::Mock(device) => device.drain(),
         }
     }
 
@@ -179,6 +193,10 @@ impl CmsisDapDevice {
             } => {
                 *max_packet_size = packet_size;
             }
+            #[cfg(test)]
+            CmsisDapDevice::Mock(device) => {
+                device.packet_size = packet_size;
+            }
         }
     }
 
@@ -220,6 +238,8 @@ impl CmsisDapDevice {
         match self {
             CmsisDapDevice::V1 { .. } => false,
             CmsisDapDevice::V2 { swo_ep, .. } => swo_ep.is_some(),
+            #[cfg(test)]
+            CmsisDapDevice::Mock(_) => false,
         }
     }
 
@@ -248,6 +268,8 @@ impl CmsisDapDevice {
                 }
                 None => Err(CmsisDapError::SwoModeNotAvailable),
             },
+            #[cfg(test)]
+            CmsisDapDevice::Mock(_) => Err(CmsisDapError::SwoModeNotAvailable),
         }
     }
 }
@@ -336,6 +358,73 @@ fn send_command_inner<Req: Request>(
     device: &mut CmsisDapDevice,
     request: Req,
 ) -> Result<Req::Response, SendError> {
+    write_request(device, &request)?;
+    read_response(device, &request)
+}
+
+/// Send several commands of the same type to the probe, keeping up to
+/// `max_in_flight` commands queued in the probe at the same time.
+///
+/// CMSIS-DAP probes report how many packets they can buffer, see
+/// [`general::info::PacketCountCommand`]. Sending the next command before
+/// the response to the previous one has been received avoids waiting for a
+/// full USB round trip for every packet, which makes large block transfers
+/// considerably faster.
+///
+/// The responses are returned in the same order as the requests. If any command
+/// fails, the responses to the commands still in flight are discarded, so that
+/// later requests and responses stay in sync.
+pub(crate) fn send_commands_pipelined<Req: Request>(
+    device: &mut CmsisDapDevice,
+    requests: &[Req],
+    max_in_flight: usize,
+) -> Result<Vec<Req::Response>, CmsisDapError> {
+    let max_in_flight = max_in_flight.max(1);
+
+    let mut responses = Vec::with_capacity(requests.len());
+    let mut sent = 0;
+
+    while responses.len() < requests.len() {
+        while sent < requests.len() && sent - responses.len() < max_in_flight {
+            if let Err(e) = write_request(device, &requests[sent]) {
+                discard_responses(device, &requests[responses.len()..sent]);
+                return Err(CmsisDapError::Send {
+                    command_id: Req::COMMAND_ID,
+                    source: e,
+                });
+            }
+
+            sent += 1;
+        }
+
+        let index = responses.len();
+
+        match read_response(device, &requests[index]) {
+            Ok(response) => responses.push(response),
+            Err(e) => {
+                discard_responses(device, &requests[index + 1..sent]);
+                return Err(CmsisDapError::Send {
+                    command_id: Req::COMMAND_ID,
+                    source: e,
+                });
+            }
+        }
+    }
+
+    Ok(responses)
+}
+
+/// Read and ignore the responses for requests which are still in flight.
+fn discard_responses<Req: Request>(device: &mut CmsisDapDevice, requests: &[Req]) {
+    for request in requests {
+        if let Err(e) = read_response(device, request) {
+            log::debug!("Discarding response failed: {}", e);
+        }
+    }
+}
+
+/// Allocate a buffer large enough for a single packet.
+fn packet_buffer(device: &CmsisDapDevice) -> Vec<u8> {
     // Size the buffer for the maximum packet size.
     // On v1, we always send this full-sized report, while
     // on v2 we can truncate to just the required data.
@@ -345,8 +434,19 @@ fn send_command_inner<Req: Request>(
         CmsisDapDevice::V2 {
             max_packet_size, ..
         } => *max_packet_size + 1,
+        #[cfg(test)]
+        CmsisDapDevice::Mock(device) => device.packet_size + 1,
     };
-    let mut buffer = vec![0; buffer_len];
+
+    vec![0; buffer_len]
+}
+
+/// Send a request to the probe, without waiting for the response.
+fn write_request<Req: Request>(
+    device: &mut CmsisDapDevice,
+    request: &Req,
+) -> Result<(), SendError> {
+    let mut buffer = packet_buffer(device);
 
     // Leave byte 0 as the HID report, and write the command and request to the buffer.
     buffer[1] = Req::COMMAND_ID as u8;
@@ -364,6 +464,16 @@ fn send_command_inner<Req: Request>(
     let _ = device.write(&buffer[..size])?;
     trace_buffer("Transmit buffer", &buffer[..size]);
 
+    Ok(())
+}
+
+/// Read the response to a previously sent request.
+fn read_response<Req: Request>(
+    device: &mut CmsisDapDevice,
+    request: &Req,
+) -> Result<Req::Response, SendError> {
+    let mut buffer = packet_buffer(device);
+
     // Read back response.
     let bytes_read = device.read(&mut buffer)?;
     let response_data = &buffer[..bytes_read];
@@ -17,7 +17,8 @@ use crate::{
         },
         BatchCommand,
     },
-    DebugProbe, DebugProbeError, DebugProbeSelector, Error as ProbeRsError, WireProtocol,
+    DebugProbe, DebugProbeError, DebugProbeSelector, Error as ProbeRsError, ProbeCapabilities,
+    WireProtocol,
 };
 
 use commands::{
@@ -375,6 +376,9 @@ impl CmsisDap {
                 if response.status.error {
                     Err(CmsisDapError::SwoTraceStreamError.into())
                 } else {
+                    if response.status.overrun {
+                        log::warn!("SWO trace buffer overrun, trace data was lost");
+                    }
                     Ok(response.data)
                 }
             }
@@ -390,7 +394,7 @@ impl CmsisDap {
         let protocol = if let Some(protocol) = self.protocol {
             match protocol {
                 WireProtocol::Swd => ConnectRequest::Swd,
-                WireProtocol::Jtag => ConnectRequest::Jtag,
+                WireProtocol::Jtag | WireProtocol::Cjtag => ConnectRequest::Jtag,
             }
         } else {
             ConnectRequest::DefaultPort
@@ -506,6 +510,7 @@ impl DebugProbe for CmsisDap {
                 self.protocol = Some(WireProtocol::Swd);
                 Ok(())
             }
+            WireProtocol::Cjtag => Err(DebugProbeError::UnsupportedProtocol(protocol)),
         }
     }
 
@@ -565,6 +570,27 @@ impl DebugProbe for CmsisDap {
     fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
         Some(self)
     }
+
+    fn capabilities(&self) -> ProbeCapabilities {
+        let caps = &self.capabilities;
+        let mut capabilities = ProbeCapabilities::default();
+
+        if caps.swd_implemented {
+            capabilities.protocols.push(WireProtocol::Swd);
+        }
+        if caps.jtag_implemented {
+            capabilities.protocols.push(WireProtocol::Jtag);
+        }
+        if caps.swo_uart_implemented {
+            capabilities.swo_modes.push(SwoMode::Uart);
+        }
+        if caps.swo_manchester_implemented {
+            capabilities.swo_modes.push(SwoMode::Manchester);
+        }
+        capabilities.atomic_commands = caps.atomic_commands_implemented;
+
+        capabilities
+    }
 }
 
 impl RawDapAccess for CmsisDap {
@@ -640,32 +666,37 @@ impl RawDapAccess for CmsisDap {
     ) -> Result<(), DebugProbeError> {
         self.process_batch()?;
 
-        // the overhead for a single packet is 6 bytes
+        // the overhead for a single write request is 5 bytes
         //
-        // [0]: HID overhead
-        // [1]: Category
-        // [2]: DAP Index
-        // [3]: Len 1
-        // [4]: Len 2
-        // [5]: Request type
+        // [0]: Command ID
+        // [1]: DAP Index
+        // [2]: Len 1
+        // [3]: Len 2
+        // [4]: Request type
         //
+        let max_packet_size_words = (self.packet_size as usize - 5) / 4;
 
-        let max_packet_size_words = (self.packet_size - 6) / 4;
-
-        let data_chunk_len = max_packet_size_words as usize;
-
-        for (i, chunk) in values.chunks(data_chunk_len).enumerate() {
-            let request =
-                TransferBlockRequest::write_request(register_address as u8, port, Vec::from(chunk));
+        let requests: Vec<_> = values
+            .chunks(max_packet_size_words)
+            .map(|chunk| {
+                TransferBlockRequest::write_request(register_address, port, Vec::from(chunk))
+            })
+            .collect();
 
-            log::debug!("Transfer block: chunk={}, len={} bytes", i, chunk.len() * 4);
+        log::debug!(
+            "Transfer block: {} words in {} packets",
+            values.len(),
+            requests.len()
+        );
 
-            let resp: TransferBlockResponse =
-                commands::send_command(&mut self.device, request).map_err(DebugProbeError::from)?;
+        let responses = commands::send_commands_pipelined(
+            &mut self.device,
+            &requests,
+            self.packet_count as usize,
+        )?;
 
-            if resp.transfer_response != 1 {
-                return Err(CmsisDapError::ErrorResponse.into());
-            }
+        if responses.iter().any(|resp| resp.transfer_response != 1) {
+            return Err(CmsisDapError::ErrorResponse.into());
         }
 
         Ok(())
@@ -679,33 +710,36 @@ impl RawDapAccess for CmsisDap {
     ) -> Result<(), DebugProbeError> {
         self.process_batch()?;
 
-        // the overhead for a single packet is 6 bytes
+        // the overhead for a single read response is 4 bytes
         //
-        // [0]: HID overhead
-        // [1]: Category
-        // [2]: DAP Index
-        // [3]: Len 1
-        // [4]: Len 2
-        // [5]: Request type
+        // [0]: Command ID
+        // [1]: Len 1
+        // [2]: Len 2
+        // [3]: Response type
         //
+        let max_packet_size_words = (self.packet_size as usize - 4) / 4;
 
-        let max_packet_size_words = (self.packet_size - 6) / 4;
-
-        let data_chunk_len = max_packet_size_words as usize;
-
-        for (i, chunk) in values.chunks_mut(data_chunk_len).enumerate() {
-            let request = TransferBlockRequest::read_request(
-                register_address as u8,
-                port,
-                chunk.len() as u16,
-            );
+        let requests: Vec<_> = values
+            .chunks(max_packet_size_words)
+            .map(|chunk| {
+                TransferBlockRequest::read_request(register_address, port, chunk.len() as u16)
+            })
+            .collect();
 
-            log::debug!("Transfer block: chunk={}, len={} bytes", i, chunk.len() * 4);
+        log::debug!(
+            "Transfer block: {} words in {} packets",
+            values.len(),
+            requests.len()
+        );
 
-            let resp: TransferBlockResponse =
-                commands::send_command(&mut self.device, request).map_err(DebugProbeError::from)?;
+        let responses = commands::send_commands_pipelined(
+            &mut self.device,
+            &requests,
+            self.packet_count as usize,
+        )?;
 
-            if resp.transfer_response != 1 {
+        for (chunk, resp) in values.chunks_mut(max_packet_size_words).zip(responses) {
+            if resp.transfer_response != 1 || resp.transfer_data.len() != chunk.len() {
                 return Err(CmsisDapError::ErrorResponse.into());
             }
 
@@ -873,3 +907,163 @@ impl Drop for CmsisDap {
         let _ = self.detach();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::commands::{mock::MockDevice, CommandId};
+    use super::*;
+
+    const DRW_ADDRESS: u8 = 0x0C;
+
+    /// Answers DAP_Transfer and DAP_TransferBlock commands like a target, which returns `n` for
+    /// the `n`th register read. `block_ack` returns the ACK for the `n`th DAP_TransferBlock
+    /// command. Other commands, like DAP_Disconnect, succeed.
+    fn target(
+        mut block_ack: impl FnMut(usize) -> u8 + Send + 'static,
+    ) -> impl FnMut(&[u8]) -> Vec<u8> + Send {
+        let mut reads = 0u32;
+        let mut blocks = 0;
+
+        move |command| {
+            let mut response = vec![command[0]];
+
+            if command[0] == CommandId::Transfer as u8 {
+                let count = command[2];
+                response.extend_from_slice(&[count, 1]);
+
+                let mut offset = 3;
+                for _ in 0..count {
+                    let request = command[offset];
+                    offset += 1;
+
+                    if request & 0x02 != 0 {
+                        response.extend_from_slice(&reads.to_le_bytes());
+                        reads += 1;
+                    } else {
+                        offset += 4;
+                    }
+                }
+            } else if command[0] == CommandId::TransferBlock as u8 {
+                let ack = block_ack(blocks);
+                blocks += 1;
+
+                let count = if ack == 1 {
+                    u16::from_le_bytes([command[2], command[3]])
+                } else {
+                    0
+                };
+                response.extend_from_slice(&count.to_le_bytes());
+                response.push(ack);
+
+                if command[4] & 0x02 != 0 {
+                    for _ in 0..count {
+                        response.extend_from_slice(&reads.to_le_bytes());
+                        reads += 1;
+                    }
+                }
+            } else {
+                response.push(0);
+            }
+
+            response
+        }
+    }
+
+    fn probe(device: MockDevice, packet_count: u8) -> CmsisDap {
+        CmsisDap {
+            packet_size: device.packet_size as u16,
+            device: CmsisDapDevice::Mock(device),
+            _hw_version: 0,
+            _jtag_version: 0,
+            protocol: Some(WireProtocol::Swd),
+            packet_count,
+            capabilities: Capabilities::default(),
+            swo_buffer_size: None,
+            swo_active: false,
+            swo_streaming: false,
+            connected: true,
+            speed_khz: 1_000,
+            batch: Vec::new(),
+        }
+    }
+
+    fn mock(probe: &CmsisDap) -> &MockDevice {
+        match &probe.device {
+            CmsisDapDevice::Mock(device) => device,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn block_transfer_spanning_several_packets() {
+        let mut probe = probe(MockDevice::new(64, target(|_| 1)), 2);
+
+        // A read response has room for 15 words.
+        let mut values = [0; 40];
+        probe
+            .raw_read_block(PortType::AccessPort, DRW_ADDRESS, &mut values)
+            .unwrap();
+
+        assert_eq!(values.to_vec(), (0..40).collect::<Vec<u32>>());
+        assert_eq!(mock(&probe).commands().len(), 3);
+        // The next packet is sent before the response to the previous one was read.
+        assert_eq!(mock(&probe).max_in_flight(), 2);
+
+        // A write request has room for 14 words.
+        let data = (100..140).collect::<Vec<u32>>();
+        probe
+            .raw_write_block(PortType::AccessPort, DRW_ADDRESS, &data)
+            .unwrap();
+
+        let commands = mock(&probe).commands();
+        assert_eq!(commands.len(), 6);
+
+        let written = commands[3..]
+            .iter()
+            .flat_map(|command| command[5..].chunks(4))
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(written, data);
+    }
+
+    #[test]
+    fn block_transfer_with_wait_or_fault_response() {
+        // The second of four read packets is answered with WAIT, and the second of four write
+        // packets with FAULT.
+        let mut probe = probe(
+            MockDevice::new(
+                64,
+                target(|block| match block {
+                    1 => 2,
+                    5 => 4,
+                    _ => 1,
+                }),
+            ),
+            4,
+        );
+
+        let mut values = [0; 60];
+        assert!(probe
+            .raw_read_block(PortType::AccessPort, DRW_ADDRESS, &mut values)
+            .is_err());
+
+        // The responses to all packets were read, so the next response belongs to the next
+        // command. The three successful packets read 45 words.
+        assert_eq!(mock(&probe).pending_responses(), 0);
+        assert_eq!(
+            probe.raw_read_register(PortType::DebugPort, 0x4).unwrap(),
+            45
+        );
+
+        let data = [0; 56];
+        assert!(probe
+            .raw_write_block(PortType::AccessPort, DRW_ADDRESS, &data)
+            .is_err());
+
+        assert_eq!(mock(&probe).pending_responses(), 0);
+        assert_eq!(
+            probe.raw_read_register(PortType::DebugPort, 0x4).unwrap(),
+            46
+        );
+    }
+}
@@ -13,6 +13,7 @@ use crate::{
             SwoAccess,
         },
         riscv::communication_interface::RiscvCommunicationInterface,
+        xtensa::communication_interface::XtensaCommunicationInterface,
     },
     probe::jlink::bits_to_byte,
     DebugProbe, DebugProbeError, DebugProbeSelector, WireProtocol,
@@ -327,11 +328,14 @@ impl DebugProbe for EspUsbJtag {
     ) -> Result<Box<Self>, DebugProbeError> {
         let protocol = ProtocolHandler::new_from_selector(selector)?;
 
+        // The probe does not report the current speed, so we assume the highest possible one.
+        let speed_khz = protocol.max_speed_khz();
+
         Ok(Box::new(EspUsbJtag {
             protocol,
             jtag_idle_cycles: 0,
             current_ir_reg: 1,
-            speed_khz: 0,
+            speed_khz,
         }))
     }
 
@@ -356,9 +360,13 @@ impl DebugProbe for EspUsbJtag {
     }
 
     fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
-        // TODO:
-
-        Ok(speed_khz)
+        match self.protocol.set_speed_khz(speed_khz)? {
+            Some(actual_speed_khz) => {
+                self.speed_khz = actual_speed_khz;
+                Ok(actual_speed_khz)
+            }
+            None => Err(DebugProbeError::UnsupportedSpeed(speed_khz)),
+        }
     }
 
     fn attach(&mut self) -> Result<(), super::DebugProbeError> {
@@ -448,6 +456,20 @@ impl DebugProbe for EspUsbJtag {
         true
     }
 
+    fn try_get_xtensa_interface(
+        self: Box<Self>,
+    ) -> Result<XtensaCommunicationInterface, (Box<dyn DebugProbe>, DebugProbeError)> {
+        // The ESP32-S3 has this probe built in as well.
+        match XtensaCommunicationInterface::new(self) {
+            Ok(interface) => Ok(interface),
+            Err((probe, err)) => Err((probe.into_probe(), err)),
+        }
+    }
+
+    fn has_xtensa_interface(&self) -> bool {
+        true
+    }
+
     fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
         self
     }
@@ -26,6 +26,9 @@ const USB_PID: u16 = 0x1001;
 
 const VENDOR_DESCRIPTOR_JTAG_CAPABILITIES: u16 = 0x2000;
 
+// Vendor request to set the JTAG clock divisor.
+const VEND_JTAG_SETDIV: u8 = 0;
+
 pub(super) struct ProtocolHandler {
     // The USB device handle.
     device_handle: rusb::DeviceHandle<rusb::Context>,
@@ -41,6 +44,12 @@ pub(super) struct ProtocolHandler {
     input_buffer: Vec<u8>,
     pending_in_bits: usize,
 
+    /// The JTAG clock frequency before the divisor is applied.
+    base_speed_khz: u32,
+    /// The valid range for the JTAG clock divisor.
+    div_min: u16,
+    div_max: u16,
+
     ep_out: u8,
     ep_in: u8,
 }
@@ -51,6 +60,9 @@ impl Debug for ProtocolHandler {
             .field("command_queue", &self.command_queue)
             .field("output_buffer", &self.output_buffer)
             .field("input_buffer", &self.input_buffer)
+            .field("base_speed_khz", &self.base_speed_khz)
+            .field("div_min", &self.div_min)
+            .field("div_max", &self.div_max)
             .field("ep_out", &self.ep_out)
             .field("ep_in", &self.ep_in)
             .finish()
@@ -163,10 +175,9 @@ impl ProtocolHandler {
             USB_TIMEOUT,
         )?;
 
-        // TODO:
-        // let mut base_speed_khz = 1000;
-        // let mut div_min = 1;
-        // let mut div_max = 1;
+        let mut base_speed_khz = 1000;
+        let mut div_min = 1;
+        let mut div_max = 1;
 
         let protocol_version = buffer[0];
         log::debug!("{:?}", &buffer[..20]);
@@ -185,10 +196,17 @@ impl ProtocolHandler {
             let length = buffer[p + 1];
 
             if typ == JTAG_PROTOCOL_CAPABILITIES_SPEED_APB_TYPE {
-                // TODO:
-                // base_speed_khz = (((buffer[p + 3] as u16) << 8) | buffer[p + 2] as u16) * 10 / 2;
-                // div_min = ((buffer[p + 5] as u16) << 8) | buffer[p + 4] as u16;
-                // div_max = ((buffer[p + 7] as u16) << 8) | buffer[p + 6] as u16;
+                // The APB speed is given in units of 10 kHz, and the JTAG clock is half of it.
+                base_speed_khz = u16::from_le_bytes([buffer[p + 2], buffer[p + 3]]) as u32 * 10 / 2;
+                div_min = u16::from_le_bytes([buffer[p + 4], buffer[p + 5]]);
+                div_max = u16::from_le_bytes([buffer[p + 6], buffer[p + 7]]);
+
+                log::debug!(
+                    "Base speed: {} kHz, divisor range: {}..={}",
+                    base_speed_khz,
+                    div_min,
+                    div_max
+                );
             } else {
                 log::warn!("Unknown capabilities type {:01X?}", typ);
             }
@@ -210,9 +228,52 @@ impl ProtocolHandler {
             ep_out: ep_out.expect("This is a bug. Please report it."),
             ep_in: ep_in.expect("This is a bug. Please report it."),
             pending_in_bits: 0,
+            base_speed_khz,
+            div_min: div_min.max(1),
+            div_max: div_max.max(div_min).max(1),
         })
     }
 
+    /// The maximum JTAG clock frequency supported by the probe.
+    pub fn max_speed_khz(&self) -> u32 {
+        self.base_speed_khz / self.div_min as u32
+    }
+
+    /// Set the JTAG clock to the highest frequency which is not higher than `speed_khz`.
+    ///
+    /// Returns the actual frequency, or `None` if the requested frequency is too low.
+    pub fn set_speed_khz(&mut self, speed_khz: u32) -> Result<Option<u32>, DebugProbeError> {
+        if speed_khz == 0 {
+            return Ok(None);
+        }
+
+        // Round the divisor up, so that the resulting speed is not higher than requested.
+        let divisor = (self.base_speed_khz + speed_khz - 1) / speed_khz;
+
+        if divisor > self.div_max as u32 {
+            return Ok(None);
+        }
+
+        let divisor = divisor.max(self.div_min as u32) as u16;
+
+        self.device_handle
+            .write_control(
+                request_type(
+                    rusb::Direction::Out,
+                    rusb::RequestType::Vendor,
+                    rusb::Recipient::Device,
+                ),
+                VEND_JTAG_SETDIV,
+                divisor,
+                0,
+                &[],
+                USB_TIMEOUT,
+            )
+            .map_err(|e| DebugProbeError::Usb(Some(Box::new(e))))?;
+
+        Ok(Some(self.base_speed_khz / divisor as u32))
+    }
+
     /// Put a bit on TDI and possibly read one from TDO.
     pub fn jtag_io(
         &mut self,
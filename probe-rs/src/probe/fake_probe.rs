@@ -25,6 +25,11 @@ pub struct FakeProbe {
 
     dap_register_write_handler:
         Option<Box<dyn Fn(PortType, u8, u32) -> Result<(), DebugProbeError> + Send>>,
+
+    swj_sequence_handler: Option<Box<dyn Fn(u8, u64) -> Result<(), DebugProbeError> + Send>>,
+
+    /// The memory of the target, starting at address 0, or `None` for a small test pattern.
+    memory: Option<Vec<u8>>,
 }
 
 impl Debug for FakeProbe {
@@ -45,6 +50,21 @@ impl FakeProbe {
 
             dap_register_read_handler: None,
             dap_register_write_handler: None,
+            swj_sequence_handler: None,
+
+            memory: None,
+        }
+    }
+
+    /// Creates a new [`FakeProbe`], with the given contents of the target memory, starting at
+    /// address 0.
+    ///
+    /// The memory is accessed through the cores of a session, e.g. to test reading the RTT
+    /// buffers of the firmware.
+    pub fn with_mocked_memory(memory: Vec<u8>) -> Self {
+        FakeProbe {
+            memory: Some(memory),
+            ..FakeProbe::new()
         }
     }
 
@@ -66,6 +86,15 @@ impl FakeProbe {
         self.dap_register_write_handler = Some(handler);
     }
 
+    /// This sets the handler for SWJ sequences.
+    /// Can be used to check the sequences sent to the target.
+    pub fn set_swj_sequence_handler(
+        &mut self,
+        handler: Box<dyn Fn(u8, u64) -> Result<(), DebugProbeError> + Send>,
+    ) {
+        self.swj_sequence_handler = Some(handler);
+    }
+
     /// Makes a generic probe out of the [`FakeProbe`]
     pub fn into_probe(self) -> Probe {
         Probe::from_specific_probe(Box::new(self))
@@ -183,8 +212,12 @@ impl RawDapAccess for FakeProbe {
         }
     }
 
-    fn swj_sequence(&mut self, _bit_len: u8, _bits: u64) -> Result<(), DebugProbeError> {
-        todo!()
+    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        if let Some(handler) = &self.swj_sequence_handler {
+            handler(bit_len, bits)
+        } else {
+            Err(DebugProbeError::CommandNotSupportedByProbe("swj_sequence"))
+        }
     }
 
     fn swj_pins(
@@ -215,7 +248,10 @@ impl FakeArmInterface<Uninitialized> {
         let state = Uninitialized {
             use_overrun_detect: false,
         };
-        let memory_ap = MockMemoryAp::with_pattern();
+        let memory_ap = match &probe.memory {
+            Some(memory) => MockMemoryAp::with_memory(memory.clone()),
+            None => MockMemoryAp::with_pattern(),
+        };
 
         Self {
             probe,
@@ -239,11 +275,10 @@ impl FakeArmInterface<Initialized> {
         interface: FakeArmInterface<Uninitialized>,
         sequence: Arc<dyn ArmDebugSequence>,
     ) -> Self {
-        let memory_ap = MockMemoryAp::with_pattern();
         FakeArmInterface::<Initialized> {
             probe: interface.probe,
             _state: Initialized::new(sequence, false),
-            memory_ap,
+            memory_ap: interface.memory_ap,
         }
     }
 }
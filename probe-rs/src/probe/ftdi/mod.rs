@@ -1,10 +1,19 @@
 use crate::architecture::{
-    arm::communication_interface::UninitializedArmProbe,
+    arm::{
+        communication_interface::{DapProbe, UninitializedArmProbe},
+        ArmCommunicationInterface,
+    },
     riscv::communication_interface::RiscvCommunicationInterface,
+    xtensa::communication_interface::XtensaCommunicationInterface,
+};
+use crate::probe::{
+    arm_debug_interface::{ProbeStatistics, RawProtocolIo, SwdSettings},
+    scan_chain::{self, ChainParams},
+    JTAGAccess, ProbeCreationError,
 };
-use crate::probe::{JTAGAccess, ProbeCreationError};
 use crate::{
-    DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, DebugProbeType, WireProtocol,
+    DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, DebugProbeType,
+    ScanChainElement, WireProtocol,
 };
 use bitvec::{order::Lsb0, slice::BitSlice, vec::BitVec};
 use rusb::UsbContext;
@@ -21,20 +30,15 @@ use self::commands::{JtagCommand, WriteRegisterCommand};
 
 use super::{BatchExecutionError, CommandResult};
 
-#[derive(Debug)]
-struct JtagChainItem {
-    idcode: u32,
-    irlen: usize,
-}
-
-#[derive(Clone, Copy, Debug)]
-pub(super) struct ChainParams {
-    irpre: usize,
-    irpost: usize,
-    drpre: usize,
-    drpost: usize,
-    irlen: usize,
-}
+/// Initial value of the low byte GPIO pins.
+const PIN_OUTPUT: u8 = 0x08;
+/// Direction of the low byte GPIO pins, with TCK, TDI and TMS as outputs.
+const PIN_DIRECTION: u8 = 0x0b;
+/// Direction of the low byte GPIO pins, with TDI switched to an input.
+///
+/// For SWD, SWDIO is connected to TDO, and through a resistor to TDI.
+/// TDI is switched to an input whenever the target drives SWDIO.
+const PIN_DIRECTION_SWDIO_INPUT: u8 = 0x09;
 
 #[derive(Debug)]
 pub struct JtagAdapter {
@@ -64,8 +68,8 @@ impl JtagAdapter {
         let _ = self.device.read_to_end(&mut junk);
 
         // Minimal values, may not work with all probes
-        let output: u16 = 0x0008;
-        let direction: u16 = 0x000b;
+        let output: u16 = PIN_OUTPUT as u16;
+        let direction: u16 = PIN_DIRECTION as u16;
         self.device
             .write_all(&[0x80, output as u8, direction as u8])?;
         self.device
@@ -204,6 +208,95 @@ impl JtagAdapter {
         Ok(reply)
     }
 
+    /// Clock a sequence of bits on TMS and TDI, and return the bits read from TDO.
+    pub fn jtag_io(&mut self, tms: &[bool], tdi: &[bool]) -> io::Result<Vec<bool>> {
+        let mut command = vec![];
+
+        for (tms, tdi) in tms.iter().zip(tdi) {
+            // Clock a single bit on TMS, with TDI in bit 7, and read TDO.
+            command.extend_from_slice(&[0x6b, 0x00, u8::from(*tms) | u8::from(*tdi) << 7]);
+        }
+
+        // Send Immediate
+        command.push(0x87);
+
+        self.device.write_all(&command)?;
+
+        let reply = self.read_response(tms.len().min(tdi.len()))?;
+
+        Ok(reply.iter().map(|byte| byte & 0x80 != 0).collect())
+    }
+
+    /// Clock a SWD bit sequence, and return the bits seen on SWDIO.
+    ///
+    /// If `dir` is `true` for a bit, the probe drives SWDIO. Otherwise,
+    /// the line is released and sampled on the falling edge of the clock.
+    pub fn swd_io(&mut self, dir: &[bool], swdio: &[bool]) -> io::Result<Vec<bool>> {
+        assert_eq!(dir.len(), swdio.len());
+
+        let mut command = vec![];
+
+        // Position and length of the bits read by each command.
+        let mut reads = vec![];
+
+        let mut index = 0;
+        while index < dir.len() {
+            let output = dir[index];
+            let run_length = dir[index..].iter().take_while(|d| **d == output).count();
+
+            let direction = if output {
+                PIN_DIRECTION
+            } else {
+                PIN_DIRECTION_SWDIO_INPUT
+            };
+            command.extend_from_slice(&[0x80, PIN_OUTPUT, direction]);
+
+            let run_end = index + run_length;
+            for start in (index..run_end).step_by(8) {
+                let bits = (run_end - start).min(8);
+
+                if output {
+                    let byte = swdio[start..start + bits]
+                        .iter()
+                        .enumerate()
+                        .fold(0u8, |acc, (i, bit)| acc | u8::from(*bit) << i);
+
+                    // Clock bits out on the falling edge, LSB first
+                    command.extend_from_slice(&[0x1b, (bits - 1) as u8, byte]);
+                } else {
+                    // Clock bits in on the falling edge, LSB first
+                    command.extend_from_slice(&[0x2e, (bits - 1) as u8]);
+                    reads.push((start, bits));
+                }
+            }
+
+            index = run_end;
+        }
+
+        // Leave SWDIO driven by the probe.
+        command.extend_from_slice(&[0x80, PIN_OUTPUT, PIN_DIRECTION]);
+
+        // Send Immediate
+        command.push(0x87);
+
+        self.device.write_all(&command)?;
+
+        let reply = self.read_response(reads.len())?;
+
+        let mut result = swdio.to_vec();
+
+        for ((start, bits), byte) in reads.into_iter().zip(reply) {
+            // The bits are shifted in from the MSB.
+            let value = byte >> (8 - bits);
+
+            for bit in 0..bits {
+                result[start + bit] = (value >> bit) & 1 == 1;
+            }
+        }
+
+        Ok(result)
+    }
+
     /// Reset and go to RUN-TEST/IDLE
     pub fn reset(&mut self) -> io::Result<()> {
         self.shift_tms(&[0xff, 0xff, 0xff, 0xff, 0x7f], 40)
@@ -243,7 +336,7 @@ impl JtagAdapter {
         Ok(r)
     }
 
-    fn scan(&mut self) -> io::Result<Vec<JtagChainItem>> {
+    fn scan(&mut self) -> io::Result<Vec<ScanChainElement>> {
         let max_device_count = 8;
 
         self.reset()?;
@@ -255,7 +348,10 @@ impl JtagAdapter {
             let idcode = u32::from_le_bytes(r[i * 4..(i + 1) * 4].try_into().unwrap());
             if idcode != 0xffffffff {
                 log::debug!("tap found: {:08x}", idcode);
-                let target = JtagChainItem { idcode, irlen: 0 };
+                let target = ScanChainElement {
+                    idcode: Some(idcode),
+                    ir_len: 0,
+                };
                 targets.push(target);
             } else {
                 break;
@@ -284,7 +380,7 @@ impl JtagAdapter {
             let r = self.transfer_ir(&[0xFF, 0x00], 16)?;
 
             let irlen = r[1].count_ones() as usize;
-            targets[0].irlen = irlen;
+            targets[0].ir_len = irlen;
             log::debug!("tap irlen: {}", irlen);
         } else {
             let cmd = vec![0xff; max_device_count];
@@ -305,7 +401,7 @@ impl JtagAdapter {
                     ir >>= irlen;
                     irbits -= irlen;
                     log::debug!("tap {} irlen: {}", i, irlen);
-                    target.irlen = irlen as usize;
+                    target.ir_len = irlen as usize;
                 } else {
                     log::debug!("invalid irlen for tap {}", i);
                     return Err(io::Error::new(
@@ -319,39 +415,6 @@ impl JtagAdapter {
         Ok(targets)
     }
 
-    pub fn select_target(&mut self, idcode: u32) -> io::Result<()> {
-        let taps = self.scan()?;
-
-        let mut found = false;
-        let mut params = ChainParams {
-            irpre: 0,
-            irpost: 0,
-            drpre: 0,
-            drpost: 0,
-            irlen: 0,
-        };
-        for tap in taps {
-            if tap.idcode == idcode {
-                params.irlen = tap.irlen;
-                found = true;
-            } else if found {
-                params.irpost += tap.irlen;
-                params.drpost += 1;
-            } else {
-                params.irpre += tap.irlen;
-                params.drpre += 1;
-            }
-        }
-
-        if found {
-            log::debug!("Target chain params: {:?}", params);
-            self.chain_params = Some(params);
-            Ok(())
-        } else {
-            Err(io::Error::new(io::ErrorKind::NotFound, "target not found"))
-        }
-    }
-
     fn get_chain_params(&self) -> io::Result<ChainParams> {
         match &self.chain_params {
             Some(params) => Ok(*params),
@@ -417,8 +480,15 @@ impl JtagAdapter {
 #[derive(Debug)]
 pub struct FtdiProbe {
     adapter: JtagAdapter,
+    /// The scan chain, either set by the user or detected when attaching.
+    scan_chain: Option<Vec<ScanChainElement>>,
+    /// The index of the TAP used as the debug target, if selected by the user.
+    jtag_tap: Option<usize>,
     speed_khz: u32,
     idle_cycles: u8,
+    protocol: WireProtocol,
+    swd_settings: SwdSettings,
+    probe_statistics: ProbeStatistics,
 }
 
 impl DebugProbe for FtdiProbe {
@@ -442,8 +512,13 @@ impl DebugProbe for FtdiProbe {
 
         let probe = FtdiProbe {
             adapter,
+            scan_chain: None,
+            jtag_tap: None,
             speed_khz: 0,
             idle_cycles: 0,
+            protocol: WireProtocol::Jtag,
+            swd_settings: SwdSettings::default(),
+            probe_statistics: ProbeStatistics::default(),
         };
         log::debug!("opened probe: {:?}", probe);
         Ok(Box::new(probe))
@@ -470,34 +545,31 @@ impl DebugProbe for FtdiProbe {
             .attach()
             .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
 
-        let taps = self
-            .adapter
-            .scan()
-            .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
-        if taps.is_empty() {
-            log::warn!("no JTAG taps detected");
-            return Err(DebugProbeError::TargetNotFound);
+        if self.protocol == WireProtocol::Swd {
+            // Attaching is handled in sequence
+            return Ok(());
         }
-        if taps.len() == 1 {
-            self.adapter
-                .select_target(taps[0].idcode)
+
+        if self.scan_chain.is_none() {
+            let taps = self
+                .adapter
+                .scan()
                 .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
+
+            self.scan_chain = Some(taps);
         } else {
-            let known_idcodes = [
-                0x1000563d, // GD32VF103
-            ];
-            let idcode = taps
-                .iter()
-                .map(|tap| tap.idcode)
-                .find(|idcode| known_idcodes.iter().any(|v| v == idcode));
-            if let Some(idcode) = idcode {
-                self.adapter
-                    .select_target(idcode)
-                    .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
-            } else {
-                return Err(DebugProbeError::TargetNotFound);
-            }
+            self.adapter
+                .reset()
+                .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
         }
+
+        let params = scan_chain::select_tap(
+            self.scan_chain.as_deref().unwrap_or_default(),
+            self.jtag_tap,
+        )?;
+
+        self.adapter.chain_params = Some(params);
+
         Ok(())
     }
 
@@ -506,31 +578,43 @@ impl DebugProbe for FtdiProbe {
     }
 
     fn target_reset(&mut self) -> Result<(), DebugProbeError> {
-        log::error!("FTDI target_reset");
-        unimplemented!()
+        Err(DebugProbeError::NotImplemented("target_reset"))
     }
 
     fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
-        log::error!("FTDI target_reset_assert");
-        unimplemented!()
+        // There is no standard pin for the reset line on FTDI adapters.
+        Err(DebugProbeError::NotImplemented("target_reset_assert"))
     }
 
     fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
-        log::error!("FTDI target_reset_deassert");
-        unimplemented!()
+        Err(DebugProbeError::NotImplemented("target_reset_deassert"))
     }
 
     fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
-        if protocol != WireProtocol::Jtag {
-            Err(DebugProbeError::UnsupportedProtocol(protocol))
-        } else {
-            Ok(())
+        if protocol == WireProtocol::Cjtag {
+            return Err(DebugProbeError::UnsupportedProtocol(protocol));
         }
+
+        self.protocol = protocol;
+        Ok(())
     }
 
     fn active_protocol(&self) -> Option<WireProtocol> {
-        // Only supports JTAG
-        Some(WireProtocol::Jtag)
+        Some(self.protocol)
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: Vec<ScanChainElement>) -> Result<(), DebugProbeError> {
+        self.scan_chain = Some(scan_chain);
+        Ok(())
+    }
+
+    fn select_jtag_tap(&mut self, index: usize) -> Result<(), DebugProbeError> {
+        self.jtag_tap = Some(index);
+        Ok(())
+    }
+
+    fn scan_chain(&self) -> Result<&[ScanChainElement], DebugProbeError> {
+        Ok(self.scan_chain.as_deref().unwrap_or_default())
     }
 
     fn try_get_riscv_interface(
@@ -543,18 +627,81 @@ impl DebugProbe for FtdiProbe {
     }
 
     fn has_riscv_interface(&self) -> bool {
-        true
+        self.protocol == WireProtocol::Jtag
+    }
+
+    fn try_get_xtensa_interface(
+        self: Box<Self>,
+    ) -> Result<XtensaCommunicationInterface, (Box<dyn DebugProbe>, DebugProbeError)> {
+        match XtensaCommunicationInterface::new(self) {
+            Ok(interface) => Ok(interface),
+            Err((probe, err)) => Err((probe.into_probe(), err)),
+        }
+    }
+
+    fn has_xtensa_interface(&self) -> bool {
+        self.protocol == WireProtocol::Jtag
     }
 
     fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
         self
     }
 
+    fn has_arm_interface(&self) -> bool {
+        true
+    }
+
     fn try_get_arm_interface<'probe>(
         self: Box<Self>,
     ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
     {
-        todo!()
+        Ok(Box::new(ArmCommunicationInterface::new(self, true)))
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        Some(self)
+    }
+}
+
+impl DapProbe for FtdiProbe {}
+
+impl RawProtocolIo for FtdiProbe {
+    fn jtag_io<M, I>(&mut self, tms: M, tdi: I) -> Result<Vec<bool>, DebugProbeError>
+    where
+        M: IntoIterator<Item = bool>,
+        I: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+
+        let tms: Vec<bool> = tms.into_iter().collect();
+        let tdi: Vec<bool> = tdi.into_iter().collect();
+
+        self.adapter
+            .jtag_io(&tms, &tdi)
+            .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))
+    }
+
+    fn swd_io<D, S>(&mut self, dir: D, swdio: S) -> Result<Vec<bool>, DebugProbeError>
+    where
+        D: IntoIterator<Item = bool>,
+        S: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+
+        let dir: Vec<bool> = dir.into_iter().collect();
+        let swdio: Vec<bool> = swdio.into_iter().collect();
+
+        self.adapter
+            .swd_io(&dir, &swdio)
+            .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))
+    }
+
+    fn swd_settings(&self) -> &SwdSettings {
+        &self.swd_settings
+    }
+
+    fn probe_statistics(&mut self) -> &mut ProbeStatistics {
+        &mut self.probe_statistics
     }
 }
 
@@ -0,0 +1,600 @@
+//! A probe which bit-bangs SWD, JTAG or cJTAG using GPIO pins of the host.
+//!
+//! The pins are accessed through the Linux GPIO character device, so this
+//! works on any single board computer with a supported GPIO controller,
+//! for example using the header pins of a Raspberry Pi.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use gpio_cdev::{Chip, Line, LineHandle, LineRequestFlags};
+
+use crate::{
+    architecture::{
+        arm::{
+            communication_interface::{DapProbe, UninitializedArmProbe},
+            ArmCommunicationInterface,
+        },
+        riscv::communication_interface::RiscvCommunicationInterface,
+    },
+    probe::{
+        arm_debug_interface::{ProbeStatistics, RawProtocolIo, SwdSettings},
+        cjtag::{self, Oscan1Sequence},
+        scan_chain::{self, BitBangJtag, JtagChain},
+    },
+    DebugProbe, DebugProbeError, DebugProbeSelector, ProbeCapabilities, ProbeCreationError,
+    ScanChainElement, WireProtocol,
+};
+
+use super::JTAGAccess;
+
+/// Name used when requesting the GPIO lines.
+const CONSUMER: &str = "probe-rs";
+
+/// An error which occurred in the GPIO probe.
+#[derive(thiserror::Error, Debug)]
+pub enum GpioProbeError {
+    /// Accessing the GPIO lines failed.
+    #[error("GPIO access failed")]
+    Gpio(#[from] gpio_cdev::Error),
+    /// A pin which is required for the selected protocol is not configured.
+    #[error("The {0} pin is required, but not configured")]
+    MissingPin(&'static str),
+}
+
+impl From<GpioProbeError> for DebugProbeError {
+    fn from(error: GpioProbeError) -> Self {
+        DebugProbeError::ProbeSpecific(Box::new(error))
+    }
+}
+
+/// Configuration of the GPIO probe.
+///
+/// Pins are given as line offsets of the GPIO chip. The default
+/// configuration uses the same pins as OpenOCD uses for the Raspberry Pi.
+///
+/// The configuration can be parsed from a comma separated list of
+/// `key=value` pairs, for example `chip=/dev/gpiochip0,swclk=11,swdio=25`.
+/// The following keys are supported:
+///
+/// - `chip`: Path of the GPIO chip.
+/// - `swclk` or `tck`: Pin used for SWCLK and TCK, and for TCKC with cJTAG.
+/// - `swdio` or `tms`: Pin used for SWDIO and TMS, and for TMSC with cJTAG.
+/// - `tdi`, `tdo`: Pins only used for JTAG.
+/// - `reset`: Pin connected to nRESET of the target.
+/// - `delay_ns`: Time to wait for every half of a clock cycle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpioProbeConfig {
+    /// Path of the GPIO chip.
+    pub chip: PathBuf,
+    /// Pin used for SWCLK and TCK.
+    pub swclk_tck: u32,
+    /// Pin used for SWDIO and TMS.
+    pub swdio_tms: u32,
+    /// Pin used for TDI, only required for JTAG.
+    pub tdi: Option<u32>,
+    /// Pin used for TDO, only required for JTAG.
+    pub tdo: Option<u32>,
+    /// Pin connected to the active low reset of the target.
+    pub nreset: Option<u32>,
+    /// Time to wait for every half of a clock cycle.
+    pub half_period: Duration,
+}
+
+impl Default for GpioProbeConfig {
+    fn default() -> Self {
+        Self {
+            chip: PathBuf::from("/dev/gpiochip0"),
+            swclk_tck: 11,
+            swdio_tms: 25,
+            tdi: Some(10),
+            tdo: Some(9),
+            nreset: Some(24),
+            half_period: Duration::ZERO,
+        }
+    }
+}
+
+impl std::str::FromStr for GpioProbeConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = GpioProbeConfig::default();
+
+        for option in s.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option
+                .split_once('=')
+                .ok_or_else(|| format!("Expected 'key=value', found '{}'", option))?;
+
+            let number = || {
+                value
+                    .parse::<u32>()
+                    .map_err(|e| format!("Invalid value '{}' for '{}': {}", value, key, e))
+            };
+
+            match key {
+                "chip" => config.chip = PathBuf::from(value),
+                "swclk" | "tck" => config.swclk_tck = number()?,
+                "swdio" | "tms" => config.swdio_tms = number()?,
+                "tdi" => config.tdi = Some(number()?),
+                "tdo" => config.tdo = Some(number()?),
+                "reset" => config.nreset = Some(number()?),
+                "delay_ns" => config.half_period = Duration::from_nanos(number()? as u64),
+                _ => return Err(format!("Unknown option '{}'", key)),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// The SWDIO/TMS line, which changes direction for SWD.
+#[derive(Debug)]
+struct SwdioLine {
+    line: Line,
+    handle: Option<LineHandle>,
+    output: bool,
+}
+
+impl SwdioLine {
+    fn new(line: Line) -> Result<Self, GpioProbeError> {
+        let handle = line.request(LineRequestFlags::OUTPUT, 1, CONSUMER)?;
+
+        Ok(Self {
+            line,
+            handle: Some(handle),
+            output: true,
+        })
+    }
+
+    fn handle(&self) -> &LineHandle {
+        // The handle is only missing while the direction is changed.
+        self.handle.as_ref().unwrap()
+    }
+
+    fn set_direction(&mut self, output: bool, value: bool) -> Result<(), GpioProbeError> {
+        if output == self.output {
+            return Ok(());
+        }
+
+        // The line has to be released before it can be requested again.
+        self.handle = None;
+
+        let flags = if output {
+            LineRequestFlags::OUTPUT
+        } else {
+            LineRequestFlags::INPUT
+        };
+
+        self.handle = Some(self.line.request(flags, u8::from(value), CONSUMER)?);
+        self.output = output;
+
+        Ok(())
+    }
+}
+
+/// A probe using GPIO pins of the host.
+#[derive(Debug)]
+pub struct GpioProbe {
+    swclk_tck: LineHandle,
+    swdio_tms: SwdioLine,
+    tdi: Option<LineHandle>,
+    tdo: Option<LineHandle>,
+    nreset: Option<LineHandle>,
+    half_period: Duration,
+
+    protocol: WireProtocol,
+
+    jtag: JtagChain,
+
+    swd_settings: SwdSettings,
+    probe_statistics: ProbeStatistics,
+}
+
+impl GpioProbe {
+    /// Request the GPIO lines given in the configuration.
+    pub fn new(config: &GpioProbeConfig) -> Result<Box<Self>, DebugProbeError> {
+        let mut chip = Chip::new(&config.chip).map_err(GpioProbeError::from)?;
+
+        let mut output = |offset: u32, flags: LineRequestFlags, value: u8| {
+            chip.get_line(offset)
+                .and_then(|line| line.request(flags, value, CONSUMER))
+                .map_err(GpioProbeError::from)
+        };
+
+        let swclk_tck = output(config.swclk_tck, LineRequestFlags::OUTPUT, 0)?;
+        let tdi = config
+            .tdi
+            .map(|offset| output(offset, LineRequestFlags::OUTPUT, 0))
+            .transpose()?;
+        // The reset is open drain, so the target can also use it.
+        let nreset = config
+            .nreset
+            .map(|offset| {
+                output(
+                    offset,
+                    LineRequestFlags::OUTPUT | LineRequestFlags::OPEN_DRAIN,
+                    1,
+                )
+            })
+            .transpose()?;
+
+        let tdo = config
+            .tdo
+            .map(|offset| {
+                chip.get_line(offset)
+                    .and_then(|line| line.request(LineRequestFlags::INPUT, 0, CONSUMER))
+                    .map_err(GpioProbeError::from)
+            })
+            .transpose()?;
+
+        let swdio_tms = chip
+            .get_line(config.swdio_tms)
+            .map_err(GpioProbeError::from)
+            .and_then(SwdioLine::new)?;
+
+        Ok(Box::new(Self {
+            swclk_tck,
+            swdio_tms,
+            tdi,
+            tdo,
+            nreset,
+            half_period: config.half_period,
+            protocol: WireProtocol::Swd,
+            jtag: JtagChain::default(),
+            swd_settings: SwdSettings::default(),
+            probe_statistics: ProbeStatistics::default(),
+        }))
+    }
+
+    /// Wait for half a clock period.
+    fn delay(&self) {
+        if self.half_period.is_zero() {
+            return;
+        }
+
+        // Sleeping is far too coarse for typical clock periods.
+        let start = Instant::now();
+        while start.elapsed() < self.half_period {
+            std::hint::spin_loop();
+        }
+    }
+
+    fn jtag_bit(&mut self, tms: bool, tdi: bool) -> Result<bool, GpioProbeError> {
+        let tdi_line = self.tdi.as_ref().ok_or(GpioProbeError::MissingPin("TDI"))?;
+        let tdo_line = self.tdo.as_ref().ok_or(GpioProbeError::MissingPin("TDO"))?;
+
+        self.swdio_tms.handle().set_value(u8::from(tms))?;
+        tdi_line.set_value(u8::from(tdi))?;
+        self.delay();
+
+        // TDO changes on the falling edge, so sample it before the rising edge.
+        let tdo = tdo_line.get_value()? != 0;
+
+        self.swclk_tck.set_value(1)?;
+        self.delay();
+        self.swclk_tck.set_value(0)?;
+
+        Ok(tdo)
+    }
+
+    fn swd_bit(&mut self, output: bool, swdio: bool) -> Result<bool, GpioProbeError> {
+        self.swdio_tms.set_direction(output, swdio)?;
+
+        if output {
+            self.swdio_tms.handle().set_value(u8::from(swdio))?;
+        }
+
+        self.delay();
+        self.swclk_tck.set_value(1)?;
+        self.delay();
+
+        // The target drives SWDIO on the rising edge, so sample it
+        // before the falling edge.
+        let value = if output {
+            swdio
+        } else {
+            self.swdio_tms.handle().get_value()? != 0
+        };
+
+        self.swclk_tck.set_value(0)?;
+
+        Ok(value)
+    }
+
+    /// Switch the TAP to the OScan1 format of cJTAG.
+    fn activate_cjtag(&mut self) -> Result<(), GpioProbeError> {
+        self.swdio_tms.set_direction(true, true)?;
+
+        for (tckc, tmsc) in cjtag::activation_sequence() {
+            self.swdio_tms.handle().set_value(u8::from(tmsc))?;
+            self.swclk_tck.set_value(u8::from(tckc))?;
+            self.delay();
+        }
+
+        Ok(())
+    }
+}
+
+impl DebugProbe for GpioProbe {
+    fn new_from_selector(
+        _selector: impl Into<DebugProbeSelector>,
+    ) -> Result<Box<Self>, DebugProbeError>
+    where
+        Self: Sized,
+    {
+        // GPIO pins can't be discovered, see `GpioProbe::new`.
+        Err(DebugProbeError::ProbeCouldNotBeCreated(
+            ProbeCreationError::NotFound,
+        ))
+    }
+
+    fn get_name(&self) -> &str {
+        "GPIO"
+    }
+
+    fn speed_khz(&self) -> u32 {
+        match self.half_period.as_nanos() {
+            // Not limited, the speed depends on the GPIO controller
+            0 => 0,
+            ns => (1_000_000 / (2 * ns)) as u32,
+        }
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        if speed_khz == 0 {
+            return Err(DebugProbeError::UnsupportedSpeed(speed_khz));
+        }
+
+        self.half_period = Duration::from_nanos(1_000_000 / (2 * speed_khz as u64));
+
+        Ok(self.speed_khz())
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        log::debug!("Attaching to GPIO probe");
+
+        if self.protocol == WireProtocol::Swd {
+            // Attaching is handled in sequence
+            return Ok(());
+        }
+
+        if self.protocol == WireProtocol::Cjtag {
+            self.activate_cjtag()?;
+        } else {
+            self.swdio_tms.set_direction(true, true)?;
+        }
+
+        scan_chain::attach(self)?;
+
+        Ok(())
+    }
+
+    fn detach(&mut self) -> Result<(), DebugProbeError> {
+        Ok(())
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        self.target_reset_assert()?;
+        std::thread::sleep(Duration::from_millis(100));
+        self.target_reset_deassert()
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        let nreset = self
+            .nreset
+            .as_ref()
+            .ok_or(GpioProbeError::MissingPin("nRESET"))?;
+
+        nreset.set_value(0).map_err(GpioProbeError::from)?;
+
+        Ok(())
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        let nreset = self
+            .nreset
+            .as_ref()
+            .ok_or(GpioProbeError::MissingPin("nRESET"))?;
+
+        nreset.set_value(1).map_err(GpioProbeError::from)?;
+
+        Ok(())
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        if protocol == WireProtocol::Jtag && (self.tdi.is_none() || self.tdo.is_none()) {
+            return Err(DebugProbeError::UnsupportedProtocol(protocol));
+        }
+
+        self.protocol = protocol;
+
+        Ok(())
+    }
+
+    fn active_protocol(&self) -> Option<WireProtocol> {
+        Some(self.protocol)
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: Vec<ScanChainElement>) -> Result<(), DebugProbeError> {
+        self.jtag.set_scan_chain(scan_chain);
+        Ok(())
+    }
+
+    fn select_jtag_tap(&mut self, index: usize) -> Result<(), DebugProbeError> {
+        self.jtag.select_tap(index);
+        Ok(())
+    }
+
+    fn scan_chain(&self) -> Result<&[ScanChainElement], DebugProbeError> {
+        Ok(self.jtag.scan_chain())
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        true
+    }
+
+    fn try_get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
+    {
+        Ok(Box::new(ArmCommunicationInterface::new(self, true)))
+    }
+
+    fn has_riscv_interface(&self) -> bool {
+        self.protocol.is_jtag()
+    }
+
+    fn try_get_riscv_interface(
+        self: Box<Self>,
+    ) -> Result<RiscvCommunicationInterface, (Box<dyn DebugProbe>, DebugProbeError)> {
+        if self.protocol == WireProtocol::Swd {
+            return Err((
+                self,
+                DebugProbeError::UnsupportedProtocol(WireProtocol::Swd),
+            ));
+        }
+
+        match RiscvCommunicationInterface::new(self) {
+            Ok(interface) => Ok(interface),
+            Err((probe, err)) => Err((probe.into_probe(), err)),
+        }
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        Some(self)
+    }
+
+    fn capabilities(&self) -> ProbeCapabilities {
+        let mut capabilities = ProbeCapabilities::default();
+
+        capabilities.protocols.push(WireProtocol::Swd);
+        if self.tdi.is_some() && self.tdo.is_some() {
+            capabilities.protocols.push(WireProtocol::Jtag);
+        }
+        capabilities.protocols.push(WireProtocol::Cjtag);
+
+        capabilities
+    }
+}
+
+impl DapProbe for GpioProbe {}
+
+impl RawProtocolIo for GpioProbe {
+    fn jtag_io<M, I>(&mut self, tms: M, tdi: I) -> Result<Vec<bool>, DebugProbeError>
+    where
+        M: IntoIterator<Item = bool>,
+        I: IntoIterator<Item = bool>,
+    {
+        if self.protocol == WireProtocol::Cjtag {
+            let sequence = Oscan1Sequence::encode(tms, tdi);
+            let captured = self.swd_io(sequence.direction, sequence.tmsc)?;
+
+            return Ok(cjtag::decode_tdo(&captured));
+        }
+
+        self.probe_statistics.report_io();
+
+        let mut tdo = Vec::new();
+
+        for (tms, tdi) in tms.into_iter().zip(tdi) {
+            tdo.push(self.jtag_bit(tms, tdi)?);
+        }
+
+        Ok(tdo)
+    }
+
+    fn swd_io<D, S>(&mut self, dir: D, swdio: S) -> Result<Vec<bool>, DebugProbeError>
+    where
+        D: IntoIterator<Item = bool>,
+        S: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+
+        let mut result = Vec::new();
+
+        for (output, swdio) in dir.into_iter().zip(swdio) {
+            result.push(self.swd_bit(output, swdio)?);
+        }
+
+        Ok(result)
+    }
+
+    fn swd_settings(&self) -> &SwdSettings {
+        &self.swd_settings
+    }
+
+    fn probe_statistics(&mut self) -> &mut ProbeStatistics {
+        &mut self.probe_statistics
+    }
+}
+
+impl JTAGAccess for GpioProbe {
+    fn set_ir_len(&mut self, len: u32) {
+        self.jtag.set_ir_len(len);
+    }
+
+    fn read_register(&mut self, address: u32, len: u32) -> Result<Vec<u8>, DebugProbeError> {
+        let data = vec![0u8; (len as usize + 7) / 8];
+
+        scan_chain::transfer_register(self, address, &data, len)
+    }
+
+    fn write_register(
+        &mut self,
+        address: u32,
+        data: &[u8],
+        len: u32,
+    ) -> Result<Vec<u8>, DebugProbeError> {
+        scan_chain::transfer_register(self, address, data, len)
+    }
+
+    fn set_idle_cycles(&mut self, idle_cycles: u8) {
+        self.jtag.set_idle_cycles(idle_cycles);
+    }
+
+    fn get_idle_cycles(&self) -> u8 {
+        self.jtag.idle_cycles()
+    }
+}
+
+impl BitBangJtag for GpioProbe {
+    fn jtag_chain(&mut self) -> &mut JtagChain {
+        &mut self.jtag
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let config: GpioProbeConfig = "chip=/dev/gpiochip1,swclk=4,swdio=5,reset=6,delay_ns=500"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            config,
+            GpioProbeConfig {
+                chip: PathBuf::from("/dev/gpiochip1"),
+                swclk_tck: 4,
+                swdio_tms: 5,
+                nreset: Some(6),
+                half_period: Duration::from_nanos(500),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_invalid_config() {
+        assert!("swclk=x".parse::<GpioProbeConfig>().is_err());
+        assert!("speed=1000".parse::<GpioProbeConfig>().is_err());
+        assert!("swclk".parse::<GpioProbeConfig>().is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! Support for the TI ICEPick JTAG router.
+//!
+//! On many TI devices, e.g. the CC13xx/CC26xx, only the ICEPick TAP is in the scan chain
+//! after a reset. The other TAPs of the device, like the Cortex-M debug port, have to be
+//! connected to the scan chain using the router of the ICEPick. A connected TAP is
+//! inserted into the scan chain before the ICEPick, closer to TDO.
+
+use crate::probe::arm_debug_interface::RawProtocolIo;
+use crate::probe::scan_chain::{self, ChainParams, ScanChainElement};
+use crate::DebugProbeError;
+
+/// IDCODEs of ICEPick TAPs, without the version bits.
+const ICEPICK_IDCODES: &[u32] = &[
+    0x0B99_A02F, // ICEPick-C of the CC13x0/CC26x0
+    0x0BB4_102F, // ICEPick-C of the CC13x2/CC26x2
+];
+
+/// The length of the IR register of the ICEPick.
+const IR_LEN: usize = 6;
+
+/// Selects the ROUTER register, which configures the connected TAPs.
+const IR_ROUTER: u32 = 0x02;
+/// Selects the CONNECT register, which enables the router.
+const IR_CONNECT: u32 = 0x07;
+/// Selects the BYPASS register.
+const IR_BYPASS: u32 = 0x3F;
+
+/// Write the connect key to the CONNECT register, which enables writes to the router.
+const CONNECT_WRITE_KEY: u32 = 0x89;
+
+/// The router block of the debug TAPs.
+const BLOCK_DEBUG_TAP: u32 = 0x2;
+
+/// Connect the debug TAP to the scan chain.
+const SDTAP_SELECT: u32 = 1 << 8;
+/// Keep the debug TAP powered.
+const SDTAP_FORCE_ACTIVE: u32 = 1 << 3;
+/// Keep the device from entering a sleep mode, which disconnects the debug TAP.
+const SDTAP_INHIBIT_SLEEP: u32 = 1 << 13;
+
+/// The number of cycles in Run-Test/Idle, which the router needs to insert the TAP.
+const CONNECT_IDLE_CYCLES: usize = 10;
+
+/// The debug TAP of the Cortex-M core on the CC13xx/CC26xx.
+pub(crate) const CORTEX_M_DEBUG_TAP: u32 = 0;
+
+/// Check if the IDCODE belongs to an ICEPick.
+fn is_icepick(idcode: u32) -> bool {
+    ICEPICK_IDCODES.contains(&(idcode & 0x0FFF_FFFF))
+}
+
+/// Find the index of an ICEPick in the scan chain.
+pub(crate) fn find(chain: &[ScanChainElement]) -> Option<usize> {
+    chain
+        .iter()
+        .position(|tap| tap.ir_len == IR_LEN && tap.idcode.map(is_icepick).unwrap_or(false))
+}
+
+/// The value of the ROUTER register, which writes `value` to the `register` of `block`.
+fn router_write(block: u32, register: u32, value: u32) -> u32 {
+    1 << 31 | block << 28 | register << 24 | (value & 0x00FF_FFFF)
+}
+
+/// Connect the debug TAP `tap` to the scan chain, using the ICEPick with the given index.
+///
+/// The scan chain has to be detected again afterwards.
+pub(crate) fn connect_debug_tap<P: RawProtocolIo + ?Sized>(
+    probe: &mut P,
+    chain: &[ScanChainElement],
+    index: usize,
+    tap: u32,
+) -> Result<(), DebugProbeError> {
+    let params = ChainParams::from_chain(chain, index).ok_or(DebugProbeError::TargetNotFound)?;
+
+    log::debug!("Connecting debug TAP {} using the ICEPick", tap);
+
+    scan_chain::shift(probe, true, &params.ir_bits(IR_CONNECT, IR_LEN))?;
+    scan_chain::shift(probe, false, &params.dr_bits(bits(CONNECT_WRITE_KEY, 8)))?;
+
+    let router = router_write(
+        BLOCK_DEBUG_TAP,
+        tap,
+        SDTAP_INHIBIT_SLEEP | SDTAP_SELECT | SDTAP_FORCE_ACTIVE,
+    );
+
+    scan_chain::shift(probe, true, &params.ir_bits(IR_ROUTER, IR_LEN))?;
+    scan_chain::shift(probe, false, &params.dr_bits(bits(router, 32)))?;
+
+    scan_chain::shift(probe, true, &params.ir_bits(IR_BYPASS, IR_LEN))?;
+
+    probe.jtag_io([false; CONNECT_IDLE_CYCLES], [false; CONNECT_IDLE_CYCLES])?;
+
+    Ok(())
+}
+
+/// The lowest `len` bits of `value`, LSB first.
+fn bits(value: u32, len: usize) -> impl Iterator<Item = bool> {
+    (0..len).map(move |i| value & (1 << i) != 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        find, router_write, ScanChainElement, BLOCK_DEBUG_TAP, SDTAP_FORCE_ACTIVE,
+        SDTAP_INHIBIT_SLEEP, SDTAP_SELECT,
+    };
+
+    #[test]
+    fn router_value() {
+        let flags = SDTAP_INHIBIT_SLEEP | SDTAP_SELECT | SDTAP_FORCE_ACTIVE;
+
+        assert_eq!(router_write(BLOCK_DEBUG_TAP, 0, flags), 0xA000_2108);
+        assert_eq!(router_write(BLOCK_DEBUG_TAP, 1, flags), 0xA100_2108);
+    }
+
+    #[test]
+    fn find_icepick() {
+        let chain = [
+            ScanChainElement {
+                idcode: Some(0x4ba0_0477),
+                ir_len: 4,
+            },
+            ScanChainElement {
+                idcode: Some(0x3b99_a02f),
+                ir_len: 6,
+            },
+        ];
+
+        assert_eq!(find(&chain), Some(1));
+        assert_eq!(find(&chain[..1]), None);
+    }
+}
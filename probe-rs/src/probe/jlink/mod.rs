@@ -14,17 +14,16 @@ use crate::{
             swo::SwoConfig, ArmCommunicationInterface, SwoAccess,
         },
         riscv::communication_interface::RiscvCommunicationInterface,
+        xtensa::communication_interface::XtensaCommunicationInterface,
     },
     probe::{
-        DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeType, JTAGAccess, WireProtocol,
+        arm_debug_interface::{ProbeStatistics, RawProtocolIo, SwdSettings},
+        DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeType, JTAGAccess, ProbeCapabilities,
+        WireProtocol,
     },
     DebugProbeSelector, Error as ProbeRsError,
 };
 
-use self::arm::{ProbeStatistics, SwdSettings};
-
-mod arm;
-
 const SWO_BUFFER_SIZE: u16 = 128;
 
 #[derive(Debug)]
@@ -49,6 +48,9 @@ pub(crate) struct JLink {
 
     speed_khz: u32,
 
+    /// Highest speed supported by the J-Link, read when the speed is set.
+    max_speed_khz: Option<u32>,
+
     probe_statistics: ProbeStatistics,
     swd_settings: SwdSettings,
 }
@@ -69,6 +71,9 @@ impl JLink {
                 let jlink_interface = match protocol {
                     WireProtocol::Swd => jaylink::Interface::Swd,
                     WireProtocol::Jtag => jaylink::Interface::Jtag,
+                    WireProtocol::Cjtag => {
+                        return Err(DebugProbeError::UnsupportedProtocol(protocol))
+                    }
                 };
 
                 if self.handle.available_interfaces().contains(jlink_interface) {
@@ -396,6 +401,7 @@ impl DebugProbe for JLink {
             protocol: None,
             current_ir_reg: 1,
             speed_khz: 0,
+            max_speed_khz: None,
             swd_settings: SwdSettings::default(),
             probe_statistics: ProbeStatistics::default(),
         }))
@@ -436,6 +442,7 @@ impl DebugProbe for JLink {
             log::debug!("Supported speeds: {:?}", speeds);
 
             let max_speed_khz = speeds.max_speed_hz() / 1000;
+            self.max_speed_khz = Some(max_speed_khz);
 
             if max_speed_khz < speed_khz {
                 return Err(DebugProbeError::UnsupportedSpeed(speed_khz));
@@ -505,7 +512,7 @@ impl DebugProbe for JLink {
         }
 
         match actual_protocol {
-            WireProtocol::Jtag => {
+            WireProtocol::Jtag | WireProtocol::Cjtag => {
                 // try some JTAG stuff
 
                 log::debug!("Resetting JTAG chain using trst");
@@ -596,6 +603,26 @@ impl DebugProbe for JLink {
         self.supported_protocols.contains(&WireProtocol::Jtag)
     }
 
+    fn try_get_xtensa_interface(
+        self: Box<Self>,
+    ) -> Result<XtensaCommunicationInterface, (Box<dyn DebugProbe>, DebugProbeError)> {
+        if self.supported_protocols.contains(&WireProtocol::Jtag) {
+            match XtensaCommunicationInterface::new(self) {
+                Ok(interface) => Ok(interface),
+                Err((probe, err)) => Err((probe.into_probe(), err)),
+            }
+        } else {
+            Err((
+                RawDapAccess::into_probe(self),
+                DebugProbeError::InterfaceNotAvailable("JTAG"),
+            ))
+        }
+    }
+
+    fn has_xtensa_interface(&self) -> bool {
+        self.supported_protocols.contains(&WireProtocol::Jtag)
+    }
+
     fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
         self
     }
@@ -617,6 +644,23 @@ impl DebugProbe for JLink {
         // Convert the integer millivolts value from self.handle to volts as an f32.
         Ok(Some((self.handle.read_target_voltage()? as f32) / 1000f32))
     }
+
+    fn capabilities(&self) -> ProbeCapabilities {
+        let jlink_capabilities = self.handle.capabilities();
+        let mut capabilities = ProbeCapabilities::default();
+
+        capabilities.max_speed_khz = self.max_speed_khz;
+        capabilities.protocols = self.supported_protocols.clone();
+        if jlink_capabilities.contains(Capability::Swo) {
+            capabilities
+                .swo_modes
+                .push(crate::architecture::arm::SwoMode::Uart);
+        }
+        capabilities.target_power = jlink_capabilities.contains(Capability::SetKsPower);
+        capabilities.target_voltage = true;
+
+        capabilities
+    }
 }
 
 impl JTAGAccess for JLink {
@@ -680,6 +724,48 @@ impl JTAGAccess for JLink {
 
 impl DapProbe for JLink {}
 
+impl RawProtocolIo for JLink {
+    fn jtag_io<M, I>(&mut self, tms: M, tdi: I) -> Result<Vec<bool>, DebugProbeError>
+    where
+        M: IntoIterator<Item = bool>,
+        I: IntoIterator<Item = bool>,
+    {
+        if self.protocol.unwrap() == crate::WireProtocol::Swd {
+            panic!("Logic error, requested jtag_io when in SWD mode");
+        }
+
+        self.probe_statistics.report_io();
+
+        let iter = self.handle.jtag_io(tms, tdi)?;
+
+        Ok(iter.collect())
+    }
+
+    fn swd_io<D, S>(&mut self, dir: D, swdio: S) -> Result<Vec<bool>, DebugProbeError>
+    where
+        D: IntoIterator<Item = bool>,
+        S: IntoIterator<Item = bool>,
+    {
+        if self.protocol.unwrap() == crate::WireProtocol::Jtag {
+            panic!("Logic error, requested swd_io when in JTAG mode");
+        }
+
+        self.probe_statistics.report_io();
+
+        let iter = self.handle.swd_io(dir, swdio)?;
+
+        Ok(iter.collect())
+    }
+
+    fn swd_settings(&self) -> &SwdSettings {
+        &self.swd_settings
+    }
+
+    fn probe_statistics(&mut self) -> &mut ProbeStatistics {
+        &mut self.probe_statistics
+    }
+}
+
 impl SwoAccess for JLink {
     fn enable_swo(&mut self, config: &SwoConfig) -> Result<(), ProbeRsError> {
         self.swo_config = Some(*config);
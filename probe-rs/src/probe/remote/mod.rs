@@ -0,0 +1,333 @@
+//! Access to probes attached to another machine.
+//!
+//! A [`RemoteProbeServer`] exposes a locally attached probe over TCP, and
+//! a [`RemoteProbe`] forwards all probe operations to such a server. This
+//! makes it possible to debug targets which are attached to a lab machine
+//! or a CI rack from a developer workstation.
+//!
+//! Only raw DAP access is forwarded, so ARM targets can be debugged if the
+//! probe attached to the server supports raw DAP access.
+
+mod protocol;
+mod server;
+
+use std::io::{BufReader, BufWriter};
+use std::net::{TcpStream, ToSocketAddrs};
+
+use crate::{
+    architecture::arm::{
+        communication_interface::{DapProbe, UninitializedArmProbe},
+        ArmCommunicationInterface, DpAddress, PortType, RawDapAccess,
+    },
+    DebugProbe, DebugProbeError, DebugProbeSelector, ProbeCreationError, WireProtocol,
+};
+
+use self::protocol::{read_message, write_message, Request, Response, PROTOCOL_VERSION};
+
+pub use self::server::RemoteProbeServer;
+
+/// An error which occurred while communicating with a remote probe.
+#[derive(thiserror::Error, Debug)]
+pub enum RemoteProbeError {
+    /// The connection to the other side failed.
+    #[error("Communication with the remote probe failed")]
+    Io(#[from] std::io::Error),
+    /// A message could not be encoded or decoded.
+    #[error("Invalid message")]
+    Encoding(#[from] bincode::Error),
+    /// A message was larger than the allowed maximum.
+    #[error("Message of {0} bytes is too large")]
+    MessageTooLarge(usize),
+    /// The server uses an incompatible version of the protocol.
+    #[error("The probe server uses protocol version {0}, but version {1} is required")]
+    VersionMismatch(u32, u32),
+    /// The server sent a response which doesn't match the request.
+    #[error("Unexpected response from the probe server")]
+    UnexpectedResponse,
+    /// The probe on the server reported an error.
+    #[error("Error on the probe server: {0}")]
+    Server(String),
+}
+
+impl From<RemoteProbeError> for DebugProbeError {
+    fn from(error: RemoteProbeError) -> Self {
+        DebugProbeError::ProbeSpecific(Box::new(error))
+    }
+}
+
+/// A probe which is attached to another machine, and accessed
+/// through a [`RemoteProbeServer`].
+#[derive(Debug)]
+pub struct RemoteProbe {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+
+    name: String,
+    speed_khz: u32,
+    protocol: Option<WireProtocol>,
+    has_arm_interface: bool,
+}
+
+impl RemoteProbe {
+    /// Connect to the probe server at the given address.
+    pub fn connect(address: impl ToSocketAddrs) -> Result<Box<Self>, DebugProbeError> {
+        let stream = TcpStream::connect(address).map_err(RemoteProbeError::from)?;
+
+        stream.set_nodelay(true).map_err(RemoteProbeError::from)?;
+
+        let peer = stream.peer_addr().map_err(RemoteProbeError::from)?;
+
+        let mut probe = Self {
+            reader: BufReader::new(stream.try_clone().map_err(RemoteProbeError::from)?),
+            writer: BufWriter::new(stream),
+            name: String::new(),
+            speed_khz: 0,
+            protocol: None,
+            has_arm_interface: false,
+        };
+
+        match probe.request(Request::Hello {
+            version: PROTOCOL_VERSION,
+        })? {
+            Response::Hello {
+                version,
+                name,
+                speed_khz,
+                protocol,
+                has_arm_interface,
+            } => {
+                if version != PROTOCOL_VERSION {
+                    return Err(RemoteProbeError::VersionMismatch(version, PROTOCOL_VERSION).into());
+                }
+
+                log::debug!("Connected to {} at {}", name, peer);
+
+                probe.name = format!("{} (remote, {})", name, peer);
+                probe.speed_khz = speed_khz;
+                probe.protocol = protocol;
+                probe.has_arm_interface = has_arm_interface;
+            }
+            _ => return Err(RemoteProbeError::UnexpectedResponse.into()),
+        }
+
+        Ok(Box::new(probe))
+    }
+
+    /// Send a request, and wait for the response.
+    fn request(&mut self, request: Request) -> Result<Response, DebugProbeError> {
+        write_message(&mut self.writer, &request)?;
+
+        match read_message(&mut self.reader)? {
+            Response::Error(error) => Err(error.into()),
+            response => Ok(response),
+        }
+    }
+
+    /// Send a request which is answered with [`Response::Ok`].
+    fn request_ok(&mut self, request: Request) -> Result<(), DebugProbeError> {
+        match self.request(request)? {
+            Response::Ok => Ok(()),
+            _ => Err(RemoteProbeError::UnexpectedResponse.into()),
+        }
+    }
+
+    /// Send a request which is answered with [`Response::Value`].
+    fn request_value(&mut self, request: Request) -> Result<u32, DebugProbeError> {
+        match self.request(request)? {
+            Response::Value(value) => Ok(value),
+            _ => Err(RemoteProbeError::UnexpectedResponse.into()),
+        }
+    }
+
+    /// Send a request which is answered with [`Response::Protocol`].
+    fn request_protocol(&mut self, request: Request) -> Result<(), DebugProbeError> {
+        match self.request(request)? {
+            Response::Protocol(protocol) => {
+                self.protocol = protocol;
+                Ok(())
+            }
+            _ => Err(RemoteProbeError::UnexpectedResponse.into()),
+        }
+    }
+}
+
+impl DebugProbe for RemoteProbe {
+    fn new_from_selector(
+        _selector: impl Into<DebugProbeSelector>,
+    ) -> Result<Box<Self>, DebugProbeError>
+    where
+        Self: Sized,
+    {
+        // Remote probes are selected by their address, see `RemoteProbe::connect`.
+        Err(DebugProbeError::ProbeCouldNotBeCreated(
+            ProbeCreationError::NotFound,
+        ))
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn speed_khz(&self) -> u32 {
+        self.speed_khz
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        match self.request(Request::SetSpeed(speed_khz))? {
+            Response::Speed(speed_khz) => {
+                self.speed_khz = speed_khz;
+                Ok(speed_khz)
+            }
+            _ => Err(RemoteProbeError::UnexpectedResponse.into()),
+        }
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        self.request_protocol(Request::Attach)
+    }
+
+    fn detach(&mut self) -> Result<(), DebugProbeError> {
+        self.request_ok(Request::Detach)
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        self.request_ok(Request::TargetReset)
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        self.request_ok(Request::TargetResetAssert)
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.request_ok(Request::TargetResetDeassert)
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        self.request_protocol(Request::SelectProtocol(protocol))
+    }
+
+    fn active_protocol(&self) -> Option<WireProtocol> {
+        self.protocol
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        self.has_arm_interface
+    }
+
+    fn try_get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
+    {
+        if self.has_arm_interface {
+            Ok(Box::new(ArmCommunicationInterface::new(self, true)))
+        } else {
+            Err((self, DebugProbeError::InterfaceNotAvailable("ARM")))
+        }
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        if self.has_arm_interface {
+            Some(self)
+        } else {
+            None
+        }
+    }
+
+    fn get_target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        match self.request(Request::GetTargetVoltage)? {
+            Response::Voltage(voltage) => Ok(voltage),
+            _ => Err(RemoteProbeError::UnexpectedResponse.into()),
+        }
+    }
+}
+
+impl RawDapAccess for RemoteProbe {
+    fn select_dp(&mut self, dp: DpAddress) -> Result<(), DebugProbeError> {
+        self.request_ok(Request::SelectDp(dp))
+    }
+
+    fn raw_read_register(&mut self, port: PortType, addr: u8) -> Result<u32, DebugProbeError> {
+        self.request_value(Request::ReadRegister { port, addr })
+    }
+
+    fn raw_read_block(
+        &mut self,
+        port: PortType,
+        addr: u8,
+        values: &mut [u32],
+    ) -> Result<(), DebugProbeError> {
+        for chunk in values.chunks_mut(protocol::MAX_BLOCK_WORDS) {
+            let request = Request::ReadBlock {
+                port,
+                addr,
+                len: chunk.len() as u32,
+            };
+
+            match self.request(request)? {
+                Response::Values(received) if received.len() == chunk.len() => {
+                    chunk.copy_from_slice(&received)
+                }
+                _ => return Err(RemoteProbeError::UnexpectedResponse.into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn raw_write_register(
+        &mut self,
+        port: PortType,
+        addr: u8,
+        value: u32,
+    ) -> Result<(), DebugProbeError> {
+        self.request_ok(Request::WriteRegister { port, addr, value })
+    }
+
+    fn raw_write_block(
+        &mut self,
+        port: PortType,
+        addr: u8,
+        values: &[u32],
+    ) -> Result<(), DebugProbeError> {
+        for chunk in values.chunks(protocol::MAX_BLOCK_WORDS) {
+            self.request_ok(Request::WriteBlock {
+                port,
+                addr,
+                values: chunk.to_vec(),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn raw_flush(&mut self) -> Result<(), DebugProbeError> {
+        self.request_ok(Request::Flush)
+    }
+
+    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        self.request_ok(Request::SwjSequence { bit_len, bits })
+    }
+
+    fn swj_pins(
+        &mut self,
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        self.request_value(Request::SwjPins {
+            pin_out,
+            pin_select,
+            pin_wait,
+        })
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+}
+
+impl DapProbe for RemoteProbe {}
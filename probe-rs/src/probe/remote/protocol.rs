@@ -0,0 +1,246 @@
+//! Messages exchanged between a remote probe client and server.
+//!
+//! Every message is encoded using `bincode`, and prefixed with its length
+//! as a little endian `u32`.
+
+use std::io::{Read, Write};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    architecture::arm::{DapError, DpAddress, PortType},
+    DebugProbeError, WireProtocol,
+};
+
+use super::RemoteProbeError;
+
+/// Version of the protocol, has to be increased on every incompatible change.
+pub(crate) const PROTOCOL_VERSION: u32 = 1;
+
+/// Maximum size of a single encoded message.
+const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+/// Maximum number of words which can be read in a single block transfer.
+pub(crate) const MAX_BLOCK_WORDS: usize = MAX_MESSAGE_SIZE / 4 - 64;
+
+/// A request sent from the client to the server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Request {
+    /// First message sent after connecting.
+    Hello {
+        version: u32,
+    },
+    SetSpeed(u32),
+    Attach,
+    Detach,
+    TargetReset,
+    TargetResetAssert,
+    TargetResetDeassert,
+    SelectProtocol(WireProtocol),
+    GetTargetVoltage,
+    SelectDp(DpAddress),
+    ReadRegister {
+        port: PortType,
+        addr: u8,
+    },
+    ReadBlock {
+        port: PortType,
+        addr: u8,
+        len: u32,
+    },
+    WriteRegister {
+        port: PortType,
+        addr: u8,
+        value: u32,
+    },
+    WriteBlock {
+        port: PortType,
+        addr: u8,
+        values: Vec<u32>,
+    },
+    Flush,
+    SwjSequence {
+        bit_len: u8,
+        bits: u64,
+    },
+    SwjPins {
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    },
+}
+
+/// A response sent from the server to the client.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) enum Response {
+    Hello {
+        version: u32,
+        name: String,
+        speed_khz: u32,
+        protocol: Option<WireProtocol>,
+        has_arm_interface: bool,
+    },
+    Ok,
+    Speed(u32),
+    Protocol(Option<WireProtocol>),
+    Voltage(Option<f32>),
+    Value(u32),
+    Values(Vec<u32>),
+    Error(RemoteError),
+}
+
+/// An error which occurred on the server.
+///
+/// Errors which are handled by the upper layers are transferred
+/// as they are, everything else is only transferred as a message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum RemoteError {
+    Dap(DapError),
+    Timeout,
+    NotAttached,
+    Attached,
+    TargetNotFound,
+    UnsupportedProtocol(WireProtocol),
+    UnsupportedSpeed(u32),
+    Other(String),
+}
+
+impl From<DebugProbeError> for RemoteError {
+    fn from(error: DebugProbeError) -> Self {
+        match error {
+            DebugProbeError::ArchitectureSpecific(e) if e.is::<DapError>() => {
+                RemoteError::Dap(e.downcast_ref::<DapError>().unwrap().clone())
+            }
+            DebugProbeError::Timeout => RemoteError::Timeout,
+            DebugProbeError::NotAttached => RemoteError::NotAttached,
+            DebugProbeError::Attached => RemoteError::Attached,
+            DebugProbeError::TargetNotFound => RemoteError::TargetNotFound,
+            DebugProbeError::UnsupportedProtocol(protocol) => {
+                RemoteError::UnsupportedProtocol(protocol)
+            }
+            DebugProbeError::UnsupportedSpeed(speed) => RemoteError::UnsupportedSpeed(speed),
+            other => {
+                // Include the sources, as they usually contain the interesting part.
+                let mut message = other.to_string();
+                let mut source = std::error::Error::source(&other);
+
+                while let Some(error) = source {
+                    message.push_str(": ");
+                    message.push_str(&error.to_string());
+                    source = error.source();
+                }
+
+                RemoteError::Other(message)
+            }
+        }
+    }
+}
+
+impl From<RemoteError> for DebugProbeError {
+    fn from(error: RemoteError) -> Self {
+        match error {
+            RemoteError::Dap(e) => e.into(),
+            RemoteError::Timeout => DebugProbeError::Timeout,
+            RemoteError::NotAttached => DebugProbeError::NotAttached,
+            RemoteError::Attached => DebugProbeError::Attached,
+            RemoteError::TargetNotFound => DebugProbeError::TargetNotFound,
+            RemoteError::UnsupportedProtocol(protocol) => {
+                DebugProbeError::UnsupportedProtocol(protocol)
+            }
+            RemoteError::UnsupportedSpeed(speed) => DebugProbeError::UnsupportedSpeed(speed),
+            RemoteError::Other(message) => RemoteProbeError::Server(message).into(),
+        }
+    }
+}
+
+/// Write a single message, and flush the writer.
+pub(crate) fn write_message<T: Serialize>(
+    writer: &mut impl Write,
+    message: &T,
+) -> Result<(), RemoteProbeError> {
+    let payload = bincode::serialize(message)?;
+
+    if payload.len() > MAX_MESSAGE_SIZE {
+        return Err(RemoteProbeError::MessageTooLarge(payload.len()));
+    }
+
+    writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+    writer.write_all(&payload)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Read a single message.
+pub(crate) fn read_message<T: DeserializeOwned>(
+    reader: &mut impl Read,
+) -> Result<T, RemoteProbeError> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+
+    let len = u32::from_le_bytes(len) as usize;
+
+    if len > MAX_MESSAGE_SIZE {
+        return Err(RemoteProbeError::MessageTooLarge(len));
+    }
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload)?;
+
+    Ok(bincode::deserialize(&payload)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn message_roundtrip() {
+        let requests = [
+            Request::Hello {
+                version: PROTOCOL_VERSION,
+            },
+            Request::SelectDp(DpAddress::Multidrop(0x0100_2927)),
+            Request::WriteBlock {
+                port: PortType::AccessPort,
+                addr: 0xc,
+                values: vec![1, 2, 3],
+            },
+        ];
+
+        let mut buffer = Vec::new();
+
+        for request in &requests {
+            write_message(&mut buffer, request).unwrap();
+        }
+
+        let mut reader = buffer.as_slice();
+
+        for request in &requests {
+            let received: Request = read_message(&mut reader).unwrap();
+            assert_eq!(&received, request);
+        }
+
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn oversized_message_is_rejected() {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&(MAX_MESSAGE_SIZE as u32 + 1).to_le_bytes());
+
+        let result: Result<Request, _> = read_message(&mut buffer.as_slice());
+
+        assert!(matches!(result, Err(RemoteProbeError::MessageTooLarge(_))));
+    }
+
+    #[test]
+    fn dap_errors_are_preserved() {
+        let error: DebugProbeError = DapError::FaultResponse.into();
+
+        assert_eq!(
+            RemoteError::from(error),
+            RemoteError::Dap(DapError::FaultResponse)
+        );
+    }
+}
@@ -0,0 +1,448 @@
+//! Server side of the remote probe protocol.
+//!
+//! The server can be used by multiple clients at the same time, for example by
+//! a GDB server and a separate RTT logger. Every request is executed atomically,
+//! and the DAP state of each client is restored before its requests are executed.
+//!
+//! Clients still have to make sure they don't interfere with each other on a
+//! higher level. For example, two clients halting and resuming the same core will
+//! confuse each other, while reading memory from one client and debugging from
+//! another one works fine.
+
+use std::collections::HashMap;
+use std::io::{self, BufReader, BufWriter};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::{
+    architecture::arm::{
+        communication_interface::DapProbe, dp::Select, DpAddress, PortType, Register,
+    },
+    DebugProbe, DebugProbeError, Probe,
+};
+
+use super::{
+    protocol::{read_message, write_message, Request, Response, MAX_BLOCK_WORDS, PROTOCOL_VERSION},
+    RemoteProbeError,
+};
+
+/// Address of the CSW register of a MEM-AP.
+const CSW_ADDRESS: u8 = 0x00;
+
+/// The probe, and the state shared between all clients.
+#[derive(Debug)]
+struct SharedProbe {
+    probe: Box<dyn DebugProbe>,
+    /// Number of clients which are currently attached.
+    attached_clients: usize,
+    /// The client which executed the last DAP request.
+    dap_owner: Option<usize>,
+}
+
+/// The DAP state of a single client.
+///
+/// Only the state which is cached by the client is tracked.
+#[derive(Debug, Default)]
+struct ClientState {
+    id: usize,
+    attached: bool,
+    /// The currently selected DP.
+    dp: Option<DpAddress>,
+    /// The last value written to the SELECT register of each DP.
+    select: HashMap<DpAddress, u32>,
+    /// The last value written to the CSW register of each AP.
+    csw: HashMap<(DpAddress, u8), u32>,
+}
+
+impl ClientState {
+    fn new(id: usize) -> Self {
+        Self {
+            id,
+            ..Default::default()
+        }
+    }
+
+    /// Remember the register writes which change state cached by the client.
+    fn track(&mut self, request: &Request) {
+        let dp = self.dp.unwrap_or(DpAddress::Default);
+
+        let (port, addr, value) = match request {
+            Request::SelectDp(dp) => {
+                self.dp = Some(*dp);
+                return;
+            }
+            Request::WriteRegister { port, addr, value } => (*port, *addr, *value),
+            Request::WriteBlock { port, addr, values } => match values.last() {
+                Some(value) => (*port, *addr, *value),
+                None => return,
+            },
+            _ => return,
+        };
+
+        match port {
+            PortType::DebugPort if addr == Select::ADDRESS => {
+                self.select.insert(dp, value);
+            }
+            PortType::AccessPort if addr == CSW_ADDRESS => {
+                let select = Select::from(self.select.get(&dp).copied().unwrap_or_default());
+
+                if select.ap_bank_sel() == 0 {
+                    self.csw.insert((dp, select.ap_sel()), value);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn is_dap_request(request: &Request) -> bool {
+    matches!(
+        request,
+        Request::SelectDp(_)
+            | Request::ReadRegister { .. }
+            | Request::ReadBlock { .. }
+            | Request::WriteRegister { .. }
+            | Request::WriteBlock { .. }
+            | Request::Flush
+            | Request::SwjSequence { .. }
+            | Request::SwjPins { .. }
+    )
+}
+
+impl SharedProbe {
+    fn dap_probe(&mut self) -> Result<&mut dyn DapProbe, DebugProbeError> {
+        self.probe
+            .try_as_dap_probe()
+            .ok_or(DebugProbeError::InterfaceNotAvailable("ARM"))
+    }
+
+    /// Restore the DAP state of a client, after another client used the DAP.
+    fn restore_dap_state(&mut self, client: &ClientState) -> Result<(), DebugProbeError> {
+        log::debug!("Restoring DAP state of client {}", client.id);
+
+        let probe = self.dap_probe()?;
+
+        for (&(dp, ap), &csw) in &client.csw {
+            let mut select = Select::from(0);
+            select.set_ap_sel(ap);
+
+            probe.select_dp(dp)?;
+            probe.raw_write_register(PortType::DebugPort, Select::ADDRESS, select.into())?;
+            probe.raw_write_register(PortType::AccessPort, CSW_ADDRESS, csw)?;
+        }
+
+        for (&dp, &select) in &client.select {
+            probe.select_dp(dp)?;
+            probe.raw_write_register(PortType::DebugPort, Select::ADDRESS, select)?;
+        }
+
+        if let Some(dp) = client.dp {
+            probe.select_dp(dp)?;
+        }
+
+        Ok(())
+    }
+
+    fn attach_client(&mut self, client: &mut ClientState) -> Result<(), DebugProbeError> {
+        if client.attached {
+            return Ok(());
+        }
+
+        // Only the first client actually attaches to the target.
+        if self.attached_clients == 0 {
+            self.probe.attach()?;
+        }
+
+        self.attached_clients += 1;
+        client.attached = true;
+
+        Ok(())
+    }
+
+    fn detach_client(&mut self, client: &mut ClientState) -> Result<(), DebugProbeError> {
+        if self.dap_owner == Some(client.id) {
+            self.dap_owner = None;
+        }
+
+        if !client.attached {
+            return Ok(());
+        }
+
+        client.attached = false;
+        self.attached_clients -= 1;
+
+        // Only detach once the last client is done.
+        if self.attached_clients == 0 {
+            self.probe.detach()?;
+        }
+
+        Ok(())
+    }
+
+    fn process_request(
+        &mut self,
+        client: &mut ClientState,
+        request: Request,
+    ) -> Result<Response, DebugProbeError> {
+        if is_dap_request(&request) {
+            if self.dap_owner != Some(client.id) {
+                // Another client might have changed the state.
+                self.restore_dap_state(client)?;
+                self.dap_owner = Some(client.id);
+            }
+
+            client.track(&request);
+        }
+
+        let response = match request {
+            Request::Hello { version } => {
+                if version != PROTOCOL_VERSION {
+                    log::warn!(
+                        "Client uses protocol version {}, expected {}",
+                        version,
+                        PROTOCOL_VERSION
+                    );
+                }
+
+                let has_arm_interface =
+                    self.probe.has_arm_interface() && self.probe.try_as_dap_probe().is_some();
+
+                Response::Hello {
+                    version: PROTOCOL_VERSION,
+                    name: self.probe.get_name().to_string(),
+                    speed_khz: self.probe.speed_khz(),
+                    protocol: self.probe.active_protocol(),
+                    has_arm_interface,
+                }
+            }
+            Request::SetSpeed(speed_khz) => Response::Speed(self.probe.set_speed(speed_khz)?),
+            Request::Attach => {
+                self.attach_client(client)?;
+                Response::Protocol(self.probe.active_protocol())
+            }
+            Request::Detach => {
+                self.detach_client(client)?;
+                Response::Ok
+            }
+            Request::TargetReset => {
+                self.probe.target_reset()?;
+                Response::Ok
+            }
+            Request::TargetResetAssert => {
+                self.probe.target_reset_assert()?;
+                Response::Ok
+            }
+            Request::TargetResetDeassert => {
+                self.probe.target_reset_deassert()?;
+                Response::Ok
+            }
+            Request::SelectProtocol(protocol) => {
+                if self.attached_clients > 0 {
+                    // The protocol can't be changed while other clients use it.
+                    if self.probe.active_protocol() != Some(protocol) {
+                        return Err(DebugProbeError::Attached);
+                    }
+                } else {
+                    self.probe.select_protocol(protocol)?;
+                }
+
+                Response::Protocol(self.probe.active_protocol())
+            }
+            Request::GetTargetVoltage => Response::Voltage(self.probe.get_target_voltage()?),
+            Request::SelectDp(dp) => {
+                self.dap_probe()?.select_dp(dp)?;
+                Response::Ok
+            }
+            Request::ReadRegister { port, addr } => {
+                Response::Value(self.dap_probe()?.raw_read_register(port, addr)?)
+            }
+            Request::ReadBlock { port, addr, len } => {
+                let len = len as usize;
+
+                if len > MAX_BLOCK_WORDS {
+                    return Err(RemoteProbeError::MessageTooLarge(len * 4).into());
+                }
+
+                let mut values = vec![0; len];
+                self.dap_probe()?.raw_read_block(port, addr, &mut values)?;
+
+                Response::Values(values)
+            }
+            Request::WriteRegister { port, addr, value } => {
+                self.dap_probe()?.raw_write_register(port, addr, value)?;
+                Response::Ok
+            }
+            Request::WriteBlock { port, addr, values } => {
+                self.dap_probe()?.raw_write_block(port, addr, &values)?;
+                Response::Ok
+            }
+            Request::Flush => {
+                self.dap_probe()?.raw_flush()?;
+                Response::Ok
+            }
+            Request::SwjSequence { bit_len, bits } => {
+                self.dap_probe()?.swj_sequence(bit_len, bits)?;
+                Response::Ok
+            }
+            Request::SwjPins {
+                pin_out,
+                pin_select,
+                pin_wait,
+            } => Response::Value(self.dap_probe()?.swj_pins(pin_out, pin_select, pin_wait)?),
+        };
+
+        Ok(response)
+    }
+}
+
+/// Exposes a locally attached probe over TCP, so that it
+/// can be used with a [`RemoteProbe`](super::RemoteProbe).
+///
+/// Multiple clients can use the probe at the same time. The connection is
+/// not authenticated, so the server should only be reachable from trusted networks.
+#[derive(Debug)]
+pub struct RemoteProbeServer {
+    shared: Arc<Mutex<SharedProbe>>,
+}
+
+impl RemoteProbeServer {
+    /// Create a new server for the given probe.
+    pub fn new(probe: Probe) -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(SharedProbe {
+                probe: probe.inner,
+                attached_clients: 0,
+                dap_owner: None,
+            })),
+        }
+    }
+
+    /// Accept and serve clients on the given listener.
+    ///
+    /// Every client is served from its own thread. This only returns
+    /// if accepting a new connection fails.
+    pub fn serve(&self, listener: &TcpListener) -> Result<(), RemoteProbeError> {
+        for (id, stream) in listener.incoming().enumerate() {
+            let stream = stream?;
+            let peer = stream.peer_addr()?;
+            let shared = self.shared.clone();
+
+            log::info!("Client {} connected from {}", id, peer);
+
+            std::thread::spawn(move || {
+                let mut client = ClientState::new(id);
+
+                match handle_client(&shared, &mut client, stream) {
+                    Ok(()) => log::info!("Client {} disconnected", id),
+                    Err(e) => log::warn!("Connection to client {} failed: {}", id, e),
+                }
+
+                if let Err(e) = lock(&shared).detach_client(&mut client) {
+                    log::warn!(
+                        "Failed to detach probe after client {} disconnected: {}",
+                        id,
+                        e
+                    );
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Lock the shared probe.
+///
+/// A panic in one client doesn't affect the others, so a poisoned lock can be ignored.
+fn lock(shared: &Mutex<SharedProbe>) -> MutexGuard<'_, SharedProbe> {
+    shared.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn handle_client(
+    shared: &Mutex<SharedProbe>,
+    client: &mut ClientState,
+    stream: TcpStream,
+) -> Result<(), RemoteProbeError> {
+    // Most requests are small and latency sensitive.
+    stream.set_nodelay(true)?;
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+
+    loop {
+        let request: Request = match read_message(&mut reader) {
+            Ok(request) => request,
+            Err(RemoteProbeError::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Ok(())
+            }
+            Err(e) => return Err(e),
+        };
+
+        log::trace!("Received request {:?} from client {}", request, client.id);
+
+        let response = lock(shared)
+            .process_request(client, request)
+            .unwrap_or_else(|e| Response::Error(e.into()));
+
+        write_message(&mut writer, &response)?;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn track_select_and_csw() {
+        let mut client = ClientState::new(0);
+
+        client.track(&Request::WriteRegister {
+            port: PortType::DebugPort,
+            addr: Select::ADDRESS,
+            value: 0x0100_0000,
+        });
+        client.track(&Request::WriteRegister {
+            port: PortType::AccessPort,
+            addr: CSW_ADDRESS,
+            value: 0x2300_0052,
+        });
+
+        assert_eq!(client.select[&DpAddress::Default], 0x0100_0000);
+        assert_eq!(client.csw[&(DpAddress::Default, 1)], 0x2300_0052);
+    }
+
+    #[test]
+    fn csw_is_only_tracked_in_bank_zero() {
+        let mut client = ClientState::new(0);
+
+        // Bank 0xf contains the IDR register, not the CSW register.
+        client.track(&Request::WriteRegister {
+            port: PortType::DebugPort,
+            addr: Select::ADDRESS,
+            value: 0x0000_00f0,
+        });
+        client.track(&Request::WriteRegister {
+            port: PortType::AccessPort,
+            addr: CSW_ADDRESS,
+            value: 0x1234,
+        });
+
+        assert!(client.csw.is_empty());
+    }
+
+    #[test]
+    fn track_multidrop_dp() {
+        let mut client = ClientState::new(0);
+        let dp = DpAddress::Multidrop(0x0100_2927);
+
+        client.track(&Request::SelectDp(dp));
+        client.track(&Request::WriteRegister {
+            port: PortType::DebugPort,
+            addr: Select::ADDRESS,
+            value: 0x10,
+        });
+
+        assert_eq!(client.dp, Some(dp));
+        assert_eq!(client.select[&dp], 0x10);
+    }
+}
@@ -0,0 +1,466 @@
+//! Driver for the `remote_bitbang` protocol of OpenOCD.
+//!
+//! The protocol is used by simulators like Verilator or Spike to expose a
+//! JTAG port over TCP, and by some hardware bridges. Every command is a single
+//! ASCII character, see `doc/manual/jtag/drivers/remote_bitbang.txt` in the
+//! OpenOCD source tree for a description.
+//!
+//! SWD uses the extension of the protocol added in OpenOCD 0.12. The same
+//! commands are used for cJTAG, with SWCLK as TCKC and SWDIO as TMSC.
+
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::{
+    architecture::{
+        arm::{
+            communication_interface::{DapProbe, UninitializedArmProbe},
+            ArmCommunicationInterface,
+        },
+        riscv::communication_interface::RiscvCommunicationInterface,
+    },
+    probe::{
+        arm_debug_interface::{ProbeStatistics, RawProtocolIo, SwdSettings},
+        cjtag::{self, Oscan1Sequence},
+        scan_chain::{self, BitBangJtag, JtagChain},
+    },
+    DebugProbe, DebugProbeError, DebugProbeSelector, ProbeCapabilities, ProbeCreationError,
+    ScanChainElement, WireProtocol,
+};
+
+use super::JTAGAccess;
+
+/// Read the current value of TDO.
+const CMD_READ_TDO: u8 = b'R';
+/// Read the current value of SWDIO.
+const CMD_READ_SWDIO: u8 = b'c';
+/// Drive SWDIO from the adapter.
+const CMD_SWDIO_OUTPUT: u8 = b'O';
+/// Release SWDIO, so that the target can drive it.
+const CMD_SWDIO_INPUT: u8 = b'o';
+/// Close the connection.
+const CMD_QUIT: u8 = b'Q';
+
+/// Command to set TCK, TMS and TDI.
+fn jtag_write(tck: bool, tms: bool, tdi: bool) -> u8 {
+    b'0' + (u8::from(tck) << 2 | u8::from(tms) << 1 | u8::from(tdi))
+}
+
+/// Command to set SWCLK and SWDIO.
+fn swd_write(swclk: bool, swdio: bool) -> u8 {
+    b'd' + (u8::from(swclk) << 1 | u8::from(swdio))
+}
+
+/// Command to set the TRST and SRST signals, `true` asserts the reset.
+fn reset(trst: bool, srst: bool) -> u8 {
+    b'r' + (u8::from(trst) << 1 | u8::from(srst))
+}
+
+/// Encode a JTAG bit sequence. TDO is read before every rising edge of TCK.
+fn encode_jtag(tms: &[bool], tdi: &[bool], commands: &mut Vec<u8>) {
+    for (tms, tdi) in tms.iter().zip(tdi) {
+        commands.push(jtag_write(false, *tms, *tdi));
+        commands.push(CMD_READ_TDO);
+        commands.push(jtag_write(true, *tms, *tdi));
+    }
+
+    // Leave TCK low
+    if let (Some(tms), Some(tdi)) = (tms.last(), tdi.last()) {
+        commands.push(jtag_write(false, *tms, *tdi));
+    }
+}
+
+/// Encode a SWD bit sequence.
+///
+/// SWDIO is read after the rising edge of SWCLK, so the sampled value
+/// belongs to the same clock cycle as the target drove it in.
+fn encode_swd(dir: &[bool], swdio: &[bool], output: &mut bool, commands: &mut Vec<u8>) {
+    for (dir, swdio) in dir.iter().zip(swdio) {
+        if *dir != *output {
+            commands.push(if *dir {
+                CMD_SWDIO_OUTPUT
+            } else {
+                CMD_SWDIO_INPUT
+            });
+            *output = *dir;
+        }
+
+        commands.push(swd_write(false, *swdio));
+        commands.push(swd_write(true, *swdio));
+
+        if !dir {
+            commands.push(CMD_READ_SWDIO);
+        }
+    }
+
+    // Leave SWCLK low
+    if let Some(swdio) = swdio.last() {
+        commands.push(swd_write(false, *swdio));
+    }
+}
+
+/// A JTAG or SWD adapter using the OpenOCD `remote_bitbang` protocol.
+#[derive(Debug)]
+pub struct RemoteBitbang {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    name: String,
+
+    protocol: WireProtocol,
+    /// Whether the adapter currently drives SWDIO.
+    swdio_output: bool,
+
+    jtag: JtagChain,
+
+    swd_settings: SwdSettings,
+    probe_statistics: ProbeStatistics,
+}
+
+impl RemoteBitbang {
+    /// Connect to a `remote_bitbang` server at the given address.
+    pub fn connect(address: impl ToSocketAddrs) -> Result<Box<Self>, DebugProbeError> {
+        let stream = TcpStream::connect(address).map_err(Self::io_error)?;
+
+        // Every command is a single byte, so avoid waiting for more data.
+        stream.set_nodelay(true).map_err(Self::io_error)?;
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .map_err(Self::io_error)?;
+
+        let name = format!(
+            "remote_bitbang ({})",
+            stream.peer_addr().map_err(Self::io_error)?
+        );
+
+        Ok(Box::new(Self {
+            reader: BufReader::new(stream.try_clone().map_err(Self::io_error)?),
+            writer: BufWriter::new(stream),
+            name,
+            protocol: WireProtocol::Jtag,
+            swdio_output: true,
+            jtag: JtagChain::default(),
+            swd_settings: SwdSettings::default(),
+            probe_statistics: ProbeStatistics::default(),
+        }))
+    }
+
+    fn io_error(error: std::io::Error) -> DebugProbeError {
+        DebugProbeError::ProbeSpecific(Box::new(error))
+    }
+
+    /// Send the commands, and read one response byte for every read command.
+    fn execute(&mut self, commands: &[u8], reads: usize) -> Result<Vec<bool>, DebugProbeError> {
+        self.writer.write_all(commands).map_err(Self::io_error)?;
+        self.writer.flush().map_err(Self::io_error)?;
+
+        let mut response = vec![0u8; reads];
+        self.reader
+            .read_exact(&mut response)
+            .map_err(Self::io_error)?;
+
+        response
+            .into_iter()
+            .map(|value| match value {
+                b'0' => Ok(false),
+                b'1' => Ok(true),
+                other => Err(DebugProbeError::ProbeSpecific(
+                    format!(
+                        "Unexpected response {:#04x} from remote_bitbang server",
+                        other
+                    )
+                    .into(),
+                )),
+            })
+            .collect()
+    }
+
+    /// Switch the TAP to the OScan1 format of cJTAG.
+    fn activate_cjtag(&mut self) -> Result<(), DebugProbeError> {
+        let mut commands = Vec::new();
+
+        if !self.swdio_output {
+            commands.push(CMD_SWDIO_OUTPUT);
+            self.swdio_output = true;
+        }
+
+        commands.extend(
+            cjtag::activation_sequence()
+                .into_iter()
+                .map(|(tckc, tmsc)| swd_write(tckc, tmsc)),
+        );
+
+        self.execute(&commands, 0)?;
+
+        Ok(())
+    }
+}
+
+impl Drop for RemoteBitbang {
+    fn drop(&mut self) {
+        // We can't do anything about errors here.
+        let _ = self
+            .writer
+            .write_all(&[CMD_QUIT])
+            .and_then(|_| self.writer.flush());
+    }
+}
+
+impl DebugProbe for RemoteBitbang {
+    fn new_from_selector(
+        _selector: impl Into<DebugProbeSelector>,
+    ) -> Result<Box<Self>, DebugProbeError>
+    where
+        Self: Sized,
+    {
+        // There is nothing to discover, see `RemoteBitbang::connect`.
+        Err(DebugProbeError::ProbeCouldNotBeCreated(
+            ProbeCreationError::NotFound,
+        ))
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn speed_khz(&self) -> u32 {
+        // The speed only depends on the server and the connection.
+        0
+    }
+
+    fn set_speed(&mut self, _speed_khz: u32) -> Result<u32, DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe("set_speed"))
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        log::debug!("Attaching to {}", self.name);
+
+        if self.protocol == WireProtocol::Swd {
+            // Attaching is handled in sequence
+            return Ok(());
+        }
+
+        if self.protocol == WireProtocol::Cjtag {
+            self.activate_cjtag()?;
+        }
+
+        scan_chain::attach(self)?;
+
+        Ok(())
+    }
+
+    fn detach(&mut self) -> Result<(), DebugProbeError> {
+        Ok(())
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        self.target_reset_assert()?;
+        std::thread::sleep(Duration::from_millis(100));
+        self.target_reset_deassert()
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        self.execute(&[reset(false, true)], 0)?;
+        Ok(())
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.execute(&[reset(false, false)], 0)?;
+        Ok(())
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        self.protocol = protocol;
+        Ok(())
+    }
+
+    fn active_protocol(&self) -> Option<WireProtocol> {
+        Some(self.protocol)
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: Vec<ScanChainElement>) -> Result<(), DebugProbeError> {
+        self.jtag.set_scan_chain(scan_chain);
+        Ok(())
+    }
+
+    fn select_jtag_tap(&mut self, index: usize) -> Result<(), DebugProbeError> {
+        self.jtag.select_tap(index);
+        Ok(())
+    }
+
+    fn scan_chain(&self) -> Result<&[ScanChainElement], DebugProbeError> {
+        Ok(self.jtag.scan_chain())
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        true
+    }
+
+    fn try_get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
+    {
+        Ok(Box::new(ArmCommunicationInterface::new(self, true)))
+    }
+
+    fn has_riscv_interface(&self) -> bool {
+        self.protocol.is_jtag()
+    }
+
+    fn try_get_riscv_interface(
+        self: Box<Self>,
+    ) -> Result<RiscvCommunicationInterface, (Box<dyn DebugProbe>, DebugProbeError)> {
+        if self.protocol == WireProtocol::Swd {
+            return Err((
+                self,
+                DebugProbeError::UnsupportedProtocol(WireProtocol::Swd),
+            ));
+        }
+
+        match RiscvCommunicationInterface::new(self) {
+            Ok(interface) => Ok(interface),
+            Err((probe, err)) => Err((probe.into_probe(), err)),
+        }
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        Some(self)
+    }
+
+    fn capabilities(&self) -> ProbeCapabilities {
+        let mut capabilities = ProbeCapabilities::default();
+
+        capabilities.protocols = vec![WireProtocol::Swd, WireProtocol::Jtag, WireProtocol::Cjtag];
+
+        capabilities
+    }
+}
+
+impl DapProbe for RemoteBitbang {}
+
+impl RawProtocolIo for RemoteBitbang {
+    fn jtag_io<M, I>(&mut self, tms: M, tdi: I) -> Result<Vec<bool>, DebugProbeError>
+    where
+        M: IntoIterator<Item = bool>,
+        I: IntoIterator<Item = bool>,
+    {
+        if self.protocol == WireProtocol::Cjtag {
+            let sequence = Oscan1Sequence::encode(tms, tdi);
+            let captured = self.swd_io(sequence.direction, sequence.tmsc)?;
+
+            return Ok(cjtag::decode_tdo(&captured));
+        }
+
+        self.probe_statistics.report_io();
+
+        let tms: Vec<bool> = tms.into_iter().collect();
+        let tdi: Vec<bool> = tdi.into_iter().collect();
+
+        let mut commands = Vec::with_capacity(tms.len() * 3 + 1);
+        encode_jtag(&tms, &tdi, &mut commands);
+
+        self.execute(&commands, tms.len().min(tdi.len()))
+    }
+
+    fn swd_io<D, S>(&mut self, dir: D, swdio: S) -> Result<Vec<bool>, DebugProbeError>
+    where
+        D: IntoIterator<Item = bool>,
+        S: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+
+        let dir: Vec<bool> = dir.into_iter().collect();
+        let swdio: Vec<bool> = swdio.into_iter().collect();
+
+        let mut commands = Vec::with_capacity(dir.len() * 3 + 2);
+        encode_swd(&dir, &swdio, &mut self.swdio_output, &mut commands);
+
+        let reads = self.execute(&commands, dir.iter().filter(|d| !**d).count())?;
+
+        // Output bits are reported as they were sent.
+        let mut reads = reads.into_iter();
+        Ok(dir
+            .iter()
+            .zip(swdio)
+            .map(|(dir, swdio)| if *dir { swdio } else { reads.next().unwrap() })
+            .collect())
+    }
+
+    fn swd_settings(&self) -> &SwdSettings {
+        &self.swd_settings
+    }
+
+    fn probe_statistics(&mut self) -> &mut ProbeStatistics {
+        &mut self.probe_statistics
+    }
+}
+
+impl JTAGAccess for RemoteBitbang {
+    fn set_ir_len(&mut self, len: u32) {
+        self.jtag.set_ir_len(len);
+    }
+
+    fn read_register(&mut self, address: u32, len: u32) -> Result<Vec<u8>, DebugProbeError> {
+        let data = vec![0u8; (len as usize + 7) / 8];
+
+        scan_chain::transfer_register(self, address, &data, len)
+    }
+
+    fn write_register(
+        &mut self,
+        address: u32,
+        data: &[u8],
+        len: u32,
+    ) -> Result<Vec<u8>, DebugProbeError> {
+        scan_chain::transfer_register(self, address, data, len)
+    }
+
+    fn set_idle_cycles(&mut self, idle_cycles: u8) {
+        self.jtag.set_idle_cycles(idle_cycles);
+    }
+
+    fn get_idle_cycles(&self) -> u8 {
+        self.jtag.idle_cycles()
+    }
+}
+
+impl BitBangJtag for RemoteBitbang {
+    fn jtag_chain(&mut self) -> &mut JtagChain {
+        &mut self.jtag
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn jtag_commands() {
+        let mut commands = Vec::new();
+        encode_jtag(&[true, false], &[false, true], &mut commands);
+
+        assert_eq!(commands, b"2R61R51");
+    }
+
+    #[test]
+    fn swd_commands_switch_direction() {
+        let mut output = true;
+        let mut commands = Vec::new();
+        encode_swd(&[true, false], &[true, false], &mut output, &mut commands);
+
+        assert_eq!(commands, b"egodfcd");
+        assert!(!output);
+    }
+
+    #[test]
+    fn reset_commands() {
+        assert_eq!(reset(false, false), b'r');
+        assert_eq!(reset(false, true), b's');
+        assert_eq!(reset(true, false), b't');
+        assert_eq!(reset(true, true), b'u');
+    }
+}
@@ -0,0 +1,558 @@
+//! Handling of JTAG scan chains with multiple TAPs.
+//!
+//! The TAPs (Test Access Ports) of a scan chain are connected in series, with the TDO
+//! output of one TAP connected to the TDI input of the next one. To access a single TAP,
+//! all other TAPs are put into the BYPASS state, by shifting ones into their IR registers.
+//! In the BYPASS state, the DR register of a TAP is a single bit, so the data shifted through
+//! the selected TAP has to be padded with one bit for each other TAP.
+//!
+//! The TAPs are numbered in the order in which their data is shifted out of TDO, so the
+//! TAP closest to TDO of the probe has the index 0.
+
+use crate::probe::arm_debug_interface::RawProtocolIo;
+use crate::probe::{cjtag, icepick};
+use crate::DebugProbeError;
+
+/// The maximum number of TAPs which are detected in a scan chain.
+const MAX_TAPS: usize = 8;
+
+/// Number of bits shifted through the IR registers to detect their total length.
+const MAX_IR_BITS: usize = MAX_TAPS * 32;
+
+/// IDCODEs of TAPs which are known to be the debug target in chains with multiple TAPs.
+const KNOWN_IDCODES: &[u32] = &[
+    0x1000563d, // GD32VF103
+    0x4ba00477, // ARM JTAG-DP, e.g. connected using a TI ICEPick
+];
+
+/// A TAP (Test Access Port) in a JTAG scan chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanChainElement {
+    /// The IDCODE of the TAP, or `None` if the TAP selects the BYPASS register after a reset.
+    pub idcode: Option<u32>,
+    /// The length of the IR register of the TAP, in bits.
+    pub ir_len: usize,
+}
+
+/// The position of the selected TAP in the scan chain.
+///
+/// The `pre` values are the number of bits of the TAPs between the selected TAP and TDO,
+/// the `post` values are the number of bits of the TAPs between TDI and the selected TAP.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ChainParams {
+    pub irpre: usize,
+    pub irpost: usize,
+    pub drpre: usize,
+    pub drpost: usize,
+    pub irlen: usize,
+}
+
+impl ChainParams {
+    /// Calculate the parameters for the TAP with the given index, or `None` if
+    /// the chain has no TAP with this index.
+    pub(crate) fn from_chain(chain: &[ScanChainElement], index: usize) -> Option<Self> {
+        let selected = chain.get(index)?;
+
+        Some(Self {
+            irpre: chain[..index].iter().map(|tap| tap.ir_len).sum(),
+            irpost: chain[index + 1..].iter().map(|tap| tap.ir_len).sum(),
+            drpre: index,
+            drpost: chain.len() - index - 1,
+            irlen: selected.ir_len,
+        })
+    }
+
+    /// The bits to shift through the IR registers of the chain, to select the register
+    /// `value` of the selected TAP, with all other TAPs in BYPASS.
+    pub(crate) fn ir_bits(&self, value: u32, ir_len: usize) -> Vec<bool> {
+        let mut bits = vec![true; self.irpre];
+        bits.extend((0..ir_len).map(|i| value & (1 << i) != 0));
+        bits.resize(bits.len() + self.irpost, true);
+        bits
+    }
+
+    /// The bits to shift through the DR registers of the chain, with the given
+    /// data for the selected TAP.
+    pub(crate) fn dr_bits(&self, data: impl IntoIterator<Item = bool>) -> Vec<bool> {
+        let mut bits = vec![false; self.drpre];
+        bits.extend(data);
+        bits.resize(bits.len() + self.drpost, false);
+        bits
+    }
+
+    /// Extract the `len` bits captured by the selected TAP from the bits shifted out of the chain.
+    pub(crate) fn dr_response<'a>(&self, captured: &'a [bool], len: usize) -> &'a [bool] {
+        &captured[self.drpre..self.drpre + len]
+    }
+}
+
+/// Select the TAP used as the debug target.
+///
+/// If no TAP is selected explicitly, a chain with only one TAP uses this TAP,
+/// and a chain with multiple TAPs uses the first TAP with a known IDCODE.
+pub(crate) fn select_tap(
+    chain: &[ScanChainElement],
+    selected: Option<usize>,
+) -> Result<ChainParams, DebugProbeError> {
+    if chain.is_empty() {
+        log::warn!("No JTAG TAPs detected");
+        return Err(DebugProbeError::TargetNotFound);
+    }
+
+    let index = match selected {
+        Some(index) => index,
+        None if chain.len() == 1 => 0,
+        None => chain
+            .iter()
+            .position(|tap| {
+                tap.idcode
+                    .map(|idcode| KNOWN_IDCODES.contains(&idcode))
+                    .unwrap_or(false)
+            })
+            .ok_or_else(|| {
+                log::warn!(
+                    "Found {} JTAG TAPs, but none is known to be the debug target. Select one using `Probe::select_jtag_tap`.",
+                    chain.len()
+                );
+                DebugProbeError::TargetNotFound
+            })?,
+    };
+
+    let params = ChainParams::from_chain(chain, index).ok_or_else(|| {
+        log::warn!(
+            "Selected JTAG TAP {}, but the scan chain only contains {} TAPs",
+            index,
+            chain.len()
+        );
+        DebugProbeError::TargetNotFound
+    })?;
+
+    log::debug!("Using JTAG TAP {}, chain params: {:?}", index, params);
+
+    Ok(params)
+}
+
+/// Parse the bits shifted out of the DR registers after a TAP reset, while shifting in ones.
+///
+/// After a reset, every TAP selects its IDCODE register, which always has the lowest bit set,
+/// or its BYPASS register, which captures a zero. The end of the chain is reached when the
+/// ones shifted in show up, which is never a valid IDCODE.
+fn parse_idcodes(bits: &[bool]) -> Vec<Option<u32>> {
+    let mut idcodes = Vec::new();
+    let mut position = 0;
+
+    while idcodes.len() < MAX_TAPS && position < bits.len() {
+        if bits[position] {
+            let idcode_bits = match bits.get(position..position + 32) {
+                Some(idcode_bits) => idcode_bits,
+                None => break,
+            };
+
+            let idcode = idcode_bits
+                .iter()
+                .enumerate()
+                .fold(0u32, |idcode, (i, bit)| idcode | (*bit as u32) << i);
+
+            if idcode == 0xffff_ffff {
+                break;
+            }
+
+            idcodes.push(Some(idcode));
+            position += 32;
+        } else {
+            idcodes.push(None);
+            position += 1;
+        }
+    }
+
+    idcodes
+}
+
+/// Split the bits captured by the IR registers of `tap_count` TAPs, with a total length of
+/// `total_len` bits, into the IR lengths of the TAPs.
+///
+/// Every TAP captures a value ending in `0b01` into its IR register, and for most TAPs the
+/// remaining bits are zero, which is used to find the start of each register.
+fn parse_ir_lengths(bits: &[bool], tap_count: usize, total_len: usize) -> Option<Vec<usize>> {
+    if tap_count == 1 {
+        return Some(vec![total_len]);
+    }
+
+    let bits = bits.get(..total_len)?;
+
+    let starts: Vec<usize> = bits
+        .iter()
+        .enumerate()
+        .filter_map(|(i, bit)| bit.then_some(i))
+        .collect();
+
+    if starts.len() != tap_count || starts[0] != 0 {
+        return None;
+    }
+
+    let lengths: Vec<usize> = starts
+        .iter()
+        .zip(starts.iter().skip(1).chain(std::iter::once(&total_len)))
+        .map(|(start, end)| end - start)
+        .collect();
+
+    lengths.iter().all(|len| *len >= 2).then_some(lengths)
+}
+
+/// The state of the scan chain of a probe, which bit-bangs JTAG using [`RawProtocolIo::jtag_io`].
+#[derive(Debug)]
+pub(crate) struct JtagChain {
+    /// The IR length of the selected TAP.
+    ir_len: u32,
+    /// The value of the IR register of the selected TAP, if it is known.
+    current_ir_reg: Option<u32>,
+    /// The number of Run-Test/Idle cycles after each DR scan.
+    idle_cycles: u8,
+    /// The scan chain, either set by the user or detected when attaching.
+    chain: Option<Vec<ScanChainElement>>,
+    /// The index of the TAP used as the debug target, if selected by the user.
+    tap: Option<usize>,
+    params: ChainParams,
+}
+
+impl Default for JtagChain {
+    fn default() -> Self {
+        Self {
+            // Used by the RISC-V debug transport module
+            ir_len: 5,
+            current_ir_reg: None,
+            idle_cycles: 0,
+            chain: None,
+            tap: None,
+            params: ChainParams::default(),
+        }
+    }
+}
+
+impl JtagChain {
+    /// Use `chain` instead of detecting the scan chain when attaching.
+    pub(crate) fn set_scan_chain(&mut self, chain: Vec<ScanChainElement>) {
+        self.chain = Some(chain);
+    }
+
+    /// Use the TAP with the given index as the debug target.
+    pub(crate) fn select_tap(&mut self, index: usize) {
+        self.tap = Some(index);
+    }
+
+    /// The scan chain, which is empty until it was detected or set.
+    pub(crate) fn scan_chain(&self) -> &[ScanChainElement] {
+        self.chain.as_deref().unwrap_or_default()
+    }
+
+    pub(crate) fn set_ir_len(&mut self, len: u32) {
+        if len != self.ir_len {
+            self.ir_len = len;
+            self.current_ir_reg = None;
+        }
+    }
+
+    pub(crate) fn set_idle_cycles(&mut self, idle_cycles: u8) {
+        self.idle_cycles = idle_cycles;
+    }
+
+    pub(crate) fn idle_cycles(&self) -> u8 {
+        self.idle_cycles
+    }
+}
+
+/// A probe which bit-bangs JTAG, and keeps the state of its scan chain in a [`JtagChain`].
+pub(crate) trait BitBangJtag: RawProtocolIo {
+    fn jtag_chain(&mut self) -> &mut JtagChain;
+}
+
+/// Reset the TAPs, detect the scan chain unless it was set by the user, and select the TAP
+/// used as the debug target.
+pub(crate) fn attach<P: BitBangJtag>(probe: &mut P) -> Result<(), DebugProbeError> {
+    probe.jtag_chain().current_ir_reg = None;
+
+    reset(probe)?;
+
+    if probe.jtag_chain().chain.is_none() {
+        let chain = detect_scan_chain(probe)?;
+        probe.jtag_chain().chain = Some(chain);
+    }
+
+    let state = probe.jtag_chain();
+    state.params = select_tap(state.scan_chain(), state.tap)?;
+
+    Ok(())
+}
+
+/// Select the register `address` of the debug TAP, shift `len` bits of `data` through it, and
+/// return the captured bits.
+///
+/// The IR register is only written if it doesn't contain `address` already. Starts and ends in
+/// the Run-Test/Idle state, and stays there for the configured number of idle cycles.
+pub(crate) fn transfer_register<P: BitBangJtag>(
+    probe: &mut P,
+    address: u32,
+    data: &[u8],
+    len: u32,
+) -> Result<Vec<u8>, DebugProbeError> {
+    let state = probe.jtag_chain();
+    let params = state.params;
+    let idle_cycles = state.idle_cycles as usize;
+
+    if state.current_ir_reg != Some(address) {
+        let ir_bits = params.ir_bits(address, state.ir_len as usize);
+
+        shift_and_idle(probe, true, &ir_bits, 0)?;
+        probe.jtag_chain().current_ir_reg = Some(address);
+    }
+
+    let len = len as usize;
+    let dr_bits = params.dr_bits(bytes_to_bits(data, len));
+
+    let captured = shift_and_idle(probe, false, &dr_bits, idle_cycles)?;
+
+    Ok(bits_to_bytes(params.dr_response(&captured, len)))
+}
+
+/// Convert bits to bytes, with the first bit in the lowest bit of the first byte.
+pub(crate) fn bits_to_bytes(bits: &[bool]) -> Vec<u8> {
+    bits.chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (i, bit)| byte | u8::from(*bit) << i)
+        })
+        .collect()
+}
+
+/// Convert the first `len` bits of `bytes` to bits, see [`bits_to_bytes`].
+pub(crate) fn bytes_to_bits(bytes: &[u8], len: usize) -> Vec<bool> {
+    (0..len)
+        .map(|i| bytes[i / 8] & (1 << (i % 8)) != 0)
+        .collect()
+}
+
+/// Reset all TAPs, and move to the Run-Test/Idle state.
+pub(crate) fn reset<P: RawProtocolIo + ?Sized>(probe: &mut P) -> Result<(), DebugProbeError> {
+    probe.jtag_io([true, true, true, true, true, false], [false; 6])?;
+
+    Ok(())
+}
+
+/// Shift `tdi` through the IR or DR registers of the chain, and return the bits shifted out.
+///
+/// Starts and ends in the Run-Test/Idle state.
+pub(crate) fn shift<P: RawProtocolIo + ?Sized>(
+    probe: &mut P,
+    ir: bool,
+    tdi: &[bool],
+) -> Result<Vec<bool>, DebugProbeError> {
+    shift_and_idle(probe, ir, tdi, 0)
+}
+
+/// Like [`shift`], but stays in the Run-Test/Idle state for `idle_cycles` afterwards.
+fn shift_and_idle<P: RawProtocolIo + ?Sized>(
+    probe: &mut P,
+    ir: bool,
+    tdi: &[bool],
+    idle_cycles: usize,
+) -> Result<Vec<bool>, DebugProbeError> {
+    let header: &[bool] = if ir {
+        // Run-Test/Idle -> Select-DR-Scan -> Select-IR-Scan -> Capture-IR -> Shift-IR
+        &[true, true, false, false]
+    } else {
+        // Run-Test/Idle -> Select-DR-Scan -> Capture-DR -> Shift-DR
+        &[true, false, false]
+    };
+
+    let mut tms = header.to_vec();
+    let mut bits = vec![false; header.len()];
+
+    // The last bit is shifted when leaving the Shift state
+    tms.extend((0..tdi.len()).map(|i| i == tdi.len() - 1));
+    bits.extend_from_slice(tdi);
+
+    // Exit1 -> Update -> Run-Test/Idle, and stay there for the idle cycles
+    let trailer = 2 + idle_cycles;
+    tms.extend((0..trailer).map(|i| i == 0));
+    bits.resize(bits.len() + trailer, false);
+
+    let response = probe.jtag_io(tms, bits)?;
+
+    Ok(response[header.len()..header.len() + tdi.len()].to_vec())
+}
+
+/// Detect the TAPs in the scan chain, and their IR lengths.
+///
+/// If no TAPs respond, the TAPs might be in the two pin cJTAG mode, and are switched to
+/// four pin JTAG. If the chain contains a TI ICEPick, the Cortex-M debug TAP is connected
+/// to the chain.
+///
+/// This resets all TAPs, and leaves them in the Run-Test/Idle state.
+pub(crate) fn detect_scan_chain<P: RawProtocolIo + ?Sized>(
+    probe: &mut P,
+) -> Result<Vec<ScanChainElement>, DebugProbeError> {
+    let mut chain = scan(probe)?;
+
+    if chain.is_empty() {
+        log::debug!("No JTAG TAPs detected, switching cJTAG TAPs to four pin JTAG");
+        cjtag::switch_to_4_wire(probe)?;
+        chain = scan(probe)?;
+    }
+
+    if let Some(index) = icepick::find(&chain) {
+        icepick::connect_debug_tap(probe, &chain, index, icepick::CORTEX_M_DEBUG_TAP)?;
+        chain = scan(probe)?;
+    }
+
+    for (index, tap) in chain.iter().enumerate() {
+        match tap.idcode {
+            Some(idcode) => log::info!(
+                "JTAG TAP {}: IDCODE {:#010x}, IR length {}",
+                index,
+                idcode,
+                tap.ir_len
+            ),
+            None => log::info!("JTAG TAP {}: no IDCODE, IR length {}", index, tap.ir_len),
+        }
+    }
+
+    Ok(chain)
+}
+
+/// Detect the TAPs which are currently in the scan chain, and their IR lengths.
+fn scan<P: RawProtocolIo + ?Sized>(
+    probe: &mut P,
+) -> Result<Vec<ScanChainElement>, DebugProbeError> {
+    reset(probe)?;
+
+    let dr = shift(probe, false, &[true; MAX_TAPS * 32 + 32])?;
+    let idcodes = parse_idcodes(&dr);
+
+    if idcodes.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Whatever is shifted into the IR registers shows up at TDO again, delayed by the
+    // total length of the registers. Shifting in ones followed by zeros, the number of
+    // ones shifted out after the captured values is the total length.
+    reset(probe)?;
+
+    let mut tdi = vec![true; MAX_IR_BITS];
+    tdi.resize(2 * MAX_IR_BITS, false);
+
+    let ir = shift(probe, true, &tdi)?;
+    let total_len = ir[MAX_IR_BITS..].iter().filter(|bit| **bit).count();
+
+    let ir_lengths = parse_ir_lengths(&ir, idcodes.len(), total_len).ok_or_else(|| {
+        DebugProbeError::Other(anyhow::anyhow!(
+            "Unable to detect the IR lengths of the {} TAPs in the JTAG scan chain, the scan chain has to be specified manually.",
+            idcodes.len()
+        ))
+    })?;
+
+    reset(probe)?;
+
+    let chain: Vec<ScanChainElement> = idcodes
+        .into_iter()
+        .zip(ir_lengths)
+        .map(|(idcode, ir_len)| ScanChainElement { idcode, ir_len })
+        .collect();
+
+    Ok(chain)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        bits_to_bytes, bytes_to_bits, parse_idcodes, parse_ir_lengths, ChainParams,
+        ScanChainElement,
+    };
+
+    fn to_bits(value: u64, len: usize) -> Vec<bool> {
+        (0..len).map(|i| value & (1 << i) != 0).collect()
+    }
+
+    #[test]
+    fn idcodes_with_bypass_tap() {
+        let mut bits = to_bits(0x4ba0_0477, 32);
+        bits.push(false);
+        bits.extend(to_bits(0x1000_563d, 32));
+        bits.extend(vec![true; 64]);
+
+        assert_eq!(
+            parse_idcodes(&bits),
+            vec![Some(0x4ba0_0477), None, Some(0x1000_563d)]
+        );
+    }
+
+    #[test]
+    fn bit_conversion_round_trip() {
+        let bytes = [0xa5, 0x03];
+        let bits = bytes_to_bits(&bytes, 10);
+
+        assert_eq!(bits_to_bytes(&bits), bytes);
+    }
+
+    #[test]
+    fn empty_chain() {
+        assert_eq!(parse_idcodes(&[true; 64]), vec![]);
+    }
+
+    #[test]
+    fn ir_lengths() {
+        // A TAP with a 4 bit IR, followed by a TAP with a 5 bit IR.
+        let mut bits = to_bits(0b0001, 4);
+        bits.extend(to_bits(0b00001, 5));
+
+        assert_eq!(parse_ir_lengths(&bits, 2, 9), Some(vec![4, 5]));
+        assert_eq!(parse_ir_lengths(&bits, 3, 9), None);
+
+        // A single TAP doesn't need to capture the standard pattern.
+        assert_eq!(parse_ir_lengths(&to_bits(0b00101, 5), 1, 5), Some(vec![5]));
+    }
+
+    #[test]
+    fn chain_params() {
+        let chain = [
+            ScanChainElement {
+                idcode: Some(0x4ba0_0477),
+                ir_len: 4,
+            },
+            ScanChainElement {
+                idcode: None,
+                ir_len: 6,
+            },
+            ScanChainElement {
+                idcode: Some(0x1000_563d),
+                ir_len: 5,
+            },
+        ];
+
+        let params = ChainParams::from_chain(&chain, 1).unwrap();
+
+        assert_eq!(
+            params,
+            ChainParams {
+                irpre: 4,
+                irpost: 5,
+                drpre: 1,
+                drpost: 1,
+                irlen: 6,
+            }
+        );
+
+        assert_eq!(
+            params.ir_bits(0b000010, 6),
+            [vec![true; 4], to_bits(0b000010, 6), vec![true; 5]].concat()
+        );
+        assert_eq!(params.dr_bits([true, true]), vec![false, true, true, false]);
+        assert_eq!(
+            params.dr_response(&[false, true, false, true], 2),
+            &[true, false]
+        );
+
+        assert_eq!(ChainParams::from_chain(&chain, 3), None);
+    }
+}
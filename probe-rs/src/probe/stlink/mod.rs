@@ -1,9 +1,12 @@
 pub mod constants;
 pub mod tools;
+mod uart;
 mod usb_interface;
 
 use self::usb_interface::{StLinkUsb, StLinkUsbDevice};
-use super::{DebugProbe, DebugProbeError, ProbeCreationError, WireProtocol};
+use super::{
+    DebugProbe, DebugProbeError, ProbeCapabilities, ProbeCreationError, ProbeUart, WireProtocol,
+};
 use crate::memory::valid_32_address;
 use crate::{
     architecture::arm::{
@@ -83,7 +86,7 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
     fn speed_khz(&self) -> u32 {
         match self.protocol {
             WireProtocol::Swd => self.swd_speed_khz,
-            WireProtocol::Jtag => self.jtag_speed_khz,
+            WireProtocol::Jtag | WireProtocol::Cjtag => self.jtag_speed_khz,
         }
     }
 
@@ -103,7 +106,7 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
                         Err(DebugProbeError::UnsupportedSpeed(speed_khz))
                     }
                 }
-                WireProtocol::Jtag => {
+                WireProtocol::Jtag | WireProtocol::Cjtag => {
                     let actual_speed = JTagFrequencyToDivider::find_setting(speed_khz);
 
                     if let Some(actual_speed) = actual_speed {
@@ -130,7 +133,9 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
 
                 match self.protocol {
                     WireProtocol::Swd => self.swd_speed_khz = actual_speed_khz,
-                    WireProtocol::Jtag => self.jtag_speed_khz = actual_speed_khz,
+                    WireProtocol::Jtag | WireProtocol::Cjtag => {
+                        self.jtag_speed_khz = actual_speed_khz
+                    }
                 }
 
                 Ok(actual_speed_khz)
@@ -144,7 +149,7 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
         self.enter_idle()?;
 
         let param = match self.protocol {
-            WireProtocol::Jtag => {
+            WireProtocol::Jtag | WireProtocol::Cjtag => {
                 log::debug!("Switching protocol to JTAG");
                 commands::JTAG_ENTER_JTAG_NO_CORE_RESET
             }
@@ -183,7 +188,7 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
         // To ensure the default speed is used if not changed,
         // we set the speed again here.
         match self.protocol {
-            WireProtocol::Jtag => {
+            WireProtocol::Jtag | WireProtocol::Cjtag => {
                 self.set_speed(self.jtag_speed_khz)?;
             }
             WireProtocol::Swd => {
@@ -249,6 +254,7 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
         match protocol {
             WireProtocol::Jtag => self.protocol = WireProtocol::Jtag,
             WireProtocol::Swd => self.protocol = WireProtocol::Swd,
+            WireProtocol::Cjtag => return Err(DebugProbeError::UnsupportedProtocol(protocol)),
         }
         Ok(())
     }
@@ -265,6 +271,12 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
         Some(self as _)
     }
 
+    fn open_uart(&mut self, baud_rate: u32) -> Result<Box<dyn ProbeUart>, DebugProbeError> {
+        let uart = uart::StLinkUart::open(&self.device.usb_device(), baud_rate)?;
+
+        Ok(Box::new(uart))
+    }
+
     fn has_arm_interface(&self) -> bool {
         true
     }
@@ -296,6 +308,28 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
                 }
             })
     }
+
+    fn capabilities(&self) -> ProbeCapabilities {
+        let mut capabilities = ProbeCapabilities::default();
+
+        // The available speeds of the V3 can only be queried from the probe itself.
+        if self.hw_version < 3 {
+            capabilities.max_speed_khz = match self.protocol {
+                WireProtocol::Swd => Some(SwdFrequencyToDelayCount::Hz4600000.to_khz()),
+                WireProtocol::Jtag | WireProtocol::Cjtag => {
+                    Some(JTagFrequencyToDivider::Hz18000000.to_khz())
+                }
+            };
+        }
+
+        capabilities.protocols = vec![WireProtocol::Swd, WireProtocol::Jtag];
+        capabilities.swo_modes = vec![SwoMode::Uart];
+        capabilities.target_voltage = true;
+        // Only the original ST-Link V2 has no virtual COM port.
+        capabilities.uart = self.device.info.version_name != "V2";
+
+        capabilities
+    }
 }
 
 impl<D: StLinkUsb> Drop for StLink<D> {
@@ -541,7 +575,7 @@ impl<D: StLinkUsb> StLink<D> {
 
         let cmd_proto = match protocol {
             WireProtocol::Swd => 0,
-            WireProtocol::Jtag => 1,
+            WireProtocol::Jtag | WireProtocol::Cjtag => 1,
         };
 
         let mut command = vec![commands::JTAG_COMMAND, commands::SET_COM_FREQ, cmd_proto, 0];
@@ -564,7 +598,7 @@ impl<D: StLinkUsb> StLink<D> {
 
         let cmd_proto = match protocol {
             WireProtocol::Swd => 0,
-            WireProtocol::Jtag => 1,
+            WireProtocol::Jtag | WireProtocol::Cjtag => 1,
         };
 
         let mut buf = [0; 52];
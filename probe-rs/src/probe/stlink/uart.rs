@@ -0,0 +1,217 @@
+//! Access to the virtual COM port of ST-Links.
+//!
+//! ST-Link V2-1 and V3 probes contain a USB to UART bridge, which is exposed
+//! as a USB CDC ACM interface next to the debug interface. The bridge is
+//! accessed directly over USB, so the serial port of the OS is not required,
+//! and the correct port is always used for the probe.
+
+use std::io;
+use std::time::Duration;
+
+use rusb::{Direction, TransferType};
+
+use crate::{DebugProbeError, ProbeUart};
+
+/// USB class code of CDC communication interfaces.
+const CDC_COMMUNICATION_CLASS: u8 = 0x02;
+
+/// USB class code of CDC data interfaces.
+const CDC_DATA_CLASS: u8 = 0x0a;
+
+/// CDC request to configure baud rate and data format.
+const SET_LINE_CODING: u8 = 0x20;
+
+/// CDC request to set the DTR and RTS signals.
+const SET_CONTROL_LINE_STATE: u8 = 0x22;
+
+/// Request type for class specific requests to an interface.
+const CLASS_INTERFACE_REQUEST: u8 = 0x21;
+
+const CONTROL_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The UART bridge of an ST-Link.
+pub struct StLinkUart {
+    handle: rusb::DeviceHandle<rusb::Context>,
+    communication_interface: u8,
+    data_interface: u8,
+    ep_in: u8,
+    ep_out: u8,
+    timeout: Duration,
+}
+
+impl std::fmt::Debug for StLinkUart {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("StLinkUart")
+            .field("communication_interface", &self.communication_interface)
+            .field("data_interface", &self.data_interface)
+            .field("ep_in", &self.ep_in)
+            .field("ep_out", &self.ep_out)
+            .finish()
+    }
+}
+
+impl StLinkUart {
+    /// Open the first UART bridge of the given ST-Link.
+    ///
+    /// A separate handle for the USB device is used, so the
+    /// debug interface can be used at the same time.
+    pub(super) fn open(
+        device: &rusb::Device<rusb::Context>,
+        baud_rate: u32,
+    ) -> Result<Self, DebugProbeError> {
+        let config = device.active_config_descriptor().map_err(usb_error)?;
+
+        let mut communication_interface = None;
+        let mut data_interface = None;
+
+        for interface in config.interfaces() {
+            for descriptor in interface.descriptors() {
+                match descriptor.class_code() {
+                    CDC_COMMUNICATION_CLASS if communication_interface.is_none() => {
+                        communication_interface = Some(descriptor.interface_number());
+                    }
+                    CDC_DATA_CLASS if communication_interface.is_some() => {
+                        let mut ep_in = None;
+                        let mut ep_out = None;
+
+                        for endpoint in descriptor.endpoint_descriptors() {
+                            if endpoint.transfer_type() != TransferType::Bulk {
+                                continue;
+                            }
+
+                            match endpoint.direction() {
+                                Direction::In => ep_in = Some(endpoint.address()),
+                                Direction::Out => ep_out = Some(endpoint.address()),
+                            }
+                        }
+
+                        if let (Some(ep_in), Some(ep_out)) = (ep_in, ep_out) {
+                            data_interface = Some((descriptor.interface_number(), ep_in, ep_out));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            if data_interface.is_some() {
+                break;
+            }
+        }
+
+        let (communication_interface, (data_interface, ep_in, ep_out)) =
+            match (communication_interface, data_interface) {
+                (Some(communication), Some(data)) => (communication, data),
+                _ => return Err(DebugProbeError::InterfaceNotAvailable("UART")),
+            };
+
+        let mut handle = device.open().map_err(usb_error)?;
+
+        // On Linux, the interfaces are usually claimed by the cdc_acm driver.
+        match handle.set_auto_detach_kernel_driver(true) {
+            Ok(()) | Err(rusb::Error::NotSupported) => {}
+            Err(e) => return Err(usb_error(e)),
+        }
+
+        handle
+            .claim_interface(communication_interface)
+            .map_err(usb_error)?;
+        handle.claim_interface(data_interface).map_err(usb_error)?;
+
+        log::debug!(
+            "Opened ST-Link UART on interfaces {} and {}",
+            communication_interface,
+            data_interface
+        );
+
+        let mut uart = Self {
+            handle,
+            communication_interface,
+            data_interface,
+            ep_in,
+            ep_out,
+            timeout: Duration::from_millis(100),
+        };
+
+        uart.set_baud_rate(baud_rate)?;
+
+        // Assert DTR and RTS, some firmware versions only forward data if DTR is set.
+        uart.handle
+            .write_control(
+                CLASS_INTERFACE_REQUEST,
+                SET_CONTROL_LINE_STATE,
+                0x03,
+                communication_interface as u16,
+                &[],
+                CONTROL_TIMEOUT,
+            )
+            .map_err(usb_error)?;
+
+        Ok(uart)
+    }
+}
+
+impl ProbeUart for StLinkUart {
+    fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), DebugProbeError> {
+        // Line coding: baud rate, 1 stop bit, no parity, 8 data bits.
+        let mut line_coding = [0u8; 7];
+        line_coding[..4].copy_from_slice(&baud_rate.to_le_bytes());
+        line_coding[6] = 8;
+
+        self.handle
+            .write_control(
+                CLASS_INTERFACE_REQUEST,
+                SET_LINE_CODING,
+                0,
+                self.communication_interface as u16,
+                &line_coding,
+                CONTROL_TIMEOUT,
+            )
+            .map_err(usb_error)?;
+
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+impl io::Read for StLinkUart {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.handle
+            .read_bulk(self.ep_in, buf, self.timeout)
+            .map_err(io_error)
+    }
+}
+
+impl io::Write for StLinkUart {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.handle
+            .write_bulk(self.ep_out, buf, self.timeout)
+            .map_err(io_error)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for StLinkUart {
+    fn drop(&mut self) {
+        // We ignore the error case as we can't do much about it anyways.
+        let _ = self.handle.release_interface(self.data_interface);
+        let _ = self.handle.release_interface(self.communication_interface);
+    }
+}
+
+fn usb_error(error: rusb::Error) -> DebugProbeError {
+    DebugProbeError::Usb(Some(Box::new(error)))
+}
+
+fn io_error(error: rusb::Error) -> io::Error {
+    match error {
+        rusb::Error::Timeout => io::Error::new(io::ErrorKind::TimedOut, error),
+        rusb::Error::NoDevice => io::Error::new(io::ErrorKind::NotConnected, error),
+        error => io::Error::new(io::ErrorKind::Other, error),
+    }
+}
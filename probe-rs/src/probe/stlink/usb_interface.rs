@@ -194,6 +194,11 @@ impl StLinkUsbDevice {
         Ok(usb_stlink)
     }
 
+    /// The USB device of the probe.
+    pub(crate) fn usb_device(&self) -> rusb::Device<rusb::Context> {
+        self.device_handle.device()
+    }
+
     /// Closes the USB interface gracefully.
     /// Internal helper.
     fn close(&mut self) -> Result<(), Error> {
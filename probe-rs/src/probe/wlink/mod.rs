@@ -0,0 +1,534 @@
+//! Driver for the WCH-Link and WCH-LinkE debug probes, in RISC-V mode.
+//!
+//! The WCH-Link does not offer raw access to the JTAG signals. Instead, it
+//! uses the proprietary single wire debug interface (SDI) of the CH32V chips,
+//! and allows direct access to the registers of the RISC-V debug module.
+//!
+//! To be able to use the existing RISC-V implementation, the JTAG registers
+//! of the debug transport module (`dtmcs` and `dmi`) are emulated on top
+//! of the DMI access commands of the probe.
+
+use std::time::Duration;
+
+use rusb::{Context, Device, UsbContext};
+
+use crate::{
+    architecture::{
+        arm::{
+            communication_interface::{DapProbe, UninitializedArmProbe},
+            SwoAccess,
+        },
+        riscv::communication_interface::RiscvCommunicationInterface,
+    },
+    probe::{DebugProbeInfo, DebugProbeType, JTAGAccess, ProbeCreationError},
+    DebugProbe, DebugProbeError, DebugProbeSelector, WireProtocol,
+};
+
+const VENDOR_ID: u16 = 0x1a86;
+/// Product ID of the WCH-Link in RISC-V mode. In ARM mode,
+/// the probe is a CMSIS-DAP probe and handled by that driver.
+const PRODUCT_ID: u16 = 0x8010;
+
+const ENDPOINT_OUT: u8 = 0x01;
+const ENDPOINT_IN: u8 = 0x81;
+
+const USB_TIMEOUT: Duration = Duration::from_millis(5000);
+
+const COMMAND_REQUEST: u8 = 0x81;
+const COMMAND_RESPONSE: u8 = 0x82;
+
+const COMMAND_DMI_OP: u8 = 0x08;
+const COMMAND_SET_SPEED: u8 = 0x0c;
+const COMMAND_CONTROL: u8 = 0x0d;
+
+const CONTROL_GET_PROBE_INFO: u8 = 0x01;
+const CONTROL_ATTACH_CHIP: u8 = 0x02;
+const CONTROL_DETACH: u8 = 0xff;
+
+/// Address of the emulated `dtmcs` JTAG register.
+const DTMCS_ADDRESS: u32 = 0x10;
+/// Address of the emulated `dmi` JTAG register.
+const DMI_ADDRESS: u32 = 0x11;
+
+/// The DMI access commands of the probe use 7 bit addresses.
+const DMI_ABITS: u32 = 7;
+
+/// Value of the emulated `dtmcs` register: Version 0.13, 7 address bits, no idle cycles.
+const DTMCS_VALUE: u32 = DMI_ABITS << 4 | 1;
+
+const DMI_OP_STATUS_BUSY: u8 = 3;
+
+/// How often a DMI operation is retried if the debug module is busy.
+const DMI_BUSY_RETRIES: usize = 100;
+
+/// The fixed speeds supported by the probe, in kHz, and the value used to select them.
+const SPEEDS: [(u32, u8); 3] = [(6000, 0x01), (4000, 0x02), (400, 0x03)];
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum WchLinkError {
+    #[error("The probe returned error code {0:#04x} for command {1:#04x}.")]
+    Protocol(u8, u8),
+    #[error("Received a malformed response from the probe.")]
+    MalformedResponse,
+    #[error("Unable to attach to the target chip.")]
+    AttachFailed,
+}
+
+impl From<WchLinkError> for DebugProbeError {
+    fn from(error: WchLinkError) -> Self {
+        DebugProbeError::ProbeSpecific(Box::new(error))
+    }
+}
+
+pub(crate) struct WchLink {
+    device_handle: rusb::DeviceHandle<rusb::Context>,
+
+    speed_khz: u32,
+
+    /// The chip family reported when attaching, needed to configure the speed.
+    chip_family: Option<u8>,
+
+    /// The JTAG DTM returns the result of the previous DMI operation
+    /// during the next access. To emulate this, the result of the last
+    /// DMI operation is stored here.
+    last_dmi_result: (u32, u8),
+
+    idle_cycles: u8,
+}
+
+impl std::fmt::Debug for WchLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WchLink")
+            .field("speed_khz", &self.speed_khz)
+            .field("chip_family", &self.chip_family)
+            .finish()
+    }
+}
+
+impl WchLink {
+    /// Send a command to the probe, and return the payload of the response.
+    fn send_command(&mut self, command: u8, payload: &[u8]) -> Result<Vec<u8>, DebugProbeError> {
+        let mut request = Vec::with_capacity(payload.len() + 3);
+        request.extend_from_slice(&[COMMAND_REQUEST, command, payload.len() as u8]);
+        request.extend_from_slice(payload);
+
+        log::trace!("WCH-Link request: {:02x?}", request);
+
+        self.device_handle
+            .write_bulk(ENDPOINT_OUT, &request, USB_TIMEOUT)
+            .map_err(|e| DebugProbeError::Usb(Some(Box::new(e))))?;
+
+        let mut response = [0u8; 64];
+
+        let length = self
+            .device_handle
+            .read_bulk(ENDPOINT_IN, &mut response, USB_TIMEOUT)
+            .map_err(|e| DebugProbeError::Usb(Some(Box::new(e))))?;
+
+        log::trace!("WCH-Link response: {:02x?}", &response[..length]);
+
+        parse_response(command, &response[..length])
+    }
+
+    /// Perform a DMI operation, and return the read value together with the operation status.
+    fn dmi_op(&mut self, address: u8, data: u32, op: u8) -> Result<(u32, u8), DebugProbeError> {
+        let mut payload = [0u8; 6];
+        payload[0] = address;
+        payload[1..5].copy_from_slice(&data.to_be_bytes());
+        payload[5] = op;
+
+        for _ in 0..DMI_BUSY_RETRIES {
+            let response = self.send_command(COMMAND_DMI_OP, &payload)?;
+
+            if response.len() != 6 {
+                return Err(WchLinkError::MalformedResponse.into());
+            }
+
+            let value = u32::from_be_bytes([response[1], response[2], response[3], response[4]]);
+            let status = response[5];
+
+            if status != DMI_OP_STATUS_BUSY {
+                return Ok((value, status));
+            }
+        }
+
+        Ok((0, DMI_OP_STATUS_BUSY))
+    }
+
+    /// Emulate an access to the `dmi` JTAG register.
+    fn dmi_register_access(&mut self, data: &[u8], len: u32) -> Result<Vec<u8>, DebugProbeError> {
+        let (address, value, op) = unpack_dmi_register(data);
+
+        let previous_result = self.last_dmi_result;
+
+        // A NOP leaves the data register unchanged.
+        self.last_dmi_result = if op == 0 {
+            (previous_result.0, 0)
+        } else {
+            self.dmi_op(address, value, op)?
+        };
+
+        Ok(pack_dmi_register(previous_result.0, previous_result.1, len))
+    }
+
+    fn apply_speed(&mut self) -> Result<(), DebugProbeError> {
+        let chip_family = match self.chip_family {
+            Some(chip_family) => chip_family,
+            // Speed is configured when attaching.
+            None => return Ok(()),
+        };
+
+        let speed = speed_setting(self.speed_khz)
+            .ok_or(DebugProbeError::UnsupportedSpeed(self.speed_khz))?;
+
+        self.send_command(COMMAND_SET_SPEED, &[chip_family, speed])?;
+
+        Ok(())
+    }
+}
+
+/// Check the header of a response, and return its payload.
+fn parse_response(command: u8, response: &[u8]) -> Result<Vec<u8>, DebugProbeError> {
+    if response.len() < 3 {
+        return Err(WchLinkError::MalformedResponse.into());
+    }
+
+    match response[0] {
+        COMMAND_RESPONSE if response[1] == command => {
+            let length = (response[2] as usize).min(response.len() - 3);
+            Ok(response[3..3 + length].to_vec())
+        }
+        COMMAND_REQUEST => Err(WchLinkError::Protocol(response[1], command).into()),
+        _ => Err(WchLinkError::MalformedResponse.into()),
+    }
+}
+
+/// Split the content of a `dmi` register scan into address, data and operation.
+fn unpack_dmi_register(data: &[u8]) -> (u8, u32, u8) {
+    let mut bytes = [0u8; 16];
+    let length = data.len().min(16);
+    bytes[..length].copy_from_slice(&data[..length]);
+
+    let value = u128::from_le_bytes(bytes);
+
+    let op = (value & 0x3) as u8;
+    let data = (value >> 2) as u32;
+    let address = ((value >> 34) & ((1 << DMI_ABITS) - 1)) as u8;
+
+    (address, data, op)
+}
+
+/// Build the content of a `dmi` register scan from data and operation status.
+fn pack_dmi_register(data: u32, status: u8, len: u32) -> Vec<u8> {
+    let value = (data as u128) << 2 | (status & 0x3) as u128;
+
+    value.to_le_bytes()[..(len as usize + 7) / 8].to_vec()
+}
+
+/// Select the fastest supported speed which is not higher than `speed_khz`.
+fn speed_setting(speed_khz: u32) -> Option<u8> {
+    SPEEDS
+        .iter()
+        .find(|(speed, _)| *speed <= speed_khz)
+        .map(|(_, setting)| *setting)
+}
+
+fn is_wlink_device<T: UsbContext>(device: &Device<T>) -> bool {
+    match device.device_descriptor() {
+        Ok(descriptor) => {
+            descriptor.vendor_id() == VENDOR_ID && descriptor.product_id() == PRODUCT_ID
+        }
+        Err(_) => false,
+    }
+}
+
+fn read_serial_number<T: UsbContext>(device: &Device<T>) -> Option<String> {
+    let descriptor = device.device_descriptor().ok()?;
+    let handle = device.open().ok()?;
+
+    handle.read_serial_number_string_ascii(&descriptor).ok()
+}
+
+/// Finds all connected WCH-Link probes.
+pub(crate) fn list_wlink_devices() -> Vec<DebugProbeInfo> {
+    rusb::Context::new()
+        .and_then(|context| context.devices())
+        .map_or(vec![], |devices| {
+            devices
+                .iter()
+                .filter(is_wlink_device)
+                .map(|device| {
+                    DebugProbeInfo::new(
+                        "WCH-Link",
+                        VENDOR_ID,
+                        PRODUCT_ID,
+                        read_serial_number(&device),
+                        DebugProbeType::WchLink,
+                        None,
+                    )
+                })
+                .collect()
+        })
+}
+
+impl DebugProbe for WchLink {
+    fn new_from_selector(
+        selector: impl Into<DebugProbeSelector>,
+    ) -> Result<Box<Self>, DebugProbeError> {
+        let selector = selector.into();
+
+        if selector.vendor_id != VENDOR_ID || selector.product_id != PRODUCT_ID {
+            return Err(ProbeCreationError::NotFound.into());
+        }
+
+        let context = Context::new().map_err(ProbeCreationError::from)?;
+
+        let device = context
+            .devices()
+            .map_err(ProbeCreationError::from)?
+            .iter()
+            .filter(is_wlink_device)
+            .find(|device| {
+                selector.serial_number.is_none()
+                    || read_serial_number(device) == selector.serial_number
+            })
+            .ok_or(ProbeCreationError::NotFound)?;
+
+        let mut device_handle = device.open().map_err(ProbeCreationError::from)?;
+
+        device_handle
+            .claim_interface(0)
+            .map_err(ProbeCreationError::from)?;
+
+        log::debug!("Aquired handle for WCH-Link");
+
+        let mut probe = WchLink {
+            device_handle,
+            speed_khz: SPEEDS[0].0,
+            chip_family: None,
+            last_dmi_result: (0, 0),
+            idle_cycles: 0,
+        };
+
+        let info = probe.send_command(COMMAND_CONTROL, &[CONTROL_GET_PROBE_INFO])?;
+
+        if info.len() >= 2 {
+            log::info!("WCH-Link: Firmware version: {}.{}", info[0], info[1]);
+        }
+
+        Ok(Box::new(probe))
+    }
+
+    fn get_name(&self) -> &str {
+        "WCH-Link"
+    }
+
+    fn speed_khz(&self) -> u32 {
+        self.speed_khz
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        let setting =
+            speed_setting(speed_khz).ok_or(DebugProbeError::UnsupportedSpeed(speed_khz))?;
+
+        // NOTE(unwrap): The setting was found in the list above.
+        self.speed_khz = SPEEDS.iter().find(|(_, s)| *s == setting).unwrap().0;
+
+        self.apply_speed()?;
+
+        Ok(self.speed_khz)
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        log::debug!("Attaching to WCH-Link");
+
+        let mut chip = None;
+
+        // Attaching sometimes fails on the first try, e.g. when the chip is in a low power state.
+        for _ in 0..3 {
+            match self.send_command(COMMAND_CONTROL, &[CONTROL_ATTACH_CHIP]) {
+                Ok(response) if response.len() >= 5 => {
+                    chip = Some(response);
+                    break;
+                }
+                Ok(_) => return Err(WchLinkError::MalformedResponse.into()),
+                Err(e) => log::debug!("Attaching to chip failed, retrying: {}", e),
+            }
+        }
+
+        let chip = chip.ok_or(WchLinkError::AttachFailed)?;
+
+        let chip_id = u32::from_be_bytes([chip[1], chip[2], chip[3], chip[4]]);
+
+        log::info!(
+            "WCH-Link: Attached to chip family {:#04x}, chip id {:#010x}",
+            chip[0],
+            chip_id
+        );
+
+        self.chip_family = Some(chip[0]);
+        self.apply_speed()?;
+
+        self.last_dmi_result = (0, 0);
+
+        Ok(())
+    }
+
+    fn detach(&mut self) -> Result<(), DebugProbeError> {
+        if self.chip_family.take().is_some() {
+            self.send_command(COMMAND_CONTROL, &[CONTROL_DETACH])?;
+        }
+
+        Ok(())
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::NotImplemented("target_reset"))
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::NotImplemented("target_reset_assert"))
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::NotImplemented("target_reset_deassert"))
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        // The debug module is accessed through an emulated JTAG DTM.
+        if matches!(protocol, WireProtocol::Jtag) {
+            Ok(())
+        } else {
+            Err(DebugProbeError::UnsupportedProtocol(protocol))
+        }
+    }
+
+    fn active_protocol(&self) -> Option<WireProtocol> {
+        Some(WireProtocol::Jtag)
+    }
+
+    fn has_riscv_interface(&self) -> bool {
+        true
+    }
+
+    fn try_get_riscv_interface(
+        self: Box<Self>,
+    ) -> Result<RiscvCommunicationInterface, (Box<dyn DebugProbe>, DebugProbeError)> {
+        match RiscvCommunicationInterface::new(self) {
+            Ok(interface) => Ok(interface),
+            Err((probe, err)) => Err((probe.into_probe(), err)),
+        }
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        // In RISC-V mode, the probe cannot debug ARM targets.
+        false
+    }
+
+    fn try_get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
+    {
+        Err((self, DebugProbeError::InterfaceNotAvailable("SWD/ARM")))
+    }
+
+    fn get_swo_interface(&self) -> Option<&dyn SwoAccess> {
+        None
+    }
+
+    fn get_swo_interface_mut(&mut self) -> Option<&mut dyn SwoAccess> {
+        None
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        None
+    }
+}
+
+impl JTAGAccess for WchLink {
+    fn read_register(&mut self, address: u32, len: u32) -> Result<Vec<u8>, DebugProbeError> {
+        match address {
+            DTMCS_ADDRESS => Ok(DTMCS_VALUE.to_le_bytes().to_vec()),
+            DMI_ADDRESS => self.dmi_register_access(&[0u8; 16], len),
+            _ => {
+                log::debug!(
+                    "Read from JTAG register {:#x} is not supported by the WCH-Link",
+                    address
+                );
+                Ok(vec![0u8; (len as usize + 7) / 8])
+            }
+        }
+    }
+
+    fn set_idle_cycles(&mut self, idle_cycles: u8) {
+        // Idle cycles are handled by the probe, the value is only stored.
+        self.idle_cycles = idle_cycles;
+    }
+
+    fn get_idle_cycles(&self) -> u8 {
+        self.idle_cycles
+    }
+
+    fn set_ir_len(&mut self, _len: u32) {
+        // There is no IR register to configure.
+    }
+
+    fn write_register(
+        &mut self,
+        address: u32,
+        data: &[u8],
+        len: u32,
+    ) -> Result<Vec<u8>, DebugProbeError> {
+        match address {
+            DTMCS_ADDRESS => {
+                // The only writable bits are dmireset and dmihardreset,
+                // which clear the status of the last DMI operation.
+                self.last_dmi_result.1 = 0;
+                Ok(DTMCS_VALUE.to_le_bytes().to_vec())
+            }
+            DMI_ADDRESS => self.dmi_register_access(data, len),
+            _ => Err(DebugProbeError::NotImplemented(
+                "Only the dtmcs and dmi registers are supported by the WCH-Link",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dmi_register_round_trip() {
+        // Read from dmstatus (0x11)
+        let scan: u64 = 0x11 << 34 | 0x1;
+
+        assert_eq!(unpack_dmi_register(&scan.to_le_bytes()), (0x11, 0, 1));
+
+        let response = pack_dmi_register(0xdead_beef, 0, 41);
+
+        assert_eq!(response.len(), 6);
+        assert_eq!(unpack_dmi_register(&response), (0, 0xdead_beef, 0));
+    }
+
+    #[test]
+    fn speed_selection() {
+        assert_eq!(speed_setting(10_000), Some(0x01));
+        assert_eq!(speed_setting(4000), Some(0x02));
+        assert_eq!(speed_setting(1000), Some(0x03));
+        assert_eq!(speed_setting(100), None);
+    }
+
+    #[test]
+    fn parse_error_response() {
+        assert!(parse_response(COMMAND_DMI_OP, &[0x81, 0x55, 0x01, 0x00]).is_err());
+        assert_eq!(
+            parse_response(COMMAND_CONTROL, &[0x82, 0x0d, 0x02, 0x02, 0x09]).unwrap(),
+            vec![0x02, 0x09]
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Decoding of semihosting requests.
+//!
+//! Semihosting lets a target use the console and other facilities of the debugger host.
+//! The target places an operation number and a parameter in registers, and then executes
+//! a special breakpoint sequence. The operations are defined by the Arm semihosting
+//! specification, which the RISC-V semihosting specification adopts unchanged.
+
+use crate::{Error, MemoryInterface};
+
+/// Write a single character to the console.
+const SYS_WRITEC: u32 = 0x03;
+/// Write a null-terminated string to the console.
+const SYS_WRITE0: u32 = 0x04;
+/// Write a buffer to a file handle.
+const SYS_WRITE: u32 = 0x05;
+/// Exit the application.
+const SYS_EXIT: u32 = 0x18;
+/// Exit the application, with an exit code also on 32-bit targets.
+const SYS_EXIT_EXTENDED: u32 = 0x20;
+
+/// The exit reason `ADP_Stopped_ApplicationExit`, used for a normal exit of the application.
+const ADP_STOPPED_APPLICATION_EXIT: u64 = 0x20026;
+
+/// A semihosting request of the target, which caused the core to halt.
+///
+/// After handling the request, use [`Core::finish_semihosting`](crate::Core::finish_semihosting)
+/// to return a value to the target and to continue after the semihosting sequence.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SemihostingCommand {
+    /// The target exited with status code 0.
+    ExitSuccess,
+    /// The target exited with an error.
+    ExitError {
+        /// The reason for the exit, `0x20026` (`ADP_Stopped_ApplicationExit`) for a normal exit.
+        reason: u64,
+        /// The exit status code, if reported by the target.
+        exit_status: Option<u64>,
+    },
+    /// The target writes a single character to the console (`SYS_WRITEC`).
+    WriteC {
+        /// The character to write.
+        character: u8,
+    },
+    /// The target writes a null-terminated string to the console (`SYS_WRITE0`).
+    ///
+    /// Use [`SemihostingCommand::read_output`] to read the string from the target.
+    Write0 {
+        /// The address of the string in target memory.
+        address: u64,
+    },
+    /// The target writes a buffer to a file handle (`SYS_WRITE`).
+    ///
+    /// Use [`SemihostingCommand::read_output`] to read the buffer from the target.
+    Write {
+        /// The file handle to write to.
+        handle: u64,
+        /// The address of the buffer in target memory.
+        address: u64,
+        /// The length of the buffer in bytes.
+        length: u64,
+    },
+    /// Any other semihosting operation, which is not decoded by probe-rs.
+    Unknown {
+        /// The operation number.
+        operation: u32,
+        /// The parameter of the operation, usually the address of a parameter block.
+        parameter: u64,
+    },
+}
+
+impl SemihostingCommand {
+    /// Decode the semihosting request with the given `operation` and `parameter`.
+    ///
+    /// Parameter blocks are read from the target memory, their fields are 64 bits wide
+    /// on 64-bit targets and 32 bits wide otherwise.
+    pub(crate) fn decode(
+        memory: &mut impl MemoryInterface,
+        operation: u32,
+        parameter: u64,
+        is_64_bit: bool,
+    ) -> Result<Self, Error> {
+        let command = match operation {
+            SYS_WRITEC => SemihostingCommand::WriteC {
+                character: memory.read_word_8(parameter)?,
+            },
+            SYS_WRITE0 => SemihostingCommand::Write0 { address: parameter },
+            SYS_WRITE => SemihostingCommand::Write {
+                handle: read_field(memory, parameter, 0, is_64_bit)?,
+                address: read_field(memory, parameter, 1, is_64_bit)?,
+                length: read_field(memory, parameter, 2, is_64_bit)?,
+            },
+            // On 32-bit targets, the parameter of SYS_EXIT is the reason itself.
+            SYS_EXIT if !is_64_bit => exit_command(parameter, None),
+            SYS_EXIT | SYS_EXIT_EXTENDED => exit_command(
+                read_field(memory, parameter, 0, is_64_bit)?,
+                Some(read_field(memory, parameter, 1, is_64_bit)?),
+            ),
+            _ => SemihostingCommand::Unknown {
+                operation,
+                parameter,
+            },
+        };
+
+        Ok(command)
+    }
+
+    /// Read the output written by a [`Write0`](SemihostingCommand::Write0) or a
+    /// [`Write`](SemihostingCommand::Write) request from the target memory.
+    ///
+    /// For a [`WriteC`](SemihostingCommand::WriteC) request, the character is returned.
+    /// Other requests have no output, and `None` is returned.
+    pub fn read_output(&self, memory: &mut impl MemoryInterface) -> Result<Option<Vec<u8>>, Error> {
+        let output = match *self {
+            SemihostingCommand::WriteC { character } => vec![character],
+            SemihostingCommand::Write0 { address } => {
+                let mut output = Vec::new();
+
+                loop {
+                    let character = memory.read_word_8(address + output.len() as u64)?;
+                    if character == 0 {
+                        break;
+                    }
+                    output.push(character);
+                }
+
+                output
+            }
+            SemihostingCommand::Write {
+                address, length, ..
+            } => {
+                let mut output = vec![0; length as usize];
+                memory.read_8(address, &mut output)?;
+                output
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(output))
+    }
+}
+
+/// Read the field with the given `index` of the parameter block at `address`.
+fn read_field(
+    memory: &mut impl MemoryInterface,
+    address: u64,
+    index: u64,
+    is_64_bit: bool,
+) -> Result<u64, Error> {
+    if is_64_bit {
+        memory.read_word_64(address + index * 8)
+    } else {
+        memory.read_word_32(address + index * 4).map(u64::from)
+    }
+}
+
+/// Decode an exit request from its `reason` and `subcode`.
+///
+/// For a normal exit, the subcode is the exit status of the application.
+fn exit_command(reason: u64, subcode: Option<u64>) -> SemihostingCommand {
+    match (reason, subcode) {
+        (ADP_STOPPED_APPLICATION_EXIT, None | Some(0)) => SemihostingCommand::ExitSuccess,
+        (ADP_STOPPED_APPLICATION_EXIT, exit_status) => SemihostingCommand::ExitError {
+            reason,
+            exit_status,
+        },
+        (reason, _) => SemihostingCommand::ExitError {
+            reason,
+            exit_status: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{exit_command, SemihostingCommand};
+
+    #[test]
+    fn decode_exit() {
+        assert_eq!(exit_command(0x20026, None), SemihostingCommand::ExitSuccess);
+        assert_eq!(
+            exit_command(0x20026, Some(0)),
+            SemihostingCommand::ExitSuccess
+        );
+        assert_eq!(
+            exit_command(0x20026, Some(3)),
+            SemihostingCommand::ExitError {
+                reason: 0x20026,
+                exit_status: Some(3)
+            }
+        );
+        // ADP_Stopped_RunTimeErrorUnknown
+        assert_eq!(
+            exit_command(0x20023, Some(0)),
+            SemihostingCommand::ExitError {
+                reason: 0x20023,
+                exit_status: None
+            }
+        );
+    }
+}
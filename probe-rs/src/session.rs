@@ -1,23 +1,24 @@
 use crate::architecture::arm::sequences::DefaultArmSequence;
-use crate::architecture::arm::{ApAddress, DpAddress};
-use crate::config::{ChipInfo, MemoryRegion, RegistryError, Target, TargetSelector};
+use crate::architecture::arm::{ApAddress, DebugAuthenticator, DpAddress, SwdSequence};
+use crate::config::{ChipInfo, MemoryRegion, RegistryError, SwjSequence, Target, TargetSelector};
 use crate::core::{Architecture, CoreState, SpecificCoreState};
 use crate::{
     architecture::{
         arm::{
             ap::{GenericAp, MemoryAp},
             communication_interface::{ArmProbeInterface, MemoryApInformation},
-            component::TraceSink,
+            component::{MtbPacket, TraceSink},
             memory::{Component, CoresightComponent},
-            ApInformation, SwoReader,
+            ApInformation, ItmDecoder, SwoReader, TraceEvent, TraceEvents,
         },
         riscv::communication_interface::RiscvCommunicationInterface,
+        xtensa::communication_interface::XtensaCommunicationInterface,
     },
     config::DebugSequence,
 };
-use crate::{AttachMethod, Core, CoreType, Error, Probe};
+use crate::{AttachMethod, Core, CoreType, DebugProbeSelector, Error, Probe, WireProtocol};
 use anyhow::anyhow;
-use std::{fmt, time::Duration};
+use std::{fmt, ops::Range, sync::Arc, time::Duration};
 
 /// The `Session` struct represents an active debug session.
 ///
@@ -43,11 +44,31 @@ pub struct Session {
     interface: ArchitectureInterface,
     cores: Vec<(SpecificCoreState, CoreState)>,
     configured_trace_sink: Option<TraceSink>,
+    trace_decoder: ItmDecoder,
+    /// Set if the cores are connected through their CTIs, see [`Session::enable_cross_trigger`].
+    cross_trigger: bool,
+    /// Information required to open the session again after the probe was disconnected.
+    reconnect_info: Option<ReconnectInfo>,
+    /// Set if the probe was disconnected, in which case the session can't be shut down cleanly.
+    disconnected: bool,
+    /// What the session is allowed to do with the target.
+    permissions: Permissions,
+}
+
+/// The settings used to open a [`Session`], which are required to restore it.
+#[derive(Debug, Clone)]
+struct ReconnectInfo {
+    selector: DebugProbeSelector,
+    protocol: Option<WireProtocol>,
+    speed_khz: u32,
+    attach_method: AttachMethod,
+    permissions: Permissions,
 }
 
 enum ArchitectureInterface {
     Arm(Box<dyn ArmProbeInterface + 'static>),
     Riscv(Box<RiscvCommunicationInterface>),
+    Xtensa(Box<XtensaCommunicationInterface>),
 }
 
 impl fmt::Debug for ArchitectureInterface {
@@ -58,6 +79,10 @@ impl fmt::Debug for ArchitectureInterface {
                 .debug_tuple("ArchitectureInterface::Riscv")
                 .field(iface)
                 .finish(),
+            ArchitectureInterface::Xtensa(iface) => f
+                .debug_tuple("ArchitectureInterface::Xtensa")
+                .field(iface)
+                .finish(),
         }
     }
 }
@@ -67,6 +92,7 @@ impl From<ArchitectureInterface> for Architecture {
         match value {
             ArchitectureInterface::Arm(_) => Architecture::Arm,
             ArchitectureInterface::Riscv(_) => Architecture::Riscv,
+            ArchitectureInterface::Xtensa(_) => Architecture::Xtensa,
         }
     }
 }
@@ -86,7 +112,8 @@ impl ArchitectureInterface {
                     .ok_or_else(|| Error::CoreNotFound(core_state.id()))?;
                 let arm_core_access_options = match &config.core_access_options {
                     probe_rs_target::CoreAccessOptions::Arm(opt) => opt,
-                    probe_rs_target::CoreAccessOptions::Riscv(_) => {
+                    probe_rs_target::CoreAccessOptions::Riscv(_)
+                    | probe_rs_target::CoreAccessOptions::Xtensa(_) => {
                         unreachable!("This should never happen. Please file a bug if it does.")
                     }
                 };
@@ -104,7 +131,36 @@ impl ArchitectureInterface {
 
                 core.attach_arm(core_state, memory, target)
             }
-            ArchitectureInterface::Riscv(state) => core.attach_riscv(core_state, state),
+            ArchitectureInterface::Riscv(state) => {
+                let config = target
+                    .cores
+                    .get(core_state.id())
+                    .ok_or_else(|| Error::CoreNotFound(core_state.id()))?;
+                let hart_id = match &config.core_access_options {
+                    probe_rs_target::CoreAccessOptions::Riscv(opt) => opt.hart_id,
+                    probe_rs_target::CoreAccessOptions::Arm(_)
+                    | probe_rs_target::CoreAccessOptions::Xtensa(_) => {
+                        unreachable!("This should never happen. Please file a bug if it does.")
+                    }
+                };
+
+                core.attach_riscv(core_state, state, hart_id)
+            }
+            ArchitectureInterface::Xtensa(state) => {
+                let config = target
+                    .cores
+                    .get(core_state.id())
+                    .ok_or_else(|| Error::CoreNotFound(core_state.id()))?;
+                let debug_level = match &config.core_access_options {
+                    probe_rs_target::CoreAccessOptions::Xtensa(opt) => opt.debug_level,
+                    probe_rs_target::CoreAccessOptions::Arm(_)
+                    | probe_rs_target::CoreAccessOptions::Riscv(_) => {
+                        unreachable!("This should never happen. Please file a bug if it does.")
+                    }
+                };
+
+                core.attach_xtensa(core_state, state, debug_level)
+            }
         }
     }
 }
@@ -117,6 +173,14 @@ impl Session {
         attach_method: AttachMethod,
         permissions: Permissions,
     ) -> Result<Self, Error> {
+        let reconnect_info = probe.selector().map(|selector| ReconnectInfo {
+            selector: selector.clone(),
+            protocol: probe.protocol(),
+            speed_khz: probe.speed_khz(),
+            attach_method,
+            permissions: permissions.clone(),
+        });
+
         let (mut probe, target) = get_target_from_selector(target, attach_method, probe)?;
 
         let cores = target
@@ -131,12 +195,13 @@ impl Session {
             })
             .collect();
 
-        let mut session = match target.architecture() {
+        let session = match target.architecture() {
             Architecture::Arm => {
                 let config = target.cores[0].clone();
                 let arm_core_access_options = match config.core_access_options {
                     probe_rs_target::CoreAccessOptions::Arm(opt) => opt,
-                    probe_rs_target::CoreAccessOptions::Riscv(_) => {
+                    probe_rs_target::CoreAccessOptions::Riscv(_)
+                    | probe_rs_target::CoreAccessOptions::Xtensa(_) => {
                         unreachable!("This should never happen. Please file a bug if it does.")
                     }
                 };
@@ -151,7 +216,7 @@ impl Session {
 
                 let sequence_handle = match &target.debug_sequence {
                     DebugSequence::Arm(sequence) => sequence.clone(),
-                    DebugSequence::Riscv(_) => {
+                    DebugSequence::Riscv(_) | DebugSequence::Xtensa(_) => {
                         panic!("Mismatch between architecture and sequence type!")
                     }
                 };
@@ -171,7 +236,9 @@ impl Session {
 
                 probe.inner_attach()?;
 
-                let interface = probe.try_into_arm_interface().map_err(|(_, err)| err)?;
+                let mut interface = probe.try_into_arm_interface().map_err(|(_, err)| err)?;
+
+                run_swj_sequences(interface.as_mut(), &target.swj_sequences.before_attach)?;
 
                 let mut interface = interface.initialize(sequence_handle.clone())?;
 
@@ -191,8 +258,12 @@ impl Session {
                         probe.detach()?;
                         probe.attach_to_unspecified()?;
 
-                        let arm_interface =
+                        let mut arm_interface =
                             probe.try_into_arm_interface().map_err(|(_, err)| err)?;
+                        run_swj_sequences(
+                            arm_interface.as_mut(),
+                            &target.swj_sequences.before_attach,
+                        )?;
                         interface = arm_interface.initialize(sequence_handle.clone())?;
 
                         log::debug!("The probe was re-attached");
@@ -200,13 +271,16 @@ impl Session {
                     Err(e) => return Err(e),
                 }
 
+                run_swj_sequences(interface.as_mut(), &target.swj_sequences.after_attach)?;
+
                 {
                     // For each core, setup debugging
                     for i in 0..target.cores.len() {
                         let config = target.cores[i].clone();
                         let arm_core_access_options = match config.core_access_options {
                             probe_rs_target::CoreAccessOptions::Arm(opt) => opt,
-                            probe_rs_target::CoreAccessOptions::Riscv(_) => {
+                            probe_rs_target::CoreAccessOptions::Riscv(_)
+                            | probe_rs_target::CoreAccessOptions::Xtensa(_) => {
                                 unreachable!(
                                     "This should never happen. Please file a bug if it does."
                                 )
@@ -233,7 +307,7 @@ impl Session {
                     }
                 }
 
-                if attach_method == AttachMethod::UnderReset {
+                let mut session = if attach_method == AttachMethod::UnderReset {
                     {
                         let mut memory_interface = interface.memory_interface(default_memory_ap)?;
                         // we need to halt the chip here
@@ -250,6 +324,11 @@ impl Session {
                         interface: ArchitectureInterface::Arm(interface),
                         cores,
                         configured_trace_sink: None,
+                        trace_decoder: ItmDecoder::new(),
+                        cross_trigger: false,
+                        reconnect_info,
+                        disconnected: false,
+                        permissions: permissions.clone(),
                     };
 
                     {
@@ -281,15 +360,24 @@ impl Session {
                         interface: ArchitectureInterface::Arm(interface),
                         cores,
                         configured_trace_sink: None,
+                        trace_decoder: ItmDecoder::new(),
+                        cross_trigger: false,
+                        reconnect_info,
+                        disconnected: false,
+                        permissions: permissions.clone(),
                     }
-                }
+                };
+
+                session.clear_all_hw_breakpoints()?;
+
+                session
             }
             Architecture::Riscv => {
                 // TODO: Handle attach under reset
 
                 let sequence_handle = match &target.debug_sequence {
                     DebugSequence::Riscv(sequence) => sequence.clone(),
-                    DebugSequence::Arm(_) => {
+                    DebugSequence::Arm(_) | DebugSequence::Xtensa(_) => {
                         panic!("Mismatch between architecture and sequence type!")
                     }
                 };
@@ -305,22 +393,52 @@ impl Session {
                     interface: ArchitectureInterface::Riscv(Box::new(interface)),
                     cores,
                     configured_trace_sink: None,
+                    trace_decoder: ItmDecoder::new(),
+                    cross_trigger: false,
+                    reconnect_info,
+                    disconnected: false,
+                    permissions: permissions.clone(),
                 };
 
-                {
-                    // Todo: Add multicore support. How to deal with any cores that are not active and won't respond?
-                    let mut core = session.core(0)?;
-
-                    core.halt(Duration::from_millis(100))?;
-                }
+                session.halt_cores_on_attach()?;
 
                 sequence_handle.on_connect(session.get_riscv_interface()?)?;
 
                 session
             }
-        };
+            Architecture::Xtensa => {
+                let sequence_handle = match &target.debug_sequence {
+                    DebugSequence::Xtensa(sequence) => sequence.clone(),
+                    DebugSequence::Arm(_) | DebugSequence::Riscv(_) => {
+                        panic!("Mismatch between architecture and sequence type!")
+                    }
+                };
+
+                probe.inner_attach()?;
+
+                let interface = probe
+                    .try_into_xtensa_interface()
+                    .map_err(|(_probe, err)| err)?;
+
+                let mut session = Session {
+                    target,
+                    interface: ArchitectureInterface::Xtensa(Box::new(interface)),
+                    cores,
+                    configured_trace_sink: None,
+                    trace_decoder: ItmDecoder::new(),
+                    cross_trigger: false,
+                    reconnect_info,
+                    disconnected: false,
+                    permissions: permissions.clone(),
+                };
+
+                session.halt_cores_on_attach()?;
 
-        session.clear_all_hw_breakpoints()?;
+                sequence_handle.on_connect(session.get_xtensa_interface()?)?;
+
+                session
+            }
+        };
 
         Ok(session)
     }
@@ -343,6 +461,66 @@ impl Session {
         probe.attach(target, permissions)
     }
 
+    /// Open the session again after the probe was disconnected.
+    ///
+    /// Probes can disappear from the USB bus, for example when they are powered by the target and the
+    /// target is power cycled. Use [`crate::is_disconnect_error`] to check if an error was caused
+    /// by this. This function then waits up to `timeout` for the probe to show up again, and attaches
+    /// to the target using the same settings as the original session.
+    ///
+    /// The debug port and the cores are initialized again, so the session is in the same state as after
+    /// the initial attach. Breakpoints and tracing have to be configured again.
+    ///
+    /// This only works if the probe was opened using [`Probe::open`], because the selector used to open
+    /// the probe is required to find it again.
+    pub fn reconnect(&mut self, timeout: Duration) -> Result<(), Error> {
+        let info = self.reconnect_info.clone().ok_or_else(|| {
+            anyhow!("The probe was not opened using a selector, so it can't be opened again.")
+        })?;
+
+        // The probe is gone, so it's not possible to shut down the current session cleanly.
+        self.disconnected = true;
+
+        log::info!("Waiting for probe {} to reconnect", info.selector);
+
+        let probe = Probe::reopen(&info.selector, timeout)?;
+
+        self.attach_reopened_probe(probe, info)
+    }
+
+    /// Replace the session with a new one, using the probe which was opened again.
+    fn attach_reopened_probe(
+        &mut self,
+        mut probe: Probe,
+        info: ReconnectInfo,
+    ) -> Result<(), Error> {
+        if let Some(protocol) = info.protocol {
+            probe.select_protocol(protocol)?;
+        }
+
+        probe.set_speed(info.speed_khz)?;
+
+        let mut session = Session::new(
+            probe,
+            TargetSelector::Specified(self.target.clone()),
+            info.attach_method,
+            info.permissions.clone(),
+        )?;
+
+        // Keep the settings of the initial connection, so the session can be reconnected again.
+        session.reconnect_info = Some(info);
+
+        // The old session is dropped here, which also closes the old probe handle.
+        *self = session;
+
+        Ok(())
+    }
+
+    /// What the session is allowed to do with the target.
+    pub(crate) fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
     /// Lists the available cores with their number and their type.
     pub fn list_cores(&self) -> Vec<(usize, CoreType)> {
         self.cores
@@ -413,6 +591,45 @@ impl Session {
         Ok(SwoReader::new(interface))
     }
 
+    /// Returns an iterator over the decoded ITM and DWT packets of the configured trace sink.
+    ///
+    /// The iterator reads new trace data whenever all previously read data has been
+    /// decoded, and ends when no more data is available. Packets which are split over
+    /// multiple reads are decoded once they are complete, so the iterator can be
+    /// created again later to continue decoding.
+    pub fn trace_events(&mut self) -> TraceEvents<'_> {
+        TraceEvents::new(self)
+    }
+
+    /// Read the available trace data, and call `callback` for every decoded ITM or DWT packet.
+    ///
+    /// Returns the number of decoded packets.
+    pub fn poll_trace_events(
+        &mut self,
+        mut callback: impl FnMut(TraceEvent),
+    ) -> Result<usize, Error> {
+        let data = self.read_trace_data()?;
+        self.trace_decoder.push(&data);
+
+        let mut count = 0;
+        while let Some(event) = self.trace_decoder.pull() {
+            callback(event);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Decode the next ITM or DWT packet from the already read trace data.
+    pub(crate) fn next_trace_event(&mut self) -> Option<TraceEvent> {
+        self.trace_decoder.pull()
+    }
+
+    /// Add raw trace data to be decoded by [`Session::trace_events`].
+    pub(crate) fn push_trace_data(&mut self, data: &[u8]) {
+        self.trace_decoder.push(data);
+    }
+
     /// Get the Arm probe interface.
     pub fn get_arm_interface(&mut self) -> Result<&mut Box<dyn ArmProbeInterface>, Error> {
         let interface = match &mut self.interface {
@@ -432,10 +649,23 @@ impl Session {
         Ok(interface)
     }
 
+    fn get_xtensa_interface(&mut self) -> Result<&mut Box<XtensaCommunicationInterface>, Error> {
+        let interface = match &mut self.interface {
+            ArchitectureInterface::Xtensa(interface) => interface,
+            _ => return Err(Error::ArchitectureRequired(&["Xtensa"])),
+        };
+
+        Ok(interface)
+    }
+
     /// Reads all the available ARM CoresightComponents of the currently attached target.
     ///
     /// This will recursively parse the Romtable of the attached target
     /// and create a list of all the contained components.
+    ///
+    /// Every returned component is the ROM table of an access port. Use
+    /// [`CoresightComponent::iter`] to visit all components in the ROM table, and
+    /// [`Component::id`] to get their base address, class and peripheral ID.
     pub fn get_arm_components(&mut self) -> Result<Vec<CoresightComponent>, Error> {
         let interface = self.get_arm_interface()?;
 
@@ -465,7 +695,7 @@ impl Session {
                         .map_err(Error::architecture_specific)?;
                     Ok(CoresightComponent::new(component, ap))
                 }
-                ApInformation::Other { address } => {
+                ApInformation::Other { address, .. } => {
                     // Return an error, only possible to get Component from MemoryAP
                     Err(Error::Other(anyhow!(
                         "AP {:#x?} is not a MemoryAP, unable to get ARM component.",
@@ -506,7 +736,7 @@ impl Session {
 
         let sequence_handle = match &self.target.debug_sequence {
             DebugSequence::Arm(sequence) => sequence.clone(),
-            DebugSequence::Riscv(_) => {
+            DebugSequence::Riscv(_) | DebugSequence::Xtensa(_) => {
                 panic!("Mismatch between architecture and sequence type!")
             }
         };
@@ -530,10 +760,67 @@ impl Session {
         crate::architecture::arm::component::setup_tracing(interface, &components, &destination)?;
 
         self.configured_trace_sink.replace(destination);
+        self.trace_decoder = ItmDecoder::new();
 
         Ok(())
     }
 
+    /// Configure the ETM of the target to trace all executed instructions into the
+    /// on-chip trace buffer (ETB or TMC).
+    ///
+    /// This doesn't require a probe with trace pins. The captured trace can be read
+    /// using [`Session::read_etm_trace`].
+    pub fn setup_etm_trace(&mut self, core_index: usize) -> Result<(), Error> {
+        {
+            let mut core = self.core(core_index)?;
+            crate::architecture::arm::component::enable_tracing(&mut core)?;
+        }
+
+        let sequence_handle = match &self.target.debug_sequence {
+            DebugSequence::Arm(sequence) => sequence.clone(),
+            DebugSequence::Riscv(_) | DebugSequence::Xtensa(_) => {
+                panic!("Mismatch between architecture and sequence type!")
+            }
+        };
+
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+
+        sequence_handle.trace_start(interface, &components, &TraceSink::TraceMemory)?;
+        crate::architecture::arm::component::setup_etm_trace(interface, &components)
+    }
+
+    /// Stop the ETM trace, and read the captured trace from the on-chip trace buffer.
+    ///
+    /// The returned data is the raw ETM trace stream, with the formatting of the
+    /// trace buffer removed, and can be decoded by an ETM trace decoder.
+    pub fn read_etm_trace(&mut self) -> Result<Vec<u8>, Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::read_etm_trace(interface, &components)
+    }
+
+    /// Clear the MTB (Micro Trace Buffer) of a Cortex-M0+ and start recording branches
+    /// into a buffer of `size` bytes at the start of the MTB SRAM.
+    ///
+    /// The recorded branches can be read using [`Session::read_mtb`].
+    pub fn setup_mtb(&mut self, size: u32) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::setup_mtb(interface, &components, size)
+    }
+
+    /// Read the branches recorded by the MTB, oldest first.
+    ///
+    /// Use [`executed_ranges`] to reconstruct the executed code from the branches.
+    ///
+    /// [`executed_ranges`]: crate::architecture::arm::component::executed_ranges
+    pub fn read_mtb(&mut self) -> Result<Vec<MtbPacket>, Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::read_mtb(interface, &components)
+    }
+
     /// Configure the target to stop emitting SWV trace data.
     pub fn disable_swv(&mut self, core_index: usize) -> Result<(), Error> {
         crate::architecture::arm::component::disable_swv(&mut self.core(core_index)?)
@@ -558,6 +845,53 @@ impl Session {
         crate::architecture::arm::component::remove_swv_data_trace(interface, &components, unit)
     }
 
+    /// Enable the ITM stimulus ports given by the bits of `ports`, and disable all others.
+    ///
+    /// [`Session::setup_tracing`] enables all stimulus ports.
+    pub fn set_itm_stimulus_ports(&mut self, ports: u32) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::set_itm_stimulus_ports(interface, &components, ports)
+    }
+
+    /// Enable or disable local timestamps in the ITM trace.
+    pub fn set_itm_local_timestamps(&mut self, enabled: bool) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::set_itm_local_timestamps(
+            interface,
+            &components,
+            enabled,
+        )
+    }
+
+    /// Enable or disable tracing of exception entry and exit by the DWT.
+    pub fn set_exception_trace(&mut self, enabled: bool) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::set_exception_trace(interface, &components, enabled)
+    }
+
+    /// Enable periodic sampling of the program counter, which is sent as
+    /// [`TraceEvent::PcSample`] packets.
+    ///
+    /// A sample is taken roughly every `interval` core clock cycles, see
+    /// [`Dwt::enable_pc_sampling`] for the supported intervals.
+    ///
+    /// [`Dwt::enable_pc_sampling`]: crate::architecture::arm::component::Dwt::enable_pc_sampling
+    pub fn enable_pc_sampling(&mut self, interval: u32) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::set_pc_sampling(interface, &components, Some(interval))
+    }
+
+    /// Disable periodic sampling of the program counter.
+    pub fn disable_pc_sampling(&mut self) -> Result<(), Error> {
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+        crate::architecture::arm::component::set_pc_sampling(interface, &components, None)
+    }
+
     /// Returns the memory map of the target.
     #[deprecated = "Use the Session::target function instead"]
     pub fn memory_map(&self) -> &[MemoryRegion] {
@@ -569,7 +903,250 @@ impl Session {
         match self.interface {
             ArchitectureInterface::Arm(_) => Architecture::Arm,
             ArchitectureInterface::Riscv(_) => Architecture::Riscv,
+            ArchitectureInterface::Xtensa(_) => Architecture::Xtensa,
+        }
+    }
+
+    /// The access port of each core, and the base address of its CTI if it is given in the target description.
+    fn core_cti_locations(&self) -> Result<Vec<(ApAddress, Option<u64>)>, Error> {
+        self.target
+            .cores
+            .iter()
+            .map(|core| match &core.core_access_options {
+                probe_rs_target::CoreAccessOptions::Arm(options) => Ok((
+                    ApAddress {
+                        dp: match options.psel {
+                            0 => DpAddress::Default,
+                            x => DpAddress::Multidrop(x),
+                        },
+                        ap: options.ap,
+                    },
+                    options.cti_base,
+                )),
+                probe_rs_target::CoreAccessOptions::Riscv(_)
+                | probe_rs_target::CoreAccessOptions::Xtensa(_) => {
+                    Err(Error::ArchitectureRequired(&["ARMv7", "ARMv8"]))
+                }
+            })
+            .collect()
+    }
+
+    /// Connect the cores through their CTIs (Cross Trigger Interfaces), so that
+    /// halting one core, e.g. on a breakpoint, halts all other cores within a few cycles.
+    ///
+    /// This also makes [`Session::halt_all`] and [`Session::run_all`] halt and restart
+    /// all cores at the same time. While cross triggering is enabled, the cores should be
+    /// resumed using [`Session::run_all`], because a pending halt request is only
+    /// acknowledged there.
+    ///
+    /// The CTI of each core is taken from the `cti_base` of the core in the target
+    /// description, or found in the ROM table of the access port of the core.
+    pub fn enable_cross_trigger(&mut self) -> Result<(), Error> {
+        let cores = self.core_cti_locations()?;
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+
+        crate::architecture::arm::component::enable_cross_trigger(interface, &components, &cores)?;
+        self.cross_trigger = true;
+
+        Ok(())
+    }
+
+    /// Disconnect the CTIs of the cores again, so that they are halted and resumed independently.
+    pub fn disable_cross_trigger(&mut self) -> Result<(), Error> {
+        let cores = self.core_cti_locations()?;
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+
+        crate::architecture::arm::component::disable_cross_trigger(interface, &components, &cores)?;
+        self.cross_trigger = false;
+
+        Ok(())
+    }
+
+    /// Halt all cores of the target.
+    ///
+    /// If cross triggering is enabled using [`Session::enable_cross_trigger`], all cores are
+    /// halted at the same time. On RISC-V targets, all harts are halted at the same time if
+    /// the debug module supports the hart array mask. Otherwise they are halted one after another.
+    pub fn halt_all(&mut self, timeout: Duration) -> Result<(), Error> {
+        if self.architecture() == Architecture::Riscv && self.cores.len() > 1 {
+            let mut harts = vec![];
+            for (n, hart) in self.riscv_harts().into_iter().enumerate() {
+                if !self.core(n)?.core_halted()? {
+                    harts.push(hart);
+                }
+            }
+
+            self.get_riscv_interface()?.halt_harts(&harts, timeout)?;
+
+            // Update the cached state of the cores.
+            for n in 0..self.cores.len() {
+                self.core(n)?.status()?;
+            }
+        } else if self.cross_trigger {
+            self.pulse_cross_trigger(false)?;
+
+            for n in 0..self.cores.len() {
+                self.core(n)?.wait_for_core_halted(timeout)?;
+            }
+
+            // Release the halt request, so that the cores can be resumed again.
+            self.acknowledge_cross_trigger()?;
+        } else {
+            for n in 0..self.cores.len() {
+                let mut core = self.core(n)?;
+
+                if !core.core_halted()? {
+                    core.halt(timeout)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resume all halted cores of the target.
+    ///
+    /// If cross triggering is enabled using [`Session::enable_cross_trigger`], all cores are
+    /// restarted at the same time. On RISC-V targets, all harts are resumed at the same time if
+    /// the debug module supports the hart array mask. Otherwise they are resumed one after another.
+    pub fn run_all(&mut self) -> Result<(), Error> {
+        if self.architecture() == Architecture::Riscv && self.cores.len() > 1 {
+            let mut harts = vec![];
+            for (n, hart) in self.riscv_harts().into_iter().enumerate() {
+                let mut core = self.core(n)?;
+
+                if core.core_halted()? {
+                    // Step over the current instruction first, like `Core::run`,
+                    // so that the hart doesn't get stuck on a breakpoint.
+                    core.step()?;
+                    harts.push(hart);
+                }
+            }
+
+            self.get_riscv_interface()?.resume_harts(&harts)?;
+
+            // Update the cached state of the cores.
+            for n in 0..self.cores.len() {
+                self.core(n)?.status()?;
+            }
+        } else if self.cross_trigger {
+            for n in 0..self.cores.len() {
+                let mut core = self.core(n)?;
+
+                if core.core_halted()? {
+                    crate::architecture::arm::component::prepare_cross_trigger_restart(&mut core)?;
+                }
+            }
+
+            self.pulse_cross_trigger(true)?;
+            self.acknowledge_cross_trigger()?;
+
+            // Update the cached state of the cores.
+            for n in 0..self.cores.len() {
+                self.core(n)?.status()?;
+            }
+        } else {
+            for n in 0..self.cores.len() {
+                let mut core = self.core(n)?;
+
+                if core.core_halted()? {
+                    core.run()?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The hart of each RISC-V core, by core index.
+    fn riscv_harts(&self) -> Vec<u32> {
+        self.target
+            .cores
+            .iter()
+            .map(|core| match &core.core_access_options {
+                probe_rs_target::CoreAccessOptions::Riscv(options) => options.hart_id,
+                probe_rs_target::CoreAccessOptions::Arm(_)
+                | probe_rs_target::CoreAccessOptions::Xtensa(_) => {
+                    unreachable!("This should never happen. Please file a bug if it does.")
+                }
+            })
+            .collect()
+    }
+
+    fn pulse_cross_trigger(&mut self, restart: bool) -> Result<(), Error> {
+        let cores = self.core_cti_locations()?;
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+
+        crate::architecture::arm::component::pulse_cross_trigger(
+            interface,
+            &components,
+            &cores,
+            restart,
+        )
+    }
+
+    fn acknowledge_cross_trigger(&mut self) -> Result<(), Error> {
+        let cores = self.core_cti_locations()?;
+        let components = self.get_arm_components()?;
+        let interface = self.get_arm_interface()?;
+
+        crate::architecture::arm::component::acknowledge_cross_trigger(
+            interface,
+            &components,
+            &cores,
+        )
+    }
+
+    /// Compute the CRC32 of the memory in `range` on the target.
+    ///
+    /// A small stub is loaded into the RAM of the first core, which computes the CRC32 at the speed
+    /// of the core. This is much faster than reading back the memory, especially with slow probes.
+    /// The CRC32 is the one used by zlib and Ethernet, as computed by the `crc32fast` crate.
+    ///
+    /// The RAM used by the stub and the registers of the core are restored afterwards, and the core
+    /// is resumed if it was running. This is only supported for Cortex-M and RISC-V cores.
+    pub fn crc32(&mut self, range: Range<u64>) -> Result<u32, Error> {
+        let core = &self.target.cores[0];
+        let size = crate::crc32::stub_size(core.core_type)?;
+        let ram = crate::crc32::stub_address(&self.target.memory_map, &core.name, &range, size)
+            .ok_or_else(|| {
+                anyhow!(
+                    "There is no RAM for the CRC32 stub outside of {:#x?}",
+                    range
+                )
+            })?;
+
+        crate::crc32::crc32(&mut self.core(0)?, ram, range)
+    }
+
+    /// Halt the cores of a RISC-V or Xtensa target after attaching, and clear their hardware
+    /// breakpoints.
+    ///
+    /// The state of these cores can only be accessed while they are halted. The first core has to
+    /// halt. The other cores are halted as well, but a core which is unavailable or doesn't halt in
+    /// time is skipped with a warning, instead of failing to attach.
+    fn halt_cores_on_attach(&mut self) -> Result<(), Error> {
+        {
+            let mut core = self.core(0)?;
+            core.halt(Duration::from_millis(100))?;
+            core.clear_all_hw_breakpoints()?;
         }
+
+        for n in 1..self.cores.len() {
+            let result = self.core(n).and_then(|mut core| {
+                core.halt(Duration::from_millis(100))?;
+                core.clear_all_hw_breakpoints()
+            });
+
+            if let Err(err) = result {
+                log::warn!("Skipping core {}, which couldn't be halted: {}", n, err);
+            }
+        }
+
+        Ok(())
     }
 
     /// Clears all hardware breakpoints on all cores
@@ -587,6 +1164,11 @@ static_assertions::assert_impl_all!(Session: Send);
 // TODO tiwalun: Enable again, after rework of Session::new is done.
 impl Drop for Session {
     fn drop(&mut self) {
+        if self.disconnected {
+            log::debug!("Probe was disconnected, skipping session shutdown");
+            return;
+        }
+
         if let Err(err) = { 0..self.cores.len() }.try_for_each(|i| {
             self.core(i)
                 .and_then(|mut core| core.clear_all_hw_breakpoints())
@@ -635,6 +1217,23 @@ impl Drop for Session {
     }
 }
 
+/// Send the SWJ sequences given in the target description.
+fn run_swj_sequences<S: SwdSequence + ?Sized>(
+    interface: &mut S,
+    sequences: &[SwjSequence],
+) -> Result<(), Error> {
+    for sequence in sequences {
+        log::debug!(
+            "Sending SWJ sequence {:#x} ({} bits)",
+            sequence.bits,
+            sequence.bit_len
+        );
+        interface.swj_sequence(sequence.bit_len, sequence.bits)?;
+    }
+
+    Ok(())
+}
+
 /// Determine the [Target] from a [TargetSelector].
 ///
 /// If the selector is [TargetSelector::Unspecified], the target will be looked up in the registry.
@@ -732,10 +1331,24 @@ fn get_target_from_selector(
 /// let permissions = Permissions::new().allow_erase_all();
 /// ```
 #[non_exhaustive]
-#[derive(Debug, Clone, Default)]
+#[derive(Clone, Default)]
 pub struct Permissions {
     /// When set to true, all memory of the chip may be erased or reset to factory default
     erase_all: bool,
+    /// Used to answer the debug authentication challenge of locked devices
+    debug_authenticator: Option<Arc<dyn DebugAuthenticator>>,
+    /// When set to true, one-time programmable memory may be programmed
+    otp_programming: bool,
+}
+
+impl fmt::Debug for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Permissions")
+            .field("erase_all", &self.erase_all)
+            .field("debug_authentication", &self.debug_authenticator.is_some())
+            .field("otp_programming", &self.otp_programming)
+            .finish()
+    }
 }
 
 impl Permissions {
@@ -757,6 +1370,37 @@ impl Permissions {
         }
     }
 
+    /// Allow the session to unlock the debug access of locked devices, using the debug authentication
+    /// of the device. The `authenticator` computes the response to the challenge sent by the device.
+    ///
+    /// Currently, this is supported for the LPC55Sxx. See [`DebugAuthenticator`] for details.
+    #[must_use]
+    pub fn allow_debug_authentication(self, authenticator: Arc<dyn DebugAuthenticator>) -> Self {
+        Self {
+            debug_authenticator: Some(authenticator),
+            ..self
+        }
+    }
+
+    /// Allow the session to program one-time programmable memory, like the OTP area of the STM32,
+    /// the customer registers of the nRF52 UICR or the CMPA and CFPA of the LPC55.
+    ///
+    /// # Warning
+    /// These writes can't be reverted. Check the [`OtpReport`](crate::flashing::OtpReport) of the image before flashing it.
+    #[must_use]
+    pub fn allow_otp_programming(self) -> Self {
+        Self {
+            otp_programming: true,
+            ..self
+        }
+    }
+
+    pub(crate) fn debug_authenticator(&self) -> Result<&dyn DebugAuthenticator, crate::Error> {
+        self.debug_authenticator
+            .as_deref()
+            .ok_or_else(|| crate::Error::MissingPermissions("debug_authentication".into()))
+    }
+
     pub(crate) fn erase_all(&self) -> Result<(), crate::Error> {
         if self.erase_all {
             Ok(())
@@ -764,4 +1408,144 @@ impl Permissions {
             Err(crate::Error::MissingPermissions("erase_all".into()))
         }
     }
+
+    pub(crate) fn otp_programming(&self) -> Result<(), crate::Error> {
+        if self.otp_programming {
+            Ok(())
+        } else {
+            Err(crate::Error::MissingPermissions("otp_programming".into()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use probe_rs_target::{ChipFamily, SwjSequence};
+
+    use super::{ReconnectInfo, Session};
+    use crate::{
+        config::Target, AttachMethod, CoreType, FakeProbe, MemoryInterface, Permissions,
+        WireProtocol,
+    };
+
+    fn fake_session(memory: Vec<u8>) -> Session {
+        FakeProbe::with_mocked_memory(memory)
+            .into_probe()
+            .attach("nrf51822_xxAC", Permissions::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn reconnect_restores_session() {
+        let mut session = fake_session(vec![0; 0x400]);
+
+        let info = ReconnectInfo {
+            selector: "1366:1015:000123456789".try_into().unwrap(),
+            protocol: Some(WireProtocol::Swd),
+            speed_khz: 4000,
+            attach_method: AttachMethod::Normal,
+            permissions: Permissions::default(),
+        };
+        session.reconnect_info = Some(info.clone());
+
+        // The probe was disconnected and shows up again, the target kept running in the meantime.
+        session.disconnected = true;
+
+        let mut memory = vec![0; 0x400];
+        memory[0x100..0x104].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+        let probe = FakeProbe::with_mocked_memory(memory).into_probe();
+
+        session.attach_reopened_probe(probe, info).unwrap();
+
+        assert!(!session.disconnected);
+        assert_eq!(session.list_cores(), [(0, CoreType::Armv6m)]);
+
+        let reconnect_info = session.reconnect_info.as_ref().unwrap();
+        assert_eq!(
+            reconnect_info.selector.serial_number.as_deref(),
+            Some("000123456789")
+        );
+        assert_eq!(reconnect_info.speed_khz, 4000);
+
+        // The core is attached again, and accesses the target through the new probe.
+        let mut core = session.core(0).unwrap();
+        assert_eq!(core.read_word_32(0x100).unwrap(), 0x1234_5678);
+    }
+
+    #[test]
+    fn attach_sends_swj_sequences() {
+        let family: ChipFamily = serde_yaml::from_str(
+            r#"
+name: Test Series
+manufacturer: null
+variants:
+  - name: TEST01
+    cores:
+      - name: main
+        type: armv6m
+        core_access_options:
+          !Arm
+            ap: 0x0
+            psel: 0x0
+    memory_map:
+      - !Ram
+          range:
+            start: 0x20000000
+            end: 0x20004000
+          is_boot_memory: false
+          cores:
+            - main
+    flash_algorithms: []
+    swj_sequences:
+      before_attach:
+        - bit_len: 8
+          bits: 0xff
+        - bit_len: 16
+          bits: 0xe3bc
+      after_attach:
+        - bit_len: 50
+          bits: 0x3ffffffffffff
+flash_algorithms: []
+"#,
+        )
+        .unwrap();
+        let target = Target::new(&family, "TEST01").unwrap();
+
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let handler_sent = sent.clone();
+
+        let mut probe = FakeProbe::with_mocked_memory(vec![0; 0x400]);
+        probe.set_swj_sequence_handler(Box::new(move |bit_len, bits| {
+            handler_sent
+                .lock()
+                .unwrap()
+                .push(SwjSequence { bit_len, bits });
+            Ok(())
+        }));
+
+        let _session = probe
+            .into_probe()
+            .attach(target, Permissions::default())
+            .unwrap();
+
+        assert_eq!(
+            *sent.lock().unwrap(),
+            [
+                SwjSequence {
+                    bit_len: 8,
+                    bits: 0xff
+                },
+                SwjSequence {
+                    bit_len: 16,
+                    bits: 0xe3bc
+                },
+                SwjSequence {
+                    bit_len: 50,
+                    bits: 0x3ffffffffffff
+                },
+            ]
+        );
+    }
 }
@@ -1,4 +1,8 @@
-use probe_rs::{flashing::DownloadOptions, FakeProbe, Permissions, Probe};
+use probe_rs::{
+    config::get_target_by_name,
+    flashing::{download_to_probes, DownloadOptions},
+    FakeProbe, Permissions, Probe,
+};
 
 /// A chip where the flash algorithm's range is greater than the NVM range.
 #[test]
@@ -47,3 +51,65 @@ fn flash_dry_run_mimxrt1010() {
         .commit(&mut session, flash_options)
         .expect("Failed to flash in dry run mode.");
 }
+
+/// The manifest of a dry run contains the digests of the image.
+#[test]
+fn flash_dry_run_manifest() {
+    let probe = Probe::from_specific_probe(Box::new(FakeProbe::new()));
+
+    let mut session = probe
+        .attach("nrf51822_xxAC", Permissions::default())
+        .expect("Failed to attach with 'fake' probe.");
+
+    let mut flasher = session.target().flash_loader();
+
+    flasher
+        .add_data(0x0, &[0x1, 0x2, 0x3, 0x4])
+        .expect("Failed to add flash");
+
+    let mut flash_options = DownloadOptions::new();
+
+    flash_options.dry_run = true;
+
+    let manifest = flasher
+        .commit_with_manifest(&mut session, flash_options)
+        .expect("Failed to flash in dry run mode.");
+
+    assert!(manifest.dry_run);
+    assert!(manifest.is_verified());
+    assert_eq!(manifest.programmed.len(), 1);
+    assert_eq!(manifest.programmed[0].start, 0x0);
+    assert_eq!(manifest.programmed[0].end, 0x4);
+    assert_eq!(manifest.programmed[0].crc32, 0xB63C_FBCD);
+}
+
+/// Multiple probes are flashed at the same time.
+#[test]
+fn flash_dry_run_multiple_probes() {
+    let probes = (0..3)
+        .map(|_| Probe::from_specific_probe(Box::new(FakeProbe::new())))
+        .collect();
+
+    let target = get_target_by_name("nrf51822_xxAC").unwrap();
+
+    let mut loader = target.flash_loader();
+    loader
+        .add_data(0x0, &[0x1, 0x2, 0x3, 0x4])
+        .expect("Failed to add flash");
+
+    let summary = download_to_probes(
+        probes,
+        target,
+        Permissions::default(),
+        loader,
+        || {
+            let mut options = DownloadOptions::new();
+            options.dry_run = true;
+            options
+        },
+        |_, _| {},
+    );
+
+    assert_eq!(summary.devices.len(), 3);
+    assert!(summary.is_success());
+}
@@ -1,7 +1,10 @@
 use crate::Error;
 use probe_rs::{config::MemoryRegion, Core, MemoryInterface};
 use scroll::{Pread, LE};
+use std::cell::Cell;
 use std::cmp::min;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 /// Trait for channel information shared between up and down channels.
 pub trait RttChannel {
@@ -24,6 +27,7 @@ pub(crate) struct Channel {
     name: Option<String>,
     buffer_ptr: u32,
     size: u32,
+    stats: Cell<ChannelStats>,
 }
 
 // Chanels must follow this data layout when reading/writing memory in order to be compatible with
@@ -86,6 +90,7 @@ impl Channel {
             name,
             buffer_ptr,
             size: mem.pread_with(Self::O_SIZE, LE).unwrap(),
+            stats: Cell::default(),
         }))
     }
 
@@ -189,10 +194,29 @@ impl UpChannel {
         Ok(())
     }
 
-    fn read_core(&self, core: &mut Core, mut buf: &mut [u8]) -> Result<(u32, usize), Error> {
+    /// Returns the statistics of the data read from the channel since it was attached, or the
+    /// statistics were reset.
+    pub fn stats(&self) -> ChannelStats {
+        self.0.stats.get()
+    }
+
+    /// Resets the statistics of the channel.
+    pub fn reset_stats(&self) {
+        self.0.stats.take();
+    }
+
+    /// Reads the data from the buffer, and returns the new read pointer, the number of bytes read
+    /// and the number of bytes which were in the buffer.
+    fn read_core(&self, core: &mut Core, mut buf: &mut [u8]) -> Result<(u32, usize, usize), Error> {
         self.0.validate_core_id(core)?;
         let (write, mut read) = self.0.read_pointers(core, "up")?;
 
+        let available = if read > write {
+            self.0.size - read + write
+        } else {
+            write - read
+        } as usize;
+
         let mut total = 0;
 
         // Read while buffer contains data and output buffer has space (maximum of two iterations)
@@ -215,7 +239,7 @@ impl UpChannel {
             buf = &mut buf[count..];
         }
 
-        Ok((read, total))
+        Ok((read, total, available))
     }
 
     /// Reads some bytes from the channel to the specified buffer and returns how many bytes were
@@ -225,13 +249,17 @@ impl UpChannel {
     /// than would fit in `buf`.
     pub fn read(&self, core: &mut Core, buf: &mut [u8]) -> Result<usize, Error> {
         self.0.validate_core_id(core)?;
-        let (read, total) = self.read_core(core, buf)?;
+        let (read, total, available) = self.read_core(core, buf)?;
 
         if total > 0 {
             // Write read pointer back to target if something was read
             core.write_word_32((self.0.ptr + Channel::O_READ as u32).into(), read)?;
         }
 
+        let mut stats = self.0.stats.get();
+        stats.record(total, available, self.0.size as usize);
+        self.0.stats.set(stats);
+
         Ok(total)
     }
 
@@ -411,3 +439,142 @@ pub enum ChannelMode {
     /// is not read by the host.
     BlockIfFull = 2,
 }
+
+impl FromStr for ChannelMode {
+    type Err = String;
+
+    /// Parses the name of a mode, ignoring the case, e.g. `BlockIfFull` or `blockiffull`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_ascii_lowercase()[..] {
+            "noblockskip" => Ok(ChannelMode::NoBlockSkip),
+            "noblocktrim" => Ok(ChannelMode::NoBlockTrim),
+            "blockiffull" => Ok(ChannelMode::BlockIfFull),
+            _ => Err(format!(
+                "{} is not a valid channel mode, expected NoBlockSkip, NoBlockTrim or BlockIfFull",
+                s
+            )),
+        }
+    }
+}
+
+/// Statistics of the data read from an up channel.
+///
+/// The target can't tell the host if it dropped data, but it can only drop data while the buffer is
+/// full. A buffer which was full when it was read means that the host may not poll often enough, or
+/// that the buffer may be too small.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct ChannelStats {
+    /// The number of bytes read from the channel.
+    pub bytes: u64,
+    /// The number of reads from the channel.
+    pub reads: u64,
+    /// The number of reads which found the buffer full.
+    ///
+    /// This is not the amount of lost data. A full buffer only means that data written by the
+    /// target while it was full was dropped in the non-blocking modes, or that the target waited
+    /// for the host in [`ChannelMode::BlockIfFull`], if the target wrote anything at all.
+    pub full_reads: u64,
+    /// The largest number of bytes which were in the buffer when it was read.
+    pub max_fill: usize,
+    /// The longest time between two reads.
+    pub max_poll_interval: Duration,
+    /// The time of the first read.
+    pub first_read: Option<Instant>,
+    /// The time of the last read.
+    pub last_read: Option<Instant>,
+}
+
+impl ChannelStats {
+    /// Returns the average number of bytes read per second, between the first and the last read.
+    pub fn throughput(&self) -> Option<f64> {
+        let duration = self.last_read?.duration_since(self.first_read?);
+
+        if duration.is_zero() {
+            None
+        } else {
+            Some(self.bytes as f64 / duration.as_secs_f64())
+        }
+    }
+
+    fn record(&mut self, count: usize, available: usize, size: usize) {
+        let now = Instant::now();
+
+        if let Some(last_read) = self.last_read {
+            self.max_poll_interval = self.max_poll_interval.max(now - last_read);
+        }
+        self.first_read.get_or_insert(now);
+        self.last_read = Some(now);
+
+        self.bytes += count as u64;
+        self.reads += 1;
+        self.max_fill = self.max_fill.max(available);
+
+        // One byte of the buffer is never used, to tell a full buffer from an empty one.
+        if available + 1 >= size {
+            self.full_reads += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use std::time::Duration;
+
+    use probe_rs::{FakeProbe, MemoryInterface, Permissions};
+
+    use crate::{Rtt, ScanRegion};
+
+    #[test]
+    fn up_channel_stats() {
+        let mut session = FakeProbe::with_mocked_memory(vec![0; 0x400])
+            .into_probe()
+            .attach("nrf51822_xxAC", Permissions::default())
+            .unwrap();
+        let mut core = session.core(0).unwrap();
+
+        // The buffer of the up channel is at 0x200, and full with 15 of its 16 bytes.
+        let mut control_block = b"SEGGER RTT\0\0\0\0\0\0".to_vec();
+        for value in [1u32, 0, 0, 0x200, 16, 15, 0, 0] {
+            control_block.extend_from_slice(&value.to_le_bytes());
+        }
+        core.write_8(0x200, &[b'a'; 15]).unwrap();
+        core.write_8(0x100, &control_block).unwrap();
+
+        let mut rtt = Rtt::attach_region(&mut core, &[], &ScanRegion::Exact(0x100)).unwrap();
+        let up = rtt.up_channels().take(0).unwrap();
+
+        let mut buf = [0; 64];
+        assert_eq!(up.read(&mut core, &mut buf).unwrap(), 15);
+
+        let stats = up.stats();
+        assert_eq!(stats.bytes, 15);
+        assert_eq!(stats.reads, 1);
+        assert_eq!(stats.full_reads, 1);
+        assert_eq!(stats.max_fill, 15);
+        assert_eq!(stats.throughput(), None);
+
+        thread::sleep(Duration::from_millis(10));
+
+        // The target writes 4 bytes, wrapping around the end of the buffer.
+        core.write_8(0x20f, b"b").unwrap();
+        core.write_8(0x200, b"cde").unwrap();
+        core.write_word_32(0x124, 3).unwrap();
+
+        assert_eq!(up.read(&mut core, &mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"bcde");
+
+        let stats = up.stats();
+        assert_eq!(stats.bytes, 19);
+        assert_eq!(stats.reads, 2);
+        assert_eq!(stats.full_reads, 1);
+        assert_eq!(stats.max_fill, 15);
+        assert!(stats.max_poll_interval >= Duration::from_millis(10));
+
+        let throughput = stats.throughput().unwrap();
+        assert!(throughput > 0.0 && throughput <= 1900.0);
+
+        up.reset_stats();
+        assert_eq!(up.stats(), Default::default());
+    }
+}
@@ -0,0 +1,395 @@
+//! Decoding of [defmt](https://defmt.ferrous-systems.com) log frames received over RTT.
+//!
+//! The firmware sends the defmt frames over the up channel named `defmt`. The format strings and
+//! the source locations are not transferred, they are read from the ELF file of the firmware.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use probe_rs::{Probe, Permissions};
+//! use probe_rs_rtt::{defmt::{DefmtDecoder, DefmtTable}, Rtt};
+//!
+//! let probe = Probe::list_all()[0].open()?;
+//! let mut session = probe.attach("somechip", Permissions::default())?;
+//! let memory_map = session.target().memory_map.clone();
+//! let mut core = session.core(0)?;
+//!
+//! let elf = std::fs::read("firmware.elf")?;
+//! let table = Arc::new(DefmtTable::parse(&elf)?.expect("The firmware doesn't use defmt"));
+//!
+//! let mut rtt = Rtt::attach(&mut core, &memory_map)?;
+//! let channel = rtt.defmt_channel().expect("The firmware has no defmt channel");
+//! let mut decoder = DefmtDecoder::new(table);
+//!
+//! loop {
+//!     decoder.poll(&mut core, &channel)?;
+//!
+//!     for record in decoder.records() {
+//!         let record = record?;
+//!         println!("{:?} {}", record.level, record.message);
+//!     }
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use defmt_decoder::{DecodeError, Frame, Locations, StreamDecoder, Table};
+use probe_rs::Core;
+
+use crate::{Error, Rtt, UpChannel};
+
+/// The name of the up channel used by defmt.
+pub const DEFMT_CHANNEL_NAME: &str = "defmt";
+
+/// The format strings and source locations of the defmt frames, read from the ELF file of the firmware.
+pub struct DefmtTable {
+    table: Table,
+    locations: Option<Locations>,
+}
+
+impl DefmtTable {
+    /// Read the table from the contents of the ELF file `elf`.
+    ///
+    /// Returns `None` if the firmware doesn't use defmt. The source locations are only available if the
+    /// firmware was compiled with debug info for all frames.
+    pub fn parse(elf: &[u8]) -> Result<Option<Self>, Error> {
+        let table = match Table::parse(elf).map_err(|error| Error::Defmt(error.to_string()))? {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+
+        let locations = table
+            .get_locations(elf)
+            .map_err(|error| Error::Defmt(error.to_string()))?;
+
+        let locations = if !table.is_empty() && locations.is_empty() {
+            log::warn!("Insufficient DWARF info; compile your program with `debug = 2` to enable location info.");
+            None
+        } else if table
+            .indices()
+            .all(|index| locations.contains_key(&(index as u64)))
+        {
+            Some(locations)
+        } else {
+            log::warn!("Location info is incomplete; it will be omitted from the output.");
+            None
+        };
+
+        Ok(Some(Self { table, locations }))
+    }
+
+    /// Check if the source locations of the frames are known.
+    pub fn has_locations(&self) -> bool {
+        self.locations.is_some()
+    }
+
+    fn record(&self, frame: &Frame<'_>) -> DefmtRecord {
+        let location = self
+            .locations
+            .as_ref()
+            .and_then(|locations| locations.get(&frame.index()))
+            .map(|location| DefmtLocation {
+                file: location.file.clone(),
+                line: location.line,
+                module: location.module.clone(),
+            });
+
+        DefmtRecord {
+            index: frame.index(),
+            level: frame
+                .level()
+                .map(|level| format!("{:?}", level).to_lowercase()),
+            timestamp: frame
+                .display_timestamp()
+                .map(|timestamp| timestamp.to_string()),
+            message: frame.display_message().to_string(),
+            location,
+        }
+    }
+}
+
+impl fmt::Debug for DefmtTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DefmtTable")
+            .field("has_locations", &self.has_locations())
+            .finish()
+    }
+}
+
+/// The source location of a log record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefmtLocation {
+    /// The path of the source file.
+    pub file: PathBuf,
+    /// The line in the source file.
+    pub line: u64,
+    /// The path of the module.
+    pub module: String,
+}
+
+/// A decoded defmt log record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefmtRecord {
+    /// The index of the frame in the table.
+    pub index: u64,
+    /// The log level, e.g. `info`, or `None` for `println!`.
+    pub level: Option<String>,
+    /// The formatted timestamp, if the firmware defines one.
+    pub timestamp: Option<String>,
+    /// The formatted message.
+    pub message: String,
+    /// The source location, if it is known.
+    pub location: Option<DefmtLocation>,
+}
+
+impl fmt::Display for DefmtRecord {
+    /// Formats the record like `defmt-print`, without the location: `<timestamp> <LEVEL> <message>`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(timestamp) = &self.timestamp {
+            write!(f, "{} ", timestamp)?;
+        }
+
+        if let Some(level) = &self.level {
+            write!(f, "{} ", level.to_uppercase())?;
+        }
+
+        write!(f, "{}", self.message)
+    }
+}
+
+type BoxedStreamDecoder<'table> = Box<dyn StreamDecoder + 'table>;
+
+self_cell::self_cell!(
+    struct DecoderCell {
+        owner: Arc<DefmtTable>,
+
+        #[not_covariant]
+        dependent: BoxedStreamDecoder,
+    }
+);
+
+/// Decodes the stream of defmt frames of an up channel.
+///
+/// A frame can be split over several reads of the channel. The incomplete frame is kept until the rest
+/// of it is received.
+pub struct DefmtDecoder {
+    cell: DecoderCell,
+    buffer: Vec<u8>,
+}
+
+impl DefmtDecoder {
+    /// Create a decoder for the frames described by `table`.
+    pub fn new(table: Arc<DefmtTable>) -> Self {
+        Self {
+            cell: DecoderCell::new(table, |table| table.table.new_stream_decoder()),
+            buffer: vec![0; 1024],
+        }
+    }
+
+    /// Add data of the stream, which was read from the channel.
+    pub fn received(&mut self, data: &[u8]) {
+        self.cell
+            .with_dependent_mut(|_, decoder| decoder.received(data));
+    }
+
+    /// Read the available data from `channel`, and add it to the stream.
+    ///
+    /// Returns the number of bytes read. The decoded records are returned by [`DefmtDecoder::records`].
+    pub fn poll(&mut self, core: &mut Core, channel: &UpChannel) -> Result<usize, Error> {
+        if self.buffer.len() < channel.buffer_size() {
+            self.buffer.resize(channel.buffer_size(), 0);
+        }
+
+        let count = channel.read(core, &mut self.buffer)?;
+
+        let data = &self.buffer[..count];
+        self.cell
+            .with_dependent_mut(|_, decoder| decoder.received(data));
+
+        Ok(count)
+    }
+
+    /// Decode the next complete record of the stream.
+    ///
+    /// Returns `None` if no complete frame was received. Malformed frames are skipped if the encoding of
+    /// the stream allows it, otherwise an error is returned, and some data was lost.
+    pub fn decode(&mut self) -> Result<Option<DefmtRecord>, Error> {
+        self.cell.with_dependent_mut(|table, decoder| loop {
+            match decoder.decode() {
+                Ok(frame) => return Ok(Some(table.record(&frame))),
+                Err(DecodeError::UnexpectedEof) => return Ok(None),
+                Err(DecodeError::Malformed) if table.table.encoding().can_recover() => {
+                    log::warn!("Skipping a malformed defmt frame");
+                }
+                Err(DecodeError::Malformed) => {
+                    return Err(Error::Defmt(
+                        "Unrecoverable error while decoding the defmt data, some data may have been lost"
+                            .to_owned(),
+                    ))
+                }
+            }
+        })
+    }
+
+    /// An iterator over the complete records of the stream, see [`DefmtDecoder::decode`].
+    pub fn records(&mut self) -> impl Iterator<Item = Result<DefmtRecord, Error>> + '_ {
+        std::iter::from_fn(move || self.decode().transpose())
+    }
+}
+
+impl fmt::Debug for DefmtDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DefmtDecoder")
+            .field("table", self.cell.borrow_owner())
+            .finish()
+    }
+}
+
+impl Rtt {
+    /// Take the up channel used by defmt from the channels of the control block.
+    pub fn defmt_channel(&mut self) -> Option<UpChannel> {
+        let number = self
+            .up_channels()
+            .iter()
+            .find(|channel| channel.name() == Some(DEFMT_CHANNEL_NAME))?
+            .number();
+
+        self.up_channels().take(number)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use defmt_decoder::Table;
+
+    use super::{DefmtDecoder, DefmtTable};
+
+    /// A minimal 64-bit ELF file with a `.defmt` section, containing the symbols `(name, section, value)`.
+    fn elf_file(symbols: &[(&str, u16, u64)]) -> Vec<u8> {
+        let mut strtab = vec![0];
+        let mut symtab = vec![0; 24];
+
+        for &(name, section, value) in symbols {
+            symtab.extend_from_slice(&(strtab.len() as u32).to_le_bytes());
+            // STB_GLOBAL, STT_NOTYPE
+            symtab.extend_from_slice(&[0x10, 0]);
+            symtab.extend_from_slice(&section.to_le_bytes());
+            symtab.extend_from_slice(&value.to_le_bytes());
+            symtab.extend_from_slice(&0u64.to_le_bytes());
+
+            strtab.extend_from_slice(name.as_bytes());
+            strtab.push(0);
+        }
+
+        let shstrtab = b"\0.defmt\0.symtab\0.strtab\0.shstrtab\0";
+
+        // The sections follow the header, the section headers follow the sections.
+        let symtab_offset = 64;
+        let strtab_offset = symtab_offset + symtab.len();
+        let shstrtab_offset = strtab_offset + strtab.len();
+        let section_headers_offset = (shstrtab_offset + shstrtab.len() + 7) & !7;
+
+        let mut elf = vec![0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        // ET_REL, EM_ARM, EV_CURRENT, no entry and program headers
+        elf.extend_from_slice(&1u16.to_le_bytes());
+        elf.extend_from_slice(&40u16.to_le_bytes());
+        elf.extend_from_slice(&1u32.to_le_bytes());
+        elf.extend_from_slice(&0u64.to_le_bytes());
+        elf.extend_from_slice(&0u64.to_le_bytes());
+        elf.extend_from_slice(&(section_headers_offset as u64).to_le_bytes());
+        elf.extend_from_slice(&0u32.to_le_bytes());
+        // Header sizes, 5 sections, `.shstrtab` is section 4
+        for value in [64u16, 56, 0, 64, 5, 4] {
+            elf.extend_from_slice(&value.to_le_bytes());
+        }
+
+        elf.extend_from_slice(&symtab);
+        elf.extend_from_slice(&strtab);
+        elf.extend_from_slice(shstrtab);
+        elf.resize(section_headers_offset, 0);
+
+        // (name, type, offset, size, link, info, entsize)
+        let sections = [
+            (0, 0, 0, 0, 0, 0, 0),
+            (1, 1, symtab_offset, 0, 0, 0, 0),
+            (8, 2, symtab_offset, symtab.len(), 3, 1, 24),
+            (16, 3, strtab_offset, strtab.len(), 0, 0, 0),
+            (24, 3, shstrtab_offset, shstrtab.len(), 0, 0, 0),
+        ];
+
+        for (name, kind, offset, size, link, info, entsize) in sections {
+            elf.extend_from_slice(&(name as u32).to_le_bytes());
+            elf.extend_from_slice(&(kind as u32).to_le_bytes());
+            elf.extend_from_slice(&0u64.to_le_bytes());
+            elf.extend_from_slice(&0u64.to_le_bytes());
+            elf.extend_from_slice(&(offset as u64).to_le_bytes());
+            elf.extend_from_slice(&(size as u64).to_le_bytes());
+            elf.extend_from_slice(&(link as u32).to_le_bytes());
+            elf.extend_from_slice(&(info as u32).to_le_bytes());
+            elf.extend_from_slice(&1u64.to_le_bytes());
+            elf.extend_from_slice(&(entsize as u64).to_le_bytes());
+        }
+
+        elf
+    }
+
+    fn table() -> Arc<DefmtTable> {
+        // SHN_ABS
+        let abs = 0xfff1;
+
+        let elf = elf_file(&[
+            ("_defmt_version_ = 4", abs, 0),
+            ("_defmt_encoding_ = raw", abs, 0),
+            (
+                r#"{"package":"app","tag":"defmt_info","data":"Hello {=u8}","disambiguator":"1","crate_name":"app"}"#,
+                1,
+                1,
+            ),
+        ]);
+
+        let table = Table::parse_ignore_version(&elf).unwrap().unwrap();
+
+        Arc::new(DefmtTable {
+            table,
+            locations: None,
+        })
+    }
+
+    #[test]
+    fn frames_split_over_reads() {
+        let mut decoder = DefmtDecoder::new(table());
+
+        // The index of the frame is split
+        decoder.received(&[1]);
+        assert_eq!(decoder.decode().unwrap(), None);
+
+        // The argument is missing
+        decoder.received(&[0]);
+        assert_eq!(decoder.decode().unwrap(), None);
+
+        // The rest of the frame, and the start of the next one
+        decoder.received(&[42, 1]);
+
+        let record = decoder.decode().unwrap().unwrap();
+        assert_eq!(record.index, 1);
+        assert_eq!(record.level.as_deref(), Some("info"));
+        assert_eq!(record.message, "Hello 42");
+        assert_eq!(record.to_string(), "INFO Hello 42");
+
+        assert_eq!(decoder.decode().unwrap(), None);
+
+        decoder.received(&[0, 7]);
+
+        let messages = decoder
+            .records()
+            .map(|record| record.unwrap().message)
+            .collect::<Vec<_>>();
+        assert_eq!(messages, ["Hello 7"]);
+    }
+}
@@ -52,6 +52,12 @@ pub use channels::Channels;
 mod rtt;
 pub use rtt::*;
 
+pub mod terminal;
+pub use terminal::VirtualTerminals;
+
+#[cfg(feature = "defmt")]
+pub mod defmt;
+
 /// Error type for RTT operations.
 #[derive(Error, Debug)]
 pub enum Error {
@@ -84,4 +90,9 @@ pub enum Error {
     /// Wraps errors propagated up from reading memory on the target.
     #[error("Unexpected error while reading {0} from target memory. Please report this as a bug.")]
     MemoryRead(String),
+
+    /// The defmt data couldn't be decoded. The data contains a detailed error.
+    #[cfg(feature = "defmt")]
+    #[error("Error decoding defmt data: {0}")]
+    Defmt(String),
 }
@@ -37,6 +37,9 @@ use std::ops::Range;
 #[derive(Debug)]
 pub struct Rtt {
     ptr: u32,
+    /// The numbers of up and down channels of the control block, to detect when the firmware
+    /// initializes it again.
+    max_channels: (u32, u32),
     up_channels: Channels<UpChannel>,
     down_channels: Channels<DownChannel>,
 }
@@ -66,6 +69,14 @@ impl Rtt {
     const O_MAX_DOWN_CHANNELS: usize = 20;
     const O_CHANNEL_ARRAYS: usize = 24;
 
+    /// The numbers of up and down channels in the control block `mem`.
+    fn max_channels(mem: &[u8]) -> (u32, u32) {
+        (
+            mem.pread_with(Self::O_MAX_UP_CHANNELS, LE).unwrap(),
+            mem.pread_with(Self::O_MAX_DOWN_CHANNELS, LE).unwrap(),
+        )
+    }
+
     fn from(
         core: &mut Core,
         memory_map: &[MemoryRegion],
@@ -155,6 +166,7 @@ impl Rtt {
 
         Ok(Some(Rtt {
             ptr,
+            max_channels: Self::max_channels(&mem),
             up_channels: Channels(up_channels),
             down_channels: Channels(down_channels),
         }))
@@ -203,6 +215,11 @@ impl Rtt {
 
                 vec![region.clone()]
             }
+            ScanRegion::Ranges(regions) => {
+                log::debug!("Scanning regions: {:?}", regions);
+
+                regions.clone()
+            }
         };
 
         let mut mem: Vec<u8> = Vec::new();
@@ -247,6 +264,31 @@ impl Rtt {
         Ok(instances.remove(0))
     }
 
+    /// Checks if the target was reset, or the firmware initialized the control block again, since
+    /// it was attached. The channels are no longer valid then, and RTT has to be attached again.
+    ///
+    /// A reset is detected with [`Core::reset_detected`], on the cores which support it. Of the
+    /// control block, only the ID and the numbers of channels are checked, so this is cheap enough
+    /// to be called before every read of the channels.
+    pub fn is_stale(&self, core: &mut Core) -> Result<bool, Error> {
+        if core.reset_detected()? {
+            log::debug!("The target was reset");
+            return Ok(true);
+        }
+
+        let mut mem = [0u8; Self::MIN_SIZE];
+        core.read(self.ptr.into(), &mut mem)?;
+
+        if mem[Self::O_ID..Self::O_ID + Self::RTT_ID.len()] != Self::RTT_ID
+            || Self::max_channels(&mem) != self.max_channels
+        {
+            log::debug!("The RTT control block at {:#010x} changed", self.ptr);
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
     /// Returns the memory address of the control block in target memory.
     pub fn ptr(&self) -> u32 {
         self.ptr
@@ -274,6 +316,10 @@ pub enum ScanRegion {
     /// that reading from this range will not read from undefined memory.
     Range(Range<u32>),
 
+    /// Limit scanning to these memory ranges in target memory, e.g. the RTT scan regions of the
+    /// target description.
+    Ranges(Vec<Range<u32>>),
+
     /// Tries to find the control block starting at this exact address. It is up to the user to
     /// ensure that reading the necessary bytes after the pointer will no read from undefined
     /// memory.
@@ -285,3 +331,42 @@ impl Default for ScanRegion {
         ScanRegion::Ram
     }
 }
+
+impl ScanRegion {
+    /// Scan the `ranges`, or all RAM if there are none, e.g. the `rtt_scan_regions` of a target.
+    ///
+    /// Ranges which aren't in the 32-bit address space are ignored.
+    pub fn from_ranges(ranges: &[Range<u64>]) -> Self {
+        let ranges = ranges
+            .iter()
+            .filter_map(|range| Some(range.start.try_into().ok()?..range.end.try_into().ok()?))
+            .collect::<Vec<Range<u32>>>();
+
+        if ranges.is_empty() {
+            ScanRegion::Ram
+        } else {
+            ScanRegion::Ranges(ranges)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ScanRegion;
+
+    #[test]
+    fn scan_region_from_ranges() {
+        assert!(matches!(ScanRegion::from_ranges(&[]), ScanRegion::Ram));
+
+        // Ranges outside of the 32-bit address space are ignored
+        assert!(matches!(
+            ScanRegion::from_ranges(&[0x1_0000_0000..0x1_0000_1000]),
+            ScanRegion::Ram
+        ));
+
+        match ScanRegion::from_ranges(&[0x2000_0000..0x2000_4000, 0x1_0000_0000..0x1_0000_1000]) {
+            ScanRegion::Ranges(ranges) => assert_eq!(ranges, [0x2000_0000..0x2000_4000]),
+            other => panic!("Unexpected scan region {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Demultiplexing of the SEGGER virtual terminals.
+//!
+//! The SEGGER RTT implementation can send the output of up to 16 virtual terminals over up channel
+//! 0. `SEGGER_RTT_SetTerminal` and `SEGGER_RTT_TerminalOut` switch to a terminal by writing the
+//! byte `0xFF` followed by the terminal id, `'0'` to `'9'` or `'A'` to `'F'`. All data after this
+//! escape sequence belongs to the new terminal, until the next one.
+
+/// The byte which starts the escape sequence switching the terminal.
+const ESCAPE: u8 = 0xFF;
+
+/// Splits the data of up channel 0 into the data of the virtual terminals.
+///
+/// An escape sequence can be split over two reads of the channel, so the same demultiplexer has to
+/// be used for all data of the channel.
+#[derive(Debug, Default)]
+pub struct VirtualTerminals {
+    terminal: u8,
+    escape: bool,
+    used: bool,
+}
+
+impl VirtualTerminals {
+    /// Create a demultiplexer, which starts with terminal 0 like the target.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The terminal which the following data belongs to.
+    pub fn terminal(&self) -> u8 {
+        self.terminal
+    }
+
+    /// Returns `true` if the target switched the terminal at least once.
+    ///
+    /// Firmware which doesn't use the virtual terminals only sends data for terminal 0.
+    pub fn is_used(&self) -> bool {
+        self.used
+    }
+
+    /// Split `data` read from the channel, and call `output` with the number of the terminal and its
+    /// data, for each part of `data` between the escape sequences.
+    ///
+    /// An escape byte which isn't followed by a valid terminal id is passed on as data.
+    pub fn demux(&mut self, data: &[u8], mut output: impl FnMut(u8, &[u8])) {
+        let mut start = 0;
+
+        for (index, &byte) in data.iter().enumerate() {
+            if self.escape {
+                self.escape = false;
+
+                match terminal_id(byte) {
+                    Some(terminal) => {
+                        self.terminal = terminal;
+                        self.used = true;
+                    }
+                    None if index == 0 => {
+                        // The escape byte was at the end of the previous data.
+                        output(self.terminal, &[ESCAPE]);
+                        continue;
+                    }
+                    None => {
+                        // Pass on the escape byte and this byte.
+                        start = index - 1;
+                        continue;
+                    }
+                }
+
+                start = index + 1;
+            } else if byte == ESCAPE {
+                if index > start {
+                    output(self.terminal, &data[start..index]);
+                }
+
+                self.escape = true;
+                start = index + 1;
+            }
+        }
+
+        if start < data.len() && !self.escape {
+            output(self.terminal, &data[start..]);
+        }
+    }
+}
+
+/// The number of the terminal with the id `byte`.
+fn terminal_id(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::VirtualTerminals;
+
+    fn demux(terminals: &mut VirtualTerminals, data: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut parts = vec![];
+        terminals.demux(data, |terminal, data| parts.push((terminal, data.to_vec())));
+        parts
+    }
+
+    #[test]
+    fn split_terminals() {
+        let mut terminals = VirtualTerminals::new();
+
+        assert_eq!(
+            demux(&mut terminals, b"boot\n\xFF1error\n\xFF0ok\n"),
+            vec![
+                (0, b"boot\n".to_vec()),
+                (1, b"error\n".to_vec()),
+                (0, b"ok\n".to_vec())
+            ]
+        );
+        assert!(terminals.is_used());
+
+        // The escape sequence is split over two reads.
+        assert_eq!(demux(&mut terminals, b"a\xFF"), vec![(0, b"a".to_vec())]);
+        assert_eq!(demux(&mut terminals, b"Fb"), vec![(15, b"b".to_vec())]);
+
+        // Invalid escape sequences are data.
+        assert_eq!(
+            demux(&mut terminals, b"c\xFFxd"),
+            vec![(15, b"c".to_vec()), (15, b"\xFFxd".to_vec())]
+        );
+    }
+}
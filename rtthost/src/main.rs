@@ -1,12 +1,21 @@
 use probe_rs::Permissions;
 use probe_rs::{config::TargetSelector, DebugProbeInfo, Probe};
-use probe_rs_rtt::{Channels, Rtt, RttChannel, ScanRegion};
+use probe_rs_rtt::{
+    ChannelMode, Channels, Rtt, RttChannel, ScanRegion, UpChannel, VirtualTerminals,
+};
 
 use clap::Parser;
 use std::io::prelude::*;
 use std::io::{stdin, stdout};
+use std::net::SocketAddr;
 use std::sync::mpsc::{channel, Receiver};
 use std::thread;
+use std::time::{Duration, Instant};
+
+mod tcp;
+
+/// How often the statistics of the up channel are printed.
+const STATS_INTERVAL: Duration = Duration::from_secs(5);
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 enum ProbeInfo {
@@ -92,6 +101,35 @@ struct Opts {
     )]
     down: Option<usize>,
 
+    #[clap(
+        short,
+        long,
+        help = "Change the mode of the up channel on the target to NoBlockSkip, NoBlockTrim or BlockIfFull. BlockIfFull doesn't lose data, but the target stalls while the buffer is full."
+    )]
+    mode: Option<ChannelMode>,
+
+    #[clap(
+        short,
+        long,
+        help = "Only output this SEGGER virtual terminal of up channel 0. The escape sequences switching the terminals are always removed."
+    )]
+    terminal: Option<u8>,
+
+    #[clap(
+        long,
+        help = "Print statistics of the up channel to stderr every 5 seconds. If the buffer was full, the target may have dropped data."
+    )]
+    stats: bool,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        default_missing_value = tcp::DEFAULT_ADDRESS,
+        conflicts_with_all = ["up", "down", "mode", "terminal", "stats"],
+        help = "Serve all RTT channels over TCP instead of stdin and stdout. Channel n is served on the port of the address plus n, by default channel 0 on 127.0.0.1:19021 like the J-Link RTT telnet server."
+    )]
+    tcp: Option<SocketAddr>,
+
     #[clap(
         long,
         default_value="",
@@ -161,6 +199,12 @@ fn run() -> i32 {
 
     let memory_map = session.target().memory_map.clone();
 
+    // Without an explicit region, the scan regions of the target are used, if it has any.
+    let scan_region = match &opts.scan_region {
+        ScanRegion::Ram => ScanRegion::from_ranges(&session.target().rtt_scan_regions),
+        scan_region => scan_region.clone(),
+    };
+
     let mut core = match session.core(0) {
         Ok(core) => core,
         Err(err) => {
@@ -171,7 +215,7 @@ fn run() -> i32 {
 
     eprintln!("Attaching to RTT...");
 
-    let mut rtt = match Rtt::attach_region(&mut core, &memory_map, &opts.scan_region) {
+    let mut rtt = match Rtt::attach_region(&mut core, &memory_map, &scan_region) {
         Ok(rtt) => rtt,
         Err(err) => {
             eprintln!("Error attaching to RTT: {}", err);
@@ -189,6 +233,16 @@ fn run() -> i32 {
         return 0;
     }
 
+    if let Some(address) = opts.tcp {
+        eprintln!("Found control block at 0x{:08x}", rtt.ptr());
+
+        if let Err(err) = tcp::serve(&mut core, &mut rtt, address) {
+            eprintln!("Error serving RTT over TCP: {}", err);
+        }
+
+        return 1;
+    }
+
     let up_channel = if let Some(up) = opts.up {
         let chan = rtt.up_channels().take(up);
 
@@ -202,6 +256,17 @@ fn run() -> i32 {
         rtt.up_channels().take(0)
     };
 
+    if let (Some(up_channel), Some(mode)) = (up_channel.as_ref(), opts.mode) {
+        if let Err(err) = up_channel.set_mode(&mut core, mode) {
+            eprintln!(
+                "Error changing the mode of up channel {}: {}",
+                up_channel.number(),
+                err
+            );
+            return 1;
+        }
+    }
+
     let down_channel = if let Some(down) = opts.down {
         let chan = rtt.down_channels().take(down);
 
@@ -221,6 +286,9 @@ fn run() -> i32 {
 
     let mut up_buf = [0u8; 1024];
     let mut down_buf = vec![];
+    let mut terminals = VirtualTerminals::new();
+    let mut output = vec![];
+    let mut last_stats = Instant::now();
 
     loop {
         if let Some(up_channel) = up_channel.as_ref() {
@@ -232,7 +300,18 @@ fn run() -> i32 {
                 }
             };
 
-            match stdout().write_all(&up_buf[..count]) {
+            output.clear();
+            if up_channel.number() == 0 {
+                terminals.demux(&up_buf[..count], |terminal, data| {
+                    if opts.terminal.map_or(true, |selected| selected == terminal) {
+                        output.extend_from_slice(data);
+                    }
+                });
+            } else {
+                output.extend_from_slice(&up_buf[..count]);
+            }
+
+            match stdout().write_all(&output) {
                 Ok(_) => {
                     stdout().flush().ok();
                 }
@@ -241,6 +320,11 @@ fn run() -> i32 {
                     return 1;
                 }
             }
+
+            if opts.stats && last_stats.elapsed() >= STATS_INTERVAL {
+                print_stats(up_channel);
+                last_stats = Instant::now();
+            }
         }
 
         if let (Some(down_channel), Some(stdin)) = (down_channel.as_ref(), &stdin) {
@@ -265,6 +349,22 @@ fn run() -> i32 {
     }
 }
 
+fn print_stats(up_channel: &UpChannel) {
+    let stats = up_channel.stats();
+
+    eprintln!(
+        "\nUp channel {}: {} bytes, {:.0} bytes/s, buffer full in {} of {} reads, up to {} of {} bytes used, up to {:?} between reads",
+        up_channel.number(),
+        stats.bytes,
+        stats.throughput().unwrap_or_default(),
+        stats.full_reads,
+        stats.reads,
+        stats.max_fill,
+        up_channel.buffer_size() - 1,
+        stats.max_poll_interval
+    );
+}
+
 fn list_probes(mut stream: impl std::io::Write, probes: &[DebugProbeInfo]) {
     writeln!(stream, "Available probes:").unwrap();
 
@@ -320,3 +420,27 @@ fn stdin_channel() -> Receiver<Vec<u8>> {
 
     rx
 }
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+
+    use super::Opts;
+
+    #[test]
+    fn tcp_conflicts_with_channel_options() {
+        assert!(Opts::try_parse_from(["rtthost", "--tcp"]).is_ok());
+        assert!(Opts::try_parse_from(["rtthost", "--tcp", "127.0.0.1:1234"]).is_ok());
+
+        for option in [
+            &["--up", "1"][..],
+            &["--down", "1"],
+            &["--mode", "BlockIfFull"],
+            &["--terminal", "1"],
+            &["--stats"],
+        ] {
+            let args = ["rtthost", "--tcp"].iter().chain(option);
+            assert!(Opts::try_parse_from(args).is_err(), "{:?}", option);
+        }
+    }
+}
@@ -0,0 +1,324 @@
+//! Serving the RTT channels over TCP, like the RTT telnet server of the J-Link software.
+//!
+//! Each channel number gets its own port, the port of the server address plus the channel number,
+//! so channel 0 is served on port 19021 with the default address, like by the J-Link software. The
+//! data of the up channel is sent to all clients of the port, and the data received from the
+//! clients is written to the down channel with the same number. No telnet negotiation is done, so
+//! plain TCP clients like netcat work as well.
+
+use std::collections::BTreeSet;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use probe_rs::Core;
+use probe_rs_rtt::{DownChannel, Rtt, UpChannel};
+
+/// The default address of the server, with the port of the J-Link RTT telnet server.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:19021";
+
+/// How long to wait before polling again, if there was nothing to do.
+const IDLE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How much data may be waiting for a client which doesn't receive it, before it is disconnected.
+const MAX_PENDING: usize = 64 * 1024;
+
+type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// A client connected to a port.
+///
+/// The socket is non-blocking, so a client which doesn't receive the data doesn't stall the other
+/// clients and channels. The data which couldn't be sent yet is kept until the next poll.
+struct Client {
+    stream: TcpStream,
+    address: SocketAddr,
+    /// The data of the up channel, which wasn't sent to the client yet.
+    pending: Vec<u8>,
+}
+
+impl Client {
+    /// Send as much of the pending data as possible without blocking.
+    fn flush(&mut self) -> std::io::Result<()> {
+        while !self.pending.is_empty() {
+            match self.stream.write(&self.pending) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(count) => {
+                    self.pending.drain(..count);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        if self.pending.len() > MAX_PENDING {
+            return Err(std::io::Error::new(
+                ErrorKind::TimedOut,
+                "the client doesn't receive the data",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// The port serving the up and the down channel with the same number.
+struct ChannelPort {
+    number: usize,
+    up: Option<UpChannel>,
+    down: Option<DownChannel>,
+    listener: TcpListener,
+    clients: Vec<Client>,
+    /// The data received from the clients, which wasn't written to the down channel yet.
+    down_buf: Vec<u8>,
+}
+
+impl ChannelPort {
+    /// Accept new clients, and pass on the data between the clients and the channels.
+    ///
+    /// The up channel is only read while a client is connected, so the data sent by the target
+    /// before, like the boot messages, stays in its buffer for the first client.
+    ///
+    /// Returns `true` if any data was transferred.
+    fn poll(&mut self, core: &mut Core, buf: &mut [u8]) -> Result<bool, Error> {
+        self.accept()?;
+
+        let mut active = false;
+
+        if let Some(up) = self.up.as_ref().filter(|_| !self.clients.is_empty()) {
+            let count = up.read(core, buf)?;
+
+            if count > 0 {
+                active = true;
+
+                for client in &mut self.clients {
+                    client.pending.extend_from_slice(&buf[..count]);
+                }
+            }
+        }
+
+        let number = self.number;
+        let down = self.down.is_some();
+        let down_buf = &mut self.down_buf;
+
+        self.clients.retain_mut(|client| {
+            let result = client.flush().and_then(|()| loop {
+                match client.stream.read(buf) {
+                    Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                    Ok(count) => {
+                        active = true;
+
+                        // Without a down channel, the data is discarded.
+                        if down {
+                            down_buf.extend_from_slice(&buf[..count]);
+                        }
+                    }
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                    Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                    Err(err) => return Err(err),
+                }
+            });
+
+            match result {
+                Ok(()) => true,
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
+                    eprintln!(
+                        "Client {} disconnected from channel {}",
+                        client.address, number
+                    );
+                    false
+                }
+                Err(err) => {
+                    eprintln!(
+                        "Disconnecting client {} of channel {}: {}",
+                        client.address, number, err
+                    );
+                    false
+                }
+            }
+        });
+
+        if let Some(down) = &self.down {
+            if !self.down_buf.is_empty() {
+                let count = down.write(core, &self.down_buf)?;
+                self.down_buf.drain(..count);
+            }
+        }
+
+        Ok(active)
+    }
+
+    fn accept(&mut self) -> Result<(), Error> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, address)) => {
+                    eprintln!("Client {} connected to channel {}", address, self.number);
+
+                    stream.set_nonblocking(true)?;
+                    stream.set_nodelay(true)?;
+                    self.clients.push(Client {
+                        stream,
+                        address,
+                        pending: vec![],
+                    });
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+/// Serve all channels of `rtt` over TCP, until an error occurs.
+pub fn serve(core: &mut Core, rtt: &mut Rtt, address: SocketAddr) -> Result<(), Error> {
+    let numbers = rtt
+        .up_channels()
+        .iter()
+        .map(|channel| channel.number())
+        .chain(rtt.down_channels().iter().map(|channel| channel.number()))
+        .collect::<BTreeSet<_>>();
+
+    let mut ports = vec![];
+    let mut buf_size = 1024;
+
+    for number in numbers {
+        let port = u16::try_from(address.port() as usize + number)
+            .map_err(|_| format!("There is no port for channel {}", number))?;
+        let address = SocketAddr::new(address.ip(), port);
+
+        let up = rtt.up_channels().take(number);
+        let down = rtt.down_channels().take(number);
+
+        let listener = TcpListener::bind(address)
+            .map_err(|err| format!("Failed to listen on {}: {}", address, err))?;
+        listener.set_nonblocking(true)?;
+
+        let name = up
+            .as_ref()
+            .and_then(|up| up.name())
+            .or_else(|| down.as_ref().and_then(|down| down.name()))
+            .unwrap_or("(no name)");
+        eprintln!("Serving channel {} {} on {}", number, name, address);
+
+        if let Some(up) = &up {
+            buf_size = buf_size.max(up.buffer_size());
+        }
+
+        ports.push(ChannelPort {
+            number,
+            up,
+            down,
+            listener,
+            clients: vec![],
+            down_buf: vec![],
+        });
+    }
+
+    let mut buf = vec![0; buf_size];
+
+    loop {
+        let mut active = false;
+
+        for port in &mut ports {
+            active |= port.poll(core, &mut buf)?;
+        }
+
+        if !active {
+            thread::sleep(IDLE_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use std::time::Duration;
+
+    use probe_rs::{Core, FakeProbe, MemoryInterface, Permissions};
+    use probe_rs_rtt::{Rtt, ScanRegion};
+
+    use super::{ChannelPort, IDLE_INTERVAL};
+
+    /// Poll `port` until `done` returns `true`.
+    fn poll_until(
+        port: &mut ChannelPort,
+        core: &mut Core,
+        mut done: impl FnMut(&mut ChannelPort, &mut Core) -> bool,
+    ) {
+        let mut buf = [0; 64];
+
+        for _ in 0..100 {
+            port.poll(core, &mut buf).unwrap();
+
+            if done(port, core) {
+                return;
+            }
+
+            thread::sleep(IDLE_INTERVAL);
+        }
+
+        panic!("Timeout while polling the port");
+    }
+
+    #[test]
+    fn loopback() {
+        let mut session = FakeProbe::with_mocked_memory(vec![0; 0x400])
+            .into_probe()
+            .attach("nrf51822_xxAC", Permissions::default())
+            .unwrap();
+        let mut core = session.core(0).unwrap();
+
+        // 1 up channel with its buffer at 0x200, and 1 down channel with its buffer at 0x300.
+        let mut control_block = b"SEGGER RTT\0\0\0\0\0\0".to_vec();
+        for value in [1u32, 1, 0, 0x200, 64, 5, 0, 0, 0, 0x300, 64, 0, 0, 0] {
+            control_block.extend_from_slice(&value.to_le_bytes());
+        }
+        core.write_8(0x200, b"boot\n").unwrap();
+        core.write_8(0x100, &control_block).unwrap();
+
+        let mut rtt = Rtt::attach_region(&mut core, &[], &ScanRegion::Exact(0x100)).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let mut port = ChannelPort {
+            number: 0,
+            up: rtt.up_channels().take(0),
+            down: rtt.down_channels().take(0),
+            listener,
+            clients: vec![],
+            down_buf: vec![],
+        };
+
+        // Without a client, the data stays in the buffer of the target.
+        assert!(!port.poll(&mut core, &mut [0; 64]).unwrap());
+        assert_eq!(core.read_word_32(0x128).unwrap(), 0);
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+
+        poll_until(&mut port, &mut core, |port, _| !port.clients.is_empty());
+
+        let mut data = [0; 5];
+        client.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"boot\n");
+
+        client.write_all(b"hello").unwrap();
+        poll_until(&mut port, &mut core, |_, core| {
+            core.read_word_32(0x13c).unwrap() == 5
+        });
+
+        let mut data = [0; 5];
+        core.read_8(0x300, &mut data).unwrap();
+        assert_eq!(&data, b"hello");
+
+        drop(client);
+        poll_until(&mut port, &mut core, |port, _| port.clients.is_empty());
+    }
+}
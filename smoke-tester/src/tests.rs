@@ -3,7 +3,7 @@ use std::{path::Path, time::Instant};
 use colored::Colorize;
 use probe_rs::{
     config::MemoryRegion,
-    flashing::{download_file_with_options, DownloadOptions, FlashProgress, Format},
+    flashing::{download_file_with_options, DownloadOptions, ElfOptions, FlashProgress, Format},
     Architecture, Core, MemoryInterface, Session,
 };
 
@@ -171,7 +171,12 @@ pub fn test_flashing(
 
     let start_time = Instant::now();
 
-    download_file_with_options(session, test_binary, Format::Elf, options)?;
+    download_file_with_options(
+        session,
+        test_binary,
+        Format::Elf(ElfOptions::default()),
+        options,
+    )?;
 
     println!();
 
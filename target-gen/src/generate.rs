@@ -11,7 +11,10 @@ use probe_rs::config::{
     RawFlashAlgorithm,
 };
 use probe_rs::{Architecture, CoreType};
-use probe_rs_target::{ArmCoreAccessOptions, CoreAccessOptions, RiscvCoreAccessOptions};
+use probe_rs_target::{
+    ArmCoreAccessOptions, CoreAccessOptions, RiscvCoreAccessOptions, SwjSequences,
+    XtensaCoreAccessOptions,
+};
 use tokio::runtime::Builder;
 
 pub(crate) enum Kind<'a, T>
@@ -143,6 +146,8 @@ where
             cores,
             memory_map: get_mem_map(&device),
             flash_algorithms: flash_algorithm_names,
+            swj_sequences: SwjSequences::default(),
+            rtt_scan_regions: vec![],
         });
     }
 
@@ -165,7 +170,10 @@ fn create_core(processor: &Processor) -> Result<ProbeCore> {
                 debug_base: None,
                 cti_base: None,
             }),
-            Architecture::Riscv => CoreAccessOptions::Riscv(RiscvCoreAccessOptions {}),
+            Architecture::Riscv => CoreAccessOptions::Riscv(RiscvCoreAccessOptions { hart_id: 0 }),
+            Architecture::Xtensa => {
+                CoreAccessOptions::Xtensa(XtensaCoreAccessOptions { debug_level: 6 })
+            }
         },
     })
 }
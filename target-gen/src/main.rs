@@ -1,6 +1,4 @@
-pub mod algorithm_binary;
 pub mod fetch;
-pub mod flash_device;
 pub mod generate;
 pub mod parser;
 
@@ -14,7 +12,7 @@ use probe_rs::{
     },
     CoreType,
 };
-use probe_rs_target::{ArmCoreAccessOptions, CoreAccessOptions};
+use probe_rs_target::{ArmCoreAccessOptions, CoreAccessOptions, SwjSequences};
 use simplelog::*;
 use std::{
     env::current_dir,
@@ -207,6 +205,8 @@ fn cmd_elf(
                     }),
                 ],
                 flash_algorithms: vec![algorithm_name],
+                swj_sequences: SwjSequences::default(),
+                rtt_scan_regions: vec![],
             }],
             flash_algorithms: vec![algorithm],
             source: BuiltIn,